@@ -0,0 +1,158 @@
+//! Measures the scaling of PES demuxing with `TsPacketSplitter` on a multi-program stream.
+//!
+//! Each PES packet is checksummed by the worker that assembled it,
+//! which stands in for the per-packet work done by applications.
+//!
+//! Run with `cargo bench --bench demux`.
+use mpeg2ts::crc::crc32_mpeg;
+use mpeg2ts::es::{StreamId, StreamType};
+use mpeg2ts::pes::{PesHeader, PesPacketReader, ReadPesPacket};
+use mpeg2ts::ts::payload::{Bytes, Pat, Pes, Pmt};
+use mpeg2ts::ts::{
+    ContinuityCounter, EsInfo, Pid, ProgramAssociation, TransportScramblingControl, TsHeader,
+    TsPacket, TsPacketReader, TsPacketSplitter, TsPacketWriter, TsPayload, VersionNumber,
+    WriteTsPacket,
+};
+use std::time::{Duration, Instant};
+
+const PROGRAMS: u16 = 8;
+const PES_PACKETS_PER_PROGRAM: usize = 1_000;
+const CONTINUATION_PACKETS: usize = 20;
+const ITERATIONS: u32 = 3;
+
+fn packet(pid: u16, payload_unit_start_indicator: bool, payload: TsPayload) -> TsPacket {
+    TsPacket {
+        header: TsHeader {
+            transport_error_indicator: false,
+            payload_unit_start_indicator,
+            transport_priority: false,
+            pid: Pid::new(pid).unwrap(),
+            transport_scrambling_control: TransportScramblingControl::NotScrambled,
+            continuity_counter: ContinuityCounter::new(),
+        },
+        adaptation_field: None,
+        payload: Some(payload),
+        arrival_timestamp: None,
+    }
+}
+
+fn es_pid(program: u16) -> u16 {
+    256 + program
+}
+
+fn make_stream() -> Vec<u8> {
+    let pat = Pat {
+        transport_stream_id: 1,
+        version_number: VersionNumber::new(),
+        is_current: true,
+        table: (0..PROGRAMS)
+            .map(|i| ProgramAssociation {
+                program_num: i + 1,
+                program_map_pid: Pid::new(0x1000 + i).unwrap(),
+            })
+            .collect(),
+        raw: None,
+    };
+    let pes_header = PesHeader::new(StreamId::new(0xE0));
+
+    let mut writer = TsPacketWriter::new(Vec::new());
+    writer
+        .write_ts_packet(&packet(0, true, TsPayload::Pat(pat)))
+        .unwrap();
+    for i in 0..PROGRAMS {
+        let pmt = Pmt {
+            program_num: i + 1,
+            pcr_pid: None,
+            version_number: VersionNumber::new(),
+            is_current: true,
+            program_info: vec![],
+            es_info: vec![EsInfo {
+                stream_type: StreamType::H264,
+                elementary_pid: Pid::new(es_pid(i)).unwrap(),
+                descriptors: vec![],
+            }],
+            raw: None,
+        };
+        writer
+            .write_ts_packet(&packet(0x1000 + i, true, TsPayload::Pmt(pmt)))
+            .unwrap();
+    }
+
+    let mut counters = vec![ContinuityCounter::new(); usize::from(PROGRAMS)];
+    for _ in 0..PES_PACKETS_PER_PROGRAM {
+        for j in 0..=CONTINUATION_PACKETS {
+            for (i, counter) in (0..PROGRAMS).zip(&mut counters) {
+                let mut packet = if j == 0 {
+                    let pes = Pes {
+                        header: pes_header.clone(),
+                        pes_packet_len: 0,
+                        data: Bytes::new(&[i as u8; 170]).unwrap(),
+                    };
+                    packet(es_pid(i), true, TsPayload::Pes(pes))
+                } else {
+                    let data = Bytes::new(&[i as u8; Bytes::MAX_SIZE]).unwrap();
+                    packet(es_pid(i), false, TsPayload::Raw(data))
+                };
+                packet.header.continuity_counter = *counter;
+                counter.increment();
+                writer.write_ts_packet(&packet).unwrap();
+            }
+        }
+    }
+    writer.into_stream()
+}
+
+fn demux<R: ReadPesPacket>(mut reader: R) -> u32 {
+    let mut checksum = 0;
+    while let Some(packet) = reader.read_pes_packet().unwrap() {
+        checksum ^= crc32_mpeg(&packet.data);
+    }
+    checksum
+}
+
+fn single_thread(bytes: &[u8]) -> u32 {
+    demux(PesPacketReader::new(TsPacketReader::new(bytes)))
+}
+
+fn split(bytes: &[u8], shards: usize) -> u32 {
+    let (splitter, readers) = TsPacketSplitter::new(TsPacketReader::new(bytes), shards, |pid| {
+        usize::from(pid.as_u16())
+    })
+    .unwrap();
+    std::thread::scope(|scope| {
+        let workers = readers
+            .into_iter()
+            .map(|reader| scope.spawn(move || demux(PesPacketReader::new(reader))))
+            .collect::<Vec<_>>();
+        splitter.run().unwrap();
+        workers
+            .into_iter()
+            .fold(0, |checksum, worker| checksum ^ worker.join().unwrap())
+    })
+}
+
+fn measure<F: FnMut() -> u32>(name: &str, packets: u64, mut f: F) -> u32 {
+    let mut elapsed = Duration::ZERO;
+    let mut checksum = 0;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        checksum = std::hint::black_box(f());
+        elapsed += start.elapsed();
+    }
+    let elapsed = elapsed / ITERATIONS;
+    let mbps = (packets * TsPacket::SIZE as u64 * 8) as f64 / elapsed.as_secs_f64() / 1_000_000.0;
+    println!("{:<20} {:>10.2?} {:>10.1} Mbps", name, elapsed, mbps);
+    checksum
+}
+
+fn main() {
+    let bytes = make_stream();
+    let packets = (bytes.len() / TsPacket::SIZE) as u64;
+
+    let expected = measure("single thread", packets, || single_thread(&bytes));
+    for shards in [1, 2, 4, 8] {
+        let name = format!("{} shard(s)", shards);
+        let checksum = measure(&name, packets, || split(&bytes, shards));
+        assert_eq!(checksum, expected);
+    }
+}
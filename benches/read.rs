@@ -0,0 +1,159 @@
+//! Measures the throughput of `TsPacketReader` and `PesPacketReader`.
+//!
+//! The input stream is read through an unbuffered reader that counts `read` calls,
+//! which corresponds to the number of system calls when reading from a `File` or a socket.
+//!
+//! Run with `cargo bench --bench read`.
+use mpeg2ts::es::{StreamId, StreamType};
+use mpeg2ts::pes::{PesHeader, PesPacketReader, ReadPesPacket};
+use mpeg2ts::ts::payload::{Bytes, Pat, Pes, Pmt};
+use mpeg2ts::ts::{
+    ContinuityCounter, EsInfo, Pid, ProgramAssociation, ReadTsPacket, TransportScramblingControl,
+    TsHeader, TsPacket, TsPacketReader, TsPacketWriter, TsPayload, VersionNumber, WriteTsPacket,
+};
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+const PES_PACKETS: usize = 5_000;
+const CONTINUATION_PACKETS: usize = 20;
+const ITERATIONS: u32 = 5;
+
+struct CountingReader<'a> {
+    bytes: &'a [u8],
+    read_calls: u64,
+}
+impl Read for CountingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.read_calls += 1;
+        self.bytes.read(buf)
+    }
+}
+
+fn packet(pid: u16, payload_unit_start_indicator: bool, payload: TsPayload) -> TsPacket {
+    TsPacket {
+        header: TsHeader {
+            transport_error_indicator: false,
+            payload_unit_start_indicator,
+            transport_priority: false,
+            pid: Pid::new(pid).unwrap(),
+            transport_scrambling_control: TransportScramblingControl::NotScrambled,
+            continuity_counter: ContinuityCounter::new(),
+        },
+        adaptation_field: None,
+        payload: Some(payload),
+        arrival_timestamp: None,
+    }
+}
+
+fn make_stream() -> Vec<u8> {
+    let pat = Pat {
+        transport_stream_id: 1,
+        version_number: VersionNumber::new(),
+        is_current: true,
+        table: vec![ProgramAssociation {
+            program_num: 1,
+            program_map_pid: Pid::new(0x1000).unwrap(),
+        }],
+        raw: None,
+    };
+    let pmt = Pmt {
+        program_num: 1,
+        pcr_pid: None,
+        version_number: VersionNumber::new(),
+        is_current: true,
+        program_info: vec![],
+        es_info: vec![EsInfo {
+            stream_type: StreamType::H264,
+            elementary_pid: Pid::new(256).unwrap(),
+            descriptors: vec![],
+        }],
+        raw: None,
+    };
+    let pes_header = PesHeader::new(StreamId::new(0xE0));
+
+    let mut writer = TsPacketWriter::new(Vec::new());
+    writer
+        .write_ts_packet(&packet(0, true, TsPayload::Pat(pat)))
+        .unwrap();
+    writer
+        .write_ts_packet(&packet(0x1000, true, TsPayload::Pmt(pmt)))
+        .unwrap();
+    let mut counter = ContinuityCounter::new();
+    for _ in 0..PES_PACKETS {
+        let pes = Pes {
+            header: pes_header.clone(),
+            pes_packet_len: 0,
+            data: Bytes::new(&[0; 170]).unwrap(),
+        };
+        let mut start = packet(256, true, TsPayload::Pes(pes));
+        start.header.continuity_counter = counter;
+        counter.increment();
+        writer.write_ts_packet(&start).unwrap();
+        for _ in 0..CONTINUATION_PACKETS {
+            let data = Bytes::new(&[0; Bytes::MAX_SIZE]).unwrap();
+            let mut continuation = packet(256, false, TsPayload::Raw(data));
+            continuation.header.continuity_counter = counter;
+            counter.increment();
+            writer.write_ts_packet(&continuation).unwrap();
+        }
+    }
+    writer.into_stream()
+}
+
+fn report(name: &str, elapsed: Duration, packets: u64, read_calls: u64) {
+    let mbps = (packets * TsPacket::SIZE as u64 * 8) as f64 / elapsed.as_secs_f64() / 1_000_000.0;
+    println!(
+        "{:<20} {:>10.2?} {:>10.1} Mbps {:>6.2} reads/packet",
+        name,
+        elapsed,
+        mbps,
+        read_calls as f64 / packets as f64
+    );
+}
+
+fn main() {
+    let bytes = make_stream();
+    let packets = (bytes.len() / TsPacket::SIZE) as u64;
+
+    let mut elapsed = Duration::ZERO;
+    let mut read_calls = 0;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        let mut reader = TsPacketReader::new(CountingReader {
+            bytes: &bytes,
+            read_calls: 0,
+        });
+        while let Some(packet) = reader.read_ts_packet().unwrap() {
+            std::hint::black_box(packet);
+        }
+        elapsed += start.elapsed();
+        read_calls += reader.stream().read_calls;
+    }
+    report(
+        "TsPacketReader",
+        elapsed / ITERATIONS,
+        packets,
+        read_calls / u64::from(ITERATIONS),
+    );
+
+    let mut elapsed = Duration::ZERO;
+    let mut read_calls = 0;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        let mut reader = PesPacketReader::new(TsPacketReader::new(CountingReader {
+            bytes: &bytes,
+            read_calls: 0,
+        }));
+        while let Some(packet) = reader.read_pes_packet().unwrap() {
+            std::hint::black_box(packet);
+        }
+        elapsed += start.elapsed();
+        read_calls += reader.ts_packet_reader().stream().read_calls;
+    }
+    report(
+        "PesPacketReader",
+        elapsed / ITERATIONS,
+        packets,
+        read_calls / u64::from(ITERATIONS),
+    );
+}
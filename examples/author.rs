@@ -0,0 +1,50 @@
+use clap::Parser;
+use mpeg2ts::ts::{EsMuxer, TsPacketWriter};
+use std::error::Error;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Writes a single-program TS made from an H.264 Annex-B file and an ADTS AAC file to the standard output.
+#[derive(Debug, Parser)]
+struct Args {
+    /// H.264 Annex-B byte stream file.
+    #[arg(long)]
+    video: Option<PathBuf>,
+
+    /// ADTS AAC file.
+    #[arg(long)]
+    audio: Option<PathBuf>,
+
+    /// Numerator of the video frame rate.
+    #[arg(long, default_value_t = 30)]
+    fps_num: u32,
+
+    /// Denominator of the video frame rate.
+    #[arg(long, default_value_t = 1)]
+    fps_den: u32,
+
+    /// Interval of the PAT and PMT in milliseconds.
+    #[arg(long, default_value_t = 100)]
+    psi_interval_ms: u64,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let video = args
+        .video
+        .map(std::fs::read)
+        .transpose()?
+        .unwrap_or_default();
+    let audio = args
+        .audio
+        .map(std::fs::read)
+        .transpose()?
+        .unwrap_or_default();
+
+    let mut muxer = EsMuxer::new(args.fps_num, args.fps_den);
+    muxer.set_psi_interval(std::time::Duration::from_millis(args.psi_interval_ms));
+    let mut writer = TsPacketWriter::new(std::io::BufWriter::new(std::io::stdout()));
+    muxer.mux(&video, &audio, &mut writer)?;
+    writer.into_stream().flush()?;
+    Ok(())
+}
@@ -1,15 +1,233 @@
-use trackable::error::{ErrorKind as TrackableErrorKind, TrackableError};
+use crate::io;
+#[cfg(not(feature = "trackable"))]
+use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+use core::fmt;
+
+/// Shared I/O error kept as the source of an `Error` (`std::io::Error` is not `Clone`).
+#[cfg(all(feature = "std", not(feature = "trackable")))]
+type IoErrorSource = std::sync::Arc<io::Error>;
+#[cfg(not(feature = "std"))]
+type IoErrorSource = io::Error;
 
 /// This crate specific `Error` type.
-#[derive(Debug, Clone, trackable::TrackableError)]
-pub struct Error(TrackableError<ErrorKind>);
+///
+/// The kind of an error is available via `Error::kind`,
+/// and the underlying I/O error (if any) via `Error::io_error` (or `std::error::Error::source`).
+///
+/// If the `trackable` feature is enabled, this is a newtype of `trackable::error::TrackableError`
+/// that also records the locations the error has passed through.
+#[cfg(not(feature = "trackable"))]
+#[derive(Debug, Clone)]
+pub struct Error {
+    kind: ErrorKind,
+    messages: Vec<String>,
+    source: Option<IoErrorSource>,
+}
+#[cfg(not(feature = "trackable"))]
+impl Error {
+    /// Returns the kind of the error.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Returns the underlying I/O error (if any).
+    pub fn io_error(&self) -> Option<&io::Error> {
+        self.source.as_ref().map(core::borrow::Borrow::borrow)
+    }
+
+    pub(crate) fn with_message(kind: ErrorKind, message: String) -> Self {
+        Error {
+            kind,
+            messages: vec![message],
+            source: None,
+        }
+    }
+
+    /// Changes the kind of the error, keeping its messages and source.
+    pub(crate) fn with_kind(mut self, kind: ErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+}
+#[cfg(not(feature = "trackable"))]
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.kind)?;
+        for (i, message) in self.messages.iter().enumerate() {
+            let separator = if i == 0 { ": " } else { "; " };
+            write!(f, "{}{}", separator, message)?;
+        }
+        Ok(())
+    }
+}
+#[cfg(all(feature = "std", not(feature = "trackable")))]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_ref().map(|e| &**e as _)
+    }
+}
+#[cfg(not(feature = "trackable"))]
+impl From<ErrorKind> for Error {
+    fn from(f: ErrorKind) -> Self {
+        Error {
+            kind: f,
+            messages: Vec::new(),
+            source: None,
+        }
+    }
+}
+#[cfg(not(feature = "trackable"))]
+impl From<io::Error> for Error {
+    fn from(f: io::Error) -> Self {
+        Error {
+            kind: ErrorKind::Other,
+            messages: vec!["I/O error".to_owned()],
+            source: Some(IoErrorSource::from(f)),
+        }
+    }
+}
+
+/// Adds a context message to an error (the counterpart of `trackable::Trackable` used by `track!`).
+#[cfg(not(feature = "trackable"))]
+pub(crate) trait Track {
+    fn track<F: FnOnce() -> String>(self, f: F) -> Self;
+}
+#[cfg(not(feature = "trackable"))]
+impl Track for Error {
+    fn track<F: FnOnce() -> String>(mut self, f: F) -> Self {
+        self.messages.push(f());
+        self
+    }
+}
+#[cfg(not(feature = "trackable"))]
+impl<T> Track for Result<T, Error> {
+    fn track<F: FnOnce() -> String>(self, f: F) -> Self {
+        self.map_err(|e| e.track(f))
+    }
+}
+
+/// This crate specific `Error` type.
+///
+/// The kind of an error is available via `Error::kind`,
+/// and the underlying I/O error (if any) via `std::error::Error::source`.
+#[cfg(feature = "trackable")]
+#[derive(Debug, Clone)]
+pub struct Error(trackable::error::TrackableError<ErrorKind>);
+#[cfg(feature = "trackable")]
+impl core::ops::Deref for Error {
+    type Target = trackable::error::TrackableError<ErrorKind>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+#[cfg(feature = "trackable")]
+impl Error {
+    /// Returns the underlying I/O error (if any).
+    pub fn io_error(&self) -> Option<&io::Error> {
+        self.0.concrete_cause::<io::Error>()
+    }
+
+    /// Changes the kind of the error, keeping its history and cause.
+    pub(crate) fn with_kind(self, kind: ErrorKind) -> Self {
+        use trackable::error::ErrorKindExt;
+        Error(kind.takes_over(self.0))
+    }
+}
+#[cfg(feature = "trackable")]
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+#[cfg(feature = "trackable")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        // `TrackableError` does not implement `source`, so the cause is downcasted instead
+        self.0
+            .concrete_cause::<io::Error>()
+            .map(|e| e as &(dyn std::error::Error + 'static))
+    }
+}
+#[cfg(feature = "trackable")]
+impl trackable::Trackable for Error {
+    type Event = trackable::Location;
+    fn history(&self) -> Option<&trackable::History<Self::Event>> {
+        self.0.history()
+    }
+    fn history_mut(&mut self) -> Option<&mut trackable::History<Self::Event>> {
+        self.0.history_mut()
+    }
+}
+#[cfg(feature = "trackable")]
+impl From<trackable::error::TrackableError<ErrorKind>> for Error {
+    fn from(f: trackable::error::TrackableError<ErrorKind>) -> Self {
+        Error(f)
+    }
+}
+#[cfg(feature = "trackable")]
+impl From<Error> for trackable::error::TrackableError<ErrorKind> {
+    fn from(f: Error) -> Self {
+        f.0
+    }
+}
+#[cfg(feature = "trackable")]
+impl From<ErrorKind> for Error {
+    fn from(f: ErrorKind) -> Self {
+        use trackable::error::ErrorKindExt;
+        Error(f.error())
+    }
+}
+#[cfg(feature = "trackable")]
+impl From<io::Error> for Error {
+    fn from(f: io::Error) -> Self {
+        use trackable::error::ErrorKindExt;
+        Error(ErrorKind::Other.cause(f))
+    }
+}
 
 /// Possible error kinds.
+///
+/// New kinds may be added in future releases.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[allow(missing_docs)]
+#[non_exhaustive]
 pub enum ErrorKind {
     InvalidInput,
     Unsupported,
+    ContinuityError,
+    CrcMismatch,
+    SizeLimitExceeded,
+    TruncatedPacket,
     Other,
 }
-impl TrackableErrorKind for ErrorKind {}
+#[cfg(feature = "trackable")]
+impl trackable::error::ErrorKind for ErrorKind {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn io_error() {
+        let e = Error::from(io::Error::new(io::ErrorKind::UnexpectedEof, "eof"));
+        assert_eq!(*e.kind(), ErrorKind::Other);
+        assert_eq!(e.io_error().unwrap().kind(), io::ErrorKind::UnexpectedEof);
+
+        let e = Error::from(ErrorKind::InvalidInput);
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+        assert!(e.io_error().is_none());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn source() {
+        use std::error::Error as _;
+
+        let e = Error::from(io::Error::new(io::ErrorKind::UnexpectedEof, "eof"));
+        let source = e.source().unwrap().downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::UnexpectedEof);
+
+        let e = Error::from(ErrorKind::InvalidInput);
+        assert!(e.source().is_none());
+    }
+}
@@ -0,0 +1,390 @@
+//! AC-3 and E-AC-3 (Dolby Digital and Dolby Digital Plus) syncframes.
+//!
+//! # References
+//!
+//! - ETSI TS 102 366
+use crate::{ErrorKind, Result};
+
+const SYNC_WORD: u16 = 0x0B77;
+
+// Nominal bitrates in kbps indexed by `frmsizecod / 2`
+const BITRATES: [u32; 19] = [
+    32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 448, 512, 576, 640,
+];
+
+const SAMPLE_RATES: [u32; 3] = [48000, 44100, 32000];
+
+const REDUCED_SAMPLE_RATES: [u32; 3] = [24000, 22050, 16000];
+
+/// Type specific fields of a syncframe.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameType {
+    /// AC-3 syncframe.
+    Ac3 {
+        /// Frame size code (6 bits).
+        frmsizecod: u8,
+
+        /// Bit stream mode (3 bits).
+        bsmod: u8,
+    },
+
+    /// E-AC-3 syncframe.
+    Eac3 {
+        /// Stream type (2 bits).
+        strmtyp: u8,
+
+        /// Substream identification (3 bits).
+        substreamid: u8,
+
+        /// Frame size in 16-bit words minus one (11 bits).
+        frmsiz: u16,
+
+        /// Sample rate code 2 (present if `fscod` is `3`).
+        fscod2: Option<u8>,
+
+        /// Number of audio blocks code (2 bits, `3` if `fscod2` is present).
+        numblkscod: u8,
+    },
+}
+
+/// The fields at the beginning of an AC-3 or E-AC-3 syncframe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SyncInfo {
+    /// Type specific fields.
+    pub frame_type: FrameType,
+
+    /// Bit stream identification (5 bits, `16` for E-AC-3).
+    pub bsid: u8,
+
+    /// Sample rate code (2 bits).
+    pub fscod: u8,
+
+    /// Audio coding mode (3 bits).
+    pub acmod: u8,
+
+    /// `true` indicates that the low frequency effects channel is present.
+    pub lfeon: bool,
+}
+impl SyncInfo {
+    /// Parses the fields at the beginning of a syncframe.
+    ///
+    /// If `bytes` is too short to contain the fields, it will return an `ErrorKind::TruncatedPacket` error.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut r = BitReader::new(bytes);
+        let sync_word = track!(r.read(16))? as u16;
+        track_assert_eq!(
+            sync_word,
+            SYNC_WORD,
+            ErrorKind::InvalidInput,
+            "Unexpected AC-3 sync word"
+        );
+        let bsid = bytes.get(5).map_or(0, |b| b >> 3);
+        if bsid > 10 {
+            track!(Self::parse_eac3(r))
+        } else {
+            track!(Self::parse_ac3(r))
+        }
+    }
+
+    fn parse_ac3(mut r: BitReader) -> Result<Self> {
+        let _crc1 = track!(r.read(16))?;
+        let fscod = track!(r.read(2))? as u8;
+        let frmsizecod = track!(r.read(6))? as u8;
+        track_assert_ne!(fscod, 3, ErrorKind::InvalidInput, "Reserved AC-3 fscod");
+        track_assert!(
+            usize::from(frmsizecod / 2) < BITRATES.len(),
+            ErrorKind::InvalidInput,
+            "Unknown AC-3 frmsizecod: {}",
+            frmsizecod
+        );
+
+        let bsid = track!(r.read(5))? as u8;
+        let bsmod = track!(r.read(3))? as u8;
+        let acmod = track!(r.read(3))? as u8;
+        if (acmod & 0b001) != 0 && acmod != 0b001 {
+            let _cmixlev = track!(r.read(2))?;
+        }
+        if (acmod & 0b100) != 0 {
+            let _surmixlev = track!(r.read(2))?;
+        }
+        if acmod == 0b010 {
+            let _dsurmod = track!(r.read(2))?;
+        }
+        let lfeon = track!(r.read(1))? != 0;
+        Ok(SyncInfo {
+            frame_type: FrameType::Ac3 { frmsizecod, bsmod },
+            bsid,
+            fscod,
+            acmod,
+            lfeon,
+        })
+    }
+
+    fn parse_eac3(mut r: BitReader) -> Result<Self> {
+        let strmtyp = track!(r.read(2))? as u8;
+        let substreamid = track!(r.read(3))? as u8;
+        let frmsiz = track!(r.read(11))? as u16;
+        let fscod = track!(r.read(2))? as u8;
+        let (fscod2, numblkscod) = if fscod == 3 {
+            let fscod2 = track!(r.read(2))? as u8;
+            track_assert_ne!(fscod2, 3, ErrorKind::InvalidInput, "Reserved E-AC-3 fscod2");
+            (Some(fscod2), 3)
+        } else {
+            (None, track!(r.read(2))? as u8)
+        };
+        let acmod = track!(r.read(3))? as u8;
+        let lfeon = track!(r.read(1))? != 0;
+        let bsid = track!(r.read(5))? as u8;
+        track_assert!(
+            bsid <= 16,
+            ErrorKind::Unsupported,
+            "Unsupported E-AC-3 bsid: {}",
+            bsid
+        );
+        Ok(SyncInfo {
+            frame_type: FrameType::Eac3 {
+                strmtyp,
+                substreamid,
+                frmsiz,
+                fscod2,
+                numblkscod,
+            },
+            bsid,
+            fscod,
+            acmod,
+            lfeon,
+        })
+    }
+
+    /// Returns `true` if it is an E-AC-3 syncframe, otherwise `false`.
+    pub fn is_eac3(&self) -> bool {
+        matches!(self.frame_type, FrameType::Eac3 { .. })
+    }
+
+    /// Returns the size of the syncframe in bytes.
+    pub fn frame_size(&self) -> usize {
+        match self.frame_type {
+            FrameType::Ac3 { frmsizecod, .. } => {
+                let kbps = BITRATES[usize::from(frmsizecod / 2)];
+                let words = match self.fscod {
+                    0 => kbps * 2,
+                    1 => kbps * 320 / 147 + u32::from(frmsizecod & 1),
+                    _ => kbps * 3,
+                };
+                words as usize * 2
+            }
+            FrameType::Eac3 { frmsiz, .. } => (usize::from(frmsiz) + 1) * 2,
+        }
+    }
+
+    /// Returns the sample rate in Hz.
+    pub fn sample_rate(&self) -> u32 {
+        match self.frame_type {
+            FrameType::Eac3 {
+                fscod2: Some(fscod2),
+                ..
+            } => REDUCED_SAMPLE_RATES[usize::from(fscod2)],
+            _ => SAMPLE_RATES[usize::from(self.fscod)],
+        }
+    }
+
+    /// Returns the number of audio samples per channel in the syncframe (256 per audio block).
+    pub fn samples(&self) -> usize {
+        let blocks = match self.frame_type {
+            FrameType::Ac3 { .. } => 6,
+            FrameType::Eac3 { numblkscod, .. } => [1, 2, 3, 6][usize::from(numblkscod)],
+        };
+        blocks * 256
+    }
+
+    /// Returns the number of the channels including the low frequency effects channel.
+    pub fn channels(&self) -> u8 {
+        let full_bandwidth = [2, 1, 2, 3, 3, 4, 4, 5][usize::from(self.acmod)];
+        full_bandwidth + u8::from(self.lfeon)
+    }
+}
+
+/// AC-3 or E-AC-3 syncframe.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SyncFrame<'a> {
+    /// The fields at the beginning of the syncframe.
+    pub info: SyncInfo,
+
+    /// Bytes of the whole syncframe (including the sync word).
+    pub bytes: &'a [u8],
+}
+
+/// Iterator over the syncframes in a byte slice (e.g., the data of a PES packet).
+///
+/// If the last syncframe is truncated (e.g., it continues in the next PES packet),
+/// the last item is an `ErrorKind::TruncatedPacket` error,
+/// and the bytes of the truncated syncframe are available via `remaining`.
+#[derive(Debug, Clone)]
+pub struct SyncFrames<'a> {
+    bytes: &'a [u8],
+    failed: bool,
+}
+impl<'a> SyncFrames<'a> {
+    /// Makes a new `SyncFrames` instance.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        SyncFrames {
+            bytes,
+            failed: false,
+        }
+    }
+
+    /// Returns the bytes that have not been consumed yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    fn next_frame(&mut self) -> Result<Option<SyncFrame<'a>>> {
+        if self.bytes.is_empty() {
+            return Ok(None);
+        }
+        let info = track!(SyncInfo::parse(self.bytes))?;
+        let frame_size = info.frame_size();
+        track_assert!(
+            frame_size <= self.bytes.len(),
+            ErrorKind::TruncatedPacket,
+            "Truncated AC-3 syncframe: size={}, frame_size={}",
+            self.bytes.len(),
+            frame_size
+        );
+
+        let (bytes, rest) = self.bytes.split_at(frame_size);
+        self.bytes = rest;
+        Ok(Some(SyncFrame { info, bytes }))
+    }
+}
+impl<'a> Iterator for SyncFrames<'a> {
+    type Item = Result<SyncFrame<'a>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.next_frame().transpose();
+        if let Some(Err(_)) = result {
+            self.failed = true;
+        }
+        result
+    }
+}
+impl core::iter::FusedIterator for SyncFrames<'_> {}
+
+#[derive(Debug)]
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    bit_offset: usize,
+}
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader {
+            bytes,
+            bit_offset: 0,
+        }
+    }
+
+    fn read(&mut self, bits: usize) -> Result<u32> {
+        track_assert!(
+            self.bit_offset + bits <= self.bytes.len() * 8,
+            ErrorKind::TruncatedPacket,
+            "Truncated AC-3 syncframe header: size={}",
+            self.bytes.len()
+        );
+        let mut n = 0;
+        for _ in 0..bits {
+            let b = self.bytes[self.bit_offset / 8] >> (7 - self.bit_offset % 8);
+            n = (n << 1) | u32::from(b & 1);
+            self.bit_offset += 1;
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ac3() {
+        // 48 kHz, 384 kbps, 3/2 + LFE
+        let mut frame = vec![0x0B, 0x77, 0x12, 0x34, 0x1C, 0x08, 0xE1, 0x00];
+        frame.resize(1536, 0);
+        let info = track_try_unwrap!(SyncInfo::parse(&frame));
+        assert_eq!(
+            info.frame_type,
+            FrameType::Ac3 {
+                frmsizecod: 28,
+                bsmod: 0
+            }
+        );
+        assert_eq!(info.bsid, 1);
+        assert_eq!(info.acmod, 7);
+        assert!(info.lfeon);
+        assert!(!info.is_eac3());
+        assert_eq!(info.frame_size(), 1536);
+        assert_eq!(info.sample_rate(), 48000);
+        assert_eq!(info.samples(), 1536);
+        assert_eq!(info.channels(), 6);
+
+        // 44.1 kHz frames have an extra word if frmsizecod is odd
+        frame[4] = 0b0100_0001;
+        let info = track_try_unwrap!(SyncInfo::parse(&frame));
+        assert_eq!(info.frame_size(), 70 * 2);
+        assert_eq!(info.sample_rate(), 44100);
+
+        assert!(SyncInfo::parse(&[0x0B, 0x78, 0, 0, 0, 0, 0, 0]).is_err());
+        let e = SyncInfo::parse(&frame[..5]).err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::TruncatedPacket);
+    }
+
+    #[test]
+    fn eac3() {
+        // Independent substream, 1024 bytes, 48 kHz, 6 blocks, 2/0
+        let mut frame = vec![0x0B, 0x77, 0x01, 0xFF, 0x34, 0x80];
+        frame.resize(1024, 0);
+        let info = track_try_unwrap!(SyncInfo::parse(&frame));
+        assert_eq!(
+            info.frame_type,
+            FrameType::Eac3 {
+                strmtyp: 0,
+                substreamid: 0,
+                frmsiz: 511,
+                fscod2: None,
+                numblkscod: 3
+            }
+        );
+        assert_eq!(info.bsid, 16);
+        assert_eq!(info.acmod, 2);
+        assert!(!info.lfeon);
+        assert!(info.is_eac3());
+        assert_eq!(info.frame_size(), 1024);
+        assert_eq!(info.sample_rate(), 48000);
+        assert_eq!(info.samples(), 1536);
+        assert_eq!(info.channels(), 2);
+
+        // Reduced sample rate (fscod = 3, fscod2 = 1)
+        frame[4] = 0b1101_0100;
+        let info = track_try_unwrap!(SyncInfo::parse(&frame));
+        assert_eq!(info.sample_rate(), 22050);
+        assert_eq!(info.samples(), 1536);
+    }
+
+    #[test]
+    fn sync_frames() {
+        let mut frame = vec![0x0B, 0x77, 0x01, 0xFF, 0x34, 0x80];
+        frame.resize(1024, 0);
+        let mut bytes = frame.clone();
+        bytes.extend(&frame[..100]);
+
+        let mut frames = SyncFrames::new(&bytes);
+        let f = track_try_unwrap!(frames.next().unwrap());
+        assert_eq!(f.bytes.len(), 1024);
+        let e = frames.next().unwrap().err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::TruncatedPacket);
+        assert_eq!(frames.remaining().len(), 100);
+        assert!(frames.next().is_none());
+    }
+}
@@ -0,0 +1,325 @@
+//! ADTS (Audio Data Transport Stream) used to carry AAC frames (`StreamType::AdtsAac`).
+//!
+//! # References
+//!
+//! - ISO/IEC 13818-7
+//! - [ADTS](https://wiki.multimedia.cx/index.php/ADTS)
+use crate::io::{Read, ReadBytesExt, Write, WriteBytesExt};
+use crate::{ErrorKind, Result};
+use byteorder::BigEndian;
+
+const SYNC_WORD: u64 = 0xFFF;
+
+const SAMPLING_FREQUENCIES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+/// ADTS frame header.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AdtsHeader {
+    /// `true` indicates MPEG-2 AAC, and `false` indicates MPEG-4 AAC.
+    pub mpeg2: bool,
+
+    /// MPEG-4 audio object type minus one (2 bits, e.g., `1` for AAC-LC).
+    pub profile: u8,
+
+    /// Index of the sampling frequency (4 bits, see `sampling_frequency`).
+    pub sampling_frequency_index: u8,
+
+    pub private_bit: bool,
+
+    /// Channel configuration (3 bits).
+    ///
+    /// `0` means that the configuration is sent in the raw data (via a program config element).
+    pub channel_configuration: u8,
+
+    pub original_or_copy: bool,
+    pub home: bool,
+    pub copyright_identification_bit: bool,
+    pub copyright_identification_start: bool,
+
+    /// Length of the frame including the header (13 bits).
+    pub frame_length: u16,
+
+    /// Buffer fullness (11 bits, `0x7FF` indicates variable bitrate).
+    pub buffer_fullness: u16,
+
+    /// Number of the raw data blocks in the frame minus one (2 bits).
+    pub number_of_raw_data_blocks: u8,
+
+    /// CRC of the frame (present if `protection_absent` is `0`).
+    pub crc: Option<u16>,
+}
+impl AdtsHeader {
+    /// Size of the header without CRC.
+    pub const MIN_SIZE: usize = 7;
+
+    /// Size of the header with CRC.
+    pub const MAX_SIZE: usize = 9;
+
+    /// Returns the size of the header.
+    pub fn header_len(&self) -> usize {
+        if self.crc.is_some() {
+            Self::MAX_SIZE
+        } else {
+            Self::MIN_SIZE
+        }
+    }
+
+    /// Returns the size of the raw data following the header.
+    pub fn data_len(&self) -> usize {
+        usize::from(self.frame_length).saturating_sub(self.header_len())
+    }
+
+    /// Returns the sampling frequency in Hz.
+    ///
+    /// It will return `None` if `sampling_frequency_index` is reserved.
+    pub fn sampling_frequency(&self) -> Option<u32> {
+        SAMPLING_FREQUENCIES
+            .get(usize::from(self.sampling_frequency_index))
+            .copied()
+    }
+
+    /// Returns the number of audio samples per channel in the frame (1024 per raw data block).
+    pub fn samples(&self) -> usize {
+        (usize::from(self.number_of_raw_data_blocks) + 1) * 1024
+    }
+
+    /// Reads an ADTS header.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+        let n = track_io!(reader.read_uint::<BigEndian>(7))?;
+        track_assert_eq!(
+            n >> 44,
+            SYNC_WORD,
+            ErrorKind::InvalidInput,
+            "Unexpected ADTS sync word"
+        );
+        let layer = (n >> 41) & 0b11;
+        track_assert_eq!(layer, 0, ErrorKind::InvalidInput, "Unexpected ADTS layer");
+
+        let protection_absent = (n >> 40) & 1 != 0;
+        let mut header = AdtsHeader {
+            mpeg2: (n >> 43) & 1 != 0,
+            profile: ((n >> 38) & 0b11) as u8,
+            sampling_frequency_index: ((n >> 34) & 0b1111) as u8,
+            private_bit: (n >> 33) & 1 != 0,
+            channel_configuration: ((n >> 30) & 0b111) as u8,
+            original_or_copy: (n >> 29) & 1 != 0,
+            home: (n >> 28) & 1 != 0,
+            copyright_identification_bit: (n >> 27) & 1 != 0,
+            copyright_identification_start: (n >> 26) & 1 != 0,
+            frame_length: ((n >> 13) & 0x1FFF) as u16,
+            buffer_fullness: ((n >> 2) & 0x7FF) as u16,
+            number_of_raw_data_blocks: (n & 0b11) as u8,
+            crc: None,
+        };
+        if !protection_absent {
+            header.crc = Some(track_io!(reader.read_u16::<BigEndian>())?);
+        }
+        track_assert!(
+            usize::from(header.frame_length) >= header.header_len(),
+            ErrorKind::InvalidInput,
+            "Too small ADTS frame length: {}",
+            header.frame_length
+        );
+        Ok(header)
+    }
+
+    /// Writes the ADTS header.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        track_assert!(self.profile < 4, ErrorKind::InvalidInput);
+        track_assert!(self.sampling_frequency_index < 16, ErrorKind::InvalidInput);
+        track_assert!(self.channel_configuration < 8, ErrorKind::InvalidInput);
+        track_assert!(self.frame_length < 0x2000, ErrorKind::InvalidInput);
+        track_assert!(
+            usize::from(self.frame_length) >= self.header_len(),
+            ErrorKind::InvalidInput
+        );
+        track_assert!(self.buffer_fullness < 0x800, ErrorKind::InvalidInput);
+        track_assert!(self.number_of_raw_data_blocks < 4, ErrorKind::InvalidInput);
+
+        let n = (SYNC_WORD << 44)
+            | (u64::from(self.mpeg2) << 43)
+            | (u64::from(self.crc.is_none()) << 40)
+            | (u64::from(self.profile) << 38)
+            | (u64::from(self.sampling_frequency_index) << 34)
+            | (u64::from(self.private_bit) << 33)
+            | (u64::from(self.channel_configuration) << 30)
+            | (u64::from(self.original_or_copy) << 29)
+            | (u64::from(self.home) << 28)
+            | (u64::from(self.copyright_identification_bit) << 27)
+            | (u64::from(self.copyright_identification_start) << 26)
+            | (u64::from(self.frame_length) << 13)
+            | (u64::from(self.buffer_fullness) << 2)
+            | u64::from(self.number_of_raw_data_blocks);
+        track_io!(writer.write_uint::<BigEndian>(n, 7))?;
+        if let Some(crc) = self.crc {
+            track_io!(writer.write_u16::<BigEndian>(crc))?;
+        }
+        Ok(())
+    }
+}
+
+/// ADTS frame.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AdtsFrame<'a> {
+    /// Header of the frame.
+    pub header: AdtsHeader,
+
+    /// Raw data following the header.
+    pub data: &'a [u8],
+}
+
+/// Iterator over the ADTS frames in a byte slice (e.g., the data of a PES packet).
+///
+/// If the last frame is truncated (e.g., it continues in the next PES packet),
+/// the last item is an `ErrorKind::TruncatedPacket` error,
+/// and the bytes of the truncated frame are available via `remaining`.
+/// If the slice does not start with an ADTS frame, the last item is an `ErrorKind::InvalidInput` error.
+#[derive(Debug, Clone)]
+pub struct AdtsFrames<'a> {
+    bytes: &'a [u8],
+    failed: bool,
+}
+impl<'a> AdtsFrames<'a> {
+    /// Makes a new `AdtsFrames` instance.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        AdtsFrames {
+            bytes,
+            failed: false,
+        }
+    }
+
+    /// Returns the bytes that have not been consumed yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    fn next_frame(&mut self) -> Result<Option<AdtsFrame<'a>>> {
+        if self.bytes.is_empty() {
+            return Ok(None);
+        }
+        let header_len = if self.bytes.len() >= 2 && self.bytes[1] & 1 == 0 {
+            AdtsHeader::MAX_SIZE
+        } else {
+            AdtsHeader::MIN_SIZE
+        };
+        track_assert!(
+            self.bytes.len() >= header_len,
+            ErrorKind::TruncatedPacket,
+            "Truncated ADTS header: size={}",
+            self.bytes.len()
+        );
+
+        let header = track!(AdtsHeader::read_from(self.bytes))?;
+        let frame_length = usize::from(header.frame_length);
+        track_assert!(
+            frame_length <= self.bytes.len(),
+            ErrorKind::TruncatedPacket,
+            "Truncated ADTS frame: size={}, frame_length={}",
+            self.bytes.len(),
+            frame_length
+        );
+
+        let data = &self.bytes[header.header_len()..frame_length];
+        self.bytes = &self.bytes[frame_length..];
+        Ok(Some(AdtsFrame { header, data }))
+    }
+}
+impl<'a> Iterator for AdtsFrames<'a> {
+    type Item = Result<AdtsFrame<'a>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.next_frame().transpose();
+        if let Some(Err(_)) = result {
+            self.failed = true;
+        }
+        result
+    }
+}
+impl core::iter::FusedIterator for AdtsFrames<'_> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header(frame_length: u16, crc: Option<u16>) -> AdtsHeader {
+        AdtsHeader {
+            mpeg2: false,
+            profile: 1,
+            sampling_frequency_index: 4,
+            private_bit: false,
+            channel_configuration: 2,
+            original_or_copy: false,
+            home: false,
+            copyright_identification_bit: false,
+            copyright_identification_start: false,
+            frame_length,
+            buffer_fullness: 0x7FF,
+            number_of_raw_data_blocks: 0,
+            crc,
+        }
+    }
+
+    #[test]
+    fn adts_header() {
+        // AAC-LC, 44.1 kHz, stereo, 371 bytes
+        let bytes = [0xFF, 0xF1, 0x50, 0x80, 0x2E, 0x7F, 0xFC];
+        let h = track_try_unwrap!(AdtsHeader::read_from(&bytes[..]));
+        assert_eq!(h, header(371, None));
+        assert_eq!(h.sampling_frequency(), Some(44100));
+        assert_eq!(h.header_len(), 7);
+        assert_eq!(h.data_len(), 364);
+
+        let mut buf = Vec::new();
+        track_try_unwrap!(h.write_to(&mut buf));
+        assert_eq!(buf, bytes);
+
+        // With CRC
+        let h = header(100, Some(0x1234));
+        let mut buf = Vec::new();
+        track_try_unwrap!(h.write_to(&mut buf));
+        assert_eq!(buf.len(), 9);
+        assert_eq!(buf[1] & 1, 0);
+        assert_eq!(track_try_unwrap!(AdtsHeader::read_from(&buf[..])), h);
+
+        assert!(AdtsHeader::read_from(&[0xFF, 0xE1, 0, 0, 0, 0, 0][..]).is_err());
+    }
+
+    #[test]
+    fn adts_frames() {
+        let mut bytes = Vec::new();
+        track_try_unwrap!(header(10, None).write_to(&mut bytes));
+        bytes.extend([1; 3]);
+        track_try_unwrap!(header(12, Some(0)).write_to(&mut bytes));
+        bytes.extend([2; 3]);
+        let len = bytes.len();
+        track_try_unwrap!(header(20, None).write_to(&mut bytes));
+        bytes.extend([3; 5]); // truncated
+
+        let mut frames = AdtsFrames::new(&bytes);
+        let frame = track_try_unwrap!(frames.next().unwrap());
+        assert_eq!(frame.data, [1; 3]);
+        let frame = track_try_unwrap!(frames.next().unwrap());
+        assert_eq!(frame.header.crc, Some(0));
+        assert_eq!(frame.data, [2; 3]);
+        let e = frames.next().unwrap().err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::TruncatedPacket);
+        assert_eq!(frames.remaining(), &bytes[len..]);
+        assert!(frames.next().is_none());
+
+        // Truncated in the header
+        let mut frames = AdtsFrames::new(&bytes[len..len + 5]);
+        let e = frames.next().unwrap().err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::TruncatedPacket);
+
+        // Not an ADTS frame
+        let mut frames = AdtsFrames::new(&[0; 10]);
+        let e = frames.next().unwrap().err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+        assert!(frames.next().is_none());
+    }
+}
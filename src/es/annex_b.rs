@@ -0,0 +1,40 @@
+/// Iterator over the NAL units in an Annex-B byte stream (ITU-T H.264 Annex B, ITU-T H.265 Annex B).
+///
+/// The NAL units are separated by 3-byte (`00 00 01`) or 4-byte (`00 00 00 01`) start codes.
+/// The bytes before the first start code and the trailing zero bytes of each unit are discarded.
+#[derive(Debug, Clone)]
+pub(crate) struct AnnexBUnits<'a> {
+    bytes: &'a [u8],
+}
+impl<'a> AnnexBUnits<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        let bytes = match find_start_code(bytes) {
+            Some(i) => &bytes[i + 3..],
+            None => &[],
+        };
+        AnnexBUnits { bytes }
+    }
+}
+impl<'a> Iterator for AnnexBUnits<'a> {
+    type Item = &'a [u8];
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.bytes.is_empty() {
+            let (unit, rest) = match find_start_code(self.bytes) {
+                Some(i) => (&self.bytes[..i], &self.bytes[i + 3..]),
+                None => (self.bytes, &[][..]),
+            };
+            self.bytes = rest;
+
+            let len = unit.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+            if len > 0 {
+                return Some(&unit[..len]);
+            }
+        }
+        None
+    }
+}
+impl core::iter::FusedIterator for AnnexBUnits<'_> {}
+
+fn find_start_code(bytes: &[u8]) -> Option<usize> {
+    bytes.windows(3).position(|w| w == [0, 0, 1])
+}
@@ -0,0 +1,174 @@
+//! H.264/AVC NAL units carried in Annex-B byte streams (`StreamType::H264`).
+//!
+//! # References
+//!
+//! - ITU-T Rec. H.264
+use crate::es::annex_b::AnnexBUnits;
+
+/// H.264 NAL unit type (5 bits).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NalUnitType(u8);
+impl NalUnitType {
+    /// Coded slice of a non-IDR picture.
+    pub const NON_IDR_SLICE: Self = NalUnitType(1);
+
+    /// Coded slice data partition A.
+    pub const SLICE_DATA_PARTITION_A: Self = NalUnitType(2);
+
+    /// Coded slice of an IDR picture.
+    pub const IDR_SLICE: Self = NalUnitType(5);
+
+    /// Supplemental enhancement information.
+    pub const SEI: Self = NalUnitType(6);
+
+    /// Sequence parameter set.
+    pub const SPS: Self = NalUnitType(7);
+
+    /// Picture parameter set.
+    pub const PPS: Self = NalUnitType(8);
+
+    /// Access unit delimiter.
+    pub const AUD: Self = NalUnitType(9);
+
+    /// End of sequence.
+    pub const END_OF_SEQUENCE: Self = NalUnitType(10);
+
+    /// End of stream.
+    pub const END_OF_STREAM: Self = NalUnitType(11);
+
+    /// Filler data.
+    pub const FILLER_DATA: Self = NalUnitType(12);
+
+    /// Makes a new `NalUnitType` instance.
+    ///
+    /// The upper three bits of `n` are ignored.
+    pub fn new(n: u8) -> Self {
+        NalUnitType(n & 0b1_1111)
+    }
+
+    /// Returns the value of the type.
+    pub fn as_u8(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns `true` if it is a type of the coded slices (including data partitions), otherwise `false`.
+    pub fn is_slice(&self) -> bool {
+        (1..=5).contains(&self.0)
+    }
+}
+
+/// H.264 NAL unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NalUnit<'a> {
+    bytes: &'a [u8],
+}
+impl<'a> NalUnit<'a> {
+    /// Makes a new `NalUnit` instance from the bytes of a NAL unit (without the start code).
+    ///
+    /// It will return `None` if `bytes` is empty.
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        if bytes.is_empty() {
+            None
+        } else {
+            Some(NalUnit { bytes })
+        }
+    }
+
+    /// Returns the bytes of the NAL unit including the header.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns the bytes following the NAL unit header.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.bytes[1..]
+    }
+
+    /// Returns the `nal_ref_idc` field (2 bits).
+    pub fn nal_ref_idc(&self) -> u8 {
+        (self.bytes[0] >> 5) & 0b11
+    }
+
+    /// Returns the type of the NAL unit.
+    pub fn nal_unit_type(&self) -> NalUnitType {
+        NalUnitType::new(self.bytes[0])
+    }
+
+    /// Returns `true` if it is a coded slice of an IDR picture, otherwise `false`.
+    pub fn is_idr(&self) -> bool {
+        self.nal_unit_type() == NalUnitType::IDR_SLICE
+    }
+
+    /// Returns `true` if it is an access unit delimiter, otherwise `false`.
+    pub fn is_access_unit_delimiter(&self) -> bool {
+        self.nal_unit_type() == NalUnitType::AUD
+    }
+}
+
+/// Iterator over the NAL units in an Annex-B byte stream (e.g., the data of a PES packet).
+///
+/// Both 3-byte and 4-byte start codes are accepted.
+/// The bytes before the first start code and the trailing zero bytes of each NAL unit are discarded.
+#[derive(Debug, Clone)]
+pub struct NalUnits<'a> {
+    inner: AnnexBUnits<'a>,
+}
+impl<'a> NalUnits<'a> {
+    /// Makes a new `NalUnits` instance.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        NalUnits {
+            inner: AnnexBUnits::new(bytes),
+        }
+    }
+}
+impl<'a> Iterator for NalUnits<'a> {
+    type Item = NalUnit<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().and_then(NalUnit::new)
+    }
+}
+impl core::iter::FusedIterator for NalUnits<'_> {}
+
+/// Returns `true` if the given Annex-B byte stream contains a coded slice of an IDR picture, otherwise `false`.
+pub fn contains_idr(bytes: &[u8]) -> bool {
+    NalUnits::new(bytes).any(|nal| nal.is_idr())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nal_units() {
+        let bytes = [
+            0, 0, 0, 1, 0x09, 0xF0, // AUD (4-byte start code)
+            0, 0, 1, 0x67, 0x64, 0x00, 0x1F, // SPS
+            0, 0, 1, 0x68, 0xEE, 0x3C, 0x80, 0, 0, // PPS with trailing zero bytes
+            0, 0, 0, 1, 0x65, 0x88, 0x84, 0x00, // IDR
+        ];
+        let units = NalUnits::new(&bytes).collect::<Vec<_>>();
+        let types = units.iter().map(|n| n.nal_unit_type()).collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            [
+                NalUnitType::AUD,
+                NalUnitType::SPS,
+                NalUnitType::PPS,
+                NalUnitType::IDR_SLICE
+            ]
+        );
+        assert!(units[0].is_access_unit_delimiter());
+        assert_eq!(units[1].nal_ref_idc(), 3);
+        assert_eq!(units[2].as_bytes(), [0x68, 0xEE, 0x3C, 0x80]);
+        assert_eq!(units[3].payload(), [0x88, 0x84]);
+        assert!(contains_idr(&bytes));
+
+        let bytes = [0xAB, 0, 0, 1, 0x41, 0x9A, 0, 0, 1];
+        let units = NalUnits::new(&bytes).collect::<Vec<_>>();
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].nal_unit_type(), NalUnitType::NON_IDR_SLICE);
+        assert!(!contains_idr(&bytes));
+
+        assert_eq!(NalUnits::new(&[0, 0, 0]).count(), 0);
+    }
+}
@@ -0,0 +1,234 @@
+//! H.265/HEVC NAL units carried in Annex-B byte streams (`StreamType::H265`).
+//!
+//! # References
+//!
+//! - ITU-T Rec. H.265
+use crate::es::annex_b::AnnexBUnits;
+
+/// H.265 NAL unit type (6 bits).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NalUnitType(u8);
+impl NalUnitType {
+    /// Coded slice of a non-TSA, non-STSA trailing picture (non-reference).
+    pub const TRAIL_N: Self = NalUnitType(0);
+
+    /// Coded slice of a non-TSA, non-STSA trailing picture (reference).
+    pub const TRAIL_R: Self = NalUnitType(1);
+
+    /// Coded slice of a BLA picture that may have leading pictures.
+    pub const BLA_W_LP: Self = NalUnitType(16);
+
+    /// Coded slice of a BLA picture that may have RADL pictures.
+    pub const BLA_W_RADL: Self = NalUnitType(17);
+
+    /// Coded slice of a BLA picture without leading pictures.
+    pub const BLA_N_LP: Self = NalUnitType(18);
+
+    /// Coded slice of an IDR picture that may have RADL pictures.
+    pub const IDR_W_RADL: Self = NalUnitType(19);
+
+    /// Coded slice of an IDR picture without leading pictures.
+    pub const IDR_N_LP: Self = NalUnitType(20);
+
+    /// Coded slice of a CRA picture.
+    pub const CRA: Self = NalUnitType(21);
+
+    /// Video parameter set.
+    pub const VPS: Self = NalUnitType(32);
+
+    /// Sequence parameter set.
+    pub const SPS: Self = NalUnitType(33);
+
+    /// Picture parameter set.
+    pub const PPS: Self = NalUnitType(34);
+
+    /// Access unit delimiter.
+    pub const AUD: Self = NalUnitType(35);
+
+    /// End of sequence.
+    pub const END_OF_SEQUENCE: Self = NalUnitType(36);
+
+    /// End of bitstream.
+    pub const END_OF_BITSTREAM: Self = NalUnitType(37);
+
+    /// Filler data.
+    pub const FILLER_DATA: Self = NalUnitType(38);
+
+    /// Prefix supplemental enhancement information.
+    pub const PREFIX_SEI: Self = NalUnitType(39);
+
+    /// Suffix supplemental enhancement information.
+    pub const SUFFIX_SEI: Self = NalUnitType(40);
+
+    /// Makes a new `NalUnitType` instance.
+    ///
+    /// The upper two bits of `n` are ignored.
+    pub fn new(n: u8) -> Self {
+        NalUnitType(n & 0b11_1111)
+    }
+
+    /// Returns the value of the type.
+    pub fn as_u8(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns `true` if it is a type of the coded slices (VCL NAL units), otherwise `false`.
+    pub fn is_slice(&self) -> bool {
+        self.0 < 32
+    }
+
+    /// Returns `true` if it is a type of the IRAP (intra random access point) pictures, otherwise `false`.
+    ///
+    /// The IRAP pictures are BLA, IDR and CRA pictures (including the reserved types 22 and 23).
+    pub fn is_irap(&self) -> bool {
+        (16..=23).contains(&self.0)
+    }
+
+    /// Returns `true` if it is a type of the IDR pictures, otherwise `false`.
+    pub fn is_idr(&self) -> bool {
+        *self == Self::IDR_W_RADL || *self == Self::IDR_N_LP
+    }
+}
+
+/// H.265 NAL unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NalUnit<'a> {
+    bytes: &'a [u8],
+}
+impl<'a> NalUnit<'a> {
+    /// Makes a new `NalUnit` instance from the bytes of a NAL unit (without the start code).
+    ///
+    /// It will return `None` if `bytes` is shorter than the two-byte NAL unit header.
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        if bytes.len() < 2 {
+            None
+        } else {
+            Some(NalUnit { bytes })
+        }
+    }
+
+    /// Returns the bytes of the NAL unit including the header.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns the bytes following the NAL unit header.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.bytes[2..]
+    }
+
+    /// Returns the type of the NAL unit.
+    pub fn nal_unit_type(&self) -> NalUnitType {
+        NalUnitType::new(self.bytes[0] >> 1)
+    }
+
+    /// Returns the `nuh_layer_id` field (6 bits).
+    pub fn nuh_layer_id(&self) -> u8 {
+        ((self.bytes[0] & 0b1) << 5) | (self.bytes[1] >> 3)
+    }
+
+    /// Returns the temporal identifier (i.e., `nuh_temporal_id_plus1` minus one).
+    pub fn temporal_id(&self) -> u8 {
+        (self.bytes[1] & 0b111).saturating_sub(1)
+    }
+
+    /// Returns `true` if it is a coded slice of an IRAP picture, otherwise `false`.
+    pub fn is_irap(&self) -> bool {
+        self.nal_unit_type().is_irap()
+    }
+
+    /// Returns `true` if it is an access unit delimiter, otherwise `false`.
+    pub fn is_access_unit_delimiter(&self) -> bool {
+        self.nal_unit_type() == NalUnitType::AUD
+    }
+}
+
+/// Iterator over the NAL units in an Annex-B byte stream (e.g., the data of a PES packet).
+///
+/// Both 3-byte and 4-byte start codes are accepted.
+/// The bytes before the first start code and the trailing zero bytes of each NAL unit are discarded.
+#[derive(Debug, Clone)]
+pub struct NalUnits<'a> {
+    inner: AnnexBUnits<'a>,
+}
+impl<'a> NalUnits<'a> {
+    /// Makes a new `NalUnits` instance.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        NalUnits {
+            inner: AnnexBUnits::new(bytes),
+        }
+    }
+}
+impl<'a> Iterator for NalUnits<'a> {
+    type Item = NalUnit<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.by_ref().find_map(NalUnit::new)
+    }
+}
+impl core::iter::FusedIterator for NalUnits<'_> {}
+
+/// Returns `true` if the given Annex-B byte stream contains a coded slice of an IRAP picture, otherwise `false`.
+///
+/// A segment or a random access can start at an access unit that contains an IRAP picture.
+pub fn contains_irap(bytes: &[u8]) -> bool {
+    NalUnits::new(bytes).any(|nal| nal.is_irap())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Synthesized beginning of the data of a PES packet that starts a closed GOP
+    // (AUD, VPS, SPS, PPS, prefix SEI and the first bytes of an IDR_W_RADL slice).
+    // The NAL units follow the H.265 syntax, but are not taken from an encoder output.
+    const IDR_PES_DATA: &[u8] = &[
+        0x00, 0x00, 0x00, 0x01, 0x46, 0x01, 0x10, // AUD
+        0x00, 0x00, 0x00, 0x01, 0x40, 0x01, 0x0C, 0x01, 0xFF, 0xFF, 0x01, 0x60, 0x00, 0x00, 0x03,
+        0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00, 0x03, 0x00, 0x5D, 0x95, 0x98, 0x09, // VPS
+        0x00, 0x00, 0x00, 0x01, 0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00,
+        0x00, 0x03, 0x00, 0x00, 0x03, 0x00, 0x5D, 0xA0, 0x02, 0x80, 0x80, 0x2D, 0x16, 0x59, 0x59,
+        0xA4, 0x93, 0x2B, 0xC0, 0x40, // SPS
+        0x00, 0x00, 0x00, 0x01, 0x44, 0x01, 0xC1, 0x72, 0xB4, 0x62, 0x40, // PPS
+        0x00, 0x00, 0x01, 0x4E, 0x01, 0x05, 0x1A, 0x47, 0x56, 0x4A, 0xDC, 0x5C, 0x80, // SEI
+        0x00, 0x00, 0x01, 0x26, 0x01, 0xAF, 0x06, 0xB8, 0x63, 0xEF, 0x3A, 0x7F, // IDR_W_RADL
+    ];
+
+    #[test]
+    fn nal_units() {
+        let types = NalUnits::new(IDR_PES_DATA)
+            .map(|n| n.nal_unit_type())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            [
+                NalUnitType::AUD,
+                NalUnitType::VPS,
+                NalUnitType::SPS,
+                NalUnitType::PPS,
+                NalUnitType::PREFIX_SEI,
+                NalUnitType::IDR_W_RADL
+            ]
+        );
+        assert!(contains_irap(IDR_PES_DATA));
+
+        let idr = NalUnits::new(IDR_PES_DATA).last().unwrap();
+        assert!(idr.is_irap());
+        assert!(idr.nal_unit_type().is_idr());
+        assert_eq!(idr.nuh_layer_id(), 0);
+        assert_eq!(idr.temporal_id(), 0);
+        assert_eq!(idr.payload()[0], 0xAF);
+
+        // TRAIL_N in the temporal sub-layer 2 of the layer 1
+        let bytes = [0, 0, 1, 0x00, 0x0B, 0xD0];
+        let nal = NalUnits::new(&bytes).next().unwrap();
+        assert_eq!(nal.nal_unit_type(), NalUnitType::TRAIL_N);
+        assert_eq!(nal.nuh_layer_id(), 1);
+        assert_eq!(nal.temporal_id(), 2);
+        assert!(!nal.is_irap());
+        assert!(!contains_irap(&bytes));
+
+        let cra = [0, 0, 1, 0x2A, 0x01, 0xAF];
+        assert!(contains_irap(&cra));
+        assert!(!NalUnits::new(&cra).next().unwrap().nal_unit_type().is_idr());
+    }
+}
@@ -0,0 +1,321 @@
+//! ID3v2 tags carried as timed metadata (e.g., `StreamType::PacketizedMetadata` in HLS streams).
+//!
+//! The presentation time of a tag is the PTS of the PES packet that carries it.
+//!
+//! # References
+//!
+//! - [ID3 tag version 2.4.0](https://id3.org/id3v2.4.0-structure)
+//! - [Timed Metadata for HTTP Live Streaming](https://developer.apple.com/library/archive/documentation/AudioVideo/Conceptual/HTTP_Live_Streaming_Metadata_Spec/)
+use crate::{ErrorKind, Result};
+use alloc::string::String;
+
+const HEADER_SIZE: usize = 10;
+
+/// ID3v2 tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Id3Tag<'a> {
+    /// Major version (`3` for ID3v2.3 and `4` for ID3v2.4).
+    pub major_version: u8,
+
+    /// Revision number.
+    pub revision: u8,
+
+    /// Flags of the tag.
+    pub flags: u8,
+
+    frames: &'a [u8],
+}
+impl<'a> Id3Tag<'a> {
+    /// Parses the ID3v2 tag at the beginning of `bytes` (e.g., the data of a PES packet).
+    ///
+    /// If `bytes` is shorter than the size declared in the tag header,
+    /// it will return an `ErrorKind::TruncatedPacket` error.
+    /// ID3v2.2 tags are not supported.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self> {
+        let tag_size = track!(Self::tag_size(bytes))?;
+        let major_version = bytes[3];
+        let revision = bytes[4];
+        let flags = bytes[5];
+        track_assert!(
+            major_version == 3 || major_version == 4,
+            ErrorKind::Unsupported,
+            "Unsupported ID3 version: 2.{}",
+            major_version
+        );
+        track_assert!(
+            tag_size <= bytes.len(),
+            ErrorKind::TruncatedPacket,
+            "Truncated ID3 tag: size={}, tag_size={}",
+            bytes.len(),
+            tag_size
+        );
+
+        let mut frames = &bytes[HEADER_SIZE..tag_size];
+        if flags & 0b0100_0000 != 0 {
+            // Skips the extended header
+            track_assert!(frames.len() >= 4, ErrorKind::InvalidInput);
+            let extended_header_size = if major_version == 4 {
+                track!(syncsafe(&frames[..4]))?
+            } else {
+                u32::from_be_bytes([frames[0], frames[1], frames[2], frames[3]]) as usize + 4
+            };
+            track_assert!(
+                extended_header_size <= frames.len(),
+                ErrorKind::InvalidInput,
+                "Too large ID3 extended header: {}",
+                extended_header_size
+            );
+            frames = &frames[extended_header_size..];
+        }
+        Ok(Id3Tag {
+            major_version,
+            revision,
+            flags,
+            frames,
+        })
+    }
+
+    /// Returns the size of the whole tag (including the header) declared in the tag header at the beginning of `bytes`.
+    pub fn tag_size(bytes: &[u8]) -> Result<usize> {
+        track_assert!(
+            bytes.len() >= HEADER_SIZE,
+            ErrorKind::TruncatedPacket,
+            "Truncated ID3 header: size={}",
+            bytes.len()
+        );
+        track_assert_eq!(
+            &bytes[..3],
+            b"ID3",
+            ErrorKind::InvalidInput,
+            "Not an ID3v2 tag"
+        );
+        let size = track!(syncsafe(&bytes[6..10]))?;
+        Ok(HEADER_SIZE + size)
+    }
+
+    /// Returns an iterator over the frames in the tag.
+    pub fn frames(&self) -> Id3Frames<'a> {
+        Id3Frames {
+            bytes: self.frames,
+            major_version: self.major_version,
+            failed: false,
+        }
+    }
+}
+
+/// ID3v2 frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Id3Frame<'a> {
+    /// Frame identifier (e.g., `*b"TXXX"`).
+    pub id: [u8; 4],
+
+    /// Flags of the frame.
+    pub flags: u16,
+
+    /// Content of the frame.
+    pub data: &'a [u8],
+}
+impl<'a> Id3Frame<'a> {
+    /// Interprets the frame as a private frame (`PRIV`).
+    ///
+    /// It will return `None` if the frame is not a well-formed private frame.
+    pub fn as_priv(&self) -> Option<PrivFrame<'a>> {
+        if &self.id != b"PRIV" {
+            return None;
+        }
+        let i = self.data.iter().position(|&b| b == 0)?;
+        Some(PrivFrame {
+            owner: &self.data[..i],
+            data: &self.data[i + 1..],
+        })
+    }
+
+    /// Interprets the frame as a user defined text information frame (`TXXX`).
+    ///
+    /// It will return `None` if the frame is not a well-formed user defined text information frame.
+    pub fn as_txxx(&self) -> Option<TxxxFrame<'a>> {
+        if &self.id != b"TXXX" {
+            return None;
+        }
+        let (&encoding, rest) = self.data.split_first()?;
+        let (description, value) = match encoding {
+            0 | 3 => {
+                let i = rest.iter().position(|&b| b == 0)?;
+                (&rest[..i], &rest[i + 1..])
+            }
+            1 | 2 => {
+                let i = rest
+                    .chunks_exact(2)
+                    .position(|c| c == [0, 0])
+                    .map(|i| i * 2)?;
+                (&rest[..i], &rest[i + 2..])
+            }
+            _ => return None,
+        };
+        Some(TxxxFrame {
+            encoding,
+            description,
+            value,
+        })
+    }
+}
+
+/// Private frame (`PRIV`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PrivFrame<'a> {
+    /// Owner identifier (e.g., `b"com.apple.streaming.transportStreamTimestamp"`).
+    pub owner: &'a [u8],
+
+    /// Private data.
+    pub data: &'a [u8],
+}
+
+/// User defined text information frame (`TXXX`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TxxxFrame<'a> {
+    /// Text encoding (`0`: ISO-8859-1, `1`: UTF-16 with BOM, `2`: UTF-16BE, `3`: UTF-8).
+    pub encoding: u8,
+
+    /// Encoded description.
+    pub description: &'a [u8],
+
+    /// Encoded value (possibly terminated by a null character).
+    pub value: &'a [u8],
+}
+
+/// Iterator over the frames in an ID3v2 tag.
+///
+/// The iteration ends at the padding (if any).
+/// If a frame is broken, the last item is an `ErrorKind::InvalidInput` error.
+#[derive(Debug, Clone)]
+pub struct Id3Frames<'a> {
+    bytes: &'a [u8],
+    major_version: u8,
+    failed: bool,
+}
+impl<'a> Id3Frames<'a> {
+    fn next_frame(&mut self) -> Result<Option<Id3Frame<'a>>> {
+        if self.bytes.len() < HEADER_SIZE || self.bytes[0] == 0 {
+            // End of the frames or padding
+            return Ok(None);
+        }
+        let id = [self.bytes[0], self.bytes[1], self.bytes[2], self.bytes[3]];
+        let size = if self.major_version == 4 {
+            track!(syncsafe(&self.bytes[4..8]))?
+        } else {
+            u32::from_be_bytes([self.bytes[4], self.bytes[5], self.bytes[6], self.bytes[7]])
+                as usize
+        };
+        let flags = u16::from_be_bytes([self.bytes[8], self.bytes[9]]);
+        track_assert!(
+            HEADER_SIZE + size <= self.bytes.len(),
+            ErrorKind::InvalidInput,
+            "Too large ID3 frame: id={:?}, size={}",
+            String::from_utf8_lossy(&id),
+            size
+        );
+
+        let data = &self.bytes[HEADER_SIZE..][..size];
+        self.bytes = &self.bytes[HEADER_SIZE + size..];
+        Ok(Some(Id3Frame { id, flags, data }))
+    }
+}
+impl<'a> Iterator for Id3Frames<'a> {
+    type Item = Result<Id3Frame<'a>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.next_frame().transpose();
+        if let Some(Err(_)) = result {
+            self.failed = true;
+        }
+        result
+    }
+}
+impl core::iter::FusedIterator for Id3Frames<'_> {}
+
+fn syncsafe(bytes: &[u8]) -> Result<usize> {
+    let mut n = 0;
+    for &b in bytes {
+        track_assert_eq!(
+            b & 0x80,
+            0,
+            ErrorKind::InvalidInput,
+            "Invalid ID3 syncsafe integer"
+        );
+        n = (n << 7) | usize::from(b);
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn frame(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend((data.len() as u32).to_be_bytes());
+        bytes.extend([0, 0]);
+        bytes.extend(data);
+        bytes
+    }
+
+    fn tag(frames: &[u8], padding: usize) -> Vec<u8> {
+        let size = frames.len() + padding;
+        let mut bytes = b"ID3\x04\x00\x00".to_vec();
+        bytes.extend([
+            (size >> 21) as u8 & 0x7F,
+            (size >> 14) as u8 & 0x7F,
+            (size >> 7) as u8 & 0x7F,
+            size as u8 & 0x7F,
+        ]);
+        bytes.extend(frames);
+        bytes.resize(bytes.len() + padding, 0);
+        bytes
+    }
+
+    #[test]
+    fn id3_tag() {
+        let owner = b"com.apple.streaming.transportStreamTimestamp";
+        let mut priv_data = owner.to_vec();
+        priv_data.push(0);
+        priv_data.extend([0, 0, 0, 0, 0, 0x01, 0x5F, 0x90]);
+        let mut frames = frame(b"PRIV", &priv_data);
+        frames.extend(frame(b"TXXX", b"\x03key\x00value"));
+        let bytes = tag(&frames, 200);
+
+        assert_eq!(track_try_unwrap!(Id3Tag::tag_size(&bytes)), bytes.len());
+        let tag = track_try_unwrap!(Id3Tag::parse(&bytes));
+        assert_eq!(tag.major_version, 4);
+
+        let frames = track_try_unwrap!(tag.frames().collect::<Result<Vec<_>>>());
+        assert_eq!(frames.len(), 2);
+
+        let priv_frame = frames[0].as_priv().unwrap();
+        assert_eq!(priv_frame.owner, owner);
+        assert_eq!(priv_frame.data, [0, 0, 0, 0, 0, 0x01, 0x5F, 0x90]);
+        assert_eq!(frames[0].as_txxx(), None);
+
+        let txxx = frames[1].as_txxx().unwrap();
+        assert_eq!(txxx.encoding, 3);
+        assert_eq!(txxx.description, b"key");
+        assert_eq!(txxx.value, b"value");
+
+        let e = Id3Tag::parse(&bytes[..bytes.len() - 1]).err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::TruncatedPacket);
+        assert!(Id3Tag::parse(b"ID2\x04\x00\x00\x00\x00\x00\x00").is_err());
+    }
+
+    #[test]
+    fn broken_frame() {
+        let mut frames = frame(b"TXXX", b"\x03key\x00value");
+        frames.truncate(frames.len() - 1);
+        let bytes = tag(&frames, 0);
+
+        let tag = track_try_unwrap!(Id3Tag::parse(&bytes));
+        let mut frames = tag.frames();
+        assert!(frames.next().unwrap().is_err());
+        assert!(frames.next().is_none());
+    }
+}
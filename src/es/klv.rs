@@ -0,0 +1,271 @@
+//! KLV (Key-Length-Value, SMPTE 336M) metadata.
+//!
+//! KLV metadata streams are identified by `EsInfo::is_klv`.
+//!
+//! # References
+//!
+//! - SMPTE 336M
+//! - MISB ST 1402 (MPEG-2 transport stream for class 1/class 2 motion imagery, audio and metadata)
+use crate::{ErrorKind, Result};
+use alloc::vec::Vec;
+
+const KEY_SIZE: usize = 16;
+
+/// KLV item (triplet).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KlvItem<'a> {
+    /// 16-byte universal key.
+    pub key: [u8; KEY_SIZE],
+
+    /// Value of the item.
+    pub value: &'a [u8],
+}
+
+/// Iterator over the KLV items in a byte slice (e.g., the data of a PES packet).
+///
+/// If the last item is truncated, the last item of the iterator is an `ErrorKind::TruncatedPacket` error,
+/// and the bytes of the truncated item are available via `remaining`.
+/// Use `KlvBuffer` to handle the items split across PES packets.
+#[derive(Debug, Clone)]
+pub struct KlvItems<'a> {
+    bytes: &'a [u8],
+    failed: bool,
+}
+impl<'a> KlvItems<'a> {
+    /// Makes a new `KlvItems` instance.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        KlvItems {
+            bytes,
+            failed: false,
+        }
+    }
+
+    /// Returns the bytes that have not been consumed yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    fn next_item(&mut self) -> Result<Option<KlvItem<'a>>> {
+        if self.bytes.is_empty() {
+            return Ok(None);
+        }
+        let (item, size) = track!(parse_item(self.bytes))?;
+        self.bytes = &self.bytes[size..];
+        Ok(Some(item))
+    }
+}
+impl<'a> Iterator for KlvItems<'a> {
+    type Item = Result<KlvItem<'a>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.next_item().transpose();
+        if let Some(Err(_)) = result {
+            self.failed = true;
+        }
+        result
+    }
+}
+impl core::iter::FusedIterator for KlvItems<'_> {}
+
+/// Buffer that reassembles the KLV items split across PES packets.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::es::klv::KlvBuffer;
+///
+/// let mut item = vec![0x06, 0x0E, 0x2B, 0x34];
+/// item.extend([0; 12]); // rest of the key
+/// item.extend([0x03, 1, 2, 3]); // length and value
+///
+/// let mut buf = KlvBuffer::new();
+/// assert_eq!(buf.push(&item[..10]).count(), 0);
+/// let items = buf.push(&item[10..]).collect::<Result<Vec<_>, _>>().unwrap();
+/// assert_eq!(items[0].value, [1, 2, 3]);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct KlvBuffer {
+    buf: Vec<u8>,
+    consumed: usize,
+}
+impl KlvBuffer {
+    /// Makes a new `KlvBuffer` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `data` (e.g., the data of a PES packet) to the buffer,
+    /// and returns an iterator over the complete items in the buffer.
+    ///
+    /// The bytes of an incomplete item at the end are kept until the next call.
+    /// If the buffer contains a broken item, the iterator yields an `ErrorKind::InvalidInput` error
+    /// and the buffered bytes are discarded.
+    pub fn push(&mut self, data: &[u8]) -> KlvBufferItems<'_> {
+        self.buf.drain(..self.consumed);
+        self.consumed = 0;
+        self.buf.extend_from_slice(data);
+        KlvBufferItems {
+            buf: &self.buf,
+            consumed: &mut self.consumed,
+        }
+    }
+
+    /// Returns the number of the buffered bytes that have not been consumed as items yet.
+    pub fn pending_len(&self) -> usize {
+        self.buf.len() - self.consumed
+    }
+
+    /// Discards the buffered bytes.
+    ///
+    /// This should be called when the continuity of the stream is lost (e.g., after seeking).
+    pub fn clear(&mut self) {
+        self.buf.clear();
+        self.consumed = 0;
+    }
+}
+
+/// Iterator over the complete KLV items in a `KlvBuffer`.
+#[derive(Debug)]
+pub struct KlvBufferItems<'a> {
+    buf: &'a [u8],
+    consumed: &'a mut usize,
+}
+impl<'a> Iterator for KlvBufferItems<'a> {
+    type Item = Result<KlvItem<'a>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = &self.buf[*self.consumed..];
+        if bytes.is_empty() {
+            return None;
+        }
+        match parse_item(bytes) {
+            Ok((item, size)) => {
+                *self.consumed += size;
+                Some(Ok(item))
+            }
+            Err(e) if *e.kind() == ErrorKind::TruncatedPacket => None,
+            Err(e) => {
+                *self.consumed = self.buf.len();
+                Some(Err(track!(e)))
+            }
+        }
+    }
+}
+
+fn parse_item(bytes: &[u8]) -> Result<(KlvItem<'_>, usize)> {
+    track_assert!(
+        bytes.len() > KEY_SIZE,
+        ErrorKind::TruncatedPacket,
+        "Truncated KLV key: size={}",
+        bytes.len()
+    );
+    let key: [u8; KEY_SIZE] = bytes[..KEY_SIZE].try_into().expect("Never fails");
+
+    // BER length
+    let mut offset = KEY_SIZE + 1;
+    let first = bytes[KEY_SIZE];
+    let len = if first < 0x80 {
+        u64::from(first)
+    } else {
+        let n = usize::from(first & 0x7F);
+        track_assert!(
+            (1..=8).contains(&n),
+            ErrorKind::InvalidInput,
+            "Unsupported KLV length size: {}",
+            n
+        );
+        track_assert!(
+            bytes.len() >= offset + n,
+            ErrorKind::TruncatedPacket,
+            "Truncated KLV length: size={}",
+            bytes.len()
+        );
+        let len = bytes[offset..offset + n]
+            .iter()
+            .fold(0, |acc, &b| (acc << 8) | u64::from(b));
+        offset += n;
+        len
+    };
+    track_assert!(
+        len <= (bytes.len() - offset) as u64,
+        ErrorKind::TruncatedPacket,
+        "Truncated KLV value: size={}, len={}",
+        bytes.len() - offset,
+        len
+    );
+    let len = len as usize;
+
+    let value = &bytes[offset..offset + len];
+    Ok((KlvItem { key, value }, offset + len))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const UAS_LOCAL_SET_KEY: [u8; 16] = [
+        0x06, 0x0E, 0x2B, 0x34, 0x02, 0x0B, 0x01, 0x01, 0x0E, 0x01, 0x03, 0x01, 0x01, 0x00, 0x00,
+        0x00,
+    ];
+
+    fn item(value_len: usize, long_form: bool) -> Vec<u8> {
+        let mut bytes = UAS_LOCAL_SET_KEY.to_vec();
+        if long_form {
+            bytes.extend([0x82, (value_len >> 8) as u8, value_len as u8]);
+        } else {
+            bytes.push(value_len as u8);
+        }
+        bytes.extend((0..value_len).map(|i| i as u8));
+        bytes
+    }
+
+    #[test]
+    fn klv_items() {
+        let mut bytes = item(10, false);
+        bytes.extend(item(300, true));
+        bytes.extend(&item(20, false)[..25]); // truncated
+
+        let mut items = KlvItems::new(&bytes);
+        let i = track_try_unwrap!(items.next().unwrap());
+        assert_eq!(i.key, UAS_LOCAL_SET_KEY);
+        assert_eq!(i.value.len(), 10);
+        let i = track_try_unwrap!(items.next().unwrap());
+        assert_eq!(i.value.len(), 300);
+        assert_eq!(i.value[299], (299 % 256) as u8);
+        let e = items.next().unwrap().err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::TruncatedPacket);
+        assert_eq!(items.remaining().len(), 25);
+        assert!(items.next().is_none());
+    }
+
+    #[test]
+    fn klv_buffer() {
+        let mut bytes = item(10, false);
+        bytes.extend(item(300, true));
+        let (first, second) = bytes.split_at(100);
+
+        let mut buf = KlvBuffer::new();
+        let values = buf
+            .push(first)
+            .map(|i| track_try_unwrap!(i).value.len())
+            .collect::<Vec<_>>();
+        assert_eq!(values, [10]);
+        assert_eq!(buf.pending_len(), 100 - 27);
+
+        let values = buf
+            .push(second)
+            .map(|i| track_try_unwrap!(i).value.len())
+            .collect::<Vec<_>>();
+        assert_eq!(values, [300]);
+        assert_eq!(buf.pending_len(), 0);
+
+        // Broken length
+        let mut broken = UAS_LOCAL_SET_KEY.to_vec();
+        broken.push(0x80);
+        let mut items = buf.push(&broken);
+        assert!(items.next().unwrap().is_err());
+        assert!(items.next().is_none());
+        assert_eq!(buf.pending_len(), 0);
+    }
+}
@@ -1,6 +1,16 @@
 //! Elementary stream.
-pub use self::stream_id::StreamId;
+pub use self::stream_id::{StreamId, StreamIdKind};
 pub use self::stream_type::StreamType;
 
+pub mod ac3;
+pub mod adts;
+pub mod h264;
+pub mod h265;
+pub mod id3;
+pub mod klv;
+pub mod opus;
+pub mod teletext;
+
+mod annex_b;
 mod stream_id;
 mod stream_type;
@@ -0,0 +1,285 @@
+//! Opus packets carried in PES packets ("Opus in MPEG-TS").
+//!
+//! An Opus stream is carried as `StreamType::Mpeg2PacketizedData` with a registration descriptor `Opus`
+//! and an Opus audio descriptor (see `ts::Codec::Opus` and `ts::OpusAudioDescriptor`).
+//! The data of its PES packets is a sequence of access units,
+//! each of which is an Opus packet preceded by a control header (starting with `0x7F 0xE0`).
+//!
+//! # Examples
+//!
+//! ```
+//! use mpeg2ts::es::opus::{self, OpusAccessUnits};
+//!
+//! let packets: [&[u8]; 2] = [&[0xFC, 1, 2, 3], &[0xFC; 300]];
+//! let data = opus::frame_packets(packets).unwrap();
+//! assert_eq!(&data[..3], [0x7F, 0xE0, 4]);
+//!
+//! let units = OpusAccessUnits::new(&data)
+//!     .map(|unit| unit.map(|unit| unit.packet))
+//!     .collect::<Result<Vec<_>, _>>()
+//!     .unwrap();
+//! assert_eq!(units, packets);
+//! ```
+use crate::io::{Write, WriteBytesExt};
+use crate::{ErrorKind, Result};
+use alloc::vec::Vec;
+use byteorder::BigEndian;
+
+/// `control_header_prefix` (11 bits).
+const CONTROL_HEADER_PREFIX: u16 = 0x3FF;
+
+/// Opus access unit (an Opus packet with its control header).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OpusAccessUnit<'a> {
+    /// Number of the samples (at 48 kHz) to be discarded from the beginning of the packet.
+    pub start_trim: Option<u16>,
+
+    /// Number of the samples (at 48 kHz) to be discarded from the end of the packet.
+    pub end_trim: Option<u16>,
+
+    /// Opus packet.
+    pub packet: &'a [u8],
+}
+impl<'a> OpusAccessUnit<'a> {
+    /// Maximum value of `start_trim` and `end_trim`.
+    pub const MAX_TRIM: u16 = 0x1FFF;
+
+    /// Makes a new `OpusAccessUnit` instance without the trims.
+    pub fn new(packet: &'a [u8]) -> Self {
+        OpusAccessUnit {
+            start_trim: None,
+            end_trim: None,
+            packet,
+        }
+    }
+
+    /// Writes the control header followed by the Opus packet.
+    ///
+    /// `au_size` is encoded as a sequence of `0xFF` bytes followed by the remainder,
+    /// so a packet of 255 bytes or more takes two or more size bytes.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        for trim in [self.start_trim, self.end_trim].into_iter().flatten() {
+            track_assert!(
+                trim <= Self::MAX_TRIM,
+                ErrorKind::InvalidInput,
+                "Too large trim: {}",
+                trim
+            );
+        }
+
+        let n = (CONTROL_HEADER_PREFIX << 5)
+            | (u16::from(self.start_trim.is_some()) << 4)
+            | (u16::from(self.end_trim.is_some()) << 3);
+        track_io!(writer.write_u16::<BigEndian>(n))?;
+
+        let mut au_size = self.packet.len();
+        while au_size >= 0xFF {
+            track_io!(writer.write_u8(0xFF))?;
+            au_size -= 0xFF;
+        }
+        track_io!(writer.write_u8(au_size as u8))?;
+
+        for trim in [self.start_trim, self.end_trim].into_iter().flatten() {
+            track_io!(writer.write_u16::<BigEndian>(trim))?;
+        }
+        track_io!(writer.write_all(self.packet))?;
+        Ok(())
+    }
+}
+
+/// Iterator over the Opus access units in a byte slice (e.g., the data of a PES packet).
+///
+/// The control extensions are skipped.
+/// If the bytes are malformed, the last item is an `ErrorKind::InvalidInput` error
+/// (or an `ErrorKind::TruncatedPacket` error if the last access unit is truncated).
+#[derive(Debug, Clone)]
+pub struct OpusAccessUnits<'a> {
+    bytes: &'a [u8],
+    failed: bool,
+}
+impl<'a> OpusAccessUnits<'a> {
+    /// Makes a new `OpusAccessUnits` instance.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        OpusAccessUnits {
+            bytes,
+            failed: false,
+        }
+    }
+
+    /// Returns the bytes that have not been consumed yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    fn next_unit(&mut self) -> Result<Option<OpusAccessUnit<'a>>> {
+        if self.bytes.is_empty() {
+            return Ok(None);
+        }
+        let mut bytes = self.bytes;
+        let mut take = |n: usize| -> Result<&'a [u8]> {
+            track_assert!(
+                bytes.len() >= n,
+                ErrorKind::TruncatedPacket,
+                "Truncated Opus access unit: size={}",
+                self.bytes.len()
+            );
+            let (head, tail) = bytes.split_at(n);
+            bytes = tail;
+            Ok(head)
+        };
+
+        let header = track!(take(2))?;
+        let n = u16::from_be_bytes([header[0], header[1]]);
+        track_assert_eq!(
+            n >> 5,
+            CONTROL_HEADER_PREFIX,
+            ErrorKind::InvalidInput,
+            "Not an Opus control header"
+        );
+
+        let mut au_size = 0;
+        loop {
+            let b = track!(take(1))?[0];
+            au_size += usize::from(b);
+            if b != 0xFF {
+                break;
+            }
+        }
+
+        let mut trim = |flag: u16| -> Result<Option<u16>> {
+            if n & flag == 0 {
+                return Ok(None);
+            }
+            let b = track!(take(2))?;
+            Ok(Some(
+                u16::from_be_bytes([b[0], b[1]]) & OpusAccessUnit::MAX_TRIM,
+            ))
+        };
+        let start_trim = track!(trim(1 << 4))?;
+        let end_trim = track!(trim(1 << 3))?;
+        if n & (1 << 2) != 0 {
+            let len = track!(take(1))?[0];
+            track!(take(usize::from(len)))?;
+        }
+
+        let packet = track!(take(au_size))?;
+        self.bytes = bytes;
+        Ok(Some(OpusAccessUnit {
+            start_trim,
+            end_trim,
+            packet,
+        }))
+    }
+}
+impl<'a> Iterator for OpusAccessUnits<'a> {
+    type Item = Result<OpusAccessUnit<'a>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.next_unit().transpose();
+        if let Some(Err(_)) = result {
+            self.failed = true;
+        }
+        result
+    }
+}
+impl core::iter::FusedIterator for OpusAccessUnits<'_> {}
+
+/// Frames the Opus packets as access units without the trims (i.e., makes the data of a PES packet).
+pub fn frame_packets<'a, I>(packets: I) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    let mut bytes = Vec::new();
+    for packet in packets {
+        track!(OpusAccessUnit::new(packet).write_to(&mut bytes))?;
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::{StreamId, StreamType};
+    use crate::pes::{PesHeader, PesPacket, PesPacketReader, PesPacketWriter, ReadPesPacket};
+    use crate::time::Timestamp;
+    use crate::ts::{
+        Codec, KnownDescriptor, OpusAudioDescriptor, Pid, TsPacketReader, TsPacketWriter,
+    };
+
+    #[test]
+    fn access_unit() {
+        let packet = [0xAB; 600];
+        let mut unit = OpusAccessUnit::new(&packet);
+        unit.start_trim = Some(312);
+        unit.end_trim = Some(OpusAccessUnit::MAX_TRIM);
+        let mut bytes = Vec::new();
+        track_try_unwrap!(unit.write_to(&mut bytes));
+        assert_eq!(
+            &bytes[..9],
+            [0x7F, 0xF8, 0xFF, 0xFF, 90, 0x01, 0x38, 0x1F, 0xFF]
+        );
+        let mut units = OpusAccessUnits::new(&bytes);
+        assert_eq!(track_try_unwrap!(units.next().unwrap()), unit);
+        assert!(units.next().is_none());
+
+        // Control extension
+        let bytes = [0x7F, 0xE4, 1, 2, 0xAA, 0xBB, 0xCC];
+        let unit = track_try_unwrap!(OpusAccessUnits::new(&bytes).next().unwrap());
+        assert_eq!(unit, OpusAccessUnit::new(&[0xCC]));
+
+        // Truncated
+        let mut units = OpusAccessUnits::new(&[0x7F, 0xE0, 3, 0xFC]);
+        let e = units.next().unwrap().err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::TruncatedPacket);
+        assert!(units.next().is_none());
+
+        // Not an access unit
+        let e = OpusAccessUnits::new(&[0xFC, 0xE0, 0])
+            .next()
+            .unwrap()
+            .err()
+            .unwrap();
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn round_trip() {
+        let packets = [0, 1, 251, 252, 254, 255, 256, 509, 510, 1000]
+            .iter()
+            .map(|&size| (0..size).map(|i| i as u8).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        let pid = Pid::new(0x101).unwrap();
+        let mut writer = PesPacketWriter::new(TsPacketWriter::new(Vec::new()));
+        for (i, chunk) in packets.chunks(3).enumerate() {
+            let mut header = PesHeader::new(StreamId::new(0xBD));
+            header.pts = Some(Timestamp::new(i as u64 * 1800).unwrap());
+            let data = track_try_unwrap!(frame_packets(chunk.iter().map(|p| &p[..])));
+            let packet = PesPacket::new(pid, header, data);
+            track_try_unwrap!(writer.write_codec_pes_packet(pid, Codec::Opus, &packet));
+        }
+        let stream = writer.into_ts_packet_writer().into_stream();
+
+        let mut reader = PesPacketReader::new(TsPacketReader::new(&stream[..]));
+        let mut extracted = Vec::new();
+        while let Some(packet) = track_try_unwrap!(reader.read_pes_packet()) {
+            assert_eq!(packet.stream_type, Some(StreamType::Mpeg2PacketizedData));
+            for unit in OpusAccessUnits::new(&packet.data) {
+                extracted.push(track_try_unwrap!(unit).packet.to_vec());
+            }
+        }
+        assert_eq!(extracted, packets);
+
+        let d = OpusAudioDescriptor {
+            channel_config_code: 2,
+            channel_config_extension: vec![],
+        };
+        let d = track_try_unwrap!(KnownDescriptor::OpusAudio(d).to_descriptor());
+        assert_eq!(
+            Codec::resolve(StreamType::Mpeg2PacketizedData, &[d]),
+            Codec::Opus
+        );
+    }
+}
@@ -2,6 +2,8 @@ use crate::{ErrorKind, Result};
 
 /// Stream identifier.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct StreamId(u8);
 impl StreamId {
     /// Minimum value of the identifiers for audio streams.
@@ -16,8 +18,41 @@ impl StreamId {
     /// Maximum value of the identifiers for video streams.
     pub const VIDEO_MAX: u8 = 0xEF;
 
+    /// `program_stream_map`.
+    pub const PROGRAM_STREAM_MAP: Self = StreamId(0xBC);
+
+    /// `private_stream_1` (e.g., AC-3, DTS, DVB subtitles and teletext).
+    pub const PRIVATE_STREAM_1: Self = StreamId(0xBD);
+
+    /// `padding_stream`.
+    pub const PADDING: Self = StreamId(0xBE);
+
+    /// `private_stream_2`.
+    pub const PRIVATE_STREAM_2: Self = StreamId(0xBF);
+
+    /// `ECM_stream`.
+    pub const ECM: Self = StreamId(0xF0);
+
+    /// `EMM_stream`.
+    pub const EMM: Self = StreamId(0xF1);
+
+    /// `DSMCC_stream`.
+    pub const DSMCC: Self = StreamId(0xF2);
+
+    /// `ITU-T Rec. H.222.1 type E` stream.
+    pub const H222_TYPE_E: Self = StreamId(0xF8);
+
+    /// `metadata_stream` (e.g., ID3 timed metadata).
+    pub const METADATA: Self = StreamId(0xFC);
+
+    /// `extended_stream_id`.
+    pub const EXTENDED: Self = StreamId(0xFD);
+
+    /// `program_stream_directory`.
+    pub const PROGRAM_STREAM_DIRECTORY: Self = StreamId(0xFF);
+
     /// Makes a new `StreamId` instance.
-    pub fn new(id: u8) -> Self {
+    pub const fn new(id: u8) -> Self {
         StreamId(id)
     }
 
@@ -65,4 +100,117 @@ impl StreamId {
     pub fn is_video(&self) -> bool {
         0xE0 <= self.0 && self.0 <= 0xEF
     }
+
+    /// Returns `true` if it is the identifier of `private_stream_1`, otherwise `false`.
+    pub fn is_private_stream_1(&self) -> bool {
+        *self == Self::PRIVATE_STREAM_1
+    }
+
+    /// Returns `true` if it is the identifier of `private_stream_2`, otherwise `false`.
+    pub fn is_private_stream_2(&self) -> bool {
+        *self == Self::PRIVATE_STREAM_2
+    }
+
+    /// Returns `true` if it is the identifier of `padding_stream`, otherwise `false`.
+    pub fn is_padding(&self) -> bool {
+        *self == Self::PADDING
+    }
+
+    /// Returns the kind of the stream that this identifier is assigned to.
+    pub fn kind(&self) -> StreamIdKind {
+        match self.0 {
+            0xBC => StreamIdKind::ProgramStreamMap,
+            0xBD => StreamIdKind::PrivateStream1,
+            0xBE => StreamIdKind::Padding,
+            0xBF => StreamIdKind::PrivateStream2,
+            Self::AUDIO_MIN..=Self::AUDIO_MAX => StreamIdKind::Audio,
+            Self::VIDEO_MIN..=Self::VIDEO_MAX => StreamIdKind::Video,
+            0xF0 => StreamIdKind::Ecm,
+            0xF1 => StreamIdKind::Emm,
+            0xF2 => StreamIdKind::Dsmcc,
+            0xF3 => StreamIdKind::Iso13522,
+            0xF4..=0xF8 => StreamIdKind::H2221,
+            0xF9 => StreamIdKind::Ancillary,
+            0xFA => StreamIdKind::SlPacketized,
+            0xFB => StreamIdKind::FlexMux,
+            0xFC => StreamIdKind::Metadata,
+            0xFD => StreamIdKind::Extended,
+            0xFF => StreamIdKind::ProgramStreamDirectory,
+            _ => StreamIdKind::Reserved,
+        }
+    }
+
+    /// Returns `true` if PES packets with this identifier have the optional PES header, otherwise `false`.
+    ///
+    /// The data of PES packets for `program_stream_map`, `padding_stream`, `private_stream_2`,
+    /// `ECM`, `EMM`, `DSMCC_stream`, `ITU-T Rec. H.222.1 type E` and `program_stream_directory`
+    /// immediately follows the `PES_packet_length` field.
+    pub fn has_optional_header(&self) -> bool {
+        !matches!(
+            self.0,
+            0xBC | 0xBE | 0xBF | 0xF0 | 0xF1 | 0xF2 | 0xF8 | 0xFF
+        )
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for StreamId {
+    /// Generates a stream identifier in the range `0xBC..=0xFF`.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(StreamId(u.int_in_range(0xBC..=0xFF)?))
+    }
+}
+
+/// Kind of the stream that a `StreamId` is assigned to.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StreamIdKind {
+    ProgramStreamMap,
+    PrivateStream1,
+    Padding,
+    PrivateStream2,
+    Audio,
+    Video,
+    Ecm,
+    Emm,
+    Dsmcc,
+    Iso13522,
+
+    /// `ITU-T Rec. H.222.1` type A to E streams (`0xF4..=0xF8`).
+    H2221,
+
+    Ancillary,
+    SlPacketized,
+    FlexMux,
+    Metadata,
+    Extended,
+    ProgramStreamDirectory,
+
+    /// Reserved identifiers (`0x00..=0xBB` and `0xFE`).
+    Reserved,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kind() {
+        assert_eq!(StreamId::new(0xBD), StreamId::PRIVATE_STREAM_1);
+        assert!(StreamId::PRIVATE_STREAM_1.is_private_stream_1());
+        assert_eq!(
+            StreamId::PRIVATE_STREAM_1.kind(),
+            StreamIdKind::PrivateStream1
+        );
+        assert_eq!(StreamId::new(0xC3).kind(), StreamIdKind::Audio);
+        assert_eq!(StreamId::new(0xE0).kind(), StreamIdKind::Video);
+        assert_eq!(StreamId::H222_TYPE_E.kind(), StreamIdKind::H2221);
+        assert_eq!(StreamId::METADATA.kind(), StreamIdKind::Metadata);
+        assert_eq!(StreamId::new(0xFE).kind(), StreamIdKind::Reserved);
+        assert!(!StreamId::PADDING.has_optional_header());
+
+        match StreamId::new(0xFF) {
+            StreamId::PROGRAM_STREAM_DIRECTORY => {}
+            _ => panic!(),
+        }
+    }
 }
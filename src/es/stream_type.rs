@@ -1,60 +1,90 @@
-use crate::{ErrorKind, Result};
-
 /// Elementary stream type.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "u8", into = "u8"))]
 pub enum StreamType {
-    Mpeg1Video = 0x01,
-    Mpeg2Video = 0x02,
-    Mpeg1Audio = 0x03,
-    Mpeg2HalvedSampleRateAudio = 0x04,
-    Mpeg2TabledData = 0x05,
-    Mpeg2PacketizedData = 0x06,
-    Mheg = 0x07,
-    DsmCc = 0x08,
-    AuxiliaryData09 = 0x09,
-    DsmCcMultiprotocolEncapsulation = 0x0A,
-    DsmCcUnMessages = 0x0B,
-    DsmCcStreamDescriptors = 0x0C,
-    DsmCcTabledData = 0x0D,
-    AuxiliaryData0e = 0x0E,
-    AdtsAac = 0x0F,
-    Mpeg4H263BasedVideo = 0x10,
-    Mpeg4LoasMultiFormatFramedAudio = 0x11,
-    Mpeg4FlexMux = 0x12,
-    Mpeg4FlexMuxInTable = 0x13,
-    DsmCcSynchronizedDownloadProtocol = 0x14,
-    PacketizedMetadata = 0x15,
-    SectionedMetadata = 0x16,
-    DsmCcDataCarouselMetadata = 0x17,
-    DsmCcObjectCarouselMetadata = 0x18,
-    SynchronizedDownloadProtocolMetadata = 0x19,
-    Ipmp = 0x1A,
-    H264 = 0x1B,
-    H265 = 0x24,
-    ChineseVideoStandard = 0x42,
-    PcmAudio = 0x80,
-    DolbyDigitalUpToSixChannelAudio = 0x81,
-    Dts6ChannelAudio = 0x82,
-    DolbyTrueHdLosslessAudio = 0x83,
-    DolbyDigitalPlusUpTo16ChannelAudio = 0x84,
-    Dts8ChannelAudio = 0x85,
-    Dts8ChannelLosslessAudio = 0x86,
-    DolbyDigitalPlusUpTo16ChannelAudioForAtsc = 0x87,
-    PresentationGraphicStream = 0x90,
-    AtscDsmCcNetworkResourcesTable = 0x91,
-    DigiCipher2Text = 0xC0,
-    DolbyDigitalUpToSixChannelAudioWithAes128Cbc = 0xC1,
-    DolbyDigitalPlusUpToSixChannelAudioWithAes128Cbc = 0xC2,
-    AdtsAacWithAes128Cbc = 0xCF,
-    UltraHdVideo = 0xD1,
-    H264WithAes128Cbc = 0xDB,
-    MicrosoftWindowsMediaVideo9 = 0xEA,
+    Mpeg1Video,
+    Mpeg2Video,
+    Mpeg1Audio,
+    Mpeg2HalvedSampleRateAudio,
+    Mpeg2TabledData,
+    Mpeg2PacketizedData,
+    Mheg,
+    DsmCc,
+    AuxiliaryData09,
+    DsmCcMultiprotocolEncapsulation,
+    DsmCcUnMessages,
+    DsmCcStreamDescriptors,
+    DsmCcTabledData,
+    AuxiliaryData0e,
+    AdtsAac,
+    Mpeg4H263BasedVideo,
+    Mpeg4LoasMultiFormatFramedAudio,
+    Mpeg4FlexMux,
+    Mpeg4FlexMuxInTable,
+    DsmCcSynchronizedDownloadProtocol,
+    PacketizedMetadata,
+    SectionedMetadata,
+    DsmCcDataCarouselMetadata,
+    DsmCcObjectCarouselMetadata,
+    SynchronizedDownloadProtocolMetadata,
+    Ipmp,
+    H264,
+    Mpeg4RawAudio,
+    Mpeg4Text,
+    AuxiliaryVideo,
+    SvcSubBitstream,
+    MvcSubBitstream,
+    Jpeg2000Video,
+    Mpeg2StereoscopicAdditionalView,
+    H264StereoscopicAdditionalView,
+    H265,
+    H265TemporalVideoSubset,
+    MvcdSubBitstream,
+    TimelineAndExternalMediaInformation,
+    MvHevcEnhancementSubPartition,
+    MvHevcTemporalEnhancementSubPartition,
+    ShvcEnhancementSubPartition,
+    ShvcTemporalEnhancementSubPartition,
+    GreenAccessUnits,
+    MpegH3dAudioMain,
+    MpegH3dAudioAuxiliary,
+    QualityAccessUnits,
+    MediaOrchestrationAccessUnits,
+    H265MotionConstrainedTileSets,
+    JpegXsVideo,
+    H266,
+    H266TemporalVideoSubset,
+    ChineseVideoStandard,
+    PcmAudio,
+    DolbyDigitalUpToSixChannelAudio,
+    Dts6ChannelAudio,
+    DolbyTrueHdLosslessAudio,
+    DolbyDigitalPlusUpTo16ChannelAudio,
+    Dts8ChannelAudio,
+    Dts8ChannelLosslessAudio,
+    DolbyDigitalPlusUpTo16ChannelAudioForAtsc,
+    PresentationGraphicStream,
+    AtscDsmCcNetworkResourcesTable,
+    DigiCipher2Text,
+    DolbyDigitalUpToSixChannelAudioWithAes128Cbc,
+    DolbyDigitalPlusUpToSixChannelAudioWithAes128Cbc,
+    AdtsAacWithAes128Cbc,
+    UltraHdVideo,
+    H264WithAes128Cbc,
+    MicrosoftWindowsMediaVideo9,
+
+    /// Stream type that is not known to this crate (e.g., reserved or user private values).
+    Unknown(u8),
 }
 impl StreamType {
     /// Makes a `StreamType` instance that associated with the given number.
-    pub fn from_u8(n: u8) -> Result<Self> {
-        Ok(match n {
+    ///
+    /// Values that are not known to this crate are mapped to `StreamType::Unknown`.
+    pub fn from_u8(n: u8) -> Self {
+        match n {
             0x01 => StreamType::Mpeg1Video,
             0x02 => StreamType::Mpeg2Video,
             0x03 => StreamType::Mpeg1Audio,
@@ -82,7 +112,31 @@ impl StreamType {
             0x19 => StreamType::SynchronizedDownloadProtocolMetadata,
             0x1A => StreamType::Ipmp,
             0x1B => StreamType::H264,
+            0x1C => StreamType::Mpeg4RawAudio,
+            0x1D => StreamType::Mpeg4Text,
+            0x1E => StreamType::AuxiliaryVideo,
+            0x1F => StreamType::SvcSubBitstream,
+            0x20 => StreamType::MvcSubBitstream,
+            0x21 => StreamType::Jpeg2000Video,
+            0x22 => StreamType::Mpeg2StereoscopicAdditionalView,
+            0x23 => StreamType::H264StereoscopicAdditionalView,
             0x24 => StreamType::H265,
+            0x25 => StreamType::H265TemporalVideoSubset,
+            0x26 => StreamType::MvcdSubBitstream,
+            0x27 => StreamType::TimelineAndExternalMediaInformation,
+            0x28 => StreamType::MvHevcEnhancementSubPartition,
+            0x29 => StreamType::MvHevcTemporalEnhancementSubPartition,
+            0x2A => StreamType::ShvcEnhancementSubPartition,
+            0x2B => StreamType::ShvcTemporalEnhancementSubPartition,
+            0x2C => StreamType::GreenAccessUnits,
+            0x2D => StreamType::MpegH3dAudioMain,
+            0x2E => StreamType::MpegH3dAudioAuxiliary,
+            0x2F => StreamType::QualityAccessUnits,
+            0x30 => StreamType::MediaOrchestrationAccessUnits,
+            0x31 => StreamType::H265MotionConstrainedTileSets,
+            0x32 => StreamType::JpegXsVideo,
+            0x33 => StreamType::H266,
+            0x34 => StreamType::H266TemporalVideoSubset,
             0x42 => StreamType::ChineseVideoStandard,
             0x80 => StreamType::PcmAudio,
             0x81 => StreamType::DolbyDigitalUpToSixChannelAudio,
@@ -101,7 +155,222 @@ impl StreamType {
             0xD1 => StreamType::UltraHdVideo,
             0xDB => StreamType::H264WithAes128Cbc,
             0xEA => StreamType::MicrosoftWindowsMediaVideo9,
-            _ => track_panic!(ErrorKind::InvalidInput, "Unknown stream type: {}", n),
-        })
+            _ => StreamType::Unknown(n),
+        }
+    }
+
+    /// Returns the number associated with the stream type.
+    pub fn as_u8(self) -> u8 {
+        match self {
+            StreamType::Mpeg1Video => 0x01,
+            StreamType::Mpeg2Video => 0x02,
+            StreamType::Mpeg1Audio => 0x03,
+            StreamType::Mpeg2HalvedSampleRateAudio => 0x04,
+            StreamType::Mpeg2TabledData => 0x05,
+            StreamType::Mpeg2PacketizedData => 0x06,
+            StreamType::Mheg => 0x07,
+            StreamType::DsmCc => 0x08,
+            StreamType::AuxiliaryData09 => 0x09,
+            StreamType::DsmCcMultiprotocolEncapsulation => 0x0A,
+            StreamType::DsmCcUnMessages => 0x0B,
+            StreamType::DsmCcStreamDescriptors => 0x0C,
+            StreamType::DsmCcTabledData => 0x0D,
+            StreamType::AuxiliaryData0e => 0x0E,
+            StreamType::AdtsAac => 0x0F,
+            StreamType::Mpeg4H263BasedVideo => 0x10,
+            StreamType::Mpeg4LoasMultiFormatFramedAudio => 0x11,
+            StreamType::Mpeg4FlexMux => 0x12,
+            StreamType::Mpeg4FlexMuxInTable => 0x13,
+            StreamType::DsmCcSynchronizedDownloadProtocol => 0x14,
+            StreamType::PacketizedMetadata => 0x15,
+            StreamType::SectionedMetadata => 0x16,
+            StreamType::DsmCcDataCarouselMetadata => 0x17,
+            StreamType::DsmCcObjectCarouselMetadata => 0x18,
+            StreamType::SynchronizedDownloadProtocolMetadata => 0x19,
+            StreamType::Ipmp => 0x1A,
+            StreamType::H264 => 0x1B,
+            StreamType::Mpeg4RawAudio => 0x1C,
+            StreamType::Mpeg4Text => 0x1D,
+            StreamType::AuxiliaryVideo => 0x1E,
+            StreamType::SvcSubBitstream => 0x1F,
+            StreamType::MvcSubBitstream => 0x20,
+            StreamType::Jpeg2000Video => 0x21,
+            StreamType::Mpeg2StereoscopicAdditionalView => 0x22,
+            StreamType::H264StereoscopicAdditionalView => 0x23,
+            StreamType::H265 => 0x24,
+            StreamType::H265TemporalVideoSubset => 0x25,
+            StreamType::MvcdSubBitstream => 0x26,
+            StreamType::TimelineAndExternalMediaInformation => 0x27,
+            StreamType::MvHevcEnhancementSubPartition => 0x28,
+            StreamType::MvHevcTemporalEnhancementSubPartition => 0x29,
+            StreamType::ShvcEnhancementSubPartition => 0x2A,
+            StreamType::ShvcTemporalEnhancementSubPartition => 0x2B,
+            StreamType::GreenAccessUnits => 0x2C,
+            StreamType::MpegH3dAudioMain => 0x2D,
+            StreamType::MpegH3dAudioAuxiliary => 0x2E,
+            StreamType::QualityAccessUnits => 0x2F,
+            StreamType::MediaOrchestrationAccessUnits => 0x30,
+            StreamType::H265MotionConstrainedTileSets => 0x31,
+            StreamType::JpegXsVideo => 0x32,
+            StreamType::H266 => 0x33,
+            StreamType::H266TemporalVideoSubset => 0x34,
+            StreamType::ChineseVideoStandard => 0x42,
+            StreamType::PcmAudio => 0x80,
+            StreamType::DolbyDigitalUpToSixChannelAudio => 0x81,
+            StreamType::Dts6ChannelAudio => 0x82,
+            StreamType::DolbyTrueHdLosslessAudio => 0x83,
+            StreamType::DolbyDigitalPlusUpTo16ChannelAudio => 0x84,
+            StreamType::Dts8ChannelAudio => 0x85,
+            StreamType::Dts8ChannelLosslessAudio => 0x86,
+            StreamType::DolbyDigitalPlusUpTo16ChannelAudioForAtsc => 0x87,
+            StreamType::PresentationGraphicStream => 0x90,
+            StreamType::AtscDsmCcNetworkResourcesTable => 0x91,
+            StreamType::DigiCipher2Text => 0xC0,
+            StreamType::DolbyDigitalUpToSixChannelAudioWithAes128Cbc => 0xC1,
+            StreamType::DolbyDigitalPlusUpToSixChannelAudioWithAes128Cbc => 0xC2,
+            StreamType::AdtsAacWithAes128Cbc => 0xCF,
+            StreamType::UltraHdVideo => 0xD1,
+            StreamType::H264WithAes128Cbc => 0xDB,
+            StreamType::MicrosoftWindowsMediaVideo9 => 0xEA,
+            StreamType::Unknown(n) => n,
+        }
+    }
+
+    /// Returns `true` if this is a video stream type, otherwise `false`.
+    pub fn is_video(self) -> bool {
+        matches!(
+            self,
+            StreamType::Mpeg1Video
+                | StreamType::Mpeg2Video
+                | StreamType::Mpeg4H263BasedVideo
+                | StreamType::H264
+                | StreamType::H265
+                | StreamType::AuxiliaryVideo
+                | StreamType::SvcSubBitstream
+                | StreamType::MvcSubBitstream
+                | StreamType::Jpeg2000Video
+                | StreamType::Mpeg2StereoscopicAdditionalView
+                | StreamType::H264StereoscopicAdditionalView
+                | StreamType::H265TemporalVideoSubset
+                | StreamType::MvcdSubBitstream
+                | StreamType::MvHevcEnhancementSubPartition
+                | StreamType::MvHevcTemporalEnhancementSubPartition
+                | StreamType::ShvcEnhancementSubPartition
+                | StreamType::ShvcTemporalEnhancementSubPartition
+                | StreamType::H265MotionConstrainedTileSets
+                | StreamType::JpegXsVideo
+                | StreamType::H266
+                | StreamType::H266TemporalVideoSubset
+                | StreamType::ChineseVideoStandard
+                | StreamType::UltraHdVideo
+                | StreamType::H264WithAes128Cbc
+                | StreamType::MicrosoftWindowsMediaVideo9
+        )
+    }
+
+    /// Returns `true` if this is an audio stream type, otherwise `false`.
+    ///
+    /// Note that audio streams carried as `StreamType::Mpeg2PacketizedData` (e.g., DVB AC-3) are not included.
+    /// Use `EsInfo::is_audio` to detect such streams.
+    pub fn is_audio(self) -> bool {
+        matches!(
+            self,
+            StreamType::Mpeg1Audio
+                | StreamType::Mpeg2HalvedSampleRateAudio
+                | StreamType::AdtsAac
+                | StreamType::Mpeg4LoasMultiFormatFramedAudio
+                | StreamType::Mpeg4RawAudio
+                | StreamType::MpegH3dAudioMain
+                | StreamType::MpegH3dAudioAuxiliary
+                | StreamType::PcmAudio
+                | StreamType::DolbyDigitalUpToSixChannelAudio
+                | StreamType::Dts6ChannelAudio
+                | StreamType::DolbyTrueHdLosslessAudio
+                | StreamType::DolbyDigitalPlusUpTo16ChannelAudio
+                | StreamType::Dts8ChannelAudio
+                | StreamType::Dts8ChannelLosslessAudio
+                | StreamType::DolbyDigitalPlusUpTo16ChannelAudioForAtsc
+                | StreamType::DolbyDigitalUpToSixChannelAudioWithAes128Cbc
+                | StreamType::DolbyDigitalPlusUpToSixChannelAudioWithAes128Cbc
+                | StreamType::AdtsAacWithAes128Cbc
+        )
+    }
+
+    /// Returns `true` if the data of the PES packets of this type is encrypted with AES-128-CBC
+    /// (see `pes::SampleDecrypter`), otherwise `false`.
+    pub fn is_aes128_cbc(self) -> bool {
+        matches!(
+            self,
+            StreamType::DolbyDigitalUpToSixChannelAudioWithAes128Cbc
+                | StreamType::DolbyDigitalPlusUpToSixChannelAudioWithAes128Cbc
+                | StreamType::AdtsAacWithAes128Cbc
+                | StreamType::H264WithAes128Cbc
+        )
+    }
+
+    /// Returns `true` if the streams of this type carry DSM-CC sections (ISO/IEC 13818-6)
+    /// instead of PES packets, otherwise `false`.
+    pub fn is_dsm_cc_sections(self) -> bool {
+        matches!(
+            self,
+            StreamType::DsmCcMultiprotocolEncapsulation
+                | StreamType::DsmCcUnMessages
+                | StreamType::DsmCcStreamDescriptors
+                | StreamType::DsmCcTabledData
+        )
+    }
+
+    /// Returns `true` if this is a metadata stream type, otherwise `false`.
+    pub fn is_metadata(self) -> bool {
+        matches!(
+            self,
+            StreamType::PacketizedMetadata
+                | StreamType::SectionedMetadata
+                | StreamType::DsmCcDataCarouselMetadata
+                | StreamType::DsmCcObjectCarouselMetadata
+                | StreamType::SynchronizedDownloadProtocolMetadata
+        )
+    }
+}
+
+impl From<u8> for StreamType {
+    fn from(f: u8) -> Self {
+        Self::from_u8(f)
+    }
+}
+impl From<StreamType> for u8 {
+    fn from(f: StreamType) -> Self {
+        f.as_u8()
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for StreamType {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Self::from_u8(u.arbitrary()?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_u8() {
+        for n in 0..=u8::MAX {
+            assert_eq!(StreamType::from_u8(n).as_u8(), n);
+        }
+        assert_eq!(StreamType::from_u8(0x1B), StreamType::H264);
+        assert_eq!(StreamType::from_u8(0xFE), StreamType::Unknown(0xFE));
+
+        let unknown = StreamType::from_u8(0xFE);
+        assert!(!unknown.is_video() && !unknown.is_audio() && !unknown.is_metadata());
+        assert!(StreamType::PacketizedMetadata.is_metadata());
+        assert_eq!(
+            StreamType::from_u8(0x27),
+            StreamType::TimelineAndExternalMediaInformation
+        );
+        assert!(StreamType::from_u8(0x25).is_video());
+        assert!(StreamType::from_u8(0x33).is_video());
+        assert!(StreamType::from_u8(0x2D).is_audio());
     }
 }
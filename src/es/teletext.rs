@@ -0,0 +1,359 @@
+//! EBU Teletext carried in PES packets (`StreamType::Mpeg2PacketizedData` with a teletext descriptor).
+//!
+//! # References
+//!
+//! - ETSI EN 300 472 (Specification for conveying ITU-R System B Teletext in DVB bitstreams)
+//! - ETSI EN 300 706 (Enhanced Teletext specification)
+use crate::{ErrorKind, Result};
+use alloc::vec::Vec;
+
+const DATA_UNIT_DATA_SIZE: usize = 44;
+
+const FRAMING_CODE: u8 = 0xE4;
+
+/// Data unit identifier of EBU Teletext non-subtitle data.
+pub const DATA_UNIT_ID_NON_SUBTITLE: u8 = 0x02;
+
+/// Data unit identifier of EBU Teletext subtitle data.
+pub const DATA_UNIT_ID_SUBTITLE: u8 = 0x03;
+
+/// Data unit identifier of stuffing.
+pub const DATA_UNIT_ID_STUFFING: u8 = 0xFF;
+
+/// Iterator over the data units in the data of a teletext PES packet.
+///
+/// The stuffing data units are skipped.
+/// If a data unit is truncated, the last item is an `ErrorKind::InvalidInput` error.
+#[derive(Debug, Clone)]
+pub struct DataUnits<'a> {
+    data_identifier: u8,
+    bytes: &'a [u8],
+    failed: bool,
+}
+impl<'a> DataUnits<'a> {
+    /// Makes a new `DataUnits` instance.
+    ///
+    /// `bytes` starts with the `data_identifier` field.
+    /// If it is not an EBU data identifier (`0x10..=0x1F`), it will return an `ErrorKind::InvalidInput` error.
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        let (&data_identifier, bytes) = track_assert_some!(
+            bytes.split_first(),
+            ErrorKind::InvalidInput,
+            "Empty teletext PES data"
+        );
+        track_assert!(
+            (0x10..=0x1F).contains(&data_identifier),
+            ErrorKind::InvalidInput,
+            "Not an EBU teletext data identifier: {}",
+            data_identifier
+        );
+        Ok(DataUnits {
+            data_identifier,
+            bytes,
+            failed: false,
+        })
+    }
+
+    /// Returns the `data_identifier` field.
+    pub fn data_identifier(&self) -> u8 {
+        self.data_identifier
+    }
+
+    fn next_unit(&mut self) -> Result<Option<DataUnit<'a>>> {
+        loop {
+            let [data_unit_id, len, rest @ ..] = self.bytes else {
+                return Ok(None);
+            };
+            let len = usize::from(*len);
+            track_assert!(
+                len <= rest.len(),
+                ErrorKind::InvalidInput,
+                "Truncated teletext data unit: size={}, len={}",
+                rest.len(),
+                len
+            );
+            let (field, rest) = rest.split_at(len);
+            self.bytes = rest;
+            if *data_unit_id == DATA_UNIT_ID_STUFFING {
+                continue;
+            }
+            track_assert_eq!(
+                len,
+                DATA_UNIT_DATA_SIZE,
+                ErrorKind::InvalidInput,
+                "Unexpected teletext data unit length"
+            );
+            let field: &[u8; DATA_UNIT_DATA_SIZE] = field.try_into().expect("Never fails");
+            track_assert_eq!(
+                field[1],
+                FRAMING_CODE,
+                ErrorKind::InvalidInput,
+                "Unexpected teletext framing code"
+            );
+            return Ok(Some(DataUnit {
+                data_unit_id: *data_unit_id,
+                field,
+            }));
+        }
+    }
+}
+impl<'a> Iterator for DataUnits<'a> {
+    type Item = Result<DataUnit<'a>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.next_unit().transpose();
+        if let Some(Err(_)) = result {
+            self.failed = true;
+        }
+        result
+    }
+}
+impl core::iter::FusedIterator for DataUnits<'_> {}
+
+/// Teletext data unit (i.e., a teletext packet in a VBI line).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DataUnit<'a> {
+    /// Data unit identifier (e.g., `DATA_UNIT_ID_SUBTITLE`).
+    pub data_unit_id: u8,
+
+    field: &'a [u8; DATA_UNIT_DATA_SIZE],
+}
+impl<'a> DataUnit<'a> {
+    /// Returns `true` if this is a subtitle data unit, otherwise `false`.
+    pub fn is_subtitle(&self) -> bool {
+        self.data_unit_id == DATA_UNIT_ID_SUBTITLE
+    }
+
+    /// Returns the field parity (`true` for the first field).
+    pub fn field_parity(&self) -> bool {
+        (self.field[0] & 0b0010_0000) != 0
+    }
+
+    /// Returns the line offset (5 bits).
+    pub fn line_offset(&self) -> u8 {
+        self.field[0] & 0b0001_1111
+    }
+
+    /// Decodes the Hamming 8/4 coded magazine and packet address.
+    ///
+    /// It returns the magazine number (`1..=8`) and the packet number (`0..=31`).
+    /// If the address has an uncorrectable error, it will return an `ErrorKind::InvalidInput` error.
+    pub fn address(&self) -> Result<(u8, u8)> {
+        let a = track_assert_some!(
+            hamming_8_4(self.field[2].reverse_bits()),
+            ErrorKind::InvalidInput,
+            "Uncorrectable teletext address"
+        );
+        let b = track_assert_some!(
+            hamming_8_4(self.field[3].reverse_bits()),
+            ErrorKind::InvalidInput,
+            "Uncorrectable teletext address"
+        );
+        let magazine = match a & 0b111 {
+            0 => 8,
+            m => m,
+        };
+        let packet_number = (a >> 3) | (b << 1);
+        Ok((magazine, packet_number))
+    }
+
+    /// Returns the 40 data bytes following the address, in transmission order (i.e., not decoded).
+    pub fn data_bytes(&self) -> &'a [u8] {
+        &self.field[4..]
+    }
+
+    /// Decodes the data bytes as odd-parity 7-bit characters.
+    ///
+    /// The bytes that have parity errors are decoded as `None`.
+    /// Note that the meaning of the data bytes depends on the packet number (e.g., the packet 0 is a page header).
+    pub fn characters(&self) -> impl Iterator<Item = Option<u8>> + 'a {
+        self.data_bytes().iter().map(|&b| {
+            let b = b.reverse_bits();
+            if b.count_ones() % 2 == 1 {
+                Some(b & 0x7F)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Page entry of a teletext descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TeletextPage {
+    /// ISO 639 language code.
+    pub language: [u8; 3],
+
+    /// Teletext type (5 bits, e.g., `0x02` for teletext subtitles).
+    pub teletext_type: u8,
+
+    /// Magazine number (3 bits, `0` means `8`).
+    pub magazine_number: u8,
+
+    /// Page number in BCD.
+    pub page_number: u8,
+}
+impl TeletextPage {
+    /// Teletext type of the initial teletext page.
+    pub const TYPE_INITIAL_PAGE: u8 = 0x01;
+
+    /// Teletext type of the teletext subtitle page.
+    pub const TYPE_SUBTITLE_PAGE: u8 = 0x02;
+
+    /// Teletext type of the teletext subtitle page for hearing impaired people.
+    pub const TYPE_HEARING_IMPAIRED_SUBTITLE_PAGE: u8 = 0x05;
+
+    /// Returns the page in the conventional notation (e.g., `888`).
+    ///
+    /// It will return `None` if the page number is not a valid BCD.
+    pub fn page(&self) -> Option<u16> {
+        let tens = self.page_number >> 4;
+        let units = self.page_number & 0x0F;
+        if tens > 9 || units > 9 {
+            return None;
+        }
+        let magazine = if self.magazine_number == 0 {
+            8
+        } else {
+            self.magazine_number
+        };
+        Some(u16::from(magazine) * 100 + u16::from(tens) * 10 + u16::from(units))
+    }
+
+    pub(crate) fn read_entries(data: &[u8]) -> Option<Vec<Self>> {
+        if data.len() % 5 != 0 {
+            return None;
+        }
+        let pages = data
+            .chunks_exact(5)
+            .map(|c| TeletextPage {
+                language: [c[0], c[1], c[2]],
+                teletext_type: c[3] >> 3,
+                magazine_number: c[3] & 0b111,
+                page_number: c[4],
+            })
+            .collect();
+        Some(pages)
+    }
+
+    pub(crate) fn write_entry(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.language);
+        buf.push((self.teletext_type << 3) | (self.magazine_number & 0b111));
+        buf.push(self.page_number);
+    }
+}
+
+/// Decodes a Hamming 8/4 byte (in the LSB-first order), correcting a single bit error.
+fn hamming_8_4(b: u8) -> Option<u8> {
+    (0..16)
+        .map(|d| (d, (hamming_8_4_encode(d) ^ b).count_ones()))
+        .find(|&(_, distance)| distance <= 1)
+        .map(|(d, _)| d)
+}
+
+fn hamming_8_4_encode(d: u8) -> u8 {
+    let d1 = d & 1;
+    let d2 = (d >> 1) & 1;
+    let d3 = (d >> 2) & 1;
+    let d4 = (d >> 3) & 1;
+    let p1 = 1 ^ d1 ^ d3 ^ d4;
+    let p2 = 1 ^ d1 ^ d2 ^ d4;
+    let p3 = 1 ^ d1 ^ d2 ^ d3;
+    let p4 = 1 ^ p1 ^ d1 ^ p2 ^ d2 ^ p3 ^ d3 ^ d4;
+    p1 | (d1 << 1) | (p2 << 2) | (d2 << 3) | (p3 << 4) | (d3 << 5) | (p4 << 6) | (d4 << 7)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn odd_parity(c: u8) -> u8 {
+        if c.count_ones() % 2 == 0 {
+            c | 0x80
+        } else {
+            c
+        }
+    }
+
+    fn data_unit(magazine: u8, packet_number: u8, text: &[u8]) -> Vec<u8> {
+        let a = (magazine & 0b111) | ((packet_number & 1) << 3);
+        let b = packet_number >> 1;
+        let mut bytes = vec![
+            DATA_UNIT_ID_SUBTITLE,
+            44,
+            0b1110_0000 | 21,
+            FRAMING_CODE,
+            hamming_8_4_encode(a).reverse_bits(),
+            hamming_8_4_encode(b).reverse_bits(),
+        ];
+        for i in 0..40 {
+            let c = text.get(i).copied().unwrap_or(b' ');
+            bytes.push(odd_parity(c).reverse_bits());
+        }
+        bytes
+    }
+
+    #[test]
+    fn hamming() {
+        let table = (0..16).map(hamming_8_4_encode).collect::<Vec<_>>();
+        assert_eq!(
+            table,
+            [
+                0x15, 0x02, 0x49, 0x5E, 0x64, 0x73, 0x38, 0x2F, 0xD0, 0xC7, 0x8C, 0x9B, 0xA1, 0xB6,
+                0xFD, 0xEA
+            ]
+        );
+        assert_eq!(hamming_8_4(0x5E ^ 0b0100_0000), Some(3));
+        assert_eq!(hamming_8_4(0x5E ^ 0b0100_0001), None);
+    }
+
+    #[test]
+    fn data_units() {
+        let mut bytes = vec![0x10];
+        bytes.extend(data_unit(8, 0, b"header"));
+        bytes.extend([DATA_UNIT_ID_STUFFING, 2, 0xFF, 0xFF]);
+        let mut unit = data_unit(1, 22, b"Hello");
+        unit[7] ^= 0x01; // parity error in the second character
+        bytes.extend(unit);
+
+        let units = DataUnits::new(&bytes).unwrap();
+        assert_eq!(units.data_identifier(), 0x10);
+        let units = track_try_unwrap!(units.collect::<Result<Vec<_>>>());
+        assert_eq!(units.len(), 2);
+
+        assert!(units[0].is_subtitle());
+        assert!(units[0].field_parity());
+        assert_eq!(units[0].line_offset(), 21);
+        assert_eq!(track_try_unwrap!(units[0].address()), (8, 0));
+
+        assert_eq!(track_try_unwrap!(units[1].address()), (1, 22));
+        let chars = units[1].characters().take(5).collect::<Vec<_>>();
+        assert_eq!(
+            chars,
+            [Some(b'H'), None, Some(b'l'), Some(b'l'), Some(b'o')]
+        );
+
+        assert!(DataUnits::new(&[0x20]).is_err());
+        let mut units = DataUnits::new(&bytes[..40]).unwrap();
+        assert!(units.next().unwrap().is_err());
+        assert!(units.next().is_none());
+    }
+
+    #[test]
+    fn teletext_page() {
+        let data = [b'e', b'n', b'g', 0x10, 0x88, b'd', b'e', b'u', 0x29, 0x50];
+        let pages = TeletextPage::read_entries(&data).unwrap();
+        assert_eq!(pages[0].language, *b"eng");
+        assert_eq!(pages[0].teletext_type, TeletextPage::TYPE_SUBTITLE_PAGE);
+        assert_eq!(pages[0].page(), Some(888));
+        assert_eq!(
+            pages[1].teletext_type,
+            TeletextPage::TYPE_HEARING_IMPAIRED_SUBTITLE_PAGE
+        );
+        assert_eq!(pages[1].page(), Some(150));
+
+        assert_eq!(TeletextPage::read_entries(&data[..6]), None);
+    }
+}
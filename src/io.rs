@@ -0,0 +1,426 @@
+//! I/O traits used by the readers and writers of this crate.
+//!
+//! If the `std` feature is enabled (the default), the items are re-exported from `std::io`
+//! (and the extension traits from `byteorder`).
+//! Otherwise, minimal equivalents are defined here,
+//! so that `no_std` applications can implement `Read` and `Write` for their own I/O types.
+//! `Read` is implemented for `&[u8]`, and `Write` for `Vec<u8>` and `&mut [u8]`.
+//! `Seek` and `SeekFrom` are only available with the `std` feature.
+#[cfg(feature = "std")]
+pub use byteorder::{ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "std")]
+pub use std::io::{
+    empty, Chain, Empty, Error, ErrorKind, Read, Result, Seek, SeekFrom, Take, Write,
+};
+
+#[cfg(not(feature = "std"))]
+pub use self::core_io::*;
+
+#[cfg(not(feature = "std"))]
+mod core_io {
+    use alloc::boxed::Box;
+    use alloc::vec::Vec;
+    use byteorder::ByteOrder;
+    use core::fmt;
+
+    /// A specialized `Result` type for I/O operations.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// Kinds of I/O errors (a subset of `std::io::ErrorKind`).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[allow(missing_docs)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        InvalidInput,
+        InvalidData,
+        UnexpectedEof,
+        WriteZero,
+        Interrupted,
+        Other,
+    }
+
+    /// I/O error.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Error {
+        kind: ErrorKind,
+        message: &'static str,
+    }
+    impl Error {
+        /// Makes a new `Error` instance.
+        pub const fn new(kind: ErrorKind, message: &'static str) -> Self {
+            Error { kind, message }
+        }
+
+        /// Returns the kind of the error.
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+    impl From<ErrorKind> for Error {
+        fn from(kind: ErrorKind) -> Self {
+            Error { kind, message: "" }
+        }
+    }
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            if self.message.is_empty() {
+                write!(f, "{:?}", self.kind)
+            } else {
+                write!(f, "{:?}: {}", self.kind, self.message)
+            }
+        }
+    }
+
+    /// The `Read` trait allows for reading bytes from a source (the counterpart of `std::io::Read`).
+    pub trait Read {
+        /// Pulls some bytes from this source into `buf`, and returns how many bytes were read.
+        ///
+        /// `Ok(0)` means that the end of the source has been reached (or `buf` is empty).
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+        /// Reads the exact number of bytes required to fill `buf`.
+        fn read_exact(&mut self, mut buf: &mut [u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.read(buf) {
+                    Ok(0) => break,
+                    Ok(n) => buf = &mut buf[n..],
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            if buf.is_empty() {
+                Ok(())
+            } else {
+                Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                ))
+            }
+        }
+
+        /// Reads all the bytes until the end of the source, and appends them to `buf`.
+        fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+            let mut chunk = [0; 256];
+            let mut total = 0;
+            loop {
+                match self.read(&mut chunk) {
+                    Ok(0) => return Ok(total),
+                    Ok(n) => {
+                        buf.extend_from_slice(&chunk[..n]);
+                        total += n;
+                    }
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        /// Makes an adapter that reads at most `limit` bytes from this source.
+        fn take(self, limit: u64) -> Take<Self>
+        where
+            Self: Sized,
+        {
+            Take { inner: self, limit }
+        }
+
+        /// Makes an adapter that reads from this source and then from `next`.
+        fn chain<R: Read>(self, next: R) -> Chain<Self, R>
+        where
+            Self: Sized,
+        {
+            Chain {
+                first: self,
+                second: next,
+                done_first: false,
+            }
+        }
+
+        /// Makes a "by reference" adapter for this instance of `Read`.
+        fn by_ref(&mut self) -> &mut Self
+        where
+            Self: Sized,
+        {
+            self
+        }
+    }
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+    }
+    impl<R: Read + ?Sized> Read for Box<R> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+    }
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = buf.len().min(self.len());
+            let (a, b) = self.split_at(n);
+            buf[..n].copy_from_slice(a);
+            *self = b;
+            Ok(n)
+        }
+    }
+
+    /// The `Write` trait allows for writing bytes to a destination (the counterpart of `std::io::Write`).
+    pub trait Write {
+        /// Writes some bytes of `buf` into this destination, and returns how many bytes were written.
+        fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+        /// Flushes the buffered bytes (if any) to the destination.
+        fn flush(&mut self) -> Result<()>;
+
+        /// Writes the whole of `buf` into this destination.
+        fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.write(buf) {
+                    Ok(0) => {
+                        return Err(Error::new(
+                            ErrorKind::WriteZero,
+                            "failed to write whole buffer",
+                        ))
+                    }
+                    Ok(n) => buf = &buf[n..],
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        }
+    }
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            (**self).write(buf)
+        }
+        fn flush(&mut self) -> Result<()> {
+            (**self).flush()
+        }
+    }
+    impl<W: Write + ?Sized> Write for Box<W> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            (**self).write(buf)
+        }
+        fn flush(&mut self) -> Result<()> {
+            (**self).flush()
+        }
+    }
+    impl Write for Vec<u8> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+    impl Write for &mut [u8] {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            let n = buf.len().min(self.len());
+            let (a, b) = core::mem::take(self).split_at_mut(n);
+            a.copy_from_slice(&buf[..n]);
+            *self = b;
+            Ok(n)
+        }
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Reader adapter that limits the number of bytes read (see `Read::take`).
+    #[derive(Debug)]
+    pub struct Take<R> {
+        inner: R,
+        limit: u64,
+    }
+    impl<R> Take<R> {
+        /// Returns the number of bytes that can be read before this instance returns EOF.
+        pub fn limit(&self) -> u64 {
+            self.limit
+        }
+
+        /// Returns a reference to the underlaying reader.
+        pub fn get_ref(&self) -> &R {
+            &self.inner
+        }
+
+        /// Returns a mutable reference to the underlaying reader.
+        pub fn get_mut(&mut self) -> &mut R {
+            &mut self.inner
+        }
+
+        /// Takes ownership of `self`, and returns the underlaying reader.
+        pub fn into_inner(self) -> R {
+            self.inner
+        }
+    }
+    impl<R: Read> Read for Take<R> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let max = buf
+                .len()
+                .min(usize::try_from(self.limit).unwrap_or(usize::MAX));
+            let n = self.inner.read(&mut buf[..max])?;
+            self.limit -= n as u64;
+            Ok(n)
+        }
+    }
+
+    /// Reader adapter that chains two readers (see `Read::chain`).
+    #[derive(Debug)]
+    pub struct Chain<T, U> {
+        first: T,
+        second: U,
+        done_first: bool,
+    }
+    impl<T: Read, U: Read> Read for Chain<T, U> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            if !self.done_first {
+                match self.first.read(buf)? {
+                    0 if !buf.is_empty() => self.done_first = true,
+                    n => return Ok(n),
+                }
+            }
+            self.second.read(buf)
+        }
+    }
+
+    /// Reader that is always at the end of the stream (see `empty`).
+    #[derive(Debug, Default, Clone, Copy)]
+    pub struct Empty;
+    impl Read for Empty {
+        fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
+            Ok(0)
+        }
+    }
+
+    /// Makes a reader that is always at the end of the stream.
+    pub fn empty() -> Empty {
+        Empty
+    }
+
+    /// Extends `Read` with methods for reading numbers (the counterpart of `byteorder::ReadBytesExt`).
+    #[allow(missing_docs)]
+    pub trait ReadBytesExt: Read {
+        fn read_u8(&mut self) -> Result<u8> {
+            let mut buf = [0; 1];
+            self.read_exact(&mut buf)?;
+            Ok(buf[0])
+        }
+        fn read_i8(&mut self) -> Result<i8> {
+            self.read_u8().map(|n| n as i8)
+        }
+        fn read_u16<T: ByteOrder>(&mut self) -> Result<u16> {
+            let mut buf = [0; 2];
+            self.read_exact(&mut buf)?;
+            Ok(T::read_u16(&buf))
+        }
+        fn read_u24<T: ByteOrder>(&mut self) -> Result<u32> {
+            let mut buf = [0; 3];
+            self.read_exact(&mut buf)?;
+            Ok(T::read_u24(&buf))
+        }
+        fn read_u32<T: ByteOrder>(&mut self) -> Result<u32> {
+            let mut buf = [0; 4];
+            self.read_exact(&mut buf)?;
+            Ok(T::read_u32(&buf))
+        }
+        fn read_u64<T: ByteOrder>(&mut self) -> Result<u64> {
+            let mut buf = [0; 8];
+            self.read_exact(&mut buf)?;
+            Ok(T::read_u64(&buf))
+        }
+        fn read_uint<T: ByteOrder>(&mut self, nbytes: usize) -> Result<u64> {
+            let mut buf = [0; 8];
+            self.read_exact(&mut buf[..nbytes])?;
+            Ok(T::read_uint(&buf[..nbytes], nbytes))
+        }
+        fn read_u16_into<T: ByteOrder>(&mut self, dst: &mut [u16]) -> Result<()> {
+            for n in dst {
+                *n = self.read_u16::<T>()?;
+            }
+            Ok(())
+        }
+    }
+    impl<R: Read + ?Sized> ReadBytesExt for R {}
+
+    /// Extends `Write` with methods for writing numbers (the counterpart of `byteorder::WriteBytesExt`).
+    #[allow(missing_docs)]
+    pub trait WriteBytesExt: Write {
+        fn write_u8(&mut self, n: u8) -> Result<()> {
+            self.write_all(&[n])
+        }
+        fn write_i8(&mut self, n: i8) -> Result<()> {
+            self.write_u8(n as u8)
+        }
+        fn write_u16<T: ByteOrder>(&mut self, n: u16) -> Result<()> {
+            let mut buf = [0; 2];
+            T::write_u16(&mut buf, n);
+            self.write_all(&buf)
+        }
+        fn write_u24<T: ByteOrder>(&mut self, n: u32) -> Result<()> {
+            let mut buf = [0; 3];
+            T::write_u24(&mut buf, n);
+            self.write_all(&buf)
+        }
+        fn write_u32<T: ByteOrder>(&mut self, n: u32) -> Result<()> {
+            let mut buf = [0; 4];
+            T::write_u32(&mut buf, n);
+            self.write_all(&buf)
+        }
+        fn write_u64<T: ByteOrder>(&mut self, n: u64) -> Result<()> {
+            let mut buf = [0; 8];
+            T::write_u64(&mut buf, n);
+            self.write_all(&buf)
+        }
+        fn write_uint<T: ByteOrder>(&mut self, n: u64, nbytes: usize) -> Result<()> {
+            let mut buf = [0; 8];
+            T::write_uint(&mut buf, n, nbytes);
+            self.write_all(&buf[..nbytes])
+        }
+    }
+    impl<W: Write + ?Sized> WriteBytesExt for W {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use byteorder::BigEndian;
+
+    #[test]
+    fn adapters() {
+        let mut reader = (&[1, 2, 3][..]).take(2).chain(&[4, 5][..]);
+        let mut buf = Vec::new();
+        assert_eq!(reader.read_to_end(&mut buf).unwrap(), 4);
+        assert_eq!(buf, [1, 2, 4, 5]);
+
+        let mut buf = [0; 3];
+        let mut writer = &mut buf[..];
+        assert_eq!(writer.write(&[1, 2, 3, 4]).unwrap(), 3);
+        assert_eq!(
+            writer.write_all(&[5]).unwrap_err().kind(),
+            ErrorKind::WriteZero
+        );
+        assert_eq!(buf, [1, 2, 3]);
+
+        let mut buf = [0; 2];
+        let e = empty().read_exact(&mut buf).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn numbers() {
+        let mut buf = Vec::new();
+        buf.write_u8(1).unwrap();
+        buf.write_u16::<BigEndian>(0x0203).unwrap();
+        buf.write_u24::<BigEndian>(0x04_0506).unwrap();
+        buf.write_uint::<BigEndian>(0x07_0809_0A0B, 5).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+
+        let mut reader = &buf[..];
+        assert_eq!(reader.read_u8().unwrap(), 1);
+        assert_eq!(reader.read_u16::<BigEndian>().unwrap(), 0x0203);
+        assert_eq!(reader.read_u24::<BigEndian>().unwrap(), 0x04_0506);
+        assert_eq!(reader.read_uint::<BigEndian>(5).unwrap(), 0x07_0809_0A0B);
+        assert!(reader.read_u8().is_err());
+    }
+}
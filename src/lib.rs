@@ -12,29 +12,46 @@
 //! - [MPEG transport stream](https://en.wikipedia.org/wiki/MPEG_transport_stream)
 //! - [Program-specific information](https://en.wikipedia.org/wiki/Program-specific_information)
 //! - [Packetized elementary stream](https://en.wikipedia.org/wiki/Packetized_elementary_stream)
+//!
+//! # Features
+//!
+//! - `std` (enabled by default): Uses `std::io` for the readers and writers, and enables the items that need the
+//!   standard library (e.g., seeking, pacing, and the integration with `std::error::Error`).
+//!   Without it, the crate is `no_std` (but requires `alloc`), and the I/O traits in [`io`] are used instead.
+//! - `arbitrary`: Implements `arbitrary::Arbitrary` for the packet and table types
+//!   (only values that can be written and read back are generated).
+//! - `serde`: Implements `Serialize` and `Deserialize` for the packet and table types.
+//! - `tokio`: Adds the asynchronous reader and writer traits.
+//! - `trackable`: Makes `Error` a [trackable](https://crates.io/crates/trackable) error
+//!   that records the locations it has passed through.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![warn(missing_docs)]
+extern crate alloc;
+#[cfg(feature = "trackable")]
 #[macro_use]
 extern crate trackable;
 
 pub use error::{Error, ErrorKind};
 
+#[cfg(not(feature = "trackable"))]
+#[macro_use]
+mod macros;
+
 macro_rules! track_io {
     ($expr:expr) => {
-        $expr.map_err(|e: ::std::io::Error| {
-            use trackable::error::ErrorKindExt;
-            track!(crate::Error::from(crate::ErrorKind::Other.cause(e)))
-        })
+        $expr.map_err(|e: crate::io::Error| track!(crate::Error::from(e)))
     };
 }
 
+pub mod crc;
 pub mod es;
+pub mod io;
 pub mod pes;
 pub mod time;
 pub mod ts;
 
-mod crc;
 mod error;
 mod util;
 
 /// This crate specific `Result` type.
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
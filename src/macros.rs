@@ -0,0 +1,137 @@
+//! Plain versions of the `trackable` macros used when the `trackable` feature is disabled.
+//!
+//! They have the same syntax as the originals (for the forms used in this crate),
+//! but only keep the messages instead of recording the locations.
+
+macro_rules! track {
+    ($target:expr) => {
+        $target
+    };
+    ($target:expr, $($format_arg:tt)+) => {
+        crate::error::Track::track($target, || ::alloc::format!($($format_arg)+))
+    };
+}
+
+macro_rules! track_panic {
+    ($error_kind:expr) => {
+        return Err(From::from(crate::Error::from($error_kind)))
+    };
+    ($error_kind:expr, $($format_arg:tt)+) => {
+        return Err(From::from(crate::Error::with_message(
+            $error_kind,
+            ::alloc::format!($($format_arg)+),
+        )))
+    };
+}
+
+macro_rules! track_assert {
+    ($cond:expr, $error_kind:expr) => {
+        if !$cond {
+            track_panic!($error_kind, "assertion failed: `{}`", stringify!($cond));
+        }
+    };
+    ($cond:expr, $error_kind:expr, $($format_arg:tt)+) => {
+        if !$cond {
+            track_panic!(
+                $error_kind,
+                "assertion failed: `{}`; {}",
+                stringify!($cond),
+                format_args!($($format_arg)+)
+            );
+        }
+    };
+}
+
+macro_rules! track_assert_eq {
+    ($left:expr, $right:expr, $error_kind:expr) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                track_assert!(
+                    *left == *right,
+                    $error_kind,
+                    "left={:?}, right={:?}",
+                    left,
+                    right
+                );
+            }
+        }
+    };
+    ($left:expr, $right:expr, $error_kind:expr, $($format_arg:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                track_assert!(
+                    *left == *right,
+                    $error_kind,
+                    "left={:?}, right={:?}; {}",
+                    left,
+                    right,
+                    format_args!($($format_arg)+)
+                );
+            }
+        }
+    };
+}
+
+macro_rules! track_assert_ne {
+    ($left:expr, $right:expr, $error_kind:expr) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                track_assert!(
+                    *left != *right,
+                    $error_kind,
+                    "left={:?}, right={:?}",
+                    left,
+                    right
+                );
+            }
+        }
+    };
+    ($left:expr, $right:expr, $error_kind:expr, $($format_arg:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                track_assert!(
+                    *left != *right,
+                    $error_kind,
+                    "left={:?}, right={:?}; {}",
+                    left,
+                    right,
+                    format_args!($($format_arg)+)
+                );
+            }
+        }
+    };
+}
+
+macro_rules! track_assert_some {
+    ($expr:expr, $error_kind:expr) => {
+        match $expr {
+            Some(v) => v,
+            None => track_panic!(
+                $error_kind,
+                "assertion failed: `{}.is_some()`",
+                stringify!($expr)
+            ),
+        }
+    };
+    ($expr:expr, $error_kind:expr, $($format_arg:tt)+) => {
+        match $expr {
+            Some(v) => v,
+            None => track_panic!(
+                $error_kind,
+                "assertion failed: `{}.is_some()`; {}",
+                stringify!($expr),
+                format_args!($($format_arg)+)
+            ),
+        }
+    };
+}
+
+#[cfg(test)]
+macro_rules! track_try_unwrap {
+    ($expr:expr) => {
+        match $expr {
+            Ok(v) => v,
+            Err(e) => panic!("\nEXPRESSION: {}\nERROR: {}\n", stringify!($expr), e),
+        }
+    };
+}
@@ -0,0 +1,20 @@
+use crate::es::StreamType;
+use crate::ts::Pid;
+use crate::Result;
+use alloc::{boxed::Box, vec::Vec};
+
+/// The `SampleDecrypter` trait allows for decrypting the data of PES packets
+/// of the AES-128-CBC stream types (see `StreamType::is_aes128_cbc`).
+///
+/// Note that this crate does not provide the cipher or the key management itself.
+pub trait SampleDecrypter {
+    /// Decrypts the data of a PES packet in place.
+    ///
+    /// `data` may be resized (e.g., to remove the padding).
+    fn decrypt(&mut self, stream_type: StreamType, pid: Pid, data: &mut Vec<u8>) -> Result<()>;
+}
+impl<T: SampleDecrypter + ?Sized> SampleDecrypter for Box<T> {
+    fn decrypt(&mut self, stream_type: StreamType, pid: Pid, data: &mut Vec<u8>) -> Result<()> {
+        (**self).decrypt(stream_type, pid, data)
+    }
+}
@@ -3,8 +3,17 @@
 //! # References
 //!
 //! - [Packetized elementary stream](https://en.wikipedia.org/wiki/Packetized_elementary_stream)
-pub use self::packet::{PesHeader, PesPacket};
-pub use self::reader::{PesPacketReader, ReadPesPacket};
+pub use self::decrypter::SampleDecrypter;
+pub use self::packet::{
+    DsmTrickMode, PStdBuffer, PesExtension, PesHeader, PesHeaderBuilder, PesPacket,
+    ProgramPacketSequenceCounter,
+};
+#[cfg(feature = "tokio")]
+pub use self::reader::AsyncReadPesPacket;
+pub use self::reader::{PesPacketReader, PesPackets, ReadPesPacket};
+pub use self::writer::{PesPacketWriter, WritePesPacket};
 
+mod decrypter;
 mod packet;
 mod reader;
+mod writer;
@@ -1,9 +1,12 @@
-use crate::es::StreamId;
+use crate::es::{StreamId, StreamType};
+use crate::io::{Read, ReadBytesExt, Write, WriteBytesExt};
 use crate::time::{ClockReference, Timestamp};
+use crate::ts::Pid;
 use crate::util;
 use crate::{ErrorKind, Result};
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{Read, Write};
+use alloc::{vec, vec::Vec};
+use byteorder::BigEndian;
+use core::fmt;
 
 const PACKET_START_CODE_PREFIX: u64 = 0x00_0001;
 
@@ -11,15 +14,56 @@ const PACKET_START_CODE_PREFIX: u64 = 0x00_0001;
 #[allow(missing_docs)]
 #[derive(Debug, Clone)]
 pub struct PesPacket<B> {
+    /// PID of the TS packets that carried this PES packet.
+    pub pid: Pid,
+
+    /// Stream type declared for `pid` by the PMT (if the PMT has been received).
+    pub stream_type: Option<StreamType>,
+
+    /// The most recent PCR of the program seen up to the TS packet that started this PES packet
+    /// (including the PCR in that packet).
+    ///
+    /// This is `None` if the PMT or a PCR of the program has not been received yet.
+    pub pcr: Option<ClockReference>,
+
+    /// `random_access_indicator` in the adaptation field of the TS packet that started this PES packet.
+    ///
+    /// This is `false` if the TS packet has no adaptation field.
+    pub random_access_indicator: bool,
+
+    /// `discontinuity_indicator` in the adaptation field of the TS packet that started this PES packet.
+    ///
+    /// This is `false` if the TS packet has no adaptation field.
+    pub discontinuity_indicator: bool,
+
     pub header: PesHeader,
     pub data: B,
 }
+impl<B> PesPacket<B> {
+    /// Makes a new `PesPacket` instance carried by `pid`.
+    ///
+    /// The stream type and PCR are set to `None`, and the indicators are set to `false`.
+    pub fn new(pid: Pid, header: PesHeader, data: B) -> Self {
+        PesPacket {
+            pid,
+            stream_type: None,
+            pcr: None,
+            random_access_indicator: false,
+            discontinuity_indicator: false,
+            header,
+            data,
+        }
+    }
+}
 
 /// PES packet header.
 ///
 /// Note that `PesHeader` contains the fields that belong to the optional PES header.
+/// If the stream identifier has no optional header (see `StreamId::has_optional_header`),
+/// those fields are ignored when writing and set to `false`/`None` when reading.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PesHeader {
     pub stream_id: StreamId,
     pub priority: bool,
@@ -39,13 +83,97 @@ pub struct PesHeader {
 
     /// Elementary stream clock reference.
     pub escr: Option<ClockReference>,
+
+    /// Elementary stream rate in units of 50 bytes/second (22 bits).
+    pub es_rate: Option<u32>,
+
+    pub trick_mode: Option<DsmTrickMode>,
+
+    /// Additional copyright information (7 bits).
+    pub additional_copy_info: Option<u8>,
+
+    /// CRC of the previous PES packet.
+    pub previous_crc: Option<u16>,
+
+    pub extension: Option<PesExtension>,
 }
 impl PesHeader {
-    pub(super) fn optional_header_len(&self) -> u16 {
-        3 + self.pts.map_or(0, |_| 5) + self.dts.map_or(0, |_| 5) + self.escr.map_or(0, |_| 6)
+    /// Maximum elementary stream rate value.
+    pub const MAX_ES_RATE: u32 = (1 << 22) - 1;
+
+    /// Maximum additional copy info value.
+    pub const MAX_ADDITIONAL_COPY_INFO: u8 = (1 << 7) - 1;
+
+    /// Makes a new `PesHeader` instance that has no optional fields.
+    ///
+    /// `data_alignment_indicator` is set to `true` if `stream_id` is a video identifier, otherwise `false`.
+    pub fn new(stream_id: StreamId) -> Self {
+        PesHeader {
+            stream_id,
+            priority: false,
+            data_alignment_indicator: stream_id.is_video(),
+            copyright: false,
+            original_or_copy: false,
+            pts: None,
+            dts: None,
+            escr: None,
+            es_rate: None,
+            trick_mode: None,
+            additional_copy_info: None,
+            previous_crc: None,
+            extension: None,
+        }
+    }
+
+    /// Makes a new `PesHeaderBuilder` instance.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mpeg2ts::es::StreamId;
+    /// use mpeg2ts::pes::PesHeader;
+    /// use mpeg2ts::time::Timestamp;
+    ///
+    /// let header = PesHeader::builder(StreamId::new(0xE0))
+    ///     .pts(Timestamp::new(3600).unwrap())
+    ///     .dts(Timestamp::new(0).unwrap())
+    ///     .build()
+    ///     .unwrap();
+    /// assert!(header.data_alignment_indicator);
+    ///
+    /// // DTS without PTS
+    /// assert!(PesHeader::builder(StreamId::new(0xE0))
+    ///     .dts(Timestamp::new(0).unwrap())
+    ///     .build()
+    ///     .is_err());
+    /// ```
+    pub fn builder(stream_id: StreamId) -> PesHeaderBuilder {
+        PesHeaderBuilder {
+            header: Self::new(stream_id),
+        }
+    }
+
+    pub(crate) fn optional_header_len(&self) -> u16 {
+        if !self.stream_id.has_optional_header() {
+            return 0;
+        }
+        3 + self.pts.map_or(0, |_| 5)
+            + self.dts.map_or(0, |_| 5)
+            + self.escr.map_or(0, |_| 6)
+            + self.es_rate.map_or(0, |_| 3)
+            + self.trick_mode.map_or(0, |_| 1)
+            + self.additional_copy_info.map_or(0, |_| 1)
+            + self.previous_crc.map_or(0, |_| 2)
+            + self
+                .extension
+                .as_ref()
+                .map_or(0, |x| x.external_size() as u16)
     }
 
-    pub(crate) fn read_from<R: Read>(mut reader: R) -> Result<(Self, u16)> {
+    /// Reads a PES header.
+    ///
+    /// If `lenient` is `true`, invalid marker bits in PTS and DTS fields are tolerated.
+    pub(crate) fn read_from<R: Read>(mut reader: R, lenient: bool) -> Result<(Self, u16)> {
         let packet_start_code_prefix = track_io!(reader.read_uint::<BigEndian>(3))?;
         track_assert_eq!(
             packet_start_code_prefix,
@@ -55,6 +183,9 @@ impl PesHeader {
 
         let stream_id = StreamId::new(track_io!(reader.read_u8())?);
         let packet_len = track_io!(reader.read_u16::<BigEndian>())?;
+        if !stream_id.has_optional_header() {
+            return Ok((PesHeader::new(stream_id), packet_len));
+        }
 
         let b = track_io!(reader.read_u8())?;
         track_assert_eq!(
@@ -81,24 +212,27 @@ impl PesHeader {
         let additional_copy_info_flag = (b & 0b0000_0100) != 0;
         let crc_flag = (b & 0b0000_0010) != 0;
         let extension_flag = (b & 0b0000_0001) != 0;
-        track_assert!(!es_rate_flag, ErrorKind::Unsupported);
-        track_assert!(!dsm_trick_mode_flag, ErrorKind::Unsupported);
-        track_assert!(!additional_copy_info_flag, ErrorKind::Unsupported);
-        track_assert!(!crc_flag, ErrorKind::Unsupported);
-        track_assert!(!extension_flag, ErrorKind::Unsupported);
 
         let pes_header_len = track_io!(reader.read_u8())?;
 
         let mut reader = reader.take(u64::from(pes_header_len));
         let pts = if pts_flag {
             let check_bits = if dts_flag { 3 } else { 2 };
-            Some(track!(Timestamp::read_from(&mut reader, check_bits))?)
+            Some(track!(Timestamp::read_from(
+                &mut reader,
+                check_bits,
+                lenient
+            ))?)
         } else {
             None
         };
         let dts = if dts_flag {
             let check_bits = 1;
-            Some(track!(Timestamp::read_from(&mut reader, check_bits))?)
+            Some(track!(Timestamp::read_from(
+                &mut reader,
+                check_bits,
+                lenient
+            ))?)
         } else {
             None
         };
@@ -107,6 +241,45 @@ impl PesHeader {
         } else {
             None
         };
+        let es_rate = if es_rate_flag {
+            let n = track_io!(reader.read_uint::<BigEndian>(3))? as u32;
+            track_assert_eq!(
+                n & 0x80_0001,
+                0x80_0001,
+                ErrorKind::InvalidInput,
+                "Unexpected marker bits"
+            );
+            Some((n >> 1) & Self::MAX_ES_RATE)
+        } else {
+            None
+        };
+        let trick_mode = if dsm_trick_mode_flag {
+            Some(DsmTrickMode::from_u8(track_io!(reader.read_u8())?))
+        } else {
+            None
+        };
+        let additional_copy_info = if additional_copy_info_flag {
+            let b = track_io!(reader.read_u8())?;
+            track_assert_eq!(
+                b & 0b1000_0000,
+                0b1000_0000,
+                ErrorKind::InvalidInput,
+                "Unexpected marker bit"
+            );
+            Some(b & Self::MAX_ADDITIONAL_COPY_INFO)
+        } else {
+            None
+        };
+        let previous_crc = if crc_flag {
+            Some(track_io!(reader.read_u16::<BigEndian>())?)
+        } else {
+            None
+        };
+        let extension = if extension_flag {
+            Some(track!(PesExtension::read_from(&mut reader))?)
+        } else {
+            None
+        };
         track!(util::consume_stuffing_bytes(reader))?;
 
         let header = PesHeader {
@@ -118,6 +291,11 @@ impl PesHeader {
             pts,
             dts,
             escr,
+            es_rate,
+            trick_mode,
+            additional_copy_info,
+            previous_crc,
+            extension,
         };
         Ok((header, packet_len))
     }
@@ -126,6 +304,9 @@ impl PesHeader {
         track_io!(writer.write_uint::<BigEndian>(PACKET_START_CODE_PREFIX, 3))?;
         track_io!(writer.write_u8(self.stream_id.as_u8()))?;
         track_io!(writer.write_u16::<BigEndian>(pes_header_len))?;
+        if !self.stream_id.has_optional_header() {
+            return Ok(());
+        }
 
         let n = 0b1000_0000
             | ((self.priority as u8) << 3)
@@ -139,10 +320,21 @@ impl PesHeader {
         }
         let n = ((self.pts.is_some() as u8) << 7)
             | ((self.dts.is_some() as u8) << 6)
-            | ((self.escr.is_some() as u8) << 5);
+            | ((self.escr.is_some() as u8) << 5)
+            | ((self.es_rate.is_some() as u8) << 4)
+            | ((self.trick_mode.is_some() as u8) << 3)
+            | ((self.additional_copy_info.is_some() as u8) << 2)
+            | ((self.previous_crc.is_some() as u8) << 1)
+            | self.extension.is_some() as u8;
         track_io!(writer.write_u8(n))?;
 
-        let pes_header_len = self.optional_header_len() as u8 - 3;
+        let data_len = self.optional_header_len() - 3;
+        let pes_header_len = track_assert_some!(
+            u8::try_from(data_len).ok(),
+            ErrorKind::InvalidInput,
+            "Too large PES header data: {} bytes",
+            data_len
+        );
         track_io!(writer.write_u8(pes_header_len))?;
         if let Some(x) = self.pts {
             let check_bits = if self.dts.is_some() { 3 } else { 2 };
@@ -155,7 +347,760 @@ impl PesHeader {
         if let Some(x) = self.escr {
             track!(x.write_escr_to(&mut writer))?;
         }
+        if let Some(x) = self.es_rate {
+            track_assert!(x <= Self::MAX_ES_RATE, ErrorKind::InvalidInput);
+            let n = 0x80_0001 | (x << 1);
+            track_io!(writer.write_uint::<BigEndian>(u64::from(n), 3))?;
+        }
+        if let Some(x) = self.trick_mode {
+            track_io!(writer.write_u8(x.to_u8()))?;
+        }
+        if let Some(x) = self.additional_copy_info {
+            track_assert!(x <= Self::MAX_ADDITIONAL_COPY_INFO, ErrorKind::InvalidInput);
+            track_io!(writer.write_u8(0b1000_0000 | x))?;
+        }
+        if let Some(x) = self.previous_crc {
+            track_io!(writer.write_u16::<BigEndian>(x))?;
+        }
+        if let Some(ref x) = self.extension {
+            track!(x.write_to(&mut writer))?;
+        }
+
+        Ok(())
+    }
+}
+impl fmt::Display for PesHeader {
+    /// Formats a one-line summary of the header, e.g., `SID=0xE0 PTS=93003 DTS=90000`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SID=0x{:02X}", self.stream_id.as_u8())?;
+        if let Some(pts) = self.pts {
+            write!(f, " PTS={}", pts.as_u64())?;
+        }
+        if let Some(dts) = self.dts {
+            write!(f, " DTS={}", dts.as_u64())?;
+        }
+        if let Some(escr) = self.escr {
+            write!(f, " ESCR={}", escr.as_u64())?;
+        }
+        if let Some(es_rate) = self.es_rate {
+            write!(f, " ES_RATE={}", es_rate)?;
+        }
+        if self.data_alignment_indicator {
+            write!(f, " DAI")?;
+        }
+        Ok(())
+    }
+}
+impl Default for PesHeader {
+    /// Returns a header for `private_stream_1` without optional fields.
+    fn default() -> Self {
+        Self::new(StreamId::PRIVATE_STREAM_1)
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for PesHeader {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let stream_id: StreamId = u.arbitrary()?;
+        if !stream_id.has_optional_header() {
+            return Ok(Self::new(stream_id));
+        }
+
+        let pts: Option<Timestamp> = u.arbitrary()?;
+        let dts = if pts.is_some() { u.arbitrary()? } else { None };
+        let es_rate = if u.arbitrary()? {
+            Some(u.int_in_range(0..=Self::MAX_ES_RATE)?)
+        } else {
+            None
+        };
+        let additional_copy_info = if u.arbitrary()? {
+            Some(u.int_in_range(0..=Self::MAX_ADDITIONAL_COPY_INFO)?)
+        } else {
+            None
+        };
+        Ok(PesHeader {
+            stream_id,
+            priority: u.arbitrary()?,
+            data_alignment_indicator: u.arbitrary()?,
+            copyright: u.arbitrary()?,
+            original_or_copy: u.arbitrary()?,
+            pts,
+            dts,
+            escr: u.arbitrary()?,
+            es_rate,
+            trick_mode: u.arbitrary()?,
+            additional_copy_info,
+            previous_crc: u.arbitrary()?,
+            extension: u.arbitrary()?,
+        })
+    }
+}
+
+/// Builder of `PesHeader`.
+///
+/// The consistency of the fields is validated by `build` rather than when writing the header.
+#[derive(Debug, Clone)]
+pub struct PesHeaderBuilder {
+    header: PesHeader,
+}
+impl PesHeaderBuilder {
+    /// Sets `PES_priority`.
+    pub fn priority(mut self, priority: bool) -> Self {
+        self.header.priority = priority;
+        self
+    }
+
+    /// Sets `data_alignment_indicator`.
+    pub fn data_alignment_indicator(mut self, indicator: bool) -> Self {
+        self.header.data_alignment_indicator = indicator;
+        self
+    }
+
+    /// Sets `copyright`.
+    pub fn copyright(mut self, copyright: bool) -> Self {
+        self.header.copyright = copyright;
+        self
+    }
+
+    /// Sets `original_or_copy`.
+    pub fn original_or_copy(mut self, original: bool) -> Self {
+        self.header.original_or_copy = original;
+        self
+    }
+
+    /// Sets the PTS.
+    pub fn pts(mut self, pts: Timestamp) -> Self {
+        self.header.pts = Some(pts);
+        self
+    }
+
+    /// Sets the DTS.
+    pub fn dts(mut self, dts: Timestamp) -> Self {
+        self.header.dts = Some(dts);
+        self
+    }
+
+    /// Sets the ESCR.
+    pub fn escr(mut self, escr: ClockReference) -> Self {
+        self.header.escr = Some(escr);
+        self
+    }
+
+    /// Sets `ES_rate`.
+    pub fn es_rate(mut self, rate: u32) -> Self {
+        self.header.es_rate = Some(rate);
+        self
+    }
+
+    /// Sets the trick mode.
+    pub fn trick_mode(mut self, trick_mode: DsmTrickMode) -> Self {
+        self.header.trick_mode = Some(trick_mode);
+        self
+    }
+
+    /// Sets `additional_copy_info`.
+    pub fn additional_copy_info(mut self, info: u8) -> Self {
+        self.header.additional_copy_info = Some(info);
+        self
+    }
+
+    /// Sets `previous_PES_packet_CRC`.
+    pub fn previous_crc(mut self, crc: u16) -> Self {
+        self.header.previous_crc = Some(crc);
+        self
+    }
+
+    /// Sets the PES extension.
+    pub fn extension(mut self, extension: PesExtension) -> Self {
+        self.header.extension = Some(extension);
+        self
+    }
+
+    /// Builds a `PesHeader` instance.
+    ///
+    /// # Errors
+    ///
+    /// It will return an `ErrorKind::InvalidInput` error if
+    /// - the DTS is set without the PTS,
+    /// - `ES_rate` or `additional_copy_info` is out of range,
+    /// - the pack header or the PES extension field 2 data of the extension is too long,
+    /// - the optional fields do not fit in the 255 bytes of `PES_header_data`, or
+    /// - an optional field is set for a stream identifier that has no optional header.
+    pub fn build(self) -> Result<PesHeader> {
+        let h = self.header;
+        track_assert!(
+            h.dts.is_none() || h.pts.is_some(),
+            ErrorKind::InvalidInput,
+            "DTS without PTS"
+        );
+        if let Some(rate) = h.es_rate {
+            track_assert!(
+                rate <= PesHeader::MAX_ES_RATE,
+                ErrorKind::InvalidInput,
+                "Too large ES rate: {}",
+                rate
+            );
+        }
+        if let Some(info) = h.additional_copy_info {
+            track_assert!(
+                info <= PesHeader::MAX_ADDITIONAL_COPY_INFO,
+                ErrorKind::InvalidInput,
+                "Too large additional copy info: {}",
+                info
+            );
+        }
+        if let Some(ref x) = h.extension {
+            if let Some(ref pack_header) = x.pack_header {
+                track_assert!(
+                    pack_header.len() <= 0xFF,
+                    ErrorKind::InvalidInput,
+                    "Too large pack header: {} bytes",
+                    pack_header.len()
+                );
+            }
+            if let Some(ref data) = x.extension_2 {
+                track_assert!(
+                    data.len() <= PesExtension::MAX_EXTENSION_2_LEN,
+                    ErrorKind::InvalidInput,
+                    "Too large PES extension field 2: {} bytes",
+                    data.len()
+                );
+            }
+        }
+        if h.stream_id.has_optional_header() {
+            let data_len = h.optional_header_len() - 3;
+            track_assert!(
+                data_len <= 0xFF,
+                ErrorKind::InvalidInput,
+                "Too large PES header data: {} bytes",
+                data_len
+            );
+        } else {
+            let has_optional_fields = h.priority
+                || h.data_alignment_indicator
+                || h.copyright
+                || h.original_or_copy
+                || h.pts.is_some()
+                || h.escr.is_some()
+                || h.es_rate.is_some()
+                || h.trick_mode.is_some()
+                || h.additional_copy_info.is_some()
+                || h.previous_crc.is_some()
+                || h.extension.is_some();
+            track_assert!(
+                !has_optional_fields,
+                ErrorKind::InvalidInput,
+                "Stream ID {:#04x} has no optional header",
+                h.stream_id.as_u8()
+            );
+        }
+        Ok(h)
+    }
+}
+
+/// PES extension.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PesExtension {
+    pub private_data: Option<[u8; 16]>,
+
+    /// Pack header (used when the PES packet is carried in a program stream).
+    pub pack_header: Option<Vec<u8>>,
+
+    pub program_packet_sequence_counter: Option<ProgramPacketSequenceCounter>,
+    pub p_std_buffer: Option<PStdBuffer>,
+
+    /// Data of the PES extension field 2 (e.g., `stream_id_extension`).
+    pub extension_2: Option<Vec<u8>>,
+}
+impl PesExtension {
+    /// Maximum length of the PES extension field 2 data.
+    pub const MAX_EXTENSION_2_LEN: usize = (1 << 7) - 1;
+
+    fn external_size(&self) -> usize {
+        1 /* flags */
+            + self.private_data.map_or(0, |_| 16)
+            + self.pack_header.as_ref().map_or(0, |x| 1 + x.len())
+            + self.program_packet_sequence_counter.map_or(0, |_| 2)
+            + self.p_std_buffer.map_or(0, |_| 2)
+            + self.extension_2.as_ref().map_or(0, |x| 1 + x.len())
+    }
+
+    fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+        let b = track_io!(reader.read_u8())?;
+        let private_data_flag = (b & 0b1000_0000) != 0;
+        let pack_header_field_flag = (b & 0b0100_0000) != 0;
+        let program_packet_sequence_counter_flag = (b & 0b0010_0000) != 0;
+        let p_std_buffer_flag = (b & 0b0001_0000) != 0;
+        let extension_flag_2 = (b & 0b0000_0001) != 0;
+
+        let private_data = if private_data_flag {
+            let mut buf = [0; 16];
+            track_io!(reader.read_exact(&mut buf))?;
+            Some(buf)
+        } else {
+            None
+        };
+        let pack_header = if pack_header_field_flag {
+            let len = track_io!(reader.read_u8())?;
+            let mut buf = vec![0; len as usize];
+            track_io!(reader.read_exact(&mut buf))?;
+            Some(buf)
+        } else {
+            None
+        };
+        let program_packet_sequence_counter = if program_packet_sequence_counter_flag {
+            Some(track!(ProgramPacketSequenceCounter::read_from(
+                &mut reader
+            ))?)
+        } else {
+            None
+        };
+        let p_std_buffer = if p_std_buffer_flag {
+            Some(track!(PStdBuffer::read_from(&mut reader))?)
+        } else {
+            None
+        };
+        let extension_2 = if extension_flag_2 {
+            let b = track_io!(reader.read_u8())?;
+            track_assert_eq!(
+                b & 0b1000_0000,
+                0b1000_0000,
+                ErrorKind::InvalidInput,
+                "Unexpected marker bit"
+            );
+            let mut buf = vec![0; (b & 0b0111_1111) as usize];
+            track_io!(reader.read_exact(&mut buf))?;
+            Some(buf)
+        } else {
+            None
+        };
+        Ok(PesExtension {
+            private_data,
+            pack_header,
+            program_packet_sequence_counter,
+            p_std_buffer,
+            extension_2,
+        })
+    }
+
+    fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        let n = ((self.private_data.is_some() as u8) << 7)
+            | ((self.pack_header.is_some() as u8) << 6)
+            | ((self.program_packet_sequence_counter.is_some() as u8) << 5)
+            | ((self.p_std_buffer.is_some() as u8) << 4)
+            | 0b0000_1110
+            | self.extension_2.is_some() as u8;
+        track_io!(writer.write_u8(n))?;
+
+        if let Some(ref x) = self.private_data {
+            track_io!(writer.write_all(x))?;
+        }
+        if let Some(ref x) = self.pack_header {
+            track_assert!(x.len() <= 0xFF, ErrorKind::InvalidInput);
+            track_io!(writer.write_u8(x.len() as u8))?;
+            track_io!(writer.write_all(x))?;
+        }
+        if let Some(x) = self.program_packet_sequence_counter {
+            track!(x.write_to(&mut writer))?;
+        }
+        if let Some(x) = self.p_std_buffer {
+            track!(x.write_to(&mut writer))?;
+        }
+        if let Some(ref x) = self.extension_2 {
+            track_assert!(
+                x.len() <= Self::MAX_EXTENSION_2_LEN,
+                ErrorKind::InvalidInput
+            );
+            track_io!(writer.write_u8(0b1000_0000 | x.len() as u8))?;
+            track_io!(writer.write_all(x))?;
+        }
+        Ok(())
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for PesExtension {
+    /// Generates an extension whose pack header and field 2 data are at most 64 bytes each,
+    /// so that any `PesHeader` fits in a TS packet.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let bytes = |u: &mut arbitrary::Unstructured<'a>| -> arbitrary::Result<Option<Vec<u8>>> {
+            if u.arbitrary()? {
+                let len = u.int_in_range(0..=64)?;
+                Ok(Some(u.bytes(len)?.to_owned()))
+            } else {
+                Ok(None)
+            }
+        };
+        Ok(PesExtension {
+            private_data: u.arbitrary()?,
+            pack_header: bytes(u)?,
+            program_packet_sequence_counter: u.arbitrary()?,
+            p_std_buffer: u.arbitrary()?,
+            extension_2: bytes(u)?,
+        })
+    }
+}
+
+/// Program packet sequence counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProgramPacketSequenceCounter {
+    /// Counter value (7 bits).
+    pub counter: u8,
+
+    /// `true` indicates that the PES packet carries information from an ISO/IEC 11172-1 stream.
+    pub mpeg1_mpeg2_identifier: bool,
+
+    /// Number of stuffing bytes used in the original PES packet header (6 bits).
+    pub original_stuff_length: u8,
+}
+impl ProgramPacketSequenceCounter {
+    /// Maximum counter value.
+    pub const MAX_COUNTER: u8 = (1 << 7) - 1;
+
+    /// Maximum original stuff length.
+    pub const MAX_ORIGINAL_STUFF_LENGTH: u8 = (1 << 6) - 1;
+
+    fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+        let n = track_io!(reader.read_u16::<BigEndian>())?;
+        track_assert_eq!(
+            n & 0b1000_0000_1000_0000,
+            0b1000_0000_1000_0000,
+            ErrorKind::InvalidInput,
+            "Unexpected marker bits"
+        );
+        Ok(ProgramPacketSequenceCounter {
+            counter: ((n >> 8) as u8) & Self::MAX_COUNTER,
+            mpeg1_mpeg2_identifier: (n & 0b0100_0000) != 0,
+            original_stuff_length: (n as u8) & Self::MAX_ORIGINAL_STUFF_LENGTH,
+        })
+    }
+
+    fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        track_assert!(self.counter <= Self::MAX_COUNTER, ErrorKind::InvalidInput);
+        track_assert!(
+            self.original_stuff_length <= Self::MAX_ORIGINAL_STUFF_LENGTH,
+            ErrorKind::InvalidInput
+        );
+        let n = 0b1000_0000_1000_0000
+            | (u16::from(self.counter) << 8)
+            | ((self.mpeg1_mpeg2_identifier as u16) << 6)
+            | u16::from(self.original_stuff_length);
+        track_io!(writer.write_u16::<BigEndian>(n))?;
+        Ok(())
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ProgramPacketSequenceCounter {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ProgramPacketSequenceCounter {
+            counter: u.int_in_range(0..=Self::MAX_COUNTER)?,
+            mpeg1_mpeg2_identifier: u.arbitrary()?,
+            original_stuff_length: u.int_in_range(0..=Self::MAX_ORIGINAL_STUFF_LENGTH)?,
+        })
+    }
+}
+
+/// P-STD buffer information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PStdBuffer {
+    /// `false` means the buffer size unit is 128 bytes, `true` means 1024 bytes.
+    pub scale: bool,
+
+    /// Buffer size in the unit specified by `scale` (13 bits).
+    pub size: u16,
+}
+impl PStdBuffer {
+    /// Maximum buffer size value.
+    pub const MAX_SIZE: u16 = (1 << 13) - 1;
+
+    /// Returns the buffer size in bytes.
+    pub fn size_in_bytes(&self) -> u32 {
+        u32::from(self.size) * if self.scale { 1024 } else { 128 }
+    }
+
+    fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+        let n = track_io!(reader.read_u16::<BigEndian>())?;
+        track_assert_eq!(
+            n >> 14,
+            0b01,
+            ErrorKind::InvalidInput,
+            "Unexpected marker bits"
+        );
+        Ok(PStdBuffer {
+            scale: (n & 0b0010_0000_0000_0000) != 0,
+            size: n & Self::MAX_SIZE,
+        })
+    }
 
+    fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        track_assert!(self.size <= Self::MAX_SIZE, ErrorKind::InvalidInput);
+        let n = 0b0100_0000_0000_0000 | ((self.scale as u16) << 13) | self.size;
+        track_io!(writer.write_u16::<BigEndian>(n))?;
         Ok(())
     }
 }
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for PStdBuffer {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(PStdBuffer {
+            scale: u.arbitrary()?,
+            size: u.int_in_range(0..=Self::MAX_SIZE)?,
+        })
+    }
+}
+
+/// DSM trick mode.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DsmTrickMode {
+    FastForward {
+        field_id: u8,
+        intra_slice_refresh: bool,
+        frequency_truncation: u8,
+    },
+    SlowMotion {
+        rep_cntrl: u8,
+    },
+    FreezeFrame {
+        field_id: u8,
+    },
+    FastReverse {
+        field_id: u8,
+        intra_slice_refresh: bool,
+        frequency_truncation: u8,
+    },
+    SlowReverse {
+        rep_cntrl: u8,
+    },
+    Reserved {
+        trick_mode_control: u8,
+        data: u8,
+    },
+}
+impl DsmTrickMode {
+    fn from_u8(n: u8) -> Self {
+        let trick_mode_control = n >> 5;
+        let field_id = (n >> 3) & 0b11;
+        let intra_slice_refresh = (n & 0b100) != 0;
+        let frequency_truncation = n & 0b11;
+        let rep_cntrl = n & 0b1_1111;
+        match trick_mode_control {
+            0b000 => DsmTrickMode::FastForward {
+                field_id,
+                intra_slice_refresh,
+                frequency_truncation,
+            },
+            0b001 => DsmTrickMode::SlowMotion { rep_cntrl },
+            0b010 => DsmTrickMode::FreezeFrame { field_id },
+            0b011 => DsmTrickMode::FastReverse {
+                field_id,
+                intra_slice_refresh,
+                frequency_truncation,
+            },
+            0b100 => DsmTrickMode::SlowReverse { rep_cntrl },
+            _ => DsmTrickMode::Reserved {
+                trick_mode_control,
+                data: rep_cntrl,
+            },
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            DsmTrickMode::FastForward {
+                field_id,
+                intra_slice_refresh,
+                frequency_truncation,
+            } => {
+                ((field_id & 0b11) << 3)
+                    | ((intra_slice_refresh as u8) << 2)
+                    | (frequency_truncation & 0b11)
+            }
+            DsmTrickMode::SlowMotion { rep_cntrl } => (0b001 << 5) | (rep_cntrl & 0b1_1111),
+            DsmTrickMode::FreezeFrame { field_id } => (0b010 << 5) | ((field_id & 0b11) << 3),
+            DsmTrickMode::FastReverse {
+                field_id,
+                intra_slice_refresh,
+                frequency_truncation,
+            } => {
+                (0b011 << 5)
+                    | ((field_id & 0b11) << 3)
+                    | ((intra_slice_refresh as u8) << 2)
+                    | (frequency_truncation & 0b11)
+            }
+            DsmTrickMode::SlowReverse { rep_cntrl } => (0b100 << 5) | (rep_cntrl & 0b1_1111),
+            DsmTrickMode::Reserved {
+                trick_mode_control,
+                data,
+            } => (trick_mode_control << 5) | (data & 0b1_1111),
+        }
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for DsmTrickMode {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Self::from_u8(u.arbitrary()?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn builder() {
+        let header = PesHeader::builder(StreamId::new(0xC0))
+            .pts(Timestamp::new(1000).unwrap())
+            .es_rate(100)
+            .build()
+            .unwrap();
+        assert!(!header.data_alignment_indicator);
+        assert_eq!(header.pts, Some(Timestamp::new(1000).unwrap()));
+        assert_eq!(header.es_rate, Some(100));
+
+        assert!(PesHeader::new(StreamId::new(0xE0)).data_alignment_indicator);
+        assert_eq!(PesHeader::default().stream_id, StreamId::PRIVATE_STREAM_1);
+
+        let e = PesHeader::builder(StreamId::new(0xC0))
+            .dts(Timestamp::new(0).unwrap())
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+        assert!(PesHeader::builder(StreamId::new(0xC0))
+            .es_rate(PesHeader::MAX_ES_RATE + 1)
+            .build()
+            .is_err());
+        assert!(PesHeader::builder(StreamId::PADDING)
+            .pts(Timestamp::new(0).unwrap())
+            .build()
+            .is_err());
+        assert!(PesHeader::builder(StreamId::PADDING).build().is_ok());
+    }
+
+    #[test]
+    fn optional_fields() {
+        let header = PesHeader {
+            stream_id: StreamId::new(0xE0),
+            priority: false,
+            data_alignment_indicator: true,
+            copyright: false,
+            original_or_copy: false,
+            pts: Some(Timestamp::new(1000).unwrap()),
+            dts: Some(Timestamp::new(900).unwrap()),
+            escr: Some(ClockReference::new(27_000).unwrap()),
+            es_rate: Some(12_345),
+            trick_mode: Some(DsmTrickMode::FastReverse {
+                field_id: 2,
+                intra_slice_refresh: true,
+                frequency_truncation: 1,
+            }),
+            additional_copy_info: Some(0x55),
+            previous_crc: Some(0xBEEF),
+            extension: Some(PesExtension {
+                private_data: Some([7; 16]),
+                pack_header: Some(vec![0, 0, 1, 0xBA]),
+                program_packet_sequence_counter: Some(ProgramPacketSequenceCounter {
+                    counter: 100,
+                    mpeg1_mpeg2_identifier: true,
+                    original_stuff_length: 3,
+                }),
+                p_std_buffer: Some(PStdBuffer {
+                    scale: false,
+                    size: 8000,
+                }),
+                extension_2: Some(vec![0x80, 0x55]),
+            }),
+        };
+
+        let mut buf = Vec::new();
+        let packet_len = header.optional_header_len();
+        header.write_to(&mut buf, packet_len).unwrap();
+        assert_eq!(buf.len(), 6 + packet_len as usize);
+
+        let (actual, actual_packet_len) = PesHeader::read_from(&buf[..], false).unwrap();
+        assert_eq!(actual, header);
+        assert_eq!(actual_packet_len, packet_len);
+    }
+
+    #[test]
+    fn oversized_extension() {
+        let extension = PesExtension {
+            private_data: Some([0; 16]),
+            pack_header: Some(vec![0; 0xFF]),
+            program_packet_sequence_counter: None,
+            p_std_buffer: None,
+            extension_2: Some(vec![0; PesExtension::MAX_EXTENSION_2_LEN]),
+        };
+        let e = PesHeader::builder(StreamId::new(0xE0))
+            .pts(Timestamp::new(0).unwrap())
+            .extension(extension.clone())
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+
+        let mut header = PesHeader::new(StreamId::new(0xE0));
+        header.extension = Some(extension);
+        let mut buf = Vec::new();
+        let e = header
+            .write_to(&mut buf, header.optional_header_len())
+            .err()
+            .unwrap();
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+
+        let mut extension = header.extension.take().unwrap();
+        extension.pack_header = Some(vec![0; 100]);
+        assert!(PesHeader::builder(StreamId::new(0xE0))
+            .extension(extension)
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn p_std_buffer_extension() {
+        // Hand-assembled in the layout of the headers of DVD-Video MPEG-2 video PES packets
+        // (not taken from a capture)
+        let bytes = [
+            0x00, 0x00, 0x01, 0xE0, 0x07, 0xEC, 0x81, 0x81, 0x08, 0x21, 0x00, 0x01, 0x00, 0x01,
+            0x1E, 0x60, 0xE8,
+        ];
+        let (header, packet_len) = PesHeader::read_from(&bytes[..], false).unwrap();
+        assert_eq!(packet_len, 0x07EC);
+        assert_eq!(header.pts, Some(Timestamp::new(0).unwrap()));
+
+        let extension = header.extension.clone().unwrap();
+        assert_eq!(extension.private_data, None);
+        assert_eq!(extension.pack_header, None);
+        assert_eq!(extension.program_packet_sequence_counter, None);
+        assert_eq!(extension.extension_2, None);
+        let p_std_buffer = extension.p_std_buffer.unwrap();
+        assert!(p_std_buffer.scale);
+        assert_eq!(p_std_buffer.size, 232);
+        assert_eq!(p_std_buffer.size_in_bytes(), 232 * 1024);
+
+        let mut buf = Vec::new();
+        header.write_to(&mut buf, packet_len).unwrap();
+        assert_eq!(buf, bytes);
+    }
+
+    #[test]
+    fn no_optional_header() {
+        let bytes = [0x00, 0x00, 0x01, 0xBE, 0x00, 0x04, 0xFF, 0xFF, 0xFF, 0xFF];
+        let mut reader = &bytes[..];
+        let (header, packet_len) = PesHeader::read_from(&mut reader, false).unwrap();
+        assert_eq!(header.stream_id, StreamId::PADDING);
+        assert!(!header.stream_id.has_optional_header());
+        assert_eq!(header.pts, None);
+        assert_eq!(header.optional_header_len(), 0);
+        assert_eq!(packet_len, 4);
+        assert_eq!(reader, [0xFF; 4]);
+
+        let mut buf = Vec::new();
+        header.write_to(&mut buf, packet_len).unwrap();
+        assert_eq!(buf, &bytes[..6]);
+    }
+}
@@ -1,8 +1,17 @@
-use crate::pes::PesPacket;
-use crate::ts::payload::{Bytes, Pes};
-use crate::ts::{Pid, ReadTsPacket, TsPayload};
+use crate::es::StreamType;
+use crate::pes::{PesPacket, SampleDecrypter};
+use crate::time::ClockReference;
+use crate::ts::payload::{Bytes, Pes, Pmt};
+#[cfg(feature = "tokio")]
+use crate::ts::AsyncReadTsPacket;
+use crate::ts::{
+    AdaptationField, Pid, PidTable, ReadTsPacket, TruncatedPacketPolicy, TsPacket, TsPayload,
+};
 use crate::{ErrorKind, Result};
-use std::collections::HashMap;
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+#[cfg(feature = "tokio")]
+use core::future::Future;
 
 /// The `ReadPesPacket` trait allows for reading PES packets from a source.
 pub trait ReadPesPacket {
@@ -10,50 +19,318 @@ pub trait ReadPesPacket {
     ///
     /// If the end of the stream is reached, it will return `Ok(None)`.
     fn read_pes_packet(&mut self) -> Result<Option<PesPacket<Vec<u8>>>>;
+
+    /// Returns an iterator over the PES packets.
+    ///
+    /// The iterator ends when the end of the stream is reached or after the first error is returned.
+    fn packets(&mut self) -> PesPackets<&mut Self>
+    where
+        Self: Sized,
+    {
+        PesPackets::new(self)
+    }
+}
+impl<T: ReadPesPacket + ?Sized> ReadPesPacket for &mut T {
+    fn read_pes_packet(&mut self) -> Result<Option<PesPacket<Vec<u8>>>> {
+        (**self).read_pes_packet()
+    }
+}
+impl<T: ReadPesPacket + ?Sized> ReadPesPacket for Box<T> {
+    fn read_pes_packet(&mut self) -> Result<Option<PesPacket<Vec<u8>>>> {
+        (**self).read_pes_packet()
+    }
 }
 
-/// PES packet reader.
+/// The `AsyncReadPesPacket` trait allows for reading PES packets from an asynchronous source.
+///
+/// `PesPacketReader` implements this trait if the underlaying reader implements `AsyncReadTsPacket`.
+#[cfg(feature = "tokio")]
+pub trait AsyncReadPesPacket {
+    /// Reads a PES packet.
+    ///
+    /// If the end of the stream is reached, it will return `Ok(None)`.
+    fn read_pes_packet(&mut self) -> impl Future<Output = Result<Option<PesPacket<Vec<u8>>>>>;
+}
+
+/// Iterator over the PES packets read by a `ReadPesPacket` implementation.
+///
+/// This is created by `ReadPesPacket::packets` or `PesPacketReader::into_iter`.
 #[derive(Debug)]
+pub struct PesPackets<R> {
+    reader: R,
+    done: bool,
+}
+impl<R: ReadPesPacket> PesPackets<R> {
+    fn new(reader: R) -> Self {
+        PesPackets {
+            reader,
+            done: false,
+        }
+    }
+
+    /// Converts `PesPackets` into the underlaying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+impl<R: ReadPesPacket> Iterator for PesPackets<R> {
+    type Item = Result<PesPacket<Vec<u8>>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = track!(self.reader.read_pes_packet()).transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+impl<R: ReadPesPacket> core::iter::FusedIterator for PesPackets<R> {}
+
+/// PES packet reader.
+///
+/// When the end of the stream is reached, the pending PES packets are returned in ascending order of PID.
+///
+/// The stream types and PCRs of the resulting packets are taken from
+/// the PMT and PCR packets read through the underlaying TS packet reader,
+/// and the random access and discontinuity indicators from the adaptation field of the TS packet
+/// that started each PES packet.
 pub struct PesPacketReader<R> {
     ts_packet_reader: R,
-    pes_packets: HashMap<Pid, PartialPesPacket>,
+    pes_packets: PidTable<Box<PartialPesPacket>>,
+    streams: PidTable<(StreamType, Option<Pid>)>,
+    pcrs: PidTable<ClockReference>,
+    pid_filter: Option<Box<dyn Fn(Pid) -> bool + Send>>,
+    decrypter: Option<Box<dyn SampleDecrypter + Send>>,
+    skipped_pids: PidTable<()>,
+    max_packet_data_size: usize,
+    truncated_packet_policy: TruncatedPacketPolicy,
+    strict_continuation: bool,
+    orphan_payload_count: u64,
+    free_buffers: Vec<Vec<u8>>,
     eos: bool,
 }
-impl<R: ReadTsPacket> PesPacketReader<R> {
+impl<R> PesPacketReader<R> {
+    /// Default value of the maximum size of the data of a PES packet.
+    pub const DEFAULT_MAX_PACKET_DATA_SIZE: usize = 16 * 1024 * 1024;
+
+    /// Maximum number of the buffers kept for reuse (see `recycle`).
+    pub const MAX_FREE_BUFFERS: usize = 8;
+
     /// Makes a new `PesPacketReader` instance.
     pub fn new(ts_packet_reader: R) -> Self {
         PesPacketReader {
             ts_packet_reader,
-            pes_packets: HashMap::new(),
+            pes_packets: PidTable::new(),
+            streams: PidTable::new(),
+            pcrs: PidTable::new(),
+            pid_filter: None,
+            decrypter: None,
+            skipped_pids: PidTable::new(),
+            max_packet_data_size: Self::DEFAULT_MAX_PACKET_DATA_SIZE,
+            truncated_packet_policy: TruncatedPacketPolicy::default(),
+            strict_continuation: false,
+            orphan_payload_count: 0,
+            free_buffers: Vec::new(),
             eos: false,
         }
     }
 
+    /// Sets the filter that selects the PIDs whose PES packets are assembled.
+    ///
+    /// The payloads of the TS packets with PIDs for which `filter` returns `false` are discarded
+    /// without being buffered.
+    /// Note that PAT and PMT packets are still handled by the underlaying TS packet reader,
+    /// so the PIDs of the elementary streams keep being discovered.
+    pub fn set_pid_filter<F>(&mut self, filter: F)
+    where
+        F: Fn(Pid) -> bool + Send + 'static,
+    {
+        self.pid_filter = Some(Box::new(filter));
+    }
+
+    /// Sets the decrypter for the PES packets of the AES-128-CBC stream types (see `StreamType::is_aes128_cbc`).
+    ///
+    /// The data of such packets is decrypted when they are completely assembled, before being returned.
+    /// The stream types are taken from the PMTs, so the packets received before the PMT are not decrypted.
+    /// If no decrypter is set, the data is returned as is.
+    pub fn set_decrypter<D>(&mut self, decrypter: D)
+    where
+        D: SampleDecrypter + Send + 'static,
+    {
+        self.decrypter = Some(Box::new(decrypter));
+    }
+
+    /// Sets the maximum size of the data of a PES packet being assembled.
+    ///
+    /// This bounds the memory used for each PID, e.g., when a stream sets `PES_packet_length` to zero
+    /// and never starts a new PES packet.
+    /// If the data of a PES packet exceeds the limit, the packet is discarded and
+    /// reading it results in an `ErrorKind::SizeLimitExceeded` error.
+    /// The rest of the packet is skipped, so the reading can be continued after the error.
+    ///
+    /// The default value is `PesPacketReader::DEFAULT_MAX_PACKET_DATA_SIZE` (16 MiB).
+    pub fn set_max_packet_data_size(&mut self, size: usize) {
+        self.max_packet_data_size = size;
+    }
+
+    /// Sets the policy for the PES packets truncated by the end of the stream.
+    ///
+    /// With `TruncatedPacketPolicy::TruncatedPacket`, the truncated packet is discarded
+    /// after the `ErrorKind::TruncatedPacket` error is returned, so the reading can be continued.
+    /// Note that the underlaying TS packet reader has its own policy for a truncated TS packet.
+    ///
+    /// The default value is `TruncatedPacketPolicy::InvalidInput`.
+    pub fn set_truncated_packet_policy(&mut self, policy: TruncatedPacketPolicy) {
+        self.truncated_packet_policy = policy;
+    }
+
+    /// Sets whether continuation payloads for PIDs that have no PES packet in progress are rejected.
+    ///
+    /// If `false`, such payloads (e.g., the ones received after joining a live stream
+    /// in the middle of a PES packet) are skipped and counted.
+    /// If `true`, reading such a payload results in an `ErrorKind::InvalidInput` error.
+    /// Note that the latter also applies to the continuation packets of non-PES PIDs.
+    ///
+    /// The default value is `false`.
+    pub fn set_strict_continuation(&mut self, strict: bool) {
+        self.strict_continuation = strict;
+    }
+
+    /// Returns the number of skipped continuation payloads that had no PES packet in progress.
+    pub fn orphan_payload_count(&self) -> u64 {
+        self.orphan_payload_count
+    }
+
     /// Returns a reference to the underlaying TS packet reader.
     pub fn ts_packet_reader(&self) -> &R {
         &self.ts_packet_reader
     }
 
+    /// Returns a mutable reference to the underlaying TS packet reader.
+    pub fn ts_packet_reader_mut(&mut self) -> &mut R {
+        &mut self.ts_packet_reader
+    }
+
+    /// Discards the PES packets being assembled and the PCRs seen so far.
+    ///
+    /// This should be called after seeking the underlaying stream,
+    /// so that the data before and after the seek are not mixed up.
+    /// The stream types learned from PMTs are kept.
+    pub fn reset(&mut self) {
+        self.pes_packets.clear();
+        self.pcrs.clear();
+        self.skipped_pids.clear();
+        self.eos = false;
+    }
+
+    /// Returns the data buffer of a PES packet that is no longer needed, so that it is reused for the subsequent packets.
+    ///
+    /// Recycling the packets returned by `read_pes_packet` keeps the steady-state allocation near zero.
+    /// At most `PesPacketReader::MAX_FREE_BUFFERS` buffers are kept,
+    /// and the buffers larger than the maximum packet data size (see `set_max_packet_data_size`) are dropped.
+    pub fn recycle(&mut self, packet: PesPacket<Vec<u8>>) {
+        self.recycle_buffer(packet.data);
+    }
+
     /// Converts `PesPacketReader` into the underlaying TS packet reader.
     pub fn into_ts_packet_reader(self) -> R {
         self.ts_packet_reader
     }
 
+    fn handle_ts_packet(
+        &mut self,
+        ts_packet: TsPacket,
+        byte_offset: Option<u64>,
+    ) -> Result<Option<PesPacket<Vec<u8>>>> {
+        let header = ts_packet.header;
+        let pid = header.pid;
+        if let Some(pcr) = ts_packet.adaptation_field.as_ref().and_then(|a| a.pcr) {
+            self.pcrs.insert(pid, pcr);
+        }
+        if let Some(TsPayload::Pmt(ref pmt)) = ts_packet.payload {
+            self.handle_pmt(pmt);
+            return Ok(None);
+        }
+        if !self.pid_filter.as_ref().map_or(true, |f| f(pid)) {
+            return Ok(None);
+        }
+        match ts_packet.payload {
+            Some(_)
+                if header.transport_error_indicator
+                    || header.transport_scrambling_control.is_scrambled() =>
+            {
+                let pusi = header.payload_unit_start_indicator;
+                track!(self.handle_unassemblable_payload(pid, pusi))
+            }
+            Some(TsPayload::Pes(payload)) => track!(self.handle_pes_payload(
+                pid,
+                payload,
+                ts_packet.adaptation_field.as_ref(),
+                byte_offset
+            )),
+            Some(TsPayload::Raw(_)) if header.payload_unit_start_indicator => {
+                // PES packet that has scrambled data, or a packet on a non-PES PID
+                track!(self.handle_unassemblable_payload(pid, true))
+            }
+            Some(TsPayload::Raw(payload)) => track!(self.handle_raw_payload(pid, &payload)),
+            _ => Ok(None),
+        }
+    }
+
+    fn decrypt(
+        &mut self,
+        packet: Option<PesPacket<Vec<u8>>>,
+    ) -> Result<Option<PesPacket<Vec<u8>>>> {
+        let Some(mut packet) = packet else {
+            return Ok(None);
+        };
+        let stream_type = packet.stream_type.filter(|t| t.is_aes128_cbc());
+        if let (Some(stream_type), Some(decrypter)) = (stream_type, self.decrypter.as_mut()) {
+            track!(decrypter.decrypt(stream_type, packet.pid, &mut packet.data))?;
+        }
+        Ok(Some(packet))
+    }
+
     fn handle_eos(&mut self) -> Result<Option<PesPacket<Vec<u8>>>> {
-        if let Some(key) = self.pes_packets.keys().next().cloned() {
-            let partial = self.pes_packets.remove(&key).expect("Never fails");
-            track_assert!(
-                partial.data_len.is_none() || partial.data_len == Some(partial.packet.data.len()),
-                ErrorKind::InvalidInput,
-                "Unexpected EOS"
-            );
-            Ok(Some(partial.packet))
-        } else {
-            Ok(None)
+        while let Some((pid, partial)) = self.pes_packets.pop_first() {
+            let completed =
+                partial.data_len.is_none() || partial.data_len == Some(partial.packet.data.len());
+            if completed {
+                return Ok(Some(partial.packet));
+            }
+            match self.truncated_packet_policy {
+                TruncatedPacketPolicy::InvalidInput => {
+                    track_panic!(
+                        ErrorKind::InvalidInput,
+                        "Unexpected EOS: pid={:?}, byte_offset={:?}",
+                        pid,
+                        partial.byte_offset
+                    );
+                }
+                TruncatedPacketPolicy::TruncatedPacket => {
+                    track_panic!(
+                        ErrorKind::TruncatedPacket,
+                        "Truncated PES packet: pid={:?}, byte_offset={:?}",
+                        pid,
+                        partial.byte_offset
+                    );
+                }
+                TruncatedPacketPolicy::Discard => self.recycle_buffer(partial.packet.data),
+            }
         }
+        Ok(None)
     }
 
-    fn handle_pes_payload(&mut self, pid: Pid, pes: Pes) -> Result<Option<PesPacket<Vec<u8>>>> {
+    fn handle_pes_payload(
+        &mut self,
+        pid: Pid,
+        pes: Pes,
+        adaptation_field: Option<&AdaptationField>,
+        byte_offset: Option<u64>,
+    ) -> Result<Option<PesPacket<Vec<u8>>>> {
+        self.skipped_pids.remove(pid);
         let data_len = if pes.pes_packet_len == 0 {
             None
         } else {
@@ -68,21 +345,85 @@ impl<R: ReadTsPacket> PesPacketReader<R> {
             Some((pes.pes_packet_len - optional_header_len) as usize)
         };
 
-        let mut data = Vec::with_capacity(data_len.unwrap_or(pes.data.len()));
+        track_assert!(
+            pes.data.len() <= self.max_packet_data_size,
+            ErrorKind::SizeLimitExceeded,
+            "Too large PES packet data: pid={:?}, size={}",
+            pid,
+            pes.data.len()
+        );
+        let capacity = data_len
+            .unwrap_or(pes.data.len())
+            .min(self.max_packet_data_size);
+        let mut data = self.free_buffers.pop().unwrap_or_default();
+        data.reserve(capacity);
         data.extend_from_slice(&pes.data);
 
+        let stream = self.streams.get(pid).copied();
         let packet = PesPacket {
+            pid,
+            stream_type: stream.map(|(stream_type, _)| stream_type),
+            pcr: stream
+                .and_then(|(_, pcr_pid)| pcr_pid)
+                .and_then(|pcr_pid| self.pcrs.get(pcr_pid).copied()),
+            random_access_indicator: adaptation_field.is_some_and(|a| a.random_access_indicator),
+            discontinuity_indicator: adaptation_field.is_some_and(|a| a.discontinuity_indicator),
             header: pes.header,
             data,
         };
-        let partial = PartialPesPacket { packet, data_len };
-        if let Some(pred) = self.pes_packets.insert(pid, partial) {
+        let partial = PartialPesPacket {
+            packet,
+            data_len,
+            byte_offset,
+        };
+        if let Some(pred) = self.pes_packets.insert(pid, Box::new(partial)) {
             track_assert!(
                 pred.data_len.is_none() || pred.data_len == Some(pred.packet.data.len()),
                 ErrorKind::InvalidInput,
-                "Mismatched PES packet data length: actual={}, expected={}",
+                "Mismatched PES packet data length: actual={}, expected={}, byte_offset={:?}",
+                pred.packet.data.len(),
                 pred.data_len.expect("Never fails"),
-                pred.packet.data.len()
+                pred.byte_offset
+            );
+            Ok(Some(pred.packet))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn handle_pmt(&mut self, pmt: &Pmt) {
+        if !pmt.is_current {
+            return;
+        }
+        for es in &pmt.es_info {
+            self.streams
+                .insert(es.elementary_pid, (es.stream_type, pmt.pcr_pid));
+        }
+    }
+
+    fn handle_unassemblable_payload(
+        &mut self,
+        pid: Pid,
+        payload_unit_start_indicator: bool,
+    ) -> Result<Option<PesPacket<Vec<u8>>>> {
+        // Scrambled or corrupted data can't be assembled,
+        // so it is skipped until the next PES packet starts.
+        self.skipped_pids.insert(pid, ());
+        let pred = self.pes_packets.remove(pid);
+        if !payload_unit_start_indicator {
+            if let Some(pred) = pred {
+                self.recycle_buffer(pred.packet.data);
+            }
+            return Ok(None);
+        }
+        if let Some(pred) = pred {
+            track_assert!(
+                pred.data_len.is_none() || pred.data_len == Some(pred.packet.data.len()),
+                ErrorKind::InvalidInput,
+                "Mismatched PES packet data length: actual={}, expected={}, byte_offset={:?}",
+                pred.packet.data.len(),
+                pred.data_len.expect("Never fails"),
+                pred.byte_offset
             );
             Ok(Some(pred.packet))
         } else {
@@ -91,8 +432,31 @@ impl<R: ReadTsPacket> PesPacketReader<R> {
     }
 
     fn handle_raw_payload(&mut self, pid: Pid, data: &Bytes) -> Result<Option<PesPacket<Vec<u8>>>> {
-        let mut partial =
-            track_assert_some!(self.pes_packets.remove(&pid), ErrorKind::InvalidInput);
+        if self.skipped_pids.contains(pid) {
+            return Ok(None);
+        }
+        let Some(mut partial) = self.pes_packets.remove(pid) else {
+            // The payload is not a part of a PES packet, or the start of the packet was missed
+            track_assert!(
+                !self.strict_continuation,
+                ErrorKind::InvalidInput,
+                "No PES packet in progress: pid={:?}",
+                pid
+            );
+            self.orphan_payload_count += 1;
+            return Ok(None);
+        };
+        if partial.packet.data.len() + data.len() > self.max_packet_data_size {
+            self.skipped_pids.insert(pid, ());
+            self.recycle_buffer(partial.packet.data);
+            track_panic!(
+                ErrorKind::SizeLimitExceeded,
+                "Too large PES packet data: pid={:?}, limit={}, byte_offset={:?}",
+                pid,
+                self.max_packet_data_size,
+                partial.byte_offset
+            );
+        }
         partial.packet.data.extend_from_slice(data);
         if Some(partial.packet.data.len()) == partial.data_len {
             Ok(Some(partial.packet))
@@ -101,36 +465,89 @@ impl<R: ReadTsPacket> PesPacketReader<R> {
                 track_assert!(
                     partial.packet.data.len() <= expected,
                     ErrorKind::InvalidInput,
-                    "Too large PES packet data: actual={}, expected={}",
+                    "Too large PES packet data: actual={}, expected={}, byte_offset={:?}",
                     partial.packet.data.len(),
-                    expected
+                    expected,
+                    partial.byte_offset
                 );
             }
             self.pes_packets.insert(pid, partial);
             Ok(None)
         }
     }
+
+    fn recycle_buffer(&mut self, mut buf: Vec<u8>) {
+        if self.free_buffers.len() < Self::MAX_FREE_BUFFERS
+            && buf.capacity() <= self.max_packet_data_size
+        {
+            buf.clear();
+            self.free_buffers.push(buf);
+        }
+    }
+}
+impl<R: ReadTsPacket> IntoIterator for PesPacketReader<R> {
+    type Item = Result<PesPacket<Vec<u8>>>;
+    type IntoIter = PesPackets<Self>;
+    fn into_iter(self) -> Self::IntoIter {
+        PesPackets::new(self)
+    }
+}
+impl<R: fmt::Debug> fmt::Debug for PesPacketReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PesPacketReader")
+            .field("ts_packet_reader", &self.ts_packet_reader)
+            .field("pes_packets", &self.pes_packets)
+            .field("streams", &self.streams)
+            .field("pcrs", &self.pcrs)
+            .field("pid_filter", &self.pid_filter.as_ref().map(|_| "_"))
+            .field("decrypter", &self.decrypter.as_ref().map(|_| "_"))
+            .field("skipped_pids", &self.skipped_pids)
+            .field("max_packet_data_size", &self.max_packet_data_size)
+            .field("truncated_packet_policy", &self.truncated_packet_policy)
+            .field("strict_continuation", &self.strict_continuation)
+            .field("orphan_payload_count", &self.orphan_payload_count)
+            .field("free_buffers", &self.free_buffers.len())
+            .field("eos", &self.eos)
+            .finish()
+    }
 }
 impl<R: ReadTsPacket> ReadPesPacket for PesPacketReader<R> {
     fn read_pes_packet(&mut self) -> Result<Option<PesPacket<Vec<u8>>>> {
         if self.eos {
-            return track!(self.handle_eos());
+            let packet = track!(self.handle_eos())?;
+            return track!(self.decrypt(packet));
         }
 
         while let Some(ts_packet) = track!(self.ts_packet_reader.read_ts_packet())? {
-            let pid = ts_packet.header.pid;
-            let result = match ts_packet.payload {
-                Some(TsPayload::Pes(payload)) => track!(self.handle_pes_payload(pid, payload))?,
-                Some(TsPayload::Raw(payload)) => track!(self.handle_raw_payload(pid, &payload))?,
-                _ => None,
-            };
-            if result.is_some() {
-                return Ok(result);
+            let byte_offset = self.ts_packet_reader.last_packet_byte_offset();
+            if let Some(packet) = track!(self.handle_ts_packet(ts_packet, byte_offset))? {
+                return track!(self.decrypt(Some(packet)));
             }
         }
 
         self.eos = true;
-        track!(self.handle_eos())
+        let packet = track!(self.handle_eos())?;
+        track!(self.decrypt(packet))
+    }
+}
+#[cfg(feature = "tokio")]
+impl<R: AsyncReadTsPacket> AsyncReadPesPacket for PesPacketReader<R> {
+    async fn read_pes_packet(&mut self) -> Result<Option<PesPacket<Vec<u8>>>> {
+        if self.eos {
+            let packet = track!(self.handle_eos())?;
+            return track!(self.decrypt(packet));
+        }
+
+        while let Some(ts_packet) = track!(self.ts_packet_reader.read_ts_packet().await)? {
+            let byte_offset = self.ts_packet_reader.last_packet_byte_offset();
+            if let Some(packet) = track!(self.handle_ts_packet(ts_packet, byte_offset))? {
+                return track!(self.decrypt(Some(packet)));
+            }
+        }
+
+        self.eos = true;
+        let packet = track!(self.handle_eos())?;
+        track!(self.decrypt(packet))
     }
 }
 
@@ -138,4 +555,451 @@ impl<R: ReadTsPacket> ReadPesPacket for PesPacketReader<R> {
 struct PartialPesPacket {
     packet: PesPacket<Vec<u8>>,
     data_len: Option<usize>,
+
+    // Byte offset of the TS packet that started this PES packet (if known)
+    byte_offset: Option<u64>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::StreamId;
+    use crate::pes::PesHeader;
+    use crate::ts::{
+        ContinuityCounter, EsInfo, TransportScramblingControl, TsHeader, TsPacket, VersionNumber,
+    };
+
+    struct TsPackets(Vec<TsPacket>);
+    impl ReadTsPacket for TsPackets {
+        fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+            Ok(if self.0.is_empty() {
+                None
+            } else {
+                Some(self.0.remove(0))
+            })
+        }
+    }
+
+    fn pes_start_packet(pid: u16, pes_packet_len: u16) -> TsPacket {
+        let header = PesHeader::new(StreamId::new(0xE0));
+        TsPacket {
+            header: TsHeader {
+                transport_error_indicator: false,
+                payload_unit_start_indicator: true,
+                transport_priority: false,
+                pid: Pid::new(pid).unwrap(),
+                transport_scrambling_control: TransportScramblingControl::NotScrambled,
+                continuity_counter: ContinuityCounter::new(),
+            },
+            adaptation_field: None,
+            payload: Some(TsPayload::Pes(Pes {
+                header,
+                pes_packet_len,
+                data: Bytes::new(&[pid as u8; 10]).unwrap(),
+            })),
+            arrival_timestamp: None,
+        }
+    }
+
+    #[test]
+    fn eos_order() {
+        let packets = vec![
+            pes_start_packet(300, 0),
+            pes_start_packet(256, 0),
+            pes_start_packet(280, 0),
+        ];
+        let mut reader = PesPacketReader::new(TsPackets(packets));
+        let mut pids = Vec::new();
+        while let Some(packet) = track_try_unwrap!(reader.read_pes_packet()) {
+            pids.push(packet.data[0]);
+        }
+        assert_eq!(pids, [0x00, 0x18, 0x2C]); // 256, 280, 300
+    }
+
+    #[test]
+    fn iterator() {
+        let packets = vec![
+            pes_start_packet(256, 0),
+            pes_start_packet(256, 3 + 100), // truncated
+            pes_start_packet(300, 0),
+        ];
+        let reader = PesPacketReader::new(TsPackets(packets));
+        let results = reader.into_iter().collect::<Vec<_>>();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+    }
+
+    #[test]
+    fn orphan_payload() {
+        let mut orphan = pes_start_packet(256, 0);
+        orphan.header.payload_unit_start_indicator = false;
+        orphan.payload = Some(TsPayload::Raw(Bytes::new(&[1; 10]).unwrap()));
+        let packets = || vec![orphan.clone(), pes_start_packet(256, 0)];
+
+        let mut reader = PesPacketReader::new(TsPackets(packets()));
+        let packet = track_try_unwrap!(reader.read_pes_packet()).unwrap();
+        assert_eq!(packet.data, [0; 10]);
+        assert_eq!(reader.orphan_payload_count(), 1);
+
+        let mut reader = PesPacketReader::new(TsPackets(packets()));
+        reader.set_strict_continuation(true);
+        assert!(reader.read_pes_packet().is_err());
+    }
+
+    #[test]
+    fn truncated_packet_at_eos() {
+        // The first packet is truncated (its data must be 100 bytes)
+        let packets = || vec![pes_start_packet(256, 3 + 100), pes_start_packet(300, 0)];
+
+        let mut reader = PesPacketReader::new(TsPackets(packets()));
+        assert!(reader.read_pes_packet().is_err());
+
+        let mut reader = PesPacketReader::new(TsPackets(packets()));
+        reader.set_truncated_packet_policy(TruncatedPacketPolicy::Discard);
+        let packet = track_try_unwrap!(reader.read_pes_packet()).unwrap();
+        assert_eq!(packet.data.len(), 10);
+        assert!(track_try_unwrap!(reader.read_pes_packet()).is_none());
+
+        let mut reader = PesPacketReader::new(TsPackets(packets()));
+        reader.set_truncated_packet_policy(TruncatedPacketPolicy::TruncatedPacket);
+        let e = reader.read_pes_packet().err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::TruncatedPacket);
+        let packet = track_try_unwrap!(reader.read_pes_packet()).unwrap();
+        assert_eq!(packet.data.len(), 10);
+        assert!(track_try_unwrap!(reader.read_pes_packet()).is_none());
+    }
+
+    #[test]
+    fn error_position() {
+        use crate::ts::{PidKind, TsPacketReader, TsPacketWriter, WriteTsPacket};
+
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packet(&pes_start_packet(256, 0)));
+        track_try_unwrap!(writer.write_ts_packet(&pes_start_packet(257, 3 + 100)));
+        let bytes = writer.into_stream();
+
+        let pids = [
+            (Pid::new(256).unwrap(), PidKind::Pes),
+            (Pid::new(257).unwrap(), PidKind::Pes),
+        ];
+        let ts_reader = TsPacketReader::with_pids(&bytes[..], pids.into_iter().collect());
+        let mut reader = PesPacketReader::new(ts_reader);
+        assert!(track_try_unwrap!(reader.read_pes_packet()).is_some());
+        let e = reader.read_pes_packet().err().unwrap();
+        assert!(e.to_string().contains("byte_offset=Some(188)"), "{}", e);
+    }
+
+    #[test]
+    fn stream_info() {
+        let mut pmt_packet = pes_start_packet(0x1000, 0);
+        pmt_packet.payload = Some(TsPayload::Pmt(Pmt {
+            program_num: 1,
+            pcr_pid: Some(Pid::new(300).unwrap()),
+            version_number: VersionNumber::new(),
+            is_current: true,
+            program_info: vec![],
+            es_info: vec![EsInfo {
+                stream_type: StreamType::AdtsAac,
+                elementary_pid: Pid::new(256).unwrap(),
+                descriptors: vec![],
+            }],
+            raw: None,
+        }));
+        let mut pcr_packet = pes_start_packet(300, 0);
+        pcr_packet.adaptation_field = Some(AdaptationField {
+            discontinuity_indicator: false,
+            random_access_indicator: false,
+            es_priority_indicator: false,
+            pcr: Some(ClockReference::new(1234).unwrap()),
+            opcr: None,
+            splice_countdown: None,
+            transport_private_data: vec![],
+            extension: None,
+        });
+        pcr_packet.payload = None;
+        let packets = vec![
+            pes_start_packet(256, 0),
+            pmt_packet,
+            pcr_packet,
+            pes_start_packet(256, 0),
+        ];
+
+        let mut reader = PesPacketReader::new(TsPackets(packets));
+        let packet = track_try_unwrap!(reader.read_pes_packet()).unwrap();
+        assert_eq!(packet.pid, Pid::new(256).unwrap());
+        assert_eq!(packet.stream_type, None);
+        assert_eq!(packet.pcr, None);
+
+        let packet = track_try_unwrap!(reader.read_pes_packet()).unwrap();
+        assert_eq!(packet.pid, Pid::new(256).unwrap());
+        assert_eq!(packet.stream_type, Some(StreamType::AdtsAac));
+        assert_eq!(packet.pcr, Some(ClockReference::new(1234).unwrap()));
+    }
+
+    #[test]
+    fn start_packet_flags() {
+        let mut pmt_packet = pes_start_packet(0x1000, 0);
+        pmt_packet.payload = Some(TsPayload::Pmt(Pmt {
+            program_num: 1,
+            pcr_pid: Some(Pid::new(256).unwrap()),
+            version_number: VersionNumber::new(),
+            is_current: true,
+            program_info: vec![],
+            es_info: vec![EsInfo {
+                stream_type: StreamType::H264,
+                elementary_pid: Pid::new(256).unwrap(),
+                descriptors: vec![],
+            }],
+            raw: None,
+        }));
+        let mut key_frame = pes_start_packet(256, 0);
+        key_frame.adaptation_field = Some(
+            AdaptationField::builder()
+                .random_access_indicator(true)
+                .discontinuity_indicator(true)
+                .pcr(ClockReference::new(1234).unwrap())
+                .build()
+                .unwrap(),
+        );
+        let mut continuation = pes_start_packet(256, 0);
+        continuation.header.payload_unit_start_indicator = false;
+        continuation.adaptation_field = Some(AdaptationField::random_access());
+        continuation.payload = Some(TsPayload::Raw(Bytes::new(&[1; 10]).unwrap()));
+        let packets = vec![
+            pmt_packet,
+            key_frame,
+            continuation,
+            pes_start_packet(256, 0),
+        ];
+
+        let mut reader = PesPacketReader::new(TsPackets(packets));
+        let packet = track_try_unwrap!(reader.read_pes_packet()).unwrap();
+        assert!(packet.random_access_indicator);
+        assert!(packet.discontinuity_indicator);
+        assert_eq!(packet.pcr, Some(ClockReference::new(1234).unwrap()));
+        assert_eq!(packet.data.len(), 20);
+
+        // The flags of the continuation packets are not taken into account
+        let packet = track_try_unwrap!(reader.read_pes_packet()).unwrap();
+        assert!(!packet.random_access_indicator);
+        assert!(!packet.discontinuity_indicator);
+        assert_eq!(packet.pcr, Some(ClockReference::new(1234).unwrap()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn split_by_pid() {
+        use crate::ts::TsPacketSplitter;
+
+        let mut pmt_packet = pes_start_packet(0x1000, 0);
+        pmt_packet.payload = Some(TsPayload::Pmt(Pmt {
+            program_num: 1,
+            pcr_pid: Some(Pid::new(256).unwrap()),
+            version_number: VersionNumber::new(),
+            is_current: true,
+            program_info: vec![],
+            es_info: vec![
+                EsInfo {
+                    stream_type: StreamType::H264,
+                    elementary_pid: Pid::new(256).unwrap(),
+                    descriptors: vec![],
+                },
+                EsInfo {
+                    stream_type: StreamType::AdtsAac,
+                    elementary_pid: Pid::new(257).unwrap(),
+                    descriptors: vec![],
+                },
+            ],
+            raw: None,
+        }));
+        let mut pcr_packet = pes_start_packet(256, 0);
+        pcr_packet.adaptation_field = Some(AdaptationField {
+            discontinuity_indicator: false,
+            random_access_indicator: false,
+            es_priority_indicator: false,
+            pcr: Some(ClockReference::new(1234).unwrap()),
+            opcr: None,
+            splice_countdown: None,
+            transport_private_data: vec![],
+            extension: None,
+        });
+        let packets = vec![
+            pmt_packet,
+            pcr_packet,
+            pes_start_packet(257, 0),
+            pes_start_packet(256, 0),
+            pes_start_packet(257, 0),
+        ];
+
+        let (splitter, shards) =
+            track_try_unwrap!(TsPacketSplitter::new(TsPackets(packets), 2, |pid| {
+                usize::from(pid.as_u16())
+            }));
+        let workers = shards
+            .into_iter()
+            .map(|shard| {
+                std::thread::spawn(move || {
+                    let index = shard.index();
+                    let mut reader = PesPacketReader::new(shard);
+                    let mut packets = Vec::new();
+                    while let Some(packet) = track_try_unwrap!(reader.read_pes_packet()) {
+                        packets.push((packet.pid.as_u16(), packet.stream_type, packet.pcr));
+                    }
+                    (index, packets)
+                })
+            })
+            .collect::<Vec<_>>();
+        track_try_unwrap!(splitter.run());
+
+        let pcr = Some(ClockReference::new(1234).unwrap());
+        for worker in workers {
+            let (index, packets) = worker.join().unwrap();
+            let expected = if index == 0 {
+                vec![
+                    (256, Some(StreamType::H264), pcr),
+                    (256, Some(StreamType::H264), pcr),
+                ]
+            } else {
+                vec![
+                    (257, Some(StreamType::AdtsAac), pcr),
+                    (257, Some(StreamType::AdtsAac), pcr),
+                ]
+            };
+            assert_eq!(packets, expected);
+        }
+
+        assert!(TsPacketSplitter::new(TsPackets(vec![]), 0, |_| 0).is_err());
+    }
+
+    #[test]
+    fn pid_filter() {
+        let packets = vec![
+            pes_start_packet(256, 0),
+            pes_start_packet(257, 0),
+            pes_start_packet(256, 0),
+            pes_start_packet(257, 0),
+        ];
+        let mut reader = PesPacketReader::new(TsPackets(packets));
+        reader.set_pid_filter(|pid| pid.as_u16() == 257);
+        let mut pids = Vec::new();
+        while let Some(packet) = track_try_unwrap!(reader.read_pes_packet()) {
+            pids.push(packet.pid.as_u16());
+        }
+        assert_eq!(pids, [257, 257]);
+    }
+
+    #[test]
+    fn max_packet_data_size() {
+        let mut continuation = pes_start_packet(256, 0);
+        continuation.header.payload_unit_start_indicator = false;
+        continuation.payload = Some(TsPayload::Raw(Bytes::new(&[1; 10]).unwrap()));
+        let packets = vec![
+            pes_start_packet(256, 0),
+            continuation.clone(),
+            continuation.clone(),
+            continuation,
+            pes_start_packet(256, 0),
+        ];
+
+        let mut reader = PesPacketReader::new(TsPackets(packets));
+        reader.set_max_packet_data_size(25);
+        let e = reader.read_pes_packet().err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::SizeLimitExceeded);
+
+        // The rest of the oversized packet is skipped
+        let packet = track_try_unwrap!(reader.read_pes_packet()).unwrap();
+        assert_eq!(packet.data, [0; 10]);
+        assert!(track_try_unwrap!(reader.read_pes_packet()).is_none());
+    }
+
+    #[test]
+    fn recycle() {
+        let packets = vec![
+            pes_start_packet(256, 0),
+            pes_start_packet(256, 0),
+            pes_start_packet(256, 0),
+        ];
+        let mut reader = PesPacketReader::new(TsPackets(packets));
+        let packet = track_try_unwrap!(reader.read_pes_packet()).unwrap();
+        let ptr = packet.data.as_ptr();
+        reader.recycle(packet);
+
+        // The second packet had been started before the call, so the buffer is reused by the third one
+        let packet = track_try_unwrap!(reader.read_pes_packet()).unwrap();
+        assert_ne!(packet.data.as_ptr(), ptr);
+        let packet = track_try_unwrap!(reader.read_pes_packet()).unwrap();
+        assert_eq!(packet.data, [0; 10]);
+        assert_eq!(packet.data.as_ptr(), ptr);
+
+        // Too large buffers are not kept
+        reader.set_max_packet_data_size(5);
+        reader.recycle(packet);
+        assert!(reader.free_buffers.is_empty());
+    }
+
+    #[test]
+    fn decrypter() {
+        struct XorDecrypter;
+        impl SampleDecrypter for XorDecrypter {
+            fn decrypt(
+                &mut self,
+                stream_type: StreamType,
+                pid: Pid,
+                data: &mut Vec<u8>,
+            ) -> Result<()> {
+                assert_eq!(stream_type, StreamType::H264WithAes128Cbc);
+                assert_eq!(pid.as_u16(), 256);
+                data.iter_mut().for_each(|b| *b ^= 0xFF);
+                data.pop();
+                Ok(())
+            }
+        }
+
+        let mut pmt_packet = pes_start_packet(0x1000, 0);
+        pmt_packet.payload = Some(TsPayload::Pmt(Pmt {
+            program_num: 1,
+            pcr_pid: None,
+            version_number: VersionNumber::new(),
+            is_current: true,
+            program_info: vec![],
+            es_info: vec![
+                EsInfo {
+                    stream_type: StreamType::H264WithAes128Cbc,
+                    elementary_pid: Pid::new(256).unwrap(),
+                    descriptors: vec![],
+                },
+                EsInfo {
+                    stream_type: StreamType::AdtsAac,
+                    elementary_pid: Pid::new(257).unwrap(),
+                    descriptors: vec![],
+                },
+            ],
+            raw: None,
+        }));
+        let packets = || {
+            vec![
+                pmt_packet.clone(),
+                pes_start_packet(256, 0),
+                pes_start_packet(257, 0),
+                pes_start_packet(256, 0),
+            ]
+        };
+
+        // Pass-through
+        let mut reader = PesPacketReader::new(TsPackets(packets()));
+        let data = reader
+            .packets()
+            .map(|p| track_try_unwrap!(p).data)
+            .collect::<Vec<_>>();
+        assert_eq!(data, [[0; 10], [0; 10], [1; 10]]);
+
+        let mut reader = PesPacketReader::new(TsPackets(packets()));
+        reader.set_decrypter(XorDecrypter);
+        let data = reader
+            .packets()
+            .map(|p| track_try_unwrap!(p).data)
+            .collect::<Vec<_>>();
+        assert_eq!(data, [vec![0xFF; 9], vec![0xFF; 9], vec![1; 10]]);
+    }
 }
@@ -0,0 +1,445 @@
+use crate::es::StreamType;
+use crate::pes::PesPacket;
+use crate::time::{ClockReference, Timestamp};
+use crate::ts::payload::{Bytes, Pat, Pes, Pmt};
+use crate::ts::{
+    AdaptationField, Codec, ContinuityCounter, EsInfo, Pid, PidTable, ProgramAssociation, TsPacket,
+    TsPayload, VersionNumber, WriteTsPacket,
+};
+use crate::{ErrorKind, Result};
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::time::Duration;
+
+/// The `WritePesPacket` trait allows for writing PES packets to a destination.
+pub trait WritePesPacket {
+    /// Writes a PES packet of the elementary stream of `stream_type` carried by `pid`.
+    ///
+    /// `packet.pid` and `packet.stream_type` are ignored in favor of `pid` and `stream_type`.
+    fn write_pes_packet(
+        &mut self,
+        pid: Pid,
+        stream_type: StreamType,
+        packet: &PesPacket<Vec<u8>>,
+    ) -> Result<()>;
+}
+impl<T: WritePesPacket + ?Sized> WritePesPacket for &mut T {
+    fn write_pes_packet(
+        &mut self,
+        pid: Pid,
+        stream_type: StreamType,
+        packet: &PesPacket<Vec<u8>>,
+    ) -> Result<()> {
+        (**self).write_pes_packet(pid, stream_type, packet)
+    }
+}
+impl<T: WritePesPacket + ?Sized> WritePesPacket for Box<T> {
+    fn write_pes_packet(
+        &mut self,
+        pid: Pid,
+        stream_type: StreamType,
+        packet: &PesPacket<Vec<u8>>,
+    ) -> Result<()> {
+        (**self).write_pes_packet(pid, stream_type, packet)
+    }
+}
+
+/// PES packet writer.
+///
+/// The PES packets are written as a single program (`PROGRAM_NUM` with the PMT on `PMT_PID`).
+/// The PAT and PMT are written before the first PES packet and
+/// whenever a new pair of PID and stream type (or codec) is seen (incrementing the version number of the PMT).
+/// They can also be repeated periodically (see `set_psi_interval`).
+///
+/// The first PID written becomes the PCR PID.
+/// The TS packets starting its PES packets carry `PesPacket::pcr`,
+/// or a PCR synthesized from the DTS (or PTS) if it is `None` (see `set_pcr_delay`).
+///
+/// A PES packet too large for `PES_packet_length` is written with the length `0` (unbounded)
+/// if the stream type is video, and is rejected as an `ErrorKind::InvalidInput` error otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::es::{StreamId, StreamType};
+/// use mpeg2ts::pes::{PesHeader, PesPacket, PesPacketWriter, WritePesPacket};
+/// use mpeg2ts::time::Timestamp;
+/// use mpeg2ts::ts::{Pid, TsPacketWriter};
+///
+/// let pid = Pid::new(0x100).unwrap();
+/// let mut writer = PesPacketWriter::new(TsPacketWriter::new(Vec::new()));
+/// for i in 0..10 {
+///     let mut header = PesHeader::new(StreamId::new(0xC0));
+///     header.pts = Some(Timestamp::new(i * 1920).unwrap());
+///     let packet = PesPacket::new(pid, header, vec![0; 300]);
+///     writer.write_pes_packet(pid, StreamType::AdtsAac, &packet).unwrap();
+/// }
+/// let stream = writer.into_ts_packet_writer().into_stream();
+/// assert_eq!(stream.len(), (2 + 10 * 2) * 188);
+/// ```
+#[derive(Debug)]
+pub struct PesPacketWriter<W> {
+    ts_packet_writer: W,
+    pcr_delay: Duration,
+    psi_interval: Option<Duration>,
+    last_psi_timestamp: Option<Timestamp>,
+    streams: Vec<EsInfo>,
+    version_number: Option<VersionNumber>,
+    pat_counter: ContinuityCounter,
+    pmt_counter: ContinuityCounter,
+    counters: PidTable<ContinuityCounter>,
+}
+impl<W> PesPacketWriter<W> {
+    /// Program number of the written program.
+    pub const PROGRAM_NUM: u16 = 1;
+
+    /// PID of the written PMT.
+    pub const PMT_PID: u16 = 0x1000;
+
+    /// Default value of the PCR delay.
+    pub const DEFAULT_PCR_DELAY: Duration = Duration::from_millis(700);
+
+    /// Makes a new `PesPacketWriter` instance.
+    pub fn new(ts_packet_writer: W) -> Self {
+        PesPacketWriter {
+            ts_packet_writer,
+            pcr_delay: Self::DEFAULT_PCR_DELAY,
+            psi_interval: None,
+            last_psi_timestamp: None,
+            streams: Vec::new(),
+            version_number: None,
+            pat_counter: ContinuityCounter::new(),
+            pmt_counter: ContinuityCounter::new(),
+            counters: PidTable::new(),
+        }
+    }
+
+    /// Sets how far the synthesized PCRs precede the DTSs (or PTSs) of the PES packets.
+    ///
+    /// The default value is `DEFAULT_PCR_DELAY`.
+    pub fn set_pcr_delay(&mut self, delay: Duration) {
+        self.pcr_delay = delay;
+    }
+
+    /// Sets the interval at which the PAT and PMT are repeated.
+    ///
+    /// The interval is measured by the DTSs (or PTSs) of the written PES packets, and
+    /// the PAT and PMT are written before the first PES packet whose timestamp is `interval` or more
+    /// after the one at which they were written last time.
+    ///
+    /// The default value is `None` (not repeated).
+    pub fn set_psi_interval(&mut self, interval: Option<Duration>) {
+        self.psi_interval = interval;
+    }
+
+    /// Returns a reference to the underlaying TS packet writer.
+    pub fn ts_packet_writer(&self) -> &W {
+        &self.ts_packet_writer
+    }
+
+    /// Returns a mutable reference to the underlaying TS packet writer.
+    pub fn ts_packet_writer_mut(&mut self) -> &mut W {
+        &mut self.ts_packet_writer
+    }
+
+    /// Converts `PesPacketWriter` into the underlaying TS packet writer.
+    pub fn into_ts_packet_writer(self) -> W {
+        self.ts_packet_writer
+    }
+
+    /// Updates the PMT, and returns `true` if it has changed.
+    fn declare_stream(&mut self, es_info: EsInfo) -> bool {
+        match self
+            .streams
+            .iter_mut()
+            .find(|s| s.elementary_pid == es_info.elementary_pid)
+        {
+            Some(s) if *s == es_info => return false,
+            Some(s) => *s = es_info,
+            None => self.streams.push(es_info),
+        }
+        self.version_number = Some(match self.version_number {
+            None => VersionNumber::new(),
+            Some(mut v) => {
+                v.increment();
+                v
+            }
+        });
+        true
+    }
+
+    fn psi_packets(&mut self) -> Result<[TsPacket; 2]> {
+        let version_number = self.version_number.unwrap_or_default();
+        let pmt_pid = Pid::new_const(Self::PMT_PID);
+        let pat = TsPayload::Pat(Pat {
+            transport_stream_id: 1,
+            version_number,
+            is_current: true,
+            table: vec![ProgramAssociation {
+                program_num: Self::PROGRAM_NUM,
+                program_map_pid: pmt_pid,
+            }],
+            raw: None,
+        });
+        let pmt = TsPayload::Pmt(Pmt {
+            program_num: Self::PROGRAM_NUM,
+            pcr_pid: self.streams.first().map(|s| s.elementary_pid),
+            version_number,
+            is_current: true,
+            program_info: Vec::new(),
+            es_info: self.streams.clone(),
+            raw: None,
+        });
+        let pat = track!(TsPacket::builder(Pid::new_const(Pid::PAT))
+            .continuity(next_counter(&mut self.pat_counter))
+            .payload(pat)
+            .build())?;
+        let pmt = track!(TsPacket::builder(pmt_pid)
+            .continuity(next_counter(&mut self.pmt_counter))
+            .payload(pmt)
+            .build())?;
+        Ok([pat, pmt])
+    }
+
+    fn is_psi_due(&self, timestamp: Option<Timestamp>) -> bool {
+        let (Some(interval), Some(timestamp), Some(last)) =
+            (self.psi_interval, timestamp, self.last_psi_timestamp)
+        else {
+            return false;
+        };
+        let interval = interval.as_nanos() * 9 / 100_000;
+        // Large backward jumps (e.g., discontinuities) are also taken into account
+        u128::from(timestamp.delta(last).unsigned_abs()) >= interval
+    }
+
+    fn pcr(&self, pid: Pid, packet: &PesPacket<Vec<u8>>) -> Option<ClockReference> {
+        if self.streams.first().map(|s| s.elementary_pid) != Some(pid) {
+            return None;
+        }
+        packet.pcr.or_else(|| {
+            let timestamp = packet.header.dts.or(packet.header.pts)?;
+            let delay = self.pcr_delay.as_nanos() * 27 / 1000 % u128::from(ClockReference::MODULUS);
+            Some(
+                ClockReference::from(timestamp)
+                    .wrapping_add(ClockReference::MODULUS - delay as u64),
+            )
+        })
+    }
+
+    fn pes_ts_packets(
+        &mut self,
+        pid: Pid,
+        packet: &PesPacket<Vec<u8>>,
+        packets: &mut Vec<TsPacket>,
+    ) -> Result<()> {
+        let header = &packet.header;
+        let pcr = self.pcr(pid, packet);
+        let adaptation_field =
+            if pcr.is_some() || packet.random_access_indicator || packet.discontinuity_indicator {
+                Some(AdaptationField {
+                    discontinuity_indicator: packet.discontinuity_indicator,
+                    random_access_indicator: packet.random_access_indicator,
+                    pcr,
+                    ..Default::default()
+                })
+            } else {
+                None
+            };
+
+        // The size of the PES packet after the `PES_packet_length` field
+        // (`0` means unbounded, which is only allowed for video streams and checked by `write`)
+        let optional_header_len = usize::from(header.optional_header_len());
+        let pes_packet_len =
+            u16::try_from(optional_header_len + packet.data.len()).unwrap_or_default();
+
+        let mut counter = self.counters.get(pid).copied().unwrap_or_default();
+        let header_len = 6 + optional_header_len;
+        let adaptation_field_len = adaptation_field.as_ref().map_or(0, |a| a.external_size());
+        let capacity = Bytes::MAX_SIZE.saturating_sub(adaptation_field_len + header_len);
+        let (first, mut rest) = packet.data.split_at(capacity.min(packet.data.len()));
+        let mut builder = TsPacket::builder(pid)
+            .continuity(next_counter(&mut counter))
+            .payload_pes(Pes {
+                header: header.clone(),
+                pes_packet_len,
+                data: track!(Bytes::new(first))?,
+            });
+        if let Some(adaptation_field) = adaptation_field {
+            builder = builder.adaptation(adaptation_field);
+        }
+        packets.push(track!(builder.build())?);
+
+        while !rest.is_empty() {
+            let (chunk, remaining) = rest.split_at(Bytes::MAX_SIZE.min(rest.len()));
+            rest = remaining;
+            packets.push(track!(TsPacket::builder(pid)
+                .continuity(next_counter(&mut counter))
+                .payload_raw(chunk)
+                .build())?);
+        }
+        self.counters.insert(pid, counter);
+        Ok(())
+    }
+}
+impl<W: WriteTsPacket> PesPacketWriter<W> {
+    /// Writes a PES packet of the elementary stream of `codec` carried by `pid`.
+    ///
+    /// Unlike `write_pes_packet`, the PMT carries the descriptors identifying the codec
+    /// (see `Codec::stream_type` and `Codec::descriptors`).
+    pub fn write_codec_pes_packet(
+        &mut self,
+        pid: Pid,
+        codec: Codec,
+        packet: &PesPacket<Vec<u8>>,
+    ) -> Result<()> {
+        let es_info = EsInfo::with_codec(pid, codec);
+        track!(self.write(es_info, packet))
+    }
+
+    fn write(&mut self, es_info: EsInfo, packet: &PesPacket<Vec<u8>>) -> Result<()> {
+        let pid = es_info.elementary_pid;
+        let pes_packet_len = usize::from(packet.header.optional_header_len()) + packet.data.len();
+        track_assert!(
+            pes_packet_len <= 0xFFFF || es_info.stream_type.is_video(),
+            ErrorKind::InvalidInput,
+            "Too large PES packet for a non-video stream: {} bytes",
+            pes_packet_len
+        );
+
+        let mut packets = Vec::with_capacity(2 + packet.data.len() / Bytes::MAX_SIZE + 1);
+        let timestamp = packet.header.dts.or(packet.header.pts);
+        if self.declare_stream(es_info) || self.is_psi_due(timestamp) {
+            packets.extend(track!(self.psi_packets())?);
+            self.last_psi_timestamp = timestamp.or(self.last_psi_timestamp);
+        }
+        track!(self.pes_ts_packets(pid, packet, &mut packets))?;
+        track!(self.ts_packet_writer.write_ts_packets(&packets))?;
+        Ok(())
+    }
+}
+impl<W: WriteTsPacket> WritePesPacket for PesPacketWriter<W> {
+    fn write_pes_packet(
+        &mut self,
+        pid: Pid,
+        stream_type: StreamType,
+        packet: &PesPacket<Vec<u8>>,
+    ) -> Result<()> {
+        let es_info = EsInfo {
+            stream_type,
+            elementary_pid: pid,
+            descriptors: Vec::new(),
+        };
+        track!(self.write(es_info, packet))
+    }
+}
+
+fn next_counter(counter: &mut ContinuityCounter) -> ContinuityCounter {
+    let current = *counter;
+    counter.increment();
+    current
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::StreamId;
+    use crate::pes::{PesHeader, PesPacketReader, ReadPesPacket};
+    use crate::ts::{ContinuityChecker, ReadTsPacket, TsPacketReader, TsPacketWriter};
+
+    fn pes_packet(pid: Pid, stream_id: u8, i: u64, size: usize) -> PesPacket<Vec<u8>> {
+        let mut header = PesHeader::new(StreamId::new(stream_id));
+        header.pts = Some(Timestamp::new(90_000 + i * 3003).unwrap());
+        if stream_id == 0xE0 {
+            header.dts = Some(Timestamp::new(90_000 + i * 3003 - 3003).unwrap());
+        }
+        let mut packet = PesPacket::new(pid, header, vec![i as u8; size]);
+        packet.random_access_indicator = i == 0;
+        packet
+    }
+
+    #[test]
+    fn write_pes_packets() {
+        let video = Pid::new(0x100).unwrap();
+        let audio = Pid::new(0x101).unwrap();
+        let mut packets = Vec::new();
+        for i in 0..5 {
+            packets.push((video, StreamType::H264, pes_packet(video, 0xE0, i, 1000)));
+            packets.push((audio, StreamType::AdtsAac, pes_packet(audio, 0xC0, i, 100)));
+        }
+        // Changes the stream type of the audio
+        packets.push((
+            audio,
+            StreamType::DolbyDigitalUpToSixChannelAudio,
+            pes_packet(audio, 0xC0, 5, 0),
+        ));
+
+        let mut writer = PesPacketWriter::new(TsPacketWriter::new(Vec::new()));
+        for (pid, stream_type, packet) in &packets {
+            track_try_unwrap!(writer.write_pes_packet(*pid, *stream_type, packet));
+        }
+        let stream = writer.into_ts_packet_writer().into_stream();
+
+        // Three versions of the PAT and PMT
+        let mut reader = TsPacketReader::new(&stream[..]);
+        let mut checker = ContinuityChecker::new();
+        let mut versions = Vec::new();
+        while let Some(packet) = track_try_unwrap!(reader.read_ts_packet()) {
+            assert!(!checker.check(&packet).is_discontinuous());
+            if let Some(TsPayload::Pmt(pmt)) = &packet.payload {
+                assert_eq!(pmt.pcr_pid, Some(video));
+                versions.push((pmt.version_number.as_u8(), pmt.es_info.len()));
+            }
+        }
+        assert_eq!(versions, [(0, 1), (1, 2), (2, 2)]);
+
+        // The PES packets are completed when the next ones on the same PIDs start
+        let mut reader = PesPacketReader::new(TsPacketReader::new(&stream[..]));
+        let mut actual = Vec::new();
+        while let Some(packet) = track_try_unwrap!(reader.read_pes_packet()) {
+            actual.push(packet);
+        }
+        assert_eq!(actual.len(), packets.len());
+        for pid in [video, audio] {
+            let actual = actual.iter().filter(|p| p.pid == pid);
+            let expected = packets.iter().filter(|(p, _, _)| *p == pid);
+            for (actual, (_, stream_type, expected)) in actual.zip(expected) {
+                assert_eq!(actual.stream_type, Some(*stream_type));
+                assert_eq!(actual.header, expected.header);
+                assert_eq!(actual.data, expected.data);
+                assert_eq!(
+                    actual.random_access_indicator,
+                    expected.random_access_indicator
+                );
+
+                // The PCRs precede the DTSs by 700 ms
+                if pid == video {
+                    let dts = expected.header.dts.unwrap();
+                    let pcr = actual.pcr.unwrap();
+                    assert_eq!(Timestamp::from(pcr).as_u64(), dts.as_u64() - 63_000);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn oversized_pes_packets() {
+        let video = Pid::new(0x100).unwrap();
+        let audio = Pid::new(0x101).unwrap();
+        let mut writer = PesPacketWriter::new(TsPacketWriter::new(Vec::new()));
+
+        let packet = pes_packet(audio, 0xC0, 0, 70_000);
+        let e = writer
+            .write_pes_packet(audio, StreamType::AdtsAac, &packet)
+            .err()
+            .unwrap();
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+        assert!(writer.ts_packet_writer().stream().is_empty());
+
+        // Video PES packets can be unbounded
+        let packet = pes_packet(video, 0xE0, 0, 70_000);
+        track_try_unwrap!(writer.write_pes_packet(video, StreamType::H264, &packet));
+        let stream = writer.into_ts_packet_writer().into_stream();
+        let mut reader = PesPacketReader::new(TsPacketReader::new(&stream[..]));
+        let actual = track_try_unwrap!(reader.read_pes_packet()).unwrap();
+        assert_eq!(actual.data, packet.data);
+    }
+}
@@ -1,10 +1,14 @@
 //! Time-related constituent elements.
-use crate::{ErrorKind, Result};
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{Read, Write};
+use crate::io::{Read, ReadBytesExt, Write, WriteBytesExt};
+use crate::{Error, ErrorKind, Result};
+use byteorder::BigEndian;
+use core::fmt;
+use core::time::Duration;
 
 /// Timestamp type for PTS/DTS.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "u64", into = "u64"))]
 pub struct Timestamp(u64);
 impl Timestamp {
     /// 90 kHz.
@@ -13,6 +17,9 @@ impl Timestamp {
     /// Maximum timestamp value.
     pub const MAX: u64 = (1 << 33) - 1;
 
+    /// Modulus of timestamp values (the values wrap around at 33 bits).
+    pub const MODULUS: u64 = 1 << 33;
+
     /// Makes a new `Timestamp` instance.
     ///
     /// # Errors
@@ -33,7 +40,63 @@ impl Timestamp {
         self.0
     }
 
+    /// Converts the timestamp into the `Duration` since zero.
+    pub fn to_duration(&self) -> Duration {
+        Duration::from_nanos(self.0 * 100_000 / 9)
+    }
+
+    /// Makes a new `Timestamp` instance from the `Duration` since zero.
+    ///
+    /// The value is rounded to the nearest 90 kHz tick.
+    ///
+    /// # Errors
+    ///
+    /// If the resulting value exceeds `Timestamp::MAX`, it will return an `ErrorKind::InvalidInput` error.
+    pub fn from_duration(d: Duration) -> Result<Self> {
+        let n = (d.as_nanos() * 9 + 50_000) / 100_000;
+        track_assert!(
+            n <= u128::from(Self::MAX),
+            ErrorKind::InvalidInput,
+            "Too large duration: {:?}",
+            d
+        );
+        Ok(Timestamp(n as u64))
+    }
+
+    /// Adds `ticks` to the timestamp, wrapping around at `Timestamp::MODULUS`.
+    pub fn wrapping_add(&self, ticks: u64) -> Self {
+        Timestamp((self.0 + ticks % Self::MODULUS) % Self::MODULUS)
+    }
+
+    /// Returns the forward distance (in ticks) from `earlier` to `self`, assuming that the value may have wrapped around.
+    ///
+    /// The result is always less than `Timestamp::MODULUS`.
+    pub fn wrapping_sub(&self, earlier: Timestamp) -> u64 {
+        (self.0 + Self::MODULUS - earlier.0) % Self::MODULUS
+    }
+
+    /// Returns the signed difference (in ticks) between `self` and `earlier`, interpreted modulo `Timestamp::MODULUS`.
+    ///
+    /// The shorter of the forward and backward distances is chosen,
+    /// so the result is in the range `-MODULUS / 2..MODULUS / 2`.
+    pub fn delta(&self, earlier: Timestamp) -> i64 {
+        signed_delta(self.wrapping_sub(earlier), Self::MODULUS)
+    }
+
     pub(crate) fn from_u64(n: u64) -> Result<Self> {
+        track!(Self::check_marker_bits(n))?;
+        Ok(Self::from_u64_lenient(n))
+    }
+
+    /// Same as `from_u64` except that the marker bits are ignored.
+    pub(crate) fn from_u64_lenient(n: u64) -> Self {
+        let n0 = n >> (32 + 1) & ((1 << 3) - 1);
+        let n1 = n >> (16 + 1) & ((1 << 15) - 1);
+        let n2 = n >> 1 & ((1 << 15) - 1);
+        Timestamp((n0 << 30) | (n1 << 15) | n2)
+    }
+
+    fn check_marker_bits(n: u64) -> Result<()> {
         track_assert!(
             (n & 1) != 0,
             ErrorKind::InvalidInput,
@@ -49,15 +112,17 @@ impl Timestamp {
             ErrorKind::InvalidInput,
             "Unexpected marker bit"
         );
-
-        let n0 = n >> (32 + 1) & ((1 << 3) - 1);
-        let n1 = n >> (16 + 1) & ((1 << 15) - 1);
-        let n2 = n >> 1 & ((1 << 15) - 1);
-        Ok(Timestamp((n0 << 30) | (n1 << 15) | n2))
+        Ok(())
     }
 
-    pub(crate) fn read_from<R: Read>(mut reader: R, check_bits: u8) -> Result<Self> {
+    /// Reads a PTS or DTS field.
+    ///
+    /// If `lenient` is `true`, the prefix bits (`check_bits`) and the marker bits are not validated.
+    pub(crate) fn read_from<R: Read>(mut reader: R, check_bits: u8, lenient: bool) -> Result<Self> {
         let n = track_io!(reader.read_uint::<BigEndian>(5))?;
+        if lenient {
+            return Ok(Self::from_u64_lenient(n));
+        }
         track_assert_eq!((n >> 36) as u8, check_bits, ErrorKind::InvalidInput);
         track!(Self::from_u64(n))
     }
@@ -78,9 +143,39 @@ impl From<u32> for Timestamp {
         Timestamp(u64::from(n))
     }
 }
+impl From<ClockReference> for Timestamp {
+    fn from(f: ClockReference) -> Self {
+        // Values at or above `ClockReference::MODULUS` have a base of 2^33, which wraps around to zero
+        Timestamp(f.base() % Timestamp::MODULUS)
+    }
+}
+impl TryFrom<u64> for Timestamp {
+    type Error = Error;
+    fn try_from(f: u64) -> Result<Self> {
+        track!(Timestamp::new(f))
+    }
+}
+impl From<Timestamp> for u64 {
+    fn from(f: Timestamp) -> Self {
+        f.0
+    }
+}
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_duration(self.to_duration(), f)
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Timestamp {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Timestamp(u.int_in_range(0..=Self::MAX)?))
+    }
+}
 
 /// Timestamp type for PCR/OPCR/ESCR.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "u64", into = "u64"))]
 pub struct ClockReference(u64);
 impl ClockReference {
     /// 27MHz.
@@ -89,6 +184,9 @@ impl ClockReference {
     /// Maximum PCR value.
     pub const MAX: u64 = ((1 << 33) - 1) * 300 + 0b1_1111_1111;
 
+    /// Modulus of PCR values (the 33-bit base multiplied by 300).
+    pub const MODULUS: u64 = (1 << 33) * 300;
+
     /// Makes a new `ClockReference` instance.
     ///
     /// # Errors
@@ -109,6 +207,59 @@ impl ClockReference {
         self.0
     }
 
+    /// Returns the 90 kHz base part of the value.
+    pub fn base(&self) -> u64 {
+        self.0 / 300
+    }
+
+    /// Returns the 27 MHz extension part of the value (`0..300`).
+    pub fn extension(&self) -> u16 {
+        (self.0 % 300) as u16
+    }
+
+    /// Converts the clock reference into the `Duration` since zero.
+    pub fn to_duration(&self) -> Duration {
+        Duration::from_nanos(self.0 * 1000 / 27)
+    }
+
+    /// Makes a new `ClockReference` instance from the `Duration` since zero.
+    ///
+    /// The value is rounded to the nearest 27 MHz tick.
+    ///
+    /// # Errors
+    ///
+    /// If the resulting value exceeds `ClockReference::MAX`, it will return an `ErrorKind::InvalidInput` error.
+    pub fn from_duration(d: Duration) -> Result<Self> {
+        let n = (d.as_nanos() * 27 + 500) / 1000;
+        track_assert!(
+            n <= u128::from(Self::MAX),
+            ErrorKind::InvalidInput,
+            "Too large duration: {:?}",
+            d
+        );
+        Ok(ClockReference(n as u64))
+    }
+
+    /// Adds `ticks` to the clock reference, wrapping around at `ClockReference::MODULUS`.
+    pub fn wrapping_add(&self, ticks: u64) -> Self {
+        ClockReference((self.0 % Self::MODULUS + ticks % Self::MODULUS) % Self::MODULUS)
+    }
+
+    /// Returns the forward distance (in ticks) from `earlier` to `self`, assuming that the value may have wrapped around.
+    ///
+    /// The result is always less than `ClockReference::MODULUS`.
+    pub fn wrapping_sub(&self, earlier: ClockReference) -> u64 {
+        (self.0 % Self::MODULUS + Self::MODULUS - earlier.0 % Self::MODULUS) % Self::MODULUS
+    }
+
+    /// Returns the signed difference (in ticks) between `self` and `earlier`, interpreted modulo `ClockReference::MODULUS`.
+    ///
+    /// The shorter of the forward and backward distances is chosen,
+    /// so the result is in the range `-MODULUS / 2..MODULUS / 2`.
+    pub fn delta(&self, earlier: ClockReference) -> i64 {
+        signed_delta(self.wrapping_sub(earlier), Self::MODULUS)
+    }
+
     pub(crate) fn read_pcr_from<R: Read>(mut reader: R) -> Result<Self> {
         let n = track_io!(reader.read_uint::<BigEndian>(6))?;
         let base = n >> 15;
@@ -116,9 +267,17 @@ impl ClockReference {
         Ok(ClockReference(base * 300 + extension))
     }
 
+    /// Returns the base and extension parts to be written.
+    ///
+    /// The values of `MODULUS` or greater do not fit in the 33-bit base,
+    /// so they are encoded with an extension of `300` or greater (up to `511`).
+    fn encoded_parts(&self) -> (u64, u64) {
+        let base = (self.0 / 300).min(Timestamp::MAX);
+        (base, self.0 - base * 300)
+    }
+
     pub(crate) fn write_pcr_to<W: Write>(&self, mut writer: W) -> Result<()> {
-        let base = self.0 / 300;
-        let extension = self.0 % 300;
+        let (base, extension) = self.encoded_parts();
 
         let n = (base << 15) | extension;
         track_io!(writer.write_uint::<BigEndian>(n, 6))?;
@@ -145,8 +304,7 @@ impl ClockReference {
     }
 
     pub(crate) fn write_escr_to<W: Write>(&self, mut writer: W) -> Result<()> {
-        let base = self.0 / 300;
-        let extension = self.0 % 300;
+        let (base, extension) = self.encoded_parts();
 
         let marker = 1;
         let base0 = base & ((1 << 15) - 1);
@@ -175,6 +333,219 @@ impl From<Timestamp> for ClockReference {
         ClockReference(f.0 * 300)
     }
 }
+impl TryFrom<u64> for ClockReference {
+    type Error = Error;
+    fn try_from(f: u64) -> Result<Self> {
+        track!(ClockReference::new(f))
+    }
+}
+impl From<ClockReference> for u64 {
+    fn from(f: ClockReference) -> Self {
+        f.0
+    }
+}
+impl fmt::Display for ClockReference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_duration(self.to_duration(), f)
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ClockReference {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ClockReference(u.int_in_range(0..=Self::MAX)?))
+    }
+}
+
+/// Result of `TimestampUnwrapper::unwrap` and `ClockReferenceUnwrapper::unwrap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unwrapped {
+    /// The value is continuous with the previous one (possibly across a wrap).
+    Continuous(u64),
+
+    /// The value jumped farther than the maximum jump from the previous one.
+    ///
+    /// The timeline has been re-anchored so that the value is mapped to the previous unwrapped value.
+    Discontinuity(u64),
+}
+impl Unwrapped {
+    /// Returns the unwrapped value.
+    pub fn value(&self) -> u64 {
+        match *self {
+            Unwrapped::Continuous(n) | Unwrapped::Discontinuity(n) => n,
+        }
+    }
+
+    /// Returns `true` if this is `Unwrapped::Discontinuity`, otherwise `false`.
+    pub fn is_discontinuity(&self) -> bool {
+        matches!(self, Unwrapped::Discontinuity(_))
+    }
+}
+
+/// Converter from successive `Timestamp`s of a stream into ticks on a 64-bit timeline that does not wrap around.
+///
+/// The first timestamp is mapped to its own value.
+/// Each subsequent timestamp is placed at the shortest (forward or backward) distance from the previous one
+/// (see `Timestamp::delta`), so backward steps such as the PTS of reordered frames are preserved.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::time::{Timestamp, TimestampUnwrapper};
+///
+/// let mut unwrapper = TimestampUnwrapper::new();
+/// let before_wrap = Timestamp::new(Timestamp::MAX - 89_999).unwrap();
+/// let after_wrap = Timestamp::new(90_000).unwrap();
+/// assert_eq!(unwrapper.unwrap(before_wrap).value(), Timestamp::MAX - 89_999);
+/// assert_eq!(unwrapper.unwrap(after_wrap).value(), Timestamp::MODULUS + 90_000);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimestampUnwrapper {
+    inner: Unwrapper,
+}
+impl TimestampUnwrapper {
+    /// Default maximum jump (10 seconds).
+    pub const DEFAULT_MAX_JUMP: u64 = 10 * Timestamp::RESOLUTION;
+
+    /// Makes a new `TimestampUnwrapper` instance.
+    pub fn new() -> Self {
+        TimestampUnwrapper {
+            inner: Unwrapper::new(Self::DEFAULT_MAX_JUMP),
+        }
+    }
+
+    /// Sets the maximum jump (in ticks, either forward or backward) regarded as continuous.
+    ///
+    /// A larger jump is reported as `Unwrapped::Discontinuity`.
+    ///
+    /// The default value is `TimestampUnwrapper::DEFAULT_MAX_JUMP`.
+    pub fn set_max_jump(&mut self, ticks: u64) {
+        self.inner.max_jump = ticks;
+    }
+
+    /// Maps `ts` onto the unwrapped timeline.
+    pub fn unwrap(&mut self, ts: Timestamp) -> Unwrapped {
+        let delta = self.inner.last.map(|(last, _)| ts.delta(Timestamp(last)));
+        self.inner.unwrap(ts.0, delta)
+    }
+
+    /// Returns the last unwrapped value (if any).
+    pub fn last(&self) -> Option<u64> {
+        self.inner.last.map(|(_, n)| n)
+    }
+
+    /// Forgets the previous timestamp, so the next timestamp is mapped to its own value.
+    pub fn reset(&mut self) {
+        self.inner.last = None;
+    }
+}
+impl Default for TimestampUnwrapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converter from successive `ClockReference`s of a stream into ticks on a 64-bit timeline that does not wrap around.
+///
+/// This works in the same way as `TimestampUnwrapper` except that the values are 27 MHz ticks.
+#[derive(Debug, Clone)]
+pub struct ClockReferenceUnwrapper {
+    inner: Unwrapper,
+}
+impl ClockReferenceUnwrapper {
+    /// Default maximum jump (10 seconds).
+    pub const DEFAULT_MAX_JUMP: u64 = 10 * ClockReference::RESOLUTION;
+
+    /// Makes a new `ClockReferenceUnwrapper` instance.
+    pub fn new() -> Self {
+        ClockReferenceUnwrapper {
+            inner: Unwrapper::new(Self::DEFAULT_MAX_JUMP),
+        }
+    }
+
+    /// Sets the maximum jump (in ticks, either forward or backward) regarded as continuous.
+    ///
+    /// A larger jump is reported as `Unwrapped::Discontinuity`.
+    ///
+    /// The default value is `ClockReferenceUnwrapper::DEFAULT_MAX_JUMP`.
+    pub fn set_max_jump(&mut self, ticks: u64) {
+        self.inner.max_jump = ticks;
+    }
+
+    /// Maps `cr` onto the unwrapped timeline.
+    pub fn unwrap(&mut self, cr: ClockReference) -> Unwrapped {
+        let delta = self
+            .inner
+            .last
+            .map(|(last, _)| cr.delta(ClockReference(last)));
+        self.inner.unwrap(cr.0 % ClockReference::MODULUS, delta)
+    }
+
+    /// Returns the last unwrapped value (if any).
+    pub fn last(&self) -> Option<u64> {
+        self.inner.last.map(|(_, n)| n)
+    }
+
+    /// Forgets the previous clock reference, so the next one is mapped to its own value.
+    pub fn reset(&mut self) {
+        self.inner.last = None;
+    }
+}
+impl Default for ClockReferenceUnwrapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Unwrapper {
+    max_jump: u64,
+
+    /// The last raw value and its unwrapped value.
+    last: Option<(u64, u64)>,
+}
+impl Unwrapper {
+    fn new(max_jump: u64) -> Self {
+        Unwrapper {
+            max_jump,
+            last: None,
+        }
+    }
+
+    /// `delta` is the signed difference from the last raw value (`None` if there is no last value).
+    fn unwrap(&mut self, raw: u64, delta: Option<i64>) -> Unwrapped {
+        let result = match (self.last, delta) {
+            (Some((_, last)), Some(delta)) if delta.unsigned_abs() <= self.max_jump => {
+                // Saturates if the timeline goes backward beyond zero
+                Unwrapped::Continuous(last.checked_add_signed(delta).unwrap_or(0))
+            }
+            (Some((_, last)), _) => Unwrapped::Discontinuity(last),
+            (None, _) => Unwrapped::Continuous(raw),
+        };
+        self.last = Some((raw, result.value()));
+        result
+    }
+}
+
+fn signed_delta(forward: u64, modulus: u64) -> i64 {
+    if forward < modulus / 2 {
+        forward as i64
+    } else {
+        forward as i64 - modulus as i64
+    }
+}
+
+/// Formats `d` as `HH:MM:SS.mmm`.
+fn fmt_duration(d: Duration, f: &mut fmt::Formatter) -> fmt::Result {
+    let secs = d.as_secs();
+    write!(
+        f,
+        "{:02}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        d.subsec_millis()
+    )
+}
 
 #[cfg(test)]
 mod test {
@@ -187,6 +558,160 @@ mod test {
         cr.write_pcr_to(&mut buf).unwrap();
         let new_cr = ClockReference::read_pcr_from(&buf[..]).unwrap();
         assert_eq!(cr, new_cr);
+
+        // Values that do not fit in the 33-bit base
+        for n in [ClockReference::MODULUS, ClockReference::MAX] {
+            let cr = ClockReference::new(n).unwrap();
+            let mut buf = Vec::new();
+            cr.write_pcr_to(&mut buf).unwrap();
+            assert_eq!(ClockReference::read_pcr_from(&buf[..]).unwrap(), cr);
+
+            let mut buf = Vec::new();
+            cr.write_escr_to(&mut buf).unwrap();
+            assert_eq!(ClockReference::read_escr_from(&buf[..]).unwrap(), cr);
+        }
+    }
+
+    #[test]
+    fn clock_reference_to_timestamp() {
+        let ts = |n| Timestamp::from(ClockReference::new(n).unwrap());
+        assert_eq!(ts(299).as_u64(), 0);
+        assert_eq!(ts(ClockReference::MODULUS - 1).as_u64(), Timestamp::MAX);
+        assert_eq!(ts(ClockReference::MODULUS).as_u64(), 0);
+        assert_eq!(ts(ClockReference::MAX).as_u64(), 0);
+    }
+
+    #[test]
+    fn lenient_timestamp() {
+        let ts = Timestamp::new(123_456_789).unwrap();
+        let mut buf = Vec::new();
+        ts.write_to(&mut buf, 2).unwrap();
+        buf[4] &= 0b1111_1110; // clears the last marker bit
+
+        assert!(Timestamp::read_from(&buf[..], 2, false).is_err());
+        assert_eq!(Timestamp::read_from(&buf[..], 2, true).unwrap(), ts);
+    }
+
+    #[test]
+    fn duration_conversion() {
+        let ts = Timestamp::new(90_000 * 3723 + 45_000).unwrap();
+        assert_eq!(ts.to_duration(), Duration::from_millis(3_723_500));
+        assert_eq!(Timestamp::from_duration(ts.to_duration()).unwrap(), ts);
+        assert_eq!(ts.to_string(), "01:02:03.500");
+
+        let max = Timestamp::new(Timestamp::MAX).unwrap();
+        assert_eq!(Timestamp::from_duration(max.to_duration()).unwrap(), max);
+        assert!(Timestamp::from_duration(max.to_duration() + Duration::from_millis(1)).is_err());
+
+        let cr = ClockReference::new(27_000_000 + 301).unwrap();
+        assert_eq!(cr.base(), 90_001);
+        assert_eq!(cr.extension(), 1);
+        assert_eq!(Timestamp::from(cr), Timestamp::new(90_001).unwrap());
+        assert_eq!(ClockReference::from_duration(cr.to_duration()).unwrap(), cr);
+        assert_eq!(cr.to_string(), "00:00:01.000");
+
+        let max = ClockReference::new(ClockReference::MAX).unwrap();
+        assert_eq!(
+            ClockReference::from_duration(max.to_duration()).unwrap(),
+            max
+        );
+        assert!(ClockReference::from_duration(Duration::from_secs(100_000)).is_err());
+    }
+
+    #[test]
+    fn wrapping_timestamp() {
+        let m = Timestamp::MODULUS;
+        let max = Timestamp::new(Timestamp::MAX).unwrap();
+        assert_eq!(max.wrapping_add(1), Timestamp::new(0).unwrap());
+        assert_eq!(Timestamp::new(0).unwrap().wrapping_sub(max), 1);
+        assert_eq!(max.delta(Timestamp::new(0).unwrap()), -1);
+
+        // Values around the wrap point
+        let points = [0, 1, 90_000, m / 2 - 1, m / 2, m - 90_000, m - 1];
+        let steps = [0, 1, 2_999, 90_000, 90_000 * 3600, m / 2 - 1];
+        for &p in &points {
+            let t = Timestamp::new(p).unwrap();
+            for &n in &steps {
+                let u = t.wrapping_add(n);
+                assert!(u.as_u64() <= Timestamp::MAX);
+                assert_eq!(u.wrapping_sub(t), n);
+                assert_eq!(u.delta(t), n as i64);
+                assert_eq!(t.delta(u), -(n as i64));
+                assert_eq!(t.wrapping_add(n + m), u);
+            }
+        }
+    }
+
+    #[test]
+    fn wrapping_clock_reference() {
+        let m = ClockReference::MODULUS;
+        let last = ClockReference::new(m - 1).unwrap();
+        assert_eq!(last.wrapping_add(1), ClockReference::new(0).unwrap());
+        assert_eq!(ClockReference::new(0).unwrap().delta(last), 1);
+
+        let points = [0, 299, 300, m / 2, m - 300, m - 1];
+        let steps = [0, 1, 300, 27_000_000, m / 2 - 1];
+        for &p in &points {
+            let c = ClockReference::new(p).unwrap();
+            for &n in &steps {
+                let d = c.wrapping_add(n);
+                assert!(d.as_u64() < m);
+                assert_eq!(d.wrapping_sub(c), n);
+                assert_eq!(d.delta(c), n as i64);
+                assert_eq!(c.delta(d), -(n as i64));
+            }
+        }
+    }
+
+    #[test]
+    fn unwrap_timestamp() {
+        let ts = |n| Timestamp::new(n).unwrap();
+        let m = Timestamp::MODULUS;
+        let mut unwrapper = TimestampUnwrapper::new();
+        assert_eq!(
+            unwrapper.unwrap(ts(m - 3000)),
+            Unwrapped::Continuous(m - 3000)
+        );
+        assert_eq!(
+            unwrapper.unwrap(ts(m - 6000)),
+            Unwrapped::Continuous(m - 6000)
+        );
+        assert_eq!(unwrapper.unwrap(ts(3000)), Unwrapped::Continuous(m + 3000));
+        assert_eq!(unwrapper.unwrap(ts(m - 1)), Unwrapped::Continuous(m - 1));
+
+        // Second wrap
+        let mut last = m - 1;
+        for i in 1..100_000 {
+            last = unwrapper.unwrap(ts(i * 90_000 % m)).value();
+        }
+        assert_eq!(last, m + 99_999 * 90_000);
+        assert_eq!(unwrapper.last(), Some(last));
+
+        // Discontinuity
+        let n = (99_999 * 90_000 + 10 * 3600 * 90_000) % m;
+        assert_eq!(unwrapper.unwrap(ts(n)), Unwrapped::Discontinuity(last));
+        assert_eq!(
+            unwrapper.unwrap(ts(n + 3000)),
+            Unwrapped::Continuous(last + 3000)
+        );
+
+        unwrapper.reset();
+        assert_eq!(unwrapper.unwrap(ts(5)), Unwrapped::Continuous(5));
+        assert_eq!(unwrapper.unwrap(ts(m - 5)), Unwrapped::Continuous(0));
+    }
+
+    #[test]
+    fn unwrap_clock_reference() {
+        let cr = |n| ClockReference::new(n).unwrap();
+        let m = ClockReference::MODULUS;
+        let mut unwrapper = ClockReferenceUnwrapper::new();
+        unwrapper.set_max_jump(27_000_000);
+        assert_eq!(
+            unwrapper.unwrap(cr(m - 100)),
+            Unwrapped::Continuous(m - 100)
+        );
+        assert_eq!(unwrapper.unwrap(cr(200)), Unwrapped::Continuous(m + 200));
+        assert!(unwrapper.unwrap(cr(200 + 27_000_001)).is_discontinuity());
     }
 
     #[test]
@@ -1,13 +1,15 @@
+use crate::io::{Read, ReadBytesExt, Write, WriteBytesExt};
 use crate::time::ClockReference;
-use crate::ts::{LegalTimeWindow, PiecewiseRate, SeamlessSplice};
+use crate::ts::{LegalTimeWindow, PiecewiseRate, SeamlessSplice, TsPacket};
 use crate::util;
 use crate::{ErrorKind, Result};
-use byteorder::{ReadBytesExt, WriteBytesExt};
-use std::io::{Read, Write};
+use alloc::{borrow::ToOwned, format, vec, vec::Vec};
+use core::fmt;
 
 /// Adaptation field.
 #[allow(missing_docs)]
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AdaptationField {
     /// Set `true` if current TS packet is in a discontinuity state with respect to
     /// either the continuity counter or the program clock reference.
@@ -29,7 +31,56 @@ pub struct AdaptationField {
     pub extension: Option<AdaptationExtensionField>,
 }
 impl AdaptationField {
-    pub(super) fn external_size(&self) -> usize {
+    /// Maximum size of an adaptation field in bytes (including the `adaptation_field_length` byte).
+    ///
+    /// This is the size of a packet excluding the header,
+    /// so an adaptation field of `n` bytes leaves `MAX_SIZE - n` bytes for the payload.
+    pub const MAX_SIZE: usize = TsPacket::SIZE - 4;
+
+    /// Maximum size of `transport_private_data` in bytes.
+    pub const MAX_PRIVATE_DATA_SIZE: usize = 0xFF;
+
+    /// Makes a new `AdaptationFieldBuilder` instance.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mpeg2ts::time::ClockReference;
+    /// use mpeg2ts::ts::AdaptationField;
+    ///
+    /// let adaptation_field = AdaptationField::builder()
+    ///     .random_access_indicator(true)
+    ///     .pcr(ClockReference::new(27_000_000).unwrap())
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(adaptation_field.external_size(), 8);
+    /// ```
+    pub fn builder() -> AdaptationFieldBuilder {
+        AdaptationFieldBuilder {
+            adaptation_field: AdaptationField::default(),
+        }
+    }
+
+    /// Makes a new `AdaptationField` instance that only has the PCR.
+    pub fn with_pcr(pcr: ClockReference) -> Self {
+        AdaptationField {
+            pcr: Some(pcr),
+            ..Default::default()
+        }
+    }
+
+    /// Makes a new `AdaptationField` instance that only has the random access indicator set.
+    pub fn random_access() -> Self {
+        AdaptationField {
+            random_access_indicator: true,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the size of the field in bytes when written (including the `adaptation_field_length` byte).
+    ///
+    /// Stuffing bytes are not included.
+    pub fn external_size(&self) -> usize {
         let mut n = 1 /* adaptation_field_len */ + 1 /* flags */;
         if self.pcr.is_some() {
             n += 6;
@@ -40,7 +91,9 @@ impl AdaptationField {
         if self.splice_countdown.is_some() {
             n += 1;
         }
-        n += self.transport_private_data.len();
+        if !self.transport_private_data.is_empty() {
+            n += 1 /* transport_private_data_length */ + self.transport_private_data.len();
+        }
         if let Some(ref x) = self.extension {
             n += x.external_size();
         }
@@ -55,6 +108,11 @@ impl AdaptationField {
         let mut reader = reader.take(u64::from(adaptation_field_len));
 
         let b = track_io!(reader.read_u8())?;
+        if b == 0 {
+            // No flags are set, so the rest of the field is stuffing bytes
+            track!(util::consume_stuffing_bytes(reader))?;
+            return Ok(None);
+        }
         let discontinuity_indicator = (b & 0b1000_0000) != 0;
         let random_access_indicator = (b & 0b0100_0000) != 0;
         let es_priority_indicator = (b & 0b0010_0000) != 0;
@@ -141,7 +199,14 @@ impl AdaptationField {
         if let Some(x) = self.splice_countdown {
             track_io!(writer.write_i8(x))?;
         }
-        track_io!(writer.write_all(&self.transport_private_data))?;
+        if !self.transport_private_data.is_empty() {
+            track_assert!(
+                self.transport_private_data.len() <= Self::MAX_PRIVATE_DATA_SIZE,
+                ErrorKind::InvalidInput
+            );
+            track_io!(writer.write_u8(self.transport_private_data.len() as u8))?;
+            track_io!(writer.write_all(&self.transport_private_data))?;
+        }
         if let Some(ref x) = self.extension {
             track!(x.write_to(&mut writer))?;
         }
@@ -151,10 +216,149 @@ impl AdaptationField {
         Ok(())
     }
 }
+impl fmt::Display for AdaptationField {
+    /// Formats a one-line summary of the set fields, e.g., `RAI PCR=27000000`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut items = Vec::new();
+        if self.discontinuity_indicator {
+            items.push("DI".to_owned());
+        }
+        if self.random_access_indicator {
+            items.push("RAI".to_owned());
+        }
+        if self.es_priority_indicator {
+            items.push("ESPI".to_owned());
+        }
+        if let Some(pcr) = self.pcr {
+            items.push(format!("PCR={}", pcr.as_u64()));
+        }
+        if let Some(opcr) = self.opcr {
+            items.push(format!("OPCR={}", opcr.as_u64()));
+        }
+        if let Some(n) = self.splice_countdown {
+            items.push(format!("SPLICE={}", n));
+        }
+        if !self.transport_private_data.is_empty() {
+            items.push(format!("PRIV={}", self.transport_private_data.len()));
+        }
+        if self.extension.is_some() {
+            items.push("EXT".to_owned());
+        }
+        write!(f, "{}", items.join(" "))
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for AdaptationField {
+    /// Generates an adaptation field whose `external_size` is at most `AdaptationField::MAX_SIZE`.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut a = AdaptationField {
+            discontinuity_indicator: u.arbitrary()?,
+            random_access_indicator: u.arbitrary()?,
+            es_priority_indicator: u.arbitrary()?,
+            pcr: u.arbitrary()?,
+            opcr: u.arbitrary()?,
+            splice_countdown: u.arbitrary()?,
+            transport_private_data: Vec::new(),
+            extension: u.arbitrary()?,
+        };
+        let len = u
+            .arbitrary_len::<u8>()?
+            .min(
+                Self::MAX_SIZE - a.external_size() - 1, /* transport_private_data_length */
+            )
+            .min(Self::MAX_PRIVATE_DATA_SIZE);
+        a.transport_private_data = u.bytes(len)?.to_owned();
+        Ok(a)
+    }
+}
+
+/// Builder of `AdaptationField`.
+///
+/// Unlike `WriteTsPacket::write_ts_packet`, `build` checks up front that the field fits in a packet.
+#[derive(Debug, Clone)]
+pub struct AdaptationFieldBuilder {
+    adaptation_field: AdaptationField,
+}
+impl AdaptationFieldBuilder {
+    /// Sets `discontinuity_indicator`.
+    pub fn discontinuity_indicator(mut self, indicator: bool) -> Self {
+        self.adaptation_field.discontinuity_indicator = indicator;
+        self
+    }
+
+    /// Sets `random_access_indicator`.
+    pub fn random_access_indicator(mut self, indicator: bool) -> Self {
+        self.adaptation_field.random_access_indicator = indicator;
+        self
+    }
+
+    /// Sets `elementary_stream_priority_indicator`.
+    pub fn es_priority_indicator(mut self, indicator: bool) -> Self {
+        self.adaptation_field.es_priority_indicator = indicator;
+        self
+    }
+
+    /// Sets the PCR.
+    pub fn pcr(mut self, pcr: ClockReference) -> Self {
+        self.adaptation_field.pcr = Some(pcr);
+        self
+    }
+
+    /// Sets the OPCR.
+    pub fn opcr(mut self, opcr: ClockReference) -> Self {
+        self.adaptation_field.opcr = Some(opcr);
+        self
+    }
+
+    /// Sets `splice_countdown`.
+    pub fn splice_countdown(mut self, countdown: i8) -> Self {
+        self.adaptation_field.splice_countdown = Some(countdown);
+        self
+    }
+
+    /// Sets `transport_private_data`.
+    pub fn transport_private_data(mut self, data: &[u8]) -> Self {
+        self.adaptation_field.transport_private_data = data.to_vec();
+        self
+    }
+
+    /// Sets the adaptation field extension.
+    pub fn extension(mut self, extension: AdaptationExtensionField) -> Self {
+        self.adaptation_field.extension = Some(extension);
+        self
+    }
+
+    /// Builds an `AdaptationField` instance.
+    ///
+    /// # Errors
+    ///
+    /// If `transport_private_data` is longer than `AdaptationField::MAX_PRIVATE_DATA_SIZE` bytes or
+    /// the field is larger than `AdaptationField::MAX_SIZE` bytes,
+    /// it will return an `ErrorKind::InvalidInput` error.
+    pub fn build(self) -> Result<AdaptationField> {
+        let a = self.adaptation_field;
+        track_assert!(
+            a.transport_private_data.len() <= AdaptationField::MAX_PRIVATE_DATA_SIZE,
+            ErrorKind::InvalidInput,
+            "Too large transport private data: {} bytes",
+            a.transport_private_data.len()
+        );
+        let size = a.external_size();
+        track_assert!(
+            size <= AdaptationField::MAX_SIZE,
+            ErrorKind::InvalidInput,
+            "Too large adaptation field: size={}, overflow={}",
+            size,
+            size - AdaptationField::MAX_SIZE
+        );
+        Ok(a)
+    }
+}
 
 /// Adaptation extension field.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AdaptationExtensionField {
     pub legal_time_window: Option<LegalTimeWindow>,
     pub piecewise_rate: Option<PiecewiseRate>,
@@ -228,6 +432,16 @@ impl AdaptationExtensionField {
         Ok(())
     }
 }
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for AdaptationExtensionField {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(AdaptationExtensionField {
+            legal_time_window: u.arbitrary()?,
+            piecewise_rate: u.arbitrary()?,
+            seamless_splice: u.arbitrary()?,
+        })
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum AdaptationFieldControl {
@@ -0,0 +1,126 @@
+use crate::ts::{PacketFormat, TsPacket, TsPacketReader, TsPacketWriter};
+use crate::Result;
+use core::future::Future;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// The `AsyncReadTsPacket` trait allows for reading TS packets from an asynchronous source.
+pub trait AsyncReadTsPacket {
+    /// Reads a TS packet.
+    ///
+    /// If the end of the stream is reached, it will return `Ok(None)`.
+    fn read_ts_packet(&mut self) -> impl Future<Output = Result<Option<TsPacket>>>;
+
+    /// Returns the byte offset of the last read packet in the stream (if known).
+    ///
+    /// The default implementation returns `None`.
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        None
+    }
+}
+impl<T: AsyncReadTsPacket + ?Sized> AsyncReadTsPacket for &mut T {
+    fn read_ts_packet(&mut self) -> impl Future<Output = Result<Option<TsPacket>>> {
+        (**self).read_ts_packet()
+    }
+
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        (**self).last_packet_byte_offset()
+    }
+}
+
+/// The `AsyncWriteTsPacket` trait allows for writing TS packets to an asynchronous destination.
+pub trait AsyncWriteTsPacket {
+    /// Writes a TS packet.
+    fn write_ts_packet(&mut self, packet: &TsPacket) -> impl Future<Output = Result<()>>;
+}
+impl<T: AsyncWriteTsPacket + ?Sized> AsyncWriteTsPacket for &mut T {
+    fn write_ts_packet(&mut self, packet: &TsPacket) -> impl Future<Output = Result<()>> {
+        (**self).write_ts_packet(packet)
+    }
+}
+
+/// Asynchronous TS packet reader.
+///
+/// This decodes packets in the same way as `TsPacketReader`,
+/// which can be configured via `ts_packet_reader_mut`.
+#[derive(Debug)]
+pub struct AsyncTsPacketReader<R> {
+    inner: TsPacketReader<R>,
+}
+impl<R: AsyncRead + Unpin> AsyncTsPacketReader<R> {
+    /// Makes a new `AsyncTsPacketReader` instance.
+    pub fn new(stream: R) -> Self {
+        AsyncTsPacketReader {
+            inner: TsPacketReader::new(stream),
+        }
+    }
+
+    /// Makes a new `AsyncTsPacketReader` instance whose packet format is detected from the stream.
+    ///
+    /// See `TsPacketReader::with_detected_packet_format` for details.
+    pub async fn with_detected_packet_format(stream: R) -> Result<Self> {
+        let inner = track!(TsPacketReader::with_detected_packet_format_async(stream).await)?;
+        Ok(AsyncTsPacketReader { inner })
+    }
+
+    /// Returns a reference to the underlaying packet decoder.
+    pub fn ts_packet_reader(&self) -> &TsPacketReader<R> {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlaying packet decoder.
+    ///
+    /// This can be used to change the settings of the decoding.
+    pub fn ts_packet_reader_mut(&mut self) -> &mut TsPacketReader<R> {
+        &mut self.inner
+    }
+
+    /// Converts `AsyncTsPacketReader` into the underlaying byte stream `R`.
+    pub fn into_stream(self) -> R {
+        self.inner.into_stream()
+    }
+}
+impl<R: AsyncRead + Unpin> AsyncReadTsPacket for AsyncTsPacketReader<R> {
+    async fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+        track!(self.inner.read_ts_packet_async().await)
+    }
+
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        (self.inner.packet_index() > 0).then_some(self.inner.last_packet_location().0)
+    }
+}
+
+/// Asynchronous TS packet writer.
+#[derive(Debug)]
+pub struct AsyncTsPacketWriter<W> {
+    inner: TsPacketWriter<W>,
+}
+impl<W: AsyncWrite + Unpin> AsyncTsPacketWriter<W> {
+    /// Makes a new `AsyncTsPacketWriter` instance.
+    pub fn new(stream: W) -> Self {
+        AsyncTsPacketWriter {
+            inner: TsPacketWriter::new(stream),
+        }
+    }
+
+    /// Sets the framing of the packets to be written.
+    ///
+    /// See `TsPacketWriter::set_packet_format` for details.
+    pub fn set_packet_format(&mut self, format: PacketFormat) {
+        self.inner.set_packet_format(format);
+    }
+
+    /// Returns a reference to the underlaying byte stream.
+    pub fn stream(&self) -> &W {
+        self.inner.stream()
+    }
+
+    /// Converts `AsyncTsPacketWriter` into the underlaying byte stream.
+    pub fn into_stream(self) -> W {
+        self.inner.into_stream()
+    }
+}
+impl<W: AsyncWrite + Unpin> AsyncWriteTsPacket for AsyncTsPacketWriter<W> {
+    async fn write_ts_packet(&mut self, packet: &TsPacket) -> Result<()> {
+        track!(self.inner.write_ts_packet_async(packet).await)
+    }
+}
@@ -0,0 +1,276 @@
+use crate::es::StreamType;
+use crate::ts::{Descriptor, KnownDescriptor};
+use alloc::{vec, vec::Vec};
+
+/// Codec of an elementary stream.
+///
+/// Unlike `StreamType`, it distinguishes the codecs carried as `StreamType::Mpeg2PacketizedData`
+/// (and `StreamType::PacketizedMetadata`) by their descriptors.
+/// It can be obtained by `EsInfo::codec`.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Codec {
+    Mpeg1Video,
+    Mpeg2Video,
+    H264,
+    H265,
+    H266,
+
+    /// MPEG-1 or MPEG-2 audio (e.g., MP2 and MP3).
+    MpegAudio,
+
+    /// AAC in ADTS.
+    Aac,
+
+    /// AAC in LATM/LOAS.
+    AacLatm,
+
+    /// Dolby Digital.
+    Ac3,
+
+    /// Dolby Digital Plus.
+    Eac3,
+    Opus,
+    Dts,
+    DvbSubtitles,
+    Teletext,
+    Id3,
+    Klv,
+    Scte35,
+
+    /// Codec that cannot be identified from the stream type and descriptors.
+    Unknown(StreamType),
+}
+impl Codec {
+    /// Tag of the DVB VBI teletext descriptor.
+    const VBI_TELETEXT_TAG: u8 = 0x46;
+
+    /// Tag of the DVB AC-3 descriptor.
+    const AC3_TAG: u8 = 0x6A;
+
+    /// Tag of the DVB enhanced AC-3 descriptor.
+    const EAC3_TAG: u8 = 0x7A;
+
+    /// Tag of the DVB DTS descriptor.
+    const DTS_TAG: u8 = 0x7B;
+
+    /// Resolves the codec of a stream from its stream type and ES descriptors.
+    pub fn resolve(stream_type: StreamType, descriptors: &[Descriptor]) -> Self {
+        let registered = |id: &[u8; 4]| {
+            descriptors
+                .iter()
+                .any(|d| d.format_identifier() == Some(*id))
+        };
+        match stream_type {
+            StreamType::Mpeg1Video => Codec::Mpeg1Video,
+            StreamType::Mpeg2Video => Codec::Mpeg2Video,
+            StreamType::H264 => Codec::H264,
+            StreamType::H265 => Codec::H265,
+            StreamType::H266 => Codec::H266,
+            StreamType::Mpeg1Audio | StreamType::Mpeg2HalvedSampleRateAudio => Codec::MpegAudio,
+            StreamType::AdtsAac => Codec::Aac,
+            StreamType::Mpeg4LoasMultiFormatFramedAudio => Codec::AacLatm,
+            StreamType::DolbyDigitalUpToSixChannelAudio => Codec::Ac3,
+            StreamType::DolbyDigitalPlusUpTo16ChannelAudio
+            | StreamType::DolbyDigitalPlusUpTo16ChannelAudioForAtsc => Codec::Eac3,
+            // SCTE 35 shares the stream type 0x86 with DTS-HD
+            StreamType::Dts8ChannelLosslessAudio if registered(b"CUEI") => Codec::Scte35,
+            StreamType::Dts6ChannelAudio
+            | StreamType::Dts8ChannelAudio
+            | StreamType::Dts8ChannelLosslessAudio => Codec::Dts,
+            StreamType::Mpeg2PacketizedData | StreamType::PacketizedMetadata => descriptors
+                .iter()
+                .find_map(Self::from_descriptor)
+                .unwrap_or(Codec::Unknown(stream_type)),
+            _ => Codec::Unknown(stream_type),
+        }
+    }
+
+    fn from_descriptor(d: &Descriptor) -> Option<Self> {
+        if let Some(id) = d
+            .format_identifier()
+            .or_else(|| d.metadata_format_identifier())
+        {
+            return match &id {
+                b"AC-3" => Some(Codec::Ac3),
+                b"EAC3" => Some(Codec::Eac3),
+                b"Opus" => Some(Codec::Opus),
+                b"DTS1" | b"DTS2" | b"DTS3" => Some(Codec::Dts),
+                b"ID3 " => Some(Codec::Id3),
+                b"KLVA" => Some(Codec::Klv),
+                _ => None,
+            };
+        }
+        match d.tag {
+            Self::AC3_TAG => Some(Codec::Ac3),
+            Self::EAC3_TAG => Some(Codec::Eac3),
+            Self::DTS_TAG => Some(Codec::Dts),
+            KnownDescriptor::EXTENSION_TAG
+                if d.data.first() == Some(&KnownDescriptor::OPUS_AUDIO_TAG_EXTENSION) =>
+            {
+                Some(Codec::Opus)
+            }
+            KnownDescriptor::SUBTITLING_TAG => Some(Codec::DvbSubtitles),
+            Descriptor::TELETEXT_TAG | Self::VBI_TELETEXT_TAG => Some(Codec::Teletext),
+            _ => None,
+        }
+    }
+
+    /// Returns the stream type used to write a stream of the codec.
+    pub fn stream_type(self) -> StreamType {
+        match self {
+            Codec::Mpeg1Video => StreamType::Mpeg1Video,
+            Codec::Mpeg2Video => StreamType::Mpeg2Video,
+            Codec::H264 => StreamType::H264,
+            Codec::H265 => StreamType::H265,
+            Codec::H266 => StreamType::H266,
+            Codec::MpegAudio => StreamType::Mpeg1Audio,
+            Codec::Aac => StreamType::AdtsAac,
+            Codec::AacLatm => StreamType::Mpeg4LoasMultiFormatFramedAudio,
+            Codec::Dts => StreamType::Dts6ChannelAudio,
+            Codec::Id3 => StreamType::PacketizedMetadata,
+            Codec::Scte35 => StreamType::Dts8ChannelLosslessAudio,
+            Codec::Ac3
+            | Codec::Eac3
+            | Codec::Opus
+            | Codec::DvbSubtitles
+            | Codec::Teletext
+            | Codec::Klv => StreamType::Mpeg2PacketizedData,
+            Codec::Unknown(stream_type) => stream_type,
+        }
+    }
+
+    /// Returns the ES descriptors used to write a stream of the codec.
+    ///
+    /// `Codec::resolve(codec.stream_type(), &codec.descriptors())` returns the original codec
+    /// (except for `Codec::Unknown` with a stream type of a known codec).
+    /// The descriptors carry no optional fields (e.g., the subtitling and teletext descriptors have no entries,
+    /// and `Codec::Opus` has no `OpusAudioDescriptor` since the channel configuration is unknown),
+    /// so it may be necessary to add the entries or other descriptors (e.g., ISO 639 language descriptors).
+    pub fn descriptors(self) -> Vec<Descriptor> {
+        let registration = |id: &[u8; 4]| Descriptor {
+            tag: Descriptor::REGISTRATION_TAG,
+            data: id.to_vec(),
+        };
+        let descriptor = |tag, data: &[u8]| Descriptor {
+            tag,
+            data: data.to_vec(),
+        };
+        match self {
+            Codec::Ac3 => vec![descriptor(Self::AC3_TAG, &[0x00])],
+            Codec::Eac3 => vec![descriptor(Self::EAC3_TAG, &[0x00])],
+            Codec::Opus => vec![registration(b"Opus")],
+            Codec::DvbSubtitles => vec![descriptor(KnownDescriptor::SUBTITLING_TAG, &[])],
+            Codec::Teletext => vec![descriptor(Descriptor::TELETEXT_TAG, &[])],
+            Codec::Id3 => {
+                let mut data = vec![0xFF, 0xFF];
+                data.extend_from_slice(b"ID3 ");
+                data.push(0xFF);
+                data.extend_from_slice(b"ID3 ");
+                // metadata_service_id, decoder_config_flags = 0, DSM-CC_flag = 0 and reserved bits
+                data.extend_from_slice(&[0x00, 0x0F]);
+                vec![descriptor(Descriptor::METADATA_TAG, &data)]
+            }
+            Codec::Klv => vec![registration(b"KLVA")],
+            Codec::Scte35 => vec![registration(b"CUEI")],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::StreamId;
+    use crate::pes::{PesHeader, PesPacket, PesPacketWriter};
+    use crate::ts::{Pid, ReadTsPacket, TsPacketReader, TsPacketWriter, TsPayload};
+
+    fn descriptor(tag: u8, data: &[u8]) -> Descriptor {
+        Descriptor {
+            tag,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn resolve_works() {
+        let private = StreamType::Mpeg2PacketizedData;
+        let cases = [
+            (StreamType::H264, vec![], Codec::H264),
+            (
+                StreamType::DolbyDigitalUpToSixChannelAudio,
+                vec![],
+                Codec::Ac3,
+            ),
+            (private, vec![descriptor(0x05, b"AC-3")], Codec::Ac3),
+            (private, vec![descriptor(0x6A, &[0x00])], Codec::Ac3),
+            (private, vec![descriptor(0x7A, &[0x00])], Codec::Eac3),
+            (private, vec![descriptor(0x7F, &[0x80, 0x02])], Codec::Opus),
+            (private, vec![descriptor(0x7B, &[0; 5])], Codec::Dts),
+            // Unknown registrations are skipped
+            (
+                private,
+                vec![descriptor(0x05, b"HDMV"), descriptor(0x59, &[])],
+                Codec::DvbSubtitles,
+            ),
+            (private, vec![descriptor(0x56, &[])], Codec::Teletext),
+            (private, vec![descriptor(0x05, b"KLVA")], Codec::Klv),
+            (StreamType::Dts8ChannelLosslessAudio, vec![], Codec::Dts),
+            (
+                StreamType::Dts8ChannelLosslessAudio,
+                vec![descriptor(0x05, b"CUEI")],
+                Codec::Scte35,
+            ),
+            (
+                private,
+                vec![descriptor(0x0A, b"eng\0")],
+                Codec::Unknown(private),
+            ),
+            (StreamType::Mheg, vec![], Codec::Unknown(StreamType::Mheg)),
+        ];
+        for (stream_type, descriptors, codec) in cases {
+            assert_eq!(Codec::resolve(stream_type, &descriptors), codec);
+        }
+    }
+
+    #[test]
+    fn write_codecs() {
+        let codecs = [
+            Codec::H264,
+            Codec::Aac,
+            Codec::Ac3,
+            Codec::Eac3,
+            Codec::Opus,
+            Codec::Dts,
+            Codec::DvbSubtitles,
+            Codec::Teletext,
+            Codec::Id3,
+            Codec::Klv,
+            Codec::Unknown(StreamType::Mpeg2PacketizedData),
+        ];
+        let mut writer = PesPacketWriter::new(TsPacketWriter::new(Vec::new()));
+        for (i, &codec) in codecs.iter().enumerate() {
+            let pid = Pid::new(0x100 + i as u16).unwrap();
+            let packet = PesPacket::new(pid, PesHeader::new(StreamId::new(0xBD)), vec![0; 10]);
+            track_try_unwrap!(writer.write_codec_pes_packet(pid, codec, &packet));
+        }
+        let stream = writer.into_ts_packet_writer().into_stream();
+
+        let mut reader = TsPacketReader::new(&stream[..]);
+        let mut pmt = None;
+        while let Some(packet) = track_try_unwrap!(reader.read_ts_packet()) {
+            if let Some(TsPayload::Pmt(p)) = packet.payload {
+                pmt = Some(p);
+            }
+        }
+        let resolved = pmt
+            .unwrap()
+            .es_info
+            .iter()
+            .map(|es| es.codec())
+            .collect::<Vec<_>>();
+        assert_eq!(resolved, codecs);
+    }
+}
@@ -0,0 +1,327 @@
+use crate::pes::{PesPacket, PesPacketReader, ReadPesPacket};
+use crate::ts::{Pid, PidTable, ReadTsPacket, TsPacket};
+use crate::Result;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+/// Kind of the difference found by `compare_streams`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Difference {
+    /// The stream types declared by the PMTs or the stream IDs of the PES packets differ.
+    StreamMap,
+
+    /// The PTSs differ.
+    Pts,
+
+    /// The DTSs differ.
+    Dts,
+
+    /// The data bytes differ.
+    Data {
+        /// Offset of the first differing byte in the data.
+        ///
+        /// If one data is a prefix of the other, this is the size of the shorter one.
+        offset: usize,
+    },
+
+    /// The PES packet exists only in one of the streams.
+    Missing,
+}
+
+/// First divergence between two streams found by `compare_streams`.
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    /// PID of the diverging PES packets.
+    pub pid: Pid,
+
+    /// Index (zero origin) of the diverging PES packets among the PES packets on `pid`.
+    pub pes_index: u64,
+
+    /// Indices (zero origin) of the TS packets at which the diverging PES packets were completed
+    /// in the first and second streams, respectively.
+    ///
+    /// An index is `None` if the PES packet does not exist in the stream.
+    pub packet_indices: (Option<u64>, Option<u64>),
+
+    /// Kind of the difference.
+    pub difference: Difference,
+
+    /// Diverging PES packet in the first stream (if any).
+    pub a: Option<PesPacket<Vec<u8>>>,
+
+    /// Diverging PES packet in the second stream (if any).
+    pub b: Option<PesPacket<Vec<u8>>>,
+}
+
+/// Compares the elementary streams of two streams, and returns the first divergence (if any).
+///
+/// The streams are demultiplexed into the PES packets of each PID,
+/// and the PES packets are compared in order on each PID.
+/// Hence, the differences that do not change the elementary streams are ignored:
+/// the interleaving of the PIDs, stuffing, continuity counters, null packets,
+/// the PSI other than the stream types declared by the PMTs (e.g., version numbers), and so on.
+///
+/// Both streams are read alternately one PES packet at a time,
+/// so the PES packets waiting for their counterparts are buffered in memory.
+pub fn compare_streams<A, B>(a: A, b: B) -> Result<Option<Divergence>>
+where
+    A: ReadTsPacket,
+    B: ReadTsPacket,
+{
+    let mut a = Side::new(a);
+    let mut b = Side::new(b);
+    let mut pes_indices = PidTable::new();
+    while !(a.eos && b.eos) {
+        for pid in [track!(a.read())?, track!(b.read())?].into_iter().flatten() {
+            while !a.queue_mut(pid).is_empty() && !b.queue_mut(pid).is_empty() {
+                let x = a.queue_mut(pid).pop_front().expect("Never fails");
+                let y = b.queue_mut(pid).pop_front().expect("Never fails");
+                let pes_index = pes_indices.get(pid).copied().unwrap_or(0);
+                pes_indices.insert(pid, pes_index + 1);
+                if let Some(difference) = compare_pes_packets(&x.pes, &y.pes) {
+                    return Ok(Some(Divergence {
+                        pid,
+                        pes_index,
+                        packet_indices: (Some(x.packet_index), Some(y.packet_index)),
+                        difference,
+                        a: Some(x.pes),
+                        b: Some(y.pes),
+                    }));
+                }
+            }
+        }
+    }
+
+    // PES packets without counterparts
+    let pids = a.queues.iter().chain(b.queues.iter());
+    let Some(pid) = pids
+        .filter(|(_, q)| !q.is_empty())
+        .map(|(pid, _)| pid)
+        .min()
+    else {
+        return Ok(None);
+    };
+    let x = a.queue_mut(pid).pop_front();
+    let y = b.queue_mut(pid).pop_front();
+    Ok(Some(Divergence {
+        pid,
+        pes_index: pes_indices.get(pid).copied().unwrap_or(0),
+        packet_indices: (
+            x.as_ref().map(|x| x.packet_index),
+            y.as_ref().map(|y| y.packet_index),
+        ),
+        difference: Difference::Missing,
+        a: x.map(|x| x.pes),
+        b: y.map(|y| y.pes),
+    }))
+}
+
+fn compare_pes_packets(x: &PesPacket<Vec<u8>>, y: &PesPacket<Vec<u8>>) -> Option<Difference> {
+    if x.stream_type != y.stream_type || x.header.stream_id != y.header.stream_id {
+        Some(Difference::StreamMap)
+    } else if x.header.pts != y.header.pts {
+        Some(Difference::Pts)
+    } else if x.header.dts != y.header.dts {
+        Some(Difference::Dts)
+    } else if x.data != y.data {
+        let offset = x
+            .data
+            .iter()
+            .zip(&y.data)
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| x.data.len().min(y.data.len()));
+        Some(Difference::Data { offset })
+    } else {
+        None
+    }
+}
+
+/// One of the compared streams.
+struct Side<R> {
+    reader: PesPacketReader<PacketCounter<R>>,
+    queues: PidTable<VecDeque<QueuedPesPacket>>,
+    eos: bool,
+}
+impl<R: ReadTsPacket> Side<R> {
+    fn new(inner: R) -> Self {
+        Side {
+            reader: PesPacketReader::new(PacketCounter { inner, count: 0 }),
+            queues: PidTable::new(),
+            eos: false,
+        }
+    }
+
+    /// Reads the next PES packet (if any), and returns its PID.
+    fn read(&mut self) -> Result<Option<Pid>> {
+        if self.eos {
+            return Ok(None);
+        }
+        let Some(pes) = track!(self.reader.read_pes_packet())? else {
+            self.eos = true;
+            return Ok(None);
+        };
+        let pid = pes.pid;
+        let packet_index = self.reader.ts_packet_reader().count.saturating_sub(1);
+        self.queue_mut(pid)
+            .push_back(QueuedPesPacket { pes, packet_index });
+        Ok(Some(pid))
+    }
+
+    fn queue_mut(&mut self, pid: Pid) -> &mut VecDeque<QueuedPesPacket> {
+        if !self.queues.contains(pid) {
+            self.queues.insert(pid, VecDeque::new());
+        }
+        self.queues.get_mut(pid).expect("Never fails")
+    }
+}
+
+struct QueuedPesPacket {
+    pes: PesPacket<Vec<u8>>,
+    packet_index: u64,
+}
+
+/// TS packet reader that counts the read packets.
+struct PacketCounter<R> {
+    inner: R,
+    count: u64,
+}
+impl<R: ReadTsPacket> ReadTsPacket for PacketCounter<R> {
+    fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+        let packet = track!(self.inner.read_ts_packet())?;
+        if packet.is_some() {
+            self.count += 1;
+        }
+        Ok(packet)
+    }
+
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        self.inner.last_packet_byte_offset()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::StreamType;
+    use crate::time::Timestamp;
+    use crate::ts::payload::{Bytes, Null, Pat, Pmt};
+    use crate::ts::test_util::{self, pid};
+    use crate::ts::{
+        AdaptationField, TsPacketReader, TsPacketWriter, TsPayload, VersionNumber, WriteTsPacket,
+    };
+
+    fn psi(version: u8) -> Vec<(u16, TsPayload)> {
+        let version_number = VersionNumber::from_u8(version).unwrap();
+        let pat = TsPayload::Pat(Pat {
+            version_number,
+            ..test_util::pat(&[(1, 0x1000)])
+        });
+        let streams = [(256, StreamType::H264), (257, StreamType::AdtsAac)];
+        let pmt = TsPayload::Pmt(Pmt {
+            version_number,
+            ..test_util::pmt(1, None, &streams)
+        });
+        vec![(0, pat), (0x1000, pmt)]
+    }
+
+    fn pes(n: u16, i: u64) -> (u16, TsPayload) {
+        let stream_id = if n == 256 { 0xE0 } else { 0xC0 };
+        let pes = test_util::pes(stream_id, Some(i * 3600), None, &[i as u8; 100]);
+        (n, TsPayload::Pes(pes))
+    }
+
+    fn write(packets: Vec<(u16, TsPayload)>, first_counter: u8, stuffing: bool) -> Vec<u8> {
+        let items = packets.into_iter().map(|(n, payload)| {
+            let adaptation_field = (stuffing && n != 0 && n != 0x1000).then(|| AdaptationField {
+                transport_private_data: vec![0; 20],
+                ..Default::default()
+            });
+            (n, adaptation_field, Some(payload))
+        });
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packets(&test_util::packets(items, first_counter)));
+        writer.into_stream()
+    }
+
+    fn stream_a() -> Vec<u8> {
+        let mut packets = psi(0);
+        for i in 0..10 {
+            packets.push(pes(256, i));
+            packets.push(pes(257, i));
+        }
+        write(packets, 0, false)
+    }
+
+    /// Makes the same streams as `stream_a` with different interleaving, PSI versions, counters, stuffing and null packets.
+    fn stream_b(mut modify: impl FnMut(&mut Vec<(u16, TsPayload)>)) -> Vec<u8> {
+        let mut packets = psi(3);
+        for i in 0..10 {
+            packets.push(pes(257, i));
+            packets.push((Pid::NULL, TsPayload::Null(Null)));
+        }
+        for i in 0..10 {
+            packets.push(pes(256, i));
+        }
+        modify(&mut packets);
+        write(packets, 7, true)
+    }
+
+    fn compare(a: &[u8], b: &[u8]) -> Option<Divergence> {
+        track_try_unwrap!(compare_streams(
+            TsPacketReader::new(a),
+            TsPacketReader::new(b)
+        ))
+    }
+
+    #[test]
+    fn same_streams() {
+        let a = stream_a();
+        let b = stream_b(|_| {});
+        assert_ne!(a, b);
+        assert!(compare(&a, &b).is_none());
+    }
+
+    #[test]
+    fn different_streams() {
+        let a = stream_a();
+
+        // The data of the fourth video frame differs
+        let b = stream_b(|packets| {
+            let TsPayload::Pes(pes) = &mut packets[2 + 20 + 3].1 else {
+                unreachable!();
+            };
+            let mut data = pes.data.to_vec();
+            data[42] = 0xFF;
+            pes.data = Bytes::new(&data).unwrap();
+        });
+        let divergence = compare(&a, &b).unwrap();
+        assert_eq!(divergence.pid, pid(256));
+        assert_eq!(divergence.pes_index, 3);
+        assert_eq!(divergence.difference, Difference::Data { offset: 42 });
+
+        // The PTS of the second audio frame differs
+        let b = stream_b(|packets| {
+            let TsPayload::Pes(pes) = &mut packets[2 + 2].1 else {
+                unreachable!();
+            };
+            pes.header.pts = Some(Timestamp::new(0).unwrap());
+        });
+        let divergence = compare(&a, &b).unwrap();
+        assert_eq!(divergence.pid, pid(257));
+        assert_eq!(divergence.pes_index, 1);
+        assert_eq!(divergence.difference, Difference::Pts);
+        // Completed when the next audio frames start
+        assert_eq!(divergence.packet_indices, (Some(7), Some(6)));
+
+        // The last audio frame is missing
+        let b = stream_b(|packets| {
+            packets.remove(2 + 18);
+        });
+        let divergence = compare(&a, &b).unwrap();
+        assert_eq!(divergence.pid, pid(257));
+        assert_eq!(divergence.pes_index, 9);
+        assert_eq!(divergence.difference, Difference::Missing);
+        assert!(divergence.a.is_some());
+        assert!(divergence.b.is_none());
+    }
+}
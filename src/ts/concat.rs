@@ -0,0 +1,743 @@
+use crate::es::StreamType;
+use crate::time::{ClockReference, Timestamp};
+use crate::ts::payload::{Pat, Pmt};
+use crate::ts::{
+    AdaptationField, ContinuityCounter, Pid, PidTable, ReadTsPacket, TimestampShifter, TsPacket,
+    TsPayload, VersionNumber, WriteTsPacket,
+};
+use crate::{ErrorKind, Result};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+
+/// Maximum number of the packets of the second stream buffered by `concat` to find its first timestamps.
+const MAX_LOOKAHEAD_PACKETS: usize = 10_000;
+
+/// Maximum PTS/DTS interval regarded as a frame duration (one second).
+const MAX_FRAME_DURATION: u64 = Timestamp::RESOLUTION;
+
+/// Policy for handling the second stream of `concat` whose layout differs from the first one.
+///
+/// The layout of a stream consists of the programs in the PAT,
+/// and the PCR PIDs and the elementary streams (PIDs and stream types) in the PMTs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LayoutMismatchPolicy {
+    /// `concat` fails with an `ErrorKind::InvalidInput` error before writing any packet of the second stream
+    /// (the default).
+    #[default]
+    Error,
+
+    /// The program numbers and PIDs of the second stream are remapped to the ones of the first stream.
+    ///
+    /// The programs are associated in the order of the PATs, and
+    /// the elementary streams of each program are associated in the order of the PMTs among the ones
+    /// with the same stream type.
+    /// If some programs or streams of the second stream have no counterparts,
+    /// `concat` fails with an `ErrorKind::InvalidInput` error.
+    ///
+    /// The PATs and PMTs of the second stream are rewritten accordingly, so they must fit in a packet
+    /// (the packets carrying the fragments of the tables are dropped).
+    Remap,
+}
+
+/// Writes the packets of `first` followed by the ones of `second` to `writer`,
+/// so that the result plays as one continuous stream.
+///
+/// The timestamps (PTS, DTS, ESCR, PCR and OPCR) of `second` are offset, so that
+/// its earliest PTS follows the latest PTS of `first` by one frame duration
+/// (the last PTS/DTS interval of the stream), and its first PCR follows the last PCR of `first`.
+/// The continuity counters of `second` are continued from the ones of `first`, and
+/// the `discontinuity_indicator` is set in the first packet of each PID of `second`
+/// (an adaptation-field-only packet is inserted if the packet has no room for an adaptation field).
+///
+/// If the layouts (see `LayoutMismatchPolicy`) of the streams differ, `policy` is applied.
+///
+/// To find the first timestamps, the beginning of `second` is buffered until
+/// its first PCR and the first PTS of each video and audio stream are received
+/// (up to 10,000 packets).
+/// The PES headers that are not parsed by the reader (e.g., the scrambled ones) are left untouched.
+///
+/// It returns the offset (in 90 kHz ticks) added to the timestamps of `second`.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::ts::{concat, LayoutMismatchPolicy, TsPacketReader, TsPacketWriter};
+///
+/// let mut first = TsPacketReader::new(mpeg2ts::io::empty());
+/// let mut second = TsPacketReader::new(mpeg2ts::io::empty());
+/// let mut writer = TsPacketWriter::new(Vec::new());
+/// concat(&mut first, &mut second, &mut writer, LayoutMismatchPolicy::Error).unwrap();
+/// ```
+pub fn concat<A, B, W>(
+    first: &mut A,
+    second: &mut B,
+    writer: &mut W,
+    policy: LayoutMismatchPolicy,
+) -> Result<u64>
+where
+    A: ReadTsPacket,
+    B: ReadTsPacket,
+    W: WriteTsPacket,
+{
+    let mut a = StreamState::new();
+    let mut counters = PidTable::new();
+    while let Some(packet) = track!(first.read_ts_packet())? {
+        a.observe(&packet);
+        counters.insert(packet.header.pid, packet.header.continuity_counter);
+        track!(writer.write_ts_packet(&packet))?;
+    }
+
+    let mut b = StreamState::new();
+    let mut pending = VecDeque::new();
+    while pending.len() < MAX_LOOKAHEAD_PACKETS && !b.is_ready() {
+        let Some(packet) = track!(second.read_ts_packet())? else {
+            break;
+        };
+        b.observe(&packet);
+        pending.push_back(packet);
+    }
+
+    let remapper = if a.layout.shape() == b.layout.shape() {
+        None
+    } else {
+        track_assert_eq!(
+            policy,
+            LayoutMismatchPolicy::Remap,
+            ErrorKind::InvalidInput,
+            "The stream layouts differ"
+        );
+        Some(track!(Remapper::new(&a.layout, &b.layout))?)
+    };
+    let offset = track!(seam_offset(&a, &b))?;
+    let mut seam = Seam {
+        shifter: TimestampShifter::new(offset as i64),
+        pcr_offset: offset * 300,
+        remapper,
+        counters,
+        counter_shifts: PidTable::new(),
+    };
+    loop {
+        let packet = if let Some(packet) = pending.pop_front() {
+            packet
+        } else if let Some(packet) = track!(second.read_ts_packet())? {
+            packet
+        } else {
+            break;
+        };
+        for packet in seam.transform(packet) {
+            track!(writer.write_ts_packet(&packet))?;
+        }
+    }
+    Ok(offset)
+}
+
+/// Returns the offset (in 90 kHz ticks) to be added to the timestamps of the second stream.
+fn seam_offset(a: &StreamState, b: &StreamState) -> Result<u64> {
+    let mut offset = None;
+    if let (Some(end), Some(start)) = (a.end_pts(), b.start_pts()) {
+        offset = Some(track!(Timestamp::new(end.wrapping_sub(start)))?);
+    }
+    if let (Some(last), Some(first)) = (a.last_pcr, b.first_pcr) {
+        // `Timestamp::from` wraps the PCR bases around, so malformed PCRs beyond 33 bits are tolerated
+        let last = Timestamp::from(last);
+        let first = Timestamp::from(first);
+        let o = track!(Timestamp::new(last.wrapping_add(1).wrapping_sub(first)))?;
+        if offset.map_or(true, |offset| o.delta(offset) > 0) {
+            offset = Some(o);
+        }
+    }
+    Ok(offset.map_or(0, |o| o.as_u64()))
+}
+
+/// Information on a stream collected by `concat`.
+#[derive(Debug)]
+struct StreamState {
+    layout: Layout,
+    timestamps: PidTable<EsTimestamps>,
+    first_pcr: Option<ClockReference>,
+    last_pcr: Option<ClockReference>,
+}
+impl StreamState {
+    fn new() -> Self {
+        StreamState {
+            layout: Layout::default(),
+            timestamps: PidTable::new(),
+            first_pcr: None,
+            last_pcr: None,
+        }
+    }
+
+    fn observe(&mut self, packet: &TsPacket) {
+        let pid = packet.header.pid;
+        match packet.payload {
+            Some(TsPayload::Pat(ref pat)) if pat.is_current => {
+                self.layout.pat = Some(pat.clone());
+                let pmt_pids = pat
+                    .table
+                    .iter()
+                    .filter(|pa| pa.program_num != 0)
+                    .map(|pa| pa.program_map_pid)
+                    .collect::<Vec<_>>();
+                self.layout.pmts.retain(|pid, _| pmt_pids.contains(pid));
+            }
+            Some(TsPayload::Pmt(ref pmt)) if pmt.is_current => {
+                self.layout.pmts.insert(pid, pmt.clone());
+            }
+            Some(TsPayload::Pes(ref pes)) => {
+                if let Some(pts) = pes.header.pts {
+                    let dts = pes.header.dts.unwrap_or(pts);
+                    if let Some(t) = self.timestamps.get_mut(pid) {
+                        t.update(pts, dts);
+                    } else {
+                        self.timestamps.insert(pid, EsTimestamps::new(pts, dts));
+                    }
+                }
+            }
+            _ => {}
+        }
+        if let Some(pcr) = packet.adaptation_field.as_ref().and_then(|a| a.pcr) {
+            if self.layout.is_pcr_pid(pid) || self.layout.pmts.is_empty() {
+                self.first_pcr.get_or_insert(pcr);
+                self.last_pcr = Some(pcr);
+            }
+        }
+    }
+
+    /// Returns `true` if the layout and the first timestamps of the stream have been received.
+    fn is_ready(&self) -> bool {
+        if !self.layout.is_complete() {
+            return false;
+        }
+        if self.first_pcr.is_none() && self.layout.pcr_pids().next().is_some() {
+            return false;
+        }
+        self.layout
+            .pmts
+            .values()
+            .flat_map(|pmt| &pmt.es_info)
+            .filter(|es| es.is_video() || es.is_audio())
+            .all(|es| self.timestamps.contains(es.elementary_pid))
+    }
+
+    /// Returns the earliest first PTS of the elementary streams.
+    fn start_pts(&self) -> Option<Timestamp> {
+        extreme(self.timestamps.iter().map(|(_, t)| t.first_pts), |x, y| {
+            x.delta(y) < 0
+        })
+    }
+
+    /// Returns the time following the latest PTS of the elementary streams by one frame duration.
+    fn end_pts(&self) -> Option<Timestamp> {
+        let ends = self
+            .timestamps
+            .iter()
+            .map(|(_, t)| t.max_pts.wrapping_add(t.frame_duration.max(1)));
+        extreme(ends, |x, y| x.delta(y) > 0)
+    }
+}
+
+/// Returns the timestamp that `precedes` all the others.
+fn extreme<I, F>(iter: I, precedes: F) -> Option<Timestamp>
+where
+    I: Iterator<Item = Timestamp>,
+    F: Fn(Timestamp, Timestamp) -> bool,
+{
+    iter.fold(None, |acc, t| match acc {
+        Some(acc) if !precedes(t, acc) => Some(acc),
+        _ => Some(t),
+    })
+}
+
+#[derive(Debug)]
+struct EsTimestamps {
+    first_pts: Timestamp,
+    max_pts: Timestamp,
+    last_dts: Timestamp,
+    frame_duration: u64,
+}
+impl EsTimestamps {
+    fn new(pts: Timestamp, dts: Timestamp) -> Self {
+        EsTimestamps {
+            first_pts: pts,
+            max_pts: pts,
+            last_dts: dts,
+            frame_duration: 0,
+        }
+    }
+
+    fn update(&mut self, pts: Timestamp, dts: Timestamp) {
+        if pts.delta(self.max_pts) > 0 {
+            self.max_pts = pts;
+        }
+        let delta = dts.delta(self.last_dts);
+        if 0 < delta && delta as u64 <= MAX_FRAME_DURATION {
+            self.frame_duration = delta as u64;
+        }
+        self.last_dts = dts;
+    }
+}
+
+/// Program number, PMT PID, PCR PID and elementary streams of a program.
+type ProgramShape = (u16, Pid, Option<Pid>, Vec<(Pid, StreamType)>);
+
+/// Programs and elementary streams of a stream.
+#[derive(Debug, Default)]
+struct Layout {
+    pat: Option<Pat>,
+    pmts: BTreeMap<Pid, Pmt>,
+}
+impl Layout {
+    fn programs(&self) -> impl Iterator<Item = (u16, Pid)> + '_ {
+        self.pat
+            .iter()
+            .flat_map(|pat| &pat.table)
+            .filter(|pa| pa.program_num != 0)
+            .map(|pa| (pa.program_num, pa.program_map_pid))
+    }
+
+    fn is_complete(&self) -> bool {
+        self.pat.is_some() && self.programs().all(|(_, pid)| self.pmts.contains_key(&pid))
+    }
+
+    fn pcr_pids(&self) -> impl Iterator<Item = Pid> + '_ {
+        self.pmts
+            .values()
+            .filter_map(|pmt| pmt.pcr_pid)
+            .filter(|pid| pid.as_u16() != Pid::NULL)
+    }
+
+    fn is_pcr_pid(&self, pid: Pid) -> bool {
+        self.pcr_pids().any(|p| p == pid)
+    }
+
+    /// Returns the properties compared by `concat`.
+    fn shape(&self) -> Vec<ProgramShape> {
+        self.programs()
+            .map(|(program_num, pid)| {
+                let pmt = self.pmts.get(&pid);
+                let streams = pmt
+                    .iter()
+                    .flat_map(|pmt| &pmt.es_info)
+                    .map(|es| (es.elementary_pid, es.stream_type))
+                    .collect();
+                (program_num, pid, pmt.and_then(|pmt| pmt.pcr_pid), streams)
+            })
+            .collect()
+    }
+}
+
+/// Maps the program numbers and PIDs of the second stream to the ones of the first stream.
+#[derive(Debug)]
+struct Remapper {
+    programs: BTreeMap<u16, u16>,
+    pids: PidTable<Pid>,
+    pat: Pat,
+    pmts: BTreeMap<u16, Pmt>,
+}
+impl Remapper {
+    fn new(a: &Layout, b: &Layout) -> Result<Self> {
+        let a_programs = a.programs().collect::<Vec<_>>();
+        let b_programs = b.programs().collect::<Vec<_>>();
+        track_assert_eq!(
+            a_programs.len(),
+            b_programs.len(),
+            ErrorKind::InvalidInput,
+            "The numbers of the programs differ"
+        );
+        let a_pat = track_assert_some!(a.pat.clone(), ErrorKind::InvalidInput);
+
+        let mut remapper = Remapper {
+            programs: BTreeMap::new(),
+            pids: PidTable::new(),
+            pat: a_pat,
+            pmts: BTreeMap::new(),
+        };
+        for (&(a_num, a_pmt_pid), &(b_num, b_pmt_pid)) in a_programs.iter().zip(&b_programs) {
+            remapper.programs.insert(b_num, a_num);
+            track!(remapper.map_pid(b_pmt_pid, a_pmt_pid))?;
+
+            let a_pmt = track_assert_some!(a.pmts.get(&a_pmt_pid), ErrorKind::InvalidInput);
+            let b_pmt = track_assert_some!(b.pmts.get(&b_pmt_pid), ErrorKind::InvalidInput);
+            for (i, es) in b_pmt.es_info.iter().enumerate() {
+                let nth = b_pmt.es_info[..i]
+                    .iter()
+                    .filter(|x| x.stream_type == es.stream_type)
+                    .count();
+                let counterpart = track_assert_some!(
+                    a_pmt
+                        .es_info
+                        .iter()
+                        .filter(|x| x.stream_type == es.stream_type)
+                        .nth(nth),
+                    ErrorKind::InvalidInput,
+                    "No counterpart of the stream: program_num={}, pid={:?}, stream_type={:?}",
+                    b_num,
+                    es.elementary_pid,
+                    es.stream_type
+                );
+                track!(remapper.map_pid(es.elementary_pid, counterpart.elementary_pid))?;
+            }
+            if let (Some(b_pcr_pid), Some(a_pcr_pid)) = (b_pmt.pcr_pid, a_pmt.pcr_pid) {
+                if !remapper.pids.contains(b_pcr_pid) {
+                    track!(remapper.map_pid(b_pcr_pid, a_pcr_pid))?;
+                }
+            }
+            remapper.pmts.insert(a_num, a_pmt.clone());
+        }
+        Ok(remapper)
+    }
+
+    fn map_pid(&mut self, from: Pid, to: Pid) -> Result<()> {
+        if let Some(&old) = self.pids.get(from) {
+            track_assert_eq!(
+                old,
+                to,
+                ErrorKind::InvalidInput,
+                "Conflicting PID mapping: from={:?}",
+                from
+            );
+        }
+        self.pids.insert(from, to);
+        Ok(())
+    }
+
+    /// Returns `true` if `pid` carries the tables rewritten by this remapper.
+    fn is_table_pid(&self, pid: Pid) -> bool {
+        pid.as_u16() == Pid::PAT || self.pat.table.iter().any(|pa| pa.program_map_pid == pid)
+    }
+
+    fn pid(&self, pid: Pid) -> Pid {
+        self.pids.get(pid).copied().unwrap_or(pid)
+    }
+
+    fn remap_pat(&self, mut pat: Pat) -> Pat {
+        pat.transport_stream_id = self.pat.transport_stream_id;
+        pat.raw = None;
+        for pa in &mut pat.table {
+            pa.program_num = self
+                .programs
+                .get(&pa.program_num)
+                .copied()
+                .unwrap_or(pa.program_num);
+            pa.program_map_pid = self.pid(pa.program_map_pid);
+        }
+        pat.version_number = next_version(self.pat.table == pat.table, self.pat.version_number);
+        pat
+    }
+
+    fn remap_pmt(&self, mut pmt: Pmt) -> Pmt {
+        pmt.raw = None;
+        pmt.program_num = self
+            .programs
+            .get(&pmt.program_num)
+            .copied()
+            .unwrap_or(pmt.program_num);
+        pmt.pcr_pid = pmt.pcr_pid.map(|pid| self.pid(pid));
+        for es in &mut pmt.es_info {
+            es.elementary_pid = self.pid(es.elementary_pid);
+        }
+        if let Some(a_pmt) = self.pmts.get(&pmt.program_num) {
+            let same = Pmt {
+                version_number: a_pmt.version_number,
+                is_current: a_pmt.is_current,
+                raw: a_pmt.raw.clone(),
+                ..pmt.clone()
+            } == *a_pmt;
+            pmt.version_number = next_version(same, a_pmt.version_number);
+        }
+        pmt
+    }
+}
+
+/// Returns the version number of a rewritten table that follows the table of the first stream numbered `version`.
+fn next_version(same_table: bool, mut version: VersionNumber) -> VersionNumber {
+    if !same_table {
+        version.increment();
+    }
+    version
+}
+
+/// Transform applied to the packets of the second stream.
+#[derive(Debug)]
+struct Seam {
+    shifter: TimestampShifter,
+    pcr_offset: u64,
+    remapper: Option<Remapper>,
+
+    // Continuity counters of the last written packets
+    counters: PidTable<ContinuityCounter>,
+
+    // Values added to the continuity counters of the second stream (`None` if the counters are renumbered)
+    counter_shifts: PidTable<Option<u8>>,
+}
+impl Seam {
+    fn transform(&mut self, mut packet: TsPacket) -> Vec<TsPacket> {
+        if let Some(remapper) = &self.remapper {
+            packet.header.pid = remapper.pid(packet.header.pid);
+            match packet.payload.take() {
+                Some(TsPayload::Pat(pat)) => {
+                    packet.payload = Some(TsPayload::Pat(remapper.remap_pat(pat)));
+                    packet.adaptation_field = None;
+                }
+                Some(TsPayload::Pmt(pmt)) => {
+                    packet.payload = Some(TsPayload::Pmt(remapper.remap_pmt(pmt)));
+                    packet.adaptation_field = None;
+                }
+                _ if remapper.is_table_pid(packet.header.pid) => {
+                    // Fragments of the tables
+                    return Vec::new();
+                }
+                payload => packet.payload = payload,
+            }
+        }
+
+        self.shifter.shift_packet(&mut packet);
+        if let Some(a) = &mut packet.adaptation_field {
+            a.pcr = a.pcr.map(|pcr| pcr.wrapping_add(self.pcr_offset));
+            a.opcr = a.opcr.map(|opcr| opcr.wrapping_add(self.pcr_offset));
+        }
+
+        let pid = packet.header.pid;
+        let has_payload = packet.payload.is_some();
+        let cc = packet.header.continuity_counter.as_u8();
+        let last = self.counters.get(pid).map(|c| c.as_u8());
+        let is_first = !self.counter_shifts.contains(pid);
+        if is_first {
+            let renumbered = self.remapper.as_ref().is_some_and(|r| r.is_table_pid(pid));
+            let shift = match last {
+                _ if renumbered => None,
+                Some(last) => Some((last + u8::from(has_payload)).wrapping_sub(cc)),
+                None => Some(0),
+            };
+            self.counter_shifts.insert(pid, shift);
+        }
+        let cc = match (self.counter_shifts.get(pid).copied().flatten(), last) {
+            (Some(shift), _) => cc.wrapping_add(shift),
+            (None, Some(last)) => last + u8::from(has_payload),
+            (None, None) => cc,
+        };
+        packet.header.continuity_counter =
+            ContinuityCounter::from_u8(cc & ContinuityCounter::MAX).expect("Never fails");
+        self.counters.insert(pid, packet.header.continuity_counter);
+
+        let mut packets = Vec::with_capacity(2);
+        if is_first {
+            if let Some(mut af_only) = mark_discontinuity(&mut packet) {
+                // The counter is not incremented by the packets without payloads
+                if let Some(last) = last {
+                    af_only.header.continuity_counter =
+                        ContinuityCounter::from_u8(last).expect("Never fails");
+                }
+                packets.push(af_only);
+            }
+        }
+        packets.push(packet);
+        packets
+    }
+}
+
+/// Sets the `discontinuity_indicator` of `packet`.
+///
+/// If `packet` has no room for an adaptation field, an adaptation-field-only packet to be written before it is returned.
+fn mark_discontinuity(packet: &mut TsPacket) -> Option<TsPacket> {
+    if let Some(a) = &mut packet.adaptation_field {
+        a.discontinuity_indicator = true;
+        return None;
+    }
+    let adaptation_field = AdaptationField {
+        discontinuity_indicator: true,
+        ..Default::default()
+    };
+    let payload_len = packet.payload.as_ref().map_or(0, |payload| {
+        let mut buf = Vec::with_capacity(TsPacket::SIZE);
+        payload
+            .write_to(&mut buf)
+            .map_or(usize::MAX, |()| buf.len())
+    });
+    if payload_len.saturating_add(adaptation_field.external_size()) <= AdaptationField::MAX_SIZE {
+        packet.adaptation_field = Some(adaptation_field);
+        return None;
+    }
+    Some(TsPacket {
+        header: packet.header.clone(),
+        adaptation_field: Some(adaptation_field),
+        payload: None,
+        arrival_timestamp: packet.arrival_timestamp,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ts::test_util::{self, pid, ts};
+    use crate::ts::{ContinuityChecker, TsPacketReader, TsPacketWriter};
+    use std::collections::HashMap;
+
+    /// Makes a stream that has a video stream on `video_pid` (also carrying the PCRs) and an audio stream on PID 257.
+    fn stream(video_pid: u16, first_pts: u64, frames: u64) -> Vec<u8> {
+        let pat = TsPayload::Pat(test_util::pat(&[(1, 0x1000)]));
+        let streams = [(video_pid, StreamType::H264), (257, StreamType::AdtsAac)];
+        let pmt = TsPayload::Pmt(test_util::pmt(1, Some(video_pid), &streams));
+        let mut packets = vec![(0, None, Some(pat)), (0x1000, None, Some(pmt))];
+        for i in 0..frames {
+            // Video frames are reordered (I P B B ...)
+            let order = [0, 3, 1, 2][i as usize % 4] + i / 4 * 4;
+            let dts = first_pts + i * 3003;
+            let pts = first_pts + 3003 + order * 3003;
+            let pcr = ClockReference::new((dts - 9000) % Timestamp::MODULUS * 300).unwrap();
+            let video = test_util::pes(0xE0, Some(pts), Some(dts), &[0; 100]);
+            packets.push((
+                video_pid,
+                Some(AdaptationField::with_pcr(pcr)),
+                Some(TsPayload::Pes(video)),
+            ));
+
+            // The audio packets are full, so that no adaptation field can be added
+            let audio = test_util::pes(0xC0, Some(first_pts + i * 1920), None, &[0; 184 - 14]);
+            packets.push((257, None, Some(TsPayload::Pes(audio))));
+        }
+        test_util::write_packets(packets)
+    }
+
+    fn read_packets(stream: &[u8]) -> Vec<TsPacket> {
+        let mut reader = TsPacketReader::new(stream);
+        let mut packets = Vec::new();
+        while let Some(packet) = track_try_unwrap!(reader.read_ts_packet()) {
+            packets.push(packet);
+        }
+        packets
+    }
+
+    #[test]
+    fn concat_streams() {
+        let a = stream(256, 90_000, 8);
+        // Wraps around in the middle
+        let b = stream(256, Timestamp::MODULUS - 3 * 3003, 8);
+        let mut writer = TsPacketWriter::new(Vec::new());
+        let offset = track_try_unwrap!(concat(
+            &mut TsPacketReader::new(&a[..]),
+            &mut TsPacketReader::new(&b[..]),
+            &mut writer,
+            LayoutMismatchPolicy::Error
+        ));
+        let packets = read_packets(&writer.into_stream());
+        let a_len = read_packets(&a).len();
+        assert_eq!(
+            packets.len(),
+            a_len + read_packets(&b).len() + 1 /* AF-only */
+        );
+
+        // The earliest PTS of B follows the latest PTS of A by one frame
+        let a_end = 90_000 + 3003 + 7 * 3003 + 3003;
+        assert_eq!(offset, ts(a_end + 3 * 3003).as_u64());
+
+        let mut checker = ContinuityChecker::new();
+        let mut last_pts = HashMap::new();
+        let mut last_dts = None;
+        let mut last_pcr = None;
+        for (i, packet) in packets.iter().enumerate() {
+            assert!(!checker.check(packet).is_discontinuous());
+
+            let discontinuity = packet
+                .adaptation_field
+                .as_ref()
+                .is_some_and(|a| a.discontinuity_indicator);
+            let first_in_b = (a_len..a_len + 4).contains(&i);
+            assert_eq!(discontinuity, first_in_b, "{}", i);
+            if i == a_len + 3 {
+                // The audio packet has no room, so an adaptation-field-only packet precedes it
+                assert!(packet.payload.is_none());
+                assert_eq!(packets[i + 1].header.pid, pid(257));
+            }
+
+            if let Some(pcr) = packet.adaptation_field.as_ref().and_then(|a| a.pcr) {
+                if let Some(last) = last_pcr {
+                    assert!(pcr.delta(last) > 0);
+                }
+                last_pcr = Some(pcr);
+            }
+            let Some(TsPayload::Pes(pes)) = &packet.payload else {
+                continue;
+            };
+            let pts = pes.header.pts.unwrap();
+            if packet.header.pid == pid(257) {
+                if let Some(last) = last_pts.insert(packet.header.pid, pts) {
+                    assert!(pts.delta(last) > 0);
+                }
+            } else {
+                let dts = pes.header.dts.unwrap();
+                if let Some(last) = last_dts {
+                    assert!(dts.delta(last) > 0);
+                }
+                last_dts = Some(dts);
+                // Presentation order is kept across the seam
+                if i > a_len {
+                    assert!(pts.delta(ts(a_end - 3003)) > 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn concat_with_malformed_pcr() {
+        let mut a = stream(256, 90_000, 4);
+        let b = stream(256, 90_000, 4);
+
+        // The last PCR of A has the maximum base (2^33 - 1) and an extension of 511
+        let packet = a
+            .chunks_mut(TsPacket::SIZE)
+            .filter(|p| p[3] & 0x20 != 0 && p[5] & 0x10 != 0)
+            .last()
+            .unwrap();
+        let pcr = ClockReference::new(ClockReference::MAX).unwrap();
+        track_try_unwrap!(pcr.write_pcr_to(&mut packet[6..12]));
+
+        let mut writer = TsPacketWriter::new(Vec::new());
+        let offset = track_try_unwrap!(concat(
+            &mut TsPacketReader::new(&a[..]),
+            &mut TsPacketReader::new(&b[..]),
+            &mut writer,
+            LayoutMismatchPolicy::Error
+        ));
+        assert!(offset < Timestamp::MODULUS);
+    }
+
+    #[test]
+    fn concat_different_layouts() {
+        let a = stream(256, 90_000, 4);
+        let b = stream(300, 90_000, 4);
+
+        let mut writer = TsPacketWriter::new(Vec::new());
+        let e = concat(
+            &mut TsPacketReader::new(&a[..]),
+            &mut TsPacketReader::new(&b[..]),
+            &mut writer,
+            LayoutMismatchPolicy::Error,
+        )
+        .unwrap_err();
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+        // No packet of B is written
+        assert_eq!(writer.into_stream(), a);
+
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(concat(
+            &mut TsPacketReader::new(&a[..]),
+            &mut TsPacketReader::new(&b[..]),
+            &mut writer,
+            LayoutMismatchPolicy::Remap
+        ));
+        let packets = read_packets(&writer.into_stream());
+        let mut checker = ContinuityChecker::new();
+        for packet in &packets {
+            assert!(!checker.check(packet).is_discontinuous());
+            assert_ne!(packet.header.pid, pid(300));
+            if let Some(TsPayload::Pmt(pmt)) = &packet.payload {
+                assert_eq!(pmt.pcr_pid, Some(pid(256)));
+                assert_eq!(pmt.es_info[0].elementary_pid, pid(256));
+                assert_eq!(pmt.version_number, VersionNumber::new());
+            }
+        }
+        let video_packets = packets.iter().filter(|p| p.header.pid == pid(256)).count();
+        assert_eq!(video_packets, 8);
+    }
+}
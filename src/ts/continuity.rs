@@ -0,0 +1,435 @@
+use crate::ts::{ContinuityCounter, Pid, PidTable, ReadTsPacket, TsPacket, WriteTsPacket};
+use crate::util::Fnv1aHasher;
+use crate::{ErrorKind, Result};
+use alloc::collections::BTreeMap;
+use core::hash::{Hash, Hasher};
+
+/// Continuity counter checking mode of `TsPacketReader`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContinuityCheck {
+    /// Continuity counters are not checked (the default).
+    #[default]
+    Disabled,
+
+    /// Discontinuities are counted, but reading continues.
+    Count,
+
+    /// Discontinuities are reported as `ErrorKind::ContinuityError` errors.
+    Strict,
+}
+/// Result of checking the continuity counter of a TS packet.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Continuity {
+    /// The packet follows the previous one (or is not subject to the check).
+    Continuous,
+
+    /// The packet is a duplicate of the previous one.
+    Duplicate,
+
+    /// The continuity counter of the packet is not the expected one.
+    Discontinuous {
+        expected: ContinuityCounter,
+        actual: ContinuityCounter,
+    },
+}
+impl Continuity {
+    /// Returns `true` if the packet is discontinuous, otherwise `false`.
+    pub fn is_discontinuous(&self) -> bool {
+        matches!(self, Continuity::Discontinuous { .. })
+    }
+}
+
+/// Per-PID continuity counter checker.
+///
+/// Packets without payload and null packets are not checked,
+/// and the state of a PID is reset when the packet has the discontinuity indicator set.
+/// A single exact duplicate of the previous packet is permitted.
+#[derive(Debug, Default)]
+pub struct ContinuityChecker {
+    states: PidTable<State>,
+}
+impl ContinuityChecker {
+    /// Makes a new `ContinuityChecker` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks the continuity counter of the given packet.
+    pub fn check(&mut self, packet: &TsPacket) -> Continuity {
+        if packet.payload.is_none() || packet.header.pid.as_u16() == Pid::NULL {
+            return Continuity::Continuous;
+        }
+
+        let counter = packet.header.continuity_counter;
+        let mut hasher = Fnv1aHasher::default();
+        packet.adaptation_field.hash(&mut hasher);
+        packet.payload.hash(&mut hasher);
+        let packet_hash = hasher.finish();
+
+        let discontinuity_indicator = packet
+            .adaptation_field
+            .as_ref()
+            .is_some_and(|a| a.discontinuity_indicator);
+        let new_state = State {
+            counter,
+            packet_hash,
+            duplicated: false,
+        };
+        let state = match self.states.insert(packet.header.pid, new_state) {
+            Some(state) if !discontinuity_indicator => state,
+            _ => return Continuity::Continuous,
+        };
+
+        if counter == state.counter && packet_hash == state.packet_hash && !state.duplicated {
+            if let Some(s) = self.states.get_mut(packet.header.pid) {
+                s.duplicated = true;
+            }
+            return Continuity::Duplicate;
+        }
+
+        let mut expected = state.counter;
+        expected.increment();
+        if counter == expected {
+            Continuity::Continuous
+        } else {
+            Continuity::Discontinuous {
+                expected,
+                actual: counter,
+            }
+        }
+    }
+
+    /// Forgets the state of all PIDs.
+    pub fn reset(&mut self) {
+        self.states.clear();
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    counter: ContinuityCounter,
+    packet_hash: u64,
+    duplicated: bool,
+}
+
+/// Reads the whole of `reader`, and writes the packets to `writer` with their continuity counters repaired.
+///
+/// Returns the number of the rewritten packets per PID (see `ContinuityRepairer`).
+pub fn repair_continuity<R, W>(reader: R, writer: W) -> Result<BTreeMap<Pid, u64>>
+where
+    R: ReadTsPacket,
+    W: WriteTsPacket,
+{
+    let mut repairer = ContinuityRepairer::new();
+    track!(repairer.repair_stream(reader, writer))?;
+    Ok(repairer.into_rewritten_packets())
+}
+
+/// Transform that renumbers the continuity counters so that the sequence of each PID is contiguous.
+///
+/// The first packet of each PID keeps its counter, and the counters of the following packets are
+/// the previous ones incremented by one.
+/// As in `ContinuityChecker`, packets without payload do not increment the counter,
+/// a single exact duplicate of the previous packet keeps the counter of the previous packet,
+/// and null packets are left untouched.
+///
+/// Only the header fields are modified, so the packets can be repaired
+/// either in the parsed form (`repair_packet`) or in the serialized form (`repair_bytes`).
+#[derive(Debug, Default)]
+pub struct ContinuityRepairer {
+    clear_transport_error_indicator: bool,
+    states: PidTable<RepairState>,
+    rewritten_packets: BTreeMap<Pid, u64>,
+}
+impl ContinuityRepairer {
+    /// Makes a new `ContinuityRepairer` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether to clear the `transport_error_indicator` of the packets.
+    ///
+    /// The default value is `false`.
+    pub fn set_clear_transport_error_indicator(&mut self, clear: bool) {
+        self.clear_transport_error_indicator = clear;
+    }
+
+    /// Returns the number of the rewritten packets per PID.
+    ///
+    /// A packet is counted if its continuity counter or `transport_error_indicator` was changed.
+    pub fn rewritten_packets(&self) -> &BTreeMap<Pid, u64> {
+        &self.rewritten_packets
+    }
+
+    /// Takes ownership of `self`, and returns the number of the rewritten packets per PID.
+    pub fn into_rewritten_packets(self) -> BTreeMap<Pid, u64> {
+        self.rewritten_packets
+    }
+
+    /// Reads the whole of `reader`, and writes the repaired packets to `writer`.
+    pub fn repair_stream<R, W>(&mut self, mut reader: R, mut writer: W) -> Result<()>
+    where
+        R: ReadTsPacket,
+        W: WriteTsPacket,
+    {
+        while let Some(mut packet) = track!(reader.read_ts_packet())? {
+            self.repair_packet(&mut packet);
+            track!(writer.write_ts_packet(&packet))?;
+        }
+        Ok(())
+    }
+
+    /// Repairs `packet`, and returns `true` if it was rewritten.
+    pub fn repair_packet(&mut self, packet: &mut TsPacket) -> bool {
+        let pid = packet.header.pid;
+        let counter = self.next_counter(
+            pid,
+            packet.header.continuity_counter,
+            packet.payload.is_some(),
+            || {
+                let mut hasher = Fnv1aHasher::default();
+                packet.adaptation_field.hash(&mut hasher);
+                packet.payload.hash(&mut hasher);
+                hasher.finish()
+            },
+        );
+        let clear = self.clear_transport_error_indicator && packet.header.transport_error_indicator;
+        let rewritten = counter != packet.header.continuity_counter || clear;
+        packet.header.continuity_counter = counter;
+        if clear {
+            packet.header.transport_error_indicator = false;
+        }
+        if rewritten {
+            *self.rewritten_packets.entry(pid).or_insert(0) += 1;
+        }
+        rewritten
+    }
+
+    /// Repairs the serialized packet `bytes` in place, and returns `true` if it was rewritten.
+    ///
+    /// `bytes` must start with the TS header (i.e., the sync byte).
+    pub fn repair_bytes(&mut self, bytes: &mut [u8]) -> Result<bool> {
+        track_assert!(
+            bytes.len() >= 4,
+            ErrorKind::InvalidInput,
+            "size={}",
+            bytes.len()
+        );
+        track_assert_eq!(bytes[0], TsPacket::SYNC_BYTE, ErrorKind::InvalidInput);
+
+        let pid = track!(Pid::new(
+            (u16::from(bytes[1] & 0b1_1111) << 8) | u16::from(bytes[2])
+        ))?;
+        let has_payload = bytes[3] & 0b0001_0000 != 0;
+        let actual = track!(ContinuityCounter::from_u8(bytes[3] & 0b1111))?;
+        let counter = self.next_counter(pid, actual, has_payload, || {
+            let mut hasher = Fnv1aHasher::default();
+            bytes[4..].hash(&mut hasher);
+            hasher.finish()
+        });
+        let clear = self.clear_transport_error_indicator && bytes[1] & 0b1000_0000 != 0;
+        let rewritten = counter != actual || clear;
+        bytes[3] = (bytes[3] & 0b1111_0000) | counter.as_u8();
+        if clear {
+            bytes[1] &= 0b0111_1111;
+        }
+        if rewritten {
+            *self.rewritten_packets.entry(pid).or_insert(0) += 1;
+        }
+        Ok(rewritten)
+    }
+
+    fn next_counter<F>(
+        &mut self,
+        pid: Pid,
+        actual: ContinuityCounter,
+        has_payload: bool,
+        packet_hash: F,
+    ) -> ContinuityCounter
+    where
+        F: FnOnce() -> u64,
+    {
+        if pid.as_u16() == Pid::NULL {
+            return actual;
+        }
+        let Some(state) = self.states.get_mut(pid) else {
+            let packet_hash = if has_payload {
+                Some(packet_hash())
+            } else {
+                None
+            };
+            self.states.insert(
+                pid,
+                RepairState {
+                    actual,
+                    counter: actual,
+                    packet_hash,
+                    duplicated: false,
+                },
+            );
+            return actual;
+        };
+        if !has_payload {
+            return state.counter;
+        }
+
+        let packet_hash = packet_hash();
+        if actual == state.actual && Some(packet_hash) == state.packet_hash && !state.duplicated {
+            state.duplicated = true;
+            return state.counter;
+        }
+        state.counter.increment();
+        state.actual = actual;
+        state.packet_hash = Some(packet_hash);
+        state.duplicated = false;
+        state.counter
+    }
+}
+
+#[derive(Debug)]
+struct RepairState {
+    // Counter of the last input packet with payload
+    actual: ContinuityCounter,
+
+    // Counter of the last output packet
+    counter: ContinuityCounter,
+
+    // Hash of the last input packet with payload
+    packet_hash: Option<u64>,
+
+    duplicated: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ts::payload::Bytes;
+    use crate::ts::{
+        AdaptationField, TransportScramblingControl, TsHeader, TsPacketReader, TsPacketWriter,
+        TsPayload,
+    };
+
+    fn packet(cc: u8, data: u8, discontinuity_indicator: bool) -> TsPacket {
+        TsPacket {
+            header: TsHeader {
+                transport_error_indicator: false,
+                payload_unit_start_indicator: false,
+                transport_priority: false,
+                pid: Pid::new(256).unwrap(),
+                transport_scrambling_control: TransportScramblingControl::NotScrambled,
+                continuity_counter: ContinuityCounter::from_u8(cc).unwrap(),
+            },
+            adaptation_field: if discontinuity_indicator {
+                Some(AdaptationField {
+                    discontinuity_indicator,
+                    random_access_indicator: false,
+                    es_priority_indicator: false,
+                    pcr: None,
+                    opcr: None,
+                    splice_countdown: None,
+                    transport_private_data: Vec::new(),
+                    extension: None,
+                })
+            } else {
+                None
+            },
+            payload: Some(TsPayload::Raw(Bytes::new(&[data]).unwrap())),
+            arrival_timestamp: None,
+        }
+    }
+
+    #[test]
+    fn continuity_check_works() {
+        let mut checker = ContinuityChecker::new();
+        assert_eq!(checker.check(&packet(15, 0, false)), Continuity::Continuous);
+        assert_eq!(checker.check(&packet(0, 1, false)), Continuity::Continuous);
+        assert_eq!(checker.check(&packet(0, 1, false)), Continuity::Duplicate);
+        assert!(checker.check(&packet(0, 1, false)).is_discontinuous());
+        assert_eq!(checker.check(&packet(1, 2, false)), Continuity::Continuous);
+
+        // Same counter but different payload
+        assert!(checker.check(&packet(1, 3, false)).is_discontinuous());
+
+        // Gap
+        assert_eq!(
+            checker.check(&packet(5, 4, false)),
+            Continuity::Discontinuous {
+                expected: ContinuityCounter::from_u8(2).unwrap(),
+                actual: ContinuityCounter::from_u8(5).unwrap(),
+            }
+        );
+
+        // Packets without payload are ignored
+        let mut p = packet(9, 5, false);
+        p.payload = None;
+        assert_eq!(checker.check(&p), Continuity::Continuous);
+        assert_eq!(checker.check(&packet(6, 6, false)), Continuity::Continuous);
+
+        // Discontinuity indicator
+        assert_eq!(checker.check(&packet(12, 7, true)), Continuity::Continuous);
+        assert_eq!(checker.check(&packet(13, 8, false)), Continuity::Continuous);
+    }
+
+    #[test]
+    fn continuity_repair_works() {
+        let mut packets = vec![
+            packet(3, 0, false),
+            // Gap
+            packet(7, 1, false),
+            // Duplicate
+            packet(7, 1, false),
+            packet(8, 2, false),
+            // Discontinuity indicator
+            packet(0, 3, true),
+        ];
+        // Without payload
+        let mut p = packet(2, 4, false);
+        p.payload = None;
+        p.adaptation_field = Some(AdaptationField::default());
+        packets.push(p);
+        // Transport error
+        let mut p = packet(1, 5, false);
+        p.header.transport_error_indicator = true;
+        packets.push(p);
+
+        let mut writer = TsPacketWriter::new(Vec::new());
+        for p in &packets {
+            track_try_unwrap!(writer.write_ts_packet(p));
+        }
+        let mut stream = writer.into_stream();
+
+        let mut repairer = ContinuityRepairer::new();
+        repairer.set_clear_transport_error_indicator(true);
+        let rewritten = packets
+            .iter_mut()
+            .map(|p| repairer.repair_packet(p))
+            .collect::<Vec<_>>();
+        assert_eq!(rewritten, [false, true, true, true, true, true, true]);
+        let counters = packets
+            .iter()
+            .map(|p| p.header.continuity_counter.as_u8())
+            .collect::<Vec<_>>();
+        assert_eq!(counters, [3, 4, 4, 5, 6, 6, 7]);
+        assert!(!packets[6].header.transport_error_indicator);
+        assert_eq!(
+            repairer.rewritten_packets().get(&Pid::new(256).unwrap()),
+            Some(&6)
+        );
+
+        let mut checker = ContinuityChecker::new();
+        assert!(packets.iter().all(|p| !checker.check(p).is_discontinuous()));
+
+        // The serialized packets are repaired in the same way
+        let mut repairer = ContinuityRepairer::new();
+        repairer.set_clear_transport_error_indicator(true);
+        for bytes in stream.chunks_mut(TsPacket::SIZE) {
+            track_try_unwrap!(repairer.repair_bytes(bytes));
+        }
+        let mut reader = TsPacketReader::new(&stream[..]);
+        for expected in &packets {
+            let actual = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+            assert_eq!(actual.header, expected.header);
+        }
+    }
+}
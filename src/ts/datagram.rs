@@ -0,0 +1,158 @@
+use crate::ts::{TsPacket, TsPacketReader};
+use crate::{ErrorKind, Result};
+
+const RTP_VERSION: u8 = 2;
+const RTP_HEADER_SIZE: usize = 12;
+
+/// TS packet reader for datagram-oriented transports (e.g., UDP and RTP).
+///
+/// Each datagram must contain whole packets (typically seven 188-byte packets).
+/// If a datagram does not start with the sync byte, it is regarded as an RTP packet (RFC 3550),
+/// and the RTP header (including CSRCs, header extension, and padding) is skipped.
+///
+/// The state of the PIDs (e.g., the ones learned from PAT and PMT) is maintained across datagrams
+/// in the same way as `TsPacketReader`.
+#[derive(Debug)]
+pub struct TsDatagramReader {
+    inner: TsPacketReader<()>,
+    last_rtp_sequence_number: Option<u16>,
+    lost_rtp_packet_count: u64,
+}
+impl TsDatagramReader {
+    /// Makes a new `TsDatagramReader` instance.
+    pub fn new() -> Self {
+        TsDatagramReader {
+            inner: TsPacketReader::new(()),
+            last_rtp_sequence_number: None,
+            lost_rtp_packet_count: 0,
+        }
+    }
+
+    /// Returns a reference to the underlaying packet decoder.
+    pub fn ts_packet_reader(&self) -> &TsPacketReader<()> {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlaying packet decoder.
+    ///
+    /// This can be used to change the settings of the decoding (e.g., the packet format).
+    pub fn ts_packet_reader_mut(&mut self) -> &mut TsPacketReader<()> {
+        &mut self.inner
+    }
+
+    /// Returns the number of RTP packets lost so far,
+    /// estimated from the gaps in the RTP sequence numbers.
+    pub fn lost_rtp_packet_count(&self) -> u64 {
+        self.lost_rtp_packet_count
+    }
+
+    /// Pushes a datagram, and returns an iterator over the TS packets contained in it.
+    ///
+    /// The packets that are not consumed by the iterator are discarded when the next datagram is pushed.
+    ///
+    /// # Errors
+    ///
+    /// If the datagram has a malformed RTP header or its payload size is not a multiple of the packet size,
+    /// it will return an `ErrorKind::InvalidInput` error.
+    pub fn push_datagram(&mut self, datagram: &[u8]) -> Result<DatagramPackets<'_>> {
+        self.inner.set_buffered_bytes(&[]);
+        let payload = if datagram.first() == Some(&TsPacket::SYNC_BYTE) {
+            datagram
+        } else {
+            track!(self.strip_rtp_header(datagram))?
+        };
+
+        let packet_size = self.inner.packet_format().packet_size();
+        track_assert_eq!(
+            payload.len() % packet_size,
+            0,
+            ErrorKind::InvalidInput,
+            "Datagram payload is not a multiple of the packet size"
+        );
+        self.inner.set_buffered_bytes(payload);
+        Ok(DatagramPackets {
+            reader: &mut self.inner,
+            done: false,
+        })
+    }
+
+    fn strip_rtp_header<'a>(&mut self, datagram: &'a [u8]) -> Result<&'a [u8]> {
+        track_assert!(
+            datagram.len() >= RTP_HEADER_SIZE,
+            ErrorKind::InvalidInput,
+            "Too short RTP packet: {} bytes",
+            datagram.len()
+        );
+        let version = datagram[0] >> 6;
+        track_assert_eq!(
+            version,
+            RTP_VERSION,
+            ErrorKind::InvalidInput,
+            "Neither a TS packet nor an RTP packet"
+        );
+        let padding = (datagram[0] & 0b0010_0000) != 0;
+        let extension = (datagram[0] & 0b0001_0000) != 0;
+        let csrc_count = usize::from(datagram[0] & 0b0000_1111);
+        let sequence_number = u16::from_be_bytes([datagram[2], datagram[3]]);
+
+        let mut offset = RTP_HEADER_SIZE + csrc_count * 4;
+        if extension {
+            track_assert!(
+                datagram.len() >= offset + 4,
+                ErrorKind::InvalidInput,
+                "Too short RTP packet: {} bytes",
+                datagram.len()
+            );
+            let extension_len = u16::from_be_bytes([datagram[offset + 2], datagram[offset + 3]]);
+            offset += 4 + usize::from(extension_len) * 4;
+        }
+        let mut end = datagram.len();
+        if padding {
+            end = end.saturating_sub(usize::from(datagram[end - 1]));
+        }
+        track_assert!(
+            offset <= end,
+            ErrorKind::InvalidInput,
+            "Too short RTP packet: {} bytes",
+            datagram.len()
+        );
+
+        if let Some(last) = self.last_rtp_sequence_number {
+            let gap = sequence_number.wrapping_sub(last).wrapping_sub(1);
+            if gap < u16::MAX / 2 {
+                self.lost_rtp_packet_count += u64::from(gap);
+            }
+        }
+        self.last_rtp_sequence_number = Some(sequence_number);
+        Ok(&datagram[offset..end])
+    }
+}
+impl Default for TsDatagramReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Iterator over the TS packets contained in a datagram.
+///
+/// This is created by `TsDatagramReader::push_datagram`.
+/// The iterator ends after the first error is returned.
+#[derive(Debug)]
+pub struct DatagramPackets<'a> {
+    reader: &'a mut TsPacketReader<()>,
+    done: bool,
+}
+impl Iterator for DatagramPackets<'_> {
+    type Item = Result<TsPacket>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = track!(self.reader.decode_buffered_packet()).transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+impl core::iter::FusedIterator for DatagramPackets<'_> {}
@@ -0,0 +1,349 @@
+use crate::es::StreamType;
+use crate::pes::{PesPacket, PesPacketReader, ReadPesPacket, SampleDecrypter};
+use crate::time::ClockReference;
+use crate::ts::describe::fill_program;
+use crate::ts::{Pid, PidTable, ProgramInfo, ReadTsPacket, TsPacket, TsPayload};
+use crate::Result;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Event yielded by `Demuxer`.
+#[derive(Debug, Clone)]
+pub enum DemuxEvent {
+    /// The PMT of a program has been received for the first time, or its content has changed.
+    ProgramFound(ProgramInfo),
+
+    /// A PCR of a program has been received.
+    ///
+    /// If the PCR PID is shared by several programs, this event is yielded for each of them.
+    PcrUpdate {
+        /// Program number.
+        program: u16,
+
+        #[allow(missing_docs)]
+        pcr: ClockReference,
+    },
+
+    /// A PES packet of an elementary stream of a program has been assembled.
+    Packet {
+        /// Program number.
+        program: u16,
+
+        /// PID of the elementary stream.
+        pid: Pid,
+
+        /// Stream type of the elementary stream declared by the PMT.
+        stream_type: StreamType,
+
+        #[allow(missing_docs)]
+        pes: PesPacket<Vec<u8>>,
+    },
+}
+
+/// Program-aware demultiplexer.
+///
+/// It builds the program model (program numbers, PCR PIDs and elementary streams) from the PAT and PMTs,
+/// tracks the PCR of each program, and assembles the PES packets of the elementary streams.
+/// The PES packets of the PIDs that do not belong to any known program
+/// (e.g., the ones received before the PMT) are discarded.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::ts::{DemuxEvent, Demuxer, TsPacketReader};
+///
+/// let mut demuxer = Demuxer::new(TsPacketReader::new(mpeg2ts::io::empty()));
+/// while let Some(event) = demuxer.next_event().unwrap() {
+///     match event {
+///         DemuxEvent::ProgramFound(program) => println!("{:?}", program),
+///         DemuxEvent::PcrUpdate { program, pcr } => println!("{}: {}", program, pcr),
+///         DemuxEvent::Packet { program, pid, pes, .. } => {
+///             println!("{}: {} {}", program, pid, pes.data.len());
+///             demuxer.recycle(pes);
+///         }
+///     }
+/// }
+/// ```
+pub struct Demuxer<R> {
+    pes_packet_reader: PesPacketReader<ProgramTracker<R>>,
+}
+impl<R: ReadTsPacket> Demuxer<R> {
+    /// Makes a new `Demuxer` instance.
+    pub fn new(ts_packet_reader: R) -> Self {
+        let tracker = ProgramTracker {
+            inner: ts_packet_reader,
+            programs: BTreeMap::new(),
+            es_programs: PidTable::new(),
+            events: VecDeque::new(),
+        };
+        Demuxer {
+            pes_packet_reader: PesPacketReader::new(tracker),
+        }
+    }
+
+    /// Returns the next event.
+    ///
+    /// If the end of the stream is reached, it will return `Ok(None)`.
+    pub fn next_event(&mut self) -> Result<Option<DemuxEvent>> {
+        loop {
+            if let Some(event) = self.tracker_mut().events.pop_front() {
+                return Ok(Some(event));
+            }
+            let Some(pes) = track!(self.pes_packet_reader.read_pes_packet())? else {
+                return Ok(self.tracker_mut().events.pop_front());
+            };
+
+            let tracker = self.pes_packet_reader.ts_packet_reader();
+            let stream = tracker.es_programs.get(pes.pid).and_then(|&program| {
+                let info = &tracker.programs.get(&program)?.info;
+                let stream = info.streams.iter().find(|s| s.pid == pes.pid)?;
+                Some((program, stream.stream_type))
+            });
+            if let Some((program, stream_type)) = stream {
+                // Queued after the events of the TS packets read while assembling the PES packet
+                self.tracker_mut().events.push_back(DemuxEvent::Packet {
+                    program,
+                    pid: pes.pid,
+                    stream_type,
+                    pes,
+                });
+            } else {
+                self.pes_packet_reader.recycle(pes);
+            }
+        }
+    }
+}
+impl<R> Demuxer<R> {
+    /// Returns an iterator over the programs whose PMTs have been received, in ascending order of program number.
+    pub fn programs(&self) -> impl Iterator<Item = &ProgramInfo> {
+        self.tracker().programs.values().map(|p| &p.info)
+    }
+
+    /// Returns the most recent PCR of `program`.
+    pub fn pcr(&self, program: u16) -> Option<ClockReference> {
+        self.tracker().programs.get(&program).and_then(|p| p.pcr)
+    }
+
+    /// Sets the decrypter for the PES packets of the AES-128-CBC stream types (see `PesPacketReader::set_decrypter`).
+    pub fn set_decrypter<D>(&mut self, decrypter: D)
+    where
+        D: SampleDecrypter + Send + 'static,
+    {
+        self.pes_packet_reader.set_decrypter(decrypter);
+    }
+
+    /// Returns the data buffer of a PES packet that is no longer needed (see `PesPacketReader::recycle`).
+    pub fn recycle(&mut self, packet: PesPacket<Vec<u8>>) {
+        self.pes_packet_reader.recycle(packet);
+    }
+
+    /// Returns a reference to the underlaying TS packet reader.
+    pub fn ts_packet_reader(&self) -> &R {
+        &self.tracker().inner
+    }
+
+    /// Returns a mutable reference to the underlaying TS packet reader.
+    pub fn ts_packet_reader_mut(&mut self) -> &mut R {
+        &mut self.tracker_mut().inner
+    }
+
+    /// Converts `Demuxer` into the underlaying TS packet reader.
+    pub fn into_ts_packet_reader(self) -> R {
+        self.pes_packet_reader.into_ts_packet_reader().inner
+    }
+
+    fn tracker(&self) -> &ProgramTracker<R> {
+        self.pes_packet_reader.ts_packet_reader()
+    }
+
+    fn tracker_mut(&mut self) -> &mut ProgramTracker<R> {
+        self.pes_packet_reader.ts_packet_reader_mut()
+    }
+}
+impl<R: fmt::Debug> fmt::Debug for Demuxer<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Demuxer")
+            .field("pes_packet_reader", &self.pes_packet_reader)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+struct ProgramState {
+    info: ProgramInfo,
+    pcr: Option<ClockReference>,
+}
+
+/// TS packet reader that builds the program model from the packets passing through it.
+#[derive(Debug)]
+struct ProgramTracker<R> {
+    inner: R,
+    programs: BTreeMap<u16, ProgramState>,
+    es_programs: PidTable<u16>,
+    events: VecDeque<DemuxEvent>,
+}
+impl<R> ProgramTracker<R> {
+    fn handle_ts_packet(&mut self, packet: &TsPacket) {
+        let pid = packet.header.pid;
+        match packet.payload {
+            Some(TsPayload::Pat(ref pat)) if pat.is_current => {
+                let removed = self
+                    .programs
+                    .keys()
+                    .copied()
+                    .filter(|&n| !pat.table.iter().any(|pa| pa.program_num == n))
+                    .collect::<Vec<_>>();
+                for program_num in removed {
+                    self.remove_program(program_num);
+                }
+            }
+            Some(TsPayload::Pmt(ref pmt)) if pmt.is_current => {
+                let mut info = ProgramInfo {
+                    program_num: pmt.program_num,
+                    pmt_pid: pid,
+                    pcr_pid: None,
+                    descriptors: Vec::new(),
+                    streams: Vec::new(),
+                };
+                fill_program(&mut info, pmt.clone());
+                if self.programs.get(&pmt.program_num).map(|p| &p.info) != Some(&info) {
+                    let pcr = self.remove_program(pmt.program_num).and_then(|p| p.pcr);
+                    for stream in &info.streams {
+                        self.es_programs.insert(stream.pid, info.program_num);
+                    }
+                    self.events
+                        .push_back(DemuxEvent::ProgramFound(info.clone()));
+                    self.programs
+                        .insert(info.program_num, ProgramState { info, pcr });
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(pcr) = packet.adaptation_field.as_ref().and_then(|a| a.pcr) {
+            for (&program, state) in &mut self.programs {
+                if state.info.pcr_pid == Some(pid) {
+                    state.pcr = Some(pcr);
+                    self.events
+                        .push_back(DemuxEvent::PcrUpdate { program, pcr });
+                }
+            }
+        }
+    }
+
+    fn remove_program(&mut self, program_num: u16) -> Option<ProgramState> {
+        let state = self.programs.remove(&program_num)?;
+        for stream in &state.info.streams {
+            if self.es_programs.get(stream.pid) == Some(&program_num) {
+                self.es_programs.remove(stream.pid);
+            }
+        }
+        Some(state)
+    }
+}
+impl<R: ReadTsPacket> ReadTsPacket for ProgramTracker<R> {
+    fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+        let packet = track!(self.inner.read_ts_packet())?;
+        if let Some(ref packet) = packet {
+            self.handle_ts_packet(packet);
+        }
+        Ok(packet)
+    }
+
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        self.inner.last_packet_byte_offset()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ts::test_util::{self, pid};
+    use crate::ts::{AdaptationField, TsPacketReader};
+
+    fn pmt(program_num: u16, pcr_pid: u16, es_pid: u16, stream_type: StreamType) -> TsPayload {
+        TsPayload::Pmt(test_util::pmt(
+            program_num,
+            Some(pcr_pid),
+            &[(es_pid, stream_type)],
+        ))
+    }
+
+    fn pes(data: u8) -> TsPayload {
+        TsPayload::Pes(test_util::pes(0xE0, None, None, &[data; 10]))
+    }
+
+    fn pcr(n: u64) -> AdaptationField {
+        AdaptationField::with_pcr(ClockReference::new(n).unwrap())
+    }
+
+    #[test]
+    fn demux_mpts() {
+        let pat = TsPayload::Pat(test_util::pat(&[(1, 0x1000), (2, 0x1001)]));
+        let packets: Vec<(u16, Option<AdaptationField>, Option<TsPayload>)> = vec![
+            (0, None, Some(pat)),
+            // Not listed in any PMT
+            (300, None, Some(pes(0))),
+            (0x1000, None, Some(pmt(1, 256, 256, StreamType::H264))),
+            // Program 2 has a standalone PCR PID
+            (0x1001, None, Some(pmt(2, 0x1FF0, 257, StreamType::AdtsAac))),
+            (256, Some(pcr(1000)), Some(pes(1))),
+            (257, None, Some(pes(2))),
+            (0x1FF0, Some(pcr(2000)), None),
+            (256, Some(pcr(3000)), Some(pes(3))),
+            (300, None, Some(pes(4))),
+        ];
+
+        let stream = test_util::write_packets(packets);
+        let mut demuxer = Demuxer::new(TsPacketReader::new(&stream[..]));
+        let mut events = Vec::new();
+        while let Some(event) = track_try_unwrap!(demuxer.next_event()) {
+            events.push(match event {
+                DemuxEvent::ProgramFound(info) => {
+                    format!("program:{}:{:?}", info.program_num, info.pcr_pid.unwrap())
+                }
+                DemuxEvent::PcrUpdate { program, pcr } => {
+                    format!("pcr:{}:{}", program, pcr.as_u64())
+                }
+                DemuxEvent::Packet {
+                    program,
+                    pid,
+                    stream_type,
+                    pes,
+                } => format!(
+                    "packet:{}:{}:{:?}:{}",
+                    program,
+                    pid.as_u16(),
+                    stream_type,
+                    pes.data[0]
+                ),
+            });
+        }
+        assert_eq!(
+            events,
+            [
+                format!("program:1:{:?}", pid(256)),
+                format!("program:2:{:?}", pid(0x1FF0)),
+                "pcr:1:1000".to_owned(),
+                "pcr:2:2000".to_owned(),
+                "pcr:1:3000".to_owned(),
+                "packet:1:256:H264:1".to_owned(),
+                // The PES packets pending at the end of the stream are flushed in ascending order of PID
+                "packet:1:256:H264:3".to_owned(),
+                "packet:2:257:AdtsAac:2".to_owned(),
+            ]
+        );
+
+        assert_eq!(
+            demuxer
+                .programs()
+                .map(|p| p.program_num)
+                .collect::<Vec<_>>(),
+            [1, 2]
+        );
+        assert_eq!(demuxer.pcr(1), Some(ClockReference::new(3000).unwrap()));
+        assert_eq!(demuxer.pcr(2), Some(ClockReference::new(2000).unwrap()));
+        assert_eq!(demuxer.pcr(3), None);
+    }
+}
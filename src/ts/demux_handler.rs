@@ -0,0 +1,555 @@
+use crate::io::Read;
+use crate::pes::PesPacket;
+use crate::ts::describe::fill_program;
+use crate::ts::payload::Pmt;
+use crate::ts::{
+    DemuxEvent, Demuxer, Descriptor, EsStreamInfo, Pid, PidKind, PidTable, ProgramInfo,
+    ReadTsPacket, SectionAssembler, TsPacket, TsPacketReader, TsPayload,
+};
+use crate::{Error, ErrorKind, Result};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::ops::ControlFlow;
+
+/// Stream type of SCTE-35 splice information.
+const SCTE35_STREAM_TYPE: u8 = 0x86;
+
+/// The `DemuxHandler` trait receives the events of `Demux`.
+///
+/// Every method has a default implementation that ignores the event.
+/// Returning `ControlFlow::Break` from any method stops `Demux::run`.
+#[allow(unused_variables)]
+pub trait DemuxHandler {
+    /// Called when the PMT of a program has been received for the first time, or its content has changed.
+    fn on_program_change(&mut self, program: &ProgramInfo) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called when a PES packet of a video stream (see `StreamType::is_video`) has been assembled.
+    fn on_video(&mut self, pes: PesPacket<Vec<u8>>, meta: &StreamMeta) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called when a PES packet of an audio stream (see `StreamType::is_audio`) has been assembled.
+    fn on_audio(&mut self, pes: PesPacket<Vec<u8>>, meta: &StreamMeta) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called when a PES packet of a metadata stream (see `StreamType::is_metadata`) has been assembled.
+    fn on_metadata(&mut self, pes: PesPacket<Vec<u8>>, meta: &StreamMeta) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called when a PES packet of any other stream (e.g., subtitles) has been assembled.
+    fn on_other(&mut self, pes: PesPacket<Vec<u8>>, meta: &StreamMeta) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called when an SCTE-35 splice information section has been received.
+    ///
+    /// `section` contains the whole section (from `table_id` to `CRC_32`), which is not validated.
+    fn on_scte35(&mut self, section: &[u8], meta: &StreamMeta) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called when a part of the stream could not be decoded.
+    ///
+    /// The undecodable data is skipped, and the demultiplexing continues.
+    fn on_error(&mut self, error: &Error, position: &ErrorPosition) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+}
+impl<T: DemuxHandler + ?Sized> DemuxHandler for &mut T {
+    fn on_program_change(&mut self, program: &ProgramInfo) -> ControlFlow<()> {
+        (**self).on_program_change(program)
+    }
+
+    fn on_video(&mut self, pes: PesPacket<Vec<u8>>, meta: &StreamMeta) -> ControlFlow<()> {
+        (**self).on_video(pes, meta)
+    }
+
+    fn on_audio(&mut self, pes: PesPacket<Vec<u8>>, meta: &StreamMeta) -> ControlFlow<()> {
+        (**self).on_audio(pes, meta)
+    }
+
+    fn on_metadata(&mut self, pes: PesPacket<Vec<u8>>, meta: &StreamMeta) -> ControlFlow<()> {
+        (**self).on_metadata(pes, meta)
+    }
+
+    fn on_other(&mut self, pes: PesPacket<Vec<u8>>, meta: &StreamMeta) -> ControlFlow<()> {
+        (**self).on_other(pes, meta)
+    }
+
+    fn on_scte35(&mut self, section: &[u8], meta: &StreamMeta) -> ControlFlow<()> {
+        (**self).on_scte35(section, meta)
+    }
+
+    fn on_error(&mut self, error: &Error, position: &ErrorPosition) -> ControlFlow<()> {
+        (**self).on_error(error, position)
+    }
+}
+
+/// Elementary stream that an event of `Demux` belongs to.
+#[derive(Debug, Clone)]
+pub struct StreamMeta {
+    /// Program number.
+    pub program: u16,
+
+    /// Elementary stream declared by the PMT of the program.
+    pub stream: EsStreamInfo,
+}
+
+/// Position of an error reported by `Demux`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ErrorPosition {
+    /// Byte offset of the TS packet that could not be decoded
+    /// (or the last TS packet read when a PES packet could not be assembled).
+    pub byte_offset: Option<u64>,
+
+    /// PID of the TS packet that could not be decoded.
+    ///
+    /// This is `None` if the packet does not have a valid header or the error is not specific to a TS packet.
+    pub pid: Option<Pid>,
+}
+
+/// Callback-based demultiplexer.
+///
+/// This runs the whole pipeline (PAT, PMT, PCR and PES packet assembly) of `Demuxer` over a byte stream,
+/// and dispatches the assembled PES packets to the methods of `DemuxHandler`
+/// according to the stream types declared by the PMTs.
+///
+/// SCTE-35 sections are taken from the streams of the stream type `0x86` with the `CUEI` registration
+/// descriptor (either in the program or the elementary stream descriptors).
+///
+/// Undecodable TS packets and PES packets are skipped and reported via `DemuxHandler::on_error`.
+/// Only I/O errors stop `run` with `Err`.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::pes::PesPacket;
+/// use mpeg2ts::ts::{Demux, DemuxHandler, StreamMeta};
+/// use std::ops::ControlFlow;
+///
+/// struct Handler;
+/// impl DemuxHandler for Handler {
+///     fn on_video(&mut self, pes: PesPacket<Vec<u8>>, meta: &StreamMeta) -> ControlFlow<()> {
+///         println!("{}: {:?} {} bytes", meta.program, pes.header.pts, pes.data.len());
+///         ControlFlow::Continue(())
+///     }
+/// }
+///
+/// let mut demux = Demux::new(mpeg2ts::io::empty());
+/// assert!(demux.run(&mut Handler).unwrap().is_continue());
+/// ```
+#[derive(Debug)]
+pub struct Demux<R> {
+    demuxer: Demuxer<DemuxSource<R>>,
+    streams: PidTable<StreamMeta>,
+    pending: Option<DemuxEvent>,
+}
+impl<R: Read> Demux<R> {
+    /// Makes a new `Demux` instance that reads TS packets from `stream`.
+    pub fn new(stream: R) -> Self {
+        Self::from_ts_packet_reader(TsPacketReader::new(stream))
+    }
+
+    /// Makes a new `Demux` instance from a configured TS packet reader.
+    pub fn from_ts_packet_reader(reader: TsPacketReader<R>) -> Self {
+        let source = DemuxSource {
+            inner: reader,
+            scte35_streams: PidTable::new(),
+            scte35_sections: SectionAssembler::new(),
+            events: VecDeque::new(),
+        };
+        Demux {
+            demuxer: Demuxer::new(source),
+            streams: PidTable::new(),
+            pending: None,
+        }
+    }
+
+    /// Reads the stream, and dispatches the events to `handler`.
+    ///
+    /// It returns `ControlFlow::Continue` when the end of the stream is reached,
+    /// or `ControlFlow::Break` when a method of `handler` has returned it.
+    /// In the latter case, calling `run` again resumes from the next event.
+    pub fn run<H: DemuxHandler>(&mut self, mut handler: H) -> Result<ControlFlow<()>> {
+        loop {
+            // The events of the TS packets precede the event assembled from them
+            if let Some(event) = self.source_mut().events.pop_front() {
+                if Self::dispatch_source_event(event, &mut handler).is_break() {
+                    return Ok(ControlFlow::Break(()));
+                }
+                continue;
+            }
+            if let Some(event) = self.pending.take() {
+                if self.dispatch_event(event, &mut handler).is_break() {
+                    return Ok(ControlFlow::Break(()));
+                }
+                continue;
+            }
+
+            match self.demuxer.next_event() {
+                Ok(Some(event)) => {
+                    self.pending = Some(event);
+                }
+                Ok(None) => {
+                    if self.source_mut().events.is_empty() {
+                        return Ok(ControlFlow::Continue(()));
+                    }
+                }
+                Err(e) if *e.kind() != ErrorKind::Other => {
+                    // An undecodable PES packet (or a truncated one at the end of the stream)
+                    let position = ErrorPosition {
+                        byte_offset: self.demuxer.ts_packet_reader().last_packet_byte_offset(),
+                        pid: None,
+                    };
+                    if handler.on_error(&e, &position).is_break() {
+                        return Ok(ControlFlow::Break(()));
+                    }
+                }
+                Err(e) => return Err(track!(e)),
+            }
+        }
+    }
+}
+impl<R> Demux<R> {
+    /// Returns a reference to the underlaying TS packet reader.
+    pub fn ts_packet_reader(&self) -> &TsPacketReader<R> {
+        &self.demuxer.ts_packet_reader().inner
+    }
+
+    /// Converts `Demux` into the underlaying TS packet reader.
+    pub fn into_ts_packet_reader(self) -> TsPacketReader<R> {
+        self.demuxer.into_ts_packet_reader().inner
+    }
+
+    fn source_mut(&mut self) -> &mut DemuxSource<R> {
+        self.demuxer.ts_packet_reader_mut()
+    }
+
+    fn dispatch_event<H: DemuxHandler>(
+        &mut self,
+        event: DemuxEvent,
+        handler: &mut H,
+    ) -> ControlFlow<()> {
+        match event {
+            DemuxEvent::ProgramFound(program) => {
+                let pids = self
+                    .streams
+                    .iter()
+                    .filter(|(_, meta)| meta.program == program.program_num)
+                    .map(|(pid, _)| pid)
+                    .collect::<Vec<_>>();
+                for pid in pids {
+                    self.streams.remove(pid);
+                }
+                for stream in &program.streams {
+                    let meta = StreamMeta {
+                        program: program.program_num,
+                        stream: stream.clone(),
+                    };
+                    self.streams.insert(stream.pid, meta);
+                }
+                handler.on_program_change(&program)
+            }
+            DemuxEvent::PcrUpdate { .. } => ControlFlow::Continue(()),
+            DemuxEvent::Packet {
+                pid,
+                stream_type,
+                pes,
+                ..
+            } => {
+                let Some(meta) = self.streams.get(pid) else {
+                    return ControlFlow::Continue(());
+                };
+                if stream_type.is_video() {
+                    handler.on_video(pes, meta)
+                } else if stream_type.is_audio() {
+                    handler.on_audio(pes, meta)
+                } else if stream_type.is_metadata() {
+                    handler.on_metadata(pes, meta)
+                } else {
+                    handler.on_other(pes, meta)
+                }
+            }
+        }
+    }
+
+    fn dispatch_source_event<H: DemuxHandler>(
+        event: SourceEvent,
+        handler: &mut H,
+    ) -> ControlFlow<()> {
+        match event {
+            SourceEvent::Scte35 { section, meta } => handler.on_scte35(&section, &meta),
+            SourceEvent::Error { error, position } => handler.on_error(&error, &position),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SourceEvent {
+    Scte35 {
+        section: Vec<u8>,
+        meta: StreamMeta,
+    },
+    Error {
+        error: Error,
+        position: ErrorPosition,
+    },
+}
+
+/// TS packet reader that skips undecodable packets and extracts SCTE-35 sections.
+#[derive(Debug)]
+struct DemuxSource<R> {
+    inner: TsPacketReader<R>,
+    scte35_streams: PidTable<StreamMeta>,
+    scte35_sections: SectionAssembler,
+    events: VecDeque<SourceEvent>,
+}
+impl<R: Read> DemuxSource<R> {
+    fn handle_pmt(&mut self, pmt_pid: Pid, pmt: &Pmt) {
+        let cuei = |descriptors: &[Descriptor]| {
+            descriptors
+                .iter()
+                .any(|d| d.format_identifier() == Some(*b"CUEI"))
+        };
+        let program_cuei = cuei(&pmt.program_info);
+        let mut program = ProgramInfo {
+            program_num: pmt.program_num,
+            pmt_pid,
+            pcr_pid: None,
+            descriptors: Vec::new(),
+            streams: Vec::new(),
+        };
+        fill_program(&mut program, pmt.clone());
+        for stream in program.streams {
+            if self.scte35_streams.contains(stream.pid)
+                || stream.stream_type.as_u8() != SCTE35_STREAM_TYPE
+                || !(program_cuei || cuei(&stream.descriptors))
+            {
+                continue;
+            }
+            self.inner.register_pid(stream.pid, PidKind::Section);
+            let meta = StreamMeta {
+                program: pmt.program_num,
+                stream,
+            };
+            self.scte35_streams.insert(meta.stream.pid, meta);
+        }
+    }
+
+    fn handle_ts_packet(&mut self, packet: &TsPacket) {
+        let pid = packet.header.pid;
+        match &packet.payload {
+            Some(TsPayload::Pmt(pmt)) if pmt.is_current => self.handle_pmt(pid, pmt),
+            _ => {
+                if let Some(meta) = self.scte35_streams.get(pid) {
+                    for section in self.scte35_sections.push(packet) {
+                        self.events.push_back(SourceEvent::Scte35 {
+                            section,
+                            meta: meta.clone(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+impl<R: Read> ReadTsPacket for DemuxSource<R> {
+    fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+        loop {
+            match self.inner.read_ts_packet() {
+                Err(error) if *error.kind() != ErrorKind::Other => {
+                    let (byte_offset, pid) = self.inner.last_packet_location();
+                    if matches!(
+                        error.kind(),
+                        ErrorKind::InvalidInput | ErrorKind::CrcMismatch | ErrorKind::Unsupported
+                    ) {
+                        track!(self.inner.recover())?;
+                    }
+                    let position = ErrorPosition {
+                        byte_offset: Some(byte_offset),
+                        pid,
+                    };
+                    self.events
+                        .push_back(SourceEvent::Error { error, position });
+                }
+                result => {
+                    let packet = track!(result)?;
+                    if let Some(packet) = &packet {
+                        self.handle_ts_packet(packet);
+                    }
+                    return Ok(packet);
+                }
+            }
+        }
+    }
+
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        self.inner.last_packet_byte_offset()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::StreamType;
+    use crate::ts::payload::{Bytes, Pmt, Section};
+    use crate::ts::test_util::{self, pid};
+    use crate::ts::{TsPacketWriter, WriteTsPacket};
+
+    fn psi() -> Vec<(u16, TsPayload)> {
+        let pat = TsPayload::Pat(test_util::pat(&[(1, 0x1000)]));
+        let streams = [
+            (0x100, StreamType::H264),
+            (0x101, StreamType::AdtsAac),
+            (0x102, StreamType::PacketizedMetadata),
+            (0x103, StreamType::from_u8(SCTE35_STREAM_TYPE)),
+        ];
+        let pmt = TsPayload::Pmt(Pmt {
+            program_info: vec![Descriptor {
+                tag: Descriptor::REGISTRATION_TAG,
+                data: b"CUEI".to_vec(),
+            }],
+            ..test_util::pmt(1, Some(0x100), &streams)
+        });
+        vec![(0, pat), (0x1000, pmt)]
+    }
+
+    fn pes(n: u16, i: u64) -> (u16, TsPayload) {
+        let pes = test_util::pes(0xE0, Some(i * 3000), None, &[i as u8; 10]);
+        (n, TsPayload::Pes(pes))
+    }
+
+    fn stream() -> Vec<u8> {
+        let mut packets = psi();
+        for i in 0..3 {
+            packets.push(pes(0x100, i));
+            packets.push(pes(0x101, i));
+            packets.push(pes(0x102, i));
+        }
+
+        // An SCTE-35 section spanning two packets
+        let mut section = vec![0xFC, 0x30, 197];
+        section.resize(200, 0xAB);
+        packets.push((
+            0x103,
+            TsPayload::Section(Section::new(0, &section[..183]).unwrap()),
+        ));
+        packets.push((0x103, TsPayload::Raw(Bytes::new(&section[183..]).unwrap())));
+
+        let packets = test_util::packets(packets.into_iter().map(|(n, p)| (n, None, Some(p))), 0);
+        let mut stream = Vec::new();
+        for (i, packet) in packets.iter().enumerate() {
+            if i == 6 {
+                // A corrupted packet
+                stream.extend_from_slice(&[0; TsPacket::SIZE]);
+            }
+            if i == 10 {
+                // A complete packet whose adaptation field is too short for its PCR
+                let mut malformed = vec![0x47, 0x01, 0x00, 0x30, 0x01, 0x10];
+                malformed.resize(TsPacket::SIZE, 0xFF);
+                stream.extend_from_slice(&malformed);
+            }
+            track_try_unwrap!(TsPacketWriter::new(&mut stream).write_ts_packet(packet));
+        }
+        stream
+    }
+
+    #[derive(Debug, Default)]
+    struct Handler {
+        events: Vec<String>,
+        break_on_video: Option<u8>,
+    }
+    impl Handler {
+        fn push_pes(&mut self, kind: &str, pes: &PesPacket<Vec<u8>>, meta: &StreamMeta) {
+            assert_eq!(meta.program, 1);
+            assert_eq!(meta.stream.pid, pes.pid);
+            self.events
+                .push(format!("{kind}:{:#x}:{}", pes.pid.as_u16(), pes.data[0]));
+        }
+    }
+    impl DemuxHandler for Handler {
+        fn on_program_change(&mut self, program: &ProgramInfo) -> ControlFlow<()> {
+            self.events
+                .push(format!("program:{}", program.streams.len()));
+            ControlFlow::Continue(())
+        }
+
+        fn on_video(&mut self, pes: PesPacket<Vec<u8>>, meta: &StreamMeta) -> ControlFlow<()> {
+            self.push_pes("video", &pes, meta);
+            if self.break_on_video == Some(pes.data[0]) {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+
+        fn on_audio(&mut self, pes: PesPacket<Vec<u8>>, meta: &StreamMeta) -> ControlFlow<()> {
+            self.push_pes("audio", &pes, meta);
+            ControlFlow::Continue(())
+        }
+
+        fn on_metadata(&mut self, pes: PesPacket<Vec<u8>>, meta: &StreamMeta) -> ControlFlow<()> {
+            self.push_pes("metadata", &pes, meta);
+            ControlFlow::Continue(())
+        }
+
+        fn on_scte35(&mut self, section: &[u8], meta: &StreamMeta) -> ControlFlow<()> {
+            assert_eq!(meta.stream.pid, pid(0x103));
+            self.events.push(format!("scte35:{}", section.len()));
+            ControlFlow::Continue(())
+        }
+
+        fn on_error(&mut self, error: &Error, position: &ErrorPosition) -> ControlFlow<()> {
+            assert_eq!(*error.kind(), ErrorKind::InvalidInput);
+            self.events
+                .push(format!("error:{:?}", position.byte_offset));
+            ControlFlow::Continue(())
+        }
+    }
+
+    #[test]
+    fn demux() {
+        let stream = stream();
+        let mut demux = Demux::new(&stream[..]);
+        let mut handler = Handler {
+            break_on_video: Some(1),
+            ..Default::default()
+        };
+        let result = track_try_unwrap!(demux.run(&mut handler));
+        assert!(result.is_break());
+        assert_eq!(
+            handler.events,
+            [
+                "program:4",
+                "video:0x100:0",
+                // Before the second audio packet
+                "error:Some(1128)",
+                "audio:0x101:0",
+                "metadata:0x102:0",
+                "video:0x100:1",
+            ]
+        );
+
+        // Resumes
+        handler.events.clear();
+        let result = track_try_unwrap!(demux.run(&mut handler));
+        assert!(result.is_continue());
+        assert_eq!(
+            handler.events,
+            [
+                "audio:0x101:1",
+                // Before the second metadata packet
+                "error:Some(2068)",
+                "metadata:0x102:1",
+                "scte35:200",
+                "video:0x100:2",
+                "audio:0x101:2",
+                "metadata:0x102:2",
+            ]
+        );
+    }
+}
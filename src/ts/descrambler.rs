@@ -0,0 +1,18 @@
+use crate::ts::{KeyParity, Pid};
+use crate::Result;
+use alloc::boxed::Box;
+
+/// The `Descrambler` trait allows for decrypting the payloads of scrambled TS packets.
+///
+/// Note that this crate does not provide any descrambling algorithms (e.g., DVB-CSA) itself.
+pub trait Descrambler {
+    /// Descrambles the payload of a TS packet in place.
+    ///
+    /// `payload` contains the bytes following the adaptation field (if any).
+    fn descramble(&mut self, pid: Pid, key_parity: KeyParity, payload: &mut [u8]) -> Result<()>;
+}
+impl<T: Descrambler + ?Sized> Descrambler for Box<T> {
+    fn descramble(&mut self, pid: Pid, key_parity: KeyParity, payload: &mut [u8]) -> Result<()> {
+        (**self).descramble(pid, key_parity, payload)
+    }
+}
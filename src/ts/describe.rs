@@ -0,0 +1,158 @@
+use crate::es::StreamType;
+use crate::time::Timestamp;
+use crate::ts::payload::Pmt;
+use crate::ts::{Descriptor, Pid, ReadTsPacket, TsPayload};
+use crate::Result;
+use alloc::{string::String, vec, vec::Vec};
+
+/// Summary of the structure of a stream returned by `describe`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// Transport stream identifier in the PAT.
+    pub transport_stream_id: u16,
+
+    /// Programs in the order of the PAT.
+    pub programs: Vec<ProgramInfo>,
+}
+
+/// Program in `StreamInfo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramInfo {
+    #[allow(missing_docs)]
+    pub program_num: u16,
+
+    #[allow(missing_docs)]
+    pub pmt_pid: Pid,
+
+    /// This is `None` if the PMT has not been received or the program has no PCR.
+    pub pcr_pid: Option<Pid>,
+
+    /// Program descriptors in the PMT.
+    pub descriptors: Vec<Descriptor>,
+
+    /// Elementary streams in the order of the PMT.
+    ///
+    /// This is empty if the PMT has not been received.
+    pub streams: Vec<EsStreamInfo>,
+}
+
+/// Elementary stream in `ProgramInfo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EsStreamInfo {
+    #[allow(missing_docs)]
+    pub pid: Pid,
+
+    #[allow(missing_docs)]
+    pub stream_type: StreamType,
+
+    /// Language code in the ISO 639 language descriptor (if any).
+    pub language: Option<String>,
+
+    /// Format identifier in the registration descriptor (if any).
+    pub format_identifier: Option<[u8; 4]>,
+
+    /// Elementary stream descriptors in the PMT.
+    pub descriptors: Vec<Descriptor>,
+
+    /// PTS of the first PES packet (if any) read by `describe`.
+    ///
+    /// This is always `None` in the programs reported by `Demuxer`.
+    pub first_pts: Option<Timestamp>,
+}
+impl EsStreamInfo {
+    fn new(pid: Pid, stream_type: StreamType, descriptors: Vec<Descriptor>) -> Self {
+        let language = descriptors
+            .iter()
+            .find_map(|d| d.iso_639_language_code())
+            .map(|code| String::from_utf8_lossy(&code).into_owned());
+        let format_identifier = descriptors.iter().find_map(|d| d.format_identifier());
+        EsStreamInfo {
+            pid,
+            stream_type,
+            language,
+            format_identifier,
+            descriptors,
+            first_pts: None,
+        }
+    }
+}
+
+/// Reads packets until the PAT and all the PMTs referenced by it are received,
+/// and returns the summary of the stream.
+///
+/// After that, at most `extra_packets` more packets are read to collect the first PTS of each elementary stream.
+/// If the end of the stream is reached before the PAT is received, it will return `Ok(None)`.
+/// If it is reached before some PMTs are received, the corresponding programs have no streams.
+pub fn describe<R: ReadTsPacket>(reader: &mut R, extra_packets: u64) -> Result<Option<StreamInfo>> {
+    let mut info: Option<StreamInfo> = None;
+    let mut received_pmts = Vec::new();
+    let mut remaining = extra_packets;
+    while let Some(packet) = track!(reader.read_ts_packet())? {
+        let pid = packet.header.pid;
+        match (packet.payload, info.as_mut()) {
+            (Some(TsPayload::Pat(pat)), None) if pat.is_current => {
+                let programs = pat
+                    .table
+                    .iter()
+                    .filter(|pa| pa.program_num != 0)
+                    .map(|pa| ProgramInfo {
+                        program_num: pa.program_num,
+                        pmt_pid: pa.program_map_pid,
+                        pcr_pid: None,
+                        descriptors: Vec::new(),
+                        streams: Vec::new(),
+                    })
+                    .collect::<Vec<_>>();
+                received_pmts = vec![false; programs.len()];
+                info = Some(StreamInfo {
+                    transport_stream_id: pat.transport_stream_id,
+                    programs,
+                });
+            }
+            (Some(TsPayload::Pmt(pmt)), Some(info)) if pmt.is_current => {
+                let i = info
+                    .programs
+                    .iter()
+                    .position(|p| p.pmt_pid == pid && p.program_num == pmt.program_num);
+                if let Some(i) = i.filter(|&i| !received_pmts[i]) {
+                    received_pmts[i] = true;
+                    fill_program(&mut info.programs[i], pmt);
+                }
+            }
+            (Some(TsPayload::Pes(pes)), Some(info)) => {
+                let stream = info
+                    .programs
+                    .iter_mut()
+                    .flat_map(|p| p.streams.iter_mut())
+                    .find(|s| s.pid == pid);
+                if let Some(stream) = stream {
+                    stream.first_pts = stream.first_pts.or(pes.header.pts);
+                }
+            }
+            _ => {}
+        }
+
+        if info.is_some() && received_pmts.iter().all(|&received| received) {
+            let all_pts = info
+                .iter()
+                .flat_map(|info| &info.programs)
+                .flat_map(|p| &p.streams)
+                .all(|s| s.first_pts.is_some());
+            if remaining == 0 || all_pts {
+                break;
+            }
+            remaining -= 1;
+        }
+    }
+    Ok(info)
+}
+
+pub(super) fn fill_program(program: &mut ProgramInfo, pmt: Pmt) {
+    program.pcr_pid = pmt.pcr_pid;
+    program.descriptors = pmt.program_info;
+    program.streams = pmt
+        .es_info
+        .into_iter()
+        .map(|es| EsStreamInfo::new(es.elementary_pid, es.stream_type, es.descriptors))
+        .collect();
+}
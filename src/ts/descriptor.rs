@@ -0,0 +1,551 @@
+use crate::es::teletext::TeletextPage;
+use crate::ts::{Descriptor, Pid};
+use crate::{ErrorKind, Result};
+use alloc::vec::Vec;
+
+/// Typed representation of the common descriptors.
+///
+/// A `Descriptor` can be converted into `KnownDescriptor` by `Descriptor::parse`,
+/// and vice versa by `KnownDescriptor::to_descriptor`.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum KnownDescriptor {
+    Registration(RegistrationDescriptor),
+    Ca(CaDescriptor),
+    Iso639Language(Vec<Iso639Language>),
+    AvcVideo(AvcVideoDescriptor),
+    Service(ServiceDescriptor),
+    StreamIdentifier(u8),
+    Teletext(Vec<TeletextPage>),
+    Subtitling(Vec<Subtitling>),
+    Aac(AacDescriptor),
+    OpusAudio(OpusAudioDescriptor),
+    Unknown(u8, Vec<u8>),
+}
+impl KnownDescriptor {
+    /// Tag of the CA descriptor.
+    pub const CA_TAG: u8 = 0x09;
+
+    /// Tag of the AVC video descriptor.
+    pub const AVC_VIDEO_TAG: u8 = 0x28;
+
+    /// Tag of the service descriptor.
+    pub const SERVICE_TAG: u8 = 0x48;
+
+    /// Tag of the stream identifier descriptor.
+    pub const STREAM_IDENTIFIER_TAG: u8 = 0x52;
+
+    /// Tag of the subtitling descriptor.
+    pub const SUBTITLING_TAG: u8 = 0x59;
+
+    /// Tag of the AAC descriptor.
+    pub const AAC_TAG: u8 = 0x7C;
+
+    /// Tag of the extension descriptor.
+    pub const EXTENSION_TAG: u8 = 0x7F;
+
+    /// `descriptor_tag_extension` of the Opus audio descriptor (an extension descriptor).
+    pub const OPUS_AUDIO_TAG_EXTENSION: u8 = 0x80;
+
+    /// Returns the tag of the descriptor.
+    pub fn tag(&self) -> u8 {
+        match self {
+            KnownDescriptor::Registration(_) => Descriptor::REGISTRATION_TAG,
+            KnownDescriptor::Ca(_) => Self::CA_TAG,
+            KnownDescriptor::Iso639Language(_) => Descriptor::ISO_639_LANGUAGE_TAG,
+            KnownDescriptor::AvcVideo(_) => Self::AVC_VIDEO_TAG,
+            KnownDescriptor::Service(_) => Self::SERVICE_TAG,
+            KnownDescriptor::StreamIdentifier(_) => Self::STREAM_IDENTIFIER_TAG,
+            KnownDescriptor::Teletext(_) => Descriptor::TELETEXT_TAG,
+            KnownDescriptor::Subtitling(_) => Self::SUBTITLING_TAG,
+            KnownDescriptor::Aac(_) => Self::AAC_TAG,
+            KnownDescriptor::OpusAudio(_) => Self::EXTENSION_TAG,
+            KnownDescriptor::Unknown(tag, _) => *tag,
+        }
+    }
+
+    /// Converts the descriptor into a raw `Descriptor`.
+    ///
+    /// It will return an `ErrorKind::InvalidInput` error if the data of the descriptor exceeds 255 bytes
+    /// or a field is out of range.
+    pub fn to_descriptor(&self) -> Result<Descriptor> {
+        let mut data = Vec::new();
+        match self {
+            KnownDescriptor::Registration(d) => {
+                data.extend_from_slice(&d.format_identifier);
+                data.extend_from_slice(&d.additional_identification_info);
+            }
+            KnownDescriptor::Ca(d) => {
+                data.extend_from_slice(&d.ca_system_id.to_be_bytes());
+                data.extend_from_slice(&(0xE000 | d.ca_pid.as_u16()).to_be_bytes());
+                data.extend_from_slice(&d.private_data);
+            }
+            KnownDescriptor::Iso639Language(languages) => {
+                for l in languages {
+                    data.extend_from_slice(&l.language);
+                    data.push(l.audio_type);
+                }
+            }
+            KnownDescriptor::AvcVideo(d) => {
+                data.push(d.profile_idc);
+                data.push(d.constraint_flags);
+                data.push(d.level_idc);
+                data.push(
+                    (u8::from(d.avc_still_present) << 7)
+                        | (u8::from(d.avc_24_hour_picture) << 6)
+                        | (u8::from(d.frame_packing_sei_not_present) << 5)
+                        | 0b1_1111,
+                );
+            }
+            KnownDescriptor::Service(d) => {
+                track_assert!(d.provider_name.len() <= 0xFF, ErrorKind::InvalidInput);
+                track_assert!(d.service_name.len() <= 0xFF, ErrorKind::InvalidInput);
+                data.push(d.service_type);
+                data.push(d.provider_name.len() as u8);
+                data.extend_from_slice(&d.provider_name);
+                data.push(d.service_name.len() as u8);
+                data.extend_from_slice(&d.service_name);
+            }
+            KnownDescriptor::StreamIdentifier(component_tag) => {
+                data.push(*component_tag);
+            }
+            KnownDescriptor::Teletext(pages) => {
+                for page in pages {
+                    track_assert!(page.teletext_type < 0x20, ErrorKind::InvalidInput);
+                    track_assert!(page.magazine_number < 8, ErrorKind::InvalidInput);
+                    page.write_entry(&mut data);
+                }
+            }
+            KnownDescriptor::Subtitling(subtitlings) => {
+                for s in subtitlings {
+                    data.extend_from_slice(&s.language);
+                    data.push(s.subtitling_type);
+                    data.extend_from_slice(&s.composition_page_id.to_be_bytes());
+                    data.extend_from_slice(&s.ancillary_page_id.to_be_bytes());
+                }
+            }
+            KnownDescriptor::Aac(d) => {
+                data.push(d.profile_and_level);
+                let flags = (u8::from(d.aac_type.is_some()) << 7) | (u8::from(d.saoc_de) << 6);
+                data.push(flags);
+                if let Some(aac_type) = d.aac_type {
+                    data.push(aac_type);
+                }
+                data.extend_from_slice(&d.additional_info);
+            }
+            KnownDescriptor::OpusAudio(d) => {
+                data.push(Self::OPUS_AUDIO_TAG_EXTENSION);
+                data.push(d.channel_config_code);
+                data.extend_from_slice(&d.channel_config_extension);
+            }
+            KnownDescriptor::Unknown(_, bytes) => {
+                data.extend_from_slice(bytes);
+            }
+        }
+        track_assert!(
+            data.len() <= 0xFF,
+            ErrorKind::InvalidInput,
+            "Too large descriptor: tag={}, size={}",
+            self.tag(),
+            data.len()
+        );
+        Ok(Descriptor {
+            tag: self.tag(),
+            data,
+        })
+    }
+
+    pub(super) fn parse(d: &Descriptor) -> Result<Self> {
+        let data = &d.data[..];
+        let check_len = |min: usize| -> Result<()> {
+            track_assert!(
+                data.len() >= min,
+                ErrorKind::InvalidInput,
+                "Too short descriptor: tag={}, size={}",
+                d.tag,
+                data.len()
+            );
+            Ok(())
+        };
+        let parsed = match d.tag {
+            Descriptor::REGISTRATION_TAG => {
+                track!(check_len(4))?;
+                KnownDescriptor::Registration(RegistrationDescriptor {
+                    format_identifier: [data[0], data[1], data[2], data[3]],
+                    additional_identification_info: data[4..].to_vec(),
+                })
+            }
+            Self::CA_TAG => {
+                track!(check_len(4))?;
+                let ca_pid = u16::from_be_bytes([data[2], data[3]]) & Pid::MAX;
+                KnownDescriptor::Ca(CaDescriptor {
+                    ca_system_id: u16::from_be_bytes([data[0], data[1]]),
+                    ca_pid: track!(Pid::new(ca_pid))?,
+                    private_data: data[4..].to_vec(),
+                })
+            }
+            Descriptor::ISO_639_LANGUAGE_TAG => {
+                track_assert!(
+                    data.len() % 4 == 0,
+                    ErrorKind::InvalidInput,
+                    "Malformed ISO 639 language descriptor: size={}",
+                    data.len()
+                );
+                let languages = data
+                    .chunks_exact(4)
+                    .map(|c| Iso639Language {
+                        language: [c[0], c[1], c[2]],
+                        audio_type: c[3],
+                    })
+                    .collect();
+                KnownDescriptor::Iso639Language(languages)
+            }
+            Self::AVC_VIDEO_TAG => {
+                track!(check_len(4))?;
+                KnownDescriptor::AvcVideo(AvcVideoDescriptor {
+                    profile_idc: data[0],
+                    constraint_flags: data[1],
+                    level_idc: data[2],
+                    avc_still_present: (data[3] & 0b1000_0000) != 0,
+                    avc_24_hour_picture: (data[3] & 0b0100_0000) != 0,
+                    frame_packing_sei_not_present: (data[3] & 0b0010_0000) != 0,
+                })
+            }
+            Self::SERVICE_TAG => {
+                track!(check_len(2))?;
+                let provider_name_len = usize::from(data[1]);
+                track!(check_len(3 + provider_name_len))?;
+                let provider_name = &data[2..2 + provider_name_len];
+                let rest = &data[2 + provider_name_len..];
+                let service_name_len = usize::from(rest[0]);
+                track!(check_len(3 + provider_name_len + service_name_len))?;
+                KnownDescriptor::Service(ServiceDescriptor {
+                    service_type: data[0],
+                    provider_name: provider_name.to_vec(),
+                    service_name: rest[1..1 + service_name_len].to_vec(),
+                })
+            }
+            Self::STREAM_IDENTIFIER_TAG => {
+                track!(check_len(1))?;
+                KnownDescriptor::StreamIdentifier(data[0])
+            }
+            Descriptor::TELETEXT_TAG => {
+                let pages = track_assert_some!(
+                    TeletextPage::read_entries(data),
+                    ErrorKind::InvalidInput,
+                    "Malformed teletext descriptor: size={}",
+                    data.len()
+                );
+                KnownDescriptor::Teletext(pages)
+            }
+            Self::SUBTITLING_TAG => {
+                track_assert!(
+                    data.len() % 8 == 0,
+                    ErrorKind::InvalidInput,
+                    "Malformed subtitling descriptor: size={}",
+                    data.len()
+                );
+                let subtitlings = data
+                    .chunks_exact(8)
+                    .map(|c| Subtitling {
+                        language: [c[0], c[1], c[2]],
+                        subtitling_type: c[3],
+                        composition_page_id: u16::from_be_bytes([c[4], c[5]]),
+                        ancillary_page_id: u16::from_be_bytes([c[6], c[7]]),
+                    })
+                    .collect();
+                KnownDescriptor::Subtitling(subtitlings)
+            }
+            Self::AAC_TAG => {
+                track!(check_len(1))?;
+                let mut d = AacDescriptor {
+                    profile_and_level: data[0],
+                    aac_type: None,
+                    saoc_de: false,
+                    additional_info: Vec::new(),
+                };
+                if let Some(&flags) = data.get(1) {
+                    d.saoc_de = (flags & 0b0100_0000) != 0;
+                    let mut rest = &data[2..];
+                    if (flags & 0b1000_0000) != 0 {
+                        track!(check_len(3))?;
+                        d.aac_type = Some(rest[0]);
+                        rest = &rest[1..];
+                    }
+                    d.additional_info = rest.to_vec();
+                }
+                KnownDescriptor::Aac(d)
+            }
+            Self::EXTENSION_TAG if data.first() == Some(&Self::OPUS_AUDIO_TAG_EXTENSION) => {
+                track!(check_len(2))?;
+                KnownDescriptor::OpusAudio(OpusAudioDescriptor {
+                    channel_config_code: data[1],
+                    channel_config_extension: data[2..].to_vec(),
+                })
+            }
+            tag => KnownDescriptor::Unknown(tag, data.to_vec()),
+        };
+        Ok(parsed)
+    }
+}
+
+/// Registration descriptor (tag `0x05`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RegistrationDescriptor {
+    /// Format identifier (e.g., `*b"CUEI"` for SCTE-35).
+    pub format_identifier: [u8; 4],
+
+    /// Additional identification info.
+    pub additional_identification_info: Vec<u8>,
+}
+
+/// Conditional access descriptor (tag `0x09`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CaDescriptor {
+    /// CA system identifier.
+    pub ca_system_id: u16,
+
+    /// PID of the ECM (in PMT) or EMM (in CAT) packets.
+    pub ca_pid: Pid,
+
+    /// Private data bytes.
+    pub private_data: Vec<u8>,
+}
+
+/// Entry of an ISO 639 language descriptor (tag `0x0A`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Iso639Language {
+    /// ISO 639-2 language code (e.g., `*b"eng"`).
+    pub language: [u8; 3],
+
+    /// Audio type (e.g., `0x03` for visual impaired commentary).
+    pub audio_type: u8,
+}
+
+/// AVC video descriptor (tag `0x28`).
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AvcVideoDescriptor {
+    pub profile_idc: u8,
+
+    /// `constraint_set0_flag`..`constraint_set5_flag` and `AVC_compatible_flags`.
+    pub constraint_flags: u8,
+
+    pub level_idc: u8,
+    pub avc_still_present: bool,
+    pub avc_24_hour_picture: bool,
+    pub frame_packing_sei_not_present: bool,
+}
+
+/// Service descriptor (tag `0x48`, DVB).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ServiceDescriptor {
+    /// Service type (e.g., `0x01` for digital television service).
+    pub service_type: u8,
+
+    /// Encoded name of the service provider.
+    pub provider_name: Vec<u8>,
+
+    /// Encoded name of the service.
+    pub service_name: Vec<u8>,
+}
+
+/// Entry of a subtitling descriptor (tag `0x59`, DVB).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Subtitling {
+    /// ISO 639-2 language code.
+    pub language: [u8; 3],
+
+    /// Subtitling type (e.g., `0x10` for DVB subtitles without a monitor aspect ratio).
+    pub subtitling_type: u8,
+
+    /// Composition page identifier.
+    pub composition_page_id: u16,
+
+    /// Ancillary page identifier.
+    pub ancillary_page_id: u16,
+}
+
+/// AAC descriptor (tag `0x7C`, DVB).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AacDescriptor {
+    /// Profile and level.
+    pub profile_and_level: u8,
+
+    /// AAC type (if present).
+    pub aac_type: Option<u8>,
+
+    /// `SAOC_DE_flag`.
+    pub saoc_de: bool,
+
+    /// Additional info bytes.
+    pub additional_info: Vec<u8>,
+}
+
+/// Opus audio descriptor (tag `0x7F` with `descriptor_tag_extension` `0x80`, "Opus in MPEG-TS").
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OpusAudioDescriptor {
+    /// Channel configuration code (e.g., `0x02` for stereo).
+    ///
+    /// `0xFF` means that `channel_config_extension` follows.
+    pub channel_config_code: u8,
+
+    /// Extended channel configuration bytes (present only if `channel_config_code` is `0xFF`).
+    pub channel_config_extension: Vec<u8>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip(known: KnownDescriptor, data: &[u8]) {
+        let d = track_try_unwrap!(known.to_descriptor());
+        assert_eq!(d.tag, known.tag());
+        assert_eq!(d.data, data);
+        assert_eq!(track_try_unwrap!(d.parse()), known);
+    }
+
+    #[test]
+    fn registration() {
+        let d = RegistrationDescriptor {
+            format_identifier: *b"CUEI",
+            additional_identification_info: vec![],
+        };
+        round_trip(KnownDescriptor::Registration(d), b"CUEI");
+    }
+
+    #[test]
+    fn ca() {
+        let d = CaDescriptor {
+            ca_system_id: 0x0B00,
+            ca_pid: Pid::new(0x1FF0).unwrap(),
+            private_data: vec![1, 2],
+        };
+        round_trip(KnownDescriptor::Ca(d), &[0x0B, 0x00, 0xFF, 0xF0, 1, 2]);
+    }
+
+    #[test]
+    fn iso_639_language() {
+        let languages = vec![
+            Iso639Language {
+                language: *b"eng",
+                audio_type: 0,
+            },
+            Iso639Language {
+                language: *b"fra",
+                audio_type: 3,
+            },
+        ];
+        round_trip(
+            KnownDescriptor::Iso639Language(languages),
+            b"eng\x00fra\x03",
+        );
+    }
+
+    #[test]
+    fn avc_video() {
+        let d = AvcVideoDescriptor {
+            profile_idc: 100,
+            constraint_flags: 0,
+            level_idc: 40,
+            avc_still_present: false,
+            avc_24_hour_picture: false,
+            frame_packing_sei_not_present: true,
+        };
+        round_trip(KnownDescriptor::AvcVideo(d), &[100, 0, 40, 0x3F]);
+    }
+
+    #[test]
+    fn service() {
+        let d = ServiceDescriptor {
+            service_type: 1,
+            provider_name: b"BBC".to_vec(),
+            service_name: b"BBC ONE".to_vec(),
+        };
+        round_trip(KnownDescriptor::Service(d), b"\x01\x03BBC\x07BBC ONE");
+
+        let broken = Descriptor {
+            tag: KnownDescriptor::SERVICE_TAG,
+            data: b"\x01\x03BBC\x07BBC".to_vec(),
+        };
+        assert!(broken.parse().is_err());
+    }
+
+    #[test]
+    fn stream_identifier() {
+        round_trip(KnownDescriptor::StreamIdentifier(0x21), &[0x21]);
+    }
+
+    #[test]
+    fn teletext() {
+        let pages = vec![TeletextPage {
+            language: *b"deu",
+            teletext_type: TeletextPage::TYPE_SUBTITLE_PAGE,
+            magazine_number: 1,
+            page_number: 0x50,
+        }];
+        round_trip(KnownDescriptor::Teletext(pages), b"deu\x11\x50");
+    }
+
+    #[test]
+    fn subtitling() {
+        let subtitlings = vec![Subtitling {
+            language: *b"eng",
+            subtitling_type: 0x10,
+            composition_page_id: 1,
+            ancillary_page_id: 2,
+        }];
+        round_trip(
+            KnownDescriptor::Subtitling(subtitlings),
+            b"eng\x10\x00\x01\x00\x02",
+        );
+    }
+
+    #[test]
+    fn aac() {
+        let d = AacDescriptor {
+            profile_and_level: 0x58,
+            aac_type: Some(0x03),
+            saoc_de: false,
+            additional_info: vec![],
+        };
+        round_trip(KnownDescriptor::Aac(d), &[0x58, 0x80, 0x03]);
+
+        // The flags byte is optional
+        let d = Descriptor {
+            tag: KnownDescriptor::AAC_TAG,
+            data: vec![0x51],
+        };
+        assert!(matches!(
+            track_try_unwrap!(d.parse()),
+            KnownDescriptor::Aac(AacDescriptor { aac_type: None, .. })
+        ));
+    }
+
+    #[test]
+    fn opus_audio() {
+        let d = OpusAudioDescriptor {
+            channel_config_code: 0x02,
+            channel_config_extension: vec![],
+        };
+        round_trip(KnownDescriptor::OpusAudio(d), &[0x80, 0x02]);
+
+        // Other extension descriptors are unknown
+        let d = Descriptor {
+            tag: KnownDescriptor::EXTENSION_TAG,
+            data: vec![0x15, 0x00],
+        };
+        assert_eq!(
+            track_try_unwrap!(d.parse()),
+            KnownDescriptor::Unknown(0x7F, vec![0x15, 0x00])
+        );
+    }
+
+    #[test]
+    fn unknown() {
+        round_trip(KnownDescriptor::Unknown(0xC0, vec![1, 2, 3]), &[1, 2, 3]);
+        assert!(KnownDescriptor::Unknown(0xC0, vec![0; 256])
+            .to_descriptor()
+            .is_err());
+    }
+}
@@ -0,0 +1,354 @@
+use crate::time::{ClockReference, Timestamp};
+use crate::ts::{
+    Continuity, ContinuityChecker, ContinuityCounter, Pid, PidTable, ReadTsPacket, TsPacket,
+    TsPayload,
+};
+use crate::Result;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// Kind of a `DiscontinuityEvent`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DiscontinuityKind {
+    /// The continuity counter skipped some values.
+    ContinuityGap {
+        /// Expected continuity counter.
+        expected: ContinuityCounter,
+
+        /// Actual continuity counter.
+        actual: ContinuityCounter,
+
+        /// Number of the packets that were likely lost (`actual - expected` modulo 16).
+        lost_packets: u8,
+    },
+
+    /// The packet has the discontinuity indicator set.
+    DiscontinuityIndicator,
+
+    /// The PCR went backwards or advanced more than the threshold
+    /// (see `DiscontinuityScanner::set_pcr_jump_threshold`).
+    PcrJump {
+        /// Previous PCR on the PID.
+        previous: ClockReference,
+
+        /// Current PCR.
+        current: ClockReference,
+
+        /// Difference in 27 MHz ticks (see `ClockReference::delta`).
+        delta: i64,
+    },
+
+    /// The PTS moved (in either direction) more than the threshold
+    /// (see `DiscontinuityScanner::set_pts_jump_threshold`).
+    PtsJump {
+        /// Previous PTS on the PID.
+        previous: Timestamp,
+
+        /// Current PTS.
+        current: Timestamp,
+
+        /// Difference in 90 kHz ticks (see `Timestamp::delta`).
+        delta: i64,
+    },
+}
+
+/// Discontinuity detected by `DiscontinuityScanner`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DiscontinuityEvent {
+    /// Index (zero origin) of the packet among the observed packets.
+    pub packet_index: u64,
+
+    /// Byte offset of the packet (if known).
+    pub byte_offset: Option<u64>,
+
+    /// PID of the packet.
+    pub pid: Pid,
+
+    /// Kind of the discontinuity.
+    pub kind: DiscontinuityKind,
+}
+
+/// Reads the whole of the stream, and returns the discontinuities in it.
+///
+/// The byte offsets of the packets are taken from `ReadTsPacket::last_packet_byte_offset`.
+pub fn scan_discontinuities<R: ReadTsPacket>(reader: &mut R) -> Result<Vec<DiscontinuityEvent>> {
+    let mut scanner = DiscontinuityScanner::new();
+    while let Some(packet) = track!(reader.read_ts_packet())? {
+        scanner.observe(&packet, reader.last_packet_byte_offset());
+    }
+    Ok(scanner.into_events())
+}
+
+/// Scanner of the continuity counter gaps, discontinuity indicators, and PCR and PTS jumps in a stream.
+///
+/// The continuity counters are checked in the same way as `ContinuityChecker`.
+/// The PCRs and PTSs are compared with the previous ones on the same PID
+/// with wrap-around taken into account, and the comparison restarts after a packet
+/// with the discontinuity indicator set.
+#[derive(Debug)]
+pub struct DiscontinuityScanner {
+    pcr_jump_threshold: Duration,
+    pts_jump_threshold: Duration,
+    packet_index: u64,
+    continuity_checker: ContinuityChecker,
+    pcrs: PidTable<ClockReference>,
+    ptss: PidTable<Timestamp>,
+    events: Vec<DiscontinuityEvent>,
+}
+impl DiscontinuityScanner {
+    /// Default value of the PCR jump threshold.
+    pub const DEFAULT_PCR_JUMP_THRESHOLD: Duration = Duration::from_secs(1);
+
+    /// Default value of the PTS jump threshold.
+    pub const DEFAULT_PTS_JUMP_THRESHOLD: Duration = Duration::from_secs(1);
+
+    /// Makes a new `DiscontinuityScanner` instance.
+    pub fn new() -> Self {
+        DiscontinuityScanner {
+            pcr_jump_threshold: Self::DEFAULT_PCR_JUMP_THRESHOLD,
+            pts_jump_threshold: Self::DEFAULT_PTS_JUMP_THRESHOLD,
+            packet_index: 0,
+            continuity_checker: ContinuityChecker::new(),
+            pcrs: PidTable::new(),
+            ptss: PidTable::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Sets the maximum forward step of the PCR that is not reported as `DiscontinuityKind::PcrJump`.
+    ///
+    /// The default value is `DEFAULT_PCR_JUMP_THRESHOLD`.
+    pub fn set_pcr_jump_threshold(&mut self, threshold: Duration) {
+        self.pcr_jump_threshold = threshold;
+    }
+
+    /// Sets the maximum step (in either direction) of the PTS that is not reported as `DiscontinuityKind::PtsJump`.
+    ///
+    /// The default value is `DEFAULT_PTS_JUMP_THRESHOLD`.
+    pub fn set_pts_jump_threshold(&mut self, threshold: Duration) {
+        self.pts_jump_threshold = threshold;
+    }
+
+    /// Returns the events detected so far.
+    pub fn events(&self) -> &[DiscontinuityEvent] {
+        &self.events
+    }
+
+    /// Takes ownership of `self`, and returns the detected events.
+    pub fn into_events(self) -> Vec<DiscontinuityEvent> {
+        self.events
+    }
+
+    /// Observes `packet` located at `byte_offset` (if known) in the stream.
+    pub fn observe(&mut self, packet: &TsPacket, byte_offset: Option<u64>) {
+        let packet_index = self.packet_index;
+        self.packet_index += 1;
+        let pid = packet.header.pid;
+        let mut push = |kind| {
+            self.events.push(DiscontinuityEvent {
+                packet_index,
+                byte_offset,
+                pid,
+                kind,
+            })
+        };
+
+        if let Continuity::Discontinuous { expected, actual } =
+            self.continuity_checker.check(packet)
+        {
+            let lost_packets =
+                actual.as_u8().wrapping_sub(expected.as_u8()) & ContinuityCounter::MAX;
+            push(DiscontinuityKind::ContinuityGap {
+                expected,
+                actual,
+                lost_packets,
+            });
+        }
+
+        let discontinuity_indicator = packet
+            .adaptation_field
+            .as_ref()
+            .is_some_and(|a| a.discontinuity_indicator);
+        if discontinuity_indicator {
+            push(DiscontinuityKind::DiscontinuityIndicator);
+            self.pcrs.remove(pid);
+            self.ptss.remove(pid);
+        }
+
+        if let Some(current) = packet.adaptation_field.as_ref().and_then(|a| a.pcr) {
+            if let Some(previous) = self.pcrs.insert(pid, current) {
+                let delta = current.delta(previous);
+                let threshold = self.pcr_jump_threshold.as_nanos() * 27 / 1000;
+                if delta < 0 || delta as u128 > threshold {
+                    push(DiscontinuityKind::PcrJump {
+                        previous,
+                        current,
+                        delta,
+                    });
+                }
+            }
+        }
+
+        if let Some(TsPayload::Pes(pes)) = &packet.payload {
+            if let Some(current) = pes.header.pts {
+                if let Some(previous) = self.ptss.insert(pid, current) {
+                    let delta = current.delta(previous);
+                    let threshold = self.pts_jump_threshold.as_nanos() * 9 / 100_000;
+                    if u128::from(delta.unsigned_abs()) > threshold {
+                        push(DiscontinuityKind::PtsJump {
+                            previous,
+                            current,
+                            delta,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+impl Default for DiscontinuityScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::{StreamId, StreamType};
+    use crate::pes::PesHeader;
+    use crate::ts::payload::{Bytes, Pat, Pes, Pmt};
+    use crate::ts::{
+        AdaptationField, EsInfo, ProgramAssociation, TsPacketReader, TsPacketWriter, VersionNumber,
+        WriteTsPacket,
+    };
+
+    fn psi() -> Vec<TsPacket> {
+        let pat = TsPayload::Pat(Pat {
+            transport_stream_id: 1,
+            version_number: VersionNumber::new(),
+            is_current: true,
+            table: vec![ProgramAssociation {
+                program_num: 1,
+                program_map_pid: Pid::new(0x1000).unwrap(),
+            }],
+            raw: None,
+        });
+        let pmt = TsPayload::Pmt(Pmt {
+            program_num: 1,
+            pcr_pid: Some(Pid::new(256).unwrap()),
+            version_number: VersionNumber::new(),
+            is_current: true,
+            program_info: vec![],
+            es_info: vec![EsInfo {
+                stream_type: StreamType::H264,
+                elementary_pid: Pid::new(256).unwrap(),
+                descriptors: vec![],
+            }],
+            raw: None,
+        });
+        [(Pid::PAT, pat), (0x1000, pmt)]
+            .into_iter()
+            .map(|(pid, payload)| {
+                track_try_unwrap!(TsPacket::builder(Pid::new(pid).unwrap())
+                    .payload(payload)
+                    .build())
+            })
+            .collect()
+    }
+
+    fn packet(counter: u8, pcr: Option<u64>, pts: Option<u64>, discontinuity: bool) -> TsPacket {
+        let mut adaptation_field = AdaptationField {
+            discontinuity_indicator: discontinuity,
+            pcr: pcr.map(|pcr| ClockReference::new(pcr).unwrap()),
+            ..Default::default()
+        };
+        if adaptation_field.pcr.is_none() && !discontinuity {
+            adaptation_field.transport_private_data = vec![0; 4];
+        }
+        let mut header = PesHeader::new(StreamId::new(0xE0));
+        header.pts = pts.map(|pts| Timestamp::new(pts).unwrap());
+        let payload = TsPayload::Pes(Pes {
+            header,
+            pes_packet_len: 0,
+            data: Bytes::new(&[0; 10]).unwrap(),
+        });
+        track_try_unwrap!(TsPacket::builder(Pid::new(256).unwrap())
+            .continuity(ContinuityCounter::from_u8(counter).unwrap())
+            .adaptation(adaptation_field)
+            .payload(payload)
+            .build())
+    }
+
+    #[test]
+    fn scan() {
+        let second = ClockReference::RESOLUTION;
+        let mut packets = psi();
+        packets.extend([
+            // PCR and PTS just before wrapping around
+            packet(
+                14,
+                Some(ClockReference::MODULUS - 1000),
+                Some(Timestamp::MAX),
+                false,
+            ),
+            packet(15, Some(1000), Some(3000), false),
+            // Two packets lost
+            packet(2, Some(second / 2), Some(6000), false),
+            // PCR jump of two seconds and PTS jump backwards
+            packet(
+                3,
+                Some(second * 5 / 2),
+                Some(Timestamp::MODULUS + 3000 - 180_000),
+                false,
+            ),
+            // Expected discontinuity
+            packet(9, Some(second * 100), Some(0), true),
+            packet(10, Some(second * 100 + 1000), Some(3000), false),
+        ]);
+        let mut writer = TsPacketWriter::new(Vec::new());
+        for packet in &packets {
+            track_try_unwrap!(writer.write_ts_packet(packet));
+        }
+        let stream = writer.into_stream();
+
+        let mut reader = TsPacketReader::new(&stream[..]);
+        let events = track_try_unwrap!(scan_discontinuities(&mut reader));
+        let kinds = events.iter().map(|e| &e.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                &DiscontinuityKind::ContinuityGap {
+                    expected: ContinuityCounter::from_u8(0).unwrap(),
+                    actual: ContinuityCounter::from_u8(2).unwrap(),
+                    lost_packets: 2,
+                },
+                &DiscontinuityKind::PcrJump {
+                    previous: ClockReference::new(second / 2).unwrap(),
+                    current: ClockReference::new(second * 5 / 2).unwrap(),
+                    delta: second as i64 * 2,
+                },
+                &DiscontinuityKind::PtsJump {
+                    previous: Timestamp::new(6000).unwrap(),
+                    current: Timestamp::new(Timestamp::MODULUS + 3000 - 180_000).unwrap(),
+                    delta: -183_000,
+                },
+                &DiscontinuityKind::DiscontinuityIndicator,
+            ]
+        );
+        let positions = events
+            .iter()
+            .map(|e| (e.packet_index, e.byte_offset))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            positions,
+            [
+                (4, Some(4 * 188)),
+                (5, Some(5 * 188)),
+                (5, Some(5 * 188)),
+                (6, Some(6 * 188))
+            ]
+        );
+        assert!(events.iter().all(|e| e.pid == Pid::new(256).unwrap()));
+    }
+}
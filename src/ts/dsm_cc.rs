@@ -0,0 +1,328 @@
+use crate::crc::Crc32;
+use crate::ts::VersionNumber;
+use crate::{ErrorKind, Result};
+use alloc::vec::Vec;
+
+/// DSM-CC section (ISO/IEC 13818-6) carried by the stream types 0x0A..=0x0D.
+///
+/// Only the section header and the message headers are parsed,
+/// and the payloads (e.g., BIOP messages and module blocks) are left raw.
+/// The sections can be obtained from the packets by `SectionAssembler`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DsmCcSection {
+    /// Table ID (`0x3A`..=`0x3E`).
+    pub table_id: u8,
+
+    /// `table_id_extension` (e.g., the low 16 bits of `transactionId` or `moduleId`).
+    pub table_id_extension: u16,
+
+    #[allow(missing_docs)]
+    pub version_number: VersionNumber,
+
+    #[allow(missing_docs)]
+    pub current_next_indicator: bool,
+
+    #[allow(missing_docs)]
+    pub section_number: u8,
+
+    #[allow(missing_docs)]
+    pub last_section_number: u8,
+
+    /// `dsmccMessageHeader` (table ID `0x3B`) or `dsmccDownloadDataHeader` (table ID `0x3C`).
+    pub message_header: Option<DsmCcMessageHeader>,
+
+    /// Message payload following the message header (or the section data if there is no message header).
+    pub payload: Vec<u8>,
+}
+impl DsmCcSection {
+    /// Table ID of the sections carrying LLC/SNAP (multiprotocol encapsulation) data.
+    pub const LLC_SNAP_TABLE_ID: u8 = 0x3A;
+
+    /// Table ID of the sections carrying user-network messages (e.g., DSI and DII).
+    pub const USER_NETWORK_MESSAGE_TABLE_ID: u8 = 0x3B;
+
+    /// Table ID of the sections carrying download data messages (i.e., DDB).
+    pub const DOWNLOAD_DATA_MESSAGE_TABLE_ID: u8 = 0x3C;
+
+    /// Table ID of the sections carrying stream descriptors.
+    pub const STREAM_DESCRIPTORS_TABLE_ID: u8 = 0x3D;
+
+    /// Table ID of the sections carrying private data (e.g., multiprotocol encapsulation datagrams).
+    pub const PRIVATE_DATA_TABLE_ID: u8 = 0x3E;
+
+    /// Parses a DSM-CC section.
+    ///
+    /// If `section_syntax_indicator` is set, the CRC32 is checked
+    /// (the checksum used instead of the CRC32 is not checked).
+    ///
+    /// # Errors
+    ///
+    /// If the CRC32 does not match, it will return an `ErrorKind::CrcMismatch` error.
+    /// If the section is malformed, it will return an `ErrorKind::InvalidInput` error.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        track_assert!(
+            bytes.len() >= 3,
+            ErrorKind::InvalidInput,
+            "Too short DSM-CC section: size={}",
+            bytes.len()
+        );
+        let table_id = bytes[0];
+        track_assert!(
+            (Self::LLC_SNAP_TABLE_ID..=Self::PRIVATE_DATA_TABLE_ID).contains(&table_id),
+            ErrorKind::InvalidInput,
+            "Not a DSM-CC section: table_id={}",
+            table_id
+        );
+        let section_syntax_indicator = bytes[1] & 0b1000_0000 != 0;
+        let section_len = usize::from(u16::from_be_bytes([bytes[1], bytes[2]]) & 0x0FFF);
+        track_assert!(
+            section_len >= 5 + 4 && bytes.len() >= 3 + section_len,
+            ErrorKind::InvalidInput,
+            "Malformed DSM-CC section: size={}, section_length={}",
+            bytes.len(),
+            section_len
+        );
+        let bytes = &bytes[..3 + section_len];
+        let (body, crc) = bytes.split_at(bytes.len() - 4);
+        if section_syntax_indicator {
+            let mut crc32 = Crc32::new();
+            crc32.update(body);
+            let expected = u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]);
+            track_assert_eq!(crc32.value(), expected, ErrorKind::CrcMismatch);
+        }
+
+        let mut data = &body[8..];
+        let message_header = match table_id {
+            Self::USER_NETWORK_MESSAGE_TABLE_ID | Self::DOWNLOAD_DATA_MESSAGE_TABLE_ID => {
+                let (header, message_len) = track!(DsmCcMessageHeader::parse(data))?;
+                let offset = DsmCcMessageHeader::FIXED_SIZE + header.adaptation.len();
+                let end = DsmCcMessageHeader::FIXED_SIZE + message_len;
+                track_assert!(
+                    end >= offset && end <= data.len(),
+                    ErrorKind::InvalidInput,
+                    "Malformed DSM-CC message: size={}, message_length={}",
+                    data.len(),
+                    message_len
+                );
+                data = &data[offset..end];
+                Some(header)
+            }
+            _ => None,
+        };
+
+        Ok(DsmCcSection {
+            table_id,
+            table_id_extension: u16::from_be_bytes([body[3], body[4]]),
+            version_number: track!(VersionNumber::from_u8((body[5] >> 1) & 0b1_1111))?,
+            current_next_indicator: body[5] & 1 != 0,
+            section_number: body[6],
+            last_section_number: body[7],
+            message_header,
+            payload: data.to_vec(),
+        })
+    }
+
+    /// Returns the header of the `DownloadDataBlock` message (if this section carries one).
+    pub fn download_data_block(&self) -> Option<DownloadDataBlock> {
+        if self.table_id != Self::DOWNLOAD_DATA_MESSAGE_TABLE_ID
+            || self.message_header.as_ref()?.message_id != DownloadDataBlock::MESSAGE_ID
+        {
+            return None;
+        }
+        let b = self.payload.get(..DownloadDataBlock::HEADER_SIZE)?;
+        Some(DownloadDataBlock {
+            module_id: u16::from_be_bytes([b[0], b[1]]),
+            module_version: b[2],
+            block_number: u16::from_be_bytes([b[4], b[5]]),
+        })
+    }
+
+    /// Returns the block data of the `DownloadDataBlock` message (if this section carries one).
+    pub fn block_data(&self) -> Option<&[u8]> {
+        self.download_data_block()?;
+        Some(&self.payload[DownloadDataBlock::HEADER_SIZE..])
+    }
+}
+
+/// `dsmccMessageHeader` or `dsmccDownloadDataHeader`.
+///
+/// Both have the same layout except that the latter has `downloadId` instead of `transactionId`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DsmCcMessageHeader {
+    /// `protocolDiscriminator` (always `0x11`).
+    pub protocol_discriminator: u8,
+
+    /// `dsmccType` (e.g., `0x03` for the U-N download messages).
+    pub dsmcc_type: u8,
+
+    /// `messageId` (e.g., `0x1002` for DII and `0x1003` for DDB).
+    pub message_id: u16,
+
+    /// `transactionId` (or `downloadId` in `dsmccDownloadDataHeader`).
+    pub transaction_id: u32,
+
+    /// `dsmccAdaptationHeader` bytes.
+    pub adaptation: Vec<u8>,
+}
+impl DsmCcMessageHeader {
+    const FIXED_SIZE: usize = 12;
+
+    /// Parses the header, and returns it with `messageLength`.
+    fn parse(bytes: &[u8]) -> Result<(Self, usize)> {
+        track_assert!(
+            bytes.len() >= Self::FIXED_SIZE,
+            ErrorKind::InvalidInput,
+            "Too short DSM-CC message header: size={}",
+            bytes.len()
+        );
+        let adaptation_len = usize::from(bytes[9]);
+        let message_len = usize::from(u16::from_be_bytes([bytes[10], bytes[11]]));
+        let adaptation = track_assert_some!(
+            bytes.get(Self::FIXED_SIZE..Self::FIXED_SIZE + adaptation_len),
+            ErrorKind::InvalidInput,
+            "Truncated DSM-CC adaptation header: adaptation_length={}",
+            adaptation_len
+        );
+        let header = DsmCcMessageHeader {
+            protocol_discriminator: bytes[0],
+            dsmcc_type: bytes[1],
+            message_id: u16::from_be_bytes([bytes[2], bytes[3]]),
+            transaction_id: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            adaptation: adaptation.to_vec(),
+        };
+        Ok((header, message_len))
+    }
+}
+
+/// Header of a `DownloadDataBlock` message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DownloadDataBlock {
+    #[allow(missing_docs)]
+    pub module_id: u16,
+
+    #[allow(missing_docs)]
+    pub module_version: u8,
+
+    #[allow(missing_docs)]
+    pub block_number: u16,
+}
+impl DownloadDataBlock {
+    /// `messageId` of the `DownloadDataBlock` message.
+    pub const MESSAGE_ID: u16 = 0x1003;
+
+    const HEADER_SIZE: usize = 6;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::StreamType;
+    use crate::ts::payload::{Pat, Pmt, Section};
+    use crate::ts::{
+        EsInfo, Pid, ProgramAssociation, ReadTsPacket, SectionAssembler, TsPacket, TsPacketReader,
+        TsPacketWriter, TsPayload, WriteTsPacket,
+    };
+
+    fn ddb_section(block_number: u16, block: &[u8]) -> Vec<u8> {
+        let mut message = vec![0x00, 0x05, 0x01, 0xFF];
+        message.extend_from_slice(&block_number.to_be_bytes());
+        message.extend_from_slice(block);
+
+        let mut body = vec![0x11, 0x03, 0x10, 0x03, 0x00, 0x00, 0x00, 0x2A, 0xFF, 2];
+        body.extend_from_slice(&(2 + message.len() as u16).to_be_bytes());
+        body.extend_from_slice(&[0xAA, 0xBB]);
+        body.extend_from_slice(&message);
+
+        let section_len = 5 + body.len() + 4;
+        let mut section = vec![0x3C, 0xB0 | (section_len >> 8) as u8, section_len as u8];
+        section.extend_from_slice(&[0x00, 0x05, 0xC3, block_number as u8, 1]);
+        section.extend_from_slice(&body);
+        let mut crc32 = Crc32::new();
+        crc32.update(&section);
+        section.extend_from_slice(&crc32.value().to_be_bytes());
+        section
+    }
+
+    #[test]
+    fn dsm_cc_sections() {
+        let pid = Pid::new(0x200).unwrap();
+        let pat = TsPayload::Pat(Pat {
+            transport_stream_id: 1,
+            version_number: VersionNumber::new(),
+            is_current: true,
+            table: vec![ProgramAssociation {
+                program_num: 1,
+                program_map_pid: Pid::new(0x1000).unwrap(),
+            }],
+            raw: None,
+        });
+        let pmt = TsPayload::Pmt(Pmt {
+            program_num: 1,
+            pcr_pid: None,
+            version_number: VersionNumber::new(),
+            is_current: true,
+            program_info: vec![],
+            es_info: vec![EsInfo {
+                stream_type: StreamType::DsmCcUnMessages,
+                elementary_pid: pid,
+                descriptors: vec![],
+            }],
+            raw: None,
+        });
+        let first = ddb_section(0, &[1; 300]);
+        let second = ddb_section(1, &[2; 10]);
+        let mut bytes = first.clone();
+        bytes.extend_from_slice(&second);
+
+        let mut writer = TsPacketWriter::new(Vec::new());
+        for (pid, payload) in [
+            (Pid::new(0).unwrap(), pat),
+            (Pid::new(0x1000).unwrap(), pmt),
+        ] {
+            let packet = track_try_unwrap!(TsPacket::builder(pid).payload(payload).build());
+            track_try_unwrap!(writer.write_ts_packet(&packet));
+        }
+        let section = TsPayload::Section(track_try_unwrap!(Section::new(0, &bytes[..183])));
+        let packet = track_try_unwrap!(TsPacket::builder(pid).payload(section).build());
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+        for chunk in bytes[183..].chunks(184) {
+            let packet = track_try_unwrap!(TsPacket::builder(pid).payload_raw(chunk).build());
+            track_try_unwrap!(writer.write_ts_packet(&packet));
+        }
+        let stream = writer.into_stream();
+
+        let mut reader = TsPacketReader::new(&stream[..]);
+        let mut assembler = SectionAssembler::new();
+        let mut sections = Vec::new();
+        while let Some(packet) = track_try_unwrap!(reader.read_ts_packet()) {
+            sections.extend(assembler.push(&packet));
+        }
+        assert_eq!(sections, [first, second]);
+
+        let section = track_try_unwrap!(DsmCcSection::parse(&sections[1]));
+        assert_eq!(
+            section.table_id,
+            DsmCcSection::DOWNLOAD_DATA_MESSAGE_TABLE_ID
+        );
+        assert_eq!(section.table_id_extension, 5);
+        assert_eq!(section.section_number, 1);
+        let header = section.message_header.as_ref().unwrap();
+        assert_eq!(header.message_id, DownloadDataBlock::MESSAGE_ID);
+        assert_eq!(header.transaction_id, 42);
+        assert_eq!(header.adaptation, [0xAA, 0xBB]);
+        assert_eq!(
+            section.download_data_block(),
+            Some(DownloadDataBlock {
+                module_id: 5,
+                module_version: 1,
+                block_number: 1,
+            })
+        );
+        assert_eq!(section.block_data(), Some(&[2; 10][..]));
+
+        let mut broken = sections[0].clone();
+        broken[20] ^= 1;
+        let e = DsmCcSection::parse(&broken).err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::CrcMismatch);
+    }
+}
@@ -0,0 +1,355 @@
+use crate::ts::AdaptationField;
+use crate::{ErrorKind, Result};
+use alloc::{vec, vec::Vec};
+
+/// Encoder Boundary Point (CableLabs OC-SP-EBP) carried in `AdaptationField::transport_private_data`.
+///
+/// It marks the packet as the start of a fragment and/or segment,
+/// so that downstream packagers can align their boundaries with the encoder's ones.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::ts::{AdaptationField, Ebp};
+///
+/// let ebp = Ebp {
+///     segment: true,
+///     sap_type: Some(1),
+///     ..Default::default()
+/// };
+///
+/// let mut adaptation_field = AdaptationField::default();
+/// adaptation_field.set_ebp(&ebp).unwrap();
+/// assert_eq!(adaptation_field.ebp().unwrap(), Some(ebp));
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Ebp {
+    /// `EBP_fragment_flag`.
+    pub fragment: bool,
+
+    /// `EBP_segment_flag`.
+    pub segment: bool,
+
+    /// Type of the stream access point (`1`..=`6`) starting at the packet.
+    pub sap_type: Option<u8>,
+
+    /// Grouping identifiers (`0`..=`127` each).
+    pub grouping_ids: Vec<u8>,
+
+    /// Acquisition time in the NTP timestamp format.
+    pub time: Option<u64>,
+
+    /// `EBP_concealment_flag`.
+    pub concealment: bool,
+
+    /// `EBP_ext_partitions` (if the extension partitions are signalled).
+    pub ext_partitions: Option<u8>,
+}
+impl Ebp {
+    /// Tag of the private data structure carrying an EBP.
+    pub const TAG: u8 = 0xDF;
+
+    /// Format identifier following the tag and length.
+    pub const FORMAT_IDENTIFIER: [u8; 4] = *b"EBP0";
+
+    /// Maximum value of `sap_type`.
+    pub const MAX_SAP_TYPE: u8 = 0b111;
+
+    /// Maximum value of the grouping identifiers.
+    pub const MAX_GROUPING_ID: u8 = 0x7F;
+
+    /// Parses an EBP structure (including the tag and length bytes).
+    ///
+    /// It will return an `ErrorKind::InvalidInput` error if `bytes` is not a well-formed EBP structure.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        track_assert!(
+            bytes.len() >= 2,
+            ErrorKind::InvalidInput,
+            "Too short EBP: size={}",
+            bytes.len()
+        );
+        track_assert_eq!(bytes[0], Self::TAG, ErrorKind::InvalidInput);
+        let len = usize::from(bytes[1]);
+        track_assert!(
+            len + 2 <= bytes.len(),
+            ErrorKind::InvalidInput,
+            "Truncated EBP: size={}, length={}",
+            bytes.len(),
+            len
+        );
+        let data = &bytes[2..2 + len];
+        track_assert!(
+            data.starts_with(&Self::FORMAT_IDENTIFIER),
+            ErrorKind::InvalidInput,
+            "Unexpected format identifier: {:?}",
+            &data[..data.len().min(4)]
+        );
+
+        let mut rest = &data[4..];
+        let mut take = |n: usize| -> Result<&[u8]> {
+            track_assert!(
+                rest.len() >= n,
+                ErrorKind::InvalidInput,
+                "Truncated EBP: length={}",
+                len
+            );
+            let (head, tail) = rest.split_at(n);
+            rest = tail;
+            Ok(head)
+        };
+
+        let flags = track!(take(1))?[0];
+        let ext_partition_flag = if flags & 0b0000_0001 != 0 {
+            track!(take(1))?[0] & 0b1000_0000 != 0
+        } else {
+            false
+        };
+        let sap_type = if flags & 0b0010_0000 != 0 {
+            Some(track!(take(1))?[0] >> 5)
+        } else {
+            None
+        };
+        let mut grouping_ids = Vec::new();
+        if flags & 0b0001_0000 != 0 {
+            loop {
+                let b = track!(take(1))?[0];
+                grouping_ids.push(b & Self::MAX_GROUPING_ID);
+                if b & 0b1000_0000 == 0 {
+                    break;
+                }
+            }
+        }
+        let time = if flags & 0b0000_1000 != 0 {
+            let b = track!(take(8))?;
+            Some(u64::from_be_bytes(b.try_into().expect("Never fails")))
+        } else {
+            None
+        };
+        let ext_partitions = if ext_partition_flag {
+            Some(track!(take(1))?[0])
+        } else {
+            None
+        };
+        // The remaining bytes are reserved
+
+        Ok(Ebp {
+            fragment: flags & 0b1000_0000 != 0,
+            segment: flags & 0b0100_0000 != 0,
+            sap_type,
+            grouping_ids,
+            time,
+            concealment: flags & 0b0000_0100 != 0,
+            ext_partitions,
+        })
+    }
+
+    /// Serializes the EBP structure (including the tag and length bytes).
+    ///
+    /// It will return an `ErrorKind::InvalidInput` error if a field is out of range.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = vec![Self::TAG, 0];
+        bytes.extend_from_slice(&Self::FORMAT_IDENTIFIER);
+        bytes.push(
+            (u8::from(self.fragment) << 7)
+                | (u8::from(self.segment) << 6)
+                | (u8::from(self.sap_type.is_some()) << 5)
+                | (u8::from(!self.grouping_ids.is_empty()) << 4)
+                | (u8::from(self.time.is_some()) << 3)
+                | (u8::from(self.concealment) << 2)
+                | 0b10
+                | u8::from(self.ext_partitions.is_some()),
+        );
+        if self.ext_partitions.is_some() {
+            bytes.push(0xFF);
+        }
+        if let Some(sap_type) = self.sap_type {
+            track_assert!(
+                sap_type <= Self::MAX_SAP_TYPE,
+                ErrorKind::InvalidInput,
+                "Too large SAP type: {}",
+                sap_type
+            );
+            bytes.push((sap_type << 5) | 0b1_1111);
+        }
+        for (i, &id) in self.grouping_ids.iter().enumerate() {
+            track_assert!(
+                id <= Self::MAX_GROUPING_ID,
+                ErrorKind::InvalidInput,
+                "Too large grouping ID: {}",
+                id
+            );
+            let ext_flag = i + 1 < self.grouping_ids.len();
+            bytes.push((u8::from(ext_flag) << 7) | id);
+        }
+        if let Some(time) = self.time {
+            bytes.extend_from_slice(&time.to_be_bytes());
+        }
+        if let Some(ext_partitions) = self.ext_partitions {
+            bytes.push(ext_partitions);
+        }
+        track_assert!(
+            bytes.len() - 2 <= 0xFF,
+            ErrorKind::InvalidInput,
+            "Too many grouping IDs: {}",
+            self.grouping_ids.len()
+        );
+        bytes[1] = (bytes.len() - 2) as u8;
+        Ok(bytes)
+    }
+
+    /// Returns the range of the first EBP structure in `transport_private_data` (if any).
+    ///
+    /// The private data is regarded as a sequence of tag-length-data structures.
+    fn find(transport_private_data: &[u8]) -> Option<core::ops::Range<usize>> {
+        let mut offset = 0;
+        while let [tag, len, ..] = transport_private_data[offset..] {
+            let end = offset + 2 + usize::from(len);
+            let data = transport_private_data.get(offset + 2..end)?;
+            if tag == Self::TAG && data.starts_with(&Self::FORMAT_IDENTIFIER) {
+                return Some(offset..end);
+            }
+            offset = end;
+        }
+        None
+    }
+}
+
+impl AdaptationField {
+    /// Returns the EBP in `transport_private_data` (if any).
+    ///
+    /// It will return an `ErrorKind::InvalidInput` error if the EBP structure is malformed.
+    pub fn ebp(&self) -> Result<Option<Ebp>> {
+        let Some(range) = Ebp::find(&self.transport_private_data) else {
+            return Ok(None);
+        };
+        track!(Ebp::parse(&self.transport_private_data[range])).map(Some)
+    }
+
+    /// Sets `ebp` to `transport_private_data`, replacing the existing EBP (if any)
+    /// and keeping the other private data structures.
+    ///
+    /// If the private data or the field becomes too large
+    /// (see `AdaptationField::MAX_PRIVATE_DATA_SIZE` and `AdaptationField::MAX_SIZE`),
+    /// it will return an `ErrorKind::InvalidInput` error without modifying the field.
+    pub fn set_ebp(&mut self, ebp: &Ebp) -> Result<()> {
+        let bytes = track!(ebp.to_bytes())?;
+        let mut data = self.transport_private_data.clone();
+        match Ebp::find(&data) {
+            Some(range) => {
+                data.splice(range, bytes);
+            }
+            None => data.extend_from_slice(&bytes),
+        }
+        track_assert!(
+            data.len() <= Self::MAX_PRIVATE_DATA_SIZE,
+            ErrorKind::InvalidInput,
+            "Too large transport private data: {} bytes",
+            data.len()
+        );
+
+        let data = core::mem::replace(&mut self.transport_private_data, data);
+        let size = self.external_size();
+        if size > Self::MAX_SIZE {
+            self.transport_private_data = data;
+            track_panic!(
+                ErrorKind::InvalidInput,
+                "Too large adaptation field: size={}, overflow={}",
+                size,
+                size - Self::MAX_SIZE
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::time::ClockReference;
+    use crate::ts::{Pid, ReadTsPacket, TsPacket, TsPacketReader, TsPacketWriter, WriteTsPacket};
+
+    #[test]
+    fn ebp() {
+        let ebp = Ebp {
+            fragment: true,
+            segment: true,
+            sap_type: Some(1),
+            grouping_ids: vec![1, 0x7F],
+            time: Some(0x0123_4567_89AB_CDEF),
+            concealment: false,
+            ext_partitions: Some(3),
+        };
+        let bytes = track_try_unwrap!(ebp.to_bytes());
+        assert_eq!(
+            bytes,
+            [
+                0xDF, 18, b'E', b'B', b'P', b'0', 0xFB, 0xFF, 0x3F, 0x81, 0x7F, 0x01, 0x23, 0x45,
+                0x67, 0x89, 0xAB, 0xCD, 0xEF, 3
+            ]
+        );
+        assert_eq!(track_try_unwrap!(Ebp::parse(&bytes)), ebp);
+
+        // Minimal EBP with reserved bytes
+        let bytes = [0xDF, 7, b'E', b'B', b'P', b'0', 0x40, 0xFF, 0xFF];
+        let expected = Ebp {
+            segment: true,
+            ..Default::default()
+        };
+        assert_eq!(track_try_unwrap!(Ebp::parse(&bytes)), expected);
+
+        assert!(Ebp::parse(&[0xDF, 5, b'E', b'B', b'P', b'1', 0x40]).is_err());
+        assert!(Ebp::parse(&[0xDF, 5, b'E', b'B', b'P', b'0', 0x20]).is_err());
+        let invalid = Ebp {
+            sap_type: Some(8),
+            ..Default::default()
+        };
+        assert!(invalid.to_bytes().is_err());
+    }
+
+    #[test]
+    fn adaptation_field_ebp() {
+        let mut ebp = Ebp {
+            fragment: true,
+            sap_type: Some(2),
+            ..Default::default()
+        };
+
+        // Other private data structures are kept
+        let mut adaptation_field = AdaptationField::builder()
+            .pcr(ClockReference::new(27_000_000).unwrap())
+            .transport_private_data(&[0xA0, 2, 1, 2])
+            .build()
+            .unwrap();
+        assert_eq!(track_try_unwrap!(adaptation_field.ebp()), None);
+        track_try_unwrap!(adaptation_field.set_ebp(&ebp));
+        ebp.segment = true;
+        track_try_unwrap!(adaptation_field.set_ebp(&ebp));
+        assert_eq!(
+            &adaptation_field.transport_private_data[..4],
+            [0xA0, 2, 1, 2]
+        );
+        assert_eq!(adaptation_field.transport_private_data.len(), 4 + 8);
+
+        let packet = track_try_unwrap!(TsPacket::builder(Pid::new(0x100).unwrap())
+            .adaptation(adaptation_field.clone())
+            .payload_raw(&[0; 100])
+            .build());
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+        let stream = writer.into_stream();
+        let mut reader = TsPacketReader::new(&stream[..]);
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        let read = packet.adaptation_field.unwrap();
+        assert_eq!(track_try_unwrap!(read.ebp()), Some(ebp.clone()));
+
+        // No room for the EBP
+        let mut adaptation_field = AdaptationField {
+            transport_private_data: vec![0xA0, 176],
+            ..Default::default()
+        };
+        adaptation_field.transport_private_data.resize(178, 0);
+        let before = adaptation_field.clone();
+        assert!(adaptation_field.set_ebp(&ebp).is_err());
+        assert_eq!(adaptation_field, before);
+    }
+}
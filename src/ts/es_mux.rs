@@ -0,0 +1,331 @@
+use crate::es::adts::AdtsFrames;
+use crate::es::h264::{NalUnit, NalUnits};
+use crate::es::{StreamId, StreamType};
+use crate::pes::{PesHeader, PesPacket, PesPacketWriter, WritePesPacket};
+use crate::time::Timestamp;
+use crate::ts::{Pid, WriteTsPacket};
+use crate::{ErrorKind, Result};
+use alloc::{vec, vec::Vec};
+use core::time::Duration;
+
+/// Access unit delimiter (`primary_pic_type` = 7) inserted into the access units without one.
+const ACCESS_UNIT_DELIMITER: [u8; 6] = [0, 0, 0, 1, 0x09, 0xF0];
+
+/// Muxer that authors a single-program TS from an H.264 Annex-B byte stream and an ADTS AAC byte stream.
+///
+/// The streams are written by `PesPacketWriter` with the following policy:
+///
+/// - Each H.264 access unit is written as a PES packet (an access unit delimiter is inserted if missing).
+///   The PTSs are computed from the frame counts and the frame rate,
+///   assuming that the pictures are not reordered (i.e., no B-frames).
+///   The access units containing IDR pictures are marked by `random_access_indicator`.
+/// - Each ADTS frame is written as a PES packet.
+///   The PTSs are computed from the sample counts and the sampling frequencies in the ADTS headers.
+/// - The PES packets of both streams are interleaved in the order of the timestamps.
+/// - The PCRs are carried by the video PID (or the audio PID if there is no video).
+/// - The PAT and PMT are repeated at `psi_interval`.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::ts::{EsMuxer, TsPacketWriter};
+///
+/// let h264 = [0, 0, 0, 1, 0x65, 0x88, 0x84, 0x01, 0, 0, 0, 1, 0x41, 0x9A, 0x02];
+/// let mut writer = TsPacketWriter::new(Vec::new());
+/// EsMuxer::new(30, 1).mux(&h264, &[], &mut writer).unwrap();
+/// assert_eq!(writer.into_stream().len(), 4 * 188);
+/// ```
+#[derive(Debug, Clone)]
+pub struct EsMuxer {
+    frame_rate: (u32, u32),
+    start_timestamp: Timestamp,
+    psi_interval: Duration,
+    pcr_delay: Duration,
+}
+impl EsMuxer {
+    /// PID of the video stream.
+    pub const VIDEO_PID: u16 = 0x100;
+
+    /// PID of the audio stream.
+    pub const AUDIO_PID: u16 = 0x101;
+
+    /// Default value of the interval of the PAT and PMT.
+    pub const DEFAULT_PSI_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Makes a new `EsMuxer` instance for the video of the frame rate `frame_rate_num / frame_rate_den`
+    /// (e.g., `30000 / 1001`).
+    pub fn new(frame_rate_num: u32, frame_rate_den: u32) -> Self {
+        EsMuxer {
+            frame_rate: (frame_rate_num, frame_rate_den),
+            start_timestamp: Timestamp::from(90_000),
+            psi_interval: Self::DEFAULT_PSI_INTERVAL,
+            pcr_delay: PesPacketWriter::<()>::DEFAULT_PCR_DELAY,
+        }
+    }
+
+    /// Sets the timestamp of the first video frame and audio frame.
+    ///
+    /// The default value is one second.
+    pub fn set_start_timestamp(&mut self, timestamp: Timestamp) {
+        self.start_timestamp = timestamp;
+    }
+
+    /// Sets the interval of the PAT and PMT.
+    ///
+    /// The default value is `DEFAULT_PSI_INTERVAL`.
+    pub fn set_psi_interval(&mut self, interval: Duration) {
+        self.psi_interval = interval;
+    }
+
+    /// Sets how far the PCRs precede the timestamps of the PES packets (see `PesPacketWriter::set_pcr_delay`).
+    pub fn set_pcr_delay(&mut self, delay: Duration) {
+        self.pcr_delay = delay;
+    }
+
+    /// Muxes `h264` (an Annex-B byte stream) and `adts` (a sequence of ADTS frames), and writes them to `writer`.
+    ///
+    /// Either stream may be empty.
+    ///
+    /// # Errors
+    ///
+    /// If the frame rate is zero, it will return an `ErrorKind::InvalidInput` error.
+    /// The errors in parsing `adts` are also returned.
+    pub fn mux<W: WriteTsPacket>(&self, h264: &[u8], adts: &[u8], writer: W) -> Result<()> {
+        let (num, den) = self.frame_rate;
+        track_assert!(
+            num != 0 && den != 0,
+            ErrorKind::InvalidInput,
+            "Invalid frame rate: {}/{}",
+            num,
+            den
+        );
+
+        let mut writer = PesPacketWriter::new(writer);
+        writer.set_psi_interval(Some(self.psi_interval));
+        writer.set_pcr_delay(self.pcr_delay);
+
+        let video_pid = Pid::new_const(Self::VIDEO_PID);
+        let audio_pid = Pid::new_const(Self::AUDIO_PID);
+        let mut video = access_units(h264).enumerate().map(|(i, (data, idr))| {
+            let ticks = i as u64 * 90_000 * u64::from(den) / u64::from(num);
+            let mut header = PesHeader::new(StreamId::new(0xE0));
+            header.data_alignment_indicator = true;
+            header.pts = Some(self.start_timestamp.wrapping_add(ticks));
+            let mut pes = PesPacket::new(video_pid, header, data);
+            pes.random_access_indicator = idr;
+            pes
+        });
+        let mut audio = AdtsPesPackets {
+            frames: AdtsFrames::new(adts),
+            pid: audio_pid,
+            start_timestamp: self.start_timestamp,
+            samples: 0,
+        };
+
+        let mut next_video = video.next();
+        let mut next_audio = track!(audio.next_pes())?;
+        loop {
+            let use_video = match (&next_video, &next_audio) {
+                (None, None) => break,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (Some(v), Some(a)) => {
+                    let pts = |p: &PesPacket<Vec<u8>>| p.header.pts.expect("Never fails");
+                    pts(a).delta(pts(v)) >= 0
+                }
+            };
+            if use_video {
+                let pes = next_video.take().expect("Never fails");
+                track!(writer.write_pes_packet(video_pid, StreamType::H264, &pes))?;
+                next_video = video.next();
+            } else {
+                let pes = next_audio.take().expect("Never fails");
+                track!(writer.write_pes_packet(audio_pid, StreamType::AdtsAac, &pes))?;
+                next_audio = track!(audio.next_pes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits an H.264 Annex-B byte stream into access units,
+/// and returns their bytes (with an access unit delimiter) and whether they contain IDR pictures.
+fn access_units(bytes: &[u8]) -> impl Iterator<Item = (Vec<u8>, bool)> + '_ {
+    let mut nal_units = NalUnits::new(bytes).peekable();
+    core::iter::from_fn(move || {
+        let mut units = vec![nal_units.next()?];
+        while let Some(nal) = nal_units.peek() {
+            if starts_access_unit(nal, &units) {
+                break;
+            }
+            units.extend(nal_units.next());
+        }
+
+        let mut data = Vec::new();
+        if !units[0].is_access_unit_delimiter() {
+            data.extend_from_slice(&ACCESS_UNIT_DELIMITER);
+        }
+        for nal in &units {
+            data.extend_from_slice(&[0, 0, 0, 1]);
+            data.extend_from_slice(nal.as_bytes());
+        }
+        Some((data, units.iter().any(|nal| nal.is_idr())))
+    })
+}
+
+/// Returns `true` if `nal` starts a new access unit after `units` (ITU-T H.264 7.4.1.2.3).
+fn starts_access_unit(nal: &NalUnit, units: &[NalUnit]) -> bool {
+    if nal.is_access_unit_delimiter() {
+        return true;
+    }
+    if !units.iter().any(|u| u.nal_unit_type().is_slice()) {
+        return false;
+    }
+    match nal.nal_unit_type().as_u8() {
+        // SEI, SPS, PPS and the reserved types that precede the first slice of a picture
+        6..=8 | 14..=18 => true,
+        // `first_mb_in_slice` is zero
+        1 | 2 | 5 => nal.payload().first().is_some_and(|b| b & 0x80 != 0),
+        _ => false,
+    }
+}
+
+struct AdtsPesPackets<'a> {
+    frames: AdtsFrames<'a>,
+    pid: Pid,
+    start_timestamp: Timestamp,
+    samples: u64,
+}
+impl AdtsPesPackets<'_> {
+    fn next_pes(&mut self) -> Result<Option<PesPacket<Vec<u8>>>> {
+        let bytes = self.frames.remaining();
+        let Some(frame) = track!(self.frames.next().transpose())? else {
+            return Ok(None);
+        };
+        let bytes = &bytes[..bytes.len() - self.frames.remaining().len()];
+        let sampling_frequency = track_assert_some!(
+            frame.header.sampling_frequency(),
+            ErrorKind::InvalidInput,
+            "Unknown sampling frequency: index={}",
+            frame.header.sampling_frequency_index
+        );
+
+        let ticks = self.samples * 90_000 / u64::from(sampling_frequency);
+        self.samples += frame.header.samples() as u64;
+        let mut header = PesHeader::new(StreamId::new(0xC0));
+        header.data_alignment_indicator = true;
+        header.pts = Some(self.start_timestamp.wrapping_add(ticks));
+        Ok(Some(PesPacket::new(self.pid, header, bytes.to_vec())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::adts::AdtsHeader;
+    use crate::pes::{PesPacketReader, ReadPesPacket};
+    use crate::ts::{ReadTsPacket, TsPacketReader, TsPacketWriter, TsPayload};
+
+    fn adts_frame(fill: u8) -> Vec<u8> {
+        let header = AdtsHeader {
+            mpeg2: false,
+            profile: 1,
+            sampling_frequency_index: 3, // 48 kHz
+            private_bit: false,
+            channel_configuration: 2,
+            original_or_copy: false,
+            home: false,
+            copyright_identification_bit: false,
+            copyright_identification_start: false,
+            frame_length: 7 + 5,
+            buffer_fullness: 0x7FF,
+            number_of_raw_data_blocks: 0,
+            crc: None,
+        };
+        let mut bytes = Vec::new();
+        track_try_unwrap!(header.write_to(&mut bytes));
+        bytes.extend([fill; 5]);
+        bytes
+    }
+
+    #[test]
+    fn mux_works() {
+        let h264 = [
+            &[0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1E][..], // SPS
+            &[0, 0, 1, 0x68, 0xCE, 0x38, 0x80],        // PPS
+            &[0, 0, 1, 0x65, 0x88, 0x84, 0x01],        // IDR slice
+            &[0, 0, 1, 0x65, 0x41, 0x21, 0x01],        // IDR slice (first_mb_in_slice != 0)
+            &[0, 0, 0, 1, 0x09, 0xF0],                 // AUD
+            &[0, 0, 1, 0x41, 0x9A, 0x02],              // Non-IDR slice
+            &[0, 0, 1, 0x41, 0x9A, 0x01],              // Non-IDR slice
+        ]
+        .concat();
+        let adts = (1..=4).flat_map(adts_frame).collect::<Vec<_>>();
+
+        let mut muxer = EsMuxer::new(30, 1);
+        muxer.set_psi_interval(Duration::from_millis(50));
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(muxer.mux(&h264, &adts, &mut writer));
+        let stream = writer.into_stream();
+
+        let mut reader = TsPacketReader::new(&stream[..]);
+        let mut pats = 0;
+        while let Some(packet) = track_try_unwrap!(reader.read_ts_packet()) {
+            if let Some(TsPayload::Pat(_)) = packet.payload {
+                pats += 1;
+            }
+        }
+        // The first video and audio frames, and the audio frame at 95_760 (>= 90_000 + 4_500)
+        assert_eq!(pats, 3);
+
+        let mut video = Vec::new();
+        let mut audio = Vec::new();
+        let mut reader = PesPacketReader::new(TsPacketReader::new(&stream[..]));
+        while let Some(pes) = track_try_unwrap!(reader.read_pes_packet()) {
+            let pts = pes.header.pts.unwrap().as_u64();
+            match pes.header.stream_id.as_u8() {
+                0xE0 => video.push((pts, pes.random_access_indicator, pes.data)),
+                _ => audio.push((pts, pes.data)),
+            }
+        }
+
+        let aud = [0, 0, 0, 1, 0x09, 0xF0];
+        assert_eq!(
+            video,
+            [
+                (
+                    90_000,
+                    true,
+                    [
+                        &aud[..],
+                        &[0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1E],
+                        &[0, 0, 0, 1, 0x68, 0xCE, 0x38, 0x80],
+                        &[0, 0, 0, 1, 0x65, 0x88, 0x84, 0x01],
+                        &[0, 0, 0, 1, 0x65, 0x41, 0x21, 0x01],
+                    ]
+                    .concat()
+                ),
+                (
+                    93_000,
+                    false,
+                    [&aud[..], &[0, 0, 0, 1, 0x41, 0x9A, 0x02]].concat()
+                ),
+                (
+                    96_000,
+                    false,
+                    [&aud[..], &[0, 0, 0, 1, 0x41, 0x9A, 0x01]].concat()
+                ),
+            ]
+        );
+        assert_eq!(
+            audio,
+            (1..=4)
+                .map(|i| (90_000 + (i - 1) * 1920, adts_frame(i as u8)))
+                .collect::<Vec<_>>()
+        );
+
+        assert!(EsMuxer::new(0, 1)
+            .mux(&h264, &adts, TsPacketWriter::new(Vec::new()))
+            .is_err());
+    }
+}
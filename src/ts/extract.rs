@@ -0,0 +1,309 @@
+use crate::ts::payload::Pmt;
+use crate::ts::{
+    ContinuityCounter, Pid, PidTable, ReadTsPacket, TransportScramblingControl, TsHeader, TsPacket,
+    TsPayload,
+};
+use crate::Result;
+
+/// TS packet reader that extracts a single program from a multi-program transport stream (MPTS).
+///
+/// Only the packets of the PAT, the PMT, the elementary streams and the PCR PID of the program are kept.
+/// The PCR PID is kept even if it does not carry any elementary stream.
+///
+/// The PAT is rewritten to contain only the program (or nothing, if the program is not listed),
+/// and the PMTs of the other programs sharing the PMT PID are dropped.
+/// The PAT and PMT are emitted as newly built packets (one per received table), so
+/// their continuity counters are renumbered, and
+/// the rewritten tables must fit in a packet.
+/// The packets of the elementary streams and the PCR PID are passed through as they are.
+///
+/// The packets preceding the PMT of the program are dropped, except for the PAT.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::ts::{ProgramExtractor, ReadTsPacket, TsPacketReader, TsPacketWriter, WriteTsPacket};
+///
+/// let mut extractor = ProgramExtractor::new(TsPacketReader::new(mpeg2ts::io::empty()), 1);
+/// let mut writer = TsPacketWriter::new(Vec::new());
+/// while let Some(packet) = extractor.read_ts_packet().unwrap() {
+///     writer.write_ts_packet(&packet).unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ProgramExtractor<R> {
+    inner: R,
+    program_num: u16,
+    pmt_pid: Option<Pid>,
+    pmt: Option<Pmt>,
+    pids: PidTable<()>,
+    pat_counter: ContinuityCounter,
+    pmt_counter: ContinuityCounter,
+}
+impl<R: ReadTsPacket> ProgramExtractor<R> {
+    /// Makes a new `ProgramExtractor` instance that extracts the program numbered `program_num`.
+    pub fn new(inner: R, program_num: u16) -> Self {
+        ProgramExtractor {
+            inner,
+            program_num,
+            pmt_pid: None,
+            pmt: None,
+            pids: PidTable::new(),
+            pat_counter: ContinuityCounter::new(),
+            pmt_counter: ContinuityCounter::new(),
+        }
+    }
+}
+impl<R> ProgramExtractor<R> {
+    /// Returns the number of the program to be extracted.
+    pub fn program_num(&self) -> u16 {
+        self.program_num
+    }
+
+    /// Returns the PMT PID of the program (if the PAT listing it has been received).
+    pub fn pmt_pid(&self) -> Option<Pid> {
+        self.pmt_pid
+    }
+
+    /// Returns the current PMT of the program (if received).
+    pub fn pmt(&self) -> Option<&Pmt> {
+        self.pmt.as_ref()
+    }
+
+    /// Returns a reference to the underlaying TS packet reader.
+    pub fn ts_packet_reader(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlaying TS packet reader.
+    pub fn ts_packet_reader_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Takes ownership of `self`, and returns the underlaying TS packet reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn handle_ts_packet(&mut self, packet: TsPacket) -> Option<TsPacket> {
+        let pid = packet.header.pid;
+        match packet.payload {
+            Some(TsPayload::Pat(mut pat)) => {
+                pat.table.retain(|pa| pa.program_num == self.program_num);
+                pat.raw = None;
+                if pat.is_current {
+                    let pmt_pid = pat.table.first().map(|pa| pa.program_map_pid);
+                    if pmt_pid != self.pmt_pid {
+                        self.pmt_pid = pmt_pid;
+                        self.pmt = None;
+                        self.pids = PidTable::new();
+                    }
+                }
+                let counter = next_counter(&mut self.pat_counter);
+                Some(psi_packet(
+                    pid,
+                    counter,
+                    TsPayload::Pat(pat),
+                    packet.arrival_timestamp,
+                ))
+            }
+            Some(TsPayload::Pmt(pmt)) if Some(pid) == self.pmt_pid => {
+                if pmt.program_num != self.program_num {
+                    return None;
+                }
+                if pmt.is_current {
+                    self.pids = PidTable::new();
+                    for es in &pmt.es_info {
+                        self.pids.insert(es.elementary_pid, ());
+                    }
+                    if let Some(pcr_pid) = pmt.pcr_pid {
+                        self.pids.insert(pcr_pid, ());
+                    }
+                    self.pmt = Some(pmt.clone());
+                }
+                let counter = next_counter(&mut self.pmt_counter);
+                Some(psi_packet(
+                    pid,
+                    counter,
+                    TsPayload::Pmt(pmt),
+                    packet.arrival_timestamp,
+                ))
+            }
+            _ if pid.as_u16() == Pid::PAT || Some(pid) == self.pmt_pid => {
+                // Fragments of the tables (the rewritten tables are emitted in the packets completing them)
+                None
+            }
+            _ if self.pids.contains(pid) => Some(packet),
+            _ => None,
+        }
+    }
+}
+impl<R: ReadTsPacket> ReadTsPacket for ProgramExtractor<R> {
+    fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+        while let Some(packet) = track!(self.inner.read_ts_packet())? {
+            if let Some(packet) = self.handle_ts_packet(packet) {
+                return Ok(Some(packet));
+            }
+        }
+        Ok(None)
+    }
+
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        self.inner.last_packet_byte_offset()
+    }
+}
+
+pub(super) fn next_counter(counter: &mut ContinuityCounter) -> ContinuityCounter {
+    let current = *counter;
+    counter.increment();
+    current
+}
+
+pub(super) fn psi_packet(
+    pid: Pid,
+    counter: ContinuityCounter,
+    payload: TsPayload,
+    arrival_timestamp: Option<u32>,
+) -> TsPacket {
+    TsPacket {
+        header: TsHeader {
+            transport_error_indicator: false,
+            payload_unit_start_indicator: true,
+            transport_priority: false,
+            pid,
+            transport_scrambling_control: TransportScramblingControl::NotScrambled,
+            continuity_counter: counter,
+        },
+        adaptation_field: None,
+        payload: Some(payload),
+        arrival_timestamp,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::StreamType;
+    use crate::time::ClockReference;
+    use crate::ts::payload::{Null, Section};
+    use crate::ts::test_util::{self, pid};
+    use crate::ts::{
+        AdaptationField, ContinuityChecker, TsPacketReader, TsPacketWriter, WriteTsPacket,
+    };
+
+    fn mpts() -> Vec<u8> {
+        let pat = TsPayload::Pat(test_util::pat(&[(0, 0x10), (1, 0x1000), (2, 0x1001)]));
+        let pmt = |program_num, pcr_pid, es_pid, stream_type| {
+            TsPayload::Pmt(test_util::pmt(
+                program_num,
+                Some(pcr_pid),
+                &[(es_pid, stream_type)],
+            ))
+        };
+        let pes = |n| TsPayload::Pes(test_util::pes(0xE0, None, None, &[n; 10]));
+        let pcr = |n| Some(AdaptationField::with_pcr(ClockReference::new(n).unwrap()));
+        let packets: Vec<(u16, Option<AdaptationField>, Option<TsPayload>)> = vec![
+            (0, None, Some(pat.clone())),
+            (
+                0x10,
+                None,
+                Some(TsPayload::Section(Section::new(0, &[0x40]).unwrap())),
+            ),
+            // Program 1 has its PCR on the video PID
+            (0x1000, None, Some(pmt(1, 256, 256, StreamType::H264))),
+            // Program 2 has a standalone PCR PID
+            (0x1001, None, Some(pmt(2, 0x1FF0, 257, StreamType::AdtsAac))),
+            (256, pcr(1000), Some(pes(1))),
+            (257, None, Some(pes(2))),
+            (0x1FF0, pcr(2000), None),
+            (0x1FFF, None, Some(TsPayload::Null(Null))),
+            (0, None, Some(pat)),
+            (256, None, Some(pes(3))),
+            (257, None, Some(pes(4))),
+        ];
+
+        // The CCs of the input are shifted, so that the ones of the rewritten tables differ
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packets(&test_util::packets(packets, 5)));
+        writer.into_stream()
+    }
+
+    fn extract(stream: &[u8], program_num: u16) -> Vec<TsPacket> {
+        let mut extractor = ProgramExtractor::new(TsPacketReader::new(stream), program_num);
+        let mut packets = Vec::new();
+        while let Some(packet) = track_try_unwrap!(extractor.read_ts_packet()) {
+            packets.push(packet);
+        }
+
+        // The output can be read again, and the CCs are continuous
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packets(&packets));
+        let stream = writer.into_stream();
+        let mut reader = TsPacketReader::new(&stream[..]);
+        let mut checker = ContinuityChecker::new();
+        let mut reread = Vec::new();
+        while let Some(packet) = track_try_unwrap!(reader.read_ts_packet()) {
+            assert!(!checker.check(&packet).is_discontinuous());
+            reread.push(packet);
+        }
+        assert_eq!(reread, packets);
+        packets
+    }
+
+    #[test]
+    fn extract_program_with_shared_pcr_pid() {
+        let packets = extract(&mpts(), 1);
+        let pids = packets
+            .iter()
+            .map(|p| p.header.pid.as_u16())
+            .collect::<Vec<_>>();
+        assert_eq!(pids, [0, 0x1000, 256, 0, 256]);
+
+        let pat = packets[0].payload.as_ref().unwrap().as_pat().unwrap();
+        assert_eq!(pat.table.len(), 1);
+        assert_eq!(pat.table[0].program_num, 1);
+        assert_eq!(pat.table[0].program_map_pid, pid(0x1000));
+        assert_eq!(packets[0].header.continuity_counter.as_u8(), 0);
+        assert_eq!(packets[3].header.continuity_counter.as_u8(), 1);
+        assert_eq!(packets[1].header.continuity_counter.as_u8(), 0);
+        assert!(packets[2].adaptation_field.as_ref().unwrap().pcr.is_some());
+    }
+
+    #[test]
+    fn extract_program_with_standalone_pcr_pid() {
+        let packets = extract(&mpts(), 2);
+        let pids = packets
+            .iter()
+            .map(|p| p.header.pid.as_u16())
+            .collect::<Vec<_>>();
+        assert_eq!(pids, [0, 0x1001, 257, 0x1FF0, 0, 257]);
+
+        let pat = packets[0].payload.as_ref().unwrap().as_pat().unwrap();
+        assert_eq!(pat.table.len(), 1);
+        assert_eq!(pat.table[0].program_num, 2);
+        assert_eq!(
+            packets[1]
+                .payload
+                .as_ref()
+                .unwrap()
+                .as_pmt()
+                .unwrap()
+                .program_num,
+            2
+        );
+    }
+
+    #[test]
+    fn extract_unknown_program() {
+        let packets = extract(&mpts(), 3);
+        assert_eq!(packets.len(), 2);
+        assert!(packets.iter().all(|p| p
+            .payload
+            .as_ref()
+            .unwrap()
+            .as_pat()
+            .unwrap()
+            .table
+            .is_empty()));
+    }
+}
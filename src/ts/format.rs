@@ -0,0 +1,67 @@
+use crate::ts::TsPacket;
+
+/// Size of the largest packet format (`PacketFormat::ReedSolomon`).
+pub(super) const MAX_PACKET_SIZE: usize = 204;
+
+/// Framing of TS packets in a byte stream.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PacketFormat {
+    /// Plain 188-byte packets (the default).
+    #[default]
+    Ts,
+
+    /// 192-byte packets used by Blu-ray and AVCHD (a.k.a. M2TS).
+    ///
+    /// Each packet is prefixed by a 4-byte header that contains an arrival timestamp
+    /// (see `TsPacket::arrival_timestamp`).
+    M2ts,
+
+    /// 192-byte packets, each of which is followed by a 4-byte timestamp.
+    ///
+    /// The timestamp is handled in the same way as the prefix of `PacketFormat::M2ts`.
+    TrailingTimestamp,
+
+    /// 204-byte packets used by DVB before FEC stripping.
+    ///
+    /// Each packet is followed by 16 Reed-Solomon parity bytes.
+    /// The parity bytes are discarded on reading, and written as zeros on writing.
+    ReedSolomon,
+}
+impl PacketFormat {
+    /// The formats that `TsPacketReader::with_detected_packet_format` tries in order.
+    pub(super) const CANDIDATES: [PacketFormat; 4] = [
+        PacketFormat::Ts,
+        PacketFormat::M2ts,
+        PacketFormat::TrailingTimestamp,
+        PacketFormat::ReedSolomon,
+    ];
+
+    /// Returns the size of a packet in this format in bytes.
+    pub fn packet_size(self) -> usize {
+        self.prefix_size() + TsPacket::SIZE + self.suffix_size()
+    }
+
+    /// Returns the size of the bytes preceding the sync byte of each packet.
+    pub(super) fn prefix_size(self) -> usize {
+        match self {
+            PacketFormat::Ts | PacketFormat::TrailingTimestamp | PacketFormat::ReedSolomon => 0,
+            PacketFormat::M2ts => 4,
+        }
+    }
+
+    /// Returns the size of the bytes following each packet.
+    pub(super) fn suffix_size(self) -> usize {
+        match self {
+            PacketFormat::Ts | PacketFormat::M2ts => 0,
+            PacketFormat::TrailingTimestamp => 4,
+            PacketFormat::ReedSolomon => 16,
+        }
+    }
+
+    /// Returns `true` if the bytes in `buf` are consistent with this format,
+    /// i.e., all the packets contained in `buf` start with the sync byte.
+    pub(super) fn matches(self, buf: &[u8]) -> bool {
+        let offsets = (self.prefix_size()..buf.len()).step_by(self.packet_size());
+        offsets.len() > 0 && offsets.map(|i| buf[i]).all(|b| b == TsPacket::SYNC_BYTE)
+    }
+}
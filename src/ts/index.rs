@@ -0,0 +1,61 @@
+use crate::io::Read;
+use crate::time::Timestamp;
+use crate::ts::{Pid, ReadTsPacket, TsPacketReader, TsPayload};
+use crate::{ErrorKind, Result};
+use alloc::vec::Vec;
+
+/// Entry of a random access index built by `build_index`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IndexEntry {
+    /// Byte offset of the TS packet that starts the PES packet (see `TsPacketReader::byte_offset`).
+    pub byte_offset: u64,
+
+    /// Index (zero origin) of the TS packet that starts the PES packet (see `TsPacketReader::packet_index`).
+    pub packet_index: u64,
+
+    /// PTS of the PES packet.
+    ///
+    /// This is `None` if the PES packet has no PTS or its header is scrambled.
+    pub pts: Option<Timestamp>,
+
+    /// Random access indicator in the adaptation field of the TS packet.
+    pub random_access_indicator: bool,
+}
+
+/// Scans the rest of the stream, and returns the index entries of the PES packets on `pid`.
+///
+/// Only the TS packet headers, adaptation fields, and PES headers are examined,
+/// so the PES packets are not assembled.
+/// The byte offsets and packet indexes are the positions tracked by `reader`,
+/// so they take into account the packets read before the call, the bytes skipped by resynchronization,
+/// and the packets dropped by the reader (e.g., by `TransportErrorPolicy::Drop`).
+///
+/// This takes `TsPacketReader` rather than any `ReadTsPacket` implementation,
+/// because the packet indexes are only tracked by `TsPacketReader` (see `TsPacketReader::packet_index`).
+pub fn build_index<R: Read>(reader: &mut TsPacketReader<R>, pid: Pid) -> Result<Vec<IndexEntry>> {
+    let mut entries = Vec::new();
+    while let Some(packet) = track!(reader.read_ts_packet())? {
+        if packet.header.pid == pid && packet.header.payload_unit_start_indicator {
+            let pts = match packet.payload {
+                Some(TsPayload::Pes(ref pes)) => pes.header.pts,
+                _ => None,
+            };
+            let random_access_indicator = packet
+                .adaptation_field
+                .as_ref()
+                .is_some_and(|a| a.random_access_indicator);
+            let byte_offset = track_assert_some!(
+                reader.last_packet_byte_offset(),
+                ErrorKind::Other,
+                "No byte offset of the last packet"
+            );
+            entries.push(IndexEntry {
+                byte_offset,
+                packet_index: reader.packet_index() - 1,
+                pts,
+                random_access_indicator,
+            });
+        }
+    }
+    Ok(entries)
+}
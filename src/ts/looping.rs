@@ -0,0 +1,400 @@
+use crate::io::{self, Read, Seek};
+use crate::time::{ClockReference, Timestamp};
+use crate::ts::{
+    ContinuityCounter, Pid, PidTable, ReadTsPacket, TimestampShifter, TsPacket, TsPacketReader,
+    TsPayload,
+};
+use crate::Result;
+
+/// TS packet reader that repeats a stream endlessly with continuously increasing timestamps.
+///
+/// On every wrap, the PCRs, OPCRs and the PTS, DTS and ESCR in the PES headers are offset by
+/// the accumulated duration of the previous iterations (wrapping around at 33 bits),
+/// and the continuity counters are continued from the previous iteration.
+/// The other parts of the packets (including the PSI) are left intact.
+///
+/// The duration of an iteration is measured in the first iteration as
+/// the span of the PCRs on the first PID carrying them plus the last PCR interval.
+/// If the stream has no PCRs, the DTSs (or PTSs) of the first PID carrying them are used instead.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::ts::{LoopingReader, ReadTsPacket};
+///
+/// // An empty stream ends immediately instead of looping
+/// let mut reader = LoopingReader::from_packets(Vec::new());
+/// assert!(reader.read_ts_packet().unwrap().is_none());
+/// ```
+#[derive(Debug)]
+pub struct LoopingReader<R = io::Empty> {
+    source: Source<R>,
+    iterations: u64,
+    packets_in_iteration: u64,
+    duration: IterationDuration,
+
+    // Offset of the current iteration (in 27 MHz ticks, modulo `ClockReference::MODULUS`)
+    offset: u64,
+    shifter: TimestampShifter,
+
+    // Continuity counters of the last returned packets, and the values added to the ones of the source
+    counters: PidTable<ContinuityCounter>,
+    counter_shifts: PidTable<u8>,
+}
+impl<R: Read + Seek> LoopingReader<R> {
+    /// Makes a new `LoopingReader` instance that repeats the rest of the stream read by `reader`.
+    ///
+    /// The current position of `reader` is the start of each iteration.
+    pub fn new(mut reader: TsPacketReader<R>) -> Result<Self> {
+        let start = track!(reader.position())?;
+        Ok(Self::with_source(Source::Reader {
+            reader: Box::new(reader),
+            start,
+        }))
+    }
+}
+impl LoopingReader {
+    /// Makes a new `LoopingReader` instance that repeats `packets`.
+    pub fn from_packets(packets: Vec<TsPacket>) -> Self {
+        Self::with_source(Source::Packets { packets, index: 0 })
+    }
+}
+impl<R> LoopingReader<R> {
+    fn with_source(source: Source<R>) -> Self {
+        LoopingReader {
+            source,
+            iterations: 0,
+            packets_in_iteration: 0,
+            duration: IterationDuration::default(),
+            offset: 0,
+            shifter: TimestampShifter::new(0),
+            counters: PidTable::new(),
+            counter_shifts: PidTable::new(),
+        }
+    }
+
+    /// Returns the number of the completed iterations.
+    pub fn iterations(&self) -> u64 {
+        self.iterations
+    }
+
+    /// Returns the duration of an iteration in 27 MHz ticks.
+    ///
+    /// This is `None` until the first iteration is completed.
+    pub fn iteration_duration(&self) -> Option<u64> {
+        (self.iterations > 0).then(|| self.duration.ticks())
+    }
+
+    /// Returns the offset (in 27 MHz ticks) added to the clock references of the current iteration.
+    ///
+    /// The value wraps around at `ClockReference::MODULUS`.
+    /// The offset of the PTS and DTS is this value divided by 300.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn start_next_iteration(&mut self) {
+        self.iterations += 1;
+        self.packets_in_iteration = 0;
+        let duration = self.duration.ticks() % ClockReference::MODULUS;
+        self.offset = (self.offset + duration) % ClockReference::MODULUS;
+        self.shifter = TimestampShifter::new((self.offset / 300) as i64);
+        self.counter_shifts.clear();
+    }
+
+    fn transform(&mut self, mut packet: TsPacket) -> TsPacket {
+        if self.iterations == 0 {
+            self.duration.observe(&packet);
+        } else {
+            self.shifter.shift_packet(&mut packet);
+            if let Some(a) = &mut packet.adaptation_field {
+                a.pcr = a.pcr.map(|pcr| pcr.wrapping_add(self.offset));
+                a.opcr = a.opcr.map(|opcr| opcr.wrapping_add(self.offset));
+            }
+
+            let pid = packet.header.pid;
+            let cc = packet.header.continuity_counter.as_u8();
+            let shift = if let Some(&shift) = self.counter_shifts.get(pid) {
+                shift
+            } else {
+                let has_payload = packet.payload.is_some();
+                let shift = self.counters.get(pid).map_or(0, |last| {
+                    (last.as_u8() + u8::from(has_payload)).wrapping_sub(cc) & ContinuityCounter::MAX
+                });
+                self.counter_shifts.insert(pid, shift);
+                shift
+            };
+            packet.header.continuity_counter =
+                ContinuityCounter::from_u8((cc + shift) & ContinuityCounter::MAX)
+                    .expect("Never fails");
+        }
+        self.counters
+            .insert(packet.header.pid, packet.header.continuity_counter);
+        packet
+    }
+}
+impl<R: Read + Seek> ReadTsPacket for LoopingReader<R> {
+    fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+        loop {
+            if let Some(packet) = track!(self.source.read_ts_packet())? {
+                self.packets_in_iteration += 1;
+                return Ok(Some(self.transform(packet)));
+            }
+            if self.packets_in_iteration == 0 {
+                // The stream is empty
+                return Ok(None);
+            }
+            track!(self.source.rewind())?;
+            self.start_next_iteration();
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Source<R> {
+    Reader {
+        reader: Box<TsPacketReader<R>>,
+        start: u64,
+    },
+    Packets {
+        packets: Vec<TsPacket>,
+        index: usize,
+    },
+}
+impl<R: Read + Seek> Source<R> {
+    fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+        match self {
+            Source::Reader { reader, .. } => track!(reader.read_ts_packet()),
+            Source::Packets { packets, index } => {
+                let packet = packets.get(*index).cloned();
+                *index += 1;
+                Ok(packet)
+            }
+        }
+    }
+
+    fn rewind(&mut self) -> Result<()> {
+        match self {
+            Source::Reader { reader, start } => track!(reader.seek_to_byte(*start, true)),
+            Source::Packets { index, .. } => {
+                *index = 0;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Duration of an iteration measured from the PCRs (or PTS/DTS if there are no PCRs).
+#[derive(Debug, Default)]
+struct IterationDuration {
+    pcrs: Option<(Pid, Span<ClockReference>)>,
+    timestamps: Option<(Pid, Span<Timestamp>)>,
+}
+impl IterationDuration {
+    fn observe(&mut self, packet: &TsPacket) {
+        let pid = packet.header.pid;
+        if let Some(pcr) = packet.adaptation_field.as_ref().and_then(|a| a.pcr) {
+            Span::observe_on(&mut self.pcrs, pid, pcr, |x, y| x.wrapping_sub(y));
+        }
+        if let Some(TsPayload::Pes(pes)) = &packet.payload {
+            if let Some(dts) = pes.header.dts.or(pes.header.pts) {
+                Span::observe_on(&mut self.timestamps, pid, dts, |x, y| x.wrapping_sub(y));
+            }
+        }
+    }
+
+    /// Returns the duration in 27 MHz ticks.
+    fn ticks(&self) -> u64 {
+        match (&self.pcrs, &self.timestamps) {
+            (Some((_, span)), _) => span.ticks(),
+            (None, Some((_, span))) => span.ticks() * 300,
+            (None, None) => 0,
+        }
+    }
+}
+
+/// Span of the values on a PID.
+#[derive(Debug)]
+struct Span<T> {
+    last: Option<T>,
+    elapsed: u64,
+    interval: u64,
+}
+impl<T> Default for Span<T> {
+    fn default() -> Self {
+        Span {
+            last: None,
+            elapsed: 0,
+            interval: 0,
+        }
+    }
+}
+impl<T: Copy> Span<T> {
+    /// Observes `value` if `pid` is the first PID observed by `span`.
+    fn observe_on<F>(span: &mut Option<(Pid, Self)>, pid: Pid, value: T, wrapping_sub: F)
+    where
+        F: Fn(T, T) -> u64,
+    {
+        let (span_pid, span) = span.get_or_insert_with(|| (pid, Span::default()));
+        if *span_pid == pid {
+            span.observe(value, wrapping_sub);
+        }
+    }
+
+    fn observe<F>(&mut self, value: T, wrapping_sub: F)
+    where
+        F: Fn(T, T) -> u64,
+    {
+        if let Some(last) = self.last {
+            self.interval = wrapping_sub(value, last);
+            self.elapsed += self.interval;
+        }
+        self.last = Some(value);
+    }
+
+    fn ticks(&self) -> u64 {
+        self.elapsed + self.interval
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::StreamId;
+    use crate::pes::PesHeader;
+    use crate::ts::payload::{Bytes, Pat, Pes};
+    use crate::ts::{
+        AdaptationField, ContinuityChecker, ProgramAssociation, TransportScramblingControl,
+        TsHeader, TsPacketWriter, VersionNumber, WriteTsPacket,
+    };
+    use std::io::Cursor;
+
+    const HOUR: u64 = 3600 * 90_000;
+
+    fn packet(pid: u16, counter: u8, payload: TsPayload) -> TsPacket {
+        TsPacket {
+            header: TsHeader {
+                transport_error_indicator: false,
+                payload_unit_start_indicator: false,
+                transport_priority: false,
+                pid: Pid::new(pid).unwrap(),
+                transport_scrambling_control: TransportScramblingControl::NotScrambled,
+                continuity_counter: ContinuityCounter::from_u8(counter).unwrap(),
+            },
+            adaptation_field: None,
+            payload: Some(payload),
+            arrival_timestamp: None,
+        }
+    }
+
+    /// Makes a stream of a PAT and three video packets carrying PCRs one hour apart.
+    fn packets() -> Vec<TsPacket> {
+        let pat = TsPayload::Pat(Pat {
+            transport_stream_id: 1,
+            version_number: VersionNumber::new(),
+            is_current: true,
+            table: vec![ProgramAssociation {
+                program_num: 1,
+                program_map_pid: Pid::new(0x1000).unwrap(),
+            }],
+            raw: None,
+        });
+        let mut packets = vec![packet(0, 5, pat)];
+        for i in 0..3 {
+            let mut header = PesHeader::new(StreamId::new(0xE0));
+            header.pts = Some(Timestamp::new(i * HOUR + 9000).unwrap());
+            let pes = TsPayload::Pes(Pes {
+                header,
+                pes_packet_len: 0,
+                data: Bytes::new(&[0; 100]).unwrap(),
+            });
+            let mut packet = packet(256, (14 + i as u8) & ContinuityCounter::MAX, pes);
+            let pcr = ClockReference::new(i * HOUR * 300).unwrap();
+            packet.adaptation_field = Some(AdaptationField::with_pcr(pcr));
+            packets.push(packet);
+        }
+        packets
+    }
+
+    fn pcr(packet: &TsPacket) -> Option<u64> {
+        let a = packet.adaptation_field.as_ref()?;
+        a.pcr.map(|pcr| pcr.as_u64())
+    }
+
+    fn pts(packet: &TsPacket) -> Option<u64> {
+        if let Some(TsPayload::Pes(pes)) = &packet.payload {
+            pes.header.pts.map(|pts| pts.as_u64())
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn loop_packets() {
+        let source = packets();
+        let mut reader = LoopingReader::from_packets(source.clone());
+        assert_eq!(reader.iteration_duration(), None);
+
+        // Loops for 36 hours, beyond the 33-bit wrap around (~26.5 hours)
+        let duration = 3 * HOUR * 300;
+        let mut checker = ContinuityChecker::new();
+        let mut last_pcr = None;
+        for k in 0..12 {
+            for original in &source {
+                let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+                assert!(!checker.check(&packet).is_discontinuous());
+
+                // PSI is left intact
+                if packet.header.pid.as_u16() == Pid::PAT {
+                    assert_eq!(packet.payload, original.payload);
+                }
+
+                let expected_pcr =
+                    pcr(original).map(|x| (k * duration + x) % ClockReference::MODULUS);
+                assert_eq!(pcr(&packet), expected_pcr);
+                if let Some(pcr) = expected_pcr {
+                    if let Some(last) = last_pcr {
+                        let delta = ClockReference::new(pcr)
+                            .unwrap()
+                            .wrapping_sub(ClockReference::new(last).unwrap());
+                        assert_eq!(delta, HOUR * 300);
+                    }
+                    last_pcr = Some(pcr);
+                }
+
+                let expected_pts =
+                    pts(original).map(|x| (k * duration / 300 + x) % Timestamp::MODULUS);
+                assert_eq!(pts(&packet), expected_pts);
+            }
+        }
+        assert_eq!(reader.iterations(), 11);
+        assert_eq!(reader.iteration_duration(), Some(duration));
+        assert_eq!(reader.offset(), 11 * duration % ClockReference::MODULUS);
+    }
+
+    #[test]
+    fn loop_reader() {
+        let mut writer = TsPacketWriter::new(Vec::new());
+        for packet in packets() {
+            track_try_unwrap!(writer.write_ts_packet(&packet));
+        }
+        let mut reader = TsPacketReader::new(Cursor::new(writer.into_stream()));
+
+        // The PAT is not repeated
+        let pat = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(pat.header.pid.as_u16(), Pid::PAT);
+        let mut reader = track_try_unwrap!(LoopingReader::new(reader));
+
+        let mut checker = ContinuityChecker::new();
+        let mut pcrs = Vec::new();
+        for _ in 0..9 {
+            let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+            assert_eq!(packet.header.pid.as_u16(), 256);
+            assert!(!checker.check(&packet).is_discontinuous());
+            pcrs.extend(pcr(&packet));
+        }
+        let expected = (0..9).map(|i| i * HOUR * 300).collect::<Vec<_>>();
+        assert_eq!(pcrs, expected);
+        assert_eq!(reader.iterations(), 2);
+    }
+}
@@ -0,0 +1,515 @@
+use crate::time::ClockReference;
+use crate::ts::extract::{next_counter, psi_packet};
+use crate::ts::payload::{Pat, Pmt};
+use crate::ts::{
+    ContinuityCounter, Pid, PidTable, ProgramAssociation, ReadTsPacket, TsPacket, TsPayload,
+    VersionNumber,
+};
+use crate::{ErrorKind, Result};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// Assignment of the program number and PIDs of an input of `ProgramMerger`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramAssignment {
+    /// Index of the input.
+    pub input: usize,
+
+    /// Program number in the output.
+    pub program_num: u16,
+
+    /// PMT PID in the output.
+    pub pmt_pid: Pid,
+
+    /// Map from the PIDs in the input to the ones in the output.
+    ///
+    /// This contains the PMT PID, the PCR PID and the elementary stream PIDs.
+    pub pids: BTreeMap<Pid, Pid>,
+}
+
+/// TS packet reader that merges single-program transport streams (SPTSs) into a multi-program transport stream (MPTS).
+///
+/// The program of each input is added to the combined PAT. The program number and PIDs of the input are
+/// kept if they have not been assigned to the other inputs yet, and are reassigned otherwise
+/// (the new PIDs are allocated from `0x1FFA` downwards, so that they are unlikely to collide with
+/// the PIDs of the inputs that have not been assigned yet).
+/// The assignments are available via `assignment` once the PAT and PMT of the input have been received.
+///
+/// The combined PAT is emitted whenever a PAT is received from one of the inputs, and
+/// the PMTs are rewritten to use the assigned program numbers and PIDs.
+/// As with `ProgramExtractor`, the PSI packets are newly built (so the tables must fit in a packet), and
+/// the packets of the PIDs that are not described by the PAT and PMT of the input are dropped.
+///
+/// The packets are interleaved by the time elapsed since the first PCR of each input, that is,
+/// the next packet is taken from the input that is the least advanced in time.
+/// The inputs that have not received their first PCR yet take precedence over the others, and
+/// ties are broken by taking the input that was taken least recently.
+/// Hence, the inputs are expected to carry PCRs at regular intervals (as the standard requires).
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::ts::{ProgramMerger, ReadTsPacket, TsPacketReader};
+///
+/// let inputs = vec![
+///     TsPacketReader::new(mpeg2ts::io::empty()),
+///     TsPacketReader::new(mpeg2ts::io::empty()),
+/// ];
+/// let mut merger = ProgramMerger::new(inputs);
+/// while let Some(packet) = merger.read_ts_packet().unwrap() {
+///     // Writes `packet`
+/// }
+/// for i in 0..2 {
+///     println!("{:?}", merger.assignment(i));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ProgramMerger<R> {
+    inputs: Vec<Input<R>>,
+    used_pids: PidTable<()>,
+    transport_stream_id: u16,
+    pat: Option<Pat>,
+    pat_counter: ContinuityCounter,
+    sequence: u64,
+    last_input: Option<usize>,
+}
+impl<R: ReadTsPacket> ProgramMerger<R> {
+    /// Makes a new `ProgramMerger` instance.
+    pub fn new(inputs: Vec<R>) -> Self {
+        ProgramMerger {
+            inputs: inputs.into_iter().map(Input::new).collect(),
+            used_pids: PidTable::new(),
+            transport_stream_id: 1,
+            pat: None,
+            pat_counter: ContinuityCounter::new(),
+            sequence: 0,
+            last_input: None,
+        }
+    }
+
+    fn fill_next_packets(&mut self) -> Result<()> {
+        for input in &mut self.inputs {
+            if input.next.is_none() && !input.eos {
+                input.next = track!(input.reader.read_ts_packet())?;
+                input.eos = input.next.is_none();
+            }
+        }
+        Ok(())
+    }
+
+    fn select_input(&self) -> Option<usize> {
+        self.inputs
+            .iter()
+            .enumerate()
+            .filter(|(_, input)| input.next.is_some())
+            .min_by_key(|(_, input)| (input.next_packet_time(), input.last_taken))
+            .map(|(i, _)| i)
+    }
+
+    fn handle_ts_packet(&mut self, i: usize, mut packet: TsPacket) -> Result<Option<TsPacket>> {
+        let pid = packet.header.pid;
+        let input = &mut self.inputs[i];
+        input.update_time(&packet);
+        match packet.payload {
+            Some(TsPayload::Pat(ref pat)) => {
+                if !pat.is_current {
+                    return Ok(None);
+                }
+                let Some(pa) = pat.table.iter().find(|pa| pa.program_num != 0) else {
+                    return Ok(None);
+                };
+                if input.pmt_pid != Some(pa.program_map_pid) {
+                    input.pmt_pid = Some(pa.program_map_pid);
+                    input.pcr_pid = None;
+                }
+                let input_program_num = pa.program_num;
+                let input_pmt_pid = pa.program_map_pid;
+                track!(self.assign_program(i, input_program_num, input_pmt_pid))?;
+                let pat = self.combined_pat();
+                let counter = next_counter(&mut self.pat_counter);
+                Ok(Some(psi_packet(
+                    Pid::new_const(Pid::PAT),
+                    counter,
+                    TsPayload::Pat(pat),
+                    packet.arrival_timestamp,
+                )))
+            }
+            Some(TsPayload::Pmt(ref pmt)) if Some(pid) == input.pmt_pid => {
+                let Some(program_num) = input.assignment.as_ref().map(|a| a.program_num) else {
+                    return Ok(None);
+                };
+                if !pmt.is_current || Some(pmt.program_num) != input.program_num {
+                    return Ok(None);
+                }
+                input.pcr_pid = pmt.pcr_pid;
+                let mut pmt: Pmt = pmt.clone();
+                pmt.program_num = program_num;
+                pmt.raw = None;
+                if let Some(pcr_pid) = pmt.pcr_pid {
+                    pmt.pcr_pid = Some(track!(self.assign_pid(i, pcr_pid))?);
+                }
+                for es in &mut pmt.es_info {
+                    es.elementary_pid = track!(self.assign_pid(i, es.elementary_pid))?;
+                }
+                let input = &mut self.inputs[i];
+                let assignment = input.assignment.as_ref().expect("Never fails");
+                let counter = next_counter(&mut input.pmt_counter);
+                Ok(Some(psi_packet(
+                    assignment.pmt_pid,
+                    counter,
+                    TsPayload::Pmt(pmt),
+                    packet.arrival_timestamp,
+                )))
+            }
+            _ => {
+                if pid.as_u16() == Pid::PAT || Some(pid) == input.pmt_pid {
+                    // Fragments of the tables
+                    return Ok(None);
+                }
+                let Some(&output_pid) = input.pids.get(pid) else {
+                    return Ok(None);
+                };
+                packet.header.pid = output_pid;
+                Ok(Some(packet))
+            }
+        }
+    }
+
+    fn assign_program(
+        &mut self,
+        i: usize,
+        input_program_num: u16,
+        input_pmt_pid: Pid,
+    ) -> Result<()> {
+        let input = &self.inputs[i];
+        if input.program_num == Some(input_program_num) {
+            if let Some(assignment) = &input.assignment {
+                if assignment.pids.get(&input_pmt_pid) == Some(&assignment.pmt_pid) {
+                    return Ok(());
+                }
+            }
+        }
+
+        let program_num = match &input.assignment {
+            Some(a) if input.program_num == Some(input_program_num) => a.program_num,
+            _ => self.allocate_program_num(input_program_num),
+        };
+        let pmt_pid = track!(self.assign_pid(i, input_pmt_pid))?;
+        let input = &mut self.inputs[i];
+        input.program_num = Some(input_program_num);
+        let assignment = input.assignment.get_or_insert_with(|| ProgramAssignment {
+            input: i,
+            program_num,
+            pmt_pid,
+            pids: BTreeMap::new(),
+        });
+        assignment.program_num = program_num;
+        assignment.pmt_pid = pmt_pid;
+        assignment.pids.insert(input_pmt_pid, pmt_pid);
+        Ok(())
+    }
+
+    fn allocate_program_num(&self, preferred: u16) -> u16 {
+        let used = |n| {
+            self.inputs
+                .iter()
+                .filter_map(|input| input.assignment.as_ref())
+                .any(|a| a.program_num == n)
+        };
+        if preferred != 0 && !used(preferred) {
+            return preferred;
+        }
+        (1..=u16::MAX).find(|&n| !used(n)).expect("Never fails")
+    }
+
+    /// Returns the output PID of `input_pid` of the `i`-th input, assigning a new one if needed.
+    fn assign_pid(&mut self, i: usize, input_pid: Pid) -> Result<Pid> {
+        if let Some(&pid) = self.inputs[i].pids.get(input_pid) {
+            return Ok(pid);
+        }
+        let pid = if input_pid.as_u16() == Pid::NULL {
+            // The program does not have PCRs
+            input_pid
+        } else if input_pid.as_u16() >= 0x20 && !self.used_pids.contains(input_pid) {
+            input_pid
+        } else {
+            let n = track_assert_some!(
+                (0x20..=0x1FFA)
+                    .rev()
+                    .find(|&n| !self.used_pids.contains(Pid::new_const(n))),
+                ErrorKind::Other,
+                "No PIDs are available"
+            );
+            Pid::new_const(n)
+        };
+        if pid.as_u16() != Pid::NULL {
+            self.used_pids.insert(pid, ());
+        }
+
+        let input = &mut self.inputs[i];
+        input.pids.insert(input_pid, pid);
+        if let Some(assignment) = &mut input.assignment {
+            assignment.pids.insert(input_pid, pid);
+        }
+        Ok(pid)
+    }
+
+    fn combined_pat(&mut self) -> Pat {
+        let mut table = self
+            .inputs
+            .iter()
+            .filter_map(|input| input.assignment.as_ref())
+            .map(|a| ProgramAssociation {
+                program_num: a.program_num,
+                program_map_pid: a.pmt_pid,
+            })
+            .collect::<Vec<_>>();
+        table.sort_by_key(|pa| pa.program_num);
+
+        let mut version_number = VersionNumber::new();
+        if let Some(pat) = &self.pat {
+            version_number = pat.version_number;
+            if pat.table != table || pat.transport_stream_id != self.transport_stream_id {
+                version_number.increment();
+            }
+        }
+        let pat = Pat {
+            transport_stream_id: self.transport_stream_id,
+            version_number,
+            is_current: true,
+            table,
+            raw: None,
+        };
+        self.pat = Some(pat.clone());
+        pat
+    }
+}
+impl<R> ProgramMerger<R> {
+    /// Sets the transport stream identifier of the combined PAT.
+    ///
+    /// The default value is `1`.
+    pub fn set_transport_stream_id(&mut self, id: u16) {
+        self.transport_stream_id = id;
+    }
+
+    /// Returns the assignment of the program number and PIDs of the `i`-th input.
+    ///
+    /// This is `None` if the PAT of the input has not been received.
+    /// The elementary stream PIDs are added once the PMT of the input is received.
+    pub fn assignment(&self, i: usize) -> Option<&ProgramAssignment> {
+        self.inputs
+            .get(i)
+            .and_then(|input| input.assignment.as_ref())
+    }
+
+    /// Returns the number of the inputs.
+    pub fn input_count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Returns a reference to the `i`-th input.
+    pub fn input(&self, i: usize) -> Option<&R> {
+        self.inputs.get(i).map(|input| &input.reader)
+    }
+
+    /// Takes ownership of `self`, and returns the inputs.
+    pub fn into_inputs(self) -> Vec<R> {
+        self.inputs.into_iter().map(|input| input.reader).collect()
+    }
+}
+impl<R: ReadTsPacket> ReadTsPacket for ProgramMerger<R> {
+    fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+        loop {
+            track!(self.fill_next_packets())?;
+            let Some(i) = self.select_input() else {
+                return Ok(None);
+            };
+            let packet = self.inputs[i].next.take().expect("Never fails");
+            self.sequence += 1;
+            self.inputs[i].last_taken = self.sequence;
+            self.last_input = Some(i);
+            if let Some(packet) = track!(self.handle_ts_packet(i, packet))? {
+                return Ok(Some(packet));
+            }
+        }
+    }
+
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        let i = self.last_input?;
+        self.inputs[i].reader.last_packet_byte_offset()
+    }
+}
+
+#[derive(Debug)]
+struct Input<R> {
+    reader: R,
+    next: Option<TsPacket>,
+    eos: bool,
+    last_taken: u64,
+
+    // Program in the input
+    program_num: Option<u16>,
+    pmt_pid: Option<Pid>,
+    pcr_pid: Option<Pid>,
+
+    pids: PidTable<Pid>,
+    assignment: Option<ProgramAssignment>,
+    pmt_counter: ContinuityCounter,
+
+    last_pcr: Option<ClockReference>,
+    elapsed: u64,
+}
+impl<R> Input<R> {
+    fn new(reader: R) -> Self {
+        Input {
+            reader,
+            next: None,
+            eos: false,
+            last_taken: 0,
+            program_num: None,
+            pmt_pid: None,
+            pcr_pid: None,
+            pids: PidTable::new(),
+            assignment: None,
+            pmt_counter: ContinuityCounter::new(),
+            last_pcr: None,
+            elapsed: 0,
+        }
+    }
+
+    fn pcr_of(&self, packet: &TsPacket) -> Option<(ClockReference, bool)> {
+        if self.pcr_pid != Some(packet.header.pid) {
+            return None;
+        }
+        let af = packet.adaptation_field.as_ref()?;
+        af.pcr.map(|pcr| (pcr, af.discontinuity_indicator))
+    }
+
+    /// Returns the time (elapsed since the first PCR) of the next packet.
+    ///
+    /// This is `None` if no PCR has been received.
+    fn next_packet_time(&self) -> Option<u64> {
+        let packet = self.next.as_ref()?;
+        match (self.last_pcr, self.pcr_of(packet)) {
+            (None, Some(_)) => Some(0),
+            (None, None) => None,
+            (Some(last), Some((pcr, false))) => Some(self.elapsed + pcr.wrapping_sub(last)),
+            (Some(_), _) => Some(self.elapsed),
+        }
+    }
+
+    fn update_time(&mut self, packet: &TsPacket) {
+        let Some((pcr, discontinuity)) = self.pcr_of(packet) else {
+            return;
+        };
+        if let (Some(last), false) = (self.last_pcr, discontinuity) {
+            self.elapsed += pcr.wrapping_sub(last);
+        }
+        self.last_pcr = Some(pcr);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::StreamType;
+    use crate::ts::payload::Bytes;
+    use crate::ts::test_util::{self, pid};
+    use crate::ts::{
+        AdaptationField, ContinuityChecker, TsPacketReader, TsPacketWriter, WriteTsPacket,
+    };
+    use std::collections::HashMap;
+
+    /// Makes an SPTS whose program has a video stream on PID 256 (also carrying the PCRs).
+    fn spts(first_pcr: u64, packets_per_pcr: usize) -> Vec<u8> {
+        let pat = TsPayload::Pat(test_util::pat(&[(1, 0x1000)]));
+        let pmt = TsPayload::Pmt(test_util::pmt(1, Some(256), &[(256, StreamType::H264)]));
+        let mut packets = vec![(0, None, Some(pat)), (0x1000, None, Some(pmt))];
+        for i in 0..10 {
+            // 40 ms interval
+            let pcr = ClockReference::new(first_pcr + i * 27_000 * 40).unwrap();
+            let pes = TsPayload::Pes(test_util::pes(0xE0, None, None, &[0; 10]));
+            packets.push((256, Some(AdaptationField::with_pcr(pcr)), Some(pes)));
+            for _ in 1..packets_per_pcr {
+                let raw = TsPayload::Raw(Bytes::new(&[0; 100]).unwrap());
+                packets.push((256, None, Some(raw)));
+            }
+        }
+        test_util::write_packets(packets)
+    }
+
+    #[test]
+    fn merge_programs() {
+        let (a, b) = (spts(1_000_000, 10), spts(5_000_000_000, 1));
+        let inputs = vec![TsPacketReader::new(&a[..]), TsPacketReader::new(&b[..])];
+        let mut merger = ProgramMerger::new(inputs);
+        let mut packets = Vec::new();
+        while let Some(packet) = track_try_unwrap!(merger.read_ts_packet()) {
+            packets.push(packet);
+        }
+        assert_eq!(packets.len(), 2 + 100 + 2 + 10);
+
+        // The PIDs of the second input collide with the first ones, so they are reassigned
+        let a = merger.assignment(0).unwrap();
+        assert_eq!(a.program_num, 1);
+        assert_eq!(a.pmt_pid, pid(0x1000));
+        assert_eq!(
+            a.pids.iter().map(|(&i, &o)| (i, o)).collect::<Vec<_>>(),
+            [(pid(256), pid(256)), (pid(0x1000), pid(0x1000))]
+        );
+        let b = merger.assignment(1).unwrap();
+        assert_eq!(b.program_num, 2);
+        assert_eq!(b.pmt_pid, pid(0x1FFA));
+        assert_eq!(
+            b.pids.iter().map(|(&i, &o)| (i, o)).collect::<Vec<_>>(),
+            [(pid(256), pid(0x1FF9)), (pid(0x1000), pid(0x1FFA))]
+        );
+
+        // The output is a valid MPTS with continuous CCs
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packets(&packets));
+        let stream = writer.into_stream();
+        let mut reader = TsPacketReader::new(&stream[..]);
+        let mut checker = ContinuityChecker::new();
+        let mut pat = None;
+        let mut pmts = HashMap::new();
+        while let Some(packet) = track_try_unwrap!(reader.read_ts_packet()) {
+            assert!(!checker.check(&packet).is_discontinuous());
+            match packet.payload {
+                Some(TsPayload::Pat(x)) => pat = Some(x),
+                Some(TsPayload::Pmt(x)) => {
+                    pmts.insert(x.program_num, x);
+                }
+                _ => {}
+            }
+        }
+        let pat = pat.unwrap();
+        assert_eq!(
+            pat.table
+                .iter()
+                .map(|pa| (pa.program_num, pa.program_map_pid))
+                .collect::<Vec<_>>(),
+            [(1, pid(0x1000)), (2, pid(0x1FFA))]
+        );
+        assert_eq!(pmts[&2].pcr_pid, Some(pid(0x1FF9)));
+        assert_eq!(pmts[&2].es_info[0].elementary_pid, pid(0x1FF9));
+
+        // The packets are interleaved by the time elapsed since the first PCR of each input
+        let mut first_pcrs = HashMap::new();
+        let mut last_elapsed = 0;
+        let mut pcr_pids = Vec::new();
+        for packet in &packets {
+            let Some(pcr) = packet.adaptation_field.as_ref().and_then(|a| a.pcr) else {
+                continue;
+            };
+            let first = *first_pcrs.entry(packet.header.pid).or_insert(pcr);
+            let elapsed = pcr.wrapping_sub(first);
+            assert!(elapsed >= last_elapsed);
+            last_elapsed = elapsed;
+            pcr_pids.push(packet.header.pid.as_u16());
+        }
+        // Neither input starves the other (the order of the PCRs with the same elapsed time is unspecified)
+        assert_eq!(pcr_pids.len(), 20);
+        assert!(pcr_pids.chunks(2).all(|c| c[0] != c[1]));
+    }
+}
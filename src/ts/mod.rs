@@ -3,17 +3,78 @@
 //! # References
 //!
 //! - [MPEG transport stream](https://en.wikipedia.org/wiki/MPEG_transport_stream)
-pub use self::adaptation_field::{AdaptationExtensionField, AdaptationField};
-pub use self::packet::{TsHeader, TsPacket, TsPayload};
+pub use self::adaptation_field::{
+    AdaptationExtensionField, AdaptationField, AdaptationFieldBuilder,
+};
+#[cfg(feature = "tokio")]
+pub use self::async_io::{
+    AsyncReadTsPacket, AsyncTsPacketReader, AsyncTsPacketWriter, AsyncWriteTsPacket,
+};
+pub use self::codec::Codec;
+pub use self::compare::{compare_streams, Difference, Divergence};
+pub use self::concat::{concat, LayoutMismatchPolicy};
+pub use self::continuity::{
+    repair_continuity, Continuity, ContinuityCheck, ContinuityChecker, ContinuityRepairer,
+};
+pub use self::datagram::{DatagramPackets, TsDatagramReader};
+pub use self::demux::{DemuxEvent, Demuxer};
+pub use self::demux_handler::{Demux, DemuxHandler, ErrorPosition, StreamMeta};
+pub use self::descrambler::Descrambler;
+pub use self::describe::{describe, EsStreamInfo, ProgramInfo, StreamInfo};
+pub use self::descriptor::{
+    AacDescriptor, AvcVideoDescriptor, CaDescriptor, Iso639Language, KnownDescriptor,
+    OpusAudioDescriptor, RegistrationDescriptor, ServiceDescriptor, Subtitling,
+};
+pub use self::discontinuity::{
+    scan_discontinuities, DiscontinuityEvent, DiscontinuityKind, DiscontinuityScanner,
+};
+pub use self::dsm_cc::{DownloadDataBlock, DsmCcMessageHeader, DsmCcSection};
+pub use self::ebp::Ebp;
+pub use self::es_mux::EsMuxer;
+pub use self::extract::ProgramExtractor;
+pub use self::format::PacketFormat;
+pub use self::index::{build_index, IndexEntry};
+#[cfg(feature = "std")]
+pub use self::looping::LoopingReader;
+pub use self::merge::{ProgramAssignment, ProgramMerger};
+#[cfg(feature = "std")]
+pub use self::pacer::PacedTsPacketWriter;
+pub use self::pacer::PcrPacer;
+pub use self::packet::{TsHeader, TsPacket, TsPacketBuilder, TsPayload};
+pub use self::packet_ref::{AdaptationFieldRef, TsPacketRef, TsPacketRefs};
 pub use self::pat::ProgramAssociation;
+pub use self::pcr_analysis::{analyze_pcrs, PcrAnalyzer, PcrSample, PcrStats};
 pub use self::pmt::{Descriptor, EsInfo};
-pub use self::reader::{ReadTsPacket, TsPacketReader};
+#[cfg(feature = "std")]
+pub use self::probe::{probe, probe_linear, ProbeInfo};
+pub use self::psip::{
+    MgtTable, MultipleString, MultipleStringStructure, StringSegment, VirtualChannel,
+};
+pub use self::reader::{
+    CrcCheck, PidKind, ReadTsPacket, TransportErrorPolicy, TruncatedPacketPolicy, TsPacketReader,
+    TsPackets,
+};
+pub use self::recovery::{RecoveringTsPacketReader, SkippedRegion};
+pub use self::restamp::TimestampShifter;
+pub use self::section::SectionAssembler;
+#[cfg(feature = "std")]
+pub use self::splitter::{ShardTsPacketReader, TsPacketSplitter};
+pub use self::timestamp_check::{
+    check_timestamps, StreamTimestampReport, TimestampChecker, TimestampIssue, TimestampIssueKind,
+};
 pub use self::types::{
-    ContinuityCounter, LegalTimeWindow, Pid, PiecewiseRate, SeamlessSplice,
+    ContinuityCounter, KeyParity, LegalTimeWindow, Pid, PiecewiseRate, SeamlessSplice,
     TransportScramblingControl, VersionNumber,
 };
+pub use self::validate::{
+    validate, ValidationError, ValidationErrorKind, ValidationReport, Validator,
+};
+#[cfg(feature = "std")]
+pub use self::wallclock::WallclockMapper;
 pub use self::writer::{TsPacketWriter, WriteTsPacket};
 
+pub(crate) use self::pid_table::PidTable;
+
 pub mod payload {
     //! Transport stream payloads.
 
@@ -21,26 +82,69 @@ pub mod payload {
     pub use super::pat::Pat;
     pub use super::pes::Pes;
     pub use super::pmt::Pmt;
+    pub use super::psip::{Mgt, Vct};
     pub use super::section::Section;
+    pub use super::tsdt::Tsdt;
     pub use super::types::Bytes;
 }
 
 mod adaptation_field;
+#[cfg(feature = "tokio")]
+mod async_io;
+mod codec;
+mod compare;
+mod concat;
+mod continuity;
+mod datagram;
+mod demux;
+mod demux_handler;
+mod descrambler;
+mod describe;
+mod descriptor;
+mod discontinuity;
+mod dsm_cc;
+mod ebp;
+mod es_mux;
+mod extract;
+mod format;
+mod index;
+#[cfg(feature = "std")]
+mod looping;
+mod merge;
 mod null;
+mod pacer;
 mod packet;
+mod packet_ref;
 mod pat;
+mod pcr_analysis;
 mod pes;
+mod pid_table;
 mod pmt;
-mod psi;
+#[cfg(feature = "std")]
+mod probe;
+pub mod psi;
+mod psip;
 mod reader;
+mod recovery;
+mod restamp;
 mod section;
+#[cfg(feature = "std")]
+mod splitter;
+#[cfg(test)]
+mod test_util;
+mod timestamp_check;
+mod tsdt;
 mod types;
+mod validate;
+#[cfg(feature = "std")]
+mod wallclock;
 mod writer;
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::es::StreamType;
+    use crate::ErrorKind;
 
     #[test]
     fn pat() {
@@ -79,6 +183,7 @@ mod test {
         TsPacket {
             header: TsHeader {
                 transport_error_indicator: false,
+                payload_unit_start_indicator: true,
                 transport_priority: false,
                 pid: Pid::from(0),
                 transport_scrambling_control: TransportScramblingControl::NotScrambled,
@@ -88,11 +193,14 @@ mod test {
             payload: Some(TsPayload::Pat(payload::Pat {
                 transport_stream_id: 0,
                 version_number: VersionNumber::from_u8(1).unwrap(),
+                is_current: true,
                 table: vec![ProgramAssociation {
                     program_num: 1,
                     program_map_pid: Pid::new(480).unwrap(),
                 }],
+                raw: None,
             })),
+            arrival_timestamp: None,
         }
     }
 
@@ -120,6 +228,46 @@ mod test {
         assert_eq!(track_try_unwrap!(reader.read_ts_packet()), None);
     }
 
+    #[test]
+    fn raw_tables() {
+        let mut bytes = Vec::new();
+        bytes.extend(pat_packet_bytes());
+        bytes.extend(pmt_packet_bytes());
+        let relay = |keep_raw: bool, write_raw: bool| {
+            let mut reader = TsPacketReader::new(&bytes[..]);
+            reader.set_keep_raw_tables(keep_raw);
+            let mut writer = TsPacketWriter::new(Vec::new());
+            writer.set_write_raw_tables(write_raw);
+            while let Some(packet) = track_try_unwrap!(reader.read_ts_packet()) {
+                track_try_unwrap!(writer.write_ts_packet(&packet));
+            }
+            writer.into_stream()
+        };
+
+        // The stuffing bytes in the payloads are replaced with adaptation fields by default
+        assert_ne!(relay(false, false), bytes);
+        assert_ne!(relay(true, false), bytes);
+        assert_ne!(relay(false, true), bytes);
+        assert_eq!(relay(true, true), bytes);
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_keep_raw_tables(true);
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        let pat = packet.payload.as_ref().unwrap().as_pat().unwrap();
+        assert_eq!(pat.raw.as_deref(), Some(&pat_packet_bytes()[4..]));
+        assert_eq!(
+            payload::Pat {
+                raw: None,
+                ..pat.clone()
+            },
+            *pat_packet().payload.unwrap().as_pat().unwrap()
+        );
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        let pmt = packet.payload.as_ref().unwrap().as_pmt().unwrap();
+        // The packet has an empty adaptation field
+        assert_eq!(pmt.raw.as_deref(), Some(&pmt_packet_bytes()[5..]));
+    }
+
     fn pmt_packet_bytes() -> &'static [u8] {
         &[
             71, 65, 224, 48, 0, 0, 2, 176, 34, 0, 1, 193, 0, 0, 225, 2, 240, 6, 5, 4, 67, 85, 69,
@@ -140,6 +288,7 @@ mod test {
         TsPacket {
             header: TsHeader {
                 transport_error_indicator: false,
+                payload_unit_start_indicator: true,
                 transport_priority: false,
                 pid: Pid::new(480).unwrap(),
                 transport_scrambling_control: TransportScramblingControl::NotScrambled,
@@ -150,6 +299,7 @@ mod test {
                 program_num: 1,
                 pcr_pid: Some(Pid::new(258).unwrap()),
                 version_number: VersionNumber::new(),
+                is_current: true,
                 program_info: vec![Descriptor {
                     tag: 5,
                     data: b"CUEI".to_vec(),
@@ -171,10 +321,1442 @@ mod test {
                         descriptors: vec![],
                     },
                 ],
+                raw: None,
             })),
+            arrival_timestamp: None,
         }
     }
 
+    #[test]
+    fn packet_builder() {
+        use crate::es::StreamId;
+        use crate::pes::PesHeader;
+        use crate::time::ClockReference;
+
+        let pid = Pid::new(256).unwrap();
+        let adaptation_field = AdaptationField {
+            discontinuity_indicator: false,
+            random_access_indicator: true,
+            es_priority_indicator: false,
+            pcr: Some(track_try_unwrap!(ClockReference::new(1234))),
+            opcr: None,
+            splice_countdown: None,
+            transport_private_data: vec![],
+            extension: None,
+        };
+        let pes = payload::Pes {
+            header: PesHeader::new(StreamId::new(0xE0)),
+            pes_packet_len: 0,
+            data: track_try_unwrap!(payload::Bytes::new(&[1; 167])),
+        };
+
+        let packet = track_try_unwrap!(TsPacket::builder(pid)
+            .continuity(ContinuityCounter::from_u8(3).unwrap())
+            .adaptation(adaptation_field.clone())
+            .payload_pes(pes.clone())
+            .build());
+        assert_eq!(packet.header.continuity_counter.as_u8(), 3);
+        assert_eq!(packet.payload.as_ref().and_then(|p| p.as_pes()), Some(&pes));
+        assert!(packet.payload.as_ref().unwrap().as_pat().is_none());
+
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+        assert_eq!(writer.stream().len(), TsPacket::SIZE);
+
+        // 8 (adaptation field) + 9 (PES header) + 171 (data) = 184 + 4
+        let e = TsPacket::builder(pid)
+            .adaptation(adaptation_field)
+            .payload_pes(payload::Pes {
+                data: track_try_unwrap!(payload::Bytes::new(&[1; 171])),
+                ..pes
+            })
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+        assert!(e.to_string().contains("overflow=4"), "{}", e);
+
+        let packet = track_try_unwrap!(TsPacket::builder(pid).payload_raw(&[1, 2, 3]).build());
+        let raw = packet.payload.unwrap().into_raw().unwrap();
+        assert_eq!(&raw[..], [1, 2, 3]);
+    }
+
+    #[test]
+    fn adaptation_field_builder() {
+        use crate::time::ClockReference;
+
+        let pcr = ClockReference::new(27_000_000).unwrap();
+        assert_eq!(AdaptationField::with_pcr(pcr).external_size(), 8);
+        assert!(AdaptationField::random_access().random_access_indicator);
+
+        let adaptation_field = track_try_unwrap!(AdaptationField::builder()
+            .random_access_indicator(true)
+            .pcr(pcr)
+            .splice_countdown(-2)
+            .transport_private_data(&[1, 2, 3])
+            .build());
+        assert_eq!(adaptation_field.external_size(), 8 + 1 + 4);
+
+        // The private data is written with its length
+        let packet = track_try_unwrap!(TsPacket::builder(Pid::new(256).unwrap())
+            .adaptation(adaptation_field.clone())
+            .payload_raw(&[0; AdaptationField::MAX_SIZE - 13])
+            .build());
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+        let stream = writer.into_stream();
+        let mut reader = TsPacketReader::new(&stream[..]);
+        let read = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(read.adaptation_field, Some(adaptation_field));
+
+        // Too large
+        let e = AdaptationField::builder()
+            .pcr(pcr)
+            .transport_private_data(&[0; 180])
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+        assert!(e.to_string().contains("overflow=5"), "{}", e);
+        assert!(AdaptationField::builder()
+            .transport_private_data(&[0; 256])
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn display() {
+        use crate::es::StreamId;
+        use crate::pes::PesHeader;
+        use crate::time::{ClockReference, Timestamp};
+
+        assert_eq!(
+            pat_packet().to_string(),
+            "PID=0x0000 CC=1 PUSI PAT[TSID=0 VER=1 PROGS=1]"
+        );
+        assert_eq!(
+            pmt_packet().to_string(),
+            "PID=0x01E0 CC=0 PUSI PMT[PROG=1 VER=0 PCR_PID=0x0102 ES=3]"
+        );
+
+        let mut header = PesHeader::new(StreamId::new(0xE0));
+        header.pts = Some(Timestamp::new(93003).unwrap());
+        header.dts = Some(Timestamp::new(90000).unwrap());
+        let mut packet = TsPacket::builder(Pid::new(256).unwrap())
+            .continuity(ContinuityCounter::from_u8(7).unwrap())
+            .adaptation(AdaptationField {
+                discontinuity_indicator: false,
+                random_access_indicator: true,
+                es_priority_indicator: false,
+                pcr: Some(ClockReference::new(27_000_000).unwrap()),
+                opcr: None,
+                splice_countdown: None,
+                transport_private_data: vec![],
+                extension: None,
+            })
+            .payload_pes(payload::Pes {
+                header,
+                pes_packet_len: 0,
+                data: payload::Bytes::new(&[0; 20]).unwrap(),
+            })
+            .build()
+            .unwrap();
+        assert_eq!(
+            packet.to_string(),
+            "PID=0x0100 CC=7 PUSI AF[RAI PCR=27000000] PES[SID=0xE0 PTS=93003 DTS=90000 DAI DATA=20]"
+        );
+
+        packet.header.transport_scrambling_control =
+            TransportScramblingControl::ScrambledWithOddKey;
+        packet.adaptation_field = None;
+        packet.payload = Some(TsPayload::Raw(payload::Bytes::new(&[0xAB; 40]).unwrap()));
+        assert_eq!(packet.to_string(), "PID=0x0100 CC=7 SCR=ODD RAW[DATA=40]");
+
+        assert_eq!(
+            format!("{:?}", payload::Bytes::new(&[0, 0, 1, 0xE0]).unwrap()),
+            "Bytes(len=4, [00 00 01 E0])"
+        );
+        assert_eq!(
+            format!("{:?}", payload::Bytes::new(&[0xAB; 40]).unwrap()),
+            "Bytes(len=40, [AB AB AB AB AB AB AB AB AB AB AB AB AB AB AB AB ..])"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use crate::es::StreamId;
+        use crate::pes::{DsmTrickMode, PesHeader};
+        use crate::time::{ClockReference, Timestamp};
+
+        let json = serde_json::to_string(&pat_packet()).unwrap();
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"header":{"transport_error_indicator":false,"payload_unit_start_indicator":true,"#,
+                r#""transport_priority":false,"pid":0,"transport_scrambling_control":"NotScrambled","#,
+                r#""continuity_counter":1},"adaptation_field":null,"payload":{"Pat":{"#,
+                r#""transport_stream_id":0,"version_number":1,"is_current":true,"#,
+                r#""table":[{"program_num":1,"program_map_pid":480}]}},"arrival_timestamp":null}"#
+            )
+        );
+        let packet: TsPacket = serde_json::from_str(&json).unwrap();
+        assert_eq!(packet, pat_packet());
+
+        let packet: TsPacket =
+            serde_json::from_str(&serde_json::to_string(&pmt_packet()).unwrap()).unwrap();
+        assert_eq!(packet, pmt_packet());
+
+        let mut header = PesHeader::new(StreamId::new(0xE0));
+        header.pts = Some(Timestamp::new(Timestamp::MAX).unwrap());
+        header.escr = Some(ClockReference::new(27_000_000).unwrap());
+        header.trick_mode = Some(DsmTrickMode::SlowMotion { rep_cntrl: 3 });
+        let mut packet = pmt_packet();
+        packet.adaptation_field = Some(AdaptationField {
+            discontinuity_indicator: false,
+            random_access_indicator: true,
+            es_priority_indicator: false,
+            pcr: Some(ClockReference::new(1234).unwrap()),
+            opcr: None,
+            splice_countdown: Some(-1),
+            transport_private_data: vec![1, 2],
+            extension: None,
+        });
+        packet.payload = Some(TsPayload::Pes(payload::Pes {
+            header,
+            pes_packet_len: 0,
+            data: payload::Bytes::new(&[0xAB; 10]).unwrap(),
+        }));
+        let json = serde_json::to_string(&packet).unwrap();
+        assert!(json.contains(r#""data":[171,171,171,171,171,171,171,171,171,171]"#));
+        assert!(json.contains(r#""stream_id":224"#));
+        assert_eq!(serde_json::from_str::<TsPacket>(&json).unwrap(), packet);
+
+        // Out of range values are rejected
+        assert!(serde_json::from_str::<Pid>("8192").is_err());
+        assert!(serde_json::from_str::<ContinuityCounter>("16").is_err());
+        assert!(serde_json::from_str::<payload::Bytes>(&format!("{:?}", vec![0; 185])).is_err());
+        assert_eq!(
+            serde_json::from_str::<StreamType>("254").unwrap(),
+            StreamType::Unknown(254)
+        );
+    }
+
+    #[test]
+    fn scrambled() {
+        let mut scrambled_packet_bytes = vec![71, 65, 2, 0b1001_0000];
+        scrambled_packet_bytes.extend((0..184).map(|i| i as u8));
+
+        let mut bytes = Vec::new();
+        bytes.extend(pat_packet_bytes());
+        bytes.extend(pmt_packet_bytes());
+        bytes.extend(&scrambled_packet_bytes);
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        track_try_unwrap!(reader.read_ts_packet()).unwrap();
+
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(packet.header.pid, Pid::new(258).unwrap());
+        assert!(packet.header.payload_unit_start_indicator);
+        assert_eq!(
+            packet.header.transport_scrambling_control,
+            TransportScramblingControl::ScrambledWithEvenKey
+        );
+        assert_eq!(
+            packet.payload,
+            Some(TsPayload::Raw(track_try_unwrap!(payload::Bytes::new(
+                &scrambled_packet_bytes[4..]
+            ))))
+        );
+
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+        assert_eq!(writer.stream(), &scrambled_packet_bytes);
+    }
+
+    #[test]
+    fn descramble() {
+        use crate::es::StreamId;
+        use crate::pes::{PesHeader, PesPacketReader, ReadPesPacket};
+        use crate::time::Timestamp;
+
+        #[derive(Debug)]
+        struct XorDescrambler;
+        impl Descrambler for XorDescrambler {
+            fn descramble(
+                &mut self,
+                _pid: Pid,
+                key_parity: KeyParity,
+                payload: &mut [u8],
+            ) -> crate::Result<()> {
+                let key = if key_parity == KeyParity::Even {
+                    0xAA
+                } else {
+                    0x55
+                };
+                for b in payload {
+                    *b ^= key;
+                }
+                Ok(())
+            }
+        }
+
+        let pes_header = PesHeader {
+            stream_id: StreamId::new(0xE0),
+            priority: false,
+            data_alignment_indicator: false,
+            copyright: false,
+            original_or_copy: false,
+            pts: Some(track_try_unwrap!(Timestamp::new(90_000))),
+            dts: None,
+            escr: None,
+            es_rate: None,
+            trick_mode: None,
+            additional_copy_info: None,
+            previous_crc: None,
+            extension: None,
+        };
+        let data = [0x12; 170];
+        let packet = TsPacket {
+            header: TsHeader {
+                transport_error_indicator: false,
+                payload_unit_start_indicator: true,
+                transport_priority: false,
+                pid: Pid::new(258).unwrap(),
+                transport_scrambling_control: TransportScramblingControl::NotScrambled,
+                continuity_counter: ContinuityCounter::new(),
+            },
+            adaptation_field: None,
+            payload: Some(TsPayload::Pes(payload::Pes {
+                header: pes_header.clone(),
+                pes_packet_len: 3 + 5 + 170,
+                data: track_try_unwrap!(payload::Bytes::new(&data)),
+            })),
+            arrival_timestamp: None,
+        };
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+        let mut scrambled_packet_bytes = writer.into_stream();
+        scrambled_packet_bytes[3] |= 0b1000_0000;
+        for b in &mut scrambled_packet_bytes[4..] {
+            *b ^= 0xAA;
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend(pat_packet_bytes());
+        bytes.extend(pmt_packet_bytes());
+        bytes.extend(&scrambled_packet_bytes);
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_descrambler(XorDescrambler);
+
+        let mut reader = PesPacketReader::new(reader);
+        let pes = track_try_unwrap!(reader.read_pes_packet()).unwrap();
+        assert_eq!(pes.header, pes_header);
+        assert_eq!(pes.data, &data[..]);
+        assert!(track_try_unwrap!(reader.read_pes_packet()).is_none());
+    }
+
+    #[test]
+    fn transport_error_policy() {
+        let mut corrupted_packet_bytes = vec![71, 0b1100_0001, 224, 0b0001_0000];
+        corrupted_packet_bytes.extend([0; 184]);
+
+        let mut bytes = Vec::new();
+        bytes.extend(pat_packet_bytes());
+        bytes.extend(&corrupted_packet_bytes);
+        bytes.extend(pmt_packet_bytes());
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        track_try_unwrap!(reader.read_ts_packet());
+        assert!(reader.read_ts_packet().is_err());
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_transport_error_policy(TransportErrorPolicy::Drop);
+        track_try_unwrap!(reader.read_ts_packet());
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(packet.payload, pmt_packet().payload);
+        assert_eq!(track_try_unwrap!(reader.read_ts_packet()), None);
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_transport_error_policy(TransportErrorPolicy::Raw);
+        track_try_unwrap!(reader.read_ts_packet());
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert!(packet.header.transport_error_indicator);
+        assert_eq!(
+            packet.payload,
+            Some(TsPayload::Raw(track_try_unwrap!(payload::Bytes::new(
+                &[0; 184]
+            ))))
+        );
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(packet.payload, pmt_packet().payload);
+    }
+
+    #[test]
+    fn lenient_reserved_bits() {
+        let mut pmt_bytes = pmt_packet_bytes().to_vec();
+        pmt_bytes[14] &= 0b0001_1111; // PCR PID
+        pmt_bytes[25] &= 0b0001_1111; // elementary PID of the first ES
+
+        // Recomputes CRC32
+        let mut crc32 = crate::crc::Crc32::new();
+        crc32.update(&pmt_bytes[6..39]);
+        pmt_bytes[39..43].copy_from_slice(&crc32.value().to_be_bytes());
+
+        let mut bytes = Vec::new();
+        bytes.extend(pat_packet_bytes());
+        bytes.extend(&pmt_bytes);
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        track_try_unwrap!(reader.read_ts_packet());
+        assert!(reader.read_ts_packet().is_err());
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_lenient_reserved_bits(true);
+        track_try_unwrap!(reader.read_ts_packet());
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(packet.payload, pmt_packet().payload);
+    }
+
+    #[test]
+    fn crc_check() {
+        let mut pmt_bytes = pmt_packet_bytes().to_vec();
+        pmt_bytes[42] ^= 0xFF; // Corrupts CRC32
+
+        let mut bytes = Vec::new();
+        bytes.extend(pat_packet_bytes());
+        bytes.extend(&pmt_bytes);
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        track_try_unwrap!(reader.read_ts_packet());
+        let e = reader.read_ts_packet().err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::CrcMismatch);
+        assert_eq!(reader.crc_error_count(), 1);
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_crc_check(CrcCheck::Count);
+        track_try_unwrap!(reader.read_ts_packet());
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(packet.payload, pmt_packet().payload);
+        assert_eq!(reader.crc_error_count(), 1);
+    }
+
+    #[test]
+    fn crc_check_with_too_large_lengths() {
+        // program_info_length, and ES_info_length of the first ES
+        for (offset, len) in [(16, 0x3FF), (26, 0xFF)] {
+            let mut pmt_bytes = pmt_packet_bytes().to_vec();
+            pmt_bytes[offset] = 0xF0 | (len >> 8) as u8;
+            pmt_bytes[offset + 1] = len as u8;
+
+            let mut bytes = Vec::new();
+            bytes.extend(pat_packet_bytes());
+            bytes.extend(&pmt_bytes);
+
+            let mut reader = TsPacketReader::new(&bytes[..]);
+            reader.set_crc_check(CrcCheck::Count);
+            track_try_unwrap!(reader.read_ts_packet());
+            let e = reader.read_ts_packet().err().unwrap();
+            assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+            assert_eq!(reader.crc_error_count(), 1);
+        }
+    }
+
+    #[test]
+    fn multi_packet_pmt() {
+        let pmt = payload::Pmt {
+            program_num: 1,
+            pcr_pid: None,
+            version_number: VersionNumber::new(),
+            is_current: true,
+            program_info: vec![],
+            es_info: (0..30)
+                .map(|i| EsInfo {
+                    stream_type: StreamType::H264,
+                    elementary_pid: Pid::new(0x100 + i).unwrap(),
+                    descriptors: vec![Descriptor {
+                        tag: 5,
+                        data: b"HDMV".to_vec(),
+                    }],
+                })
+                .collect(),
+            raw: None,
+        };
+        let mut psi_bytes = Vec::new();
+        track_try_unwrap!(pmt.write_to(&mut psi_bytes));
+        assert!(psi_bytes.len() > 184 && psi_bytes.len() <= 184 * 2);
+
+        let mut bytes = Vec::new();
+        bytes.extend(pat_packet_bytes());
+        for (i, chunk) in psi_bytes.chunks(184).enumerate() {
+            let pusi = if i == 0 { 0x40 } else { 0x00 };
+            bytes.extend([0x47, pusi | 0x01, 0xE0, 0x10 | i as u8]);
+            bytes.extend(chunk);
+            bytes.extend(core::iter::repeat(0xFF).take(184 - chunk.len()));
+        }
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        track_try_unwrap!(reader.read_ts_packet());
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert!(matches!(packet.payload, Some(TsPayload::Raw(_))));
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(packet.payload, Some(TsPayload::Pmt(pmt)));
+        assert_eq!(track_try_unwrap!(reader.read_ts_packet()), None);
+    }
+
+    #[test]
+    fn next_pat() {
+        let mut packet = pat_packet();
+        if let Some(TsPayload::Pat(ref mut pat)) = packet.payload {
+            pat.is_current = false;
+        }
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+
+        let mut bytes = writer.into_stream();
+        bytes.extend(pmt_packet_bytes());
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_strict_pids(true);
+        let read = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(read.payload, packet.payload);
+
+        // PIDs are not registered from the tables that are not yet applicable
+        assert!(reader.read_ts_packet().is_err());
+    }
+
+    #[test]
+    fn resync() {
+        let mut bytes = pat_packet_bytes()[1..100].to_vec(); // truncated packet
+        bytes.extend([0x47, 0x00, 0x47]); // garbage
+        bytes.extend(pat_packet_bytes());
+        bytes.extend(pat_packet_bytes());
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        assert!(reader.read_ts_packet().is_err());
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_resync_confidence(Some(1));
+        for _ in 0..2 {
+            let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+            assert_eq!(packet.payload, pat_packet().payload);
+        }
+        assert_eq!(track_try_unwrap!(reader.read_ts_packet()), None);
+        assert_eq!(reader.skipped_bytes(), 102);
+    }
+
+    #[test]
+    fn m2ts() {
+        let mut bytes = vec![0xC0, 0x00, 0x12, 0x34];
+        bytes.extend(pat_packet_bytes());
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_packet_format(PacketFormat::M2ts);
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(packet.arrival_timestamp, Some(0xC000_1234));
+        assert_eq!(packet.payload, pat_packet().payload);
+        assert_eq!(track_try_unwrap!(reader.read_ts_packet()), None);
+
+        let mut writer = TsPacketWriter::new(Vec::new());
+        writer.set_packet_format(PacketFormat::M2ts);
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+        assert_eq!(writer.stream().len(), PacketFormat::M2ts.packet_size());
+        assert_eq!(writer.stream()[..5], bytes[..5]);
+    }
+
+    #[test]
+    fn packets() {
+        let mut bytes = pat_packet_bytes().to_vec();
+        bytes.extend(pat_packet_bytes());
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        assert_eq!(reader.packets().take(1).count(), 1);
+        assert_eq!(reader.packets().count(), 1);
+
+        bytes.extend([0x00; 10]); // truncated packet
+        let results = TsPacketReader::new(&bytes[..])
+            .into_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        assert!(results[2].is_err());
+    }
+
+    #[test]
+    fn packet_ref() {
+        use crate::time::ClockReference;
+
+        let mut pcr_packet = pat_packet();
+        pcr_packet.header.pid = Pid::new(256).unwrap();
+        pcr_packet.header.payload_unit_start_indicator = false;
+        pcr_packet.payload = None;
+        pcr_packet.adaptation_field = Some(AdaptationField {
+            discontinuity_indicator: false,
+            random_access_indicator: true,
+            es_priority_indicator: false,
+            pcr: Some(track_try_unwrap!(ClockReference::new(1234))),
+            opcr: None,
+            splice_countdown: None,
+            transport_private_data: vec![],
+            extension: None,
+        });
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packet(&pcr_packet));
+
+        let mut bytes = pat_packet_bytes().to_vec();
+        bytes.extend(pmt_packet_bytes());
+        bytes.extend(writer.into_stream());
+        bytes.extend([0x47; 10]);
+
+        let mut packets = TsPacketRefs::new(&bytes);
+        let pat = track_try_unwrap!(packets.next().unwrap());
+        let expected = pat_packet();
+        assert_eq!(pat.header, expected.header);
+        assert_eq!(
+            track_try_unwrap!(pat.pat()).map(TsPayload::Pat),
+            expected.payload
+        );
+        assert_eq!(track_try_unwrap!(pat.pes_header()), None);
+
+        let pmt = track_try_unwrap!(packets.next().unwrap());
+        assert_eq!(track_try_unwrap!(pmt.pat()), None);
+        assert_eq!(
+            track_try_unwrap!(pmt.pmt()).map(TsPayload::Pmt),
+            pmt_packet().payload
+        );
+
+        let pcr = track_try_unwrap!(packets.next().unwrap());
+        assert_eq!(pcr.payload, None);
+        let adaptation_field = pcr.adaptation_field.unwrap();
+        assert!(adaptation_field.random_access_indicator());
+        assert_eq!(adaptation_field.pcr().map(|c| c.as_u64()), Some(1234));
+        assert_eq!(adaptation_field.opcr(), None);
+        assert_eq!(track_try_unwrap!(pcr.to_ts_packet()), pcr_packet);
+
+        let e = packets.next().unwrap().err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::TruncatedPacket);
+        assert!(packets.next().is_none());
+    }
+
+    #[test]
+    fn error_position() {
+        let mut bytes = pat_packet_bytes().to_vec();
+        bytes.extend(pmt_packet_bytes());
+        bytes.extend([0x00; 188]);
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        assert_eq!(reader.last_packet_byte_offset(), None);
+        assert_eq!(reader.packets().take(2).count(), 2);
+        assert_eq!(reader.last_packet_byte_offset(), Some(188));
+        assert_eq!(reader.byte_offset(), 376);
+        assert_eq!(reader.packet_index(), 2);
+
+        let e = reader.read_ts_packet().err().unwrap();
+        assert!(
+            e.to_string().contains("byte_offset=376, packet_index=2"),
+            "{}",
+            e
+        );
+        assert_eq!(reader.packet_index(), 3);
+    }
+
+    #[test]
+    fn truncated_packet() {
+        let mut bytes = pat_packet_bytes().to_vec();
+        bytes.extend(&pmt_packet_bytes()[..100]);
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        assert!(track_try_unwrap!(reader.read_ts_packet()).is_some());
+        let e = reader.read_ts_packet().err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_truncated_packet_policy(TruncatedPacketPolicy::TruncatedPacket);
+        assert!(track_try_unwrap!(reader.read_ts_packet()).is_some());
+        let e = reader.read_ts_packet().err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::TruncatedPacket);
+        assert!(track_try_unwrap!(reader.read_ts_packet()).is_none());
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_truncated_packet_policy(TruncatedPacketPolicy::Discard);
+        assert!(track_try_unwrap!(reader.read_ts_packet()).is_some());
+        assert!(track_try_unwrap!(reader.read_ts_packet()).is_none());
+    }
+
+    #[test]
+    fn recovery() {
+        let mut broken_pat = pat_packet_bytes().to_vec();
+        broken_pat[3] &= 0b1100_1111; // reserved adaptation_field_control
+
+        let mut bytes = pat_packet_bytes().to_vec();
+        bytes.extend([0x00; 188]);
+        bytes.extend(&broken_pat);
+        bytes.extend(pmt_packet_bytes());
+        bytes.extend(&pmt_packet_bytes()[..50]);
+
+        let mut reader = RecoveringTsPacketReader::new(TsPacketReader::new(&bytes[..]));
+        let pids = reader
+            .packets()
+            .map(|p| track_try_unwrap!(p).header.pid.as_u16())
+            .collect::<Vec<_>>();
+        assert_eq!(pids, [Pid::PAT, 480]);
+
+        let regions = reader
+            .skipped_regions()
+            .iter()
+            .map(|r| (r.byte_offset, r.size, r.pid.map(|p| p.as_u16())))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            regions,
+            [
+                (188, 188, None),
+                (376, 188, Some(Pid::PAT)),
+                (752, 50, None)
+            ]
+        );
+        assert_eq!(reader.skipped_bytes(), 426);
+        assert_eq!(reader.ts_packet_reader().byte_offset(), 802);
+
+        // A complete packet whose adaptation field is too short for its PCR
+        let mut malformed = vec![0x47, 0x01, 0x00, 0x30, 0x01, 0x10];
+        malformed.extend([0xFF; 182]);
+
+        let mut bytes = malformed;
+        bytes.extend(pat_packet_bytes());
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        let e = reader.read_ts_packet().err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+
+        let mut reader = RecoveringTsPacketReader::new(TsPacketReader::new(&bytes[..]));
+        let pids = reader
+            .packets()
+            .map(|p| track_try_unwrap!(p).header.pid.as_u16())
+            .collect::<Vec<_>>();
+        assert_eq!(pids, [Pid::PAT]);
+        let region = &reader.skipped_regions()[0];
+        assert_eq!((region.byte_offset, region.size), (0, 188));
+        assert_eq!(region.pid.map(|p| p.as_u16()), Some(0x100));
+    }
+
+    #[test]
+    fn null_packets() {
+        let mut null_packet = vec![0x47, 0x1F, 0xFF, 0x10];
+        null_packet.extend([0xFF; 184]);
+
+        let mut bytes = null_packet.clone();
+        bytes.extend(pat_packet_bytes());
+        bytes.extend(&null_packet);
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(packet.header.pid.as_u16(), Pid::NULL);
+        assert_eq!(packet.payload, Some(TsPayload::Null(payload::Null)));
+        assert_eq!(reader.packets().count(), 2);
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_skip_null_packets(true);
+        let pids = reader
+            .packets()
+            .map(|p| track_try_unwrap!(p).header.pid.as_u16())
+            .collect::<Vec<_>>();
+        assert_eq!(pids, [Pid::PAT]);
+    }
+
+    #[test]
+    fn write_ts_packets() {
+        struct CountingWriter(Vec<u8>, usize);
+        impl crate::io::Write for CountingWriter {
+            fn write(&mut self, buf: &[u8]) -> crate::io::Result<usize> {
+                self.1 += 1;
+                self.0.write(buf)
+            }
+            fn flush(&mut self) -> crate::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut writer = TsPacketWriter::new(CountingWriter(Vec::new(), 0));
+        track_try_unwrap!(writer.write_ts_packet(&pat_packet()));
+        assert_eq!(writer.stream().1, 1);
+        track_try_unwrap!(writer.write_ts_packets(&[pmt_packet(), pat_packet()]));
+        assert_eq!(writer.stream().1, 2);
+
+        let mut broken_packet = pat_packet();
+        broken_packet.adaptation_field = Some(AdaptationField {
+            discontinuity_indicator: false,
+            random_access_indicator: false,
+            es_priority_indicator: false,
+            pcr: None,
+            opcr: None,
+            splice_countdown: None,
+            transport_private_data: vec![0; 180],
+            extension: None,
+        });
+        assert!(writer
+            .write_ts_packets(&[pat_packet(), broken_packet])
+            .is_err());
+        assert_eq!(writer.stream().1, 2);
+
+        let mut expected = TsPacketWriter::new(Vec::new());
+        for packet in [pat_packet(), pmt_packet(), pat_packet()] {
+            track_try_unwrap!(expected.write_ts_packet(&packet));
+        }
+        assert_eq!(writer.into_stream().0, expected.into_stream());
+    }
+
+    #[test]
+    fn trait_objects() {
+        use crate::pes::{PesPacketReader, ReadPesPacket};
+
+        fn copy<R: ReadTsPacket, W: WriteTsPacket>(mut reader: R, mut writer: W) {
+            while let Some(packet) = track_try_unwrap!(reader.read_ts_packet()) {
+                track_try_unwrap!(writer.write_ts_packet(&packet));
+            }
+        }
+
+        let mut readers: Vec<Box<dyn ReadTsPacket>> = vec![
+            Box::new(TsPacketReader::new(pat_packet_bytes())),
+            Box::new(TsPacketReader::new(pmt_packet_bytes())),
+        ];
+        let mut writer: Box<dyn WriteTsPacket> = Box::new(TsPacketWriter::new(Vec::new()));
+        for reader in &mut readers {
+            copy(reader, &mut writer);
+        }
+
+        let mut pes_reader: Box<dyn ReadPesPacket> =
+            Box::new(PesPacketReader::new(readers.remove(0)));
+        assert!(track_try_unwrap!(pes_reader.read_pes_packet()).is_none());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_io() {
+        use crate::pes::{AsyncReadPesPacket, PesPacketReader};
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut writer = AsyncTsPacketWriter::new(Vec::new());
+            writer.set_packet_format(PacketFormat::M2ts);
+            track_try_unwrap!(writer.write_ts_packet(&pat_packet()).await);
+            track_try_unwrap!(writer.write_ts_packet(&pmt_packet()).await);
+            let bytes = writer.into_stream();
+
+            let mut reader = track_try_unwrap!(
+                AsyncTsPacketReader::with_detected_packet_format(&bytes[..]).await
+            );
+            assert_eq!(
+                reader.ts_packet_reader().packet_format(),
+                PacketFormat::M2ts
+            );
+            let packet = track_try_unwrap!(reader.read_ts_packet().await).unwrap();
+            assert_eq!(packet.payload, pat_packet().payload);
+            let packet = track_try_unwrap!(reader.read_ts_packet().await).unwrap();
+            assert_eq!(packet.payload, pmt_packet().payload);
+            assert_eq!(track_try_unwrap!(reader.read_ts_packet().await), None);
+
+            let reader = AsyncTsPacketReader::new(pat_packet_bytes());
+            let mut reader = PesPacketReader::new(reader);
+            assert!(track_try_unwrap!(reader.read_pes_packet().await).is_none());
+        });
+    }
+
+    #[test]
+    fn datagram() {
+        let mut datagram = pat_packet_bytes().to_vec();
+        datagram.extend(pmt_packet_bytes());
+
+        let mut reader = TsDatagramReader::new();
+        let packets =
+            track_try_unwrap!(reader.push_datagram(&datagram)).collect::<crate::Result<Vec<_>>>();
+        let packets = track_try_unwrap!(packets);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[1].payload, pmt_packet().payload);
+
+        // RTP packets with a CSRC, and a lost packet between them
+        for sequence_number in [10u16, 12] {
+            let mut rtp_packet = vec![0x81, 33];
+            rtp_packet.extend(sequence_number.to_be_bytes());
+            rtp_packet.extend([0; 12]);
+            rtp_packet.extend(pmt_packet_bytes());
+            let mut packets = track_try_unwrap!(reader.push_datagram(&rtp_packet));
+            let packet = track_try_unwrap!(packets.next().unwrap());
+            assert_eq!(packet.payload, pmt_packet().payload);
+            assert!(packets.next().is_none());
+        }
+        assert_eq!(reader.lost_rtp_packet_count(), 1);
+
+        assert!(reader.push_datagram(&datagram[..200]).is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn seek() {
+        use crate::time::{ClockReference, Timestamp};
+        use std::io::Cursor;
+
+        let mut writer = TsPacketWriter::new(Vec::new());
+        for i in 0..100 {
+            let mut packet = pat_packet();
+            packet.header.pid = Pid::new(256).unwrap();
+            packet.payload = None;
+            packet.adaptation_field = Some(AdaptationField {
+                discontinuity_indicator: false,
+                random_access_indicator: false,
+                es_priority_indicator: false,
+                pcr: Some(track_try_unwrap!(ClockReference::new(i * 9000 * 300))),
+                opcr: None,
+                splice_countdown: None,
+                transport_private_data: vec![],
+                extension: None,
+            });
+            track_try_unwrap!(writer.write_ts_packet(&packet));
+        }
+        let pcr_of = |packet: TsPacket| packet.adaptation_field.unwrap().pcr.unwrap().as_u64();
+
+        let mut reader = TsPacketReader::new(Cursor::new(writer.into_stream()));
+        track_try_unwrap!(reader.seek_to_byte(1000, false));
+        assert_eq!(track_try_unwrap!(reader.position()), 188 * 6);
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(pcr_of(packet), 6 * 9000 * 300);
+
+        let timestamp = track_try_unwrap!(Timestamp::new(50 * 9000));
+        track_try_unwrap!(reader.seek_to_timestamp(None, timestamp));
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        let pcr = pcr_of(packet);
+        assert!(pcr < 50 * 9000 * 300);
+        assert!(pcr >= 34 * 9000 * 300);
+    }
+
+    #[test]
+    fn index() {
+        let mut pes_packet = pmt_packet();
+        pes_packet.header.pid = Pid::new(258).unwrap();
+        pes_packet.payload = Some(TsPayload::Pes(payload::Pes {
+            header: crate::pes::PesHeader {
+                stream_id: crate::es::StreamId::new(0xE0),
+                priority: false,
+                data_alignment_indicator: false,
+                copyright: false,
+                original_or_copy: false,
+                pts: Some(track_try_unwrap!(crate::time::Timestamp::new(3000))),
+                dts: None,
+                escr: None,
+                es_rate: None,
+                trick_mode: None,
+                additional_copy_info: None,
+                previous_crc: None,
+                extension: None,
+            },
+            pes_packet_len: 0,
+            data: track_try_unwrap!(payload::Bytes::new(&[0; 100])),
+        }));
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packet(&pes_packet));
+
+        let mut bytes = vec![0x00, 0x00]; // garbage
+        bytes.extend(pat_packet_bytes());
+        bytes.extend(pmt_packet_bytes());
+        bytes.extend(writer.stream());
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_resync_confidence(Some(1));
+        let entries = track_try_unwrap!(build_index(&mut reader, Pid::new(258).unwrap()));
+        assert_eq!(
+            entries,
+            [IndexEntry {
+                byte_offset: 2 + 188 * 2,
+                packet_index: 2,
+                pts: Some(track_try_unwrap!(crate::time::Timestamp::new(3000))),
+                random_access_indicator: false,
+            }]
+        );
+
+        // Dropped null packets and a reader that has already been read from
+        let mut null_packet = vec![0x47, 0x1F, 0xFF, 0x10];
+        null_packet.extend([0xFF; 184]);
+        let mut bytes = pat_packet_bytes().to_vec();
+        bytes.extend(&null_packet);
+        bytes.extend(pmt_packet_bytes());
+        bytes.extend(writer.stream());
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_skip_null_packets(true);
+        assert!(track_try_unwrap!(reader.read_ts_packet()).is_some());
+        let entries = track_try_unwrap!(build_index(&mut reader, Pid::new(258).unwrap()));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].byte_offset, 188 * 3);
+        assert_eq!(entries[0].packet_index, 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn probe() {
+        use crate::time::ClockReference;
+        use std::io::Cursor;
+
+        let mut writer = TsPacketWriter::new(Vec::new());
+        for i in 0..20_000 {
+            let mut packet = pat_packet();
+            packet.header.pid = Pid::new(256).unwrap();
+            packet.payload = None;
+            packet.adaptation_field = Some(AdaptationField {
+                discontinuity_indicator: false,
+                random_access_indicator: false,
+                es_priority_indicator: false,
+                pcr: Some(
+                    track_try_unwrap!(ClockReference::new(ClockReference::MODULUS - 27_000_000))
+                        .wrapping_add(i * 2700),
+                ),
+                opcr: None,
+                splice_countdown: None,
+                transport_private_data: vec![],
+                extension: None,
+            });
+            track_try_unwrap!(writer.write_ts_packet(&packet));
+        }
+        let bytes = writer.into_stream();
+        assert!(bytes.len() > 2 * 1024 * 1024);
+
+        let info = track_try_unwrap!(super::probe(Cursor::new(&bytes)));
+        assert_eq!(info.byte_size, 20_000 * 188);
+        assert_eq!(info.duration.map(|d| d.as_millis()), Some(1999));
+        assert_eq!(info.bitrate, Some(15_040_752));
+        assert_eq!(info.pids.len(), 1);
+
+        let linear = track_try_unwrap!(probe_linear(&bytes[..]));
+        assert_eq!(info, linear);
+
+        // A partial packet at the end of the stream
+        let mut bytes = bytes;
+        bytes.extend([0x47, 0x01, 0x00]);
+        let info = track_try_unwrap!(super::probe(Cursor::new(&bytes)));
+        assert_eq!(info.byte_size, 20_000 * 188 + 3);
+        assert_eq!(info.duration.map(|d| d.as_millis()), Some(1999));
+
+        let linear = track_try_unwrap!(probe_linear(&bytes[..]));
+        assert_eq!(info, linear);
+    }
+
+    #[test]
+    fn describe() {
+        let mut bytes = Vec::new();
+        bytes.extend(pat_packet_bytes());
+        bytes.extend(pmt_packet_bytes());
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        let info = track_try_unwrap!(super::describe(&mut reader, 100)).unwrap();
+        assert_eq!(info.programs.len(), 1);
+
+        let program = &info.programs[0];
+        assert_eq!(program.program_num, 1);
+        assert_eq!(program.pmt_pid, Pid::new(480).unwrap());
+        assert_eq!(program.pcr_pid, Some(Pid::new(258).unwrap()));
+        assert_eq!(program.descriptors[0].format_identifier(), Some(*b"CUEI"));
+
+        let pids = program
+            .streams
+            .iter()
+            .map(|s| s.pid.as_u16())
+            .collect::<Vec<_>>();
+        assert_eq!(pids, [259, 257, 258]);
+        assert_eq!(program.streams[1].stream_type, StreamType::AdtsAac);
+        assert_eq!(program.streams[1].language, None);
+        assert_eq!(program.streams[1].first_pts, None);
+
+        let mut reader = TsPacketReader::new(pmt_packet_bytes());
+        assert_eq!(track_try_unwrap!(super::describe(&mut reader, 0)), None);
+    }
+
+    #[test]
+    fn klv_stream() {
+        let mut es = EsInfo {
+            stream_type: StreamType::Mpeg2PacketizedData,
+            elementary_pid: Pid::new(300).unwrap(),
+            descriptors: vec![Descriptor {
+                tag: Descriptor::REGISTRATION_TAG,
+                data: b"KLVA".to_vec(),
+            }],
+        };
+        assert!(es.is_klv());
+
+        // Metadata descriptor (metadata_application_format=0x0100, metadata_format=0xFF)
+        es.stream_type = StreamType::PacketizedMetadata;
+        es.descriptors[0] = Descriptor {
+            tag: Descriptor::METADATA_TAG,
+            data: vec![0x01, 0x00, 0xFF, b'K', b'L', b'V', b'A', 0x00, 0x0F],
+        };
+        assert!(es.is_klv());
+
+        es.stream_type = StreamType::H264;
+        assert!(!es.is_klv());
+    }
+
+    #[test]
+    fn track_selection() {
+        // A PMT with French and English audio tracks, built here rather than taken from a capture
+        let es = |stream_type, pid, language: &[u8]| EsInfo {
+            stream_type,
+            elementary_pid: Pid::new(pid).unwrap(),
+            descriptors: vec![Descriptor {
+                tag: Descriptor::ISO_639_LANGUAGE_TAG,
+                data: language.to_vec(),
+            }],
+        };
+        let pmt = payload::Pmt {
+            program_num: 1,
+            pcr_pid: Some(Pid::new(256).unwrap()),
+            version_number: VersionNumber::new(),
+            is_current: true,
+            program_info: vec![],
+            es_info: vec![
+                es(StreamType::H264, 256, b"und\x00"),
+                es(StreamType::DolbyDigitalUpToSixChannelAudio, 257, b"fra\x00"),
+                es(StreamType::AdtsAac, 258, b"eng\x00"),
+                es(StreamType::Mpeg2PacketizedData, 259, b"eng\x00"),
+            ],
+            raw: None,
+        };
+
+        // Round-trips the PMT through a packet
+        let mut writer = TsPacketWriter::new(Vec::new());
+        let mut packet = pmt_packet();
+        packet.payload = Some(TsPayload::Pmt(pmt));
+        track_try_unwrap!(writer.write_ts_packet(&pat_packet()));
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+        let mut reader = TsPacketReader::new(&writer.stream()[..]);
+        track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        let Some(TsPayload::Pmt(pmt)) = packet.payload else {
+            panic!("Not a PMT");
+        };
+
+        let pids = |streams: Vec<&EsInfo>| {
+            streams
+                .iter()
+                .map(|es| es.elementary_pid.as_u16())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(pids(pmt.video_streams().collect()), [256]);
+        assert_eq!(pids(pmt.audio_streams().collect()), [257, 258]);
+        assert_eq!(pmt.es_info[1].language(), Some(*b"fra"));
+
+        let es = pmt.find_audio_by_language("eng").unwrap();
+        assert_eq!(es.elementary_pid.as_u16(), 258);
+        let es = pmt.find_audio_by_language("FRA").unwrap();
+        assert_eq!(es.elementary_pid.as_u16(), 257);
+        assert!(pmt.find_audio_by_language("deu").is_none());
+    }
+
+    #[test]
+    fn reed_solomon() {
+        let mut bytes = Vec::new();
+        for _ in 0..2 {
+            bytes.extend(pat_packet_bytes());
+            bytes.extend([0xAB; 16]);
+        }
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_packet_format(PacketFormat::ReedSolomon);
+        for _ in 0..2 {
+            let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+            assert_eq!(packet.payload, pat_packet().payload);
+        }
+        assert_eq!(track_try_unwrap!(reader.read_ts_packet()), None);
+
+        let mut writer = TsPacketWriter::new(Vec::new());
+        writer.set_packet_format(PacketFormat::ReedSolomon);
+        track_try_unwrap!(writer.write_ts_packet(&pat_packet()));
+        assert_eq!(writer.stream().len(), 204);
+        assert_eq!(writer.stream()[188..], [0; 16]);
+    }
+
+    #[test]
+    fn detect_packet_format() {
+        for format in PacketFormat::CANDIDATES {
+            let mut writer = TsPacketWriter::new(Vec::new());
+            writer.set_packet_format(format);
+            for _ in 0..30 {
+                track_try_unwrap!(writer.write_ts_packet(&pat_packet()));
+            }
+
+            let bytes = writer.into_stream();
+            let mut reader =
+                track_try_unwrap!(TsPacketReader::with_detected_packet_format(&bytes[..]));
+            assert_eq!(reader.packet_format(), format);
+            for _ in 0..30 {
+                let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+                assert_eq!(packet.payload, pat_packet().payload);
+            }
+            assert_eq!(track_try_unwrap!(reader.read_ts_packet()), None);
+        }
+
+        assert!(TsPacketReader::with_detected_packet_format(&[0; 1000][..]).is_err());
+    }
+
+    #[test]
+    fn unknown_pid() {
+        let mut bytes = vec![0x47, 0x41, 0x00, 0x10]; // PID 256
+        bytes.resize(TsPacket::SIZE, 0xFF);
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert!(packet.header.payload_unit_start_indicator);
+        assert!(matches!(packet.payload, Some(TsPayload::Raw(_))));
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_strict_pids(true);
+        assert!(reader.read_ts_packet().is_err());
+    }
+
+    #[test]
+    fn register_pid() {
+        let mut reader = TsPacketReader::new(pmt_packet_bytes());
+        reader.register_pid(Pid::new(480).unwrap(), PidKind::Pmt);
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(packet.payload, pmt_packet().payload);
+        assert_eq!(reader.pids().len(), 4);
+        assert_eq!(
+            reader.pids().get(&Pid::new(258).unwrap()),
+            Some(&PidKind::Pes)
+        );
+
+        let pids = reader.pids().clone();
+        let mut reader = TsPacketReader::with_pids(pmt_packet_bytes(), pids);
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(packet.payload, pmt_packet().payload);
+    }
+
+    #[test]
+    fn network_pid() {
+        // DVB streams announce the PID of their NIT as program 0 in the PAT.
+        // No DVB capture is checked in, so the entry is added to the hand-built PAT fixture.
+        let mut packet = pat_packet();
+        if let Some(TsPayload::Pat(ref mut pat)) = packet.payload {
+            pat.table.insert(
+                0,
+                ProgramAssociation {
+                    program_num: 0,
+                    program_map_pid: Pid::new(0x20).unwrap(),
+                },
+            );
+        }
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+
+        // NIT section (table_id = 0x40) on the network PID
+        let mut bytes = writer.into_stream();
+        bytes.extend([0x47, 0x40, 0x20, 0x10, 0x00, 0x40, 0xF0, 0x0D]);
+        bytes.resize(TsPacket::SIZE * 2, 0xFF);
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        track_try_unwrap!(reader.read_ts_packet());
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        let section = packet.payload.as_ref().unwrap().as_section().unwrap();
+        assert_eq!(section.pointer_field, 0);
+        assert_eq!(section.section_bytes()[0], 0x40);
+    }
+
+    #[test]
+    fn section_stream() {
+        // SDT on a well-known PID
+        let mut reader = TsPacketReader::new(pid17_packet_bytes());
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        let section = packet.payload.as_ref().unwrap().as_section().unwrap();
+        assert_eq!(section.section_bytes()[0], 0x42);
+
+        // Registered section stream
+        let pid = Pid::new(0x400).unwrap();
+        let start = track_try_unwrap!(payload::Section::new(3, &[1, 2, 3, 0x90, 0xF0, 0x00]));
+        assert_eq!(start.previous_section_bytes(), [1, 2, 3]);
+        assert_eq!(start.section_bytes(), [0x90, 0xF0, 0x00]);
+        assert!(payload::Section::new(7, &[1, 2, 3]).is_err());
+
+        let mut writer = TsPacketWriter::new(Vec::new());
+        let packet = track_try_unwrap!(TsPacket::builder(pid)
+            .payload(TsPayload::Section(start.clone()))
+            .build());
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+        let packet = track_try_unwrap!(TsPacket::builder(pid)
+            .continuity(ContinuityCounter::from_u8(1).unwrap())
+            .payload_raw(&[4, 5, 6])
+            .build());
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+        let stream = writer.into_stream();
+
+        let mut reader = TsPacketReader::new(&stream[..]);
+        reader.register_pid(pid, PidKind::Section);
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(packet.payload, Some(TsPayload::Section(start)));
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert!(matches!(packet.payload, Some(TsPayload::Raw(_))));
+    }
+
+    #[test]
+    fn pmt_version_change() {
+        let mut packet = pmt_packet();
+        if let Some(TsPayload::Pmt(ref mut pmt)) = packet.payload {
+            pmt.version_number = VersionNumber::from_u8(1).unwrap();
+            pmt.es_info
+                .retain(|es| es.elementary_pid == Pid::new(258).unwrap());
+        }
+        let mut writer = TsPacketWriter::new(Vec::new());
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+
+        let mut es_bytes = vec![0x47, 0x01, 0x03, 0x10]; // PID 259
+        es_bytes.resize(TsPacket::SIZE, 0xFF);
+
+        let mut bytes = Vec::new();
+        bytes.extend(pat_packet_bytes());
+        bytes.extend(pmt_packet_bytes());
+        bytes.extend(&es_bytes);
+        bytes.extend(writer.stream());
+        bytes.extend(&es_bytes);
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        reader.set_strict_pids(true);
+        for _ in 0..3 {
+            track_try_unwrap!(reader.read_ts_packet());
+            assert!(!reader.program_layout_changed());
+        }
+        let read = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(read.payload, packet.payload);
+        assert!(reader.program_layout_changed());
+
+        // The PID removed from the program is no longer recognized
+        assert!(reader.read_ts_packet().is_err());
+    }
+
+    #[test]
+    fn multi_section_pat() {
+        fn pat_section_packet(version: u8, section_number: u8, program_num: u16) -> Vec<u8> {
+            let pmt_pid = 0xE000 | (0x100 + program_num);
+            let mut table_data = program_num.to_be_bytes().to_vec();
+            table_data.extend(pmt_pid.to_be_bytes());
+            let psi = psi::Psi {
+                tables: vec![psi::PsiTable {
+                    header: psi::PsiTableHeader {
+                        table_id: 0,
+                        private_bit: false,
+                    },
+                    syntax: Some(psi::PsiTableSyntax {
+                        table_id_extension: 1,
+                        version_number: VersionNumber::from_u8(version).unwrap(),
+                        current_next_indicator: true,
+                        section_number,
+                        last_section_number: 1,
+                        table_data,
+                    }),
+                    crc_ok: true,
+                }],
+            };
+            let mut bytes = vec![0x47, 0x40, 0x00, 0x10];
+            track_try_unwrap!(psi.write_to(&mut bytes));
+            bytes.resize(TsPacket::SIZE, 0xFF);
+            bytes
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend(pat_section_packet(0, 0, 1));
+        bytes.extend(pat_section_packet(1, 0, 2)); // discards the sections of version 0
+        bytes.extend(pat_section_packet(1, 1, 3));
+
+        let mut reader = TsPacketReader::new(&bytes[..]);
+        for _ in 0..2 {
+            let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+            assert!(matches!(packet.payload, Some(TsPayload::Raw(_))));
+        }
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        let Some(TsPayload::Pat(pat)) = packet.payload else {
+            panic!("{:?}", packet.payload);
+        };
+        assert_eq!(pat.version_number.as_u8(), 1);
+        let program_nums = pat
+            .table
+            .iter()
+            .map(|pa| pa.program_num)
+            .collect::<Vec<_>>();
+        assert_eq!(program_nums, [2, 3]);
+    }
+
+    #[test]
+    fn private_psi() {
+        use psi::{Psi, PsiTable, PsiTableHeader, PsiTableSyntax};
+
+        let pid = Pid::new(0x300).unwrap();
+        let table = Psi {
+            tables: vec![PsiTable {
+                header: PsiTableHeader {
+                    table_id: 0x80,
+                    private_bit: true,
+                },
+                syntax: Some(PsiTableSyntax {
+                    table_id_extension: 7,
+                    version_number: VersionNumber::from_u8(3).unwrap(),
+                    current_next_indicator: true,
+                    section_number: 0,
+                    last_section_number: 0,
+                    table_data: (0..300).map(|i| i as u8).collect(),
+                }),
+                crc_ok: true,
+            }],
+        };
+        let short_form = Psi {
+            tables: vec![PsiTable {
+                header: PsiTableHeader {
+                    table_id: 0x81,
+                    private_bit: true,
+                },
+                syntax: None,
+                crc_ok: true,
+            }],
+        };
+
+        // The table spans two packets
+        let mut section = Vec::new();
+        track_try_unwrap!(table.write_to(&mut section));
+        let mut writer = TsPacketWriter::new(Vec::new());
+        for (i, chunk) in section.chunks(payload::Bytes::MAX_SIZE).enumerate() {
+            let packet = track_try_unwrap!(TsPacket::builder(pid)
+                .continuity(ContinuityCounter::from_u8(i as u8).unwrap())
+                .payload_unit_start_indicator(i == 0)
+                .payload_raw(chunk)
+                .build());
+            track_try_unwrap!(writer.write_ts_packet(&packet));
+        }
+        let packet = track_try_unwrap!(TsPacket::builder(pid)
+            .continuity(ContinuityCounter::from_u8(2).unwrap())
+            .payload(TsPayload::Psi(short_form.clone()))
+            .build());
+        assert_eq!(packet.to_string(), "PID=0x0300 CC=2 PUSI PSI[TID=0x81]");
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+
+        let stream = writer.into_stream();
+        let mut reader = TsPacketReader::new(&stream[..]);
+        reader.register_pid(pid, PidKind::Psi);
+        let packets = track_try_unwrap!(reader.into_iter().collect::<crate::Result<Vec<_>>>());
+        assert!(matches!(packets[0].payload, Some(TsPayload::Raw(_))));
+        assert_eq!(packets[1].payload.as_ref().unwrap().as_psi(), Some(&table));
+        assert_eq!(
+            packets[2].payload.as_ref().unwrap().as_psi(),
+            Some(&short_form)
+        );
+
+        let psi = packets[1].payload.as_ref().unwrap().as_psi().unwrap();
+        let sections = track_try_unwrap!(psi.table_sections(0x80));
+        assert_eq!(sections[0].table_data.len(), 300);
+    }
+
+    #[test]
+    fn pid17_psi() {
+        let mut reader = TsPacketReader::new(pid17_packet_bytes());
+        reader.register_pid(Pid::from(17), PidKind::Psi);
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        let psi = packet.payload.as_ref().unwrap().as_psi().unwrap();
+        assert_eq!(psi.tables[0].header.table_id, 0x42); // service description table
+        assert!(psi.tables[0].crc_ok);
+    }
+
     #[test]
     fn pid17() {
         let mut reader = TsPacketReader::new(pid17_packet_bytes());
@@ -197,4 +1779,49 @@ mod test {
             255, 255, 255, 255, 255, 255, 255, 25,
         ][..]
     }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary_round_trip() {
+        use alloc::collections::BTreeMap;
+        use arbitrary::{Arbitrary, Unstructured};
+
+        // Deterministic pseudo-random inputs (xorshift64)
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        let mut input = vec![0; 4096];
+        for _ in 0..2000 {
+            for b in &mut input {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                *b = state as u8;
+            }
+            let packet = TsPacket::arbitrary(&mut Unstructured::new(&input)).unwrap();
+
+            let format = if packet.arrival_timestamp.is_some() {
+                PacketFormat::M2ts
+            } else {
+                PacketFormat::Ts
+            };
+            let mut writer = TsPacketWriter::new(Vec::new());
+            writer.set_packet_format(format);
+            track_try_unwrap!(writer.write_ts_packet(&packet));
+
+            let kind = match packet.payload {
+                Some(TsPayload::Pmt(_)) => Some(PidKind::Pmt),
+                Some(TsPayload::Pes(_)) => Some(PidKind::Pes),
+                Some(TsPayload::Section(_)) => Some(PidKind::Section),
+                Some(TsPayload::Psi(_)) => Some(PidKind::Psi),
+                _ => None,
+            };
+            let pids = kind
+                .map(|kind| (packet.header.pid, kind))
+                .into_iter()
+                .collect::<BTreeMap<_, _>>();
+            let mut reader = TsPacketReader::with_pids(&writer.stream()[..], pids);
+            reader.set_packet_format(format);
+            let read = track_try_unwrap!(reader.read_ts_packet());
+            assert_eq!(read.as_ref(), Some(&packet), "{}", packet);
+        }
+    }
 }
@@ -1,13 +1,21 @@
-use crate::ts::payload::Bytes;
+use crate::io::Read;
+use crate::ts::TsPacket;
 use crate::Result;
-use std::io::Read;
 
 /// Payload for null packets.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Null;
 impl Null {
-    pub(super) fn read_from<R: Read>(reader: R) -> Result<Self> {
-        let _ = track!(Bytes::read_from(reader))?;
+    pub(super) fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+        let mut buf = [0; TsPacket::SIZE];
+        while track_io!(reader.read(&mut buf))? > 0 {}
+        Ok(Null)
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Null {
+    fn arbitrary(_u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
         Ok(Null)
     }
 }
@@ -0,0 +1,289 @@
+use crate::time::ClockReference;
+#[cfg(feature = "std")]
+use crate::ts::WriteTsPacket;
+use crate::ts::{Pid, TsPacket};
+#[cfg(feature = "std")]
+use crate::Result;
+use alloc::collections::VecDeque;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// Scheduler that computes the transmit time of each packet from the PCRs in the stream.
+///
+/// The transmit times (deadlines) are relative to the first PCR, and
+/// the times of the packets between two consecutive PCRs are linearly interpolated.
+/// Since the interpolation requires the next PCR, the packets are held until it arrives
+/// (or `finish` is called).
+///
+/// If a PCR has the discontinuity indicator set, or the interval to the previous PCR is negative or
+/// exceeds the maximum interval, the packets are scheduled with the per-packet interval of
+/// the previous PCR segment instead.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::ts::PcrPacer;
+///
+/// let mut pacer = PcrPacer::new();
+/// # let packets = Vec::new();
+/// for packet in packets {
+///     pacer.push(packet);
+///     while let Some((deadline, packet)) = pacer.pop() {
+///         // Sends `packet` at `deadline` (relative to the start of the transmission).
+///     }
+/// }
+/// pacer.finish();
+/// ```
+#[derive(Debug)]
+pub struct PcrPacer {
+    pcr_pid: Option<Pid>,
+    max_pcr_interval: Duration,
+    pending: VecDeque<TsPacket>,
+    scheduled: VecDeque<(Duration, TsPacket)>,
+    last_pcr: Option<(ClockReference, Duration)>,
+    packet_interval: Duration,
+}
+impl PcrPacer {
+    /// Default value of the maximum interval between two consecutive PCRs.
+    pub const DEFAULT_MAX_PCR_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Makes a new `PcrPacer` instance.
+    pub fn new() -> Self {
+        PcrPacer {
+            pcr_pid: None,
+            max_pcr_interval: Self::DEFAULT_MAX_PCR_INTERVAL,
+            pending: VecDeque::new(),
+            scheduled: VecDeque::new(),
+            last_pcr: None,
+            packet_interval: Duration::ZERO,
+        }
+    }
+
+    /// Sets the PID whose PCRs are used for the scheduling.
+    ///
+    /// If `None` is specified (the default), the first PID carrying a PCR is used.
+    pub fn set_pcr_pid(&mut self, pid: Option<Pid>) {
+        self.pcr_pid = pid;
+    }
+
+    /// Sets the maximum interval between two consecutive PCRs.
+    ///
+    /// Longer intervals are regarded as discontinuities.
+    /// The default value is `PcrPacer::DEFAULT_MAX_PCR_INTERVAL` (1 second).
+    pub fn set_max_pcr_interval(&mut self, interval: Duration) {
+        self.max_pcr_interval = interval;
+    }
+
+    /// Adds a packet to the schedule.
+    pub fn push(&mut self, packet: TsPacket) {
+        let pcr = packet
+            .adaptation_field
+            .as_ref()
+            .and_then(|a| a.pcr.map(|pcr| (pcr, a.discontinuity_indicator)))
+            .filter(|_| self.pcr_pid.map_or(true, |pid| pid == packet.header.pid));
+        if pcr.is_some() && self.pcr_pid.is_none() {
+            self.pcr_pid = Some(packet.header.pid);
+        }
+        self.pending.push_back(packet);
+        if let Some((pcr, discontinuity)) = pcr {
+            self.schedule(pcr, discontinuity);
+        }
+    }
+
+    /// Takes the next scheduled packet with its transmit time.
+    pub fn pop(&mut self) -> Option<(Duration, TsPacket)> {
+        self.scheduled.pop_front()
+    }
+
+    /// Schedules all the pending packets, assuming that the stream has reached the end.
+    ///
+    /// The packets following the last PCR are scheduled with the per-packet interval of the last PCR segment.
+    pub fn finish(&mut self) {
+        let start = self.last_pcr.map_or(Duration::ZERO, |(_, t)| t);
+        let mut deadline = start;
+        for packet in self.pending.drain(..) {
+            deadline += self.packet_interval;
+            self.scheduled.push_back((deadline, packet));
+        }
+        self.last_pcr = self.last_pcr.map(|(pcr, _)| (pcr, deadline));
+    }
+
+    fn schedule(&mut self, pcr: ClockReference, discontinuity: bool) {
+        let Some((last_pcr, last_deadline)) = self.last_pcr else {
+            // Packets preceding the first PCR are sent immediately
+            for packet in self.pending.drain(..) {
+                self.scheduled.push_back((Duration::ZERO, packet));
+            }
+            self.last_pcr = Some((pcr, Duration::ZERO));
+            return;
+        };
+
+        let n = self.pending.len() as u32;
+        let delta = pcr.wrapping_sub(last_pcr);
+        let elapsed = Duration::from_nanos(delta * 1000 / 27);
+        let elapsed = if discontinuity || elapsed > self.max_pcr_interval {
+            self.packet_interval * n
+        } else {
+            self.packet_interval = elapsed / n;
+            elapsed
+        };
+        for (i, packet) in self.pending.drain(..).enumerate() {
+            let offset = elapsed.as_nanos() * (i as u128 + 1) / u128::from(n);
+            let deadline = last_deadline + Duration::from_nanos(offset as u64);
+            self.scheduled.push_back((deadline, packet));
+        }
+        self.last_pcr = Some((pcr, last_deadline + elapsed));
+    }
+}
+impl Default for PcrPacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// TS packet writer that sends the packets at the rate dictated by their PCRs.
+///
+/// The packets are scheduled by `PcrPacer`, and the current thread sleeps until the transmit time
+/// of each packet before it is written to the inner writer.
+/// Note that `finish` must be called at the end of the stream to write the remaining packets.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct PacedTsPacketWriter<W> {
+    inner: W,
+    pacer: PcrPacer,
+    start: Option<Instant>,
+}
+#[cfg(feature = "std")]
+impl<W: WriteTsPacket> PacedTsPacketWriter<W> {
+    /// Makes a new `PacedTsPacketWriter` instance.
+    pub fn new(inner: W) -> Self {
+        PacedTsPacketWriter {
+            inner,
+            pacer: PcrPacer::new(),
+            start: None,
+        }
+    }
+
+    /// Returns a mutable reference to the scheduler.
+    ///
+    /// This can be used to change the settings of the scheduling.
+    pub fn pacer_mut(&mut self) -> &mut PcrPacer {
+        &mut self.pacer
+    }
+
+    /// Writes all the pending packets.
+    pub fn finish(&mut self) -> Result<()> {
+        self.pacer.finish();
+        track!(self.write_scheduled_packets())
+    }
+
+    /// Returns a reference to the inner writer.
+    pub fn inner_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Converts `PacedTsPacketWriter` into the inner writer.
+    ///
+    /// Note that the pending packets are discarded.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn write_scheduled_packets(&mut self) -> Result<()> {
+        while let Some((deadline, packet)) = self.pacer.pop() {
+            let start = *self.start.get_or_insert_with(Instant::now);
+            let now = Instant::now();
+            std::thread::sleep((start + deadline).saturating_duration_since(now));
+            track!(self.inner.write_ts_packet(&packet))?;
+        }
+        Ok(())
+    }
+}
+#[cfg(feature = "std")]
+impl<W: WriteTsPacket> WriteTsPacket for PacedTsPacketWriter<W> {
+    fn write_ts_packet(&mut self, packet: &TsPacket) -> Result<()> {
+        self.pacer.push(packet.clone());
+        track!(self.write_scheduled_packets())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ts::payload::Bytes;
+    use crate::ts::TsPayload;
+    use crate::ts::{AdaptationField, ContinuityCounter, TransportScramblingControl, TsHeader};
+
+    fn packet(pcr: Option<u64>, discontinuity_indicator: bool) -> TsPacket {
+        TsPacket {
+            header: TsHeader {
+                transport_error_indicator: false,
+                payload_unit_start_indicator: false,
+                transport_priority: false,
+                pid: Pid::new(256).unwrap(),
+                transport_scrambling_control: TransportScramblingControl::NotScrambled,
+                continuity_counter: ContinuityCounter::new(),
+            },
+            adaptation_field: pcr.map(|pcr| AdaptationField {
+                discontinuity_indicator,
+                random_access_indicator: false,
+                es_priority_indicator: false,
+                pcr: Some(ClockReference::new(pcr).unwrap()),
+                opcr: None,
+                splice_countdown: None,
+                transport_private_data: vec![],
+                extension: None,
+            }),
+            payload: Some(TsPayload::Raw(Bytes::new(&[]).unwrap())),
+            arrival_timestamp: None,
+        }
+    }
+
+    fn deadlines(pacer: &mut PcrPacer) -> Vec<u64> {
+        core::iter::from_fn(|| pacer.pop())
+            .map(|(d, _)| d.as_millis() as u64)
+            .collect()
+    }
+
+    #[test]
+    fn interpolation() {
+        let mut pacer = PcrPacer::new();
+        pacer.push(packet(None, false));
+        pacer.push(packet(Some(27_000_000), false));
+        assert_eq!(deadlines(&mut pacer), [0, 0]);
+
+        for _ in 0..3 {
+            pacer.push(packet(None, false));
+        }
+        assert!(deadlines(&mut pacer).is_empty());
+        pacer.push(packet(Some(27_000_000 + 2_700_000), false));
+        assert_eq!(deadlines(&mut pacer), [25, 50, 75, 100]);
+
+        pacer.push(packet(None, false));
+        pacer.finish();
+        assert_eq!(deadlines(&mut pacer), [125]);
+    }
+
+    #[test]
+    fn discontinuity() {
+        let mut pacer = PcrPacer::new();
+        pacer.push(packet(Some(27_000_000), false));
+        pacer.push(packet(Some(27_000_000 + 270_000), false));
+        assert_eq!(deadlines(&mut pacer), [0, 10]);
+
+        // Backward jump
+        pacer.push(packet(None, false));
+        pacer.push(packet(Some(1_000), false));
+        assert_eq!(deadlines(&mut pacer), [20, 30]);
+
+        // Explicit discontinuity
+        pacer.push(packet(Some(27_000_000 * 3600), true));
+        assert_eq!(deadlines(&mut pacer), [40]);
+
+        // Wrap around
+        pacer.push(packet(Some(ClockReference::MODULUS - 270_000), false));
+        pacer.push(packet(Some(0), false));
+        assert_eq!(deadlines(&mut pacer), [50, 60]);
+    }
+}
@@ -1,17 +1,32 @@
 use super::adaptation_field::AdaptationFieldControl;
-use crate::ts::payload::{Bytes, Null, Pat, Pes, Pmt, Section};
+use crate::io::{Read, ReadBytesExt, Write, WriteBytesExt};
+use crate::ts::payload::{Bytes, Mgt, Null, Pat, Pes, Pmt, Section, Tsdt, Vct};
+use crate::ts::psi::Psi;
 use crate::ts::{AdaptationField, ContinuityCounter, Pid, TransportScramblingControl};
 use crate::{ErrorKind, Result};
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{Cursor, Read, Write};
+use alloc::vec::Vec;
+use byteorder::BigEndian;
+use core::fmt;
 
 /// Transport stream packet.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TsPacket {
     pub header: TsHeader,
+
+    /// The adaptation field (if any).
+    ///
+    /// When reading, an adaptation field that only contains stuffing bytes (i.e., no flags are set) is `None`.
     pub adaptation_field: Option<AdaptationField>,
     pub payload: Option<TsPayload>,
+
+    /// The 4-byte header (`TP_extra_header`) preceding the packet in the M2TS format.
+    ///
+    /// The upper 2 bits are the copy permission indicator,
+    /// and the lower 30 bits are the arrival timestamp in 27MHz units.
+    /// This is `None` for the packets read in the other formats.
+    pub arrival_timestamp: Option<u32>,
 }
 impl TsPacket {
     /// Size of a packet in bytes.
@@ -22,12 +37,54 @@ impl TsPacket {
     /// Each packet starts with this byte.
     pub const SYNC_BYTE: u8 = 0x47;
 
-    pub(super) fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+    /// Makes a new `TsPacketBuilder` instance for a packet of `pid`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mpeg2ts::ts::{ContinuityCounter, Pid, TsPacket};
+    ///
+    /// let packet = TsPacket::builder(Pid::new(256).unwrap())
+    ///     .continuity(ContinuityCounter::from_u8(7).unwrap())
+    ///     .payload_raw(&[0; 184])
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(packet.payload.as_ref().and_then(|p| p.as_raw()).unwrap().len(), 184);
+    ///
+    /// // Too large payload
+    /// assert!(TsPacket::builder(Pid::new(256).unwrap())
+    ///     .payload_raw(&[0; 185])
+    ///     .build()
+    ///     .is_err());
+    /// ```
+    pub fn builder(pid: Pid) -> TsPacketBuilder {
+        TsPacketBuilder {
+            header: TsHeader {
+                transport_error_indicator: false,
+                payload_unit_start_indicator: false,
+                transport_priority: false,
+                pid,
+                transport_scrambling_control: TransportScramblingControl::NotScrambled,
+                continuity_counter: ContinuityCounter::new(),
+            },
+            adaptation_field: None,
+            payload: None,
+            arrival_timestamp: None,
+        }
+    }
+
+    /// Writes the packet.
+    ///
+    /// If `raw_tables` is `true`, the raw bytes of PAT and PMT payloads (if any) are written instead of the parsed fields.
+    pub(super) fn write_to<W: Write>(&self, mut writer: W, raw_tables: bool) -> Result<()> {
         let mut payload_buf = [0; TsPacket::SIZE - 4];
         let payload_len = if let Some(ref payload) = self.payload {
-            let mut writer = Cursor::new(&mut payload_buf[..]);
-            track!(payload.write_to(&mut writer))?;
-            writer.position() as usize
+            let mut writer = &mut payload_buf[..];
+            match payload.raw_table().filter(|_| raw_tables) {
+                Some(raw) => track_io!(writer.write_all(raw))?,
+                None => track!(payload.write_to(&mut writer))?,
+            }
+            TsPacket::SIZE - 4 - writer.len()
         } else {
             0
         };
@@ -54,14 +111,10 @@ impl TsPacket {
             (false, true) => AdaptationFieldControl::PayloadOnly,
             (false, false) => track_panic!(ErrorKind::InvalidInput, "Reserved for future use"),
         };
-        let payload_unit_start_indicator = !matches!(
-            self.payload,
-            Some(TsPayload::Raw(_)) | Some(TsPayload::Null(_)) | None
-        );
         track!(self.header.write_to(
             &mut writer,
             adaptation_field_control,
-            payload_unit_start_indicator
+            self.payload_unit_start_indicator()
         ))?;
 
         if let Some(ref adaptation_field) = self.adaptation_field {
@@ -77,22 +130,229 @@ impl TsPacket {
         track_io!(writer.write_all(&payload_buf[..payload_len]))?;
         Ok(())
     }
+
+    /// Returns the `payload_unit_start_indicator` that is actually written for this packet.
+    fn payload_unit_start_indicator(&self) -> bool {
+        match self.payload {
+            Some(TsPayload::Raw(_)) => self.header.payload_unit_start_indicator,
+            Some(TsPayload::Null(_)) | None => false,
+            _ => true,
+        }
+    }
+}
+impl fmt::Display for TsPacket {
+    /// Formats a one-line summary of the packet,
+    /// e.g., `PID=0x0100 CC=7 PUSI AF[RAI PCR=27000000] PES[SID=0xE0 PTS=90000 DATA=164]`.
+    ///
+    /// The `PUSI` flag reflects the value that is written for the payload (see `TsHeader::payload_unit_start_indicator`).
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let header = TsHeader {
+            payload_unit_start_indicator: self.payload_unit_start_indicator(),
+            ..self.header.clone()
+        };
+        write!(f, "{}", header)?;
+        if let Some(ref adaptation_field) = self.adaptation_field {
+            write!(f, " AF[{}]", adaptation_field)?;
+        }
+        if let Some(ref payload) = self.payload {
+            write!(f, " {}", payload)?;
+        }
+        Ok(())
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for TsPacket {
+    /// Generates a packet that is read back as is after being written.
+    ///
+    /// The header is made consistent with the payload:
+    /// - PAT and null payloads are carried by `Pid::PAT` and `Pid::NULL` respectively,
+    ///   and the other payloads by a PID in the range `0x0020..=0x1FFA`,
+    /// - `payload_unit_start_indicator` is set to the value that is actually written, and
+    /// - only `TsPayload::Raw` payloads may be scrambled.
+    ///
+    /// The adaptation field is dropped if it does not fit in the space left by the payload,
+    /// or if it has no fields set (since such a field is read as `None`).
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut header: TsHeader = u.arbitrary()?;
+        let payload: Option<TsPayload> = u.arbitrary()?;
+        let mut payload_len = 0;
+        if let Some(ref payload) = payload {
+            let mut buf = Vec::new();
+            payload.write_to(&mut buf).expect("Never fails");
+            payload_len = buf.len();
+
+            header.pid = match payload {
+                TsPayload::Pat(_) => Pid::new_const(Pid::PAT),
+                TsPayload::Null(_) => Pid::new_const(Pid::NULL),
+                _ => Pid::new_const(u.int_in_range(0x0020..=0x1FFA)?),
+            };
+            if !matches!(payload, TsPayload::Raw(_)) {
+                header.transport_scrambling_control = TransportScramblingControl::NotScrambled;
+            }
+        }
+
+        let adaptation_field = u.arbitrary::<Option<AdaptationField>>()?.filter(|a| {
+            *a != AdaptationField::default()
+                && a.external_size() <= AdaptationField::MAX_SIZE - payload_len
+        });
+        let mut packet = TsPacket {
+            header,
+            adaptation_field,
+            payload,
+            arrival_timestamp: u.arbitrary()?,
+        };
+        packet.header.payload_unit_start_indicator = packet.payload_unit_start_indicator();
+        Ok(packet)
+    }
+}
+
+/// Builder of `TsPacket`.
+///
+/// Unlike `WriteTsPacket::write_ts_packet`, `build` checks up front that the packet fits in `TsPacket::SIZE` bytes.
+#[derive(Debug, Clone)]
+pub struct TsPacketBuilder {
+    header: TsHeader,
+    adaptation_field: Option<AdaptationField>,
+    payload: Option<PendingPayload>,
+    arrival_timestamp: Option<u32>,
+}
+impl TsPacketBuilder {
+    /// Sets the continuity counter.
+    pub fn continuity(mut self, counter: ContinuityCounter) -> Self {
+        self.header.continuity_counter = counter;
+        self
+    }
+
+    /// Sets `payload_unit_start_indicator`.
+    ///
+    /// This is only taken into account for raw payloads (see `TsHeader::payload_unit_start_indicator`).
+    pub fn payload_unit_start_indicator(mut self, indicator: bool) -> Self {
+        self.header.payload_unit_start_indicator = indicator;
+        self
+    }
+
+    /// Sets `transport_priority`.
+    pub fn transport_priority(mut self, priority: bool) -> Self {
+        self.header.transport_priority = priority;
+        self
+    }
+
+    /// Sets `transport_scrambling_control`.
+    pub fn scrambling_control(mut self, control: TransportScramblingControl) -> Self {
+        self.header.transport_scrambling_control = control;
+        self
+    }
+
+    /// Sets the adaptation field.
+    pub fn adaptation(mut self, adaptation_field: AdaptationField) -> Self {
+        self.adaptation_field = Some(adaptation_field);
+        self
+    }
+
+    /// Sets the payload.
+    pub fn payload(mut self, payload: TsPayload) -> Self {
+        self.payload = Some(PendingPayload::Payload(payload));
+        self
+    }
+
+    /// Sets a PES payload.
+    pub fn payload_pes(self, pes: Pes) -> Self {
+        self.payload(TsPayload::Pes(pes))
+    }
+
+    /// Sets a raw payload.
+    ///
+    /// The size of `bytes` is validated by `build`.
+    pub fn payload_raw(mut self, bytes: &[u8]) -> Self {
+        self.payload = Some(PendingPayload::Raw(bytes.to_vec()));
+        self
+    }
+
+    /// Sets the arrival timestamp (see `TsPacket::arrival_timestamp`).
+    pub fn arrival_timestamp(mut self, timestamp: u32) -> Self {
+        self.arrival_timestamp = Some(timestamp);
+        self
+    }
+
+    /// Builds a `TsPacket` instance.
+    ///
+    /// # Errors
+    ///
+    /// If the adaptation field and the payload do not fit in a packet,
+    /// it will return an `ErrorKind::InvalidInput` error that reports the number of overflowing bytes.
+    pub fn build(self) -> Result<TsPacket> {
+        let payload = match self.payload {
+            None => None,
+            Some(PendingPayload::Payload(p)) => Some(p),
+            Some(PendingPayload::Raw(bytes)) => {
+                track_assert!(
+                    bytes.len() <= Bytes::MAX_SIZE,
+                    ErrorKind::InvalidInput,
+                    "Too large payload: size={}, overflow={}",
+                    bytes.len(),
+                    bytes.len() - Bytes::MAX_SIZE
+                );
+                Some(TsPayload::Raw(track!(Bytes::new(&bytes))?))
+            }
+        };
+
+        let payload_len = if let Some(ref payload) = payload {
+            let mut buf = Vec::with_capacity(TsPacket::SIZE);
+            track!(payload.write_to(&mut buf))?;
+            buf.len()
+        } else {
+            0
+        };
+        let adaptation_field_len = self
+            .adaptation_field
+            .as_ref()
+            .map_or(0, |a| a.external_size());
+        let len = adaptation_field_len + payload_len;
+        track_assert!(
+            len <= TsPacket::SIZE - 4,
+            ErrorKind::InvalidInput,
+            "Too large packet: adaptation_field={}, payload={}, overflow={}",
+            adaptation_field_len,
+            payload_len,
+            len - (TsPacket::SIZE - 4)
+        );
+
+        Ok(TsPacket {
+            header: self.header,
+            adaptation_field: self.adaptation_field,
+            payload,
+            arrival_timestamp: self.arrival_timestamp,
+        })
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+enum PendingPayload {
+    Payload(TsPayload),
+    Raw(Vec<u8>),
 }
 
 /// TS packet header.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TsHeader {
     pub transport_error_indicator: bool,
+
+    /// `true` if the payload starts a PES packet or a PSI section.
+    ///
+    /// When writing a packet, this flag is only taken into account for `TsPayload::Raw` payloads,
+    /// since it is implied by the other payload types.
+    pub payload_unit_start_indicator: bool,
+
     pub transport_priority: bool,
     pub pid: Pid,
     pub transport_scrambling_control: TransportScramblingControl,
     pub continuity_counter: ContinuityCounter,
 }
 impl TsHeader {
-    pub(super) fn read_from<R: Read>(
-        mut reader: R,
-    ) -> Result<(Self, AdaptationFieldControl, bool)> {
+    pub(super) fn read_from<R: Read>(mut reader: R) -> Result<(Self, AdaptationFieldControl)> {
         let sync_byte = track_io!(reader.read_u8())?;
         track_assert_eq!(sync_byte, TsPacket::SYNC_BYTE, ErrorKind::InvalidInput);
 
@@ -109,16 +369,13 @@ impl TsHeader {
 
         let header = TsHeader {
             transport_error_indicator,
+            payload_unit_start_indicator,
             transport_priority,
             pid,
             transport_scrambling_control,
             continuity_counter,
         };
-        Ok((
-            header,
-            adaptation_field_control,
-            payload_unit_start_indicator,
-        ))
+        Ok((header, adaptation_field_control))
     }
 
     fn write_to<W: Write>(
@@ -143,27 +400,265 @@ impl TsHeader {
         Ok(())
     }
 }
+impl fmt::Display for TsHeader {
+    /// Formats a one-line summary of the header, e.g., `PID=0x0100 CC=7 PUSI`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PID=0x{:04X} CC={}",
+            self.pid.as_u16(),
+            self.continuity_counter.as_u8()
+        )?;
+        if self.payload_unit_start_indicator {
+            write!(f, " PUSI")?;
+        }
+        if self.transport_error_indicator {
+            write!(f, " TEI")?;
+        }
+        if self.transport_priority {
+            write!(f, " PRIO")?;
+        }
+        match self.transport_scrambling_control {
+            TransportScramblingControl::NotScrambled => {}
+            TransportScramblingControl::ScrambledWithEvenKey => write!(f, " SCR=EVEN")?,
+            TransportScramblingControl::ScrambledWithOddKey => write!(f, " SCR=ODD")?,
+        }
+        Ok(())
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for TsHeader {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(TsHeader {
+            transport_error_indicator: u.arbitrary()?,
+            payload_unit_start_indicator: u.arbitrary()?,
+            transport_priority: u.arbitrary()?,
+            pid: u.arbitrary()?,
+            transport_scrambling_control: u.arbitrary()?,
+            continuity_counter: u.arbitrary()?,
+        })
+    }
+}
 
 /// TS packet payload.
 #[allow(missing_docs, clippy::large_enum_variant)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TsPayload {
     Pat(Pat),
     Pmt(Pmt),
     Pes(Pes),
     Section(Section),
+    Tsdt(Tsdt),
+
+    /// PSI sections of a PID registered as `PidKind::Psi`.
+    Psi(Psi),
+
+    /// ATSC Master Guide Table (on `Pid::PSIP`).
+    Mgt(Mgt),
+
+    /// ATSC Virtual Channel Table (on `Pid::PSIP`).
+    Vct(Vct),
+
     Null(Null),
     Raw(Bytes),
 }
 impl TsPayload {
-    fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+    /// Returns the PAT if this is `TsPayload::Pat`.
+    pub fn as_pat(&self) -> Option<&Pat> {
+        if let TsPayload::Pat(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the PMT if this is `TsPayload::Pmt`.
+    pub fn as_pmt(&self) -> Option<&Pmt> {
+        if let TsPayload::Pmt(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the PES payload if this is `TsPayload::Pes`.
+    pub fn as_pes(&self) -> Option<&Pes> {
+        if let TsPayload::Pes(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the section if this is `TsPayload::Section`.
+    pub fn as_section(&self) -> Option<&Section> {
+        if let TsPayload::Section(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the TSDT if this is `TsPayload::Tsdt`.
+    pub fn as_tsdt(&self) -> Option<&Tsdt> {
+        if let TsPayload::Tsdt(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the PSI sections if this is `TsPayload::Psi`.
+    pub fn as_psi(&self) -> Option<&Psi> {
+        if let TsPayload::Psi(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the MGT if this is `TsPayload::Mgt`.
+    pub fn as_mgt(&self) -> Option<&Mgt> {
+        if let TsPayload::Mgt(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the VCT if this is `TsPayload::Vct`.
+    pub fn as_vct(&self) -> Option<&Vct> {
+        if let TsPayload::Vct(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the bytes if this is `TsPayload::Raw`.
+    pub fn as_raw(&self) -> Option<&Bytes> {
+        if let TsPayload::Raw(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Converts into the PES payload if this is `TsPayload::Pes`.
+    pub fn into_pes(self) -> Option<Pes> {
+        if let TsPayload::Pes(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Converts into the bytes if this is `TsPayload::Raw`.
+    pub fn into_raw(self) -> Option<Bytes> {
+        if let TsPayload::Raw(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the raw bytes of the PAT or PMT (see `Pat::raw`).
+    fn raw_table(&self) -> Option<&[u8]> {
+        match self {
+            TsPayload::Pat(x) => x.raw.as_deref(),
+            TsPayload::Pmt(x) => x.raw.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub(super) fn write_to<W: Write>(&self, writer: W) -> Result<()> {
         match *self {
             TsPayload::Pat(ref x) => track!(x.write_to(writer)),
             TsPayload::Pmt(ref x) => track!(x.write_to(writer)),
             TsPayload::Pes(ref x) => track!(x.write_to(writer)),
             TsPayload::Section(ref x) => track!(x.write_to(writer)),
+            TsPayload::Tsdt(ref x) => track!(x.write_to(writer)),
+            TsPayload::Psi(ref x) => track!(x.write_to(writer)),
+            TsPayload::Mgt(ref x) => track!(x.write_to(writer)),
+            TsPayload::Vct(ref x) => track!(x.write_to(writer)),
             TsPayload::Null(_) => Ok(()),
             TsPayload::Raw(ref x) => track!(x.write_to(writer)),
         }
     }
 }
+impl fmt::Display for TsPayload {
+    /// Formats a one-line summary of the payload, e.g., `PMT[PROG=1 VER=0 PCR_PID=0x0100 ES=2]`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TsPayload::Pat(x) => write!(
+                f,
+                "PAT[TSID={} VER={} PROGS={}]",
+                x.transport_stream_id,
+                x.version_number.as_u8(),
+                x.table.len()
+            ),
+            TsPayload::Pmt(x) => {
+                write!(
+                    f,
+                    "PMT[PROG={} VER={}",
+                    x.program_num,
+                    x.version_number.as_u8()
+                )?;
+                if let Some(pcr_pid) = x.pcr_pid {
+                    write!(f, " PCR_PID=0x{:04X}", pcr_pid.as_u16())?;
+                }
+                write!(f, " ES={}]", x.es_info.len())
+            }
+            TsPayload::Pes(x) => write!(f, "PES[{} DATA={}]", x.header, x.data.len()),
+            TsPayload::Section(x) => write!(f, "SECTION[DATA={}]", x.data.len()),
+            TsPayload::Tsdt(x) => write!(
+                f,
+                "TSDT[VER={} DESCS={}]",
+                x.version_number.as_u8(),
+                x.descriptors.len()
+            ),
+            TsPayload::Psi(x) => {
+                write!(f, "PSI[")?;
+                for (i, table) in x.tables.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "TID=0x{:02X}", table.header.table_id)?;
+                }
+                write!(f, "]")
+            }
+            TsPayload::Mgt(x) => write!(
+                f,
+                "MGT[VER={} TABLES={}]",
+                x.version_number.as_u8(),
+                x.tables.len()
+            ),
+            TsPayload::Vct(x) => write!(
+                f,
+                "{}[TSID={} VER={} CHANNELS={}]",
+                if x.is_cable { "CVCT" } else { "TVCT" },
+                x.transport_stream_id,
+                x.version_number.as_u8(),
+                x.channels.len()
+            ),
+            TsPayload::Null(_) => write!(f, "NULL"),
+            TsPayload::Raw(x) => write!(f, "RAW[DATA={}]", x.len()),
+        }
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for TsPayload {
+    /// Generates a payload that fits in a TS packet.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.int_in_range(0..=6)? {
+            0 => TsPayload::Pat(u.arbitrary()?),
+            1 => TsPayload::Pmt(u.arbitrary()?),
+            2 => TsPayload::Pes(u.arbitrary()?),
+            3 => TsPayload::Section(u.arbitrary()?),
+            4 => TsPayload::Psi(u.arbitrary()?),
+            5 => TsPayload::Null(u.arbitrary()?),
+            _ => TsPayload::Raw(u.arbitrary()?),
+        })
+    }
+}
@@ -0,0 +1,241 @@
+use crate::io::Read;
+use crate::pes::PesHeader;
+use crate::time::ClockReference;
+use crate::ts::payload::{Bytes, Null, Pat, Pmt};
+use crate::ts::psi::{self, Psi};
+use crate::ts::{AdaptationField, Pid, TsHeader, TsPacket, TsPayload};
+use crate::{ErrorKind, Result};
+
+/// Transport stream packet that borrows its adaptation field and payload from the packet bytes.
+///
+/// Unlike `TsPacketReader`, this does not copy the payload nor keep track of the PIDs,
+/// so the payload is interpreted lazily (and only if needed) via `pes_header`, `pat`, and `pmt`.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TsPacketRef<'a> {
+    pub header: TsHeader,
+    pub adaptation_field: Option<AdaptationFieldRef<'a>>,
+
+    /// Payload bytes following the header and the adaptation field (if any).
+    pub payload: Option<&'a [u8]>,
+}
+impl<'a> TsPacketRef<'a> {
+    /// Parses the header of a TS packet, and splits the rest into the adaptation field and the payload.
+    pub fn parse(bytes: &'a [u8; TsPacket::SIZE]) -> Result<Self> {
+        let (header, adaptation_field_control) = track!(TsHeader::read_from(&bytes[..]))?;
+        let mut rest = &bytes[4..];
+
+        let mut adaptation_field = None;
+        if adaptation_field_control.has_adaptation_field() {
+            let len = usize::from(rest[0]);
+            track_assert!(
+                len < rest.len(),
+                ErrorKind::InvalidInput,
+                "Too large adaptation field: len={}",
+                len
+            );
+            if len > 0 {
+                adaptation_field = Some(AdaptationFieldRef {
+                    bytes: &rest[1..=len],
+                });
+            }
+            rest = &rest[1 + len..];
+        }
+
+        let payload = if adaptation_field_control.has_payload() {
+            Some(rest)
+        } else {
+            None
+        };
+        Ok(TsPacketRef {
+            header,
+            adaptation_field,
+            payload,
+        })
+    }
+
+    /// Parses the PES header at the start of the payload.
+    ///
+    /// If the payload does not start a PES packet, it will return `Ok(None)`.
+    pub fn pes_header(&self) -> Result<Option<PesHeader>> {
+        let Some(payload) = self.unit_start_payload() else {
+            return Ok(None);
+        };
+        if !payload.starts_with(&[0, 0, 1]) {
+            return Ok(None);
+        }
+        let (header, _) = track!(PesHeader::read_from(payload, false))?;
+        Ok(Some(header))
+    }
+
+    /// Parses the PAT in the payload.
+    ///
+    /// If the packet is not on `Pid::PAT` or the payload does not contain the whole of a single-section table,
+    /// it will return `Ok(None)`.
+    pub fn pat(&self) -> Result<Option<Pat>> {
+        if self.header.pid.as_u16() != Pid::PAT {
+            return Ok(None);
+        }
+        let Some(psi) = track!(self.psi())? else {
+            return Ok(None);
+        };
+        track!(Pat::from_psi(psi, false)).map(Some)
+    }
+
+    /// Parses the PMT in the payload.
+    ///
+    /// Since the packet does not know which PIDs carry PMTs, the caller must check it in advance (e.g., by `pat`).
+    /// If the payload does not contain the whole of a single-section table, it will return `Ok(None)`.
+    pub fn pmt(&self) -> Result<Option<Pmt>> {
+        let Some(psi) = track!(self.psi())? else {
+            return Ok(None);
+        };
+        track!(Pmt::from_psi(psi, false)).map(Some)
+    }
+
+    /// Converts the packet into an owned `TsPacket`.
+    ///
+    /// The payload is converted into `TsPayload::Raw` (or `TsPayload::Null` for null packets),
+    /// since the interpretation of the other payloads depends on the state of the PIDs.
+    pub fn to_ts_packet(&self) -> Result<TsPacket> {
+        let adaptation_field = if let Some(a) = &self.adaptation_field {
+            track!(a.to_adaptation_field())?
+        } else {
+            None
+        };
+        let payload = match self.payload {
+            Some(_) if self.header.pid.as_u16() == Pid::NULL => Some(TsPayload::Null(Null)),
+            Some(payload) => Some(TsPayload::Raw(track!(Bytes::new(payload))?)),
+            None => None,
+        };
+        Ok(TsPacket {
+            header: self.header.clone(),
+            adaptation_field,
+            payload,
+            arrival_timestamp: None,
+        })
+    }
+
+    fn unit_start_payload(&self) -> Option<&'a [u8]> {
+        if self.header.payload_unit_start_indicator {
+            self.payload
+        } else {
+            None
+        }
+    }
+
+    fn psi(&self) -> Result<Option<Psi>> {
+        let Some(payload) = self.unit_start_payload() else {
+            return Ok(None);
+        };
+        match psi::payload_size_of_first_section(payload) {
+            Some(size) if size <= payload.len() => {
+                let psi = track!(Psi::read_from(&payload[..size], false))?;
+                track!(psi.check_crc())?;
+                Ok(Some(psi))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Adaptation field that borrows its bytes from the packet bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AdaptationFieldRef<'a> {
+    bytes: &'a [u8],
+}
+impl<'a> AdaptationFieldRef<'a> {
+    /// Returns the bytes of the adaptation field (excluding the `adaptation_field_length` byte).
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns the discontinuity indicator.
+    pub fn discontinuity_indicator(&self) -> bool {
+        (self.bytes[0] & 0b1000_0000) != 0
+    }
+
+    /// Returns the random access indicator.
+    pub fn random_access_indicator(&self) -> bool {
+        (self.bytes[0] & 0b0100_0000) != 0
+    }
+
+    /// Returns the elementary stream priority indicator.
+    pub fn es_priority_indicator(&self) -> bool {
+        (self.bytes[0] & 0b0010_0000) != 0
+    }
+
+    /// Returns the PCR (if any).
+    pub fn pcr(&self) -> Option<ClockReference> {
+        if (self.bytes[0] & 0b0001_0000) == 0 {
+            return None;
+        }
+        self.clock_reference_at(1)
+    }
+
+    /// Returns the OPCR (if any).
+    pub fn opcr(&self) -> Option<ClockReference> {
+        if (self.bytes[0] & 0b0000_1000) == 0 {
+            return None;
+        }
+        let offset = if (self.bytes[0] & 0b0001_0000) != 0 {
+            7
+        } else {
+            1
+        };
+        self.clock_reference_at(offset)
+    }
+
+    /// Parses the whole of the adaptation field.
+    ///
+    /// It will return `Ok(None)` if the field consists only of stuffing bytes.
+    pub fn to_adaptation_field(&self) -> Result<Option<AdaptationField>> {
+        let len = [self.bytes.len() as u8];
+        track!(AdaptationField::read_from((&len[..]).chain(self.bytes)))
+    }
+
+    fn clock_reference_at(&self, offset: usize) -> Option<ClockReference> {
+        let bytes = self.bytes.get(offset..offset + 6)?;
+        ClockReference::read_pcr_from(bytes).ok()
+    }
+}
+
+/// Iterator over the TS packets in a byte slice.
+///
+/// The slice must consist of 188-byte packets (i.e., `PacketFormat::Ts`).
+/// If the length of the slice is not a multiple of the packet size,
+/// the last item is an `ErrorKind::TruncatedPacket` error.
+#[derive(Debug, Clone)]
+pub struct TsPacketRefs<'a> {
+    bytes: &'a [u8],
+}
+impl<'a> TsPacketRefs<'a> {
+    /// Makes a new `TsPacketRefs` instance.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        TsPacketRefs { bytes }
+    }
+
+    fn next_packet(&mut self) -> Result<Option<TsPacketRef<'a>>> {
+        if self.bytes.is_empty() {
+            return Ok(None);
+        }
+        let Some((packet, rest)) = self.bytes.split_first_chunk::<{ TsPacket::SIZE }>() else {
+            let size = self.bytes.len();
+            self.bytes = &[];
+            track_panic!(
+                ErrorKind::TruncatedPacket,
+                "Truncated TS packet: size={}",
+                size
+            );
+        };
+        self.bytes = rest;
+        track!(TsPacketRef::parse(packet)).map(Some)
+    }
+}
+impl<'a> Iterator for TsPacketRefs<'a> {
+    type Item = Result<TsPacketRef<'a>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet().transpose()
+    }
+}
+impl core::iter::FusedIterator for TsPacketRefs<'_> {}
@@ -1,43 +1,61 @@
+use crate::io::{Read, ReadBytesExt, Write, WriteBytesExt};
 use crate::ts::psi::{Psi, PsiTable, PsiTableHeader, PsiTableSyntax};
 use crate::ts::{Pid, VersionNumber};
 use crate::{ErrorKind, Result};
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{Read, Write};
+use alloc::{vec, vec::Vec};
+use byteorder::BigEndian;
 
 /// Payload for PAT(Program Association Table) packets.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pat {
     pub transport_stream_id: u16,
     pub version_number: VersionNumber,
+
+    /// Whether the table is currently applicable.
+    ///
+    /// If `false`, the table is not yet applicable and shall be the next table to become valid.
+    pub is_current: bool,
     pub table: Vec<ProgramAssociation>,
+
+    /// Payload bytes of the TS packet that this table was read from
+    /// (i.e., the pointer field, the sections and the stuffing bytes as received).
+    ///
+    /// This is only set if `TsPacketReader::set_keep_raw_tables` is enabled,
+    /// and is written verbatim instead of the parsed fields if `TsPacketWriter::set_write_raw_tables` is enabled.
+    /// If the table spans multiple packets, this is the payload of the last one
+    /// (the preceding ones are returned as `TsPayload::Raw`).
+    /// Set this to `None` after modifying the table, so that the modification is written.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub raw: Option<Vec<u8>>,
 }
 impl Pat {
     const TABLE_ID: u8 = 0;
 
-    pub(super) fn read_from<R: Read>(reader: R) -> Result<Self> {
-        let mut psi = track!(Psi::read_from(reader))?;
-        track_assert_eq!(psi.tables.len(), 1, ErrorKind::InvalidInput);
-
-        let table = psi.tables.pop().expect("Never fails");
-        let header = table.header;
-        track_assert_eq!(header.table_id, Self::TABLE_ID, ErrorKind::InvalidInput);
-        track_assert!(!header.private_bit, ErrorKind::InvalidInput);
-
-        let syntax = track_assert_some!(table.syntax.as_ref(), ErrorKind::InvalidInput);
-        track_assert_eq!(syntax.section_number, 0, ErrorKind::InvalidInput);
-        track_assert_eq!(syntax.last_section_number, 0, ErrorKind::InvalidInput);
-        track_assert!(syntax.current_next_indicator, ErrorKind::InvalidInput);
+    pub(super) fn from_psi(psi: Psi, lenient: bool) -> Result<Self> {
+        track_assert!(
+            psi.tables.iter().all(|t| !t.header.private_bit),
+            ErrorKind::InvalidInput
+        );
+        let sections = track!(psi.table_sections(Self::TABLE_ID))?;
 
-        let mut reader = &syntax.table_data[..];
         let mut table = Vec::new();
-        while !reader.is_empty() {
-            table.push(track!(ProgramAssociation::read_from(&mut reader))?);
+        for syntax in &sections {
+            let mut reader = &syntax.table_data[..];
+            while !reader.is_empty() {
+                table.push(track!(ProgramAssociation::read_from(&mut reader, lenient))?);
+            }
         }
         Ok(Pat {
-            transport_stream_id: syntax.table_id_extension,
-            version_number: syntax.version_number,
+            transport_stream_id: sections[0].table_id_extension,
+            version_number: sections[0].version_number,
+            is_current: sections[0].current_next_indicator,
             table,
+            raw: None,
         })
     }
 
@@ -58,29 +76,55 @@ impl Pat {
         let syntax = Some(PsiTableSyntax {
             table_id_extension: self.transport_stream_id,
             version_number: self.version_number,
-            current_next_indicator: true,
+            current_next_indicator: self.is_current,
             section_number: 0,
             last_section_number: 0,
             table_data,
         });
-        let tables = vec![PsiTable { header, syntax }];
+        let tables = vec![PsiTable {
+            header,
+            syntax,
+            crc_ok: true,
+        }];
         Ok(Psi { tables })
     }
 }
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Pat {
+    /// Generates a table that fits in a TS packet.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        // 13 bytes are taken by the pointer field, the section header and syntax, and the CRC32
+        const MAX_PROGRAMS: usize = (crate::ts::payload::Bytes::MAX_SIZE - 13) / 4;
+
+        let len = u.arbitrary_len::<ProgramAssociation>()?.min(MAX_PROGRAMS);
+        Ok(Pat {
+            transport_stream_id: u.arbitrary()?,
+            version_number: u.arbitrary()?,
+            is_current: u.arbitrary()?,
+            table: (0..len)
+                .map(|_| u.arbitrary())
+                .collect::<arbitrary::Result<_>>()?,
+            raw: None,
+        })
+    }
+}
 
 /// An entry of a program association table.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProgramAssociation {
     pub program_num: u16,
 
     /// The packet identifier that contains the associated PMT.
+    ///
+    /// If `program_num` is `0`, this is the network PID that contains the network information table.
     pub program_map_pid: Pid,
 }
 impl ProgramAssociation {
-    fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+    fn read_from<R: Read>(mut reader: R, lenient: bool) -> Result<Self> {
         let program_num = track_io!(reader.read_u16::<BigEndian>())?;
-        let program_map_pid = track!(Pid::read_from(reader))?;
+        let program_map_pid = track!(Pid::read_from(reader, lenient))?;
         Ok(ProgramAssociation {
             program_num,
             program_map_pid,
@@ -93,3 +137,12 @@ impl ProgramAssociation {
         Ok(())
     }
 }
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ProgramAssociation {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ProgramAssociation {
+            program_num: u.arbitrary()?,
+            program_map_pid: u.arbitrary()?,
+        })
+    }
+}
@@ -0,0 +1,398 @@
+use crate::time::ClockReference;
+use crate::ts::{Pid, PidTable, ReadTsPacket, TsPacket};
+use crate::Result;
+use alloc::{vec, vec::Vec};
+use core::time::Duration;
+
+/// PCR observed by `PcrAnalyzer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PcrSample {
+    /// Byte offset of the packet carrying the PCR.
+    pub byte_offset: u64,
+
+    /// Value of the PCR.
+    pub pcr: ClockReference,
+
+    /// Interval from the previous PCR on the same PID.
+    ///
+    /// This is `None` for the first PCR and the PCRs following a discontinuity.
+    pub interval: Option<Duration>,
+
+    /// Deviation (in nanoseconds) of the PCR from the linear fit of the byte offsets to the PCRs.
+    ///
+    /// A positive value means that the PCR is ahead of the time expected from its position.
+    pub jitter: f64,
+}
+
+/// Statistics of the PCRs on a PID reported by `PcrAnalyzer`.
+///
+/// The jitter is measured against a line fitted (by least squares) to the pairs of the byte offset and the PCR,
+/// so it is meaningful only for constant bitrate streams (e.g., the output of `PacedTsPacketWriter`).
+/// The segments separated by discontinuities (i.e., PCRs with the discontinuity indicator set or going backwards)
+/// are fitted separately.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PcrStats {
+    /// PID carrying the PCRs.
+    pub pid: Pid,
+
+    /// Number of the PCRs.
+    pub count: u64,
+
+    /// Number of the discontinuities.
+    pub discontinuities: u64,
+
+    /// Maximum interval between consecutive PCRs.
+    pub max_interval: Option<Duration>,
+
+    /// Number of the intervals exceeding the limit (see `PcrAnalyzer::set_max_interval`).
+    pub interval_violations: u64,
+
+    /// Multiplex bitrate (in bits per second) estimated from the linear fit.
+    ///
+    /// This is `None` if there are not enough PCRs.
+    pub bitrate: Option<f64>,
+
+    /// Maximum absolute jitter in nanoseconds.
+    pub max_jitter: f64,
+
+    /// Root mean square of the jitter in nanoseconds.
+    pub rms_jitter: f64,
+
+    /// Per-PCR samples (if enabled by `PcrAnalyzer::set_record_samples`).
+    pub samples: Option<Vec<PcrSample>>,
+}
+
+/// Reads the whole of the stream, and returns the statistics of the PCRs on each PID.
+///
+/// The byte offsets of the packets are taken from `ReadTsPacket::last_packet_byte_offset`
+/// (or counted assuming 188-byte packets if it is not available).
+pub fn analyze_pcrs<R: ReadTsPacket>(reader: &mut R) -> Result<Vec<PcrStats>> {
+    let mut analyzer = PcrAnalyzer::new();
+    let mut byte_offset = 0;
+    while let Some(packet) = track!(reader.read_ts_packet())? {
+        let offset = reader.last_packet_byte_offset().unwrap_or(byte_offset);
+        analyzer.observe(&packet, offset);
+        byte_offset = offset + TsPacket::SIZE as u64;
+    }
+    Ok(analyzer.stats())
+}
+
+/// Analyzer of the interval, accuracy and jitter of PCRs.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::time::ClockReference;
+/// use mpeg2ts::ts::{AdaptationField, PcrAnalyzer, Pid, TsPacket};
+///
+/// let mut analyzer = PcrAnalyzer::new();
+/// for i in 0..10 {
+///     // PCRs every 40 ms at 1.5 Mbps
+///     let pcr = ClockReference::new(i * 27_000 * 40).unwrap();
+///     let packet = TsPacket::builder(Pid::new(256).unwrap())
+///         .adaptation(AdaptationField::with_pcr(pcr))
+///         .build()
+///         .unwrap();
+///     analyzer.observe(&packet, i * 7500);
+/// }
+/// let stats = analyzer.stats();
+/// assert_eq!(stats[0].max_interval.unwrap().as_millis(), 40);
+/// assert_eq!(stats[0].bitrate.unwrap().round(), 1_500_000.0);
+/// ```
+#[derive(Debug)]
+pub struct PcrAnalyzer {
+    max_interval: Duration,
+    record_samples: bool,
+    pids: PidTable<Vec<Segment>>,
+}
+impl PcrAnalyzer {
+    /// Default value of the interval limit.
+    ///
+    /// Some profiles (e.g., DVB) require a stricter limit of 40 milliseconds.
+    pub const DEFAULT_MAX_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Makes a new `PcrAnalyzer` instance.
+    pub fn new() -> Self {
+        PcrAnalyzer {
+            max_interval: Self::DEFAULT_MAX_INTERVAL,
+            record_samples: false,
+            pids: PidTable::new(),
+        }
+    }
+
+    /// Sets the limit of the interval between consecutive PCRs.
+    ///
+    /// The default value is `DEFAULT_MAX_INTERVAL`.
+    pub fn set_max_interval(&mut self, interval: Duration) {
+        self.max_interval = interval;
+    }
+
+    /// Sets whether the per-PCR samples are included in the statistics.
+    ///
+    /// The default value is `false`.
+    pub fn set_record_samples(&mut self, record: bool) {
+        self.record_samples = record;
+    }
+
+    /// Observes the PCR of `packet` (if any) located at `byte_offset` in the stream.
+    pub fn observe(&mut self, packet: &TsPacket, byte_offset: u64) {
+        let Some(a) = &packet.adaptation_field else {
+            return;
+        };
+        let Some(pcr) = a.pcr else {
+            return;
+        };
+        let pid = packet.header.pid;
+        if !self.pids.contains(pid) {
+            self.pids.insert(pid, Vec::new());
+        }
+        let segments = self.pids.get_mut(pid).expect("Never fails");
+
+        let elapsed = segments
+            .last()
+            .and_then(|s| s.samples.last())
+            .filter(|_| !a.discontinuity_indicator)
+            .and_then(|last| {
+                let delta = pcr.delta(last.pcr);
+                (delta >= 0).then(|| last.elapsed + delta as u64)
+            });
+        let sample = RawSample {
+            byte_offset,
+            pcr,
+            elapsed: elapsed.unwrap_or(0),
+        };
+        match segments.last_mut() {
+            Some(segment) if elapsed.is_some() => segment.samples.push(sample),
+            _ => segments.push(Segment {
+                samples: vec![sample],
+            }),
+        }
+    }
+
+    /// Returns the statistics of the PCRs observed so far on each PID (in ascending order of PID).
+    pub fn stats(&self) -> Vec<PcrStats> {
+        self.pids
+            .iter()
+            .map(|(pid, segments)| self.pid_stats(pid, segments))
+            .collect()
+    }
+
+    fn pid_stats(&self, pid: Pid, segments: &[Segment]) -> PcrStats {
+        let max_interval = ticks(self.max_interval);
+        let mut stats = PcrStats {
+            pid,
+            count: 0,
+            discontinuities: segments.len().saturating_sub(1) as u64,
+            max_interval: None,
+            interval_violations: 0,
+            bitrate: None,
+            max_jitter: 0.0,
+            rms_jitter: 0.0,
+            samples: self.record_samples.then(Vec::new),
+        };
+
+        let mut bytes = 0.0;
+        let mut seconds = 0.0;
+        let mut squared_jitter_sum = 0.0;
+        let mut max_interval_ticks = None;
+        for segment in segments {
+            let fit = segment.fit();
+            if let (Some((slope, _)), Some(first), Some(last)) =
+                (fit, segment.samples.first(), segment.samples.last())
+            {
+                let span = (last.byte_offset - first.byte_offset) as f64;
+                bytes += span;
+                seconds += slope * span / TICKS_PER_SECOND;
+            }
+
+            let mut prev: Option<&RawSample> = None;
+            for sample in &segment.samples {
+                stats.count += 1;
+                let interval = prev.map(|p| sample.elapsed - p.elapsed);
+                if let Some(interval) = interval {
+                    max_interval_ticks = max_interval_ticks.max(Some(interval));
+                    if interval > max_interval {
+                        stats.interval_violations += 1;
+                    }
+                }
+
+                let jitter = fit.map_or(0.0, |(slope, intercept)| {
+                    let expected = intercept + slope * (sample.byte_offset as f64);
+                    (sample.elapsed as f64 - expected) * 1000.0 / 27.0
+                });
+                stats.max_jitter = stats.max_jitter.max(jitter.abs());
+                squared_jitter_sum += jitter * jitter;
+
+                if let Some(samples) = &mut stats.samples {
+                    samples.push(PcrSample {
+                        byte_offset: sample.byte_offset,
+                        pcr: sample.pcr,
+                        interval: interval.map(duration),
+                        jitter,
+                    });
+                }
+                prev = Some(sample);
+            }
+        }
+        stats.max_interval = max_interval_ticks.map(duration);
+        if stats.count > 0 {
+            stats.rms_jitter = sqrt(squared_jitter_sum / stats.count as f64);
+        }
+        if seconds > 0.0 {
+            stats.bitrate = Some(bytes * 8.0 / seconds);
+        }
+        stats
+    }
+}
+impl Default for PcrAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const TICKS_PER_SECOND: f64 = 27_000_000.0;
+
+/// Converts `duration` into 27 MHz ticks.
+fn ticks(duration: Duration) -> u64 {
+    (duration.as_nanos() * 27 / 1000) as u64
+}
+
+/// Converts 27 MHz ticks into a `Duration`.
+fn duration(ticks: u64) -> Duration {
+    Duration::from_nanos(ticks * 1000 / 27)
+}
+
+#[cfg(feature = "std")]
+fn sqrt(x: f64) -> f64 {
+    x.sqrt()
+}
+
+/// Newton's method, since `f64::sqrt` is not available without `std`.
+#[cfg(not(feature = "std"))]
+fn sqrt(x: f64) -> f64 {
+    if x <= 0.0 || !x.is_finite() {
+        return if x == 0.0 || x.is_infinite() {
+            x
+        } else {
+            f64::NAN
+        };
+    }
+    // Halving the exponent gives an initial guess within a factor of two
+    let mut y = f64::from_bits((x.to_bits() >> 1) + (1023 << 51));
+    for _ in 0..6 {
+        y = 0.5 * (y + x / y);
+    }
+    y
+}
+
+#[derive(Debug)]
+struct RawSample {
+    byte_offset: u64,
+    pcr: ClockReference,
+
+    // 27 MHz ticks since the first PCR of the segment
+    elapsed: u64,
+}
+
+/// Continuous run of PCRs.
+#[derive(Debug)]
+struct Segment {
+    samples: Vec<RawSample>,
+}
+impl Segment {
+    /// Fits a line `elapsed = intercept + slope * byte_offset` by least squares.
+    ///
+    /// This is `None` if the PCRs are not at two or more distinct positions.
+    fn fit(&self) -> Option<(f64, f64)> {
+        let n = self.samples.len() as f64;
+        let origin = self.samples.first()?.byte_offset as f64;
+        let xs = || self.samples.iter().map(|s| s.byte_offset as f64 - origin);
+        let ys = || self.samples.iter().map(|s| s.elapsed as f64);
+        let mean_x = xs().sum::<f64>() / n;
+        let mean_y = ys().sum::<f64>() / n;
+        let covariance = xs()
+            .zip(ys())
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum::<f64>();
+        let variance = xs().map(|x| (x - mean_x) * (x - mean_x)).sum::<f64>();
+        if variance == 0.0 {
+            return None;
+        }
+        let slope = covariance / variance;
+        let intercept = mean_y - slope * (mean_x + origin);
+        Some((slope, intercept))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ts::AdaptationField;
+
+    fn packet(pcr: u64, discontinuity_indicator: bool) -> TsPacket {
+        let mut adaptation_field = AdaptationField::with_pcr(ClockReference::new(pcr).unwrap());
+        adaptation_field.discontinuity_indicator = discontinuity_indicator;
+        TsPacket::builder(Pid::new(256).unwrap())
+            .adaptation(adaptation_field)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn analyze() {
+        let mut analyzer = PcrAnalyzer::new();
+        analyzer.set_max_interval(Duration::from_millis(50));
+        analyzer.set_record_samples(true);
+
+        // PCRs every 40 ms at 3 Mbps (15,000 bytes per interval) wrapping around in the middle
+        let interval = 27_000 * 40;
+        let start = ClockReference::MODULUS - 5 * interval;
+        for i in 0..10 {
+            let mut pcr = start + i * interval;
+            if i == 4 {
+                // 10 microseconds late
+                pcr += 270;
+            }
+            analyzer.observe(&packet(pcr % ClockReference::MODULUS, false), i * 15_000);
+        }
+
+        // An interval of 60 ms followed by a discontinuity
+        analyzer.observe(
+            &packet(
+                (start + 9 * interval + interval * 3 / 2) % ClockReference::MODULUS,
+                false,
+            ),
+            157_500,
+        );
+        analyzer.observe(&packet(0, true), 165_000);
+        analyzer.observe(&packet(interval, false), 180_000);
+
+        let stats = analyzer.stats();
+        assert_eq!(stats.len(), 1);
+        let stats = &stats[0];
+        assert_eq!(stats.count, 13);
+        assert_eq!(stats.discontinuities, 1);
+        assert_eq!(stats.max_interval, Some(Duration::from_millis(60)));
+        assert_eq!(stats.interval_violations, 1);
+
+        let bitrate = stats.bitrate.unwrap();
+        assert!((bitrate - 3_000_000.0).abs() < 1_000.0, "{}", bitrate);
+
+        let samples = stats.samples.as_ref().unwrap();
+        assert_eq!(samples.len(), 13);
+        assert_eq!(samples[0].interval, None);
+        assert_eq!(samples[1].interval, Some(Duration::from_millis(40)));
+        assert_eq!(samples[11].interval, None);
+
+        // The late PCR has the largest jitter
+        let (i, _) = samples
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.jitter.abs().total_cmp(&b.jitter.abs()))
+            .unwrap();
+        assert_eq!(i, 4);
+        assert!(samples[4].jitter > 5_000.0, "{}", samples[4].jitter);
+        assert_eq!(stats.max_jitter, samples[4].jitter);
+        assert!(stats.rms_jitter > 0.0 && stats.rms_jitter < stats.max_jitter);
+    }
+}
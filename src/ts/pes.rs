@@ -1,19 +1,28 @@
+use crate::es::StreamId;
+use crate::io::{Read, Write};
 use crate::pes::PesHeader;
 use crate::ts::payload::Bytes;
 use crate::Result;
-use std::io::{Read, Write};
 
 /// Payload for PES(Packetized elementary stream) packets.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pes {
     pub header: PesHeader,
     pub pes_packet_len: u16,
     pub data: Bytes,
 }
 impl Pes {
-    pub(super) fn read_from<R: Read>(mut reader: R) -> Result<Self> {
-        let (header, pes_packet_len) = track!(PesHeader::read_from(&mut reader))?;
+    /// Returns `true` if the PES header in `payload` indicates that the packet data is scrambled.
+    pub(super) fn is_scrambled(payload: &[u8]) -> bool {
+        payload.len() > 6
+            && StreamId::new(payload[3]).has_optional_header()
+            && (payload[6] & 0b0011_0000) != 0
+    }
+
+    pub(super) fn read_from<R: Read>(mut reader: R, lenient: bool) -> Result<Self> {
+        let (header, pes_packet_len) = track!(PesHeader::read_from(&mut reader, lenient))?;
         let data = track!(Bytes::read_from(reader))?;
         Ok(Pes {
             header,
@@ -28,3 +37,17 @@ impl Pes {
         Ok(())
     }
 }
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Pes {
+    /// Generates a PES payload whose header and data fit in a TS packet.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let header: PesHeader = u.arbitrary()?;
+        let header_len = 6 + usize::from(header.optional_header_len());
+        let data_len = u.arbitrary_len::<u8>()?.min(Bytes::MAX_SIZE - header_len);
+        Ok(Pes {
+            header,
+            pes_packet_len: u.arbitrary()?,
+            data: Bytes::new(u.bytes(data_len)?).expect("Never fails"),
+        })
+    }
+}
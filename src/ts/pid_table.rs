@@ -0,0 +1,130 @@
+use crate::ts::Pid;
+use alloc::boxed::Box;
+use core::fmt;
+
+const TABLE_SIZE: usize = Pid::MAX as usize + 1;
+
+/// Map from PIDs to values that is backed by a flat table indexed by PID.
+///
+/// Since PIDs are 13-bit, this avoids the hashing of `HashMap` for per-packet lookups.
+/// The entries are iterated in ascending order of PID.
+pub(crate) struct PidTable<T> {
+    slots: Box<[Option<T>]>,
+    len: usize,
+}
+impl<T> PidTable<T> {
+    pub fn new() -> Self {
+        PidTable {
+            slots: core::iter::repeat_with(|| None).take(TABLE_SIZE).collect(),
+            len: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, pid: Pid) -> Option<&T> {
+        self.slots[usize::from(pid.as_u16())].as_ref()
+    }
+
+    pub fn get_mut(&mut self, pid: Pid) -> Option<&mut T> {
+        self.slots[usize::from(pid.as_u16())].as_mut()
+    }
+
+    pub fn contains(&self, pid: Pid) -> bool {
+        self.get(pid).is_some()
+    }
+
+    pub fn insert(&mut self, pid: Pid, value: T) -> Option<T> {
+        let old = self.slots[usize::from(pid.as_u16())].replace(value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    pub fn remove(&mut self, pid: Pid) -> Option<T> {
+        let old = self.slots[usize::from(pid.as_u16())].take();
+        if old.is_some() {
+            self.len -= 1;
+        }
+        old
+    }
+
+    /// Removes and returns the entry with the smallest PID.
+    pub fn pop_first(&mut self) -> Option<(Pid, T)> {
+        if self.is_empty() {
+            return None;
+        }
+        let i = self.slots.iter().position(Option::is_some)?;
+        self.len -= 1;
+        let value = self.slots[i].take()?;
+        Some((Pid::new(i as u16).ok()?, value))
+    }
+
+    pub fn clear(&mut self) {
+        if !self.is_empty() {
+            self.slots.iter_mut().for_each(|slot| *slot = None);
+            self.len = 0;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Pid, &T)> {
+        let remaining = self.len;
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| Some((Pid::new(i as u16).ok()?, slot.as_ref()?)))
+            .take(remaining)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Pid, &mut T)> {
+        let remaining = self.len;
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, slot)| Some((Pid::new(i as u16).ok()?, slot.as_mut()?)))
+            .take(remaining)
+    }
+}
+impl<T> Default for PidTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T: fmt::Debug> fmt::Debug for PidTable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ts::test_util::pid;
+
+    #[test]
+    fn pid_table() {
+        let mut table = PidTable::new();
+        assert_eq!(table.insert(pid(300), "a"), None);
+        assert_eq!(table.insert(pid(Pid::NULL), "b"), None);
+        assert_eq!(table.insert(pid(0), "c"), None);
+        assert_eq!(table.insert(pid(300), "d"), Some("a"));
+        assert_eq!(table.get(pid(300)), Some(&"d"));
+        assert!(!table.contains(pid(301)));
+
+        let entries = table
+            .iter()
+            .map(|(p, v)| (p.as_u16(), *v))
+            .collect::<Vec<_>>();
+        assert_eq!(entries, [(0, "c"), (300, "d"), (Pid::NULL, "b")]);
+
+        assert_eq!(table.pop_first(), Some((pid(0), "c")));
+        assert_eq!(table.remove(pid(Pid::NULL)), Some("b"));
+        assert_eq!(table.remove(pid(Pid::NULL)), None);
+        assert_eq!(table.pop_first(), Some((pid(300), "d")));
+        assert_eq!(table.pop_first(), None);
+        assert!(table.is_empty());
+    }
+}
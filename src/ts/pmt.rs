@@ -1,13 +1,16 @@
+use crate::es::teletext::TeletextPage;
 use crate::es::StreamType;
+use crate::io::{Read, ReadBytesExt, Write, WriteBytesExt};
 use crate::ts::psi::{Psi, PsiTable, PsiTableHeader, PsiTableSyntax};
-use crate::ts::{Pid, VersionNumber};
+use crate::ts::{Codec, Iso639Language, KnownDescriptor, Pid, VersionNumber};
 use crate::{ErrorKind, Result};
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{Read, Write};
+use alloc::{vec, vec::Vec};
+use byteorder::BigEndian;
 
 /// Program Map Table.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pmt {
     pub program_num: u16,
 
@@ -18,65 +21,108 @@ pub struct Pmt {
     pub pcr_pid: Option<Pid>,
 
     pub version_number: VersionNumber,
+
+    /// Whether the table is currently applicable.
+    ///
+    /// If `false`, the table is not yet applicable and shall be the next table to become valid.
+    pub is_current: bool,
     pub program_info: Vec<Descriptor>,
     pub es_info: Vec<EsInfo>,
+
+    /// Payload bytes of the TS packet that this table was read from
+    /// (i.e., the pointer field, the sections and the stuffing bytes as received).
+    ///
+    /// This is only set if `TsPacketReader::set_keep_raw_tables` is enabled,
+    /// and is written verbatim instead of the parsed fields if `TsPacketWriter::set_write_raw_tables` is enabled.
+    /// If the table spans multiple packets, this is the payload of the last one
+    /// (the preceding ones are returned as `TsPayload::Raw`).
+    /// Set this to `None` after modifying the table, so that the modification is written.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub raw: Option<Vec<u8>>,
 }
 impl Pmt {
     const TABLE_ID: u8 = 2;
 
-    pub(super) fn read_from<R: Read>(reader: R) -> Result<Self> {
-        let mut psi = track!(Psi::read_from(reader))?;
-        track_assert_eq!(psi.tables.len(), 1, ErrorKind::InvalidInput);
-
-        let table = psi.tables.pop().expect("Never fails");
-        let header = table.header;
-        track_assert_eq!(header.table_id, Self::TABLE_ID, ErrorKind::InvalidInput);
-        track_assert!(!header.private_bit, ErrorKind::InvalidInput);
-
-        let syntax = track_assert_some!(table.syntax.as_ref(), ErrorKind::InvalidInput);
-        track_assert_eq!(syntax.section_number, 0, ErrorKind::InvalidInput);
-        track_assert_eq!(syntax.last_section_number, 0, ErrorKind::InvalidInput);
-        track_assert!(syntax.current_next_indicator, ErrorKind::InvalidInput);
+    /// Returns an iterator over the video streams in the program.
+    pub fn video_streams(&self) -> impl Iterator<Item = &EsInfo> {
+        self.es_info.iter().filter(|es| es.is_video())
+    }
 
-        let mut reader = &syntax.table_data[..];
+    /// Returns an iterator over the audio streams in the program.
+    pub fn audio_streams(&self) -> impl Iterator<Item = &EsInfo> {
+        self.es_info.iter().filter(|es| es.is_audio())
+    }
 
-        let pcr_pid = track!(Pid::read_from(&mut reader))?;
-        let pcr_pid = if pcr_pid.as_u16() == 0b0001_1111_1111_1111 {
-            None
-        } else {
-            Some(pcr_pid)
-        };
+    /// Returns the first audio stream that has the given ISO 639-2 language code (e.g., `"eng"`).
+    ///
+    /// The code is compared case-insensitively with every language in the ISO 639 language descriptors of the streams.
+    pub fn find_audio_by_language(&self, language: &str) -> Option<&EsInfo> {
+        self.audio_streams().find(|es| {
+            es.languages()
+                .any(|l| l.language.eq_ignore_ascii_case(language.as_bytes()))
+        })
+    }
 
-        let n = track_io!(reader.read_u16::<BigEndian>())?;
-        track_assert_eq!(
-            n & 0b1111_0000_0000_0000,
-            0b1111_0000_0000_0000,
-            ErrorKind::InvalidInput,
-            "Unexpected reserved bits"
-        );
-        track_assert_eq!(
-            n & 0b0000_1100_0000_0000,
-            0,
-            ErrorKind::InvalidInput,
-            "Unexpected program info length unused bits"
+    pub(super) fn from_psi(psi: Psi, lenient: bool) -> Result<Self> {
+        track_assert!(
+            psi.tables.iter().all(|t| !t.header.private_bit),
+            ErrorKind::InvalidInput
         );
-        let program_info_len = n & 0b0000_0011_1111_1111;
-        let mut program_info = Vec::new();
-        let (mut program_info_reader, mut reader) = reader.split_at(program_info_len as usize);
-        while !program_info_reader.is_empty() {
-            program_info.push(track!(Descriptor::read_from(&mut program_info_reader))?);
-        }
+        let sections = track!(psi.table_sections(Self::TABLE_ID))?;
 
+        let mut pcr_pid = None;
+        let mut program_info = Vec::new();
         let mut es_info = Vec::new();
-        while !reader.is_empty() {
-            es_info.push(track!(EsInfo::read_from(&mut reader))?);
+        for (i, syntax) in sections.iter().enumerate() {
+            let mut reader = &syntax.table_data[..];
+
+            let pid = track!(Pid::read_from(&mut reader, lenient))?;
+            if i == 0 && pid.as_u16() != 0b0001_1111_1111_1111 {
+                pcr_pid = Some(pid);
+            }
+
+            let n = track_io!(reader.read_u16::<BigEndian>())?;
+            if !lenient {
+                track_assert_eq!(
+                    n & 0b1111_0000_0000_0000,
+                    0b1111_0000_0000_0000,
+                    ErrorKind::InvalidInput,
+                    "Unexpected reserved bits"
+                );
+                track_assert_eq!(
+                    n & 0b0000_1100_0000_0000,
+                    0,
+                    ErrorKind::InvalidInput,
+                    "Unexpected program info length unused bits"
+                );
+            }
+            let program_info_len = n & 0b0000_0011_1111_1111;
+            track_assert!(
+                program_info_len as usize <= reader.len(),
+                ErrorKind::InvalidInput,
+                "Too large program info length: {}",
+                program_info_len
+            );
+            let (mut program_info_reader, mut reader) = reader.split_at(program_info_len as usize);
+            while !program_info_reader.is_empty() {
+                program_info.push(track!(Descriptor::read_from(&mut program_info_reader))?);
+            }
+
+            while !reader.is_empty() {
+                es_info.push(track!(EsInfo::read_from(&mut reader, lenient))?);
+            }
         }
         Ok(Pmt {
-            program_num: syntax.table_id_extension,
+            program_num: sections[0].table_id_extension,
             pcr_pid,
-            version_number: syntax.version_number,
+            version_number: sections[0].version_number,
+            is_current: sections[0].current_next_indicator,
             program_info,
             es_info,
+            raw: None,
         })
     }
 
@@ -121,19 +167,55 @@ impl Pmt {
         let syntax = Some(PsiTableSyntax {
             table_id_extension: self.program_num,
             version_number: self.version_number,
-            current_next_indicator: true,
+            current_next_indicator: self.is_current,
             section_number: 0,
             last_section_number: 0,
             table_data,
         });
-        let tables = vec![PsiTable { header, syntax }];
+        let tables = vec![PsiTable {
+            header,
+            syntax,
+            crc_ok: true,
+        }];
         Ok(Psi { tables })
     }
 }
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Pmt {
+    /// Generates a table that fits in a TS packet.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        // 17 bytes are taken by the pointer field, the section header and syntax,
+        // the PCR PID, the program info length and the CRC32
+        let mut remaining = crate::ts::payload::Bytes::MAX_SIZE - 17;
+
+        let program_info = Descriptor::arbitrary_vec(u, &mut remaining)?;
+        let mut es_info = Vec::new();
+        while remaining >= 5 && u.arbitrary()? {
+            remaining -= 5;
+            es_info.push(EsInfo {
+                stream_type: u.arbitrary()?,
+                elementary_pid: u.arbitrary()?,
+                descriptors: Descriptor::arbitrary_vec(u, &mut remaining)?,
+            });
+        }
+        Ok(Pmt {
+            program_num: u.arbitrary()?,
+            pcr_pid: u
+                .arbitrary::<Option<Pid>>()?
+                .filter(|pid| pid.as_u16() != Pid::NULL),
+            version_number: u.arbitrary()?,
+            is_current: u.arbitrary()?,
+            program_info,
+            es_info,
+            raw: None,
+        })
+    }
+}
 
 /// Elementary stream information.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EsInfo {
     pub stream_type: StreamType,
 
@@ -143,32 +225,51 @@ pub struct EsInfo {
     pub descriptors: Vec<Descriptor>,
 }
 impl EsInfo {
-    fn read_from<R: Read>(mut reader: R) -> Result<Self> {
-        let stream_type = track_io!(reader.read_u8()).and_then(StreamType::from_u8)?;
-        let elementary_pid = track!(Pid::read_from(&mut reader))?;
+    /// Makes a new `EsInfo` instance for a stream of `codec`.
+    ///
+    /// The stream type and descriptors are taken from `Codec::stream_type` and `Codec::descriptors`.
+    pub fn with_codec(elementary_pid: Pid, codec: Codec) -> Self {
+        EsInfo {
+            stream_type: codec.stream_type(),
+            elementary_pid,
+            descriptors: codec.descriptors(),
+        }
+    }
+
+    fn read_from(reader: &mut &[u8], lenient: bool) -> Result<Self> {
+        let stream_type = StreamType::from_u8(track_io!(reader.read_u8())?);
+        let elementary_pid = track!(Pid::read_from(&mut *reader, lenient))?;
 
         let n = track_io!(reader.read_u16::<BigEndian>())?;
-        track_assert_eq!(
-            n & 0b1111_0000_0000_0000,
-            0b1111_0000_0000_0000,
-            ErrorKind::InvalidInput,
-            "Unexpected reserved bits"
-        );
-        track_assert_eq!(
-            n & 0b0000_1100_0000_0000,
-            0,
+        if !lenient {
+            track_assert_eq!(
+                n & 0b1111_0000_0000_0000,
+                0b1111_0000_0000_0000,
+                ErrorKind::InvalidInput,
+                "Unexpected reserved bits"
+            );
+            track_assert_eq!(
+                n & 0b0000_1100_0000_0000,
+                0,
+                ErrorKind::InvalidInput,
+                "Unexpected ES info length unused bits"
+            );
+        }
+        let es_info_len = n & 0b0000_0011_1111_1111;
+        track_assert!(
+            es_info_len as usize <= reader.len(),
             ErrorKind::InvalidInput,
-            "Unexpected ES info length unused bits"
+            "Too large ES info length: {}",
+            es_info_len
         );
-        let es_info_len = n & 0b0000_0011_1111_1111;
 
-        let mut reader = reader.take(u64::from(es_info_len));
+        let (mut descriptors_reader, rest) = reader.split_at(es_info_len as usize);
+        *reader = rest;
         let mut descriptors = Vec::new();
-        while reader.limit() > 0 {
-            let d = track!(Descriptor::read_from(&mut reader))?;
+        while !descriptors_reader.is_empty() {
+            let d = track!(Descriptor::read_from(&mut descriptors_reader))?;
             descriptors.push(d);
         }
-        track_assert_eq!(reader.limit(), 0, ErrorKind::InvalidInput);
 
         Ok(EsInfo {
             stream_type,
@@ -177,8 +278,66 @@ impl EsInfo {
         })
     }
 
+    /// Returns the codec of the stream resolved from the stream type and descriptors (see `Codec::resolve`).
+    pub fn codec(&self) -> Codec {
+        Codec::resolve(self.stream_type, &self.descriptors)
+    }
+
+    /// Returns `true` if this is a video stream, otherwise `false`.
+    pub fn is_video(&self) -> bool {
+        self.stream_type.is_video()
+    }
+
+    /// Returns `true` if this is an audio stream, otherwise `false`.
+    ///
+    /// In addition to the audio stream types, a `StreamType::Mpeg2PacketizedData` stream
+    /// that has a DVB AC-3, E-AC-3, DTS or AAC descriptor is regarded as an audio stream.
+    pub fn is_audio(&self) -> bool {
+        if self.stream_type.is_audio() {
+            return true;
+        }
+        self.stream_type == StreamType::Mpeg2PacketizedData
+            && self
+                .descriptors
+                .iter()
+                .any(|d| matches!(d.tag, 0x6A | 0x7A | 0x7B | KnownDescriptor::AAC_TAG))
+    }
+
+    /// Returns the first language code in the ISO 639 language descriptors (if any).
+    pub fn language(&self) -> Option<[u8; 3]> {
+        self.languages().next().map(|l| l.language)
+    }
+
+    fn languages(&self) -> impl Iterator<Item = Iso639Language> + '_ {
+        self.descriptors
+            .iter()
+            .filter(|d| d.tag == Descriptor::ISO_639_LANGUAGE_TAG)
+            .filter_map(|d| match d.parse() {
+                Ok(KnownDescriptor::Iso639Language(languages)) => Some(languages),
+                _ => None,
+            })
+            .flatten()
+    }
+
+    /// Returns `true` if this is a KLV metadata stream, otherwise `false`.
+    ///
+    /// A KLV stream is a `StreamType::Mpeg2PacketizedData` or `StreamType::PacketizedMetadata` stream
+    /// that has a registration descriptor or a metadata descriptor with the format identifier `KLVA`.
+    pub fn is_klv(&self) -> bool {
+        if !matches!(
+            self.stream_type,
+            StreamType::Mpeg2PacketizedData | StreamType::PacketizedMetadata
+        ) {
+            return false;
+        }
+        self.descriptors.iter().any(|d| {
+            d.format_identifier() == Some(*b"KLVA")
+                || d.metadata_format_identifier() == Some(*b"KLVA")
+        })
+    }
+
     fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
-        track_io!(writer.write_u8(self.stream_type as u8))?;
+        track_io!(writer.write_u8(self.stream_type.as_u8()))?;
         track!(self.elementary_pid.write_to(&mut writer))?;
 
         let es_info_len: usize = self.descriptors.iter().map(|d| 2 + d.data.len()).sum();
@@ -197,12 +356,76 @@ impl EsInfo {
 /// Program or elementary stream descriptor.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Descriptor {
     pub tag: u8,
     pub data: Vec<u8>,
 }
 impl Descriptor {
-    fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+    /// Tag of the registration descriptor.
+    pub const REGISTRATION_TAG: u8 = 0x05;
+
+    /// Tag of the ISO 639 language descriptor.
+    pub const ISO_639_LANGUAGE_TAG: u8 = 0x0A;
+
+    /// Tag of the metadata descriptor.
+    pub const METADATA_TAG: u8 = 0x26;
+
+    /// Tag of the teletext descriptor.
+    pub const TELETEXT_TAG: u8 = 0x56;
+
+    /// Parses the descriptor into the typed representation.
+    ///
+    /// The descriptors with unknown tags are returned as `KnownDescriptor::Unknown`.
+    /// It will return an `ErrorKind::InvalidInput` error if the data of a known descriptor is malformed.
+    pub fn parse(&self) -> Result<KnownDescriptor> {
+        track!(KnownDescriptor::parse(self))
+    }
+
+    /// Returns the `format_identifier` if this is a registration descriptor.
+    pub fn format_identifier(&self) -> Option<[u8; 4]> {
+        if self.tag != Self::REGISTRATION_TAG {
+            return None;
+        }
+        self.data.get(..4)?.try_into().ok()
+    }
+
+    /// Returns the `metadata_format_identifier` if this is a metadata descriptor.
+    ///
+    /// It will return `None` if `metadata_format` is not `0xFF` (i.e., the identifier is absent).
+    pub fn metadata_format_identifier(&self) -> Option<[u8; 4]> {
+        if self.tag != Self::METADATA_TAG {
+            return None;
+        }
+        let application_format = self.data.get(..2)?;
+        let offset = if application_format == [0xFF, 0xFF] {
+            6
+        } else {
+            2
+        };
+        if *self.data.get(offset)? != 0xFF {
+            return None;
+        }
+        self.data.get(offset + 1..offset + 5)?.try_into().ok()
+    }
+
+    /// Returns the page entries if this is a teletext descriptor.
+    pub fn teletext_pages(&self) -> Option<Vec<TeletextPage>> {
+        if self.tag != Self::TELETEXT_TAG {
+            return None;
+        }
+        TeletextPage::read_entries(&self.data)
+    }
+
+    /// Returns the first language code if this is an ISO 639 language descriptor.
+    pub fn iso_639_language_code(&self) -> Option<[u8; 3]> {
+        if self.tag != Self::ISO_639_LANGUAGE_TAG {
+            return None;
+        }
+        self.data.get(..3)?.try_into().ok()
+    }
+
+    pub(super) fn read_from<R: Read>(mut reader: R) -> Result<Self> {
         let tag = track_io!(reader.read_u8())?;
         let len = track_io!(reader.read_u8())?;
         let mut data = vec![0; len as usize];
@@ -210,10 +433,39 @@ impl Descriptor {
         Ok(Descriptor { tag, data })
     }
 
-    fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+    pub(super) fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
         track_io!(writer.write_u8(self.tag))?;
         track_io!(writer.write_u8(self.data.len() as u8))?;
         track_io!(writer.write_all(&self.data))?;
         Ok(())
     }
 }
+#[cfg(feature = "arbitrary")]
+impl Descriptor {
+    /// Generates descriptors that take at most `remaining` bytes in total, and subtracts their size from it.
+    fn arbitrary_vec(
+        u: &mut arbitrary::Unstructured,
+        remaining: &mut usize,
+    ) -> arbitrary::Result<Vec<Self>> {
+        let mut descriptors = Vec::new();
+        while *remaining >= 2 && u.arbitrary()? {
+            let len = u.arbitrary_len::<u8>()?.min(*remaining - 2).min(0xFF);
+            *remaining -= 2 + len;
+            descriptors.push(Descriptor {
+                tag: u.arbitrary()?,
+                data: u.bytes(len)?.to_owned(),
+            });
+        }
+        Ok(descriptors)
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Descriptor {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let len = u.arbitrary_len::<u8>()?.min(0xFF);
+        Ok(Descriptor {
+            tag: u.arbitrary()?,
+            data: u.bytes(len)?.to_owned(),
+        })
+    }
+}
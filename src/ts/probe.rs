@@ -0,0 +1,132 @@
+use crate::io::{Read, Seek, SeekFrom};
+use crate::time::{ClockReference, Timestamp};
+use crate::ts::{Pid, ReadTsPacket, TruncatedPacketPolicy, TsPacket, TsPacketReader, TsPayload};
+use crate::Result;
+use alloc::collections::BTreeSet;
+use core::time::Duration;
+
+/// Number of bytes scanned at the beginning and the end of a seekable stream.
+const PROBE_REGION_SIZE: u64 = 1024 * 1024;
+
+/// Summary of a stream returned by `probe` and `probe_linear`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeInfo {
+    /// Size of the stream in bytes.
+    pub byte_size: u64,
+
+    /// Duration of the stream.
+    ///
+    /// This is calculated from the first and last PCRs of the first PID carrying a PCR,
+    /// or from the first and last PTSs if there are no PCRs.
+    /// A single wrap-around of the timestamps is taken into account.
+    pub duration: Option<Duration>,
+
+    /// Average bitrate in bits per second (i.e., `byte_size` divided by `duration`).
+    pub bitrate: Option<u64>,
+
+    /// PIDs of the packets examined.
+    ///
+    /// Note that `probe` examines only the beginning and the end of a stream.
+    pub pids: BTreeSet<Pid>,
+}
+
+/// Probes the duration and bitrate of a seekable stream.
+///
+/// Only the first and last megabytes of the stream are read.
+/// The packet format is detected automatically,
+/// and a packet truncated by the end of the stream is ignored.
+pub fn probe<R: Read + Seek>(mut stream: R) -> Result<ProbeInfo> {
+    let byte_size = track_io!(stream.seek(SeekFrom::End(0)))?;
+    track_io!(stream.seek(SeekFrom::Start(0)))?;
+    if byte_size <= PROBE_REGION_SIZE * 2 {
+        return track!(probe_linear(stream));
+    }
+
+    let mut reader = track!(TsPacketReader::with_detected_packet_format(stream))?;
+    reader.set_resync_confidence(Some(3));
+    reader.set_truncated_packet_policy(TruncatedPacketPolicy::Discard);
+    let mut scan = Scan::default();
+    while !scan.found_first() && track!(reader.position())? < PROBE_REGION_SIZE {
+        let Some(packet) = track!(reader.read_ts_packet())? else {
+            break;
+        };
+        scan.observe(&packet);
+    }
+
+    track!(reader.seek_to_byte(byte_size - PROBE_REGION_SIZE, true))?;
+    while let Some(packet) = track!(reader.read_ts_packet())? {
+        scan.observe(&packet);
+    }
+    Ok(scan.finish(byte_size))
+}
+
+/// Probes the duration and bitrate of a stream by reading the whole of it.
+///
+/// The packet format is detected automatically,
+/// and a packet truncated by the end of the stream is ignored (but counted in `ProbeInfo::byte_size`).
+pub fn probe_linear<R: Read>(stream: R) -> Result<ProbeInfo> {
+    let mut reader = track!(TsPacketReader::with_detected_packet_format(stream))?;
+    reader.set_resync_confidence(Some(3));
+    reader.set_truncated_packet_policy(TruncatedPacketPolicy::Discard);
+    let mut scan = Scan::default();
+    while let Some(packet) = track!(reader.read_ts_packet())? {
+        scan.observe(&packet);
+    }
+    Ok(scan.finish(reader.byte_offset()))
+}
+
+#[derive(Debug, Default)]
+struct Scan {
+    pcr_pid: Option<Pid>,
+    first_pcr: Option<ClockReference>,
+    last_pcr: Option<ClockReference>,
+    pts_pid: Option<Pid>,
+    first_pts: Option<Timestamp>,
+    last_pts: Option<Timestamp>,
+    pids: BTreeSet<Pid>,
+}
+impl Scan {
+    fn found_first(&self) -> bool {
+        self.first_pcr.is_some() && self.first_pts.is_some()
+    }
+
+    fn observe(&mut self, packet: &TsPacket) {
+        let pid = packet.header.pid;
+        self.pids.insert(pid);
+        if let Some(pcr) = packet.adaptation_field.as_ref().and_then(|a| a.pcr) {
+            if *self.pcr_pid.get_or_insert(pid) == pid {
+                self.first_pcr.get_or_insert(pcr);
+                self.last_pcr = Some(pcr);
+            }
+        }
+        if let Some(TsPayload::Pes(ref pes)) = packet.payload {
+            if let Some(pts) = pes.header.pts {
+                if *self.pts_pid.get_or_insert(pid) == pid {
+                    self.first_pts.get_or_insert(pts);
+                    self.last_pts = Some(pts);
+                }
+            }
+        }
+    }
+
+    fn finish(self, byte_size: u64) -> ProbeInfo {
+        let duration = if let (Some(first), Some(last)) = (self.first_pcr, self.last_pcr) {
+            let delta = last.wrapping_sub(first);
+            Some(Duration::from_nanos(delta * 1000 / 27))
+        } else if let (Some(first), Some(last)) = (self.first_pts, self.last_pts) {
+            let delta = last.wrapping_sub(first);
+            Some(Duration::from_nanos(delta * 100_000 / 9))
+        } else {
+            None
+        };
+        let bitrate = duration
+            .filter(|d| !d.is_zero())
+            .map(|d| (byte_size as u128 * 8 * 1_000_000_000 / d.as_nanos()) as u64);
+        ProbeInfo {
+            byte_size,
+            duration,
+            bitrate,
+            pids: self.pids,
+        }
+    }
+}
@@ -1,18 +1,70 @@
+//! Program-specific information (PSI) sections.
+//!
+//! This can be used to read and write the tables that are not supported by this crate
+//! (e.g., application-private tables that have table IDs of `0x80` or greater).
+//! Register the PID carrying such tables with `PidKind::Psi` to make `TsPacketReader` return them as `TsPayload::Psi`.
+use crate::io::{Read, ReadBytesExt, Write, WriteBytesExt};
 use crate::ts::VersionNumber;
 use crate::util::{self, WithCrc32};
 use crate::{ErrorKind, Result};
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::io::{Read, Write};
+use alloc::vec::Vec;
+use byteorder::BigEndian;
 
 const MAX_SYNTAX_SECTION_LEN: usize = 1021;
 
+/// Returns the size of the PSI payload (including the pointer field)
+/// that is needed to contain the whole of the first section in `bytes`.
+///
+/// If `bytes` is too short to determine the size, `None` is returned.
+pub(super) fn payload_size_of_first_section(bytes: &[u8]) -> Option<usize> {
+    let pointer_field = *bytes.first()? as usize;
+    let header_start = 1 + pointer_field;
+    let header = bytes.get(header_start..header_start + 3)?;
+    Some(header_start + 3 + section_len(header))
+}
+
+/// Returns the size of the PSI payload (including the pointer field)
+/// that contains the first section and the complete sections following it in `bytes`.
+///
+/// `bytes` must contain the whole of the first section.
+/// The stuffing bytes and the section that is not completely contained in `bytes` are excluded.
+pub(super) fn payload_size_of_complete_sections(bytes: &[u8]) -> usize {
+    let mut size = payload_size_of_first_section(bytes).expect("Never fails");
+    while let Some(header) = bytes.get(size..size + 3) {
+        let section_size = 3 + section_len(header);
+        if header[0] == 0xFF || bytes.len() < size + section_size {
+            break;
+        }
+        size += section_size;
+    }
+    size
+}
+
+fn section_len(header: &[u8]) -> usize {
+    (usize::from(header[1]) << 8 | usize::from(header[2])) & 0b0011_1111_1111
+}
+
 /// Program-specific information.
-#[derive(Debug)]
+///
+/// This is the payload of a PSI packet: a pointer field (always `0`) followed by one or more sections.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Psi {
+    /// The sections in order of appearance.
     pub tables: Vec<PsiTable>,
 }
 impl Psi {
-    pub fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+    /// Reads PSI tables.
+    ///
+    /// If `lenient` is `true`, the values of reserved and unused bits are not validated.
+    ///
+    /// CRC32 mismatches are not treated as errors here; they are reported via `PsiTable::crc_ok`.
+    /// Use `check_crc` to reject them.
+    ///
+    /// # Errors
+    ///
+    /// If the pointer field is not `0`, it will return an `ErrorKind::Unsupported` error.
+    pub fn read_from<R: Read>(mut reader: R, lenient: bool) -> Result<Self> {
         let pointer_field = track_io!(reader.read_u8())?;
         track_assert_eq!(pointer_field, 0, ErrorKind::Unsupported);
 
@@ -27,12 +79,73 @@ impl Psi {
                 track!(util::consume_stuffing_bytes(&mut reader))?;
                 break;
             }
-            let table = track!(PsiTable::read_from(peek.chain(&mut reader)))?;
+            let table = track!(PsiTable::read_from(peek.chain(&mut reader), lenient))?;
             tables.push(table);
         }
         Ok(Psi { tables })
     }
 
+    /// Returns the syntax sections of the tables in order of section number.
+    ///
+    /// It is validated that the tables are all the sections of a single table
+    /// that has the ID `table_id`.
+    pub fn table_sections(&self, table_id: u8) -> Result<Vec<&PsiTableSyntax>> {
+        track_assert!(!self.tables.is_empty(), ErrorKind::InvalidInput);
+
+        let mut sections: Vec<&PsiTableSyntax> = Vec::new();
+        for (i, table) in self.tables.iter().enumerate() {
+            track_assert_eq!(table.header.table_id, table_id, ErrorKind::InvalidInput);
+            track_assert_eq!(
+                table.header.private_bit,
+                self.tables[0].header.private_bit,
+                ErrorKind::InvalidInput
+            );
+
+            let syntax = track_assert_some!(table.syntax.as_ref(), ErrorKind::InvalidInput);
+            track_assert_eq!(syntax.section_number as usize, i, ErrorKind::InvalidInput);
+            track_assert_eq!(
+                syntax.last_section_number as usize,
+                self.tables.len() - 1,
+                ErrorKind::InvalidInput
+            );
+            if let Some(first) = sections.first() {
+                track_assert_eq!(
+                    syntax.table_id_extension,
+                    first.table_id_extension,
+                    ErrorKind::InvalidInput
+                );
+                track_assert_eq!(
+                    syntax.version_number,
+                    first.version_number,
+                    ErrorKind::InvalidInput
+                );
+                track_assert_eq!(
+                    syntax.current_next_indicator,
+                    first.current_next_indicator,
+                    ErrorKind::InvalidInput
+                );
+            }
+            sections.push(syntax);
+        }
+        Ok(sections)
+    }
+
+    /// Returns an error of kind `ErrorKind::CrcMismatch` if any of the tables has an invalid CRC32.
+    pub fn check_crc(&self) -> Result<()> {
+        for table in &self.tables {
+            track_assert!(
+                table.crc_ok,
+                ErrorKind::CrcMismatch,
+                "table_id={}",
+                table.header.table_id
+            );
+        }
+        Ok(())
+    }
+
+    /// Writes the pointer field and the tables.
+    ///
+    /// The CRC32 of each section that has the syntax section is computed while writing.
     pub fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
         track_io!(writer.write_u8(0))?; // pointer field
         for table in &self.tables {
@@ -41,30 +154,91 @@ impl Psi {
         Ok(())
     }
 }
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Psi {
+    /// Generates one or more sections that fit in a TS packet.
+    ///
+    /// The table IDs of the sections following the first one are never `0xFF`,
+    /// since such a byte is regarded as the start of the stuffing bytes.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut remaining = crate::ts::payload::Bytes::MAX_SIZE - 1 /* pointer field */;
+        let mut tables = Vec::new();
+        loop {
+            let header = PsiTableHeader {
+                table_id: if tables.is_empty() {
+                    u.arbitrary()?
+                } else {
+                    u.int_in_range(0..=0xFE)?
+                },
+                private_bit: u.arbitrary()?,
+            };
+            remaining -= 3;
+
+            let syntax = if remaining >= 9 && u.arbitrary()? {
+                let len = u.arbitrary_len::<u8>()?.min(remaining - 9);
+                remaining -= 9 + len;
+                Some(PsiTableSyntax {
+                    table_id_extension: u.arbitrary()?,
+                    version_number: u.arbitrary()?,
+                    current_next_indicator: u.arbitrary()?,
+                    section_number: u.arbitrary()?,
+                    last_section_number: u.arbitrary()?,
+                    table_data: u.bytes(len)?.to_owned(),
+                })
+            } else {
+                None
+            };
+            tables.push(PsiTable {
+                header,
+                syntax,
+                crc_ok: true,
+            });
+            if remaining < 3 || !u.arbitrary()? {
+                break;
+            }
+        }
+        Ok(Psi { tables })
+    }
+}
 
-#[derive(Debug)]
+/// PSI section.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PsiTable {
+    #[allow(missing_docs)]
     pub header: PsiTableHeader,
+
+    /// The syntax section (`None` for short-form sections).
     pub syntax: Option<PsiTableSyntax>,
+
+    /// Whether the CRC32 of the section matched (always `true` for sections without syntax).
+    ///
+    /// This is ignored when writing.
+    pub crc_ok: bool,
 }
 impl PsiTable {
-    fn read_from<R: Read>(reader: R) -> Result<Self> {
+    fn read_from<R: Read>(reader: R, lenient: bool) -> Result<Self> {
         let mut reader = WithCrc32::new(reader);
-        let (header, syntax_section_len) = track!(PsiTableHeader::read_from(&mut reader))?;
+        let (header, syntax_section_len) = track!(PsiTableHeader::read_from(&mut reader, lenient))?;
+        let mut crc_ok = true;
         let syntax = if syntax_section_len > 0 {
             let syntax = {
                 track_assert!(syntax_section_len >= 4, ErrorKind::InvalidInput);
                 let reader = reader.by_ref().take(u64::from(syntax_section_len - 4));
-                track!(PsiTableSyntax::read_from(reader))?
+                track!(PsiTableSyntax::read_from(reader, lenient))?
             };
             let crc32 = reader.crc32();
             let expected_crc32 = track_io!(reader.read_u32::<BigEndian>())?;
-            track_assert_eq!(crc32, expected_crc32, ErrorKind::InvalidInput);
+            crc_ok = crc32 == expected_crc32;
             Some(syntax)
         } else {
             None
         };
-        Ok(PsiTable { header, syntax })
+        Ok(PsiTable {
+            header,
+            syntax,
+            crc_ok,
+        })
     }
 
     fn write_to<W: Write>(&self, writer: W) -> Result<()> {
@@ -82,30 +256,37 @@ impl PsiTable {
     }
 }
 
-#[derive(Debug)]
+/// Header of a PSI section.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PsiTableHeader {
+    #[allow(missing_docs)]
     pub table_id: u8,
+
+    /// `true` for private sections (it is `false` for the standard tables such as PAT and PMT).
     pub private_bit: bool,
 }
 impl PsiTableHeader {
-    fn read_from<R: Read>(mut reader: R) -> Result<(Self, u16)> {
+    fn read_from<R: Read>(mut reader: R, lenient: bool) -> Result<(Self, u16)> {
         let table_id = track_io!(reader.read_u8())?;
 
         let n = track_io!(reader.read_u16::<BigEndian>())?;
         let syntax_section_indicator = (n & 0b1000_0000_0000_0000) != 0;
         let private_bit = (n & 0b0100_0000_0000_0000) != 0;
-        track_assert_eq!(
-            n & 0b0011_0000_0000_0000,
-            0b0011_0000_0000_0000,
-            ErrorKind::InvalidInput,
-            "Unexpected reserved bits"
-        );
-        track_assert_eq!(
-            n & 0b0000_1100_0000_0000,
-            0,
-            ErrorKind::InvalidInput,
-            "Unexpected section length unused bits"
-        );
+        if !lenient {
+            track_assert_eq!(
+                n & 0b0011_0000_0000_0000,
+                0b0011_0000_0000_0000,
+                ErrorKind::InvalidInput,
+                "Unexpected reserved bits"
+            );
+            track_assert_eq!(
+                n & 0b0000_1100_0000_0000,
+                0,
+                ErrorKind::InvalidInput,
+                "Unexpected section length unused bits"
+            );
+        }
         let syntax_section_len = n & 0b0000_0011_1111_1111;
         track_assert!(
             (syntax_section_len as usize) <= MAX_SYNTAX_SECTION_LEN,
@@ -140,13 +321,18 @@ impl PsiTableHeader {
     }
 }
 
-#[derive(Debug)]
+/// Syntax section of a PSI section.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PsiTableSyntax {
     pub table_id_extension: u16,
     pub version_number: VersionNumber,
     pub current_next_indicator: bool,
     pub section_number: u8,
     pub last_section_number: u8,
+
+    /// The body of the section (excluding the CRC32).
     pub table_data: Vec<u8>,
 }
 impl PsiTableSyntax {
@@ -159,16 +345,18 @@ impl PsiTableSyntax {
             4 /* CRC32 */
     }
 
-    fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+    fn read_from<R: Read>(mut reader: R, lenient: bool) -> Result<Self> {
         let table_id_extension = track_io!(reader.read_u16::<BigEndian>())?;
 
         let b = track_io!(reader.read_u8())?;
-        track_assert_eq!(
-            b & 0b1100_0000,
-            0b1100_0000,
-            ErrorKind::InvalidInput,
-            "Unexpected reserved bits"
-        );
+        if !lenient {
+            track_assert_eq!(
+                b & 0b1100_0000,
+                0b1100_0000,
+                ErrorKind::InvalidInput,
+                "Unexpected reserved bits"
+            );
+        }
         let version_number = track!(VersionNumber::from_u8((b & 0b0011_1110) >> 1))?;
         let current_next_indicator = (b & 0b0000_0001) != 0;
 
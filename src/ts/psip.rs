@@ -0,0 +1,773 @@
+//! ATSC Program and System Information Protocol (PSIP) tables (ATSC A/65).
+//!
+//! The base tables are carried on `Pid::PSIP`.
+//! `TsPacketReader` returns MGT and VCT sections on the PID as `TsPayload::Mgt` and `TsPayload::Vct`,
+//! and the other tables (e.g., STT and RRT) as `TsPayload::Psi`.
+//! Register `Pid::PSIP` as `PidKind::Section` to read the sections without parsing them.
+use crate::io::{Read, ReadBytesExt, Write, WriteBytesExt};
+use crate::ts::psi::{Psi, PsiTable, PsiTableHeader, PsiTableSyntax};
+use crate::ts::{Descriptor, Pid, VersionNumber};
+use crate::{ErrorKind, Result};
+use alloc::{string::String, vec, vec::Vec};
+use byteorder::BigEndian;
+
+/// Master Guide Table (MGT).
+///
+/// Since the PSI layer limits the section length to 1021 bytes, larger MGTs cannot be read.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mgt {
+    pub version_number: VersionNumber,
+
+    /// Whether the table is currently applicable.
+    ///
+    /// If `false`, the table is not yet applicable and shall be the next table to become valid.
+    pub is_current: bool,
+    pub protocol_version: u8,
+
+    /// The tables listed by the MGT.
+    pub tables: Vec<MgtTable>,
+    pub descriptors: Vec<Descriptor>,
+}
+impl Mgt {
+    /// Table ID of MGT.
+    pub const TABLE_ID: u8 = 0xC7;
+
+    pub(super) fn from_psi(psi: Psi, lenient: bool) -> Result<Self> {
+        let sections = track!(psi.table_sections(Self::TABLE_ID))?;
+        track_assert_eq!(
+            sections.len(),
+            1,
+            ErrorKind::InvalidInput,
+            "MGT must consist of a single section"
+        );
+
+        let syntax = sections[0];
+        let mut reader = &syntax.table_data[..];
+        let protocol_version = track_io!(reader.read_u8())?;
+        let tables_defined = track_io!(reader.read_u16::<BigEndian>())?;
+        let mut tables = Vec::new();
+        for _ in 0..tables_defined {
+            tables.push(track!(MgtTable::read_from(&mut reader, lenient))?);
+        }
+        let descriptors = track!(read_descriptors(&mut reader, 12, lenient))?;
+        track_assert!(reader.is_empty(), ErrorKind::InvalidInput, "Trailing bytes");
+
+        Ok(Mgt {
+            version_number: syntax.version_number,
+            is_current: syntax.current_next_indicator,
+            protocol_version,
+            tables,
+            descriptors,
+        })
+    }
+
+    pub(super) fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        track!(self.to_psi().and_then(|psi| psi.write_to(writer)))
+    }
+
+    fn to_psi(&self) -> Result<Psi> {
+        let mut table_data = Vec::new();
+        track_io!(table_data.write_u8(self.protocol_version))?;
+        track_assert!(self.tables.len() <= 0xFFFF, ErrorKind::InvalidInput);
+        track_io!(table_data.write_u16::<BigEndian>(self.tables.len() as u16))?;
+        for table in &self.tables {
+            track!(table.write_to(&mut table_data))?;
+        }
+        track!(write_descriptors(&mut table_data, &self.descriptors, 12))?;
+        Ok(psip_section(
+            Self::TABLE_ID,
+            0,
+            self.version_number,
+            self.is_current,
+            table_data,
+        ))
+    }
+}
+
+/// An entry of a master guide table.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MgtTable {
+    /// Type of the table (e.g., `0x0000` for the current TVCT and `0x0100..=0x017F` for EIT-0 to EIT-127).
+    pub table_type: u16,
+
+    /// The packet identifier that carries the table.
+    pub pid: Pid,
+
+    /// Version number of the table.
+    pub version_number: VersionNumber,
+
+    /// Total size of the sections of the table in bytes (`number_bytes`).
+    pub number_bytes: u32,
+
+    #[allow(missing_docs)]
+    pub descriptors: Vec<Descriptor>,
+}
+impl MgtTable {
+    fn read_from(reader: &mut &[u8], lenient: bool) -> Result<Self> {
+        let table_type = track_io!(reader.read_u16::<BigEndian>())?;
+        let pid = track!(Pid::read_from(&mut *reader, lenient))?;
+        let n = track_io!(reader.read_u8())?;
+        track!(check_reserved_bits(u32::from(n), 0b1110_0000, lenient))?;
+        let version_number = track!(VersionNumber::from_u8(n & 0b1_1111))?;
+        let number_bytes = track_io!(reader.read_u32::<BigEndian>())?;
+        let descriptors = track!(read_descriptors(reader, 12, lenient))?;
+        Ok(MgtTable {
+            table_type,
+            pid,
+            version_number,
+            number_bytes,
+            descriptors,
+        })
+    }
+
+    fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        track_io!(writer.write_u16::<BigEndian>(self.table_type))?;
+        track!(self.pid.write_to(&mut writer))?;
+        track_io!(writer.write_u8(0b1110_0000 | self.version_number.as_u8()))?;
+        track_io!(writer.write_u32::<BigEndian>(self.number_bytes))?;
+        track!(write_descriptors(writer, &self.descriptors, 12))?;
+        Ok(())
+    }
+}
+
+/// Terrestrial or Cable Virtual Channel Table (TVCT or CVCT).
+///
+/// The channels and descriptors of a multi-section table are merged in order of section number.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vct {
+    /// `true` for a CVCT, `false` for a TVCT.
+    pub is_cable: bool,
+    pub transport_stream_id: u16,
+    pub version_number: VersionNumber,
+
+    /// Whether the table is currently applicable.
+    ///
+    /// If `false`, the table is not yet applicable and shall be the next table to become valid.
+    pub is_current: bool,
+    pub protocol_version: u8,
+    pub channels: Vec<VirtualChannel>,
+
+    /// Additional descriptors that apply to all the channels.
+    pub descriptors: Vec<Descriptor>,
+}
+impl Vct {
+    /// Table ID of TVCT.
+    pub const TERRESTRIAL_TABLE_ID: u8 = 0xC8;
+
+    /// Table ID of CVCT.
+    pub const CABLE_TABLE_ID: u8 = 0xC9;
+
+    pub(super) fn from_psi(psi: Psi, lenient: bool) -> Result<Self> {
+        let table_id = track_assert_some!(psi.tables.first(), ErrorKind::InvalidInput)
+            .header
+            .table_id;
+        let is_cable = table_id == Self::CABLE_TABLE_ID;
+        let sections = track!(psi.table_sections(table_id))?;
+
+        let mut protocol_version = 0;
+        let mut channels = Vec::new();
+        let mut descriptors = Vec::new();
+        for (i, syntax) in sections.iter().enumerate() {
+            let mut reader = &syntax.table_data[..];
+            let n = track_io!(reader.read_u8())?;
+            if i == 0 {
+                protocol_version = n;
+            }
+            let num_channels_in_section = track_io!(reader.read_u8())?;
+            for _ in 0..num_channels_in_section {
+                channels.push(track!(VirtualChannel::read_from(
+                    &mut reader,
+                    is_cable,
+                    lenient
+                ))?);
+            }
+            descriptors.extend(track!(read_descriptors(&mut reader, 10, lenient))?);
+            track_assert!(reader.is_empty(), ErrorKind::InvalidInput, "Trailing bytes");
+        }
+
+        Ok(Vct {
+            is_cable,
+            transport_stream_id: sections[0].table_id_extension,
+            version_number: sections[0].version_number,
+            is_current: sections[0].current_next_indicator,
+            protocol_version,
+            channels,
+            descriptors,
+        })
+    }
+
+    pub(super) fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        track!(self.to_psi().and_then(|psi| psi.write_to(writer)))
+    }
+
+    fn to_psi(&self) -> Result<Psi> {
+        let mut table_data = Vec::new();
+        track_io!(table_data.write_u8(self.protocol_version))?;
+        track_assert!(self.channels.len() <= 0xFF, ErrorKind::InvalidInput);
+        track_io!(table_data.write_u8(self.channels.len() as u8))?;
+        for channel in &self.channels {
+            track!(channel.write_to(&mut table_data, self.is_cable))?;
+        }
+        track!(write_descriptors(&mut table_data, &self.descriptors, 10))?;
+
+        let table_id = if self.is_cable {
+            Self::CABLE_TABLE_ID
+        } else {
+            Self::TERRESTRIAL_TABLE_ID
+        };
+        Ok(psip_section(
+            table_id,
+            self.transport_stream_id,
+            self.version_number,
+            self.is_current,
+            table_data,
+        ))
+    }
+}
+
+/// Virtual channel (an entry of a virtual channel table).
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VirtualChannel {
+    /// Name of the channel (up to seven UTF-16 code units, e.g., `"KVTV-HD"`).
+    ///
+    /// The trailing NUL characters are removed.
+    pub short_name: String,
+    pub major_channel_number: u16,
+    pub minor_channel_number: u16,
+
+    /// Modulation mode (e.g., `0x04` for 8-VSB).
+    pub modulation_mode: u8,
+    pub carrier_frequency: u32,
+    pub channel_tsid: u16,
+    pub program_number: u16,
+    pub etm_location: u8,
+    pub access_controlled: bool,
+    pub hidden: bool,
+
+    /// `path_select` (always `false` for TVCTs).
+    pub path_select: bool,
+
+    /// `out_of_band` (always `false` for TVCTs).
+    pub out_of_band: bool,
+    pub hide_guide: bool,
+
+    /// Service type (e.g., `0x02` for ATSC digital television).
+    pub service_type: u8,
+    pub source_id: u16,
+    pub descriptors: Vec<Descriptor>,
+}
+impl VirtualChannel {
+    /// Tag of the extended channel name descriptor.
+    pub const EXTENDED_CHANNEL_NAME_TAG: u8 = 0xA0;
+
+    const SHORT_NAME_LEN: usize = 7;
+    const MAX_CHANNEL_NUMBER: u16 = 0x3FF;
+    const MAX_ETM_LOCATION: u8 = 0b11;
+    const MAX_SERVICE_TYPE: u8 = 0b11_1111;
+
+    /// Returns the long channel name in the extended channel name descriptor (if any).
+    ///
+    /// # Errors
+    ///
+    /// If the descriptor is malformed, it will return an `ErrorKind::InvalidInput` error.
+    pub fn extended_name(&self) -> Result<Option<MultipleStringStructure>> {
+        let Some(d) = self
+            .descriptors
+            .iter()
+            .find(|d| d.tag == Self::EXTENDED_CHANNEL_NAME_TAG)
+        else {
+            return Ok(None);
+        };
+        track!(MultipleStringStructure::parse(&d.data)).map(Some)
+    }
+
+    fn read_from(reader: &mut &[u8], is_cable: bool, lenient: bool) -> Result<Self> {
+        let mut short_name = [0; Self::SHORT_NAME_LEN];
+        track_io!(reader.read_u16_into::<BigEndian>(&mut short_name))?;
+        let name_len = short_name
+            .iter()
+            .rposition(|&c| c != 0)
+            .map_or(0, |i| i + 1);
+        let short_name = String::from_utf16_lossy(&short_name[..name_len]);
+
+        let n = track_io!(reader.read_u24::<BigEndian>())?;
+        track!(check_reserved_bits(n, 0xF0_0000, lenient))?;
+        let major_channel_number = (n >> 10) as u16 & Self::MAX_CHANNEL_NUMBER;
+        let minor_channel_number = n as u16 & Self::MAX_CHANNEL_NUMBER;
+
+        let modulation_mode = track_io!(reader.read_u8())?;
+        let carrier_frequency = track_io!(reader.read_u32::<BigEndian>())?;
+        let channel_tsid = track_io!(reader.read_u16::<BigEndian>())?;
+        let program_number = track_io!(reader.read_u16::<BigEndian>())?;
+
+        let n = track_io!(reader.read_u16::<BigEndian>())?;
+        let reserved_bits = if is_cable {
+            0b0000_0001_1100_0000
+        } else {
+            0b0000_1101_1100_0000
+        };
+        track!(check_reserved_bits(u32::from(n), reserved_bits, lenient))?;
+        let source_id = track_io!(reader.read_u16::<BigEndian>())?;
+        let descriptors = track!(read_descriptors(reader, 10, lenient))?;
+
+        Ok(VirtualChannel {
+            short_name,
+            major_channel_number,
+            minor_channel_number,
+            modulation_mode,
+            carrier_frequency,
+            channel_tsid,
+            program_number,
+            etm_location: (n >> 14) as u8,
+            access_controlled: n & (1 << 13) != 0,
+            hidden: n & (1 << 12) != 0,
+            path_select: is_cable && n & (1 << 11) != 0,
+            out_of_band: is_cable && n & (1 << 10) != 0,
+            hide_guide: n & (1 << 9) != 0,
+            service_type: n as u8 & Self::MAX_SERVICE_TYPE,
+            source_id,
+            descriptors,
+        })
+    }
+
+    fn write_to<W: Write>(&self, mut writer: W, is_cable: bool) -> Result<()> {
+        let short_name = self.short_name.encode_utf16().collect::<Vec<_>>();
+        track_assert!(
+            short_name.len() <= Self::SHORT_NAME_LEN,
+            ErrorKind::InvalidInput,
+            "Too long short name: {:?}",
+            self.short_name
+        );
+        for i in 0..Self::SHORT_NAME_LEN {
+            track_io!(writer.write_u16::<BigEndian>(short_name.get(i).copied().unwrap_or(0)))?;
+        }
+
+        track_assert!(
+            self.major_channel_number <= Self::MAX_CHANNEL_NUMBER
+                && self.minor_channel_number <= Self::MAX_CHANNEL_NUMBER,
+            ErrorKind::InvalidInput,
+            "Too large channel number: {}-{}",
+            self.major_channel_number,
+            self.minor_channel_number
+        );
+        let n = 0xF0_0000
+            | u32::from(self.major_channel_number) << 10
+            | u32::from(self.minor_channel_number);
+        track_io!(writer.write_u24::<BigEndian>(n))?;
+
+        track_io!(writer.write_u8(self.modulation_mode))?;
+        track_io!(writer.write_u32::<BigEndian>(self.carrier_frequency))?;
+        track_io!(writer.write_u16::<BigEndian>(self.channel_tsid))?;
+        track_io!(writer.write_u16::<BigEndian>(self.program_number))?;
+
+        track_assert!(
+            self.etm_location <= Self::MAX_ETM_LOCATION,
+            ErrorKind::InvalidInput
+        );
+        track_assert!(
+            self.service_type <= Self::MAX_SERVICE_TYPE,
+            ErrorKind::InvalidInput
+        );
+        let (path_select, out_of_band) = if is_cable {
+            (self.path_select, self.out_of_band)
+        } else {
+            // Reserved bits
+            (true, true)
+        };
+        let n = u16::from(self.etm_location) << 14
+            | u16::from(self.access_controlled) << 13
+            | u16::from(self.hidden) << 12
+            | u16::from(path_select) << 11
+            | u16::from(out_of_band) << 10
+            | u16::from(self.hide_guide) << 9
+            | 0b0000_0001_1100_0000
+            | u16::from(self.service_type);
+        track_io!(writer.write_u16::<BigEndian>(n))?;
+        track_io!(writer.write_u16::<BigEndian>(self.source_id))?;
+        track!(write_descriptors(writer, &self.descriptors, 10))?;
+        Ok(())
+    }
+}
+
+/// Multiple string structure, which carries the texts of PSIP in one or more languages.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultipleStringStructure {
+    /// The same text in different languages.
+    pub strings: Vec<MultipleString>,
+}
+impl MultipleStringStructure {
+    /// Parses a multiple string structure.
+    ///
+    /// # Errors
+    ///
+    /// If the bytes are malformed, it will return an `ErrorKind::InvalidInput` error.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = bytes;
+        let number_strings = track_io!(reader.read_u8())?;
+        let mut strings = Vec::new();
+        for _ in 0..number_strings {
+            let mut language = [0; 3];
+            track_io!(reader.read_exact(&mut language))?;
+            let number_segments = track_io!(reader.read_u8())?;
+            let mut segments = Vec::new();
+            for _ in 0..number_segments {
+                let compression_type = track_io!(reader.read_u8())?;
+                let mode = track_io!(reader.read_u8())?;
+                let number_bytes = track_io!(reader.read_u8())?;
+                let bytes = track!(take(&mut reader, usize::from(number_bytes)))?;
+                segments.push(StringSegment {
+                    compression_type,
+                    mode,
+                    bytes: bytes.to_vec(),
+                });
+            }
+            strings.push(MultipleString { language, segments });
+        }
+        Ok(MultipleStringStructure { strings })
+    }
+
+    /// Returns the text in the given ISO 639-2 language (e.g., `"eng"`),
+    /// or the first text if there is no text in the language.
+    ///
+    /// It will return `None` if the text cannot be decoded (see `StringSegment::text`).
+    pub fn text(&self, language: &str) -> Option<String> {
+        self.strings
+            .iter()
+            .find(|s| s.language.eq_ignore_ascii_case(language.as_bytes()))
+            .or_else(|| self.strings.first())?
+            .text()
+    }
+}
+
+/// Text in a language of a multiple string structure.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultipleString {
+    /// ISO 639-2 language code (e.g., `*b"eng"`).
+    pub language: [u8; 3],
+
+    /// The segments of the text, which may be encoded differently.
+    pub segments: Vec<StringSegment>,
+}
+impl MultipleString {
+    /// Decodes the segments and concatenates them.
+    ///
+    /// It will return `None` if any of the segments cannot be decoded (see `StringSegment::text`).
+    pub fn text(&self) -> Option<String> {
+        self.segments.iter().map(|s| s.text()).collect()
+    }
+}
+
+/// Segment of a multiple string structure.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StringSegment {
+    /// Compression type (`0x00` for no compression, `0x01` and `0x02` for the Huffman codings).
+    pub compression_type: u8,
+
+    /// Mode, which selects the Unicode page of the bytes (or `0x3F` for UTF-16).
+    pub mode: u8,
+    pub bytes: Vec<u8>,
+}
+impl StringSegment {
+    /// Decodes the segment.
+    ///
+    /// Only uncompressed segments in the modes that select a Unicode page
+    /// (each byte is the lower byte of a code point) or UTF-16 are supported,
+    /// and `None` is returned for the others (e.g., Huffman-coded and SCSU segments).
+    pub fn text(&self) -> Option<String> {
+        if self.compression_type != 0 {
+            return None;
+        }
+        match self.mode {
+            0x00..=0x06 | 0x09..=0x10 | 0x20..=0x27 | 0x30..=0x33 => self
+                .bytes
+                .iter()
+                .map(|&b| char::from_u32(u32::from(self.mode) << 8 | u32::from(b)))
+                .collect(),
+            0x3F => {
+                if self.bytes.len() % 2 != 0 {
+                    return None;
+                }
+                let units = self
+                    .bytes
+                    .chunks(2)
+                    .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                    .collect::<Vec<_>>();
+                String::from_utf16(&units).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+fn psip_section(
+    table_id: u8,
+    table_id_extension: u16,
+    version_number: VersionNumber,
+    is_current: bool,
+    table_data: Vec<u8>,
+) -> Psi {
+    let header = PsiTableHeader {
+        table_id,
+        private_bit: true,
+    };
+    let syntax = Some(PsiTableSyntax {
+        table_id_extension,
+        version_number,
+        current_next_indicator: is_current,
+        section_number: 0,
+        last_section_number: 0,
+        table_data,
+    });
+    let tables = vec![PsiTable {
+        header,
+        syntax,
+        crc_ok: true,
+    }];
+    Psi { tables }
+}
+
+fn check_reserved_bits(n: u32, mask: u32, lenient: bool) -> Result<()> {
+    if !lenient {
+        track_assert_eq!(
+            n & mask,
+            mask,
+            ErrorKind::InvalidInput,
+            "Unexpected reserved bits"
+        );
+    }
+    Ok(())
+}
+
+fn take<'a>(reader: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+    track_assert!(
+        reader.len() >= n,
+        ErrorKind::InvalidInput,
+        "Too short: size={}, expected={}",
+        reader.len(),
+        n
+    );
+    let (head, tail) = reader.split_at(n);
+    *reader = tail;
+    Ok(head)
+}
+
+/// Reads a descriptor loop preceded by its length field of `len_bits` bits (and reserved bits).
+fn read_descriptors(reader: &mut &[u8], len_bits: u32, lenient: bool) -> Result<Vec<Descriptor>> {
+    let n = track_io!(reader.read_u16::<BigEndian>())?;
+    let len_mask: u16 = (1 << len_bits) - 1;
+    track!(check_reserved_bits(
+        u32::from(n),
+        u32::from(!len_mask),
+        lenient
+    ))?;
+    let mut descriptors_reader = track!(take(reader, usize::from(n & len_mask)))?;
+    let mut descriptors = Vec::new();
+    while !descriptors_reader.is_empty() {
+        descriptors.push(track!(Descriptor::read_from(&mut descriptors_reader))?);
+    }
+    Ok(descriptors)
+}
+
+fn write_descriptors<W: Write>(
+    mut writer: W,
+    descriptors: &[Descriptor],
+    len_bits: u32,
+) -> Result<()> {
+    let len_mask: u16 = (1 << len_bits) - 1;
+    let len: usize = descriptors.iter().map(|d| 2 + d.data.len()).sum();
+    track_assert!(
+        len <= usize::from(len_mask),
+        ErrorKind::InvalidInput,
+        "Too large descriptors: {} bytes",
+        len
+    );
+    track_io!(writer.write_u16::<BigEndian>(!len_mask | len as u16))?;
+    for d in descriptors {
+        track!(d.write_to(&mut writer))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crc::Crc32;
+    use crate::ts::{
+        PidKind, ReadTsPacket, TsPacket, TsPacketReader, TsPacketWriter, TsPayload, WriteTsPacket,
+    };
+
+    // The tables below are built from the field layouts in ATSC A/65,
+    // since no ATSC capture is available to be checked in.
+
+    /// Makes the payload of a PSIP packet (a pointer field followed by a section).
+    fn psip_payload(table_id: u8, table_id_extension: u16, table_data: &[u8]) -> Vec<u8> {
+        let section_len = 5 + table_data.len() + 4;
+        let mut section = vec![table_id, 0xF0 | (section_len >> 8) as u8, section_len as u8];
+        section.extend_from_slice(&table_id_extension.to_be_bytes());
+        // version_number = 3, current_next_indicator = 1, section_number = 0, last_section_number = 0
+        section.extend_from_slice(&[0xC7, 0x00, 0x00]);
+        section.extend_from_slice(table_data);
+        let mut crc32 = Crc32::new();
+        crc32.update(&section);
+        section.extend_from_slice(&crc32.value().to_be_bytes());
+
+        let mut payload = vec![0];
+        payload.extend_from_slice(&section);
+        payload
+    }
+
+    fn mgt_payload() -> Vec<u8> {
+        psip_payload(
+            0xC7,
+            0x0000,
+            &[
+                0x00, // protocol_version
+                0x00, 0x02, // tables_defined
+                // TVCT
+                0x00, 0x00, 0xFF, 0xFB, 0xE3, 0x00, 0x00, 0x00, 0x4B, 0xF0, 0x00,
+                // EIT-0
+                0x01, 0x00, 0xFD, 0x00, 0xE0, 0x00, 0x00, 0x01, 0x00, 0xF0, 0x00, 0xF0,
+                0x00, // descriptors_length
+            ],
+        )
+    }
+
+    fn tvct_payload() -> Vec<u8> {
+        let mut data = vec![
+            0x00, // protocol_version
+            0x01, // num_channels_in_section
+        ];
+        for c in "KVTV-HD".encode_utf16() {
+            data.extend_from_slice(&c.to_be_bytes());
+        }
+        data.extend_from_slice(&[
+            0xF0, 0x1C, 0x01, // major_channel_number = 7, minor_channel_number = 1
+            0x04, // modulation_mode (8-VSB)
+            0x00, 0x00, 0x00, 0x00, // carrier_frequency
+            0x00, 0x01, // channel_TSID
+            0x00, 0x03, // program_number
+            0x0D, 0xC2, // ETM_location = 0, hide_guide = 0 and service_type = 2
+            0x00, 0x05, // source_id
+            0xFC, 0x13, // descriptors_length
+            // Extended channel name descriptor
+            0xA0, 0x11, 0x01, b'e', b'n', b'g', 0x02, //
+            0x00, 0x00, 0x02, b'K', b'V', // Latin-1 segment
+            0x00, 0x3F, 0x04, 0x00, b'T', 0x00, b'V', // UTF-16 segment
+            0xFC, 0x00, // additional_descriptors_length
+        ]);
+        psip_payload(0xC8, 0x0001, &data)
+    }
+
+    fn read_payloads(payloads: &[Vec<u8>], kind: Option<PidKind>) -> Vec<TsPayload> {
+        let pid = Pid::new_const(Pid::PSIP);
+        let mut writer = TsPacketWriter::new(Vec::new());
+        for payload in payloads {
+            let packet = track_try_unwrap!(TsPacket::builder(pid)
+                .payload_unit_start_indicator(true)
+                .payload_raw(payload)
+                .build());
+            track_try_unwrap!(writer.write_ts_packet(&packet));
+        }
+        let stream = writer.into_stream();
+
+        let mut reader = TsPacketReader::new(&stream[..]);
+        if let Some(kind) = kind {
+            reader.register_pid(pid, kind);
+        }
+        let mut payloads = Vec::new();
+        while let Some(packet) = track_try_unwrap!(reader.read_ts_packet()) {
+            payloads.push(packet.payload.unwrap());
+        }
+        payloads
+    }
+
+    #[test]
+    fn mgt_and_vct() {
+        // System time table
+        let stt = psip_payload(
+            0xCD,
+            0x0000,
+            &[0x00, 0x4B, 0x2F, 0x3C, 0x00, 0x12, 0x00, 0x00, 0xF0, 0x00],
+        );
+        let originals = [mgt_payload(), tvct_payload(), stt];
+        let payloads = read_payloads(&originals, None);
+
+        let mgt = payloads[0].as_mgt().unwrap();
+        assert_eq!(mgt.version_number.as_u8(), 3);
+        assert!(mgt.is_current);
+        assert_eq!(mgt.tables.len(), 2);
+        assert_eq!(mgt.tables[0].table_type, 0x0000);
+        assert_eq!(mgt.tables[0].pid, Pid::new_const(Pid::PSIP));
+        assert_eq!(mgt.tables[0].version_number.as_u8(), 3);
+        assert_eq!(mgt.tables[0].number_bytes, 0x4B);
+        assert_eq!(mgt.tables[1].table_type, 0x0100);
+        assert_eq!(mgt.tables[1].pid, Pid::new_const(0x1D00));
+        assert_eq!(payloads[0].to_string(), "MGT[VER=3 TABLES=2]");
+
+        let vct = payloads[1].as_vct().unwrap();
+        assert!(!vct.is_cable);
+        assert_eq!(vct.transport_stream_id, 1);
+        assert_eq!(vct.channels.len(), 1);
+        let channel = &vct.channels[0];
+        assert_eq!(channel.short_name, "KVTV-HD");
+        assert_eq!(
+            (channel.major_channel_number, channel.minor_channel_number),
+            (7, 1)
+        );
+        assert_eq!(channel.modulation_mode, 0x04);
+        assert_eq!(channel.channel_tsid, 1);
+        assert_eq!(channel.program_number, 3);
+        assert!(!channel.path_select && !channel.out_of_band && !channel.hide_guide);
+        assert_eq!(channel.service_type, 0x02);
+        assert_eq!(channel.source_id, 5);
+        let name = track_try_unwrap!(channel.extended_name()).unwrap();
+        assert_eq!(name.strings[0].language, *b"eng");
+        assert_eq!(name.text("eng").as_deref(), Some("KVTV"));
+        assert_eq!(payloads[1].to_string(), "TVCT[TSID=1 VER=3 CHANNELS=1]");
+
+        assert_eq!(
+            payloads[2].as_psi().unwrap().tables[0].header.table_id,
+            0xCD
+        );
+
+        // Round trip
+        for (payload, original) in payloads.iter().zip(&originals).take(2) {
+            let mut bytes = Vec::new();
+            track_try_unwrap!(payload.write_to(&mut bytes));
+            assert_eq!(&bytes, original);
+        }
+
+        // Sections
+        let payloads = read_payloads(&originals, Some(PidKind::Section));
+        assert!(payloads.iter().all(|p| p.as_section().is_some()));
+    }
+
+    #[test]
+    fn multiple_string_structure() {
+        let bytes = [
+            0x02, // number_strings
+            b's', b'p', b'a', 0x01, 0x00, 0x00, 0x04, b'C', b'a', b'f', 0xE9, //
+            b'e', b'n', b'g', 0x01, 0x01, 0x00, 0x02, 0xAB, 0xCD, // Huffman-coded
+        ];
+        let mss = track_try_unwrap!(MultipleStringStructure::parse(&bytes));
+        assert_eq!(mss.strings.len(), 2);
+        assert_eq!(mss.text("spa").as_deref(), Some("Café"));
+        assert_eq!(mss.text("fre").as_deref(), Some("Café"));
+        assert_eq!(mss.text("eng"), None);
+        assert_eq!(mss.strings[1].segments[0].compression_type, 1);
+
+        let e = MultipleStringStructure::parse(&bytes[..10]).err().unwrap();
+        assert_eq!(*e.kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -1,8 +1,31 @@
-use crate::ts::payload::{Bytes, Null, Pat, Pes, Pmt};
-use crate::ts::{AdaptationField, Pid, TsHeader, TsPacket, TsPayload};
+use crate::io::Read;
+#[cfg(feature = "std")]
+use crate::io::{Seek, SeekFrom};
+#[cfg(feature = "std")]
+use crate::time::{ClockReference, Timestamp};
+use crate::ts::format::MAX_PACKET_SIZE;
+use crate::ts::payload::{Bytes, Mgt, Null, Pat, Pes, Pmt, Section, Tsdt, Vct};
+use crate::ts::pid_table::PidTable;
+use crate::ts::psi::{self, Psi, PsiTable};
+use crate::ts::{
+    AdaptationField, ContinuityCheck, ContinuityChecker, Descrambler, PacketFormat, Pid,
+    TransportScramblingControl, TsHeader, TsPacket, TsPayload, VersionNumber,
+};
 use crate::{ErrorKind, Result};
-use std::collections::HashMap;
-use std::io::Read;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::fmt;
+
+/// Number of bytes examined to detect the packet format.
+const PROBE_SIZE: usize = 4096;
+
+/// Minimum number of sync bytes that must follow the one found after seeking.
+#[cfg(feature = "std")]
+const SEEK_RESYNC_CONFIDENCE: usize = 3;
+
+/// Range (in packets) at which the bisection of `TsPacketReader::seek_to_timestamp` stops.
+#[cfg(feature = "std")]
+const SEEK_BISECTION_PACKETS: u64 = 16;
 
 /// The `ReadTsPacket` trait allows for reading TS packets from a source.
 pub trait ReadTsPacket {
@@ -10,44 +33,827 @@ pub trait ReadTsPacket {
     ///
     /// If the end of the stream is reached, it will return `Ok(None)`.
     fn read_ts_packet(&mut self) -> Result<Option<TsPacket>>;
+
+    /// Returns the byte offset of the last read packet in the stream (if known).
+    ///
+    /// The default implementation returns `None`.
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns an iterator over the TS packets.
+    ///
+    /// The iterator ends when the end of the stream is reached or after the first error is returned.
+    fn packets(&mut self) -> TsPackets<&mut Self>
+    where
+        Self: Sized,
+    {
+        TsPackets::new(self)
+    }
+}
+impl<T: ReadTsPacket + ?Sized> ReadTsPacket for &mut T {
+    fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+        (**self).read_ts_packet()
+    }
+
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        (**self).last_packet_byte_offset()
+    }
+}
+impl<T: ReadTsPacket + ?Sized> ReadTsPacket for Box<T> {
+    fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+        (**self).read_ts_packet()
+    }
+
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        (**self).last_packet_byte_offset()
+    }
 }
 
-/// TS packet reader.
+/// Iterator over the TS packets read by a `ReadTsPacket` implementation.
+///
+/// This is created by `ReadTsPacket::packets` or `TsPacketReader::into_iter`.
 #[derive(Debug)]
+pub struct TsPackets<R> {
+    reader: R,
+    done: bool,
+}
+impl<R: ReadTsPacket> TsPackets<R> {
+    fn new(reader: R) -> Self {
+        TsPackets {
+            reader,
+            done: false,
+        }
+    }
+
+    /// Converts `TsPackets` into the underlaying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+impl<R: ReadTsPacket> Iterator for TsPackets<R> {
+    type Item = Result<TsPacket>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = track!(self.reader.read_ts_packet()).transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+impl<R: ReadTsPacket> core::iter::FusedIterator for TsPackets<R> {}
+
+/// Policy for handling packets that have the transport error indicator set.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransportErrorPolicy {
+    /// Such packets are parsed in the same way as the other packets (the default).
+    #[default]
+    Parse,
+
+    /// Such packets are dropped silently.
+    Drop,
+
+    /// Such packets are returned without being parsed.
+    ///
+    /// The adaptation field of the resulting packet is always `None`,
+    /// and the payload is a `TsPayload::Raw` that contains all the bytes following the packet header
+    /// (including the adaptation field, if any).
+    Raw,
+}
+
+/// Policy for handling a packet truncated by the end of the stream.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TruncatedPacketPolicy {
+    /// Reading such a packet results in an `ErrorKind::InvalidInput` error (the default).
+    #[default]
+    InvalidInput,
+
+    /// Reading such a packet results in an `ErrorKind::TruncatedPacket` error.
+    ///
+    /// The truncated bytes are discarded, so the next read reaches the end of the stream.
+    TruncatedPacket,
+
+    /// Such packets are discarded silently, i.e., the stream is treated as if it ended cleanly.
+    Discard,
+}
+
+/// Mode of PSI section CRC32 checking.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CrcCheck {
+    /// Sections with an invalid CRC32 are rejected as `ErrorKind::CrcMismatch` (the default).
+    #[default]
+    Strict,
+
+    /// Sections with an invalid CRC32 are parsed anyway and only counted.
+    ///
+    /// The number of such sections can be retrieved via `TsPacketReader::crc_error_count`.
+    Count,
+}
+
+/// TS packet reader.
+///
+/// PAT and PMT sections spanning multiple packets are reassembled,
+/// and tables consisting of multiple sections are merged:
+/// the table is returned as the payload of the packet that completes it,
+/// and the payloads of the preceding packets are returned as `TsPayload::Raw`.
 pub struct TsPacketReader<R> {
     stream: R,
-    pids: HashMap<Pid, PidKind>,
+    buffer: VecDeque<u8>,
+    packet_format: PacketFormat,
+    resync_confidence: Option<usize>,
+    skipped_bytes: u64,
+    byte_offset: u64,
+    packet_index: u64,
+    last_packet_location: (u64, Option<Pid>),
+    pids: BTreeMap<Pid, PidKind>,
+    pid_kinds: PidTable<PidKind>,
+    programs: BTreeMap<Pid, (VersionNumber, Vec<Pid>)>,
+    program_layout_changed: bool,
+    descrambler: Option<Box<dyn Descrambler + Send>>,
+    continuity_check: ContinuityCheck,
+    continuity_checker: ContinuityChecker,
+    continuity_error_count: u64,
+    transport_error_policy: TransportErrorPolicy,
+    truncated_packet_policy: TruncatedPacketPolicy,
+    strict_pids: bool,
+    skip_null_packets: bool,
+    lenient_timestamps: bool,
+    lenient_reserved_bits: bool,
+    keep_raw_tables: bool,
+    crc_check: CrcCheck,
+    crc_error_count: u64,
+    psi_buffers: BTreeMap<Pid, Vec<u8>>,
+    psi_sections: BTreeMap<(Pid, u16, bool), Vec<PsiTable>>,
 }
-impl<R: Read> TsPacketReader<R> {
+impl<R> TsPacketReader<R> {
     /// Makes a new `TsPacketReader` instance.
     pub fn new(stream: R) -> Self {
+        Self::with_pids(stream, BTreeMap::new())
+    }
+
+    /// Makes a new `TsPacketReader` instance that knows the given PIDs in advance.
+    ///
+    /// This is useful to start demultiplexing before the next PAT and PMT arrive
+    /// (e.g., when joining a live stream whose layout is already known).
+    pub fn with_pids(stream: R, pids: BTreeMap<Pid, PidKind>) -> Self {
         TsPacketReader {
             stream,
-            pids: HashMap::new(),
+            buffer: VecDeque::new(),
+            packet_format: PacketFormat::default(),
+            resync_confidence: None,
+            skipped_bytes: 0,
+            byte_offset: 0,
+            packet_index: 0,
+            last_packet_location: (0, None),
+            pid_kinds: Self::pid_table(&pids),
+            pids,
+            programs: BTreeMap::new(),
+            program_layout_changed: false,
+            descrambler: None,
+            continuity_check: ContinuityCheck::default(),
+            continuity_checker: ContinuityChecker::new(),
+            continuity_error_count: 0,
+            transport_error_policy: TransportErrorPolicy::default(),
+            truncated_packet_policy: TruncatedPacketPolicy::default(),
+            strict_pids: false,
+            skip_null_packets: false,
+            lenient_timestamps: false,
+            lenient_reserved_bits: false,
+            keep_raw_tables: false,
+            crc_check: CrcCheck::default(),
+            crc_error_count: 0,
+            psi_buffers: BTreeMap::new(),
+            psi_sections: BTreeMap::new(),
         }
     }
 
+    /// Returns the framing of the packets in the stream.
+    pub fn packet_format(&self) -> PacketFormat {
+        self.packet_format
+    }
+
+    /// Sets the framing of the packets in the stream.
+    ///
+    /// The default value is `PacketFormat::Ts`.
+    pub fn set_packet_format(&mut self, format: PacketFormat) {
+        self.packet_format = format;
+    }
+
+    /// Enables resynchronization to the packet boundaries of corrupted or misaligned input.
+    ///
+    /// If `Some(n)` is specified and a packet does not start with the sync byte,
+    /// the reader skips bytes until it finds a sync byte that is followed by `n` more sync bytes
+    /// at intervals of the packet size, and continues reading from there.
+    /// The number of skipped bytes can be retrieved via `skipped_bytes`.
+    ///
+    /// The default value is `None`, i.e., a packet without the sync byte results in an error.
+    pub fn set_resync_confidence(&mut self, confidence: Option<usize>) {
+        self.resync_confidence = confidence;
+    }
+
+    /// Returns the number of bytes skipped by resynchronization so far.
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped_bytes
+    }
+
+    /// Returns the byte offset of the next packet in the stream.
+    ///
+    /// The offset is counted from the position at which the reader started reading
+    /// (or from the start of the stream after `seek_to_byte` or `seek_to_timestamp` is called).
+    pub fn byte_offset(&self) -> u64 {
+        self.byte_offset
+    }
+
+    /// Returns the index (zero origin) of the next packet in the stream.
+    ///
+    /// This counts all the packets taken from the stream, including the dropped ones
+    /// (e.g., by `TransportErrorPolicy::Drop` or `set_skip_null_packets`).
+    /// Unlike `byte_offset`, this is not updated by seeking.
+    ///
+    /// The byte offset and the index of the packet are attached to the errors that occur while decoding it.
+    pub fn packet_index(&self) -> u64 {
+        self.packet_index
+    }
+
+    /// Sets the continuity counter checking mode.
+    ///
+    /// The default value is `ContinuityCheck::Disabled`.
+    pub fn set_continuity_check(&mut self, mode: ContinuityCheck) {
+        self.continuity_check = mode;
+    }
+
+    /// Sets the policy for packets that have the transport error indicator set.
+    ///
+    /// The default value is `TransportErrorPolicy::Parse`.
+    pub fn set_transport_error_policy(&mut self, policy: TransportErrorPolicy) {
+        self.transport_error_policy = policy;
+    }
+
+    /// Sets the policy for a packet truncated by the end of the stream,
+    /// e.g., a stream recorded or downloaded partially.
+    ///
+    /// The default value is `TruncatedPacketPolicy::InvalidInput`.
+    pub fn set_truncated_packet_policy(&mut self, policy: TruncatedPacketPolicy) {
+        self.truncated_packet_policy = policy;
+    }
+
+    /// Sets whether packets with PIDs that are not announced by PAT or PMT are rejected.
+    ///
+    /// If `false`, the payloads of such packets are returned as `TsPayload::Raw`.
+    /// If `true`, reading such a packet results in an `ErrorKind::InvalidInput` error.
+    /// The default value is `false`.
+    pub fn set_strict_pids(&mut self, strict: bool) {
+        self.strict_pids = strict;
+    }
+
+    /// Sets whether null packets (i.e., packets with `Pid::NULL`) are skipped.
+    ///
+    /// If `true`, null packets are discarded without being decoded and are never returned by `read_ts_packet`.
+    /// The default value is `false`.
+    pub fn set_skip_null_packets(&mut self, skip: bool) {
+        self.skip_null_packets = skip;
+    }
+
+    /// Sets whether invalid marker bits in PTS/DTS fields of PES headers are tolerated.
+    ///
+    /// If `true`, the 33-bit timestamp values are extracted ignoring the marker bits.
+    /// The default value is `false`.
+    pub fn set_lenient_timestamps(&mut self, lenient: bool) {
+        self.lenient_timestamps = lenient;
+    }
+
+    /// Sets whether non-conforming reserved bits in PAT and PMT sections are tolerated.
+    ///
+    /// The default value is `false`, i.e., such sections are rejected as `ErrorKind::InvalidInput`.
+    pub fn set_lenient_reserved_bits(&mut self, lenient: bool) {
+        self.lenient_reserved_bits = lenient;
+    }
+
+    /// Sets whether the payload bytes of PAT and PMT packets are kept in `Pat::raw` and `Pmt::raw`.
+    ///
+    /// This allows relaying the tables byte-transparently (see `TsPacketWriter::set_write_raw_tables`).
+    /// The default value is `false`.
+    pub fn set_keep_raw_tables(&mut self, keep: bool) {
+        self.keep_raw_tables = keep;
+    }
+
+    /// Sets the CRC32 checking mode for PAT and PMT sections.
+    ///
+    /// The default value is `CrcCheck::Strict`.
+    pub fn set_crc_check(&mut self, mode: CrcCheck) {
+        self.crc_check = mode;
+    }
+
+    /// Returns the number of PSI sections with an invalid CRC32 detected so far.
+    pub fn crc_error_count(&self) -> u64 {
+        self.crc_error_count
+    }
+
+    /// Returns `true` if the last read packet contained a PMT
+    /// whose version differs from the previous one on the same PID.
+    ///
+    /// When this happens, the PIDs of the elementary streams removed from the program
+    /// are no longer recognized by the reader.
+    pub fn program_layout_changed(&self) -> bool {
+        self.program_layout_changed
+    }
+
+    /// Returns the number of continuity counter errors detected so far.
+    pub fn continuity_error_count(&self) -> u64 {
+        self.continuity_error_count
+    }
+
+    /// Sets the descrambler used to decrypt the payloads of scrambled packets.
+    ///
+    /// The scrambling control bits of the descrambled packets are cleared.
+    /// If no descrambler is set, the payloads of scrambled packets are returned as `TsPayload::Raw`.
+    pub fn set_descrambler<D>(&mut self, descrambler: D)
+    where
+        D: Descrambler + Send + 'static,
+    {
+        self.descrambler = Some(Box::new(descrambler));
+    }
+
+    /// Registers the kind of the payloads carried by `pid`.
+    ///
+    /// Existing registrations (including the ones learned from PAT and PMT) are overwritten.
+    /// PIDs registered as `PidKind::Psi` or `PidKind::Section` are not overwritten by the PMT
+    /// even if they are listed as elementary streams.
+    pub fn register_pid(&mut self, pid: Pid, kind: PidKind) {
+        self.insert_pid(pid, kind);
+    }
+
+    /// Returns the PIDs known by the reader.
+    ///
+    /// This consists of the PIDs registered by the application and the ones learned from PAT and PMT.
+    pub fn pids(&self) -> &BTreeMap<Pid, PidKind> {
+        &self.pids
+    }
+
     /// Returns a reference to the underlaying byte stream.
     pub fn stream(&self) -> &R {
         &self.stream
     }
 
     /// Converts `TsPacketReader` into the underlaying byte stream `R`.
+    ///
+    /// Note that the bytes read ahead for resynchronization (if any) are lost.
     pub fn into_stream(self) -> R {
         self.stream
     }
 }
-impl<R: Read> ReadTsPacket for TsPacketReader<R> {
-    fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
-        let mut reader = self.stream.by_ref().take(TsPacket::SIZE as u64);
-        let mut peek = [0; 1];
-        let eos = track_io!(reader.read(&mut peek))? == 0;
-        if eos {
+impl<R: Read> TsPacketReader<R> {
+    /// Makes a new `TsPacketReader` instance whose packet format is detected from the stream.
+    ///
+    /// The first few kilobytes of the stream are buffered,
+    /// and the format whose sync bytes appear consistently from the beginning of the stream is chosen.
+    /// The detected format can be retrieved via `packet_format`.
+    ///
+    /// # Errors
+    ///
+    /// If no format matches, it will return an `ErrorKind::InvalidInput` error.
+    pub fn with_detected_packet_format(stream: R) -> Result<Self> {
+        let mut this = Self::new(stream);
+        track!(this.fill_buffer(PROBE_SIZE))?;
+        track!(this.detect_packet_format())?;
+        Ok(this)
+    }
+
+    /// Buffers bytes from the stream until the buffer has `len` bytes or the stream reaches the end.
+    fn fill_buffer(&mut self, len: usize) -> Result<()> {
+        let mut buf = [0; PROBE_SIZE];
+        while self.buffer.len() < len {
+            let size = (len - self.buffer.len()).min(buf.len());
+            let read_size = track_io!(self.stream.read(&mut buf[..size]))?;
+            if read_size == 0 {
+                break;
+            }
+            self.buffer.extend(&buf[..read_size]);
+        }
+        Ok(())
+    }
+
+    /// Reads the bytes of the next packet, and returns them with the M2TS arrival timestamp (if any).
+    ///
+    /// In the common case (i.e., no bytes are buffered and the packet is not misaligned),
+    /// the packet is read directly into a stack buffer without going through the internal buffer.
+    fn read_packet_bytes(&mut self) -> Result<Option<(Option<u32>, [u8; TsPacket::SIZE])>> {
+        if self.buffer.is_empty() {
+            let packet_size = self.packet_format.packet_size();
+            let mut buf = [0; MAX_PACKET_SIZE];
+            let mut read_size = 0;
+            while read_size < packet_size {
+                let n = track_io!(self.stream.read(&mut buf[read_size..packet_size]))?;
+                if n == 0 {
+                    break;
+                }
+                read_size += n;
+            }
+            let sync_byte = buf[self.packet_format.prefix_size()];
+            let misaligned = self.resync_confidence.is_some() && sync_byte != TsPacket::SYNC_BYTE;
+            if read_size == packet_size && !misaligned {
+                return Ok(Some(self.split_packet_bytes(&buf[..packet_size])));
+            }
+            self.buffer.extend(&buf[..read_size]);
+        }
+        track!(self.buffer_packet())?;
+        track!(self.take_packet_bytes())
+    }
+
+    /// Buffers the bytes of the next packet, resynchronizing to the packet boundary if enabled.
+    fn buffer_packet(&mut self) -> Result<()> {
+        let packet_size = self.packet_format.packet_size();
+        track!(self.fill_buffer(packet_size))?;
+        if self.needs_resync() {
+            let confidence = self.resync_confidence.unwrap_or(0);
+            track!(self.resync(confidence))?;
+            track!(self.fill_buffer(packet_size))?;
+        }
+        Ok(())
+    }
+
+    /// Skips bytes until the buffer starts at a packet boundary.
+    fn resync(&mut self, confidence: usize) -> Result<()> {
+        let lookahead = self.resync_lookahead(confidence);
+        loop {
+            track!(self.fill_buffer(lookahead))?;
+            if self.resync_step(confidence) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Recovers from an error that occurred while decoding the last packet taken from the buffer.
+    ///
+    /// The partially received PSI sections of the PID of the packet are discarded,
+    /// and the reader is resynchronized to the next sync byte.
+    /// If only a truncated packet remains in the stream, it is also discarded.
+    pub(super) fn recover(&mut self) -> Result<()> {
+        if let (_, Some(pid)) = self.last_packet_location {
+            self.psi_buffers.remove(&pid);
+            self.psi_sections.retain(|&(p, _, _), _| p != pid);
+        }
+        let confidence = self.resync_confidence.unwrap_or(0);
+        track!(self.resync(confidence))?;
+        track!(self.fill_buffer(self.packet_format.packet_size()))?;
+        if self.buffer.len() < self.packet_format.packet_size() {
+            self.discard_buffered_bytes();
+        }
+        Ok(())
+    }
+}
+#[cfg(feature = "std")]
+impl<R: Read + Seek> TsPacketReader<R> {
+    /// Returns the byte offset of the next packet in the stream.
+    pub fn position(&mut self) -> Result<u64> {
+        let position = track_io!(self.stream.stream_position())?;
+        Ok(position - self.buffer.len() as u64)
+    }
+
+    /// Seeks to the first packet boundary at or after `offset`, and resumes reading from there.
+    ///
+    /// The packet boundary is found by scanning for the sync bytes in the same way as resynchronization
+    /// (see `set_resync_confidence`).
+    /// The partially received PSI sections and the continuity counter states are discarded.
+    /// If `keep_pids` is `false`, all the known PIDs (including the registered ones) are also discarded,
+    /// so that they are learned again from the next PAT and PMT.
+    pub fn seek_to_byte(&mut self, offset: u64, keep_pids: bool) -> Result<()> {
+        track_io!(self.stream.seek(SeekFrom::Start(offset)))?;
+        self.buffer.clear();
+        self.byte_offset = offset;
+        self.psi_buffers.clear();
+        self.psi_sections.clear();
+        self.continuity_checker = ContinuityChecker::new();
+        if !keep_pids {
+            self.pids.clear();
+            self.pid_kinds.clear();
+            self.programs.clear();
+        }
+        let confidence = self
+            .resync_confidence
+            .unwrap_or(0)
+            .max(SEEK_RESYNC_CONFIDENCE);
+        track!(self.resync(confidence))
+    }
+
+    /// Seeks to a packet near (and not after) the position where the PCR reaches `timestamp`.
+    ///
+    /// The position is found by bisecting the stream using the PCRs on `pcr_pid`
+    /// (or any PID if `None` is specified), so the PCRs are assumed to increase monotonically
+    /// without wrapping around.
+    /// The known PIDs are kept as is (see `seek_to_byte`).
+    pub fn seek_to_timestamp(&mut self, pcr_pid: Option<Pid>, timestamp: Timestamp) -> Result<()> {
+        let granularity = self.packet_format.packet_size() as u64 * SEEK_BISECTION_PACKETS;
+        let mut lo = 0;
+        let mut hi = track_io!(self.stream.seek(SeekFrom::End(0)))?;
+        while hi - lo > granularity {
+            let mid = lo + (hi - lo) / 2;
+            track!(self.seek_to_byte(mid, true))?;
+            match track!(self.find_pcr(pcr_pid, hi))? {
+                Some((position, pcr)) if pcr.as_u64() / 300 < timestamp.as_u64() => lo = position,
+                _ => hi = mid,
+            }
+        }
+        track!(self.seek_to_byte(lo, true))
+    }
+
+    /// Finds the first PCR before `limit`, and returns it with the byte offset of the packet.
+    fn find_pcr(
+        &mut self,
+        pcr_pid: Option<Pid>,
+        limit: u64,
+    ) -> Result<Option<(u64, ClockReference)>> {
+        loop {
+            let position = track!(self.position())?;
+            if position >= limit {
+                return Ok(None);
+            }
+            track!(self.buffer_packet())?;
+            let Some((_, packet_bytes)) = track!(self.take_packet_bytes())? else {
+                return Ok(None);
+            };
+            let mut reader = &packet_bytes[..];
+            let (header, adaptation_field_control) = track!(TsHeader::read_from(&mut reader))?;
+            if !adaptation_field_control.has_adaptation_field()
+                || pcr_pid.is_some_and(|pid| pid != header.pid)
+            {
+                continue;
+            }
+            let adaptation_field = track!(AdaptationField::read_from(&mut reader))?;
+            if let Some(pcr) = adaptation_field.and_then(|a| a.pcr) {
+                return Ok(Some((position, pcr)));
+            }
+        }
+    }
+}
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncRead + Unpin> TsPacketReader<R> {
+    pub(super) async fn with_detected_packet_format_async(stream: R) -> Result<Self> {
+        let mut this = Self::new(stream);
+        track!(this.fill_buffer_async(PROBE_SIZE).await)?;
+        track!(this.detect_packet_format())?;
+        Ok(this)
+    }
+
+    /// Asynchronous version of `fill_buffer`.
+    async fn fill_buffer_async(&mut self, len: usize) -> Result<()> {
+        use tokio::io::AsyncReadExt;
+
+        let mut buf = [0; PROBE_SIZE];
+        while self.buffer.len() < len {
+            let size = (len - self.buffer.len()).min(buf.len());
+            let read_size = track_io!(self.stream.read(&mut buf[..size]).await)?;
+            if read_size == 0 {
+                break;
+            }
+            self.buffer.extend(&buf[..read_size]);
+        }
+        Ok(())
+    }
+
+    /// Asynchronous version of `buffer_packet`.
+    async fn buffer_packet_async(&mut self) -> Result<()> {
+        let packet_size = self.packet_format.packet_size();
+        track!(self.fill_buffer_async(packet_size).await)?;
+        if self.needs_resync() {
+            let confidence = self.resync_confidence.unwrap_or(0);
+            track!(self.resync_async(confidence).await)?;
+            track!(self.fill_buffer_async(packet_size).await)?;
+        }
+        Ok(())
+    }
+
+    /// Asynchronous version of `resync`.
+    async fn resync_async(&mut self, confidence: usize) -> Result<()> {
+        let lookahead = self.resync_lookahead(confidence);
+        loop {
+            track!(self.fill_buffer_async(lookahead).await)?;
+            if self.resync_step(confidence) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Asynchronous version of `ReadTsPacket::read_ts_packet`.
+    pub(super) async fn read_ts_packet_async(&mut self) -> Result<Option<TsPacket>> {
+        self.program_layout_changed = false;
+        loop {
+            track!(self.buffer_packet_async().await)?;
+            let Some((arrival_timestamp, packet_bytes)) = track!(self.take_packet_bytes())? else {
+                return Ok(None);
+            };
+            if let Some(packet) = track!(self.decode_packet(arrival_timestamp, &packet_bytes))? {
+                return Ok(Some(packet));
+            }
+        }
+    }
+}
+impl<R> TsPacketReader<R> {
+    /// Replaces the buffered bytes with `bytes`.
+    ///
+    /// `bytes` must consist of whole packets in the packet format of the reader.
+    pub(super) fn set_buffered_bytes(&mut self, bytes: &[u8]) {
+        self.buffer.clear();
+        self.buffer.extend(bytes);
+    }
+
+    /// Decodes the next packet in the buffer.
+    ///
+    /// If the buffer becomes empty, it will return `Ok(None)`.
+    pub(super) fn decode_buffered_packet(&mut self) -> Result<Option<TsPacket>> {
+        self.program_layout_changed = false;
+        while let Some((arrival_timestamp, packet_bytes)) = track!(self.take_packet_bytes())? {
+            if let Some(packet) = track!(self.decode_packet(arrival_timestamp, &packet_bytes))? {
+                return Ok(Some(packet));
+            }
+        }
+        Ok(None)
+    }
+
+    fn detect_packet_format(&mut self) -> Result<()> {
+        let buf = self.buffer.make_contiguous();
+        let format = PacketFormat::CANDIDATES
+            .into_iter()
+            .find(|f| f.matches(buf));
+        self.packet_format = track_assert_some!(
+            format,
+            ErrorKind::InvalidInput,
+            "Cannot detect packet format"
+        );
+        Ok(())
+    }
+
+    /// Discards all the buffered bytes (e.g., a truncated packet at the end of the stream).
+    fn discard_buffered_bytes(&mut self) {
+        self.byte_offset += self.buffer.len() as u64;
+        self.buffer.clear();
+    }
+
+    /// Returns the byte offset and the PID (if the packet header is valid) of the last packet taken from the buffer.
+    pub(super) fn last_packet_location(&self) -> (u64, Option<Pid>) {
+        self.last_packet_location
+    }
+
+    /// Returns `true` if resynchronization is enabled and the buffered packet does not start with the sync byte.
+    fn needs_resync(&self) -> bool {
+        let sync_byte = self.buffer.get(self.packet_format.prefix_size()).copied();
+        self.resync_confidence.is_some() && sync_byte.is_some_and(|b| b != TsPacket::SYNC_BYTE)
+    }
+
+    /// Returns the number of bytes that should be buffered before calling `resync_step`.
+    fn resync_lookahead(&self, confidence: usize) -> usize {
+        self.packet_format.prefix_size() + confidence * self.packet_format.packet_size() + 1
+    }
+
+    /// Skips a byte unless the buffer starts at a packet boundary,
+    /// and returns `true` if the resynchronization has been completed.
+    fn resync_step(&mut self, confidence: usize) -> bool {
+        let packet_size = self.packet_format.packet_size();
+        let offset = self.packet_format.prefix_size();
+        if self.buffer.len() <= offset {
+            return true;
+        }
+        let synchronized = (0..=confidence).all(|i| {
+            self.buffer
+                .get(offset + i * packet_size)
+                .map_or(true, |&b| b == TsPacket::SYNC_BYTE)
+        });
+        if synchronized {
+            return true;
+        }
+        self.buffer.pop_front();
+        self.skipped_bytes += 1;
+        self.byte_offset += 1;
+        false
+    }
+
+    /// Takes the bytes of a buffered packet, and returns them with the M2TS arrival timestamp (if any).
+    fn take_packet_bytes(&mut self) -> Result<Option<(Option<u32>, [u8; TsPacket::SIZE])>> {
+        let packet_size = self.packet_format.packet_size();
+        if self.buffer.is_empty() {
             return Ok(None);
         }
+        self.last_packet_location = (self.byte_offset, None);
+        if self.buffer.len() < packet_size {
+            let size = self.buffer.len();
+            match self.truncated_packet_policy {
+                TruncatedPacketPolicy::InvalidInput => {
+                    track_panic!(
+                        ErrorKind::InvalidInput,
+                        "Truncated TS packet: size={}, byte_offset={}",
+                        size,
+                        self.byte_offset
+                    );
+                }
+                TruncatedPacketPolicy::TruncatedPacket => {
+                    let byte_offset = self.byte_offset;
+                    self.discard_buffered_bytes();
+                    track_panic!(
+                        ErrorKind::TruncatedPacket,
+                        "Truncated TS packet: size={}, byte_offset={}",
+                        size,
+                        byte_offset
+                    );
+                }
+                TruncatedPacketPolicy::Discard => {
+                    self.discard_buffered_bytes();
+                    return Ok(None);
+                }
+            }
+        }
+
+        let mut buf = [0; MAX_PACKET_SIZE];
+        for (b, x) in buf.iter_mut().zip(self.buffer.drain(..packet_size)) {
+            *b = x;
+        }
+        Ok(Some(self.split_packet_bytes(&buf[..packet_size])))
+    }
+
+    /// Splits the bytes of a whole packet (including the prefix and suffix, if any) into
+    /// the M2TS arrival timestamp (if any) and the TS packet bytes, and advances the position of the reader.
+    fn split_packet_bytes(&mut self, packet: &[u8]) -> (Option<u32>, [u8; TsPacket::SIZE]) {
+        let prefix_size = self.packet_format.prefix_size();
+        let (prefix, rest) = packet.split_at(prefix_size);
+        let (bytes, suffix) = rest.split_at(TsPacket::SIZE);
+        let bytes: [u8; TsPacket::SIZE] = bytes.try_into().expect("Never fails");
+
+        self.last_packet_location = (self.byte_offset, None);
+        if bytes[0] == TsPacket::SYNC_BYTE {
+            let pid = Pid::new(u16::from_be_bytes([bytes[1], bytes[2]]) & 0x1FFF).ok();
+            self.last_packet_location.1 = pid;
+        }
+        self.byte_offset += packet.len() as u64;
+        self.packet_index += 1;
+
+        let to_u32 = |x: &[u8]| x.iter().fold(0, |acc, &b| (acc << 8) | u32::from(b));
+        let arrival_timestamp = match self.packet_format {
+            PacketFormat::M2ts => Some(to_u32(prefix)),
+            PacketFormat::TrailingTimestamp => Some(to_u32(suffix)),
+            PacketFormat::Ts | PacketFormat::ReedSolomon => None,
+        };
+        (arrival_timestamp, bytes)
+    }
 
-        let (header, adaptation_field_control, payload_unit_start_indicator) =
-            track!(TsHeader::read_from(peek.chain(&mut reader)))?;
+    /// Decodes the bytes of a packet.
+    ///
+    /// If the packet is dropped, it will return `Ok(None)`.
+    /// The position of the packet is attached to the returned error,
+    /// and a packet whose fields overrun its 188 bytes results in an `ErrorKind::InvalidInput` error.
+    fn decode_packet(
+        &mut self,
+        arrival_timestamp: Option<u32>,
+        packet_bytes: &[u8; TsPacket::SIZE],
+    ) -> Result<Option<TsPacket>> {
+        let result = self
+            .decode_packet_bytes(arrival_timestamp, packet_bytes)
+            .map_err(|e| {
+                // The packet is already in memory, so an I/O error means that a field overruns the packet
+                if *e.kind() == ErrorKind::Other {
+                    e.with_kind(ErrorKind::InvalidInput)
+                } else {
+                    e
+                }
+            });
+        track!(
+            result,
+            "byte_offset={}, packet_index={}",
+            self.last_packet_location.0,
+            self.packet_index - 1
+        )
+    }
+
+    fn decode_packet_bytes(
+        &mut self,
+        arrival_timestamp: Option<u32>,
+        packet_bytes: &[u8; TsPacket::SIZE],
+    ) -> Result<Option<TsPacket>> {
+        let mut reader = &packet_bytes[..];
+
+        let (mut header, adaptation_field_control) = track!(TsHeader::read_from(&mut reader))?;
+
+        if header.transport_error_indicator {
+            match self.transport_error_policy {
+                TransportErrorPolicy::Parse => {}
+                TransportErrorPolicy::Drop => return Ok(None),
+                TransportErrorPolicy::Raw => {
+                    let bytes = track!(Bytes::read_from(&mut reader))?;
+                    track_assert!(reader.is_empty(), ErrorKind::InvalidInput);
+                    return Ok(Some(TsPacket {
+                        header,
+                        adaptation_field: None,
+                        payload: Some(TsPayload::Raw(bytes)),
+                        arrival_timestamp,
+                    }));
+                }
+            }
+        }
+
+        if header.pid.as_u16() == Pid::NULL && self.skip_null_packets {
+            return Ok(None);
+        }
 
         let adaptation_field = if adaptation_field_control.has_adaptation_field() {
             track!(AdaptationField::read_from(&mut reader))?
@@ -55,67 +861,431 @@ impl<R: Read> ReadTsPacket for TsPacketReader<R> {
             None
         };
 
+        if header.pid.as_u16() == Pid::NULL {
+            // Null packets are exempt from continuity checks and their payloads carry no information,
+            // so the payload bytes are discarded without being copied
+            let payload = if adaptation_field_control.has_payload() {
+                Some(TsPayload::Null(track!(Null::read_from(&mut reader))?))
+            } else {
+                None
+            };
+            return Ok(Some(TsPacket {
+                header,
+                adaptation_field,
+                payload,
+                arrival_timestamp,
+            }));
+        }
+
         let payload = if adaptation_field_control.has_payload() {
-            let payload = match header.pid.as_u16() {
-                Pid::PAT => {
-                    let pat = track!(Pat::read_from(&mut reader))?;
+            Some(track!(Bytes::read_from(&mut reader))?)
+        } else {
+            None
+        };
+        track_assert!(reader.is_empty(), ErrorKind::InvalidInput);
+
+        let payload = if let Some(mut bytes) = payload {
+            if let Some(key_parity) = header.transport_scrambling_control.key_parity() {
+                if let Some(descrambler) = self.descrambler.as_mut() {
+                    track!(descrambler.descramble(header.pid, key_parity, &mut bytes))?;
+                    header.transport_scrambling_control = TransportScramblingControl::NotScrambled;
+                }
+            }
+            if header.transport_scrambling_control.is_scrambled() {
+                // The payload is ciphertext, so it is passed through untouched
+                Some(TsPayload::Raw(bytes))
+            } else {
+                Some(track!(self.read_payload(&header, bytes))?)
+            }
+        } else {
+            None
+        };
+        let packet = TsPacket {
+            header,
+            adaptation_field,
+            payload,
+            arrival_timestamp,
+        };
+        if self.continuity_check != ContinuityCheck::Disabled {
+            let continuity = self.continuity_checker.check(&packet);
+            if continuity.is_discontinuous() {
+                self.continuity_error_count += 1;
+                track_assert!(
+                    self.continuity_check != ContinuityCheck::Strict,
+                    ErrorKind::ContinuityError,
+                    "{:?}: pid={:?}",
+                    continuity,
+                    packet.header.pid
+                );
+            }
+        }
+        Ok(Some(packet))
+    }
+
+    /// Buffers the payload of a PSI packet, and returns the payload bytes
+    /// of the section once it has been completely received.
+    ///
+    /// If `all_sections` is `true`, the complete sections following the first one are also returned.
+    fn assemble_psi(
+        &mut self,
+        header: &TsHeader,
+        bytes: &[u8],
+        all_sections: bool,
+    ) -> Option<Vec<u8>> {
+        let buf = if header.payload_unit_start_indicator {
+            let buf = self.psi_buffers.entry(header.pid).or_default();
+            buf.clear();
+            buf
+        } else {
+            // Continuation packets that do not follow a section start are ignored
+            self.psi_buffers.get_mut(&header.pid)?
+        };
+        buf.extend_from_slice(bytes);
+
+        let size = psi::payload_size_of_first_section(buf)?;
+        if buf.len() < size {
+            return None;
+        }
+        let mut buf = self.psi_buffers.remove(&header.pid).expect("Never fails");
+        if all_sections {
+            buf.truncate(psi::payload_size_of_complete_sections(&buf));
+        } else {
+            buf.truncate(size);
+        }
+        Some(buf)
+    }
+
+    /// Reads the sections in `bytes`, and returns all the sections of the table
+    /// once they have been completely received.
+    fn read_psi(&mut self, pid: Pid, bytes: &[u8]) -> Result<Option<Psi>> {
+        let psi = track!(self.read_psi_sections(bytes))?;
+        let mut completed = None;
+        for table in psi.tables {
+            completed = track!(self.collect_psi_section(pid, table))?.or(completed);
+        }
+        Ok(completed)
+    }
+
+    /// Reads the sections in `bytes` without collecting the other sections of the tables.
+    fn read_psi_sections(&mut self, bytes: &[u8]) -> Result<Psi> {
+        let psi = track!(Psi::read_from(bytes, self.lenient_reserved_bits))?;
+        let crc_errors = psi.tables.iter().filter(|t| !t.crc_ok).count() as u64;
+        self.crc_error_count += crc_errors;
+        if self.crc_check == CrcCheck::Strict {
+            track!(psi.check_crc())?;
+        }
+        Ok(psi)
+    }
+
+    fn collect_psi_section(&mut self, pid: Pid, table: PsiTable) -> Result<Option<Psi>> {
+        let syntax = track_assert_some!(table.syntax.as_ref(), ErrorKind::InvalidInput);
+        track_assert!(
+            syntax.section_number <= syntax.last_section_number,
+            ErrorKind::InvalidInput
+        );
+        let key = (
+            pid,
+            syntax.table_id_extension,
+            syntax.current_next_indicator,
+        );
+        if syntax.last_section_number == 0 {
+            self.psi_sections.remove(&key);
+            return Ok(Some(Psi {
+                tables: vec![table],
+            }));
+        }
+
+        let sections = self.psi_sections.entry(key).or_default();
+        let is_same_table = |t: &PsiTable| {
+            let s = t.syntax.as_ref().expect("Never fails");
+            t.header.table_id == table.header.table_id
+                && s.version_number == syntax.version_number
+                && s.last_section_number == syntax.last_section_number
+        };
+        if !sections.first().is_some_and(is_same_table) {
+            // The table has been updated, so the sections collected so far are discarded
+            sections.clear();
+        }
+        let section_number = syntax.section_number;
+        sections
+            .retain(|t| t.syntax.as_ref().expect("Never fails").section_number != section_number);
+        sections.push(table);
+
+        let last_section_number = sections[0]
+            .syntax
+            .as_ref()
+            .expect("Never fails")
+            .last_section_number;
+        if sections.len() <= usize::from(last_section_number) {
+            return Ok(None);
+        }
+        let mut tables = self.psi_sections.remove(&key).expect("Never fails");
+        tables.sort_by_key(|t| t.syntax.as_ref().expect("Never fails").section_number);
+        Ok(Some(Psi { tables }))
+    }
+
+    fn update_program(&mut self, pmt_pid: Pid, pmt: &Pmt) {
+        let es_pids = pmt
+            .es_info
+            .iter()
+            .map(|es| es.elementary_pid)
+            .collect::<Vec<_>>();
+        let old = self.programs.insert(pmt_pid, (pmt.version_number, es_pids));
+        if let Some((old_version, old_pids)) = old {
+            if old_version != pmt.version_number {
+                self.program_layout_changed = true;
+                for pid in old_pids {
+                    let in_use = self.programs.values().any(|(_, pids)| pids.contains(&pid));
+                    if !in_use {
+                        self.remove_pid(pid);
+                    }
+                }
+            }
+        }
+        for es in &pmt.es_info {
+            let pid = es.elementary_pid;
+            // The PIDs registered for sections (e.g., private sections listed in the PMT) are kept
+            if matches!(
+                self.pid_kinds.get(pid),
+                Some(PidKind::Psi | PidKind::Section)
+            ) {
+                continue;
+            }
+            let kind = if es.stream_type.is_dsm_cc_sections() {
+                PidKind::Section
+            } else {
+                PidKind::Pes
+            };
+            self.insert_pid(pid, kind);
+        }
+    }
+
+    fn pid_table(pids: &BTreeMap<Pid, PidKind>) -> PidTable<PidKind> {
+        let mut table = PidTable::new();
+        for (&pid, &kind) in pids {
+            table.insert(pid, kind);
+        }
+        table
+    }
+
+    /// Registers `pid` to both the PID table (for lookups) and the PID map (for `pids`).
+    ///
+    /// The PID map is only updated if the registration changes,
+    /// since the same PIDs are registered again by every PAT and PMT.
+    fn insert_pid(&mut self, pid: Pid, kind: PidKind) {
+        if self.pid_kinds.insert(pid, kind) != Some(kind) {
+            self.pids.insert(pid, kind);
+        }
+    }
+
+    fn remove_pid(&mut self, pid: Pid) {
+        if self.pid_kinds.remove(pid).is_some() {
+            self.pids.remove(&pid);
+        }
+    }
+
+    /// Returns the payload of a packet starting a section as `TsPayload::Section`.
+    fn read_section(header: &TsHeader, bytes: Bytes) -> Result<TsPayload> {
+        if header.payload_unit_start_indicator {
+            let section = track!(Section::read_from(&bytes[..]))?;
+            Ok(TsPayload::Section(section))
+        } else {
+            Ok(TsPayload::Raw(bytes))
+        }
+    }
+
+    fn read_payload(&mut self, header: &TsHeader, bytes: Bytes) -> Result<TsPayload> {
+        let payload = match header.pid.as_u16() {
+            Pid::PAT => {
+                let Some(section) = self.assemble_psi(header, &bytes, false) else {
+                    return Ok(TsPayload::Raw(bytes));
+                };
+                let Some(psi) = track!(self.read_psi(header.pid, &section))? else {
+                    return Ok(TsPayload::Raw(bytes));
+                };
+                let mut pat = track!(Pat::from_psi(psi, self.lenient_reserved_bits))?;
+                if self.keep_raw_tables {
+                    pat.raw = Some(bytes.as_ref().to_vec());
+                }
+                if pat.is_current {
                     for pa in &pat.table {
-                        self.pids.insert(pa.program_map_pid, PidKind::Pmt);
+                        let kind = if pa.program_num == 0 {
+                            PidKind::Nit
+                        } else {
+                            PidKind::Pmt
+                        };
+                        self.insert_pid(pa.program_map_pid, kind);
                     }
-                    TsPayload::Pat(pat)
                 }
-                Pid::NULL => {
-                    let null = track!(Null::read_from(&mut reader))?;
-                    TsPayload::Null(null)
+                TsPayload::Pat(pat)
+            }
+            _ if self.pid_kinds.get(header.pid) == Some(&PidKind::Psi) => {
+                let Some(section) = self.assemble_psi(header, &bytes, true) else {
+                    return Ok(TsPayload::Raw(bytes));
+                };
+                let psi = track!(self.read_psi_sections(&section))?;
+                TsPayload::Psi(psi)
+            }
+            Pid::PSIP if self.pid_kinds.get(header.pid) != Some(&PidKind::Section) => {
+                let Some(section) = self.assemble_psi(header, &bytes, false) else {
+                    return Ok(TsPayload::Raw(bytes));
+                };
+                let Some(psi) = track!(self.read_psi(header.pid, &section))? else {
+                    return Ok(TsPayload::Raw(bytes));
+                };
+                match psi.tables[0].header.table_id {
+                    Mgt::TABLE_ID => {
+                        TsPayload::Mgt(track!(Mgt::from_psi(psi, self.lenient_reserved_bits))?)
+                    }
+                    Vct::TERRESTRIAL_TABLE_ID | Vct::CABLE_TABLE_ID => {
+                        TsPayload::Vct(track!(Vct::from_psi(psi, self.lenient_reserved_bits))?)
+                    }
+                    _ => TsPayload::Psi(psi),
                 }
-                0x01..=0x1F | 0x1FFB => {
+            }
+            Pid::TSDT => {
+                let Some(section) = self.assemble_psi(header, &bytes, false) else {
+                    return Ok(TsPayload::Raw(bytes));
+                };
+                let Some(psi) = track!(self.read_psi(header.pid, &section))? else {
+                    return Ok(TsPayload::Raw(bytes));
+                };
+                TsPayload::Tsdt(track!(Tsdt::from_psi(psi))?)
+            }
+            Pid::CAT | Pid::NIT..=Pid::TDT => {
+                // Tables that are not modelled by this crate
+                track!(Self::read_section(header, bytes))?
+            }
+            0x01..=0x1F => {
+                if self.pid_kinds.get(header.pid) == Some(&PidKind::Section) {
+                    track!(Self::read_section(header, bytes))?
+                } else {
                     // Unknown (unsupported) packets
-                    let bytes = track!(Bytes::read_from(&mut reader))?;
                     TsPayload::Raw(bytes)
                 }
-                _ => {
-                    let kind = track_assert_some!(
-                        self.pids.get(&header.pid).cloned(),
+            }
+            _ => {
+                let Some(kind) = self.pid_kinds.get(header.pid).copied() else {
+                    track_assert!(
+                        !self.strict_pids,
                         ErrorKind::InvalidInput,
                         "Unknown PID: header={:?}",
                         header
                     );
-                    match kind {
-                        PidKind::Pmt => {
-                            let pmt = track!(Pmt::read_from(&mut reader))?;
-                            for es in &pmt.es_info {
-                                self.pids.insert(es.elementary_pid, PidKind::Pes);
-                            }
-                            TsPayload::Pmt(pmt)
+                    return Ok(TsPayload::Raw(bytes));
+                };
+                match kind {
+                    PidKind::Nit | PidKind::Section => {
+                        // Network information tables are not modelled by this crate
+                        track!(Self::read_section(header, bytes))?
+                    }
+                    PidKind::Pmt => {
+                        let Some(section) = self.assemble_psi(header, &bytes, false) else {
+                            return Ok(TsPayload::Raw(bytes));
+                        };
+                        let Some(psi) = track!(self.read_psi(header.pid, &section))? else {
+                            return Ok(TsPayload::Raw(bytes));
+                        };
+                        let mut pmt = track!(Pmt::from_psi(psi, self.lenient_reserved_bits))?;
+                        if self.keep_raw_tables {
+                            pmt.raw = Some(bytes.as_ref().to_vec());
                         }
-                        PidKind::Pes => {
-                            if payload_unit_start_indicator {
-                                let pes = track!(Pes::read_from(&mut reader))?;
-                                TsPayload::Pes(pes)
-                            } else {
-                                let bytes = track!(Bytes::read_from(&mut reader))?;
-                                TsPayload::Raw(bytes)
-                            }
+                        if pmt.is_current {
+                            self.update_program(header.pid, &pmt);
+                        }
+                        TsPayload::Pmt(pmt)
+                    }
+                    PidKind::Psi => unreachable!(),
+                    PidKind::Pes => {
+                        if header.payload_unit_start_indicator && !Pes::is_scrambled(&bytes) {
+                            let pes = track!(Pes::read_from(&bytes[..], self.lenient_timestamps))?;
+                            TsPayload::Pes(pes)
+                        } else {
+                            TsPayload::Raw(bytes)
                         }
                     }
                 }
-            };
-            Some(payload)
-        } else {
-            None
+            }
         };
+        Ok(payload)
+    }
+}
+impl<R: fmt::Debug> fmt::Debug for TsPacketReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TsPacketReader")
+            .field("stream", &self.stream)
+            .field("buffer", &self.buffer)
+            .field("packet_format", &self.packet_format)
+            .field("resync_confidence", &self.resync_confidence)
+            .field("skipped_bytes", &self.skipped_bytes)
+            .field("pids", &self.pids)
+            .field("programs", &self.programs)
+            .field("program_layout_changed", &self.program_layout_changed)
+            .field("descrambler", &self.descrambler.as_ref().map(|_| "_"))
+            .field("continuity_check", &self.continuity_check)
+            .field("continuity_checker", &self.continuity_checker)
+            .field("continuity_error_count", &self.continuity_error_count)
+            .field("transport_error_policy", &self.transport_error_policy)
+            .field("strict_pids", &self.strict_pids)
+            .field("lenient_timestamps", &self.lenient_timestamps)
+            .field("lenient_reserved_bits", &self.lenient_reserved_bits)
+            .field("keep_raw_tables", &self.keep_raw_tables)
+            .field("crc_check", &self.crc_check)
+            .field("crc_error_count", &self.crc_error_count)
+            .field("psi_buffers", &self.psi_buffers)
+            .field("psi_sections", &self.psi_sections)
+            .finish()
+    }
+}
+impl<R: Read> ReadTsPacket for TsPacketReader<R> {
+    fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+        self.program_layout_changed = false;
+        loop {
+            let Some((arrival_timestamp, packet_bytes)) = track!(self.read_packet_bytes())? else {
+                return Ok(None);
+            };
+            if let Some(packet) = track!(self.decode_packet(arrival_timestamp, &packet_bytes))? {
+                return Ok(Some(packet));
+            }
+        }
+    }
 
-        track_assert_eq!(reader.limit(), 0, ErrorKind::InvalidInput);
-        Ok(Some(TsPacket {
-            header,
-            adaptation_field,
-            payload,
-        }))
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        (self.packet_index > 0).then_some(self.last_packet_location.0)
     }
 }
 
-#[derive(Debug, Clone)]
-enum PidKind {
+impl<R: Read> IntoIterator for TsPacketReader<R> {
+    type Item = Result<TsPacket>;
+    type IntoIter = TsPackets<Self>;
+    fn into_iter(self) -> Self::IntoIter {
+        TsPackets::new(self)
+    }
+}
+
+/// Kind of the payloads carried by a PID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PidKind {
+    /// Network information table.
+    Nit,
+
+    /// Program map table.
     Pmt,
+
+    /// Packetized elementary stream.
     Pes,
+
+    /// Section stream whose payloads are returned as `TsPayload::Section` without being parsed.
+    ///
+    /// The elementary streams of the DSM-CC stream types (see `StreamType::is_dsm_cc_sections`)
+    /// are registered as this kind, and their sections can be reassembled by `SectionAssembler`.
+    ///
+    /// The payloads of the packets that do not start a section are returned as `TsPayload::Raw`.
+    Section,
+
+    /// Other PSI sections (e.g., application-private tables).
+    ///
+    /// The payloads are returned as `TsPayload::Psi` once the sections in them are completely received.
+    /// Unlike PAT and PMT, the sections of a multi-section table are returned as they arrive.
+    Psi,
 }
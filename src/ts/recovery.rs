@@ -0,0 +1,94 @@
+use crate::io::Read;
+use crate::ts::{Pid, ReadTsPacket, TsPacket, TsPacketReader};
+use crate::{Error, ErrorKind, Result};
+use alloc::vec::Vec;
+
+/// Region of a stream skipped by `RecoveringTsPacketReader`.
+#[derive(Debug, Clone)]
+pub struct SkippedRegion {
+    /// Byte offset of the packet that could not be decoded (see `TsPacketReader::byte_offset`).
+    pub byte_offset: u64,
+
+    /// Number of the skipped bytes, including the ones skipped by the resynchronization.
+    pub size: u64,
+
+    /// PID of the packet that could not be decoded.
+    ///
+    /// This is `None` if the packet does not have a valid header.
+    pub pid: Option<Pid>,
+
+    /// Error that occurred while decoding the packet.
+    pub error: Error,
+}
+
+/// TS packet reader that skips undecodable packets instead of failing.
+///
+/// If the underlaying reader fails with an `ErrorKind::InvalidInput` error
+/// (e.g., a lost sync byte, or a complete packet whose fields are malformed),
+/// the packet is skipped, the reader is resynchronized to the next sync byte,
+/// and the reading continues.
+/// The skipped regions can be retrieved via `skipped_regions`.
+///
+/// The other errors (e.g., I/O errors) are returned as is.
+#[derive(Debug)]
+pub struct RecoveringTsPacketReader<R> {
+    inner: TsPacketReader<R>,
+    skipped_regions: Vec<SkippedRegion>,
+}
+impl<R: Read> RecoveringTsPacketReader<R> {
+    /// Makes a new `RecoveringTsPacketReader` instance.
+    pub fn new(inner: TsPacketReader<R>) -> Self {
+        RecoveringTsPacketReader {
+            inner,
+            skipped_regions: Vec::new(),
+        }
+    }
+
+    /// Returns the regions skipped so far.
+    pub fn skipped_regions(&self) -> &[SkippedRegion] {
+        &self.skipped_regions
+    }
+
+    /// Returns the total number of bytes skipped so far.
+    pub fn skipped_bytes(&self) -> u64 {
+        self.skipped_regions.iter().map(|r| r.size).sum()
+    }
+
+    /// Returns a reference to the underlaying TS packet reader.
+    pub fn ts_packet_reader(&self) -> &TsPacketReader<R> {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlaying TS packet reader.
+    pub fn ts_packet_reader_mut(&mut self) -> &mut TsPacketReader<R> {
+        &mut self.inner
+    }
+
+    /// Takes ownership of `self`, and returns the underlaying TS packet reader.
+    pub fn into_inner(self) -> TsPacketReader<R> {
+        self.inner
+    }
+}
+impl<R: Read> ReadTsPacket for RecoveringTsPacketReader<R> {
+    fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+        loop {
+            match self.inner.read_ts_packet() {
+                Err(e) if *e.kind() == ErrorKind::InvalidInput => {
+                    let (byte_offset, pid) = self.inner.last_packet_location();
+                    track!(self.inner.recover())?;
+                    self.skipped_regions.push(SkippedRegion {
+                        byte_offset,
+                        size: self.inner.byte_offset() - byte_offset,
+                        pid,
+                        error: e,
+                    });
+                }
+                result => return track!(result),
+            }
+        }
+    }
+
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        self.inner.last_packet_byte_offset()
+    }
+}
@@ -0,0 +1,180 @@
+use crate::pes::PesHeader;
+use crate::time::{ClockReference, Timestamp};
+use crate::ts::{Pid, TsPacket, TsPayload};
+use alloc::collections::BTreeMap;
+
+/// Transform that shifts the PTS, DTS and ESCR in PES headers by a signed offset.
+///
+/// The offset is in 90 kHz ticks (the ESCR is shifted by the offset multiplied by 300),
+/// and the shifted values wrap around at 33 bits.
+/// The offset can be set globally and overridden per PID.
+///
+/// Only the timestamp fields in the headers are modified,
+/// so the sizes of the headers and the payload data are left intact.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::ts::{Pid, TimestampShifter};
+///
+/// // Delays the subtitles on PID 0x102 by 200 ms
+/// let mut shifter = TimestampShifter::new(0);
+/// shifter.set_pid_offset(Pid::new(0x102).unwrap(), 18_000);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimestampShifter {
+    offset: i64,
+    pid_offsets: BTreeMap<Pid, i64>,
+}
+impl TimestampShifter {
+    /// Makes a new `TimestampShifter` instance that shifts the timestamps of every PID by `offset`.
+    pub fn new(offset: i64) -> Self {
+        TimestampShifter {
+            offset,
+            pid_offsets: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the offset applied to the packets of `pid` instead of the global one.
+    pub fn set_pid_offset(&mut self, pid: Pid, offset: i64) {
+        self.pid_offsets.insert(pid, offset);
+    }
+
+    /// Removes the offset set by `set_pid_offset`.
+    pub fn remove_pid_offset(&mut self, pid: Pid) {
+        self.pid_offsets.remove(&pid);
+    }
+
+    /// Returns the offset applied to the packets of `pid`.
+    pub fn offset(&self, pid: Pid) -> i64 {
+        self.pid_offsets.get(&pid).copied().unwrap_or(self.offset)
+    }
+
+    /// Shifts the timestamps in the PES header of `packet` (if any).
+    ///
+    /// Packets that do not start a PES packet are left untouched.
+    pub fn shift_packet(&self, packet: &mut TsPacket) {
+        if let Some(TsPayload::Pes(pes)) = &mut packet.payload {
+            self.shift_header(packet.header.pid, &mut pes.header);
+        }
+    }
+
+    /// Shifts the timestamps in `header` of a PES packet carried by `pid`.
+    ///
+    /// If the DTS would end up greater than the PTS, it is clamped to the PTS.
+    pub fn shift_header(&self, pid: Pid, header: &mut PesHeader) {
+        let offset = self.offset(pid);
+        if offset == 0 {
+            return;
+        }
+
+        let ticks = offset.rem_euclid(Timestamp::MODULUS as i64) as u64;
+        header.pts = header.pts.map(|pts| pts.wrapping_add(ticks));
+        header.dts = header.dts.map(|dts| dts.wrapping_add(ticks));
+        if let (Some(pts), Some(dts)) = (header.pts, header.dts) {
+            if pts.delta(dts) < 0 {
+                header.dts = Some(pts);
+            }
+        }
+
+        let ticks = (i128::from(offset) * 300).rem_euclid(i128::from(ClockReference::MODULUS));
+        header.escr = header.escr.map(|escr| escr.wrapping_add(ticks as u64));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::StreamId;
+    use crate::ts::payload::{Bytes, Pes};
+    use crate::ts::{
+        ContinuityCounter, TransportScramblingControl, TsHeader, TsPacketWriter, WriteTsPacket,
+    };
+
+    fn header(pts: u64, dts: Option<u64>) -> PesHeader {
+        PesHeader {
+            stream_id: StreamId::new(0xE0),
+            priority: false,
+            data_alignment_indicator: true,
+            copyright: false,
+            original_or_copy: false,
+            pts: Some(Timestamp::new(pts).unwrap()),
+            dts: dts.map(|n| Timestamp::new(n).unwrap()),
+            escr: Some(ClockReference::new(pts * 300).unwrap()),
+            es_rate: None,
+            trick_mode: None,
+            additional_copy_info: None,
+            previous_crc: None,
+            extension: None,
+        }
+    }
+
+    #[test]
+    fn shift_header() {
+        let pid = Pid::new(256).unwrap();
+        let mut shifter = TimestampShifter::new(-9000);
+
+        let mut h = header(3000, Some(0));
+        shifter.shift_header(pid, &mut h);
+        assert_eq!(h.pts.unwrap().as_u64(), Timestamp::MODULUS - 6000);
+        assert_eq!(h.dts.unwrap().as_u64(), Timestamp::MODULUS - 9000);
+        assert_eq!(
+            h.escr.unwrap().as_u64(),
+            ClockReference::MODULUS - 6000 * 300
+        );
+
+        // Per-PID offset
+        shifter.set_pid_offset(pid, 18_000);
+        let mut h = header(Timestamp::MAX, None);
+        shifter.shift_header(pid, &mut h);
+        assert_eq!(h.pts.unwrap().as_u64(), 17_999);
+        assert_eq!(h.dts, None);
+
+        // DTS greater than PTS is clamped
+        let mut h = header(1000, Some(2000));
+        shifter.shift_header(pid, &mut h);
+        assert_eq!(h.dts, h.pts);
+    }
+
+    #[test]
+    fn shift_packet() {
+        let data = [0, 0, 1, 9, 0xF0, 0xAB];
+        let mut packet = TsPacket {
+            header: TsHeader {
+                transport_error_indicator: false,
+                payload_unit_start_indicator: true,
+                transport_priority: false,
+                pid: Pid::new(256).unwrap(),
+                transport_scrambling_control: TransportScramblingControl::NotScrambled,
+                continuity_counter: ContinuityCounter::new(),
+            },
+            adaptation_field: None,
+            payload: Some(TsPayload::Pes(Pes {
+                header: header(90_000, Some(87_000)),
+                pes_packet_len: 0,
+                data: Bytes::new(&data).unwrap(),
+            })),
+            arrival_timestamp: None,
+        };
+        let mut before = TsPacketWriter::new(Vec::new());
+        before.write_ts_packet(&packet).unwrap();
+        TimestampShifter::new(-87_000).shift_packet(&mut packet);
+        let mut after = TsPacketWriter::new(Vec::new());
+        after.write_ts_packet(&packet).unwrap();
+
+        // Only the PTS, DTS and ESCR fields (16 bytes preceding the PES packet data) are changed
+        let (before, after) = (before.into_stream(), after.into_stream());
+        let fields = before.len() - data.len() - 16..before.len() - data.len();
+        assert_eq!(before.len(), after.len());
+        assert_ne!(before[fields.clone()], after[fields.clone()]);
+        assert_eq!(before[..fields.start], after[..fields.start]);
+        assert_eq!(before[fields.end..], after[fields.end..]);
+
+        let Some(TsPayload::Pes(pes)) = &packet.payload else {
+            panic!("Not a PES packet");
+        };
+        assert_eq!(pes.header.pts.unwrap().as_u64(), 3000);
+        assert_eq!(pes.header.dts.unwrap().as_u64(), 0);
+        assert_eq!(pes.data.as_ref(), data);
+    }
+}
@@ -1,20 +1,177 @@
+use crate::io::{Read, ReadBytesExt, Write, WriteBytesExt};
 use crate::ts::payload::Bytes;
-use crate::Result;
-use byteorder::WriteBytesExt;
-use std::io::Write;
+use crate::ts::{PidTable, TsPacket, TsPayload};
+use crate::{ErrorKind, Result};
+use alloc::vec::Vec;
 
 /// Payload for Section Stream packets.
+///
+/// This is the payload of a packet that starts a section (i.e., `payload_unit_start_indicator` is `true`).
+/// The first `pointer_field` bytes of `data` are the tail of the section started in the previous packets,
+/// and the new sections start at that offset.
+/// The payloads of the continuation packets are returned as `TsPayload::Raw`.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Section {
     pub pointer_field: u8,
     pub data: Bytes,
 }
 
 impl Section {
+    /// Makes a new `Section` instance.
+    ///
+    /// # Errors
+    ///
+    /// If `pointer_field` exceeds the length of `data` or `data` does not fit in a packet,
+    /// it will return an `ErrorKind::InvalidInput` error.
+    pub fn new(pointer_field: u8, data: &[u8]) -> Result<Self> {
+        let data = track!(Bytes::new(data))?;
+        track!(Self::check_pointer_field(pointer_field, &data))?;
+        Ok(Section {
+            pointer_field,
+            data,
+        })
+    }
+
+    /// Returns the tail of the section started in the previous packets.
+    pub fn previous_section_bytes(&self) -> &[u8] {
+        &self.data[..usize::from(self.pointer_field).min(self.data.len())]
+    }
+
+    /// Returns the bytes of the sections that start in this packet (possibly followed by stuffing bytes).
+    pub fn section_bytes(&self) -> &[u8] {
+        &self.data[usize::from(self.pointer_field).min(self.data.len())..]
+    }
+
+    pub(super) fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+        let pointer_field = track_io!(reader.read_u8())?;
+        let data = track!(Bytes::read_from(reader))?;
+        track!(Self::check_pointer_field(pointer_field, &data))?;
+        Ok(Section {
+            pointer_field,
+            data,
+        })
+    }
+
     pub(super) fn write_to<W: Write>(&self, mut writer: W) -> Result<()> {
+        track!(Self::check_pointer_field(self.pointer_field, &self.data))?;
         track_io!(writer.write_u8(self.pointer_field))?;
         track!(self.data.write_to(writer))?;
         Ok(())
     }
+
+    fn check_pointer_field(pointer_field: u8, data: &Bytes) -> Result<()> {
+        track_assert!(
+            usize::from(pointer_field) <= data.len(),
+            ErrorKind::InvalidInput,
+            "Too large pointer field: pointer_field={}, data_len={}",
+            pointer_field,
+            data.len()
+        );
+        Ok(())
+    }
+}
+
+/// Assembler of the sections carried by section streams (i.e., PIDs registered as `PidKind::Section`).
+///
+/// The sections spanning multiple packets are reassembled from `TsPayload::Section` and `TsPayload::Raw` payloads.
+/// The continuation packets received before the first section start on a PID are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::ts::payload::Section;
+/// use mpeg2ts::ts::{Pid, SectionAssembler, TsPacket, TsPayload};
+///
+/// let mut section = vec![0x3E, 0xB0, 200];
+/// section.resize(3 + 200, 0);
+///
+/// let pid = Pid::new(0x200).unwrap();
+/// let first = TsPacket::builder(pid)
+///     .payload(TsPayload::Section(Section::new(0, &section[..183]).unwrap()))
+///     .build()
+///     .unwrap();
+/// let second = TsPacket::builder(pid)
+///     .payload_raw(&section[183..])
+///     .build()
+///     .unwrap();
+///
+/// let mut assembler = SectionAssembler::new();
+/// assert!(assembler.push(&first).is_empty());
+/// assert_eq!(assembler.push(&second), [section]);
+/// ```
+#[derive(Debug, Default)]
+pub struct SectionAssembler {
+    buffers: PidTable<Vec<u8>>,
+}
+impl SectionAssembler {
+    /// Makes a new `SectionAssembler` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds the payload of `packet`, and returns the sections completed by it.
+    ///
+    /// The packets whose payloads are neither `TsPayload::Section` nor `TsPayload::Raw` are ignored.
+    pub fn push(&mut self, packet: &TsPacket) -> Vec<Vec<u8>> {
+        let pid = packet.header.pid;
+        let mut sections = Vec::new();
+        match &packet.payload {
+            Some(TsPayload::Section(section)) => {
+                if let Some(buf) = self.buffers.get_mut(pid).filter(|buf| !buf.is_empty()) {
+                    buf.extend_from_slice(section.previous_section_bytes());
+                    Self::take_sections(buf, &mut sections);
+                }
+                let mut buf = section.section_bytes().to_vec();
+                Self::take_sections(&mut buf, &mut sections);
+                self.buffers.insert(pid, buf);
+            }
+            Some(TsPayload::Raw(bytes)) => {
+                if let Some(buf) = self.buffers.get_mut(pid).filter(|buf| !buf.is_empty()) {
+                    buf.extend_from_slice(bytes);
+                    Self::take_sections(buf, &mut sections);
+                }
+            }
+            _ => {}
+        }
+        sections
+    }
+
+    /// Discards the incomplete sections.
+    pub fn clear(&mut self) {
+        self.buffers.clear();
+    }
+
+    fn take_sections(buf: &mut Vec<u8>, sections: &mut Vec<Vec<u8>>) {
+        loop {
+            if buf.first().map_or(true, |&b| b == 0xFF) {
+                // Stuffing
+                buf.clear();
+                return;
+            }
+            if buf.len() < 3 {
+                return;
+            }
+            let len = 3 + ((usize::from(buf[1]) << 8 | usize::from(buf[2])) & 0x0FFF);
+            if buf.len() < len {
+                return;
+            }
+            sections.push(buf.drain(..len).collect());
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Section {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let len = u
+            .arbitrary_len::<u8>()?
+            .min(Bytes::MAX_SIZE - 1 /* pointer field */);
+        let data = Bytes::new(u.bytes(len)?).expect("Never fails");
+        Ok(Section {
+            pointer_field: u.int_in_range(0..=len as u8)?,
+            data,
+        })
+    }
 }
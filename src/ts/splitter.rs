@@ -0,0 +1,229 @@
+use crate::ts::{Pid, ReadTsPacket, TsPacket, TsPayload};
+use crate::{ErrorKind, Result};
+use core::fmt;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+
+type Message = Result<(TsPacket, Option<u64>)>;
+
+/// Splitter that dispatches the TS packets of a single stream to multiple shards by PID.
+///
+/// The packets are read (and decoded) only once by the splitter,
+/// and each shard receives its packets through a `ShardTsPacketReader`.
+/// Since the shard readers implement `ReadTsPacket`, they can be wrapped in `PesPacketReader`s
+/// running in their own threads, so that PES packets are assembled in parallel.
+///
+/// - The packets of a PID are always sent to the same shard, so their order is preserved.
+/// - PAT and PMT packets are sent to all shards, so that every shard knows the stream types.
+/// - The PCRs are sent to all shards (the other shards receive copies of the packets without payloads).
+/// - If the underlaying reader fails, the error is sent to all shards.
+///
+/// When the splitter is dropped, the shard readers reach the end of the stream after the queued packets.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mpeg2ts::pes::{PesPacketReader, ReadPesPacket};
+/// use mpeg2ts::ts::{TsPacketReader, TsPacketSplitter};
+///
+/// let file = std::fs::File::open("foo.ts").unwrap();
+/// let reader = TsPacketReader::new(std::io::BufReader::new(file));
+/// let (splitter, shards) =
+///     TsPacketSplitter::new(reader, 4, |pid| usize::from(pid.as_u16())).unwrap();
+///
+/// let workers = shards
+///     .into_iter()
+///     .map(|shard| {
+///         std::thread::spawn(move || {
+///             let mut reader = PesPacketReader::new(shard);
+///             while let Some(packet) = reader.read_pes_packet().unwrap() {
+///                 println!("{:?}: {} bytes", packet.pid, packet.data.len());
+///             }
+///         })
+///     })
+///     .collect::<Vec<_>>();
+///
+/// splitter.run().unwrap();
+/// for worker in workers {
+///     worker.join().unwrap();
+/// }
+/// ```
+pub struct TsPacketSplitter<R> {
+    ts_packet_reader: R,
+    shard: Box<dyn Fn(Pid) -> usize + Send>,
+    senders: Vec<Option<SyncSender<Message>>>,
+}
+impl<R: ReadTsPacket> TsPacketSplitter<R> {
+    /// Default value of the number of packets that can be queued for each shard.
+    pub const DEFAULT_CHANNEL_CAPACITY: usize = 1024;
+
+    /// Makes a new `TsPacketSplitter` instance and its shard readers.
+    ///
+    /// `shard` returns the index of the shard that handles a PID
+    /// (the index is taken modulo the number of shards).
+    ///
+    /// It will return an `ErrorKind::InvalidInput` error if `shards` is zero.
+    pub fn new<F>(
+        ts_packet_reader: R,
+        shards: usize,
+        shard: F,
+    ) -> Result<(Self, Vec<ShardTsPacketReader>)>
+    where
+        F: Fn(Pid) -> usize + Send + 'static,
+    {
+        track!(Self::with_channel_capacity(
+            ts_packet_reader,
+            shards,
+            Self::DEFAULT_CHANNEL_CAPACITY,
+            shard
+        ))
+    }
+
+    /// Makes a new `TsPacketSplitter` instance with the given channel capacity.
+    ///
+    /// The splitter blocks if `channel_capacity` packets are queued for a shard.
+    pub fn with_channel_capacity<F>(
+        ts_packet_reader: R,
+        shards: usize,
+        channel_capacity: usize,
+        shard: F,
+    ) -> Result<(Self, Vec<ShardTsPacketReader>)>
+    where
+        F: Fn(Pid) -> usize + Send + 'static,
+    {
+        track_assert_ne!(shards, 0, ErrorKind::InvalidInput);
+
+        let mut senders = Vec::with_capacity(shards);
+        let mut readers = Vec::with_capacity(shards);
+        for index in 0..shards {
+            let (tx, rx) = mpsc::sync_channel(channel_capacity);
+            senders.push(Some(tx));
+            readers.push(ShardTsPacketReader {
+                index,
+                receiver: rx,
+                last_packet_byte_offset: None,
+            });
+        }
+        let splitter = TsPacketSplitter {
+            ts_packet_reader,
+            shard: Box::new(shard),
+            senders,
+        };
+        Ok((splitter, readers))
+    }
+
+    /// Reads a TS packet and dispatches it to the shards.
+    ///
+    /// It will return `Ok(false)` if the end of the stream is reached.
+    pub fn split_packet(&mut self) -> Result<bool> {
+        let packet = match track!(self.ts_packet_reader.read_ts_packet()) {
+            Ok(Some(packet)) => packet,
+            Ok(None) => return Ok(false),
+            Err(e) => {
+                for i in 0..self.senders.len() {
+                    self.send(i, Err(e.clone()));
+                }
+                return Err(e);
+            }
+        };
+        let byte_offset = self.ts_packet_reader.last_packet_byte_offset();
+
+        let target = (self.shard)(packet.header.pid) % self.senders.len();
+        let broadcast = matches!(
+            packet.payload,
+            Some(TsPayload::Pat(_)) | Some(TsPayload::Pmt(_))
+        );
+        let has_pcr = packet
+            .adaptation_field
+            .as_ref()
+            .is_some_and(|a| a.pcr.is_some());
+        if broadcast || has_pcr {
+            for i in (0..self.senders.len()).filter(|&i| i != target) {
+                let copy = if broadcast {
+                    packet.clone()
+                } else {
+                    TsPacket {
+                        header: packet.header.clone(),
+                        adaptation_field: packet.adaptation_field.clone(),
+                        payload: None,
+                        arrival_timestamp: packet.arrival_timestamp,
+                    }
+                };
+                self.send(i, Ok((copy, byte_offset)));
+            }
+        }
+        self.send(target, Ok((packet, byte_offset)));
+        Ok(true)
+    }
+
+    /// Dispatches all the TS packets of the stream to the shards.
+    ///
+    /// The shard readers reach the end of the stream when this method returns.
+    pub fn run(mut self) -> Result<()> {
+        while track!(self.split_packet())? {}
+        Ok(())
+    }
+
+    fn send(&mut self, shard: usize, message: Message) {
+        if let Some(tx) = &self.senders[shard] {
+            if tx.send(message).is_err() {
+                // The shard reader has been dropped
+                self.senders[shard] = None;
+            }
+        }
+    }
+}
+impl<R> TsPacketSplitter<R> {
+    /// Returns the number of the shards.
+    pub fn shards(&self) -> usize {
+        self.senders.len()
+    }
+
+    /// Returns a reference to the underlaying TS packet reader.
+    pub fn ts_packet_reader(&self) -> &R {
+        &self.ts_packet_reader
+    }
+
+    /// Returns a mutable reference to the underlaying TS packet reader.
+    pub fn ts_packet_reader_mut(&mut self) -> &mut R {
+        &mut self.ts_packet_reader
+    }
+}
+impl<R: fmt::Debug> fmt::Debug for TsPacketSplitter<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TsPacketSplitter")
+            .field("ts_packet_reader", &self.ts_packet_reader)
+            .field("shard", &"_")
+            .field("senders", &self.senders)
+            .finish()
+    }
+}
+
+/// TS packet reader that receives the packets of a shard from `TsPacketSplitter`.
+#[derive(Debug)]
+pub struct ShardTsPacketReader {
+    index: usize,
+    receiver: Receiver<Message>,
+    last_packet_byte_offset: Option<u64>,
+}
+impl ShardTsPacketReader {
+    /// Returns the index of the shard.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+impl ReadTsPacket for ShardTsPacketReader {
+    fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+        match self.receiver.recv() {
+            Ok(Ok((packet, byte_offset))) => {
+                self.last_packet_byte_offset = byte_offset;
+                Ok(Some(packet))
+            }
+            Ok(Err(e)) => Err(track!(e)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        self.last_packet_byte_offset
+    }
+}
@@ -0,0 +1,106 @@
+//! Fixtures shared by the tests of the `ts` module.
+use crate::es::{StreamId, StreamType};
+use crate::pes::PesHeader;
+use crate::time::Timestamp;
+use crate::ts::payload::{Bytes, Pat, Pes, Pmt};
+use crate::ts::{
+    AdaptationField, ContinuityCounter, EsInfo, Pid, ProgramAssociation, TsPacket, TsPacketWriter,
+    TsPayload, VersionNumber, WriteTsPacket,
+};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+pub fn pid(n: u16) -> Pid {
+    Pid::new(n).unwrap()
+}
+
+/// Makes a timestamp, wrapping `n` around at `Timestamp::MODULUS`.
+pub fn ts(n: u64) -> Timestamp {
+    Timestamp::new(n % Timestamp::MODULUS).unwrap()
+}
+
+/// Makes a PAT from `(program_num, program_map_pid)` pairs.
+pub fn pat(programs: &[(u16, u16)]) -> Pat {
+    Pat {
+        transport_stream_id: 1,
+        version_number: VersionNumber::new(),
+        is_current: true,
+        table: programs
+            .iter()
+            .map(|&(program_num, n)| ProgramAssociation {
+                program_num,
+                program_map_pid: pid(n),
+            })
+            .collect(),
+        raw: None,
+    }
+}
+
+/// Makes a PMT from `(elementary_pid, stream_type)` pairs.
+pub fn pmt(program_num: u16, pcr_pid: Option<u16>, streams: &[(u16, StreamType)]) -> Pmt {
+    Pmt {
+        program_num,
+        pcr_pid: pcr_pid.map(pid),
+        version_number: VersionNumber::new(),
+        is_current: true,
+        program_info: vec![],
+        es_info: streams
+            .iter()
+            .map(|&(n, stream_type)| EsInfo {
+                stream_type,
+                elementary_pid: pid(n),
+                descriptors: vec![],
+            })
+            .collect(),
+        raw: None,
+    }
+}
+
+pub fn pes(stream_id: u8, pts: Option<u64>, dts: Option<u64>, data: &[u8]) -> Pes {
+    let mut header = PesHeader::new(StreamId::new(stream_id));
+    header.pts = pts.map(ts);
+    header.dts = dts.map(ts);
+    Pes {
+        header,
+        pes_packet_len: 0,
+        data: Bytes::new(data).unwrap(),
+    }
+}
+
+/// Makes TS packets from `(pid, adaptation_field, payload)` tuples.
+///
+/// The continuity counters of each PID start from `first_counter`,
+/// and are incremented by the packets that have payloads.
+pub fn packets<I>(items: I, first_counter: u8) -> Vec<TsPacket>
+where
+    I: IntoIterator<Item = (u16, Option<AdaptationField>, Option<TsPayload>)>,
+{
+    let mut counters = BTreeMap::new();
+    items
+        .into_iter()
+        .map(|(n, adaptation_field, payload)| {
+            let counter = counters
+                .entry(n)
+                .or_insert_with(|| ContinuityCounter::from_u8(first_counter).unwrap());
+            let mut builder = TsPacket::builder(pid(n)).continuity(*counter);
+            if let Some(adaptation_field) = adaptation_field {
+                builder = builder.adaptation(adaptation_field);
+            }
+            if let Some(payload) = payload {
+                builder = builder.payload(payload);
+                counter.increment();
+            }
+            track_try_unwrap!(builder.build())
+        })
+        .collect()
+}
+
+/// Writes the packets made by `packets` (with the counters starting from zero) into a stream.
+pub fn write_packets<I>(items: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = (u16, Option<AdaptationField>, Option<TsPayload>)>,
+{
+    let mut writer = TsPacketWriter::new(Vec::new());
+    track_try_unwrap!(writer.write_ts_packets(&packets(items, 0)));
+    writer.into_stream()
+}
@@ -0,0 +1,353 @@
+use crate::time::{ClockReference, Timestamp};
+use crate::ts::{Pid, PidTable, ReadTsPacket, TsPacket, TsPayload};
+use crate::Result;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// Kind of an issue detected by `TimestampChecker`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TimestampIssueKind {
+    /// The DTS (or the PTS if the DTS is absent) does not increase from the previous PES packet.
+    NonMonotonicDts,
+
+    /// The PTS precedes the DTS.
+    PtsBeforeDts,
+
+    /// The PTS minus the latest PCR of the program is outside the window
+    /// (see `TimestampChecker::set_pcr_offset_window`).
+    ///
+    /// A negative or small offset indicates a risk of buffer underflow,
+    /// and a large one a risk of buffer overflow.
+    PcrOffsetOutOfRange,
+
+    /// A PES packet of an audio or video stream has no PTS.
+    MissingPts,
+
+    /// The DTS (or the PTS if the DTS is absent) jumps forward by more than the threshold
+    /// (see `TimestampChecker::set_max_gap`), which indicates dropped frames.
+    Gap,
+}
+
+/// Issue detected by `TimestampChecker`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TimestampIssue {
+    /// Kind of the issue.
+    pub kind: TimestampIssueKind,
+
+    /// Index (zero origin) of the packet starting the offending PES packet among the observed packets.
+    pub packet_index: u64,
+
+    /// Byte offset of the packet starting the offending PES packet (if known).
+    pub byte_offset: Option<u64>,
+}
+
+/// Result of `TimestampChecker` for an elementary stream.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StreamTimestampReport {
+    /// Number of the checked PES packets.
+    pub pes_packets: u64,
+
+    /// Number of the issues of each kind.
+    pub counts: BTreeMap<TimestampIssueKind, u64>,
+
+    /// First issues in the order of detection (see `TimestampChecker::set_max_recorded_issues`).
+    pub issues: Vec<TimestampIssue>,
+}
+impl StreamTimestampReport {
+    /// Returns the number of the issues of the given kind.
+    pub fn count(&self, kind: TimestampIssueKind) -> u64 {
+        self.counts.get(&kind).copied().unwrap_or(0)
+    }
+
+    /// Returns `true` if no issues have been detected, otherwise `false`.
+    pub fn is_ok(&self) -> bool {
+        self.counts.is_empty()
+    }
+}
+
+/// Reads the whole of the stream, and returns the result of `TimestampChecker` for each PID carrying PES packets.
+pub fn check_timestamps<R: ReadTsPacket>(
+    reader: &mut R,
+) -> Result<BTreeMap<Pid, StreamTimestampReport>> {
+    let mut checker = TimestampChecker::new();
+    while let Some(packet) = track!(reader.read_ts_packet())? {
+        checker.observe(&packet, reader.last_packet_byte_offset());
+    }
+    Ok(checker.into_reports())
+}
+
+/// Checker of the PTS and DTS in the PES packets of each elementary stream.
+///
+/// The timestamps are compared with wrap-around taken into account
+/// (i.e., the shorter of the forward and backward distances is chosen).
+/// The PCR offsets are checked against the latest PCR on the PCR PID of the program,
+/// which is learned from the PMTs.
+#[derive(Debug)]
+pub struct TimestampChecker {
+    pcr_offset_window: (Duration, Duration),
+    max_gap: Duration,
+    max_recorded_issues: usize,
+    packet_index: u64,
+    pcr_pids: PidTable<Pid>,
+    pcrs: PidTable<ClockReference>,
+    last_dts: PidTable<Timestamp>,
+    reports: BTreeMap<Pid, StreamTimestampReport>,
+}
+impl TimestampChecker {
+    /// Default value of the PCR offset window.
+    pub const DEFAULT_PCR_OFFSET_WINDOW: (Duration, Duration) =
+        (Duration::ZERO, Duration::from_secs(1));
+
+    /// Default value of the gap threshold.
+    ///
+    /// This is the maximum interval of PTSs allowed by ISO/IEC 13818-1.
+    pub const DEFAULT_MAX_GAP: Duration = Duration::from_millis(700);
+
+    /// Default value of the number of the issues recorded for each PID.
+    pub const DEFAULT_MAX_RECORDED_ISSUES: usize = 10;
+
+    /// Makes a new `TimestampChecker` instance.
+    pub fn new() -> Self {
+        TimestampChecker {
+            pcr_offset_window: Self::DEFAULT_PCR_OFFSET_WINDOW,
+            max_gap: Self::DEFAULT_MAX_GAP,
+            max_recorded_issues: Self::DEFAULT_MAX_RECORDED_ISSUES,
+            packet_index: 0,
+            pcr_pids: PidTable::new(),
+            pcrs: PidTable::new(),
+            last_dts: PidTable::new(),
+            reports: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the allowed range of the PTS minus the PCR.
+    ///
+    /// The default value is `DEFAULT_PCR_OFFSET_WINDOW`.
+    pub fn set_pcr_offset_window(&mut self, min: Duration, max: Duration) {
+        self.pcr_offset_window = (min, max);
+    }
+
+    /// Sets the threshold of the forward jumps of the DTS reported as `TimestampIssueKind::Gap`.
+    ///
+    /// The default value is `DEFAULT_MAX_GAP`.
+    pub fn set_max_gap(&mut self, gap: Duration) {
+        self.max_gap = gap;
+    }
+
+    /// Sets the number of the issues recorded for each PID.
+    ///
+    /// The issues beyond this are only counted.
+    /// The default value is `DEFAULT_MAX_RECORDED_ISSUES`.
+    pub fn set_max_recorded_issues(&mut self, n: usize) {
+        self.max_recorded_issues = n;
+    }
+
+    /// Returns the results for each PID carrying PES packets.
+    pub fn reports(&self) -> &BTreeMap<Pid, StreamTimestampReport> {
+        &self.reports
+    }
+
+    /// Takes ownership of `self`, and returns the results for each PID carrying PES packets.
+    pub fn into_reports(self) -> BTreeMap<Pid, StreamTimestampReport> {
+        self.reports
+    }
+
+    /// Observes `packet` located at `byte_offset` (if known) in the stream.
+    pub fn observe(&mut self, packet: &TsPacket, byte_offset: Option<u64>) {
+        let packet_index = self.packet_index;
+        self.packet_index += 1;
+
+        let pid = packet.header.pid;
+        if let Some(pcr) = packet.adaptation_field.as_ref().and_then(|a| a.pcr) {
+            self.pcrs.insert(pid, pcr);
+        }
+        let pes = match &packet.payload {
+            Some(TsPayload::Pmt(pmt)) => {
+                for es in &pmt.es_info {
+                    if let Some(pcr_pid) = pmt.pcr_pid {
+                        self.pcr_pids.insert(es.elementary_pid, pcr_pid);
+                    } else {
+                        self.pcr_pids.remove(es.elementary_pid);
+                    }
+                }
+                return;
+            }
+            Some(TsPayload::Pes(pes)) => pes,
+            _ => return,
+        };
+
+        let header = &pes.header;
+        let mut issues = Vec::new();
+        let stream_id = header.stream_id;
+        if header.pts.is_none() && (stream_id.is_audio() || stream_id.is_video()) {
+            issues.push(TimestampIssueKind::MissingPts);
+        }
+        if let (Some(pts), Some(dts)) = (header.pts, header.dts) {
+            if pts.delta(dts) < 0 {
+                issues.push(TimestampIssueKind::PtsBeforeDts);
+            }
+        }
+        if let Some(pts) = header.pts {
+            let pcr = self
+                .pcr_pids
+                .get(pid)
+                .and_then(|&pcr_pid| self.pcrs.get(pcr_pid));
+            if let Some(&pcr) = pcr {
+                let offset = pts.delta(Timestamp::from(pcr));
+                let (min, max) = self.pcr_offset_window;
+                if offset < ticks(min) || offset > ticks(max) {
+                    issues.push(TimestampIssueKind::PcrOffsetOutOfRange);
+                }
+            }
+        }
+        if let Some(dts) = header.dts.or(header.pts) {
+            if let Some(last) = self.last_dts.insert(pid, dts) {
+                let delta = dts.delta(last);
+                if delta <= 0 {
+                    issues.push(TimestampIssueKind::NonMonotonicDts);
+                } else if delta > ticks(self.max_gap) {
+                    issues.push(TimestampIssueKind::Gap);
+                }
+            }
+        }
+
+        let report = self.reports.entry(pid).or_default();
+        report.pes_packets += 1;
+        for kind in issues {
+            *report.counts.entry(kind).or_default() += 1;
+            if report.issues.len() < self.max_recorded_issues {
+                report.issues.push(TimestampIssue {
+                    kind,
+                    packet_index,
+                    byte_offset,
+                });
+            }
+        }
+    }
+}
+impl Default for TimestampChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts `duration` into 90 kHz ticks.
+fn ticks(duration: Duration) -> i64 {
+    (duration.as_nanos() * 9 / 100_000) as i64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::StreamType;
+    use crate::ts::payload::Pmt;
+    use crate::ts::test_util::{self, pid};
+    use crate::ts::{AdaptationField, EsInfo, VersionNumber};
+
+    fn pes(n: u16, stream_id: u8, pts: Option<u64>, dts: Option<u64>) -> TsPacket {
+        TsPacket::builder(pid(n))
+            .payload_pes(test_util::pes(stream_id, pts, dts, &[0; 10]))
+            .build()
+            .unwrap()
+    }
+
+    fn pcr(n: u16, pcr: u64) -> TsPacket {
+        let pcr = ClockReference::new(pcr % Timestamp::MODULUS * 300).unwrap();
+        TsPacket::builder(pid(n))
+            .adaptation(AdaptationField::with_pcr(pcr))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn check() {
+        let pmt = TsPayload::Pmt(Pmt {
+            program_num: 1,
+            pcr_pid: Some(pid(256)),
+            version_number: VersionNumber::new(),
+            is_current: true,
+            program_info: vec![],
+            es_info: vec![
+                EsInfo {
+                    stream_type: StreamType::H264,
+                    elementary_pid: pid(256),
+                    descriptors: vec![],
+                },
+                EsInfo {
+                    stream_type: StreamType::AdtsAac,
+                    elementary_pid: pid(257),
+                    descriptors: vec![],
+                },
+            ],
+            raw: None,
+        });
+        let pmt = TsPacket::builder(pid(0x1000)).payload(pmt).build().unwrap();
+
+        // Wraps around at the third frame
+        let base = Timestamp::MODULUS - 2 * 3600;
+        let packets = vec![
+            pmt,
+            pcr(256, base - 9000),
+            pes(256, 0xE0, Some(base + 3600), Some(base)),
+            pes(257, 0xC0, Some(base), None),
+            pes(256, 0xE0, Some(base + 3600), Some(base + 3600)),
+            // PTS < DTS
+            pes(256, 0xE0, Some(base), Some(base + 7200)),
+            // Missing PTS
+            pes(257, 0xC0, None, None),
+            // Non-monotonic
+            pes(257, 0xC0, Some(base - 1800), None),
+            // Gap of 0.8 seconds
+            pes(257, 0xC0, Some(base + 72_000), None),
+            // Too far ahead of the PCR
+            pcr(256, base),
+            pes(256, 0xE0, Some(base + 10_800 + 90_000), Some(base + 10_800)),
+            // Behind the PCR, and a gap of one second
+            pcr(256, base + 100_000),
+            pes(256, 0xE0, Some(base + 99_000), Some(base + 99_000)),
+        ];
+        let mut checker = TimestampChecker::new();
+        for (i, packet) in packets.iter().enumerate() {
+            checker.observe(packet, Some(i as u64 * 188));
+        }
+        let reports = checker.into_reports();
+        assert_eq!(reports.len(), 2);
+
+        use TimestampIssueKind::*;
+        let video = &reports[&pid(256)];
+        assert_eq!(video.pes_packets, 5);
+        assert_eq!(video.count(PtsBeforeDts), 1);
+        assert_eq!(video.count(PcrOffsetOutOfRange), 2);
+        assert_eq!(video.count(Gap), 1);
+        assert_eq!(
+            video
+                .issues
+                .iter()
+                .map(|i| (i.kind, i.packet_index))
+                .collect::<Vec<_>>(),
+            [
+                (PtsBeforeDts, 5),
+                (PcrOffsetOutOfRange, 10),
+                (PcrOffsetOutOfRange, 12),
+                (Gap, 12),
+            ]
+        );
+
+        let audio = &reports[&pid(257)];
+        assert_eq!(audio.pes_packets, 4);
+        assert!(!audio.is_ok());
+        assert_eq!(
+            audio
+                .issues
+                .iter()
+                .map(|i| (i.kind, i.byte_offset))
+                .collect::<Vec<_>>(),
+            [
+                (MissingPts, Some(6 * 188)),
+                (NonMonotonicDts, Some(7 * 188)),
+                (Gap, Some(8 * 188)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::io::Write;
+use crate::ts::psi::{Psi, PsiTable, PsiTableHeader, PsiTableSyntax};
+use crate::ts::{Descriptor, VersionNumber};
+use crate::{ErrorKind, Result};
+use alloc::{vec, vec::Vec};
+
+/// Payload for TSDT(Transport Stream Description Table) packets.
+///
+/// The descriptors of a multi-section table are merged in order of section number.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tsdt {
+    pub version_number: VersionNumber,
+
+    /// Whether the table is currently applicable.
+    ///
+    /// If `false`, the table is not yet applicable and shall be the next table to become valid.
+    pub is_current: bool,
+
+    /// Descriptors that apply to the whole transport stream.
+    pub descriptors: Vec<Descriptor>,
+}
+impl Tsdt {
+    const TABLE_ID: u8 = 3;
+
+    pub(super) fn from_psi(psi: Psi) -> Result<Self> {
+        track_assert!(
+            psi.tables.iter().all(|t| !t.header.private_bit),
+            ErrorKind::InvalidInput
+        );
+        let sections = track!(psi.table_sections(Self::TABLE_ID))?;
+
+        let mut descriptors = Vec::new();
+        for syntax in &sections {
+            let mut reader = &syntax.table_data[..];
+            while !reader.is_empty() {
+                descriptors.push(track!(Descriptor::read_from(&mut reader))?);
+            }
+        }
+        Ok(Tsdt {
+            version_number: sections[0].version_number,
+            is_current: sections[0].current_next_indicator,
+            descriptors,
+        })
+    }
+
+    pub(super) fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        track!(self.to_psi().and_then(|psi| psi.write_to(writer)))
+    }
+
+    fn to_psi(&self) -> Result<Psi> {
+        let mut table_data = Vec::new();
+        for d in &self.descriptors {
+            track!(d.write_to(&mut table_data))?;
+        }
+
+        let header = PsiTableHeader {
+            table_id: Self::TABLE_ID,
+            private_bit: false,
+        };
+        let syntax = Some(PsiTableSyntax {
+            // Reserved
+            table_id_extension: 0xFFFF,
+            version_number: self.version_number,
+            current_next_indicator: self.is_current,
+            section_number: 0,
+            last_section_number: 0,
+            table_data,
+        });
+        let tables = vec![PsiTable {
+            header,
+            syntax,
+            crc_ok: true,
+        }];
+        Ok(Psi { tables })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::crc::Crc32;
+    use crate::ts::{
+        Pid, ReadTsPacket, TsPacket, TsPacketReader, TsPacketWriter, TsPayload, WriteTsPacket,
+    };
+
+    #[test]
+    fn tsdt() {
+        let mut section = vec![
+            0x03, 0xB0, 0x00, // table_id and section_length
+            0xFF, 0xFF, 0xC5, 0x00, 0x00, // version_number = 2
+            0x05, 0x04, b'D', b'V', b'B', b' ', // registration descriptor
+            0x0A, 0x04, b'e', b'n', b'g', 0x00, // ISO 639 language descriptor
+        ];
+        section[2] = (section.len() - 3 + 4) as u8;
+        let mut crc32 = Crc32::new();
+        crc32.update(&section);
+        section.extend_from_slice(&crc32.value().to_be_bytes());
+        let mut payload = vec![0];
+        payload.extend_from_slice(&section);
+
+        let pid = Pid::new_const(Pid::TSDT);
+        let mut writer = TsPacketWriter::new(Vec::new());
+        let packet = track_try_unwrap!(TsPacket::builder(pid)
+            .payload_unit_start_indicator(true)
+            .payload_raw(&payload)
+            .build());
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+        let stream = writer.into_stream();
+
+        let mut reader = TsPacketReader::new(&stream[..]);
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        let tsdt = packet.payload.as_ref().unwrap().as_tsdt().unwrap();
+        assert_eq!(tsdt.version_number.as_u8(), 2);
+        assert!(tsdt.is_current);
+        assert_eq!(tsdt.descriptors.len(), 2);
+        assert_eq!(tsdt.descriptors[0].format_identifier(), Some(*b"DVB "));
+        assert_eq!(tsdt.descriptors[1].iso_639_language_code(), Some(*b"eng"));
+        assert_eq!(
+            packet.to_string(),
+            "PID=0x0002 CC=0 PUSI TSDT[VER=2 DESCS=2]"
+        );
+
+        // Round trip
+        let mut bytes = Vec::new();
+        track_try_unwrap!(packet.payload.as_ref().unwrap().write_to(&mut bytes));
+        assert_eq!(bytes, payload);
+
+        let mut writer = TsPacketWriter::new(Vec::new());
+        let packet = track_try_unwrap!(TsPacket::builder(pid)
+            .payload(TsPayload::Tsdt(tsdt.clone()))
+            .build());
+        track_try_unwrap!(writer.write_ts_packet(&packet));
+        let stream = writer.into_stream();
+        let mut reader = TsPacketReader::new(&stream[..]);
+        let packet = track_try_unwrap!(reader.read_ts_packet()).unwrap();
+        assert_eq!(packet.payload.as_ref().unwrap().as_tsdt(), Some(tsdt));
+    }
+}
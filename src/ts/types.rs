@@ -1,14 +1,17 @@
+use crate::io::{Read, ReadBytesExt, Write, WriteBytesExt};
 use crate::time::Timestamp;
 use crate::ts::TsPacket;
-use crate::{ErrorKind, Result};
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
-use std::fmt;
-use std::hash::{Hash, Hasher};
-use std::io::{Read, Write};
-use std::ops::Deref;
+use crate::{Error, ErrorKind, Result};
+use alloc::vec::Vec;
+use byteorder::BigEndian;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::{Deref, DerefMut};
 
 /// Packet Identifier.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "u16", into = "u16"))]
 pub struct Pid(u16);
 impl Pid {
     /// Maximum PID value.
@@ -17,6 +20,43 @@ impl Pid {
     /// PID of the Program Association Table (PAT) packet.
     pub const PAT: u16 = 0;
 
+    /// PID of the Conditional Access Table (CAT) packet.
+    pub const CAT: u16 = 0x0001;
+
+    /// PID of the Transport Stream Description Table (TSDT) packet.
+    pub const TSDT: u16 = 0x0002;
+
+    /// PID of the Network Information Table (NIT) packet (DVB).
+    pub const NIT: u16 = 0x0010;
+
+    /// PID of the Service Description Table (SDT) packet (DVB).
+    pub const SDT: u16 = 0x0011;
+
+    /// PID of the Bouquet Association Table (BAT) packet (DVB).
+    ///
+    /// This is the same as `Pid::SDT`.
+    pub const BAT: u16 = 0x0011;
+
+    /// PID of the Event Information Table (EIT) packet (DVB).
+    pub const EIT: u16 = 0x0012;
+
+    /// PID of the Running Status Table (RST) packet (DVB).
+    pub const RST: u16 = 0x0013;
+
+    /// PID of the Time and Date Table (TDT) packet (DVB).
+    pub const TDT: u16 = 0x0014;
+
+    /// PID of the Time Offset Table (TOT) packet (DVB).
+    ///
+    /// This is the same as `Pid::TDT`.
+    pub const TOT: u16 = 0x0014;
+
+    /// PID of the network synchronization packet (DVB).
+    pub const DVB_SYNC: u16 = 0x0015;
+
+    /// PID of the ATSC Program and System Information Protocol (PSIP) base tables (e.g., MGT and VCT).
+    pub const PSIP: u16 = 0x1FFB;
+
     /// PID of the null packet.
     pub const NULL: u16 = 0x1FFF;
 
@@ -35,19 +75,53 @@ impl Pid {
         Ok(Pid(pid))
     }
 
+    /// Makes a new `Pid` instance in `const` contexts.
+    ///
+    /// `Pid::new` cannot be a `const fn` because building its error is not a constant operation.
+    ///
+    /// # Panics
+    ///
+    /// If `pid` exceeds `Pid::MAX`, it will panic (at compile time if evaluated in a `const` context).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mpeg2ts::ts::Pid;
+    ///
+    /// const VIDEO_PID: Pid = Pid::new_const(0x100);
+    /// assert_eq!(VIDEO_PID.to_string(), "0x0100 (256)");
+    /// ```
+    pub const fn new_const(pid: u16) -> Self {
+        assert!(pid <= Self::MAX, "Too large PID");
+        Pid(pid)
+    }
+
     /// Returns the value of the `Pid`.
     pub fn as_u16(&self) -> u16 {
         self.0
     }
 
-    pub(super) fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+    /// Returns `true` if the PID is in the range `0x0000..=0x001F`, otherwise `false`.
+    ///
+    /// The PIDs in this range are assigned to the tables defined in ISO/IEC 13818-1 and ETSI EN 300 468
+    /// (or reserved for future use), and cannot be used for PMTs or elementary streams.
+    pub fn is_reserved(&self) -> bool {
+        self.0 <= 0x001F
+    }
+
+    /// Reads a PID that is preceded by three reserved bits.
+    ///
+    /// If `lenient` is `false`, the reserved bits must be all ones.
+    pub(super) fn read_from<R: Read>(mut reader: R, lenient: bool) -> Result<Self> {
         let n = track_io!(reader.read_u16::<BigEndian>())?;
-        track_assert_eq!(
-            n & 0b1110_0000_0000_0000,
-            0b1110_0000_0000_0000,
-            ErrorKind::InvalidInput,
-            "Unexpected reserved bits"
-        );
+        if !lenient {
+            track_assert_eq!(
+                n & 0b1110_0000_0000_0000,
+                0b1110_0000_0000_0000,
+                ErrorKind::InvalidInput,
+                "Unexpected reserved bits"
+            );
+        }
         Ok(Pid(n & 0b0001_1111_1111_1111))
     }
 
@@ -57,14 +131,38 @@ impl Pid {
         Ok(())
     }
 }
+impl fmt::Display for Pid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x{:04X} ({})", self.0, self.0)
+    }
+}
 impl From<u8> for Pid {
     fn from(f: u8) -> Self {
         Pid(u16::from(f))
     }
 }
+impl TryFrom<u16> for Pid {
+    type Error = Error;
+    fn try_from(f: u16) -> Result<Self> {
+        track!(Pid::new(f))
+    }
+}
+impl From<Pid> for u16 {
+    fn from(f: Pid) -> Self {
+        f.0
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Pid {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Pid(u.int_in_range(0..=Self::MAX)?))
+    }
+}
 
 /// Continuity counter.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "u8", into = "u8"))]
 pub struct ContinuityCounter(u8);
 impl ContinuityCounter {
     /// Maximum counter value.
@@ -120,9 +218,28 @@ impl Default for ContinuityCounter {
         Self::new()
     }
 }
+impl TryFrom<u8> for ContinuityCounter {
+    type Error = Error;
+    fn try_from(f: u8) -> Result<Self> {
+        track!(ContinuityCounter::from_u8(f))
+    }
+}
+impl From<ContinuityCounter> for u8 {
+    fn from(f: ContinuityCounter) -> Self {
+        f.0
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ContinuityCounter {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(ContinuityCounter(u.int_in_range(0..=Self::MAX)?))
+    }
+}
 
 /// Version number for PSI table syntax section.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "u8", into = "u8"))]
 pub struct VersionNumber(u8);
 impl VersionNumber {
     /// Maximum version number.
@@ -165,9 +282,28 @@ impl Default for VersionNumber {
         Self::new()
     }
 }
+impl TryFrom<u8> for VersionNumber {
+    type Error = Error;
+    fn try_from(f: u8) -> Result<Self> {
+        track!(VersionNumber::from_u8(f))
+    }
+}
+impl From<VersionNumber> for u8 {
+    fn from(f: VersionNumber) -> Self {
+        f.0
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for VersionNumber {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(VersionNumber(u.int_in_range(0..=Self::MAX)?))
+    }
+}
 
 /// Byte sequence used to represent packet payload data.
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "Vec<u8>", into = "Vec<u8>"))]
 pub struct Bytes {
     buf: [u8; Bytes::MAX_SIZE],
     len: usize,
@@ -197,6 +333,37 @@ impl Bytes {
         Ok(Bytes { buf, len })
     }
 
+    /// Returns the number of bytes that can be appended before reaching `Bytes::MAX_SIZE`.
+    pub fn remaining_capacity(&self) -> usize {
+        Self::MAX_SIZE - self.len
+    }
+
+    /// Shortens the byte sequence to `len` bytes.
+    ///
+    /// If `len` is greater than the current length, this has no effect.
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// Appends `bytes` to the end of the byte sequence.
+    ///
+    /// # Errors
+    ///
+    /// If the resulting length would exceed `Bytes::MAX_SIZE`,
+    /// it will return an `ErrorKind::InvalidInput` error and the byte sequence is left unchanged.
+    pub fn try_extend_from_slice(&mut self, bytes: &[u8]) -> Result<()> {
+        track_assert!(
+            bytes.len() <= self.remaining_capacity(),
+            ErrorKind::InvalidInput,
+            "Too large: actual={} bytes, remaining={} bytes",
+            bytes.len(),
+            self.remaining_capacity()
+        );
+        self.buf[self.len..][..bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+
     pub(super) fn read_from<R: Read>(mut reader: R) -> Result<Self> {
         let mut offset = 0;
         let mut buf = [0; Self::MAX_SIZE];
@@ -215,20 +382,111 @@ impl Bytes {
         Ok(())
     }
 }
+impl Default for Bytes {
+    /// Returns an empty byte sequence.
+    fn default() -> Self {
+        Bytes {
+            buf: [0; Self::MAX_SIZE],
+            len: 0,
+        }
+    }
+}
+impl TryFrom<&[u8]> for Bytes {
+    type Error = Error;
+    fn try_from(f: &[u8]) -> Result<Self> {
+        track!(Bytes::new(f))
+    }
+}
+impl TryFrom<Vec<u8>> for Bytes {
+    type Error = Error;
+    fn try_from(f: Vec<u8>) -> Result<Self> {
+        track!(Bytes::new(&f))
+    }
+}
+impl From<Bytes> for Vec<u8> {
+    fn from(f: Bytes) -> Self {
+        f.to_vec()
+    }
+}
 impl Deref for Bytes {
     type Target = [u8];
     fn deref(&self) -> &Self::Target {
         &self.buf[..self.len]
     }
 }
+impl DerefMut for Bytes {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buf[..self.len]
+    }
+}
+impl Extend<u8> for Bytes {
+    /// Appends the bytes of `iter`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resulting length would exceed `Bytes::MAX_SIZE`.
+    /// Use `try_extend_from_slice` to handle the overflow as an error.
+    fn extend<T: IntoIterator<Item = u8>>(&mut self, iter: T) {
+        for b in iter {
+            assert!(self.len < Self::MAX_SIZE, "Bytes overflow");
+            self.buf[self.len] = b;
+            self.len += 1;
+        }
+    }
+}
+impl<'a> Extend<&'a u8> for Bytes {
+    /// Appends the bytes of `iter`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resulting length would exceed `Bytes::MAX_SIZE`.
+    fn extend<T: IntoIterator<Item = &'a u8>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+impl IntoIterator for Bytes {
+    type Item = u8;
+    type IntoIter = core::iter::Take<core::array::IntoIter<u8, { Bytes::MAX_SIZE }>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.buf.into_iter().take(self.len)
+    }
+}
+impl<'a> IntoIterator for &'a Bytes {
+    type Item = &'a u8;
+    type IntoIter = core::slice::Iter<'a, u8>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+impl<'a> IntoIterator for &'a mut Bytes {
+    type Item = &'a mut u8;
+    type IntoIter = core::slice::IterMut<'a, u8>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
 impl AsRef<[u8]> for Bytes {
     fn as_ref(&self) -> &[u8] {
         self.deref()
     }
 }
 impl fmt::Debug for Bytes {
+    /// Formats the length and the leading bytes (up to 16) in hexadecimal,
+    /// e.g., `Bytes(len=184, [00 00 01 E0 ..])`.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Bytes({:?})", self.deref())
+        const MAX_DUMP_SIZE: usize = 16;
+
+        write!(f, "Bytes(len={}, [", self.len)?;
+        for (i, b) in self.iter().take(MAX_DUMP_SIZE).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:02X}", b)?;
+        }
+        if self.len > MAX_DUMP_SIZE {
+            write!(f, " ..")?;
+        }
+        write!(f, "])")
     }
 }
 impl PartialEq for Bytes {
@@ -242,16 +500,40 @@ impl Hash for Bytes {
         self.as_ref().hash(hasher);
     }
 }
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Bytes {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let len = u.arbitrary_len::<u8>()?.min(Self::MAX_SIZE);
+        Ok(Bytes::new(u.bytes(len)?).expect("Never fails"))
+    }
+}
 
 /// Transport scrambling control.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TransportScramblingControl {
     NotScrambled = 0b00,
     ScrambledWithEvenKey = 0b10,
     ScrambledWithOddKey = 0b11,
 }
 impl TransportScramblingControl {
+    /// Returns `true` if the payload is scrambled, otherwise `false`.
+    pub fn is_scrambled(&self) -> bool {
+        *self != TransportScramblingControl::NotScrambled
+    }
+
+    /// Returns the parity of the key used to scramble the payload.
+    ///
+    /// If the payload is not scrambled, it will return `None`.
+    pub fn key_parity(&self) -> Option<KeyParity> {
+        match *self {
+            TransportScramblingControl::NotScrambled => None,
+            TransportScramblingControl::ScrambledWithEvenKey => Some(KeyParity::Even),
+            TransportScramblingControl::ScrambledWithOddKey => Some(KeyParity::Odd),
+        }
+    }
+
     pub(super) fn from_u8(n: u8) -> Result<Self> {
         Ok(match n {
             0b00 => TransportScramblingControl::NotScrambled,
@@ -262,10 +544,29 @@ impl TransportScramblingControl {
         })
     }
 }
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for TransportScramblingControl {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&[
+            TransportScramblingControl::NotScrambled,
+            TransportScramblingControl::ScrambledWithEvenKey,
+            TransportScramblingControl::ScrambledWithOddKey,
+        ])?)
+    }
+}
+
+/// Parity of the control word used to scramble a TS packet payload.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyParity {
+    Even,
+    Odd,
+}
 
 /// Legal time window.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LegalTimeWindow {
     is_valid: bool,
     offset: u16,
@@ -313,10 +614,21 @@ impl LegalTimeWindow {
         Ok(())
     }
 }
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for LegalTimeWindow {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(LegalTimeWindow {
+            is_valid: u.arbitrary()?,
+            offset: u.int_in_range(0..=Self::MAX_OFFSET)?,
+        })
+    }
+}
 
 /// Piecewise rate.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "u32", into = "u32"))]
 pub struct PiecewiseRate(u32);
 impl PiecewiseRate {
     /// Maximum rate.
@@ -352,10 +664,28 @@ impl PiecewiseRate {
         Ok(())
     }
 }
+impl TryFrom<u32> for PiecewiseRate {
+    type Error = Error;
+    fn try_from(f: u32) -> Result<Self> {
+        track!(PiecewiseRate::new(f))
+    }
+}
+impl From<PiecewiseRate> for u32 {
+    fn from(f: PiecewiseRate) -> Self {
+        f.0
+    }
+}
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for PiecewiseRate {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(PiecewiseRate(u.int_in_range(0..=Self::MAX)?))
+    }
+}
 
 /// Seamless splice.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SeamlessSplice {
     splice_type: u8,
     dts_next_access_unit: Timestamp,
@@ -408,6 +738,15 @@ impl SeamlessSplice {
         Ok(())
     }
 }
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for SeamlessSplice {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(SeamlessSplice {
+            splice_type: u.int_in_range(0..=Self::MAX_SPLICE_TYPE)?,
+            dts_next_access_unit: u.arbitrary()?,
+        })
+    }
+}
 
 #[cfg(test)]
 mod test {
@@ -422,6 +761,53 @@ mod test {
         assert_eq!(bytes.as_ref(), &[0; Bytes::MAX_SIZE][..]);
     }
 
+    #[test]
+    fn bytes_extend() {
+        let mut bytes = Bytes::try_from(&[1, 2, 3][..]).unwrap();
+        assert_eq!(bytes.remaining_capacity(), Bytes::MAX_SIZE - 3);
+
+        bytes.try_extend_from_slice(&[4, 5]).unwrap();
+        bytes.extend([6, 7]);
+        bytes.extend(&[8]);
+        assert_eq!(bytes.as_ref(), [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(bytes.iter().copied().sum::<u8>(), 36);
+        assert_eq!(
+            bytes.clone().into_iter().collect::<Vec<_>>(),
+            bytes.to_vec()
+        );
+
+        bytes.truncate(2);
+        bytes.truncate(100);
+        assert_eq!(bytes.as_ref(), [1, 2]);
+
+        assert!(bytes
+            .try_extend_from_slice(&[0; Bytes::MAX_SIZE - 1])
+            .is_err());
+        assert_eq!(bytes.len(), 2);
+        bytes
+            .try_extend_from_slice(&[0; Bytes::MAX_SIZE - 2])
+            .unwrap();
+        assert_eq!(bytes.remaining_capacity(), 0);
+
+        assert!(Bytes::try_from(vec![0; Bytes::MAX_SIZE + 1]).is_err());
+        assert!(Bytes::default().is_empty());
+    }
+
+    #[test]
+    fn pid() {
+        const PMT_PID: Pid = Pid::new_const(0x1000);
+        assert_eq!(PMT_PID, Pid::new(0x1000).unwrap());
+        assert_eq!(PMT_PID.to_string(), "0x1000 (4096)");
+        assert_eq!(Pid::from(0x11).to_string(), "0x0011 (17)");
+
+        assert!(Pid::from(0x11).is_reserved());
+        assert!(!PMT_PID.is_reserved());
+        match PMT_PID.as_u16() {
+            Pid::PAT | Pid::CAT | Pid::NULL => panic!(),
+            _ => {}
+        }
+    }
+
     // #[test]
     // fn bytes_write_to() {
     //     let bytes = Bytes::new(&[1, 2, 3]).unwrap();
@@ -0,0 +1,656 @@
+use crate::io::Read;
+use crate::time::ClockReference;
+use crate::ts::payload::{Pat, Pmt};
+use crate::ts::{
+    ContinuityChecker, Pid, PidTable, ReadTsPacket, TsPacket, TsPacketReader, TsPayload,
+};
+use crate::{Error, ErrorKind, Result};
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// Kind of an error detected by `Validator`.
+///
+/// These correspond to the first priority indicators of ETSI TR 101 290.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ValidationErrorKind {
+    /// 1.1 TS_sync_loss: two or more consecutive packets have corrupted sync bytes.
+    ///
+    /// The synchronization is regained after five consecutive packets with correct sync bytes.
+    TsSyncLoss,
+
+    /// 1.2 Sync_byte_error: a packet does not start with the sync byte.
+    SyncByteError,
+
+    /// 1.3 PAT_error: a PAT does not occur at least every 0.5 seconds,
+    /// a section on PID 0x0000 cannot be decoded as a PAT (e.g., it has a wrong `table_id`),
+    /// or a packet on PID 0x0000 is scrambled.
+    PatError,
+
+    /// 1.4 Continuity_count_error: a packet is lost, duplicated more than once, or out of order.
+    ContinuityCountError,
+
+    /// 1.5 PMT_error: a PMT does not occur at least every 0.5 seconds on a PID referred to by the PAT,
+    /// a section on the PID cannot be decoded as a PMT, or a packet on the PID is scrambled.
+    PmtError,
+
+    /// 1.6 PID_error: an elementary stream referred to by a PMT is absent for longer than
+    /// the timeout (see `Validator::set_pid_timeout`).
+    PidError,
+}
+
+/// Error detected by `Validator`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ValidationError {
+    /// Kind of the error.
+    pub kind: ValidationErrorKind,
+
+    /// PID related to the error.
+    ///
+    /// This is `None` for the sync errors.
+    /// For the timeouts, this is the PID of the missing table or stream.
+    pub pid: Option<Pid>,
+
+    /// Index of the packet at which the error was detected (see `TsPacketReader::packet_index`).
+    ///
+    /// For the timeouts, this is the packet carrying the PCR that exceeded the limit.
+    pub packet_index: u64,
+
+    /// Byte offset of the packet at which the error was detected.
+    ///
+    /// For the sync errors, this is the offset of the first corrupted byte.
+    pub byte_offset: u64,
+}
+
+/// Result of the validation by `Validator`.
+#[derive(Debug, Default, Clone)]
+pub struct ValidationReport {
+    /// Number of the decoded packets.
+    pub packets: u64,
+
+    /// Errors in the order of detection.
+    pub errors: Vec<ValidationError>,
+}
+impl ValidationReport {
+    /// Returns the number of the errors of the given kind.
+    pub fn count(&self, kind: ValidationErrorKind) -> usize {
+        self.errors.iter().filter(|e| e.kind == kind).count()
+    }
+
+    /// Returns `true` if no errors have been detected, otherwise `false`.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Reads the whole of the stream, and returns the result of the validation.
+///
+/// See `Validator` for the details.
+pub fn validate<R: Read>(reader: TsPacketReader<R>) -> Result<ValidationReport> {
+    let mut validator = Validator::new(reader);
+    while track!(validator.read_ts_packet())?.is_some() {}
+    Ok(validator.into_report())
+}
+
+/// TS packet reader that checks the stream against the first priority indicators of ETSI TR 101 290.
+///
+/// The detected errors are recorded in the report (see `report`) instead of failing,
+/// so that damaged streams can be validated to the end.
+/// Undecodable packets are skipped in the same way as `RecoveringTsPacketReader`.
+///
+/// The repetition intervals and timeouts are measured with the PCRs on the first PID carrying them,
+/// and are checked each time the PCR advances.
+/// If the stream has no PCRs, these checks are not performed.
+#[derive(Debug)]
+pub struct Validator<R> {
+    inner: TsPacketReader<R>,
+    pid_timeout: Duration,
+    report: ValidationReport,
+    skipped_bytes: u64,
+    sync: SyncState,
+    continuity_checker: ContinuityChecker,
+    clock: Clock,
+    pat: Timer,
+    pmts: PidTable<Timer>,
+    programs: PidTable<Vec<Pid>>,
+    streams: PidTable<Timer>,
+}
+impl<R: Read> Validator<R> {
+    /// Maximum interval between the occurrences of a PAT or a PMT.
+    pub const MAX_PSI_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Default value of the timeout for `ValidationErrorKind::PidError`.
+    pub const DEFAULT_PID_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Makes a new `Validator` instance.
+    pub fn new(inner: TsPacketReader<R>) -> Self {
+        let skipped_bytes = inner.skipped_bytes();
+        Validator {
+            inner,
+            pid_timeout: Self::DEFAULT_PID_TIMEOUT,
+            report: ValidationReport::default(),
+            skipped_bytes,
+            sync: SyncState::default(),
+            continuity_checker: ContinuityChecker::new(),
+            clock: Clock::default(),
+            pat: Timer::default(),
+            pmts: PidTable::new(),
+            programs: PidTable::new(),
+            streams: PidTable::new(),
+        }
+    }
+
+    /// Sets the period after which an absent elementary stream is reported as `ValidationErrorKind::PidError`.
+    ///
+    /// The default value is `DEFAULT_PID_TIMEOUT`.
+    pub fn set_pid_timeout(&mut self, timeout: Duration) {
+        self.pid_timeout = timeout;
+    }
+
+    /// Returns the result of the validation so far.
+    pub fn report(&self) -> &ValidationReport {
+        &self.report
+    }
+
+    /// Takes ownership of `self`, and returns the result of the validation.
+    pub fn into_report(self) -> ValidationReport {
+        self.report
+    }
+
+    /// Returns a reference to the underlaying TS packet reader.
+    pub fn ts_packet_reader(&self) -> &TsPacketReader<R> {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlaying TS packet reader.
+    pub fn ts_packet_reader_mut(&mut self) -> &mut TsPacketReader<R> {
+        &mut self.inner
+    }
+
+    /// Takes ownership of `self`, and returns the underlaying TS packet reader.
+    pub fn into_inner(self) -> TsPacketReader<R> {
+        self.inner
+    }
+
+    fn push_error(
+        &mut self,
+        kind: ValidationErrorKind,
+        pid: Option<Pid>,
+        packet_index: u64,
+        byte_offset: u64,
+    ) {
+        self.report.errors.push(ValidationError {
+            kind,
+            pid,
+            packet_index,
+            byte_offset,
+        });
+    }
+
+    /// Handles an error returned by the underlaying reader, and skips the packet.
+    fn skip_packet(&mut self, error: &Error, packet_index: u64) -> Result<()> {
+        let (byte_offset, pid) = self.inner.last_packet_location();
+        let taken = self.inner.packet_index() > packet_index;
+        self.check_skipped_bytes(byte_offset, packet_index);
+        track!(self.inner.recover())?;
+        self.skipped_bytes = self.inner.skipped_bytes();
+        if !taken {
+            // A truncated packet at the end of the stream
+            return Ok(());
+        }
+
+        match pid {
+            None => {
+                let size = self.inner.byte_offset() - byte_offset;
+                self.sync_byte_error(byte_offset, size, packet_index);
+            }
+            Some(pid) if *error.kind() == ErrorKind::InvalidInput => {
+                if pid.as_u16() == Pid::PAT {
+                    self.push_error(
+                        ValidationErrorKind::PatError,
+                        Some(pid),
+                        packet_index,
+                        byte_offset,
+                    );
+                } else if self.pmts.contains(pid) {
+                    self.push_error(
+                        ValidationErrorKind::PmtError,
+                        Some(pid),
+                        packet_index,
+                        byte_offset,
+                    );
+                }
+            }
+            Some(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Reports the bytes skipped by the resynchronization of the underlaying reader (if any)
+    /// before the packet at `byte_offset`.
+    fn check_skipped_bytes(&mut self, byte_offset: u64, packet_index: u64) {
+        let size = self.inner.skipped_bytes() - self.skipped_bytes;
+        if size > 0 {
+            self.skipped_bytes += size;
+            self.sync_byte_error(byte_offset - size, size, packet_index);
+        }
+    }
+
+    /// Reports a corrupted region of `size` bytes starting at `byte_offset`.
+    fn sync_byte_error(&mut self, byte_offset: u64, size: u64, packet_index: u64) {
+        self.push_error(
+            ValidationErrorKind::SyncByteError,
+            None,
+            packet_index,
+            byte_offset,
+        );
+        let packet_size = self.inner.packet_format().packet_size() as u64;
+        if self.sync.corrupt((size / packet_size).max(1)) {
+            self.push_error(
+                ValidationErrorKind::TsSyncLoss,
+                None,
+                packet_index,
+                byte_offset,
+            );
+        }
+    }
+
+    fn check_packet(&mut self, packet: &TsPacket) {
+        let packet_index = self.inner.packet_index() - 1;
+        let (byte_offset, _) = self.inner.last_packet_location();
+        self.check_skipped_bytes(byte_offset, packet_index);
+        self.sync.correct();
+        self.report.packets += 1;
+
+        let pid = packet.header.pid;
+        let scrambled = packet.header.transport_scrambling_control.is_scrambled();
+        let now = self.clock.now;
+        if pid.as_u16() == Pid::PAT {
+            if scrambled {
+                self.push_error(
+                    ValidationErrorKind::PatError,
+                    Some(pid),
+                    packet_index,
+                    byte_offset,
+                );
+            }
+            if let Some(TsPayload::Pat(pat)) = &packet.payload {
+                self.pat.seen(now);
+                self.update_pat(pat);
+            }
+        } else if let Some(timer) = self.pmts.get_mut(pid) {
+            if let Some(TsPayload::Pmt(pmt)) = &packet.payload {
+                timer.seen(now);
+                self.update_pmt(pid, pmt);
+            }
+            if scrambled {
+                self.push_error(
+                    ValidationErrorKind::PmtError,
+                    Some(pid),
+                    packet_index,
+                    byte_offset,
+                );
+            }
+        }
+        if let Some(timer) = self.streams.get_mut(pid) {
+            timer.seen(now);
+        }
+
+        if self.continuity_checker.check(packet).is_discontinuous() {
+            self.push_error(
+                ValidationErrorKind::ContinuityCountError,
+                Some(pid),
+                packet_index,
+                byte_offset,
+            );
+        }
+
+        if self.clock.update(packet) {
+            self.check_timers(packet_index, byte_offset);
+        }
+    }
+
+    fn update_pat(&mut self, pat: &Pat) {
+        if !pat.is_current {
+            return;
+        }
+        let now = self.clock.now;
+        let mut pmts = PidTable::new();
+        for pa in pat.table.iter().filter(|pa| pa.program_num != 0) {
+            let pid = pa.program_map_pid;
+            let timer = self.pmts.remove(pid).unwrap_or_else(|| Timer::new(now));
+            pmts.insert(pid, timer);
+        }
+        self.pmts = pmts;
+
+        let removed = self
+            .programs
+            .iter()
+            .map(|(pid, _)| pid)
+            .filter(|&pid| !self.pmts.contains(pid))
+            .collect::<Vec<_>>();
+        if !removed.is_empty() {
+            for pid in removed {
+                self.programs.remove(pid);
+            }
+            self.update_streams();
+        }
+    }
+
+    fn update_pmt(&mut self, pmt_pid: Pid, pmt: &Pmt) {
+        if !pmt.is_current {
+            return;
+        }
+        let pids = pmt.es_info.iter().map(|es| es.elementary_pid).collect();
+        self.programs.insert(pmt_pid, pids);
+        self.update_streams();
+    }
+
+    /// Updates the elementary streams to be monitored according to the PMTs.
+    fn update_streams(&mut self) {
+        let now = self.clock.now;
+        let mut streams = PidTable::new();
+        for (_, pids) in self.programs.iter() {
+            for &pid in pids {
+                if !streams.contains(pid) {
+                    let timer = self.streams.remove(pid).unwrap_or_else(|| Timer::new(now));
+                    streams.insert(pid, timer);
+                }
+            }
+        }
+        self.streams = streams;
+    }
+
+    fn check_timers(&mut self, packet_index: u64, byte_offset: u64) {
+        let now = self.clock.now;
+        let psi_limit = ticks(Self::MAX_PSI_INTERVAL);
+        let pid_limit = ticks(self.pid_timeout);
+
+        let mut errors = Vec::new();
+        if self.pat.expired(now, psi_limit) {
+            errors.push((ValidationErrorKind::PatError, Pid::new_const(Pid::PAT)));
+        }
+        for (pid, timer) in self.pmts.iter_mut() {
+            if timer.expired(now, psi_limit) {
+                errors.push((ValidationErrorKind::PmtError, pid));
+            }
+        }
+        for (pid, timer) in self.streams.iter_mut() {
+            if timer.expired(now, pid_limit) {
+                errors.push((ValidationErrorKind::PidError, pid));
+            }
+        }
+        for (kind, pid) in errors {
+            self.push_error(kind, Some(pid), packet_index, byte_offset);
+        }
+    }
+}
+impl<R: Read> ReadTsPacket for Validator<R> {
+    fn read_ts_packet(&mut self) -> Result<Option<TsPacket>> {
+        loop {
+            let packet_index = self.inner.packet_index();
+            match self.inner.read_ts_packet() {
+                Err(e) if matches!(e.kind(), ErrorKind::InvalidInput | ErrorKind::CrcMismatch) => {
+                    track!(self.skip_packet(&e, packet_index))?;
+                }
+                result => {
+                    let packet = track!(result)?;
+                    if let Some(packet) = &packet {
+                        self.check_packet(packet);
+                    }
+                    return Ok(packet);
+                }
+            }
+        }
+    }
+
+    fn last_packet_byte_offset(&self) -> Option<u64> {
+        self.inner.last_packet_byte_offset()
+    }
+}
+
+/// Converts `duration` into 27 MHz ticks.
+fn ticks(duration: Duration) -> u64 {
+    (duration.as_nanos() * 27 / 1000) as u64
+}
+
+/// Synchronization state with the hysteresis of TS_sync_loss.
+#[derive(Debug, Default)]
+struct SyncState {
+    synchronized: bool,
+    correct: u64,
+    corrupted: u64,
+}
+impl SyncState {
+    fn correct(&mut self) {
+        self.corrupted = 0;
+        self.correct += 1;
+        if self.correct >= 5 {
+            self.synchronized = true;
+        }
+    }
+
+    /// Returns `true` if the synchronization is lost.
+    fn corrupt(&mut self, packets: u64) -> bool {
+        self.correct = 0;
+        self.corrupted += packets;
+        if self.synchronized && self.corrupted >= 2 {
+            self.synchronized = false;
+            return true;
+        }
+        false
+    }
+}
+
+/// Elapsed time measured with the PCRs on the first PID carrying them.
+#[derive(Debug, Default)]
+struct Clock {
+    pcr_pid: Option<Pid>,
+    last_pcr: Option<ClockReference>,
+
+    // 27 MHz ticks since the first PCR
+    now: u64,
+}
+impl Clock {
+    /// Returns `true` if the clock has advanced.
+    fn update(&mut self, packet: &TsPacket) -> bool {
+        let Some(a) = &packet.adaptation_field else {
+            return false;
+        };
+        let Some(pcr) = a.pcr else {
+            return false;
+        };
+        let pid = packet.header.pid;
+        if *self.pcr_pid.get_or_insert(pid) != pid {
+            return false;
+        }
+        let last = self.last_pcr.replace(pcr);
+        match last {
+            Some(last) if !a.discontinuity_indicator => {
+                // Backward jumps are regarded as discontinuities
+                let delta = pcr.delta(last).max(0) as u64;
+                self.now += delta;
+                delta > 0
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Last occurrence of a table or stream.
+#[derive(Debug, Default)]
+struct Timer {
+    last_seen: u64,
+    expired: bool,
+}
+impl Timer {
+    fn new(now: u64) -> Self {
+        Timer {
+            last_seen: now,
+            expired: false,
+        }
+    }
+
+    fn seen(&mut self, now: u64) {
+        self.last_seen = now;
+        self.expired = false;
+    }
+
+    /// Returns `true` if the interval since the last occurrence exceeds `limit` for the first time.
+    fn expired(&mut self, now: u64, limit: u64) -> bool {
+        if self.expired || now - self.last_seen <= limit {
+            return false;
+        }
+        self.expired = true;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::es::StreamType;
+    use crate::ts::test_util;
+    use crate::ts::AdaptationField;
+
+    /// Makes a stream of `frames` frames at 25 fps.
+    ///
+    /// The PAT and PMT are sent every `psi_interval` frames,
+    /// and the audio stream (PID 257) stops after `audio_frames` frames.
+    fn stream(frames: u64, psi_interval: u64, audio_frames: u64) -> Vec<u8> {
+        let pat = TsPayload::Pat(test_util::pat(&[(1, 0x1000)]));
+        let streams = [(256, StreamType::H264), (257, StreamType::AdtsAac)];
+        let pmt = TsPayload::Pmt(test_util::pmt(1, Some(256), &streams));
+        let pes = |stream_id, pts| {
+            let pes = test_util::pes(stream_id, Some(pts), None, &[0; 100]);
+            Some(TsPayload::Pes(pes))
+        };
+
+        let mut packets = Vec::new();
+        for i in 0..frames {
+            if i % psi_interval == 0 {
+                packets.push((0, None, Some(pat.clone())));
+                packets.push((0x1000, None, Some(pmt.clone())));
+            }
+            let pcr = ClockReference::new(i * 27_000_000 / 25).unwrap();
+            let pts = 9000 + i * 3600;
+            packets.push((256, Some(AdaptationField::with_pcr(pcr)), pes(0xE0, pts)));
+            if i < audio_frames {
+                packets.push((257, None, pes(0xC0, pts)));
+            }
+        }
+        test_util::write_packets(packets)
+    }
+
+    fn errors(report: &ValidationReport) -> Vec<(ValidationErrorKind, Option<u16>, u64)> {
+        report
+            .errors
+            .iter()
+            .map(|e| (e.kind, e.pid.map(|p| p.as_u16()), e.packet_index))
+            .collect()
+    }
+
+    #[test]
+    fn valid_stream() {
+        let stream = stream(50, 10, 50);
+        let report = track_try_unwrap!(validate(TsPacketReader::new(&stream[..])));
+        assert!(report.is_ok(), "{:?}", report);
+        assert_eq!(report.packets, 50 * 2 + 5 * 2);
+    }
+
+    #[test]
+    fn sync_and_continuity_errors() {
+        let mut stream = stream(50, 10, 50);
+        let size = TsPacket::SIZE;
+
+        // A single corrupted sync byte (video of frame #4)
+        stream[10 * size] = 0;
+
+        // Three consecutive corrupted sync bytes (video and audio of frame #13, and video of frame #14).
+        // These are skipped at once by the resynchronization, so the packet indices after them decrease by two.
+        for i in 30..33 {
+            stream[i * size] = 0;
+        }
+
+        // A lost packet (video of frame #40)
+        let lost = 90;
+        assert_eq!(stream[lost * size + 2], 0x00);
+        stream.drain(lost * size..(lost + 1) * size);
+
+        let report = track_try_unwrap!(validate(TsPacketReader::new(&stream[..])));
+        use ValidationErrorKind::*;
+        assert_eq!(
+            errors(&report),
+            [
+                (SyncByteError, None, 10),
+                (ContinuityCountError, Some(256), 12),
+                (SyncByteError, None, 30),
+                (TsSyncLoss, None, 30),
+                (ContinuityCountError, Some(257), 31),
+                (ContinuityCountError, Some(256), 32),
+                (ContinuityCountError, Some(256), 89),
+            ]
+        );
+        assert_eq!(report.errors[2].byte_offset, 30 * size as u64);
+        assert_eq!(report.count(SyncByteError), 2);
+    }
+
+    #[test]
+    fn psi_and_pid_errors() {
+        // The PAT and PMT are sent every 0.8 seconds, and the audio stops after 1 second
+        let stream = stream(100, 20, 25);
+        let mut validator = Validator::new(TsPacketReader::new(&stream[..]));
+        validator.set_pid_timeout(Duration::from_secs(2));
+        while track_try_unwrap!(validator.read_ts_packet()).is_some() {}
+
+        let report = validator.into_report();
+        use ValidationErrorKind::*;
+        let kinds = report
+            .errors
+            .iter()
+            .map(|e| (e.kind, e.pid.map(|p| p.as_u16())))
+            .collect::<Vec<_>>();
+        // Five gaps of the PSI and a single timeout of the audio stream (at 3 seconds)
+        let mut expected = Vec::new();
+        for i in 0..5 {
+            expected.push((PatError, Some(0)));
+            expected.push((PmtError, Some(0x1000)));
+            if i == 3 {
+                expected.push((PidError, Some(257)));
+            }
+        }
+        assert_eq!(kinds, expected);
+    }
+
+    #[test]
+    fn scrambled_pat() {
+        let mut stream = stream(10, 10, 10);
+        // Sets the transport_scrambling_control of the first PAT packet
+        stream[3] |= 0b1000_0000;
+        let report = track_try_unwrap!(validate(TsPacketReader::new(&stream[..])));
+        assert_eq!(
+            errors(&report),
+            [(ValidationErrorKind::PatError, Some(0), 0)]
+        );
+    }
+
+    #[test]
+    fn malformed_adaptation_fields() {
+        let mut stream = stream(10, 5, 10);
+        let size = TsPacket::SIZE;
+
+        // Adaptation fields too short for their PCRs in the first PAT packet and the second PMT packet
+        for i in [0, 13] {
+            let packet = &mut stream[i * size..(i + 1) * size];
+            packet[3] |= 0b0011_0000;
+            packet[4] = 1;
+            packet[5] = 0b0001_0000;
+        }
+        let report = track_try_unwrap!(validate(TsPacketReader::new(&stream[..])));
+        use ValidationErrorKind::*;
+        assert_eq!(
+            errors(&report),
+            [(PatError, Some(0), 0), (PmtError, Some(0x1000), 13)]
+        );
+        assert_eq!(report.packets, 10 * 2 + 2 * 2 - 2);
+    }
+}
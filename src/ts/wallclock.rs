@@ -0,0 +1,372 @@
+use crate::time::{ClockReference, ClockReferenceUnwrapper, Unwrapped};
+use crate::ts::payload::Section;
+use crate::ts::{Pid, TsPacket, TsPayload};
+use std::time::{Duration, SystemTime};
+
+/// Table ID of the Time and Date Table (DVB).
+const TDT_TABLE_ID: u8 = 0x70;
+
+/// Table ID of the Time Offset Table (DVB).
+const TOT_TABLE_ID: u8 = 0x73;
+
+/// Mapper from the PCR timeline to the UTC time broadcast in the TDT and TOT.
+///
+/// Each `UTC_time` in the TDTs and TOTs on `Pid::TDT` is paired with the nearest (in packets) PCR,
+/// and a line fitted (by least squares) to the pairs maps PCRs to wallclock times.
+/// Since `UTC_time` has a resolution of one second, the accuracy improves as more tables are received.
+///
+/// The fit is restarted at each PCR discontinuity (i.e., a PCR with the discontinuity indicator set or
+/// a jump of more than ten seconds), and the queries are answered with the fit of the current segment.
+/// Until the first table of a new segment is received, the mapping is extrapolated from the previous segment
+/// assuming that the wallclock time continues across the discontinuity.
+///
+/// # Examples
+///
+/// ```
+/// use mpeg2ts::time::ClockReference;
+/// use mpeg2ts::ts::payload::Section;
+/// use mpeg2ts::ts::{AdaptationField, Pid, TsPacket, TsPayload, WallclockMapper};
+/// use std::time::{Duration, SystemTime};
+///
+/// let mut mapper = WallclockMapper::new();
+///
+/// let pcr = ClockReference::new(27_000_000).unwrap();
+/// let packet = TsPacket::builder(Pid::new(256).unwrap())
+///     .adaptation(AdaptationField::with_pcr(pcr))
+///     .build()
+///     .unwrap();
+/// mapper.observe(&packet);
+///
+/// // TDT of 2000-01-01 00:00:00 UTC
+/// let tdt = Section::new(0, &[0x70, 0x70, 0x05, 0xC9, 0x58, 0x00, 0x00, 0x00]).unwrap();
+/// let packet = TsPacket::builder(Pid::new(Pid::TDT).unwrap())
+///     .payload(TsPayload::Section(tdt))
+///     .build()
+///     .unwrap();
+/// mapper.observe(&packet);
+///
+/// let later = ClockReference::new(27_000_000 * 11).unwrap();
+/// let expected = SystemTime::UNIX_EPOCH + Duration::from_secs(946_684_800 + 10);
+/// assert_eq!(mapper.wallclock_for(later), Some(expected));
+/// ```
+#[derive(Debug)]
+pub struct WallclockMapper {
+    pcr_pid: Option<Pid>,
+    packet_index: u64,
+    unwrapper: ClockReferenceUnwrapper,
+    last_pcr: Option<PcrPoint>,
+
+    // UTC time waiting for the next PCR, and the index of the packet that carried it
+    pending: Option<(SystemTime, u64)>,
+
+    segment: Segment,
+}
+impl WallclockMapper {
+    /// Makes a new `WallclockMapper` instance.
+    pub fn new() -> Self {
+        WallclockMapper {
+            pcr_pid: None,
+            packet_index: 0,
+            unwrapper: ClockReferenceUnwrapper::new(),
+            last_pcr: None,
+            pending: None,
+            segment: Segment::default(),
+        }
+    }
+
+    /// Sets the PID whose PCRs are used.
+    ///
+    /// If `None` is specified (the default), the first PID carrying PCRs is used.
+    pub fn set_pcr_pid(&mut self, pid: Option<Pid>) {
+        self.pcr_pid = pid;
+    }
+
+    /// Observes `packet`.
+    pub fn observe(&mut self, packet: &TsPacket) {
+        let packet_index = self.packet_index;
+        self.packet_index += 1;
+
+        let pid = packet.header.pid;
+        if pid.as_u16() == Pid::TDT {
+            if let Some(TsPayload::Section(section)) = &packet.payload {
+                if let Some(utc) = utc_time(section) {
+                    // Paired with the nearer of the previous and next PCRs once the next one is received
+                    self.pending = Some((utc, packet_index));
+                }
+            }
+            return;
+        }
+
+        let Some(a) = &packet.adaptation_field else {
+            return;
+        };
+        let Some(pcr) = a.pcr else {
+            return;
+        };
+        if *self.pcr_pid.get_or_insert(pid) != pid {
+            return;
+        }
+        let unwrapped = self.unwrapper.unwrap(pcr);
+        let discontinuous =
+            a.discontinuity_indicator || matches!(unwrapped, Unwrapped::Discontinuity(_));
+        if discontinuous {
+            self.unwrapper.reset();
+        }
+        let point = PcrPoint {
+            pcr,
+            elapsed: if discontinuous {
+                self.unwrapper.unwrap(pcr).value()
+            } else {
+                unwrapped.value()
+            },
+            packet_index,
+        };
+
+        if let Some((utc, utc_index)) = self.pending.take() {
+            match &self.last_pcr {
+                Some(last)
+                    if discontinuous
+                        || utc_index - last.packet_index <= packet_index - utc_index =>
+                {
+                    self.segment.add(last.elapsed, utc);
+                }
+                _ => self.segment.add(point.elapsed, utc),
+            }
+        }
+        if discontinuous {
+            let anchor = self
+                .last_pcr
+                .as_ref()
+                .and_then(|last| self.segment.wallclock_at(last.elapsed));
+            self.segment = Segment::default();
+            self.segment.anchor = anchor.map(|utc| (point.elapsed, utc));
+        }
+        self.last_pcr = Some(point);
+    }
+
+    /// Returns the wallclock time corresponding to `time` (a `Timestamp` or a `ClockReference`).
+    ///
+    /// `time` is placed on the timeline of the current segment at the shortest (forward or backward) distance
+    /// from the latest PCR.
+    /// If no mapping is available yet, it will return `None`.
+    pub fn wallclock_for<T: Into<ClockReference>>(&self, time: T) -> Option<SystemTime> {
+        let last = self.last_pcr.as_ref()?;
+        let elapsed = last
+            .elapsed
+            .checked_add_signed(time.into().delta(last.pcr))?;
+        if let Some((utc, _)) = self.pending {
+            // The latest table is tentatively paired with the latest PCR
+            let mut segment = self.segment.clone();
+            segment.add(last.elapsed, utc);
+            return segment.wallclock_at(elapsed);
+        }
+        self.segment.wallclock_at(elapsed)
+    }
+}
+impl Default for WallclockMapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+struct PcrPoint {
+    pcr: ClockReference,
+
+    // 27 MHz ticks on the unwrapped timeline of the current segment
+    elapsed: u64,
+
+    packet_index: u64,
+}
+
+/// Linear fit of the PCRs of a continuous segment to UTC times.
+#[derive(Debug, Default, Clone)]
+struct Segment {
+    // The first pair, to which the others are relative
+    origin: Option<(u64, SystemTime)>,
+
+    // Sums for the least squares (in seconds)
+    n: f64,
+    sum_x: f64,
+    sum_y: f64,
+    sum_xx: f64,
+    sum_xy: f64,
+
+    // Pair extrapolated from the previous segment (used until the first pair is added)
+    anchor: Option<(u64, SystemTime)>,
+}
+impl Segment {
+    fn add(&mut self, elapsed: u64, utc: SystemTime) {
+        let (x, y) = match self.origin {
+            None => {
+                self.origin = Some((elapsed, utc));
+                (0.0, 0.0)
+            }
+            Some((origin_elapsed, origin_utc)) => (
+                seconds(elapsed as i128 - origin_elapsed as i128),
+                signed_secs(utc, origin_utc),
+            ),
+        };
+        self.n += 1.0;
+        self.sum_x += x;
+        self.sum_y += y;
+        self.sum_xx += x * x;
+        self.sum_xy += x * y;
+    }
+
+    fn wallclock_at(&self, elapsed: u64) -> Option<SystemTime> {
+        let Some((origin_elapsed, origin_utc)) = self.origin else {
+            let (anchor_elapsed, anchor_utc) = self.anchor?;
+            let x = seconds(elapsed as i128 - anchor_elapsed as i128);
+            return add_secs(anchor_utc, x);
+        };
+
+        // With a single pair (or pairs at the same PCR), the PCR is assumed to be accurate
+        let x = seconds(elapsed as i128 - origin_elapsed as i128);
+        let variance = self.n * self.sum_xx - self.sum_x * self.sum_x;
+        let (slope, intercept) = if variance > 0.0 {
+            let slope = (self.n * self.sum_xy - self.sum_x * self.sum_y) / variance;
+            (slope, (self.sum_y - slope * self.sum_x) / self.n)
+        } else {
+            (1.0, (self.sum_y - self.sum_x) / self.n)
+        };
+        add_secs(origin_utc, intercept + slope * x)
+    }
+}
+
+/// Converts 27 MHz ticks into seconds.
+fn seconds(ticks: i128) -> f64 {
+    ticks as f64 / ClockReference::RESOLUTION as f64
+}
+
+/// Returns `a - b` in seconds.
+fn signed_secs(a: SystemTime, b: SystemTime) -> f64 {
+    match a.duration_since(b) {
+        Ok(d) => d.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    }
+}
+
+fn add_secs(t: SystemTime, secs: f64) -> Option<SystemTime> {
+    // Rounds to microseconds to cancel the floating point errors
+    let d = Duration::from_micros((secs.abs() * 1_000_000.0).round() as u64);
+    if secs >= 0.0 {
+        t.checked_add(d)
+    } else {
+        t.checked_sub(d)
+    }
+}
+
+/// Decodes `UTC_time` of a TDT or TOT section.
+///
+/// The field consists of the Modified Julian Date (16 bits) followed by the time in BCD (24 bits).
+fn utc_time(section: &Section) -> Option<SystemTime> {
+    let bytes = section.section_bytes();
+    if bytes.len() < 8 || !matches!(bytes[0], TDT_TABLE_ID | TOT_TABLE_ID) {
+        return None;
+    }
+    let mjd = u64::from(u16::from_be_bytes([bytes[3], bytes[4]]));
+    let bcd = |b: u8| (b >> 4 < 10 && b & 0xF < 10).then(|| u64::from((b >> 4) * 10 + (b & 0xF)));
+    let (h, m, s) = (bcd(bytes[5])?, bcd(bytes[6])?, bcd(bytes[7])?);
+    if h >= 24 || m >= 60 || s >= 60 {
+        return None;
+    }
+
+    // MJD 40587 is 1970-01-01
+    let days = mjd.checked_sub(40587)?;
+    let secs = days * 86400 + h * 3600 + m * 60 + s;
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::time::Timestamp;
+    use crate::ts::AdaptationField;
+
+    const SECOND: u64 = ClockReference::RESOLUTION;
+
+    /// 2020-02-02 12:34:56 UTC
+    const START: u64 = 1_580_646_896;
+
+    fn pcr_packet(pcr: u64, discontinuity_indicator: bool) -> TsPacket {
+        let mut adaptation_field =
+            AdaptationField::with_pcr(ClockReference::new(pcr % ClockReference::MODULUS).unwrap());
+        adaptation_field.discontinuity_indicator = discontinuity_indicator;
+        TsPacket::builder(Pid::new(256).unwrap())
+            .adaptation(adaptation_field)
+            .build()
+            .unwrap()
+    }
+
+    fn tdt_packet(secs: u64) -> TsPacket {
+        let bcd = |n: u64| (((n / 10) << 4) | (n % 10)) as u8;
+        let mjd = (secs / 86400 + 40587) as u16;
+        let mut bytes = vec![TDT_TABLE_ID, 0x70, 0x05];
+        bytes.extend_from_slice(&mjd.to_be_bytes());
+        bytes.extend([bcd(secs / 3600 % 24), bcd(secs / 60 % 60), bcd(secs % 60)]);
+        TsPacket::builder(Pid::new(Pid::TDT).unwrap())
+            .payload(TsPayload::Section(Section::new(0, &bytes).unwrap()))
+            .build()
+            .unwrap()
+    }
+
+    fn utc(secs_f64: f64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs_f64(secs_f64)
+    }
+
+    fn assert_near(actual: Option<SystemTime>, expected: SystemTime) {
+        let actual = actual.unwrap();
+        let diff = signed_secs(actual, expected).abs();
+        assert!(diff < 0.001, "{:?} vs {:?}", actual, expected);
+    }
+
+    #[test]
+    fn utc_time_of_tdt() {
+        let TsPayload::Section(section) = tdt_packet(START).payload.unwrap() else {
+            unreachable!();
+        };
+        assert_eq!(utc_time(&section), Some(utc(START as f64)));
+    }
+
+    #[test]
+    fn map_pcrs() {
+        let mut mapper = WallclockMapper::new();
+        assert_eq!(mapper.wallclock_for(ClockReference::new(0).unwrap()), None);
+
+        // PCRs every 100 ms wrapping around in the middle, and a TDT in every 10 PCRs.
+        // The TDTs are sent at 0.25 seconds past each second so that the truncation of the fraction is observable.
+        let first = ClockReference::MODULUS - 30 * SECOND;
+        let pcr_at = |t: f64| first + (t * SECOND as f64) as u64;
+        for i in 0..600 {
+            let t = i as f64 / 10.0;
+            mapper.observe(&pcr_packet(pcr_at(t), false));
+            if i % 10 == 2 {
+                mapper.observe(&tdt_packet(START + i / 10));
+            }
+        }
+        assert_near(
+            mapper.wallclock_for(
+                ClockReference::new(pcr_at(50.0) % ClockReference::MODULUS).unwrap(),
+            ),
+            utc(START as f64 + 50.0 - 0.2),
+        );
+        let pts = Timestamp::new((pcr_at(59.5) % ClockReference::MODULUS) / 300).unwrap();
+        assert_near(mapper.wallclock_for(pts), utc(START as f64 + 59.5 - 0.2));
+
+        // After a discontinuity, the wallclock time continues from the previous segment
+        mapper.observe(&pcr_packet(SECOND, true));
+        assert_near(
+            mapper.wallclock_for(ClockReference::new(2 * SECOND).unwrap()),
+            utc(START as f64 + 59.9 - 0.2 + 1.0),
+        );
+
+        // until a TDT is received
+        mapper.observe(&tdt_packet(START + 3600));
+        mapper.observe(&pcr_packet(SECOND + SECOND / 10, false));
+        assert_near(
+            mapper.wallclock_for(ClockReference::new(2 * SECOND).unwrap()),
+            utc(START as f64 + 3600.0 + 1.0),
+        );
+    }
+}
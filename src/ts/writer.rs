@@ -1,22 +1,87 @@
-use crate::ts::TsPacket;
+use crate::io::{Write, WriteBytesExt};
+use crate::ts::format::MAX_PACKET_SIZE;
+use crate::ts::{PacketFormat, TsPacket};
 use crate::Result;
-use std::io::Write;
+use alloc::{boxed::Box, vec::Vec};
+use byteorder::BigEndian;
 
 /// The `WriteTsPacket` trait allows for writing TS packets to a destination.
 pub trait WriteTsPacket {
     /// Writes a TS packet.
     fn write_ts_packet(&mut self, packet: &TsPacket) -> Result<()>;
+
+    /// Writes TS packets.
+    ///
+    /// Implementations may write the packets in a batch (e.g., seven packets per UDP datagram).
+    /// The default implementation calls `write_ts_packet` for each packet.
+    fn write_ts_packets(&mut self, packets: &[TsPacket]) -> Result<()> {
+        for packet in packets {
+            track!(self.write_ts_packet(packet))?;
+        }
+        Ok(())
+    }
+}
+impl<T: WriteTsPacket + ?Sized> WriteTsPacket for &mut T {
+    fn write_ts_packet(&mut self, packet: &TsPacket) -> Result<()> {
+        (**self).write_ts_packet(packet)
+    }
+
+    fn write_ts_packets(&mut self, packets: &[TsPacket]) -> Result<()> {
+        (**self).write_ts_packets(packets)
+    }
+}
+impl<T: WriteTsPacket + ?Sized> WriteTsPacket for Box<T> {
+    fn write_ts_packet(&mut self, packet: &TsPacket) -> Result<()> {
+        (**self).write_ts_packet(packet)
+    }
+
+    fn write_ts_packets(&mut self, packets: &[TsPacket]) -> Result<()> {
+        (**self).write_ts_packets(packets)
+    }
 }
 
 /// TS packet writer.
+///
+/// Each packet is encoded into a local buffer and written to the underlaying stream by a single `write_all`,
+/// so a packet that fails to be encoded is never written partially.
+/// `write_ts_packets` writes all the given packets by a single `write_all` in the same manner.
 #[derive(Debug)]
 pub struct TsPacketWriter<W> {
     stream: W,
+    packet_format: PacketFormat,
+    arrival_timestamp: u32,
+    write_raw_tables: bool,
 }
-impl<W: Write> TsPacketWriter<W> {
+impl<W> TsPacketWriter<W> {
     /// Makes a new `TsPacketWriter` instance.
     pub fn new(stream: W) -> Self {
-        TsPacketWriter { stream }
+        TsPacketWriter {
+            stream,
+            packet_format: PacketFormat::default(),
+            arrival_timestamp: 0,
+            write_raw_tables: false,
+        }
+    }
+
+    /// Sets the framing of the packets to be written.
+    ///
+    /// In the `PacketFormat::M2ts` and `PacketFormat::TrailingTimestamp` formats,
+    /// `TsPacket::arrival_timestamp` is written as the packet prefix or suffix.
+    /// If it is `None`, a timestamp is synthesized from the 27MHz PCR of the packet
+    /// (or the timestamp of the previous packet is reused if the packet has no PCR).
+    ///
+    /// The default value is `PacketFormat::Ts`.
+    pub fn set_packet_format(&mut self, format: PacketFormat) {
+        self.packet_format = format;
+    }
+
+    /// Sets whether `Pat::raw` and `Pmt::raw` are written verbatim as the payloads of PAT and PMT packets.
+    ///
+    /// If `true`, the tables that have the raw bytes (see `TsPacketReader::set_keep_raw_tables`)
+    /// are written as received, instead of being serialized from the parsed fields.
+    /// The default value is `false`.
+    pub fn set_write_raw_tables(&mut self, write_raw: bool) {
+        self.write_raw_tables = write_raw;
     }
 
     /// Returns a reference to the underlaying byte stream.
@@ -28,9 +93,63 @@ impl<W: Write> TsPacketWriter<W> {
     pub fn into_stream(self) -> W {
         self.stream
     }
+
+    /// Encodes `packet` in the packet format of the writer, and returns the encoded bytes.
+    fn encode_packet<'a>(
+        &mut self,
+        packet: &TsPacket,
+        buf: &'a mut [u8; MAX_PACKET_SIZE],
+    ) -> Result<&'a [u8]> {
+        let pcr = packet.adaptation_field.as_ref().and_then(|a| a.pcr);
+        if let Some(timestamp) = packet.arrival_timestamp {
+            self.arrival_timestamp = timestamp;
+        } else if let Some(pcr) = pcr {
+            self.arrival_timestamp = (pcr.as_u64() & 0x3FFF_FFFF) as u32;
+        }
+
+        let packet_size = self.packet_format.packet_size();
+        let mut writer = &mut buf[..packet_size];
+        if self.packet_format == PacketFormat::M2ts {
+            track_io!(writer.write_u32::<BigEndian>(self.arrival_timestamp))?;
+        }
+        track!(packet.write_to(&mut writer, self.write_raw_tables))?;
+        if self.packet_format == PacketFormat::TrailingTimestamp {
+            track_io!(writer.write_u32::<BigEndian>(self.arrival_timestamp))?;
+        } else {
+            let suffix = [0; 16];
+            let suffix_size = self.packet_format.suffix_size();
+            track_io!(writer.write_all(&suffix[..suffix_size]))?;
+        }
+        Ok(&buf[..packet_size])
+    }
+}
+#[cfg(feature = "tokio")]
+impl<W: tokio::io::AsyncWrite + Unpin> TsPacketWriter<W> {
+    /// Asynchronous version of `WriteTsPacket::write_ts_packet`.
+    pub(super) async fn write_ts_packet_async(&mut self, packet: &TsPacket) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut buf = [0; MAX_PACKET_SIZE];
+        let bytes = track!(self.encode_packet(packet, &mut buf))?;
+        track_io!(self.stream.write_all(bytes).await)?;
+        Ok(())
+    }
 }
 impl<W: Write> WriteTsPacket for TsPacketWriter<W> {
     fn write_ts_packet(&mut self, packet: &TsPacket) -> Result<()> {
-        track!(packet.write_to(&mut self.stream))
+        let mut buf = [0; MAX_PACKET_SIZE];
+        let bytes = track!(self.encode_packet(packet, &mut buf))?;
+        track_io!(self.stream.write_all(bytes))?;
+        Ok(())
+    }
+
+    fn write_ts_packets(&mut self, packets: &[TsPacket]) -> Result<()> {
+        let mut bytes = Vec::with_capacity(packets.len() * self.packet_format.packet_size());
+        let mut buf = [0; MAX_PACKET_SIZE];
+        for packet in packets {
+            bytes.extend_from_slice(track!(self.encode_packet(packet, &mut buf))?);
+        }
+        track_io!(self.stream.write_all(&bytes))?;
+        Ok(())
     }
 }
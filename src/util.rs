@@ -1,7 +1,8 @@
 use crate::crc::Crc32;
+use crate::io::{self, Read, Write};
 use crate::ts::TsPacket;
 use crate::{ErrorKind, Result};
-use std::io::{self, Read, Write};
+use core::hash::Hasher;
 
 pub fn consume_stuffing_bytes<R: Read>(mut reader: R) -> Result<()> {
     let mut buf = [0];
@@ -50,3 +51,24 @@ impl<T: Write> Write for WithCrc32<T> {
         self.stream.flush()
     }
 }
+
+/// FNV-1a hasher (`std::collections::hash_map::DefaultHasher` is not available without `std`).
+#[derive(Debug)]
+pub struct Fnv1aHasher(u64);
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Fnv1aHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+impl Hasher for Fnv1aHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}